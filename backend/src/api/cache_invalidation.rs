@@ -0,0 +1,53 @@
+//! Clearing the in-process caches on request of `tobira db cache-invalidate`,
+//! e.g. after the DB was modified directly. Requests are sent via `NOTIFY` on
+//! `CHANNEL`, with the name of the modified table as payload.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{db::{self, DbConfig}, prelude::*};
+use super::{AnnouncementCache, NavigationCache};
+
+
+/// The channel on which Tobira listens for invalidation requests.
+pub(crate) const CHANNEL: &str = "tobira_cache_invalidate";
+
+/// Tables whose data is cached in-process.
+pub(crate) const CACHED_TABLES: &[&str] = &[
+    "announcements",
+    "blocks",
+    "events",
+    "realms",
+    "series",
+];
+
+/// How long to wait before reconnecting after the listening connection failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+
+/// Listens for invalidation requests forever.
+pub(crate) async fn run(
+    config: DbConfig,
+    navigation_cache: Arc<NavigationCache>,
+    announcement_cache: Arc<AnnouncementCache>,
+) {
+    loop {
+        let res = db::listen(&config, CHANNEL, |table| match table {
+            "announcements" => {
+                info!("Invalidating announcement cache as requested");
+                announcement_cache.invalidate();
+            }
+            // Realm names can be derived from series and events.
+            "blocks" | "events" | "realms" | "series" => {
+                info!("Invalidating navigation cache as requested (table '{table}' changed)");
+                navigation_cache.invalidate();
+            }
+            _ => debug!("Ignoring cache invalidation request for '{table}' (not cached)"),
+        }).await;
+
+        if let Err(e) = res {
+            warn!("Cannot receive cache invalidation requests ({e:#}). \
+                Retrying in {RETRY_INTERVAL:?}.");
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
@@ -1,11 +1,17 @@
 use std::sync::Arc;
 
 use crate::{
-    api::err::{ApiError, ApiErrorKind, ApiResult},
-    auth::{AuthToken, JwtContext, AuthContext},
+    api::{
+        AnnouncementCache,
+        NavigationCache,
+        PlaybackPositionBuffer,
+        err::{ApiError, ApiErrorKind, ApiResult},
+    },
+    auth::{AuthToken, JwtContext, AuthContext, SessionId},
     config::Config,
     db::Transaction,
     search,
+    sync::OcClient,
     prelude::*,
 };
 
@@ -14,9 +20,17 @@ use crate::{
 pub(crate) struct Context {
     pub(crate) db: Transaction,
     pub(crate) auth: AuthContext,
+    /// The session of the current user, if they are logged in via Tobira's
+    /// own session management (i.e. auth mode `login-proxy`).
+    pub(crate) session_id: Option<SessionId>,
     pub(crate) config: Arc<Config>,
     pub(crate) jwt: Arc<JwtContext>,
     pub(crate) search: Arc<search::Client>,
+    pub(crate) announcement_cache: Arc<AnnouncementCache>,
+    pub(crate) navigation_cache: Arc<NavigationCache>,
+    pub(crate) playback_positions: Arc<PlaybackPositionBuffer>,
+    /// Used for changes that have to be made in Opencast (e.g. ACLs).
+    pub(crate) oc_client: Arc<OcClient>,
 }
 
 impl juniper::Context for Context {}
@@ -45,4 +59,22 @@ impl Context {
             }
         })
     }
+
+    pub(crate) fn require_admin(&self) -> ApiResult<AuthToken> {
+        self.auth.require_admin().ok_or_else(|| {
+            if let AuthContext::User(user) = &self.auth {
+                ApiError {
+                    msg: format!("admin required, but '{}' is not an admin", user.username),
+                    kind: ApiErrorKind::NotAuthorized,
+                    key: None,
+                }
+            } else {
+                ApiError {
+                    msg: "admin required, but user is not logged in".into(),
+                    kind: ApiErrorKind::NotAuthorized,
+                    key: Some("mutation.not-logged-in"),
+                }
+            }
+        })
+    }
 }
@@ -11,6 +11,7 @@
 use juniper::{FieldError, IntoFieldError, ScalarValue, graphql_value};
 
 use crate::prelude::*;
+use super::id::WrongIdKind;
 
 
 pub(crate) type ApiResult<T> = Result<T, ApiError>;
@@ -28,6 +29,10 @@ pub(crate) enum ApiErrorKind {
     /// The API request is not sufficiently authorized.
     NotAuthorized,
 
+    /// An argument refers to an item (e.g. an event) that does not exist or
+    /// that the user cannot access.
+    InvalidReference,
+
     /// Some server error out of control of the API user.
     InternalServerError,
 }
@@ -38,6 +43,7 @@ impl ApiErrorKind {
         match self {
             Self::InvalidInput => "INVALID_INPUT",
             Self::NotAuthorized => "NOT_AUTHORIZED",
+            Self::InvalidReference => "INVALID_REFERENCE",
             Self::InternalServerError => "INTERNAL_SERVER_ERROR",
         }
     }
@@ -46,6 +52,7 @@ impl ApiErrorKind {
         match self {
             Self::InvalidInput => "Invalid input",
             Self::NotAuthorized => "Not authorized",
+            Self::InvalidReference => "Invalid reference",
             Self::InternalServerError => "Internal server error",
         }
     }
@@ -76,6 +83,16 @@ impl From<meilisearch_sdk::errors::Error> for ApiError {
     }
 }
 
+impl From<WrongIdKind> for ApiError {
+    fn from(src: WrongIdKind) -> Self {
+        Self {
+            msg: src.to_string(),
+            kind: ApiErrorKind::InvalidInput,
+            key: None,
+        }
+    }
+}
+
 impl<S: ScalarValue> IntoFieldError<S> for ApiError {
     fn into_field_error(self) -> juniper::FieldError<S> {
         let msg = format!("{}: {}", self.kind.message_prefix(), self.msg);
@@ -123,6 +140,11 @@ macro_rules! not_authorized {
     ($($t:tt)+) => { $crate::api::err::api_err!(NotAuthorized, $($t)*) };
 }
 
+macro_rules! invalid_reference {
+    ($($t:tt)+) => { $crate::api::err::api_err!(InvalidReference, $($t)*) };
+}
+
 pub(crate) use api_err;
 pub(crate) use invalid_input;
+pub(crate) use invalid_reference;
 pub(crate) use not_authorized;
@@ -1,4 +1,6 @@
+use bytes::BytesMut;
 use paste::paste;
+use postgres_types::{FromSql, ToSql};
 use serde::{Deserialize, Serialize};
 use static_assertions::const_assert;
 use std::fmt;
@@ -50,6 +52,15 @@ macro_rules! define_kinds {
                         }
                     }
                 )+
+
+                /// Returns a human readable name of the given kind, e.g.
+                /// `event`, or `None` if the kind is unknown.
+                fn kind_name(kind: [u8; 2]) -> Option<&'static str> {
+                    match kind {
+                        $( Self:: [<$name:upper _KIND>] => Some(stringify!($name)), )+
+                        _ => None,
+                    }
+                }
             }
         );
 
@@ -85,6 +96,8 @@ define_kinds![
     event = b"ev",
     search_realm = b"rs",
     search_event = b"es",
+    announcement = b"an",
+    person = b"pe",
 ];
 
 
@@ -120,6 +133,124 @@ impl Id {
     }
 }
 
+
+// Macro to define typed wrappers around `Key` for some kinds of IDs. These
+// prevent mixing up keys of different kinds (e.g. passing a realm key to an
+// event query). Each implements `ToSql` and `FromSql` like `Key` and can be
+// converted from and to `Id`, checking the kind.
+macro_rules! define_typed_keys {
+    ($( $(#[$attr:meta])* $ty:ident = $kind:ident, )+) => {
+        paste!($(
+            $(#[$attr])*
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+            #[serde(transparent)]
+            pub(crate) struct $ty(pub(crate) Key);
+
+            impl $ty {
+                /// Returns the key of the given ID, or an error if the ID is
+                /// of a different kind (or malformed).
+                pub(crate) fn from_id(id: Id) -> Result<Self, WrongIdKind> {
+                    let expected = Id:: [<$kind:upper _KIND>];
+                    id.key_for(expected)
+                        .map(Self)
+                        .ok_or(WrongIdKind { expected, actual: id.kind })
+                }
+
+                /// Like `from_id`, but returns `None` for malformed IDs, as
+                /// these are treated like IDs of non-existing nodes (see
+                /// `Id::invalid`). Use this for lookups.
+                #[allow(dead_code)] // Not every kind is looked up by ID.
+                pub(crate) fn from_node_id(id: Id) -> Result<Option<Self>, WrongIdKind> {
+                    match Self::from_id(id) {
+                        Ok(key) => Ok(Some(key)),
+                        Err(_) if id.kind == Id::INVALID_KIND => Ok(None),
+                        Err(e) => Err(e),
+                    }
+                }
+            }
+
+            impl From<$ty> for Id {
+                fn from(key: $ty) -> Self {
+                    Id::$kind(key.0)
+                }
+            }
+
+            impl ToSql for $ty {
+                fn to_sql(
+                    &self,
+                    ty: &postgres_types::Type,
+                    out: &mut BytesMut,
+                ) -> Result<postgres_types::IsNull, Box<dyn std::error::Error + Sync + Send>> {
+                    self.0.to_sql(ty, out)
+                }
+
+                fn accepts(ty: &postgres_types::Type) -> bool {
+                    <Key as ToSql>::accepts(ty)
+                }
+
+                postgres_types::to_sql_checked!();
+            }
+
+            impl<'a> FromSql<'a> for $ty {
+                fn from_sql(
+                    ty: &postgres_types::Type,
+                    raw: &'a [u8],
+                ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+                    Key::from_sql(ty, raw).map(Self)
+                }
+
+                fn accepts(ty: &postgres_types::Type) -> bool {
+                    <Key as FromSql>::accepts(ty)
+                }
+            }
+
+            impl fmt::Debug for $ty {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}({:?})", stringify!($ty), self.0)
+                }
+            }
+        )+);
+    };
+}
+
+define_typed_keys![
+    /// Database key of an event.
+    EventId = event,
+    /// Database key of a series.
+    SeriesId = series,
+    /// Database key of a realm.
+    RealmId = realm,
+    /// Database key of a block.
+    BlockId = block,
+];
+
+impl RealmId {
+    /// The key of the root realm, which always exists.
+    pub(crate) const ROOT: Self = Self(Key(0));
+}
+
+/// Error when converting an `Id` into a typed key (e.g. `EventId`), but the
+/// ID refers to a different kind of node.
+#[derive(Debug)]
+pub(crate) struct WrongIdKind {
+    expected: [u8; 2],
+    actual: [u8; 2],
+}
+
+impl fmt::Display for WrongIdKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = Id::kind_name(self.expected).unwrap_or("unknown");
+        if self.actual == Id::INVALID_KIND {
+            return write!(f, "expected {expected} ID, but got a malformed ID");
+        }
+
+        match Id::kind_name(self.actual) {
+            Some(actual) => write!(f, "expected {expected} ID, but got {actual} ID"),
+            None => write!(f, "expected {expected} ID, but got ID of unknown kind"),
+        }
+    }
+}
+
 /// The URL-safe base64 alphabet.
 const BASE64_DIGITS: &[u8; 64] =
     b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
@@ -252,7 +383,7 @@ fn decode_base64(src: &[u8]) -> Option<Key> {
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
-    use super::{Id, Key, BASE64_DIGITS};
+    use super::{Id, Key, BASE64_DIGITS, EventId, RealmId};
 
     #[test]
     fn simple() {
@@ -291,6 +422,20 @@ mod tests {
         assert_eq!(Id::from_str("sr___________"), Err(()));
     }
 
+    #[test]
+    fn typed_keys() {
+        let event = Id::event(Key(7));
+        assert_eq!(EventId::from_id(event).unwrap(), EventId(Key(7)));
+        assert_eq!(Id::from(EventId(Key(7))), event);
+
+        let err = RealmId::from_id(event).unwrap_err();
+        assert_eq!(err.to_string(), "expected realm ID, but got event ID");
+        let err = EventId::from_id(Id::invalid()).unwrap_err();
+        assert_eq!(err.to_string(), "expected event ID, but got a malformed ID");
+        assert!(EventId::from_node_id(Id::invalid()).unwrap().is_none());
+        assert!(EventId::from_node_id(Id::realm(Key(7))).is_err());
+    }
+
     #[test]
     fn always_ascii() {
         // We can't test all possible u64 values, but by checking all two bytes
@@ -6,6 +6,7 @@ use self::{
     subscription::Subscription,
 };
 
+pub(crate) mod cache_invalidation;
 pub(crate) mod mutation;
 pub(crate) mod query;
 pub(crate) mod subscription;
@@ -19,9 +20,14 @@ mod model;
 mod jwt;
 
 pub(crate) use self::{
-    id::Id,
+    id::{Id, EventId, SeriesId, RealmId, BlockId},
     context::Context,
     common::{Cursor, Node, NodeValue},
+    model::{
+        announcement::AnnouncementCache,
+        playback_position::PlaybackPositionBuffer,
+        realm::NavigationCache,
+    },
 };
 
 
@@ -0,0 +1,127 @@
+use juniper::GraphQLObject;
+
+use crate::{
+    api::{Context, err::{ApiResult, invalid_input, not_authorized}},
+    auth::{AuthContext, User},
+    config::{TranslatedString, USER_ROLE_PLACEHOLDER},
+};
+
+
+/// A configured set of roles that can be applied to a video at once, with a
+/// human-readable label (e.g. "Only course members").
+#[derive(GraphQLObject)]
+pub(crate) struct AclPreset {
+    id: String,
+    label: TranslatedText,
+    /// Roles that are allowed to read, with "$USER_ROLE" already replaced.
+    read_roles: Vec<String>,
+    /// Roles that are allowed to write, with "$USER_ROLE" already replaced.
+    write_roles: Vec<String>,
+}
+
+/// The configured human-readable name for a role.
+#[derive(GraphQLObject)]
+pub(crate) struct RoleDisplayName {
+    role: String,
+    name: TranslatedText,
+}
+
+/// A configured text in all configured languages. English is always set.
+#[derive(GraphQLObject)]
+pub(crate) struct TranslatedText {
+    en: String,
+    de: Option<String>,
+}
+
+impl From<&TranslatedString> for TranslatedText {
+    fn from(src: &TranslatedString) -> Self {
+        Self {
+            en: src.en().to_owned(),
+            de: src.translation("de").map(ToOwned::to_owned),
+        }
+    }
+}
+
+impl AclPreset {
+    /// Returns all presets the current user can apply, expanded for them.
+    /// Presets using "$USER_ROLE" are left out if the user has no user role.
+    /// Empty if the user is not logged in.
+    pub(crate) fn load_all(context: &Context) -> Vec<Self> {
+        let user = match &context.auth {
+            AuthContext::User(user) => user,
+            _ => return vec![],
+        };
+
+        context.config.acl.presets.iter()
+            .filter_map(|preset| {
+                let (read_roles, write_roles) = expand(preset, user, context)?;
+                Some(Self {
+                    id: preset.id.clone(),
+                    label: (&preset.label).into(),
+                    read_roles,
+                    write_roles,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the read and write roles the preset with the given ID expands
+    /// to for the current user. These are the roles to store: the preset
+    /// itself is not referenced anymore afterwards.
+    pub(crate) fn expand_for_current_user(
+        id: &str,
+        context: &Context,
+    ) -> ApiResult<(Vec<String>, Vec<String>)> {
+        let user = match &context.auth {
+            AuthContext::User(user) => user,
+            _ => return Err(not_authorized!(
+                key = "mutation.not-logged-in",
+                "only logged in users can apply ACL presets",
+            )),
+        };
+        let preset = context.config.acl.preset(id)
+            .ok_or_else(|| invalid_input!("there is no ACL preset with ID '{}'", id))?;
+
+        expand(preset, user, context).ok_or_else(|| invalid_input!(
+            "ACL preset '{}' requires a user role, but '{}' has none",
+            id,
+            user.username,
+        ))
+    }
+}
+
+impl RoleDisplayName {
+    pub(crate) fn load_all(context: &Context) -> Vec<Self> {
+        let mut out = context.config.acl.role_display_names.iter()
+            .map(|(role, name)| Self { role: role.clone(), name: name.into() })
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| a.role.cmp(&b.role));
+        out
+    }
+}
+
+/// Replaces "$USER_ROLE" in the preset's roles. Returns `None` if the preset
+/// uses it, but the user has no user role.
+fn expand(
+    preset: &crate::config::AclPreset,
+    user: &User,
+    context: &Context,
+) -> Option<(Vec<String>, Vec<String>)> {
+    // If a user has multiple roles with the prefix, we pick one deterministically.
+    let user_role = user.roles.iter()
+        .filter(|role| role.starts_with(&context.config.acl.user_role_prefix))
+        .min();
+
+    let expand_roles = |roles: &[String]| -> Option<Vec<String>> {
+        let mut out = Vec::new();
+        for role in roles {
+            let role = if role == USER_ROLE_PLACEHOLDER { user_role?.clone() } else { role.clone() };
+            if !out.contains(&role) {
+                out.push(role);
+            }
+        }
+        Some(out)
+    };
+
+    Some((expand_roles(&preset.read_roles)?, expand_roles(&preset.write_roles)?))
+}
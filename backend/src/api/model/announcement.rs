@@ -0,0 +1,273 @@
+//! The global announcement shown on all pages, e.g. to announce maintenance
+//! windows.
+
+use std::{sync::Mutex, time::{Duration, Instant}};
+use chrono::{DateTime, Utc};
+use juniper::{graphql_object, GraphQLInputObject};
+
+use crate::{
+    api::{
+        Context,
+        Id,
+        err::{ApiResult, invalid_input, not_authorized},
+    },
+    auth::HasRoles,
+    db::{types::{AnnouncementSeverity, Key}, util::impl_from_db},
+    prelude::*,
+};
+
+
+/// How long the announcement is cached in-process. It is requested on every
+/// page load, so this saves lots of DB queries.
+const CACHE_DURATION: Duration = Duration::from_secs(5);
+
+/// The announcement as stored in the DB, i.e. independent of the user.
+#[derive(Clone)]
+struct StoredAnnouncement {
+    key: Key,
+    title: String,
+    body: String,
+    severity: AnnouncementSeverity,
+    active_from: Option<DateTime<Utc>>,
+    active_until: Option<DateTime<Utc>>,
+}
+
+impl_from_db!(
+    StoredAnnouncement,
+    select: {
+        announcements.{ id, title, body, severity, active_from, active_until },
+    },
+    |row| {
+        Self {
+            key: row.id(),
+            title: row.title(),
+            body: row.body(),
+            severity: row.severity(),
+            active_from: row.active_from(),
+            active_until: row.active_until(),
+        }
+    },
+);
+
+impl StoredAnnouncement {
+    fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.active_from.is_none_or(|from| from <= now)
+            && self.active_until.is_none_or(|until| now < until)
+    }
+}
+
+/// In-process cache of the stored announcement, shared by all API requests.
+///
+/// Changes made by other Tobira nodes are visible after `CACHE_DURATION` at
+/// the latest. To decide whether the announcement is active, the DB time at
+/// the time of loading is stored and advanced with the local monotonic clock.
+/// That way, the clock of this machine is irrelevant.
+#[derive(Default)]
+pub(crate) struct AnnouncementCache(Mutex<Option<CacheEntry>>);
+
+struct CacheEntry {
+    loaded_at: Instant,
+    db_time: DateTime<Utc>,
+    announcement: Option<StoredAnnouncement>,
+}
+
+impl AnnouncementCache {
+    /// Returns the stored announcement (if any) and the current DB time.
+    async fn get(
+        &self,
+        context: &Context,
+    ) -> ApiResult<(Option<StoredAnnouncement>, DateTime<Utc>)> {
+        if let Some(entry) = &*self.0.lock().unwrap() {
+            let elapsed = entry.loaded_at.elapsed();
+            if elapsed < CACHE_DURATION {
+                let now = entry.db_time + chrono::Duration::from_std(elapsed)
+                    .expect("cache duration out of range");
+                return Ok((entry.announcement.clone(), now));
+            }
+        }
+
+        let loaded_at = Instant::now();
+        let db_time = context.db.query_one("select now()", &[]).await?.get::<_, DateTime<Utc>>(0);
+        let selection = StoredAnnouncement::select();
+        let query = format!("select {selection} from announcements");
+        let announcement = context.db
+            .query_opt(&query, &[])
+            .await?
+            .map(|row| StoredAnnouncement::from_row_start(&row));
+
+        *self.0.lock().unwrap() = Some(CacheEntry {
+            loaded_at,
+            db_time,
+            announcement: announcement.clone(),
+        });
+        Ok((announcement, db_time))
+    }
+
+    /// Makes the next `get` load from the DB. Only affects this Tobira node.
+    pub(crate) fn invalidate(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+
+pub(crate) struct Announcement {
+    stored: StoredAnnouncement,
+    dismissed: bool,
+}
+
+#[derive(GraphQLInputObject)]
+pub(crate) struct NewAnnouncement {
+    title: String,
+    /// Markdown.
+    body: String,
+    severity: AnnouncementSeverity,
+    active_from: Option<DateTime<Utc>>,
+    active_until: Option<DateTime<Utc>>,
+}
+
+impl Announcement {
+    /// Returns the announcement if there is one that is currently active.
+    pub(crate) async fn load_active(context: &Context) -> ApiResult<Option<Self>> {
+        let (stored, now) = context.announcement_cache.get(context).await?;
+        let stored = match stored {
+            Some(stored) if stored.is_active_at(now) => stored,
+            _ => return Ok(None),
+        };
+
+        let dismissed = match &context.session_id {
+            None => false,
+            Some(session_id) => context.db
+                .query_one(
+                    "select exists (\
+                        select from announcement_dismissals \
+                        where announcement = $1 and session = $2\
+                    )",
+                    &[&stored.key, session_id],
+                )
+                .await?
+                .get(0),
+        };
+
+        Ok(Some(Self { stored, dismissed }))
+    }
+
+    /// Replaces the current announcement (if any) with the given one.
+    pub(crate) async fn set(
+        announcement: NewAnnouncement,
+        context: &Context,
+    ) -> ApiResult<Self> {
+        require_admin(context)?;
+
+        if announcement.title.trim().is_empty() {
+            return Err(invalid_input!("announcement title must not be empty"));
+        }
+        if let (Some(from), Some(until)) = (announcement.active_from, announcement.active_until) {
+            if from >= until {
+                return Err(invalid_input!("`activeFrom` has to be before `activeUntil`"));
+            }
+        }
+
+        context.db.execute("delete from announcements", &[]).await?;
+        let selection = StoredAnnouncement::select();
+        let query = format!(
+            "insert into announcements (title, body, severity, active_from, active_until) \
+                values ($1, $2, $3, $4, $5) \
+                returning {selection}",
+        );
+        let row = context.db
+            .query_one(&query, &[
+                &announcement.title,
+                &announcement.body,
+                &announcement.severity,
+                &announcement.active_from,
+                &announcement.active_until,
+            ])
+            .await?;
+        context.announcement_cache.invalidate();
+
+        Ok(Self { stored: StoredAnnouncement::from_row_start(&row), dismissed: false })
+    }
+
+    /// Removes the current announcement. Returns `false` if there was none.
+    pub(crate) async fn clear(context: &Context) -> ApiResult<bool> {
+        require_admin(context)?;
+        let deleted = context.db.execute("delete from announcements", &[]).await?;
+        context.announcement_cache.invalidate();
+        Ok(deleted > 0)
+    }
+
+    /// Marks the announcement as dismissed for the current session. Returns
+    /// `false` if there is no session to store that in, in which case the
+    /// frontend has to remember the dismissal itself.
+    pub(crate) async fn dismiss(id: Id, context: &Context) -> ApiResult<bool> {
+        let key = id.key_for(Id::ANNOUNCEMENT_KIND)
+            .ok_or_else(|| invalid_input!("`id` does not refer to an announcement"))?;
+        let session_id = match &context.session_id {
+            None => return Ok(false),
+            Some(session_id) => session_id,
+        };
+
+        let inserted = context.db
+            .execute(
+                "insert into announcement_dismissals (announcement, session) \
+                    select $1, $2 \
+                    where exists (select from announcements where id = $1) \
+                    on conflict do nothing",
+                &[&key, session_id],
+            )
+            .await?;
+        if inserted == 0 {
+            let exists = context.db
+                .query_one("select exists (select from announcements where id = $1)", &[&key])
+                .await?
+                .get::<_, bool>(0);
+            if !exists {
+                return Err(invalid_input!("announcement {} does not exist", id));
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn require_admin(context: &Context) -> ApiResult<()> {
+    if !context.auth.is_admin() {
+        return Err(not_authorized!("only admins can change the announcement"));
+    }
+    Ok(())
+}
+
+#[graphql_object(Context = Context)]
+impl Announcement {
+    fn id(&self) -> Id {
+        Id::announcement(self.stored.key)
+    }
+
+    fn title(&self) -> &str {
+        &self.stored.title
+    }
+
+    /// Markdown.
+    fn body(&self) -> &str {
+        &self.stored.body
+    }
+
+    fn severity(&self) -> AnnouncementSeverity {
+        self.stored.severity
+    }
+
+    fn active_from(&self) -> Option<DateTime<Utc>> {
+        self.stored.active_from
+    }
+
+    fn active_until(&self) -> Option<DateTime<Utc>> {
+        self.stored.active_until
+    }
+
+    /// Whether the current user dismissed this announcement in their current
+    /// session. Always `false` for users without a Tobira session (e.g.
+    /// anonymous users); for those, the frontend has to remember dismissals.
+    fn dismissed(&self) -> bool {
+        self.dismissed
+    }
+}
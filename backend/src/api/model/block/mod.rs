@@ -1,11 +1,11 @@
 //! Blocks that make up the content of realm pages.
 
-use juniper::{graphql_interface, graphql_object, GraphQLEnum};
+use juniper::{graphql_interface, graphql_object, GraphQLEnum, GraphQLObject};
 use postgres_types::{FromSql, ToSql};
 
 use crate::{
     api::{
-        Context, Id,
+        Context, Id, BlockId, EventId, RealmId, SeriesId,
         err::{ApiError, ApiResult},
         model::{
             event::{AuthorizedEvent, Event},
@@ -13,28 +13,32 @@ use crate::{
             realm::{Realm, RealmNameSourceBlockValue},
         },
     },
-    db::{types::Key, util::impl_from_db},
+    db::util::impl_from_db,
+    markdown,
     prelude::*,
 };
 
 
 mod mutations;
+mod toc;
 
 pub(crate) use mutations::{
     NewTitleBlock,
     NewTextBlock,
     NewSeriesBlock,
     NewVideoBlock,
+    NewTocBlock,
     UpdateTitleBlock,
     UpdateTextBlock,
     UpdateSeriesBlock,
     UpdateVideoBlock,
+    UpdateTocBlock,
     RemovedBlock,
 };
 
 
 /// A `Block`: a UI element that belongs to a realm.
-#[graphql_interface(Context = Context, for = [TitleBlock, TextBlock, SeriesBlock, VideoBlock])]
+#[graphql_interface(Context = Context, for = [TitleBlock, TextBlock, SeriesBlock, VideoBlock, TocBlock])]
 pub(crate) trait Block {
     // To avoid code duplication, all the shared data is stored in `SharedData`
     // and only a `shared` method is mandatory. All other method (in particular,
@@ -67,6 +71,8 @@ pub(crate) enum BlockType {
     Series,
     #[postgres(name = "video")]
     Video,
+    #[postgres(name = "toc")]
+    Toc,
 }
 
 #[derive(Debug, Clone, Copy, FromSql, ToSql, GraphQLEnum)]
@@ -76,6 +82,33 @@ pub(crate) enum VideoListOrder {
     NewToOld,
     #[postgres(name = "old_to_new")]
     OldToNew,
+    /// By title, A to Z.
+    #[postgres(name = "alphabetic")]
+    Alphabetic,
+}
+
+#[derive(Debug, Clone, Copy, FromSql, ToSql, GraphQLEnum)]
+#[postgres(name = "video_list_layout")]
+pub(crate) enum VideoListLayout {
+    #[postgres(name = "grid")]
+    Grid,
+    #[postgres(name = "list")]
+    List,
+    /// A single row that can be scrolled horizontally.
+    #[postgres(name = "slider")]
+    Slider,
+}
+
+#[derive(Debug, Clone, Copy, FromSql, ToSql, GraphQLEnum)]
+#[postgres(name = "toc_order")]
+pub(crate) enum TocOrder {
+    /// Use the `childOrder` of the respective parent realm.
+    #[postgres(name = "inherit")]
+    Inherit,
+    #[postgres(name = "alphabetic:asc")]
+    AlphabeticAsc,
+    #[postgres(name = "alphabetic:desc")]
+    AlphabeticDesc,
 }
 
 /// Data shared by all blocks.
@@ -83,7 +116,7 @@ pub(crate) enum VideoListOrder {
 pub(crate) struct SharedData {
     pub(crate) id: Id,
     pub(crate) index: i32,
-    pub(crate) realm_key: Key,
+    pub(crate) realm_key: RealmId,
 }
 
 #[derive(Debug)]
@@ -122,6 +155,8 @@ impl TitleBlock {
 pub(crate) struct TextBlock {
     pub(crate) shared: SharedData,
     pub(crate) content: String,
+    /// `None` if the block was saved before HTML was rendered on save.
+    pub(crate) html: Option<String>,
 }
 
 impl Block for TextBlock {
@@ -133,10 +168,18 @@ impl Block for TextBlock {
 /// A block just showing some text.
 #[graphql_object(Context = Context, impl = BlockValue)]
 impl TextBlock {
+    /// The Markdown source of the text.
     fn content(&self) -> &str {
         &self.content
     }
 
+    /// The text rendered to sanitized HTML.
+    fn html(&self) -> String {
+        // The HTML is rendered when saving the block and for older blocks by
+        // `db migrate`. Until that ran, it's rendered here without storing it.
+        self.html.clone().unwrap_or_else(|| markdown::render(&self.content))
+    }
+
     fn id(&self) -> Id {
         Block::id(self)
     }
@@ -153,10 +196,24 @@ impl TextBlock {
 #[derive(Debug)]
 pub(crate) struct SeriesBlock {
     pub(crate) shared: SharedData,
-    pub(crate) series: Option<Id>,
+    pub(crate) series: Option<SeriesId>,
     pub(crate) show_title: bool,
     pub(crate) show_metadata: bool,
     pub(crate) order: VideoListOrder,
+    pub(crate) layout: VideoListLayout,
+    /// Metadata that events have to have to be shown, in the shape of
+    /// `events.metadata`, e.g. `{ "dcterms": { "subject": ["exam"] } }`.
+    pub(crate) metadata_filter: Option<serde_json::Value>,
+}
+
+/// One metadata value that events have to have to be shown in a series block.
+#[derive(Debug, GraphQLObject)]
+pub(crate) struct MetadataFilterEntry {
+    /// The metadata namespace, e.g. `dcterms`, like in the `general.metadata`
+    /// configuration.
+    namespace: String,
+    field: String,
+    value: String,
 }
 
 impl Block for SeriesBlock {
@@ -172,7 +229,7 @@ impl SeriesBlock {
         match self.series {
             None => Ok(None),
             // `unwrap` is okay here because of our foreign key constraint
-            Some(series_id) => Ok(Some(Series::load_by_id(series_id, context).await?.unwrap())),
+            Some(series) => Ok(Some(Series::load_by_key(series, context).await?.unwrap())),
         }
     }
 
@@ -188,6 +245,42 @@ impl SeriesBlock {
         self.order
     }
 
+    fn layout(&self) -> VideoListLayout {
+        self.layout
+    }
+
+    /// Metadata that events have to have (all entries) to be shown in this
+    /// block. Empty if all events of the series are shown.
+    fn metadata_filter(&self) -> Vec<MetadataFilterEntry> {
+        let mut entries = vec![];
+        let namespaces = self.metadata_filter.as_ref().and_then(|filter| filter.as_object());
+        for (namespace, fields) in namespaces.into_iter().flatten() {
+            let fields = fields.as_object().into_iter().flatten();
+            for (field, values) in fields {
+                let values = values.as_array().into_iter().flatten().filter_map(|v| v.as_str());
+                entries.extend(values.map(|value| MetadataFilterEntry {
+                    namespace: namespace.clone(),
+                    field: field.clone(),
+                    value: value.to_owned(),
+                }));
+            }
+        }
+        entries
+    }
+
+    /// The events of the series that the current user can see and that match
+    /// `metadataFilter`, in the order configured for this block. Empty if the
+    /// series was deleted.
+    async fn events(&self, context: &Context) -> ApiResult<Vec<AuthorizedEvent>> {
+        match self.series {
+            None => Ok(vec![]),
+            Some(series) => {
+                let filter = self.metadata_filter.as_ref();
+                AuthorizedEvent::load_for_series(series, self.order.into(), filter, context).await
+            }
+        }
+    }
+
     fn id(&self) -> Id {
         Block::id(self)
     }
@@ -205,7 +298,7 @@ impl SeriesBlock {
 pub(crate) struct VideoBlock {
     pub(crate) shared: SharedData,
     // Is `None` if the video was removed from the DB.
-    pub(crate) event: Option<Id>,
+    pub(crate) event: Option<EventId>,
     pub(crate) show_title: bool,
 }
 
@@ -222,7 +315,7 @@ impl VideoBlock {
         match self.event {
             None => Ok(None),
             // `unwrap` is okay here because of our foreign key constraint
-            Some(event_id) => Ok(Some(AuthorizedEvent::load_by_id(event_id, context).await?.unwrap())),
+            Some(event) => Ok(Some(AuthorizedEvent::load_by_key(event, context).await?.unwrap())),
         }
     }
 
@@ -243,6 +336,54 @@ impl VideoBlock {
     }
 }
 
+#[derive(Debug)]
+pub(crate) struct TocBlock {
+    pub(crate) shared: SharedData,
+    pub(crate) depth: i16,
+    pub(crate) show_counts: bool,
+    pub(crate) order: TocOrder,
+}
+
+impl Block for TocBlock {
+    fn shared(&self) -> &SharedData {
+        &self.shared
+    }
+}
+
+/// A block showing the realms below the block's realm as nested list of links.
+#[graphql_object(Context = Context, impl = BlockValue)]
+impl TocBlock {
+    /// How many levels of the realm tree are shown. Between 1 and 4.
+    fn depth(&self) -> i32 {
+        self.depth.into()
+    }
+
+    /// Whether to show the number of videos next to each entry.
+    fn show_counts(&self) -> bool {
+        self.show_counts
+    }
+
+    fn order(&self) -> TocOrder {
+        self.order
+    }
+
+    async fn contents(&self, context: &Context) -> ApiResult<toc::TocContents> {
+        toc::load(self, context).await
+    }
+
+    fn id(&self) -> Id {
+        Block::id(self)
+    }
+
+    fn index(&self) -> i32 {
+        Block::index(self)
+    }
+
+    async fn realm(&self, context: &Context) -> ApiResult<Realm> {
+        Block::realm(self, context).await
+    }
+}
+
 impl_from_db!(
     BlockValue,
     select: {
@@ -251,18 +392,24 @@ impl_from_db!(
             ty: "type",
             index,
             text_content,
+            text_html,
             series,
             videolist_order,
+            videolist_layout,
             video,
             show_title,
             show_metadata,
+            metadata_filter,
             realm,
+            toc_depth,
+            toc_show_counts,
+            toc_order,
         },
     },
     |row| {
         let ty: BlockType = row.ty();
         let shared = SharedData {
-            id: Id::block(row.id()),
+            id: row.id::<BlockId>().into(),
             index: row.index::<i16>().into(),
             realm_key: row.realm(),
         };
@@ -276,21 +423,31 @@ impl_from_db!(
             BlockType::Text => TextBlock {
                 shared,
                 content: unwrap_type_dep(row.text_content(), "text", "text_content"),
+                html: row.text_html(),
             }.into(),
 
             BlockType::Series => SeriesBlock {
                 shared,
-                series: row.series::<Option<Key>>().map(Id::series),
+                series: row.series(),
                 order: unwrap_type_dep(row.videolist_order(), "series", "videolist_order"),
+                layout: unwrap_type_dep(row.videolist_layout(), "series", "videolist_layout"),
                 show_title: unwrap_type_dep(row.show_title(), "series", "show_title"),
                 show_metadata: unwrap_type_dep(row.show_metadata(), "series", "show_metadata"),
+                metadata_filter: row.metadata_filter(),
             }.into(),
 
             BlockType::Video => VideoBlock {
                 shared,
-                event: row.video::<Option<Key>>().map(Id::event),
+                event: row.video(),
                 show_title: unwrap_type_dep(row.show_title(), "event", "show_title"),
             }.into(),
+
+            BlockType::Toc => TocBlock {
+                shared,
+                depth: unwrap_type_dep(row.toc_depth(), "toc", "toc_depth"),
+                show_counts: unwrap_type_dep(row.toc_show_counts(), "toc", "toc_show_counts"),
+                order: unwrap_type_dep(row.toc_order(), "toc", "toc_order"),
+            }.into(),
         }
     }
 );
@@ -307,7 +464,10 @@ fn unwrap_type_dep<T>(value: Option<T>, type_name: &str, field_name: &str) -> T
 
 impl BlockValue {
     /// Fetches all blocks for the given realm from the database.
-    pub(crate) async fn load_for_realm(realm_key: Key, context: &Context) -> ApiResult<Vec<Self>> {
+    pub(crate) async fn load_for_realm(
+        realm_key: RealmId,
+        context: &Context,
+    ) -> ApiResult<Vec<Self>> {
         let selection = Self::select();
         let query = format!(
             "select {selection} \
@@ -325,7 +485,7 @@ impl BlockValue {
             .map_err(Into::into)
     }
 
-    pub(crate) async fn load_by_key(key: Key, context: &Context) -> ApiResult<Self> {
+    pub(crate) async fn load_by_key(key: BlockId, context: &Context) -> ApiResult<Self> {
         let selection = Self::select();
         let query = format!("select {selection} from blocks where id = $1 ");
         context.db
@@ -341,6 +501,7 @@ impl BlockValue {
             BlockValue::TextBlock(block) => block.shared.id,
             BlockValue::VideoBlock(block) => block.shared.id,
             BlockValue::SeriesBlock(block) => block.shared.id,
+            BlockValue::TocBlock(block) => block.shared.id,
         }
     }
 }
@@ -1,11 +1,17 @@
 use juniper::{GraphQLInputObject, GraphQLObject};
 
 use crate::{
-    api::{Context, Id, err::{ApiResult, invalid_input}, model::realm::{REALM_JOINS, Realm}},
-    db::{types::Key, util::select},
+    api::{
+        Context, Id, BlockId, EventId, RealmId, SeriesId,
+        err::{ApiResult, invalid_input, invalid_reference},
+        model::realm::{REALM_JOINS, Realm},
+    },
+    auth::HasRoles,
+    db::util::select,
+    markdown,
     prelude::*,
 };
-use super::{BlockValue, VideoListOrder};
+use super::{BlockValue, TocOrder, VideoListLayout, VideoListOrder};
 
 
 impl BlockValue {
@@ -40,13 +46,14 @@ impl BlockValue {
     ) -> ApiResult<Realm> {
         context.require_moderator()?;
 
+        let html = render_text(&block.content, context)?;
         let (realm, index) = Self::prepare_realm_for_block(realm, index, context).await?;
 
         context.db
             .execute(
-                "insert into blocks (realm, index, type, text_content) \
-                    values ($1, $2, 'text', $3)",
-                &[&realm, &index, &block.content],
+                "insert into blocks (realm, index, type, text_content, text_html) \
+                    values ($1, $2, 'text', $3, $4)",
+                &[&realm, &index, &block.content, &html],
             )
             .await?;
 
@@ -65,15 +72,29 @@ impl BlockValue {
 
         let (realm, index) = Self::prepare_realm_for_block(realm, index, context).await?;
 
-        let series = block.series.key_for(Id::SERIES_KIND)
-            .ok_or_else(|| invalid_input!("`block.series` does not refer to a series"))?;
+        let series = SeriesId::from_id(block.series)
+            .map_err(|e| invalid_input!("`block.series` does not refer to a series: {e}"))?;
+        check_reference(Reference::Series(series), context).await?;
+        let metadata_filter = block.metadata_filter
+            .map(|entries| metadata_filter_to_json(entries, context))
+            .transpose()?
+            .flatten();
 
         context.db
             .execute(
-                "insert into blocks \
-                    (realm, index, type, series, videolist_order, show_title, show_metadata) \
-                    values ($1, $2, 'series', $3, $4, $5, $6)",
-                &[&realm, &index, &series, &block.order, &block.show_title, &block.show_metadata],
+                "insert into blocks (realm, index, type, series, \
+                    videolist_order, videolist_layout, show_title, show_metadata, metadata_filter) \
+                    values ($1, $2, 'series', $3, $4, $5, $6, $7, $8)",
+                &[
+                    &realm,
+                    &index,
+                    &series,
+                    &block.order,
+                    &block.layout,
+                    &block.show_title,
+                    &block.show_metadata,
+                    &metadata_filter,
+                ],
             )
             .await?;
 
@@ -92,8 +113,9 @@ impl BlockValue {
 
         let (realm, index) = Self::prepare_realm_for_block(realm, index, context).await?;
 
-        let event = block.event.key_for(Id::EVENT_KIND)
-            .ok_or_else(|| invalid_input!("`block.event` does not refer to an event"))?;
+        let event = EventId::from_id(block.event)
+            .map_err(|e| invalid_input!("`block.event` does not refer to an event: {e}"))?;
+        check_reference(Reference::Event(event), context).await?;
 
         context.db
             .execute(
@@ -108,6 +130,30 @@ impl BlockValue {
             .ok_or_else(|| invalid_input!("`realm` does not refer to a valid realm"))
     }
 
+    pub(crate) async fn add_toc(
+        realm: Id,
+        index: i32,
+        block: NewTocBlock,
+        context: &Context,
+    ) -> ApiResult<Realm> {
+        context.require_moderator()?;
+
+        let depth = toc_depth(block.depth)?;
+        let (realm, index) = Self::prepare_realm_for_block(realm, index, context).await?;
+
+        context.db
+            .execute(
+                "insert into blocks (realm, index, type, toc_depth, toc_show_counts, toc_order) \
+                    values ($1, $2, 'toc', $3, $4, $5)",
+                &[&realm, &index, &depth, &block.show_counts, &block.order],
+            )
+            .await?;
+
+        Realm::load_by_key(realm, context)
+            .await?
+            .ok_or_else(|| invalid_input!("`realm` does not refer to a valid realm"))
+    }
+
     /// For all blocks in `realm` with an index `>= index`,
     /// increase their index by `1`.
     /// This basically moves all the blocks after the `index`-th one aside,
@@ -118,9 +164,9 @@ impl BlockValue {
         realm: Id,
         index: i32,
         context: &Context,
-    ) -> ApiResult<(Key, i16)> {
-        let realm = realm.key_for(Id::REALM_KIND)
-            .ok_or_else(|| invalid_input!("`realm` does not refer to a realm"))?;
+    ) -> ApiResult<(RealmId, i16)> {
+        let realm = RealmId::from_id(realm)
+            .map_err(|e| invalid_input!("`realm` does not refer to a realm: {e}"))?;
 
         let num_blocks: i64 = context.db
             .query_one(
@@ -156,8 +202,8 @@ impl BlockValue {
         index_b: i32,
         context: &Context,
     ) -> ApiResult<Realm> {
-        let realm_key = realm.key_for(Id::REALM_KIND)
-            .ok_or_else(|| invalid_input!("`realm` is not a valid realm id"))?;
+        let realm_key = RealmId::from_id(realm)
+            .map_err(|e| invalid_input!("`realm` is not a valid realm id: {e}"))?;
 
         if index_a == index_b {
             return Realm::load_by_key(realm_key, context)
@@ -255,16 +301,20 @@ impl BlockValue {
         set: UpdateTextBlock,
         context: &Context,
     ) -> ApiResult<Self> {
+        let html = set.content.as_deref()
+            .map(|content| render_text(content, context))
+            .transpose()?;
         let selection = Self::select();
         let query = format!(
             "update blocks set \
-                text_content = coalesce($2, text_content) \
+                text_content = coalesce($2, text_content), \
+                text_html = coalesce($3, text_html) \
                 where id = $1 \
                 and type = 'text' \
                 returning {selection}",
         );
         context.db(context.require_moderator()?)
-            .query_one(&query, &[&Self::key_for(id)?, &set.content])
+            .query_one(&query, &[&Self::key_for(id)?, &set.content, &html])
             .await?
             .pipe(|row| Ok(Self::from_row_start(&row)))
     }
@@ -275,9 +325,18 @@ impl BlockValue {
         context: &Context,
     ) -> ApiResult<BlockValue> {
         let series_id = set.series.map(
-            |series| series.key_for(Id::SERIES_KIND)
-                .ok_or_else(|| invalid_input!("`set.series` does not refer to a series"))
+            |series| SeriesId::from_id(series)
+                .map_err(|e| invalid_input!("`set.series` does not refer to a series: {e}"))
         ).transpose()?;
+        if let Some(series) = series_id {
+            context.require_moderator()?;
+            check_reference(Reference::Series(series), context).await?;
+        }
+        let update_filter = set.metadata_filter.is_some();
+        let metadata_filter = set.metadata_filter
+            .map(|entries| metadata_filter_to_json(entries, context))
+            .transpose()?
+            .flatten();
 
         let selection = Self::select();
         let query = format!(
@@ -285,7 +344,9 @@ impl BlockValue {
                 series = coalesce($2, series), \
                 videolist_order = coalesce($3, videolist_order), \
                 show_title = coalesce($4, show_title), \
-                show_metadata = coalesce($5, show_metadata) \
+                show_metadata = coalesce($5, show_metadata), \
+                videolist_layout = coalesce($6, videolist_layout), \
+                metadata_filter = case when $7 then $8 else metadata_filter end \
                 where id = $1 \
                 and type = 'series' \
                 returning {selection}",
@@ -296,11 +357,18 @@ impl BlockValue {
             &set.order,
             &set.show_title,
             &set.show_metadata,
+            &set.layout,
+            &update_filter,
+            &metadata_filter,
         ];
-        context.db(context.require_moderator()?)
+        let block = context.db(context.require_moderator()?)
             .query_one(&query, &args)
             .await?
-            .pipe(|row| Ok(Self::from_row_start(&row)))
+            .pipe(|row| Self::from_row_start(&row));
+
+        // The block might be the name source of its realm.
+        context.navigation_cache.invalidate();
+        Ok(block)
     }
 
     pub(crate) async fn update_video(
@@ -309,9 +377,13 @@ impl BlockValue {
         context: &Context,
     ) -> ApiResult<BlockValue> {
         let video_id = set.event.map(
-            |series| series.key_for(Id::EVENT_KIND)
-                .ok_or_else(|| invalid_input!("`set.event` does not refer to a event"))
+            |event| EventId::from_id(event)
+                .map_err(|e| invalid_input!("`set.event` does not refer to an event: {e}"))
         ).transpose()?;
+        if let Some(event) = video_id {
+            context.require_moderator()?;
+            check_reference(Reference::Event(event), context).await?;
+        }
 
         let selection = Self::select();
         let query = format!(
@@ -322,16 +394,58 @@ impl BlockValue {
                 and type = 'video' \
                 returning {selection}",
         );
-        context.db(context.require_moderator()?)
+        let block = context.db(context.require_moderator()?)
             .query_one(&query, &[&Self::key_for(id)?, &video_id, &set.show_title])
             .await?
+            .pipe(|row| Self::from_row_start(&row));
+
+        // The block might be the name source of its realm.
+        context.navigation_cache.invalidate();
+        Ok(block)
+    }
+
+    pub(crate) async fn update_toc(
+        id: Id,
+        set: UpdateTocBlock,
+        context: &Context,
+    ) -> ApiResult<BlockValue> {
+        let depth = set.depth.map(toc_depth).transpose()?;
+
+        let selection = Self::select();
+        let query = format!(
+            "update blocks set \
+                toc_depth = coalesce($2, toc_depth), \
+                toc_show_counts = coalesce($3, toc_show_counts), \
+                toc_order = coalesce($4, toc_order) \
+                where id = $1 \
+                and type = 'toc' \
+                returning {selection}",
+        );
+        context.db(context.require_moderator()?)
+            .query_one(&query, &[&Self::key_for(id)?, &depth, &set.show_counts, &set.order])
+            .await?
             .pipe(|row| Ok(Self::from_row_start(&row)))
     }
 
     pub(crate) async fn remove(id: Id, context: &Context) -> ApiResult<RemovedBlock> {
         let db = context.db(context.require_moderator()?);
-        let block_id = id.key_for(Id::BLOCK_KIND)
-            .ok_or_else(|| invalid_input!("`id` does not refer to a block"))?;
+        let block_id = Self::key_for(id)?;
+
+        // If the block is the name source of its realm, the realm keeps its
+        // current name as plain name. Falls back to the path segment if there
+        // is no current name.
+        db
+            .execute(
+                "update realms \
+                    set name = coalesce(series.title, events.title, realms.path_segment), \
+                        name_from_block = null \
+                    from blocks \
+                    left join events on blocks.video = events.id \
+                    left join series on blocks.series = series.id \
+                    where blocks.id = $1 and realms.name_from_block = $1",
+                &[&block_id],
+            )
+            .await?;
 
         let (selection, mapping) = select!(realm: Realm, index: "(select index from deleted)");
         let query = format!(
@@ -351,6 +465,9 @@ impl BlockValue {
         let index: i16 = mapping.index.of(&result);
         let realm = Realm::from_row(&result, mapping.realm);
 
+        // The block might have been the name source of its realm.
+        context.navigation_cache.invalidate();
+
         // Fix indices after removed block
         db
             .execute(
@@ -365,9 +482,110 @@ impl BlockValue {
         Ok(RemovedBlock { id, realm })
     }
 
-    fn key_for(id: Id) -> ApiResult<Key> {
-        id.key_for(Id::BLOCK_KIND)
-            .ok_or_else(|| invalid_input!("`id` does not refer to a block"))
+    fn key_for(id: Id) -> ApiResult<BlockId> {
+        BlockId::from_id(id).map_err(|e| invalid_input!("`id` does not refer to a block: {e}"))
+    }
+}
+
+/// Renders the Markdown content of a text block to HTML, after checking that
+/// it does not exceed `general.text_block_max_size`.
+fn render_text(content: &str, context: &Context) -> ApiResult<String> {
+    let max_size = context.config.general.text_block_max_size;
+    if content.len() > max_size as usize {
+        return Err(invalid_input!(
+            key = "block.text-too-large",
+            "text block content is too large: {} bytes, but at most {max_size} bytes are allowed",
+            content.len(),
+        ));
+    }
+
+    Ok(markdown::render(content))
+}
+
+/// Converts the given filter entries to the shape of `events.metadata`, so
+/// that events can be filtered with `@>`. Only fields configured in
+/// `general.metadata` are allowed. Returns `None` if there are no entries.
+fn metadata_filter_to_json(
+    entries: Vec<NewMetadataFilterEntry>,
+    context: &Context,
+) -> ApiResult<Option<serde_json::Value>> {
+    let mut filter = serde_json::Map::new();
+    for NewMetadataFilterEntry { namespace, field, value } in entries {
+        let known = context.config.general.metadata.get(&namespace)
+            .is_some_and(|fields| fields.contains_key(&field));
+        if !known {
+            return Err(invalid_input!(
+                "metadata field '{namespace}:{field}' is not configured in `general.metadata`",
+            ));
+        }
+        if value.is_empty() {
+            return Err(invalid_input!("metadata filter value must not be empty"));
+        }
+
+        filter.entry(namespace)
+            .or_insert_with(|| serde_json::Value::Object(Default::default()))
+            .as_object_mut()
+            .expect("namespace entry is always an object")
+            .entry(field)
+            .or_insert_with(|| serde_json::Value::Array(vec![]))
+            .as_array_mut()
+            .expect("field entry is always an array")
+            .push(value.into());
+    }
+
+    Ok((!filter.is_empty()).then(|| filter.into()))
+}
+
+#[derive(Clone, Copy)]
+enum Reference {
+    Series(SeriesId),
+    Event(EventId),
+}
+
+/// Checks that the series or event a block should refer to exists and that the
+/// user can read it. Otherwise, the block would only fail when rendering the
+/// realm. Series that are not synced yet have no ACL and are always allowed.
+async fn check_reference(kind: Reference, context: &Context) -> ApiResult<()> {
+    let (table, key): (_, &(dyn postgres_types::ToSql + Sync)) = match &kind {
+        Reference::Series(key) => ("series", key),
+        Reference::Event(key) => ("events", key),
+    };
+    let query = format!(
+        "select coalesce((read_roles || 'ROLE_ADMIN'::text) && $2, true) \
+            from {table} where id = $1",
+    );
+    let readable = context.db
+        .query_opt(&query, &[key, &context.auth.roles_vec()])
+        .await?
+        .map(|row| row.get::<_, bool>(0));
+
+    match (readable, kind) {
+        (Some(true), _) => Ok(()),
+        (None, Reference::Series(_)) => Err(invalid_reference!(
+            key = "block.series-not-found",
+            "the referenced series does not exist",
+        )),
+        (None, Reference::Event(_)) => Err(invalid_reference!(
+            key = "block.event-not-found",
+            "the referenced event does not exist",
+        )),
+        (Some(false), Reference::Series(_)) => Err(invalid_reference!(
+            key = "block.series-not-readable",
+            "you cannot read the referenced series",
+        )),
+        (Some(false), Reference::Event(_)) => Err(invalid_reference!(
+            key = "block.event-not-readable",
+            "you cannot read the referenced event",
+        )),
+    }
+}
+
+/// Checks that the given depth for a TOC block is within the range allowed by
+/// the DB constraint.
+fn toc_depth(depth: i32) -> ApiResult<i16> {
+    match depth {
+        1..=4 => Ok(depth as i16),
+        _ => Err(invalid_input!("`depth` has to be between 1 and 4")),
     }
 }
 
@@ -388,6 +606,17 @@ pub(crate) struct NewSeriesBlock {
     pub(crate) show_title: bool,
     pub(crate) show_metadata: bool,
     pub(crate) order: VideoListOrder,
+    pub(crate) layout: VideoListLayout,
+    /// Only show events that have all of these metadata values. The fields
+    /// have to be configured in `general.metadata`.
+    pub(crate) metadata_filter: Option<Vec<NewMetadataFilterEntry>>,
+}
+
+#[derive(GraphQLInputObject)]
+pub(crate) struct NewMetadataFilterEntry {
+    namespace: String,
+    field: String,
+    value: String,
 }
 
 #[derive(GraphQLInputObject)]
@@ -397,6 +626,14 @@ pub(crate) struct NewVideoBlock {
 }
 
 
+#[derive(GraphQLInputObject)]
+pub(crate) struct NewTocBlock {
+    depth: i32,
+    show_counts: bool,
+    order: TocOrder,
+}
+
+
 #[derive(GraphQLInputObject)]
 pub(crate) struct UpdateTitleBlock {
     content: Option<String>,
@@ -413,6 +650,9 @@ pub(crate) struct UpdateSeriesBlock {
     show_title: Option<bool>,
     show_metadata: Option<bool>,
     order: Option<VideoListOrder>,
+    layout: Option<VideoListLayout>,
+    /// Replaces the metadata filter. An empty list removes it.
+    metadata_filter: Option<Vec<NewMetadataFilterEntry>>,
 }
 
 #[derive(GraphQLInputObject)]
@@ -421,6 +661,13 @@ pub(crate) struct UpdateVideoBlock {
     show_title: Option<bool>,
 }
 
+#[derive(GraphQLInputObject)]
+pub(crate) struct UpdateTocBlock {
+    depth: Option<i32>,
+    show_counts: Option<bool>,
+    order: Option<TocOrder>,
+}
+
 
 #[derive(GraphQLObject)]
 #[graphql(Context = Context)]
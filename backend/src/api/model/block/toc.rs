@@ -0,0 +1,150 @@
+//! Loading the realm subtree shown by a `TocBlock`.
+
+use std::collections::HashMap;
+use juniper::GraphQLObject;
+
+use crate::{
+    api::{
+        Context, RealmId,
+        err::ApiResult,
+        model::realm::{REALM_JOINS, Realm, RealmOrder},
+    },
+    db::util::select,
+    prelude::*,
+};
+use super::{TocBlock, TocOrder};
+
+
+/// The realms shown by a `TocBlock`.
+#[derive(GraphQLObject)]
+#[graphql(Context = Context)]
+pub(crate) struct TocContents {
+    /// The children of the block's realm, each with their children and so on.
+    entries: Vec<TocEntry>,
+
+    /// `true` if some realms were omitted because the subtree is larger than
+    /// `general.toc_block_max_entries`. Omitted realms are always the deepest
+    /// ones.
+    truncated: bool,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(Context = Context)]
+pub(crate) struct TocEntry {
+    realm: Realm,
+
+    /// Number of videos on the realm's page that the current user can see.
+    /// Only set if the block has `showCounts` enabled.
+    video_count: Option<i32>,
+
+    children: Vec<TocEntry>,
+}
+
+/// A realm of the subtree before it is sorted into the tree structure.
+struct SubtreeRealm {
+    realm: Realm,
+    parent: RealmId,
+    name: Option<String>,
+    index: i32,
+    parent_order: RealmOrder,
+    video_count: Option<i32>,
+}
+
+pub(super) async fn load(block: &TocBlock, context: &Context) -> ApiResult<TocContents> {
+    let max_entries = context.config.general.toc_block_max_entries as usize;
+
+    // We walk the subtree breadth first and only load one row more than we
+    // show, to find out whether we had to truncate. The order is only
+    // relevant for which realms are cut off; sorting happens below.
+    let (selection, mapping) = select!(
+        realm: Realm,
+        parent: "realms.parent",
+        name: "coalesce(realms.name, series.title, events.title)",
+        index: "realms.index",
+        parent_order: "(select child_order from realms p where p.id = realms.parent)",
+        video_count: "case when $3 then (\
+            select count(distinct e.id)::int \
+                from blocks b \
+                join events e on b.type = 'video' and e.id = b.video \
+                    or b.type = 'series' and e.series = b.series \
+                        and (b.metadata_filter is null or e.metadata @> b.metadata_filter) \
+                where b.realm = realms.id \
+                and (e.read_roles || 'ROLE_ADMIN'::text) && $5\
+        ) end",
+    );
+    let query = format!(
+        "with recursive subtree as ( \
+            select id, 1 as depth from realms where parent = $1 \
+            union all \
+            select realms.id, subtree.depth + 1 \
+                from realms join subtree on realms.parent = subtree.id \
+                where subtree.depth < $2 \
+        ) \
+        select {selection} \
+        from subtree \
+        join realms on realms.id = subtree.id \
+        {REALM_JOINS} \
+        order by subtree.depth, realms.parent, realms.index \
+        limit $4",
+    );
+    let rows = context.db
+        .query_mapped(
+            &query,
+            dbargs![
+                &block.shared.realm_key,
+                &i32::from(block.depth),
+                &block.show_counts,
+                &(max_entries as i64 + 1),
+                &context.auth.roles_vec(),
+            ],
+            |row| SubtreeRealm {
+                realm: Realm::from_row(&row, mapping.realm),
+                parent: mapping.parent.of(&row),
+                name: mapping.name.of(&row),
+                index: mapping.index.of(&row),
+                parent_order: mapping.parent_order.of(&row),
+                video_count: mapping.video_count.of(&row),
+            },
+        )
+        .await?;
+
+    let truncated = rows.len() > max_entries;
+    let mut by_parent = HashMap::<_, Vec<_>>::new();
+    for realm in rows.into_iter().take(max_entries) {
+        by_parent.entry(realm.parent).or_default().push(realm);
+    }
+
+    Ok(TocContents {
+        entries: build_level(block.shared.realm_key, &mut by_parent, block.order),
+        truncated,
+    })
+}
+
+/// Builds the entries for all children of `parent`, recursively.
+fn build_level(
+    parent: RealmId,
+    by_parent: &mut HashMap<RealmId, Vec<SubtreeRealm>>,
+    order: TocOrder,
+) -> Vec<TocEntry> {
+    let mut children = by_parent.remove(&parent).unwrap_or_default();
+    if let Some(first) = children.first() {
+        let order = match order {
+            TocOrder::Inherit => first.parent_order,
+            TocOrder::AlphabeticAsc => RealmOrder::AlphabeticAsc,
+            TocOrder::AlphabeticDesc => RealmOrder::AlphabeticDesc,
+        };
+        match order {
+            RealmOrder::ByIndex => children.sort_by_key(|c| c.index),
+            RealmOrder::AlphabeticAsc => children.sort_by(|a, b| a.name.cmp(&b.name)),
+            RealmOrder::AlphabeticDesc => children.sort_by(|a, b| b.name.cmp(&a.name)),
+        }
+    }
+
+    children.into_iter()
+        .map(|child| TocEntry {
+            children: build_level(child.realm.key, by_parent, order),
+            realm: child.realm,
+            video_count: child.video_count,
+        })
+        .collect()
+}
@@ -8,15 +8,23 @@ use juniper::{GraphQLObject, graphql_object};
 
 use crate::{
     api::{
-        Context, Cursor, Id, Node, NodeValue,
+        Context, Cursor, Id, EventId, SeriesId, Node, NodeValue,
         common::NotAllowed,
-        err::{self, ApiResult, invalid_input},
-        model::{series::Series, realm::{Realm, REALM_JOINS}},
+        err::{self, ApiError, ApiErrorKind, ApiResult, invalid_input, not_authorized},
+        model::{
+            acl::AclPreset,
+            block::VideoListOrder,
+            license::License,
+            series::Series,
+            realm::{Realm, REALM_JOINS},
+        },
     },
     db::{
-        types::{EventTrack, EventState, Key, ExtraMetadata, EventCaption},
+        types::{EventTrack, EventState, ExtraMetadata, EventCaption},
         util::{impl_from_db, select},
     },
+    auth::ROLE_ANONYMOUS,
+    http,
     prelude::*,
     util::lazy_format,
 };
@@ -24,24 +32,28 @@ use crate::{
 
 #[derive(Debug)]
 pub(crate) struct AuthorizedEvent {
-    key: Key,
-    series: Option<Key>,
+    key: EventId,
+    series: Option<SeriesId>,
     opencast_id: String,
     is_live: bool,
+    is_processing: bool,
 
     title: String,
     description: Option<String>,
     created: DateTime<Utc>,
     creators: Vec<String>,
+    original_creators: Vec<String>,
 
     metadata: ExtraMetadata,
+    license: Option<License>,
     read_roles: Vec<String>,
     write_roles: Vec<String>,
+    external_origin: Option<String>,
 
     synced_data: Option<SyncedEventData>,
 }
 
-#[derive(Debug, GraphQLObject)]
+#[derive(Debug, Clone, GraphQLObject)]
 pub(crate) struct SyncedEventData {
     updated: DateTime<Utc>,
     start_time: Option<DateTime<Utc>>,
@@ -58,11 +70,12 @@ impl_from_db!(
     AuthorizedEvent,
     select: {
         events.{
-            id, state, series, opencast_id, is_live,
-            title, description, duration, creators, thumbnail, metadata,
+            id, state, series, opencast_id, is_live, is_processing,
+            title, description, duration, creators, creator_names, thumbnail, metadata,
+            license, license_spdx,
             created, updated, start_time, end_time,
             tracks, captions,
-            read_roles, write_roles,
+            read_roles, write_roles, external_origin,
         },
     },
     |row| {
@@ -71,13 +84,18 @@ impl_from_db!(
             series: row.series(),
             opencast_id: row.opencast_id(),
             is_live: row.is_live(),
+            is_processing: row.is_processing(),
             title: row.title(),
             description: row.description(),
             created: row.created(),
-            creators: row.creators(),
+            creators: row.creator_names::<Option<Vec<String>>>()
+                .unwrap_or_else(|| row.creators()),
+            original_creators: row.creators(),
             metadata: row.metadata(),
+            license: License::from_db(row.license(), row.license_spdx()),
             read_roles: row.read_roles::<Vec<String>>(),
             write_roles: row.write_roles::<Vec<String>>(),
+            external_origin: row.external_origin(),
             synced_data: match row.state::<EventState>() {
                 EventState::Ready => Some(SyncedEventData {
                     updated: row.updated(),
@@ -98,7 +116,7 @@ impl_from_db!(
 );
 
 
-#[derive(Debug, GraphQLObject)]
+#[derive(Debug, Clone, GraphQLObject)]
 pub(crate) struct Track {
     uri: String,
     flavor: String,
@@ -109,7 +127,7 @@ pub(crate) struct Track {
     is_master: Option<bool>,
 }
 
-#[derive(Debug, GraphQLObject)]
+#[derive(Debug, Clone, GraphQLObject)]
 pub(crate) struct Caption {
     uri: String,
     lang: Option<String>,
@@ -117,7 +135,7 @@ pub(crate) struct Caption {
 
 impl Node for AuthorizedEvent {
     fn id(&self) -> Id {
-        Id::event(self.key)
+        self.key.into()
     }
 }
 
@@ -132,6 +150,11 @@ impl AuthorizedEvent {
     fn is_live(&self) -> bool {
         self.is_live
     }
+    /// Whether Opencast is currently processing this event. While that is
+    /// the case, `syncedData` still contains the data from before processing.
+    fn is_processing(&self) -> bool {
+        self.is_processing
+    }
     fn title(&self) -> &str {
         &self.title
     }
@@ -141,20 +164,53 @@ impl AuthorizedEvent {
     fn created(&self) -> DateTime<Utc> {
         self.created
     }
+    /// Names of the persons that created this event, normalized and
+    /// deduplicated.
     fn creators(&self) -> &Vec<String> {
         &self.creators
     }
+    /// Creators exactly as stored in Opencast.
+    fn original_creators(&self) -> &Vec<String> {
+        &self.original_creators
+    }
     fn metadata(&self) -> &ExtraMetadata {
         &self.metadata
     }
+    fn license(&self) -> Option<&License> {
+        self.license.as_ref()
+    }
+
+    fn synced_data(&self, context: &Context) -> Option<SyncedEventData> {
+        let mut data = self.synced_data.clone()?;
+        for (i, track) in data.tracks.iter_mut().enumerate() {
+            if let Some(uri) = http::proxy::track_uri(&context.config, self.key, i, &track.uri) {
+                track.uri = uri;
+            }
+        }
+        Some(data)
+    }
 
-    fn synced_data(&self) -> &Option<SyncedEventData> {
-        &self.synced_data
+    /// Base URL of the Tobira instance this event was imported from, `null`
+    /// for local events. Imported events are read-only.
+    fn external_origin(&self) -> Option<&str> {
+        self.external_origin.as_deref()
     }
 
     /// Whether the current user has write access to this event.
     fn can_write(&self, context: &Context) -> bool {
-        context.auth.overlaps_roles(&self.write_roles)
+        self.external_origin.is_none() && context.auth.overlaps_roles(&self.write_roles)
+    }
+
+    /// Roles that are allowed to read this event. `null` if the current user
+    /// has no write access.
+    fn read_roles(&self, context: &Context) -> Option<&Vec<String>> {
+        context.auth.overlaps_roles(&self.write_roles).then_some(&self.read_roles)
+    }
+
+    /// Roles that are allowed to write this event. `null` if the current user
+    /// has no write access.
+    fn write_roles(&self, context: &Context) -> Option<&Vec<String>> {
+        context.auth.overlaps_roles(&self.write_roles).then_some(&self.write_roles)
     }
 
     async fn series(&self, context: &Context) -> ApiResult<Option<Series>> {
@@ -180,7 +236,9 @@ impl AuthorizedEvent {
                     type = 'video' and video = $1 \
                     or type = 'series' and series = ( \
                         select series from events where id = $1 \
-                    ) \
+                    ) and (metadata_filter is null or metadata_filter <@ ( \
+                        select metadata from events where id = $1 \
+                    )) \
                 ) \
             ) \
         ");
@@ -211,12 +269,16 @@ impl Event {
 
 impl AuthorizedEvent {
     pub(crate) async fn load_by_id(id: Id, context: &Context) -> ApiResult<Option<Event>> {
-        match id.key_for(Id::EVENT_KIND) {
-            None => return Ok(None),
-            Some(key) => Self::load_by_any_id_impl("id", &key, context).await,
+        match EventId::from_node_id(id)? {
+            Some(key) => Self::load_by_key(key, context).await,
+            None => Ok(None),
         }
     }
 
+    pub(crate) async fn load_by_key(key: EventId, context: &Context) -> ApiResult<Option<Event>> {
+        Self::load_by_any_id_impl("id", &key, context).await
+    }
+
     pub(crate) async fn load_by_opencast_id(oc_id: String, context: &Context) -> ApiResult<Option<Event>> {
         Self::load_by_any_id_impl("opencast_id", &oc_id, context).await
     }
@@ -242,21 +304,102 @@ impl AuthorizedEvent {
             .pipe(Ok)
     }
 
+    /// Replaces the ACL of the event with the roles the given preset expands
+    /// to. The ACL is changed in Opencast first and then stored in our DB,
+    /// without any reference to the preset. Requires write access.
+    pub(crate) async fn apply_acl_preset(
+        id: Id,
+        preset: String,
+        context: &Context,
+    ) -> ApiResult<Self> {
+        let (read_roles, write_roles) = AclPreset::expand_for_current_user(&preset, context)?;
+        let event = Self::load_by_id(id, context)
+            .await?
+            .ok_or_else(|| invalid_input!("`id` does not refer to an event"))?
+            .into_result()?;
+        if !context.auth.overlaps_roles(&event.write_roles) {
+            return Err(not_authorized!("you are not allowed to change the ACL of this event"));
+        }
+        if event.external_origin.is_some() {
+            return Err(invalid_input!("the ACL of events imported from another Tobira \
+                cannot be changed"));
+        }
+
+        context.oc_client.update_acl(&event.opencast_id, &read_roles, &write_roles)
+            .await
+            .map_err(|e| {
+                error!("Failed to update ACL of event '{}' in Opencast: {e:#}", event.opencast_id);
+                ApiError {
+                    msg: "failed to update ACL in Opencast".into(),
+                    kind: ApiErrorKind::InternalServerError,
+                    key: Some("event.acl-update-failed"),
+                }
+            })?;
+
+        // The next harvest will overwrite this with what Opencast reports,
+        // but until then, we want to show the new ACL already.
+        context.db
+            .execute(
+                "update events set read_roles = $1, write_roles = $2 where id = $3",
+                &[&read_roles, &write_roles, &event.key],
+            )
+            .await?;
+
+        Ok(Self { read_roles, write_roles, ..event })
+    }
+
+    /// Loads public events that were not imported from elsewhere, ordered by
+    /// Opencast ID. This is what other Tobira instances import, see
+    /// `sync::federation`.
+    pub(crate) async fn load_public(
+        after: Option<String>,
+        first: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<Self>> {
+        const MAX_COUNT: i32 = 500;
+
+        if !(1..=MAX_COUNT).contains(&first) {
+            return Err(invalid_input!("argument 'first' has to be between 1 and {MAX_COUNT}, \
+                but is {first}"));
+        }
+
+        let selection = Self::select();
+        let query = format!(
+            "select {selection} from events \
+                where state = 'ready' and external_origin is null \
+                    and read_roles && array['{ROLE_ANONYMOUS}'] \
+                    and ($1::text is null or opencast_id > $1) \
+                order by opencast_id \
+                limit $2",
+        );
+        context.db
+            .query_mapped(&query, dbargs![&after, &i64::from(first)], |row| {
+                Self::from_row_start(&row)
+            })
+            .await?
+            .pipe(Ok)
+    }
+
+    /// Loads the events of the given series the user can read. If
+    /// `metadata_filter` is given, only events whose metadata contains it are
+    /// returned (see `SeriesBlock`).
     pub(crate) async fn load_for_series(
-        series_key: Key,
+        series_key: SeriesId,
         order: EventSortOrder,
+        metadata_filter: Option<&serde_json::Value>,
         context: &Context,
     ) -> ApiResult<Vec<Self>> {
         let selection = Self::select();
         let query = format!(
             "select {selection} from events \
-                where series = $2 and (read_roles || 'ROLE_ADMIN'::text) && $1 {}",
+                where series = $2 and (read_roles || 'ROLE_ADMIN'::text) && $1 \
+                and ($3::jsonb is null or metadata @> $3) {}",
             order.to_sql(),
         );
         context.db
             .query_mapped(
                 &query,
-                dbargs![&context.auth.roles_vec(), &series_key],
+                dbargs![&context.auth.roles_vec(), &series_key, &metadata_filter],
                 |row| Self::from_row_start(&row),
             )
             .await?
@@ -478,7 +621,7 @@ enum EventSortColumn {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, juniper::GraphQLEnum)]
-enum SortDirection {
+pub(crate) enum SortDirection {
     Ascending,
     Descending,
 }
@@ -492,6 +635,17 @@ impl Default for EventSortOrder {
     }
 }
 
+impl From<VideoListOrder> for EventSortOrder {
+    fn from(order: VideoListOrder) -> Self {
+        let (column, direction) = match order {
+            VideoListOrder::NewToOld => (EventSortColumn::Created, SortDirection::Descending),
+            VideoListOrder::OldToNew => (EventSortColumn::Created, SortDirection::Ascending),
+            VideoListOrder::Alphabetic => (EventSortColumn::Title, SortDirection::Ascending),
+        };
+        Self { column, direction }
+    }
+}
+
 impl EventSortOrder {
     /// Returns an SQL query fragment like `order by foo asc`.
     fn to_sql(&self) -> impl fmt::Display {
@@ -511,14 +665,14 @@ impl EventSortColumn {
 }
 
 impl SortDirection {
-    fn to_sql(self) -> &'static str {
+    pub(crate) fn to_sql(self) -> &'static str {
         match self {
             SortDirection::Ascending => "asc",
             SortDirection::Descending => "desc",
         }
     }
 
-    fn is_ascending(&self) -> bool {
+    pub(crate) fn is_ascending(&self) -> bool {
         matches!(self, Self::Ascending)
     }
 
@@ -541,7 +695,7 @@ pub(crate) struct EventConnection {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct EventCursor {
-    key: Key,
+    key: EventId,
     sort_filter: CursorSortFilter,
 }
 
@@ -0,0 +1,329 @@
+//! Admin-only listings of all users that ever logged in and of their active
+//! sessions. Only filled if Tobira's own session management (auth mode
+//! `login-proxy`) is used.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use postgres_types::ToSql;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        Context, Cursor,
+        err::{ApiResult, invalid_input},
+        model::event::SortDirection,
+    },
+    db::util::select,
+};
+
+
+/// Maximum number of items returned per page.
+const MAX_COUNT: i32 = 100;
+
+
+/// A user that logged in at least once.
+#[derive(Debug, juniper::GraphQLObject)]
+pub(crate) struct KnownUser {
+    /// The username, a unique string identifying the user.
+    username: String,
+    /// The name of the user intended to be read by humans, as of the last
+    /// login.
+    display_name: String,
+    /// The roles of the user, as of the last login.
+    roles: Vec<String>,
+    first_login: DateTime<Utc>,
+    last_login: DateTime<Utc>,
+}
+
+/// An active session of a user.
+#[derive(Debug, juniper::GraphQLObject)]
+pub(crate) struct Session {
+    username: String,
+    display_name: String,
+    roles: Vec<String>,
+    created: DateTime<Utc>,
+    /// The language explicitly chosen in this session, if any.
+    lang: Option<String>,
+    #[graphql(skip)]
+    seq: i64,
+}
+
+#[derive(Debug, juniper::GraphQLObject)]
+pub(crate) struct KnownUserConnection {
+    page_info: ForwardPageInfo,
+    items: Vec<KnownUser>,
+    /// Number of users matching the filters, regardless of pagination.
+    total_count: i32,
+}
+
+#[derive(Debug, juniper::GraphQLObject)]
+pub(crate) struct SessionConnection {
+    page_info: ForwardPageInfo,
+    items: Vec<Session>,
+    /// Number of active sessions matching the filters, regardless of
+    /// pagination.
+    total_count: i32,
+}
+
+/// Page info for connections that can only be paginated forwards.
+#[derive(Debug, juniper::GraphQLObject)]
+pub(crate) struct ForwardPageInfo {
+    has_next_page: bool,
+    end_cursor: Option<Cursor>,
+}
+
+/// Defines the sort order for users.
+#[derive(Debug, Clone, Copy, juniper::GraphQLInputObject)]
+pub(crate) struct UserSortOrder {
+    column: UserSortColumn,
+    direction: SortDirection,
+}
+
+#[derive(Debug, Clone, Copy, juniper::GraphQLEnum)]
+enum UserSortColumn {
+    Username,
+    DisplayName,
+    LastLogin,
+}
+
+impl Default for UserSortOrder {
+    fn default() -> Self {
+        Self {
+            column: UserSortColumn::Username,
+            direction: SortDirection::Ascending,
+        }
+    }
+}
+
+impl UserSortColumn {
+    fn to_sql(self) -> &'static str {
+        match self {
+            Self::Username => "username",
+            Self::DisplayName => "display_name",
+            Self::LastLogin => "last_login",
+        }
+    }
+}
+
+/// Filters for `Query.users`. All given filters have to match.
+#[derive(Debug, Default)]
+pub(crate) struct UserFilter {
+    /// Has to be contained (case-insensitive) in username or display name.
+    pub(crate) query: Option<String>,
+    pub(crate) role: Option<String>,
+    pub(crate) logged_in_within_days: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserCursor {
+    sort_value: UserCursorValue,
+    username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum UserCursorValue {
+    Username(String),
+    DisplayName(String),
+    LastLogin(DateTime<Utc>),
+}
+
+impl UserCursor {
+    fn new(user: &KnownUser, order: UserSortOrder) -> Self {
+        let sort_value = match order.column {
+            UserSortColumn::Username => UserCursorValue::Username(user.username.clone()),
+            UserSortColumn::DisplayName => {
+                UserCursorValue::DisplayName(user.display_name.clone())
+            }
+            UserSortColumn::LastLogin => UserCursorValue::LastLogin(user.last_login),
+        };
+        Self { sort_value, username: user.username.clone() }
+    }
+
+    fn to_sql_arg(&self, order: UserSortOrder) -> ApiResult<&(dyn ToSql + Sync + '_)> {
+        match (&self.sort_value, order.column) {
+            (UserCursorValue::Username(v), UserSortColumn::Username) => Ok(v),
+            (UserCursorValue::DisplayName(v), UserSortColumn::DisplayName) => Ok(v),
+            (UserCursorValue::LastLogin(v), UserSortColumn::LastLogin) => Ok(v),
+            _ => Err(invalid_input!("sort order does not match 'after' argument")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionCursor {
+    created: NaiveDateTime,
+    seq: i64,
+}
+
+
+impl KnownUser {
+    pub(crate) async fn load(
+        filter: UserFilter,
+        order: UserSortOrder,
+        first: i32,
+        after: Option<Cursor>,
+        context: &Context,
+    ) -> ApiResult<KnownUserConnection> {
+        let db = context.db(context.require_admin()?);
+        check_first(first)?;
+        if filter.logged_in_within_days.is_some_and(|days| days < 0) {
+            return Err(invalid_input!("argument 'loggedInWithinDays' must not be negative"));
+        }
+        let after = after.map(|c| c.deserialize::<UserCursor>()).transpose()?;
+
+        let mut args: Vec<&(dyn ToSql + Sync)> = vec![];
+        let mut conditions = vec![];
+        if let Some(query) = &filter.query {
+            args.push(query);
+            let n = args.len();
+            conditions.push(format!(
+                "(strpos(lower(username), lower(${n})) > 0 \
+                    or strpos(lower(display_name), lower(${n})) > 0)"
+            ));
+        }
+        if let Some(role) = &filter.role {
+            args.push(role);
+            conditions.push(format!("roles @> array[${}::text]", args.len()));
+        }
+        if let Some(days) = &filter.logged_in_within_days {
+            args.push(days);
+            conditions.push(format!("last_login > now() - make_interval(days => ${})", args.len()));
+        }
+
+        let total_count = db
+            .query_one(&format!("select count(*) from users {}", where_clause(&conditions)), &args)
+            .await?
+            .get::<_, i64>(0);
+
+        let col = order.column.to_sql();
+        if let Some(after) = &after {
+            args.extend_from_slice(&[after.to_sql_arg(order)?, &after.username]);
+            let op = if order.direction.is_ascending() { '>' } else { '<' };
+            conditions.push(format!(
+                "({col}, username) {op} (${}, ${})",
+                args.len() - 1,
+                args.len(),
+            ));
+        }
+
+        // We fetch one more item than requested to find out whether there is
+        // a next page.
+        let (selection, mapping) = select!(
+            username,
+            display_name,
+            roles,
+            first_login,
+            last_login,
+        );
+        let query = format!(
+            "select {selection} from users {filter} \
+                order by {col} {dir}, username {dir} \
+                limit {limit}",
+            filter = where_clause(&conditions),
+            dir = order.direction.to_sql(),
+            limit = first + 1,
+        );
+        let mut users = db.query_mapped(&query, args, |row| Self {
+            username: mapping.username.of(&row),
+            display_name: mapping.display_name.of(&row),
+            roles: mapping.roles.of(&row),
+            first_login: mapping.first_login.of(&row),
+            last_login: mapping.last_login.of(&row),
+        }).await?;
+
+        let has_next_page = users.len() > first as usize;
+        users.truncate(first as usize);
+
+        Ok(KnownUserConnection {
+            page_info: ForwardPageInfo {
+                has_next_page,
+                end_cursor: users.last().map(|u| Cursor::new(UserCursor::new(u, order))),
+            },
+            items: users,
+            total_count: total_count.try_into().expect("more than 2^31 users"),
+        })
+    }
+}
+
+impl Session {
+    /// Loads all active sessions (of the given user, if specified), newest
+    /// first.
+    pub(crate) async fn load(
+        username: Option<String>,
+        first: i32,
+        after: Option<Cursor>,
+        context: &Context,
+    ) -> ApiResult<SessionConnection> {
+        let db = context.db(context.require_admin()?);
+        check_first(first)?;
+        let after = after.map(|c| c.deserialize::<SessionCursor>()).transpose()?;
+
+        // Outdated sessions are only deleted periodically, so we have to
+        // filter them here.
+        let session_duration = context.config.auth.session_duration.as_secs_f64();
+        let mut args: Vec<&(dyn ToSql + Sync)> = vec![&session_duration];
+        let mut conditions = vec!["extract(epoch from now() - created) < $1".to_owned()];
+        if let Some(username) = &username {
+            args.push(username);
+            conditions.push(format!("username = ${}", args.len()));
+        }
+
+        let total_count = db
+            .query_one(
+                &format!("select count(*) from user_sessions {}", where_clause(&conditions)),
+                &args,
+            )
+            .await?
+            .get::<_, i64>(0);
+
+        if let Some(after) = &after {
+            args.extend_from_slice(&[&after.created, &after.seq]);
+            conditions.push(format!("(created, seq) < (${}, ${})", args.len() - 1, args.len()));
+        }
+
+        let (selection, mapping) = select!(username, display_name, roles, created, lang, seq);
+        let query = format!(
+            "select {selection} from user_sessions {filter} \
+                order by created desc, seq desc \
+                limit {limit}",
+            filter = where_clause(&conditions),
+            limit = first + 1,
+        );
+        let mut sessions = db.query_mapped(&query, args, |row| Self {
+            username: mapping.username.of(&row),
+            display_name: mapping.display_name.of(&row),
+            roles: mapping.roles.of(&row),
+            created: DateTime::from_utc(mapping.created.of::<NaiveDateTime>(&row), Utc),
+            lang: mapping.lang.of(&row),
+            seq: mapping.seq.of(&row),
+        }).await?;
+
+        let has_next_page = sessions.len() > first as usize;
+        sessions.truncate(first as usize);
+
+        let end_cursor = sessions.last().map(|s| Cursor::new(SessionCursor {
+            created: s.created.naive_utc(),
+            seq: s.seq,
+        }));
+
+        Ok(SessionConnection {
+            page_info: ForwardPageInfo { has_next_page, end_cursor },
+            items: sessions,
+            total_count: total_count.try_into().expect("more than 2^31 sessions"),
+        })
+    }
+}
+
+fn check_first(first: i32) -> ApiResult<()> {
+    if !(1..=MAX_COUNT).contains(&first) {
+        return Err(invalid_input!("argument 'first' has to be between 1 and {MAX_COUNT}"));
+    }
+    Ok(())
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("where {}", conditions.join(" and "))
+    }
+}
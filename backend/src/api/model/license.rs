@@ -0,0 +1,32 @@
+//! Licenses of events and series, see `sync::license`.
+
+use crate::sync::license;
+
+
+/// The license of an event or series.
+#[derive(Debug, Clone, juniper::GraphQLObject)]
+pub(crate) struct License {
+    /// The license as stored in Opencast.
+    raw: String,
+    /// SPDX identifier (https://spdx.org/licenses/) of the license. `null`
+    /// if the license is unknown to Tobira.
+    spdx_id: Option<String>,
+    /// Human readable name of the license. Same as `raw` for unknown
+    /// licenses.
+    name: String,
+    /// Link to the text of the license, if available.
+    link: Option<String>,
+}
+
+impl License {
+    /// Creates the license from the `license` and `license_spdx` columns.
+    /// Returns `None` if no license is set.
+    pub(crate) fn from_db(raw: Option<String>, spdx_id: Option<String>) -> Option<Self> {
+        let raw = raw?;
+        let (name, link) = match &spdx_id {
+            Some(id) => (license::display_name(id).to_owned(), license::link(id)),
+            None => (raw.clone(), None),
+        };
+        Some(Self { raw, spdx_id, name, link })
+    }
+}
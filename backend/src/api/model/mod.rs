@@ -1,9 +1,16 @@
 //! This module and its children define most of the application logic of the
 //! API.
 
+pub(crate) mod acl;
+pub(crate) mod announcement;
 pub(crate) mod block;
 pub(crate) mod event;
+pub(crate) mod known_user;
+pub(crate) mod license;
+pub(crate) mod person;
+pub(crate) mod playback_position;
 pub(crate) mod realm;
 pub(crate) mod search;
 pub(crate) mod series;
+pub(crate) mod upload;
 pub(crate) mod user;
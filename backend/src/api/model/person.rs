@@ -0,0 +1,144 @@
+//! Persons, i.e. the deduplicated creators of events (see `sync::persons`).
+
+use juniper::graphql_object;
+
+use crate::{
+    api::{
+        Context,
+        Id,
+        err::{ApiResult, invalid_input},
+    },
+    db::{types::Key, util::impl_from_db},
+    prelude::*,
+};
+
+
+/// Maximum number of persons returned by `Person::search`.
+const SEARCH_LIMIT: i64 = 100;
+
+pub(crate) struct Person {
+    key: Key,
+    name: String,
+    names: Vec<String>,
+    event_count: i64,
+}
+
+impl_from_db!(
+    Person,
+    select: {
+        persons.{ id, name },
+        names: "array(select name from person_names where person = persons.id order by name)",
+        event_count: "(select count(*) from event_persons where person = persons.id)",
+    },
+    |row| {
+        Self {
+            key: row.id(),
+            name: row.name(),
+            names: row.names(),
+            event_count: row.event_count(),
+        }
+    },
+);
+
+impl Person {
+    /// Returns persons whose name contains `query` (case-insensitively),
+    /// ordered by name. Requires admin rights.
+    pub(crate) async fn search(query: Option<String>, context: &Context) -> ApiResult<Vec<Self>> {
+        let db = context.db(context.require_admin()?);
+        let pattern = query.map(|q| format!("%{}%", escape_like(q.trim())));
+        let selection = Self::select();
+        let sql = format!(
+            "select {selection} from persons \
+                where $1::text is null or exists (\
+                    select from person_names \
+                    where person = persons.id and name like $1\
+                ) \
+                order by persons.name, persons.id \
+                limit $2",
+        );
+        db.query_mapped(&sql, dbargs![&pattern, &SEARCH_LIMIT], |row| Self::from_row_start(&row))
+            .await?
+            .pipe(Ok)
+    }
+
+    /// Merges `source` into `target`: all events linked to `source` are
+    /// linked to `target` instead, and all names of `source` refer to
+    /// `target` from now on. `source` is deleted. Requires admin rights.
+    pub(crate) async fn merge(source: Id, target: Id, context: &Context) -> ApiResult<Self> {
+        let db = context.db(context.require_admin()?);
+        let source_key = source.key_for(Id::PERSON_KIND)
+            .ok_or_else(|| invalid_input!("`source` does not refer to a person"))?;
+        let target_key = target.key_for(Id::PERSON_KIND)
+            .ok_or_else(|| invalid_input!("`target` does not refer to a person"))?;
+        if source_key == target_key {
+            return Err(invalid_input!("cannot merge a person into itself"));
+        }
+
+        let existing = db
+            .query_one(
+                "select count(*) from persons where id = $1 or id = $2",
+                &[&source_key, &target_key],
+            )
+            .await?
+            .get::<_, i64>(0);
+        if existing != 2 {
+            return Err(invalid_input!("person {} or {} does not exist", source, target));
+        }
+
+        // If an event is linked to both persons, the link that comes first in
+        // the event's `creators` determines the position.
+        let events = db
+            .query_mapped(
+                "insert into event_persons (event, person, position) \
+                    select event, $2, position from event_persons where person = $1 \
+                    on conflict (event, person) do update \
+                        set position = least(event_persons.position, excluded.position) \
+                    returning event",
+                dbargs![&source_key, &target_key],
+                |row| row.get::<_, Key>(0),
+            )
+            .await?;
+        db.execute(
+            "update person_names set person = $2 where person = $1",
+            &[&source_key, &target_key],
+        ).await?;
+        db.execute("delete from persons where id = $1", &[&source_key]).await?;
+
+        // This also queues the events for reindexing.
+        db.execute("select update_creator_names($1)", &[&events]).await?;
+        info!("Merged person {source} into {target} ({} events affected)", events.len());
+
+        let selection = Self::select();
+        let row = db
+            .query_one(&format!("select {selection} from persons where id = $1"), &[&target_key])
+            .await?;
+        Ok(Self::from_row_start(&row))
+    }
+}
+
+/// Escapes the special characters of `like` patterns.
+fn escape_like(s: &str) -> String {
+    s.to_lowercase().replace('\\', r"\\").replace('%', r"\%").replace('_', r"\_")
+}
+
+#[graphql_object(Context = Context)]
+impl Person {
+    fn id(&self) -> Id {
+        Id::person(self.key)
+    }
+
+    /// The name shown for this person.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All normalized names (lowercase) that refer to this person.
+    fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Number of events this person is a creator of.
+    fn event_count(&self) -> i32 {
+        self.event_count as i32
+    }
+}
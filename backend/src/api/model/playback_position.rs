@@ -0,0 +1,237 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tokio_postgres::GenericClient;
+
+use crate::{
+    api::{
+        Context, Id, EventId,
+        err::{ApiResult, invalid_input, not_authorized},
+        model::event::AuthorizedEvent,
+    },
+    auth::{AuthContext, HasRoles},
+    db::util::select,
+    prelude::*,
+};
+
+
+/// How often buffered positions are written to the DB.
+const FLUSH_PERIOD: Duration = Duration::from_secs(10);
+
+/// Positions that differ less than this (in seconds) from the stored one are
+/// not written, unless forced.
+const MIN_CHANGE: f64 = 10.0;
+
+/// Maximum number of positions buffered between two flushes. Further updates
+/// of new user/event pairs are dropped until the next flush.
+const MAX_PENDING: usize = 10_000;
+
+/// Videos with less than this many seconds remaining are considered finished
+/// and are not returned by `User.continueWatching`.
+const FINISHED_THRESHOLD: f64 = 30.0;
+
+
+/// Buffers playback positions in memory, shared by all API requests.
+///
+/// Players report their position very often, so `savePlaybackPosition` only
+/// stores it here. Multiple updates for the same user and event are coalesced
+/// and written to the DB periodically by `flush_periodically`. Positions still
+/// buffered when Tobira stops are lost, which is fine for this use case.
+#[derive(Default)]
+pub(crate) struct PlaybackPositionBuffer(Mutex<HashMap<(String, EventId), PendingPosition>>);
+
+struct PendingPosition {
+    seconds: f64,
+    /// If `true`, the position is written even if it barely changed, e.g.
+    /// because the user paused the video.
+    force: bool,
+}
+
+impl PlaybackPositionBuffer {
+    /// Buffers the position. Returns `false` if it was dropped because too
+    /// many positions are buffered already.
+    fn add(&self, username: &str, event: EventId, seconds: f64, force: bool) -> bool {
+        let mut pending = self.0.lock().unwrap();
+        if let Some(existing) = pending.get_mut(&(username.to_owned(), event)) {
+            existing.seconds = seconds;
+            existing.force |= force;
+            return true;
+        }
+        if pending.len() >= MAX_PENDING {
+            return false;
+        }
+
+        pending.insert((username.to_owned(), event), PendingPosition { seconds, force });
+        true
+    }
+
+    /// Writes all buffered positions to the DB every `FLUSH_PERIOD`. Errors
+    /// are only logged and the affected positions are lost.
+    pub(crate) async fn flush_periodically(&self, db_pool: Pool) {
+        loop {
+            tokio::time::sleep(FLUSH_PERIOD).await;
+
+            let res = match db_pool.get().await {
+                Ok(db) => self.flush(&**db).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = res {
+                error!("Failed to store playback positions: {e:#}");
+            }
+        }
+    }
+
+    async fn flush(&self, db: &impl GenericClient) -> Result<()> {
+        let pending = std::mem::take(&mut *self.0.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let (forced, unforced) = pending.into_iter().partition::<Vec<_>, _>(|(_, p)| p.force);
+        let written = upsert(db, forced, 0.0).await? + upsert(db, unforced, MIN_CHANGE).await?;
+        debug!("Stored {written} playback positions");
+
+        Ok(())
+    }
+}
+
+/// Inserts or updates the given positions, but does not overwrite stored ones
+/// that differ by less than `min_change` seconds. Positions for events
+/// that don't exist (anymore) are ignored.
+async fn upsert(
+    db: &impl GenericClient,
+    positions: Vec<((String, EventId), PendingPosition)>,
+    min_change: f64,
+) -> Result<u64> {
+    if positions.is_empty() {
+        return Ok(0);
+    }
+
+    let mut usernames = Vec::with_capacity(positions.len());
+    let mut events = Vec::with_capacity(positions.len());
+    let mut seconds = Vec::with_capacity(positions.len());
+    for ((username, event), position) in positions {
+        usernames.push(username);
+        events.push(event);
+        seconds.push(position.seconds);
+    }
+
+    db.execute(
+        "insert into playback_positions (username, event, position) \
+            select new.username, new.event, new.position \
+            from unnest($1::text[], $2::bigint[], $3::double precision[]) \
+                as new(username, event, position) \
+            join events on events.id = new.event \
+            on conflict (username, event) do update \
+                set position = excluded.position, updated = now() \
+                where abs(playback_positions.position - excluded.position) >= $4",
+        &[&usernames, &events, &seconds, &min_change],
+    )
+        .await
+        .context("failed to upsert playback positions")
+}
+
+/// Stores the position of the current user in the given event, see the
+/// mutation with the same name.
+pub(crate) fn save(event: Id, seconds: f64, force: bool, context: &Context) -> ApiResult<bool> {
+    if !context.config.general.continue_watching {
+        return Err(invalid_input!("storing playback positions is disabled"));
+    }
+    let AuthContext::User(user) = &context.auth else {
+        return Err(not_authorized!(
+            key = "mutation.not-logged-in",
+            "only logged in users can store playback positions",
+        ));
+    };
+    let event = EventId::from_id(event)?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(invalid_input!("`seconds` has to be a non-negative number"));
+    }
+
+    Ok(context.playback_positions.add(&user.username, event, seconds, force))
+}
+
+
+/// Where a user stopped watching an event.
+#[derive(juniper::GraphQLObject)]
+#[graphql(Context = Context)]
+pub(crate) struct PlaybackPosition {
+    event: AuthorizedEvent,
+    /// Position from the start of the video in seconds.
+    seconds: f64,
+    updated: DateTime<Utc>,
+}
+
+impl PlaybackPosition {
+    /// Returns the stored positions of the given user in unfinished events
+    /// they can still read, most recently updated first.
+    pub(crate) async fn load_for_user(
+        username: &str,
+        first: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<Self>> {
+        if !context.config.general.continue_watching {
+            return Ok(vec![]);
+        }
+        if !(1..=100).contains(&first) {
+            return Err(invalid_input!("`first` has to be between 1 and 100"));
+        }
+
+        let (selection, mapping) = select!(
+            event: AuthorizedEvent,
+            position: "playback_positions.position",
+            updated: "playback_positions.updated",
+        );
+        let query = format!(
+            "select {selection} \
+                from playback_positions \
+                join events on events.id = playback_positions.event \
+                where playback_positions.username = $1 \
+                    and (events.read_roles || 'ROLE_ADMIN'::text) && $2 \
+                    and (events.duration is null \
+                        or playback_positions.position \
+                            < events.duration / 1000.0 - $3::double precision) \
+                order by playback_positions.updated desc \
+                limit $4",
+        );
+        let args = dbargs![
+            &username,
+            &context.auth.roles_vec(),
+            &FINISHED_THRESHOLD,
+            &i64::from(first),
+        ];
+        context.db
+            .query_mapped(&query, args, |row| Self {
+                event: AuthorizedEvent::from_row(&row, mapping.event),
+                seconds: mapping.position.of(&row),
+                updated: mapping.updated.of(&row),
+            })
+            .await?
+            .pipe(Ok)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::EventId, db::types::Key};
+    use super::PlaybackPositionBuffer;
+
+    #[test]
+    fn coalesces_updates() {
+        const EVENT: EventId = EventId(Key(1));
+        let buffer = PlaybackPositionBuffer::default();
+        assert!(buffer.add("peter", EVENT, 5.0, false));
+        assert!(buffer.add("peter", EVENT, 7.0, true));
+        assert!(buffer.add("peter", EVENT, 9.0, false));
+        assert!(buffer.add("sabine", EVENT, 3.0, false));
+
+        let pending = buffer.0.lock().unwrap();
+        assert_eq!(pending.len(), 2);
+        let peter = &pending[&("peter".to_owned(), EVENT)];
+        assert_eq!(peter.seconds, 9.0);
+        assert!(peter.force);
+        assert!(!pending[&("sabine".to_owned(), EVENT)].force);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashMap, future::Future, sync::Mutex, time::{Duration, Instant}};
+use prometheus_client::metrics::{counter::Counter, family::Family};
+
+use crate::{
+    api::{RealmId, err::ApiResult},
+    metrics::CacheLookup,
+};
+use super::Realm;
+
+
+/// How long entries are cached at most. Realm mutations invalidate the cache,
+/// but only on the Tobira node that handled them. Changes made by other nodes
+/// or by the sync (e.g. a series title that is used as realm name) are
+/// visible after this duration at the latest. `db cache-invalidate` clears
+/// the cache of all nodes immediately.
+const CACHE_DURATION: Duration = Duration::from_secs(10);
+
+/// Maximum number of entries per map. If exceeded, expired entries are
+/// removed; if that does not help, the whole map is cleared.
+const MAX_ENTRIES: usize = 1000;
+
+
+/// In-process cache for the realm data that the navigation needs (the realm's
+/// parent and children), shared by all API requests. The navigation is shown
+/// on almost every page but rarely changes, so this saves lots of DB queries.
+///
+/// Realm data does not depend on the user, so all users share the cache.
+pub(crate) struct NavigationCache {
+    enabled: bool,
+    realms: Mutex<HashMap<RealmId, CacheEntry<Option<Realm>>>>,
+    children: Mutex<HashMap<RealmId, CacheEntry<Vec<Realm>>>>,
+    lookups: Family<CacheLookup, Counter>,
+}
+
+struct CacheEntry<T> {
+    loaded_at: Instant,
+    value: T,
+}
+
+impl NavigationCache {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            realms: Mutex::new(HashMap::new()),
+            children: Mutex::new(HashMap::new()),
+            lookups: Family::default(),
+        }
+    }
+
+    /// Returns the realm with the given key, using `load` if it's not cached.
+    pub(super) async fn realm(
+        &self,
+        key: RealmId,
+        load: impl Future<Output = ApiResult<Option<Realm>>>,
+    ) -> ApiResult<Option<Realm>> {
+        self.get_or_load(&self.realms, key, load).await
+    }
+
+    /// Returns the children of the realm with the given key, using `load` if
+    /// they are not cached.
+    pub(super) async fn children(
+        &self,
+        parent: RealmId,
+        load: impl Future<Output = ApiResult<Vec<Realm>>>,
+    ) -> ApiResult<Vec<Realm>> {
+        self.get_or_load(&self.children, parent, load).await
+    }
+
+    /// Removes all entries. Has to be called by all mutations that change
+    /// realm data. Only affects this Tobira node.
+    pub(crate) fn invalidate(&self) {
+        self.realms.lock().unwrap().clear();
+        self.children.lock().unwrap().clear();
+    }
+
+    /// Number of cache hits and misses, for the metrics.
+    pub(crate) fn lookups(&self) -> Family<CacheLookup, Counter> {
+        self.lookups.clone()
+    }
+
+    async fn get_or_load<T: Clone>(
+        &self,
+        map: &Mutex<HashMap<RealmId, CacheEntry<T>>>,
+        key: RealmId,
+        load: impl Future<Output = ApiResult<T>>,
+    ) -> ApiResult<T> {
+        if !self.enabled {
+            return load.await;
+        }
+
+        if let Some(entry) = map.lock().unwrap().get(&key) {
+            if entry.loaded_at.elapsed() < CACHE_DURATION {
+                self.lookups.get_or_create(&CacheLookup::Hit).inc();
+                return Ok(entry.value.clone());
+            }
+        }
+
+        self.lookups.get_or_create(&CacheLookup::Miss).inc();
+        let loaded_at = Instant::now();
+        let value = load.await?;
+
+        let mut map = map.lock().unwrap();
+        if map.len() >= MAX_ENTRIES {
+            map.retain(|_, entry| entry.loaded_at.elapsed() < CACHE_DURATION);
+            if map.len() >= MAX_ENTRIES {
+                map.clear();
+            }
+        }
+        map.insert(key, CacheEntry { loaded_at, value: value.clone() });
+        Ok(value)
+    }
+}
@@ -2,15 +2,16 @@ use juniper::{graphql_object, GraphQLEnum, GraphQLObject, GraphQLUnion, graphql_
 use postgres_types::{FromSql, ToSql};
 
 use crate::{
-    api::{Context, Id, err::ApiResult, Node, NodeValue},
-    db::{types::Key, util::{select, impl_from_db}},
+    api::{Context, Id, BlockId, EventId, RealmId, SeriesId, err::ApiResult, Node, NodeValue},
+    db::util::{select, impl_from_db},
     prelude::*,
 };
 use super::block::{Block, BlockValue, SeriesBlock, VideoBlock};
 
-
+mod cache;
 mod mutations;
 
+pub(crate) use cache::NavigationCache;
 pub(crate) use mutations::{
     ChildIndex, NewRealm, RemovedRealm, UpdateRealm, UpdatedRealmName, RealmSpecifier,
 };
@@ -43,7 +44,7 @@ pub(crate) struct PlainRealmName {
 
 #[derive(Debug)]
 pub(crate) struct RealmNameFromBlock {
-    block: Key,
+    block: BlockId,
 }
 
 /// A realm name that is derived from a block of that realm.
@@ -89,12 +90,13 @@ impl Block for RealmNameSourceBlockValue {
 }
 
 
+#[derive(Clone)]
 pub(crate) struct Realm {
-    pub(crate) key: Key,
-    parent_key: Option<Key>,
+    pub(crate) key: RealmId,
+    parent_key: Option<RealmId>,
     plain_name: Option<String>,
     resolved_name: Option<String>,
-    name_from_block: Option<Key>,
+    name_from_block: Option<BlockId>,
     path_segment: String,
     full_path: String,
     index: i32,
@@ -132,13 +134,20 @@ impl_from_db!(
 
 impl Realm {
     pub(crate) async fn root(context: &Context) -> ApiResult<Self> {
+        context.navigation_cache
+            .realm(RealmId::ROOT, async { Self::load_root(context).await.map(Some) })
+            .await
+            .map(|root| root.expect("root realm always exists"))
+    }
+
+    async fn load_root(context: &Context) -> ApiResult<Self> {
         let (selection, mapping) = select!(child_order);
         let row = context.db
             .query_one(&format!("select {selection} from realms where id = 0"), &[])
             .await?;
 
         Ok(Self {
-            key: Key(0),
+            key: RealmId::ROOT,
             parent_key: None,
             plain_name: None,
             resolved_name: None,
@@ -151,15 +160,14 @@ impl Realm {
     }
 
     pub(crate) async fn load_by_id(id: Id, context: &Context) -> ApiResult<Option<Self>> {
-        if let Some(key) = id.key_for(Id::REALM_KIND) {
-            Self::load_by_key(key, context).await
-        } else {
-            Ok(None)
+        match RealmId::from_node_id(id)? {
+            Some(key) => Self::load_by_key(key, context).await,
+            None => Ok(None),
         }
     }
 
-    pub(crate) async fn load_by_key(key: Key, context: &Context) -> ApiResult<Option<Self>> {
-        if key.0 == 0 {
+    pub(crate) async fn load_by_key(key: RealmId, context: &Context) -> ApiResult<Option<Self>> {
+        if key == RealmId::ROOT {
             return Ok(Some(Self::root(context).await?));
         }
 
@@ -202,7 +210,7 @@ impl Realm {
 
 impl Node for Realm {
     fn id(&self) -> Id {
-        Id::realm(self.key)
+        self.key.into()
     }
 }
 
@@ -235,7 +243,7 @@ impl Realm {
     }
 
     fn is_root(&self) -> bool {
-        self.key.0 == 0
+        self.key == RealmId::ROOT
     }
 
     fn index(&self) -> i32 {
@@ -258,13 +266,16 @@ impl Realm {
     /// non-root realms, the path always starts with `/` and never has a
     /// trailing `/`.
     fn path(&self) -> &str {
-        if self.key.0 == 0 { "/" } else { &self.full_path }
+        if self.key == RealmId::ROOT { "/" } else { &self.full_path }
     }
 
     /// Returns the immediate parent of this realm.
     async fn parent(&self, context: &Context) -> ApiResult<Option<Realm>> {
         match self.parent_key {
-            Some(parent_key) => Realm::load_by_key(parent_key, context).await,
+            Some(RealmId::ROOT) => Realm::root(context).await.map(Some),
+            Some(parent_key) => context.navigation_cache
+                .realm(parent_key, Realm::load_by_key(parent_key, context))
+                .await,
             None => Ok(None)
         }
     }
@@ -291,22 +302,25 @@ impl Realm {
     /// different from `BY_INDEX`, the frontend is supposed to sort the
     /// children.
     async fn children(&self, context: &Context) -> ApiResult<Vec<Self>> {
-        let selection = Self::select();
-        let query = format!(
-            "select {selection} \
-                from realms \
-                {REALM_JOINS} \
-                where realms.parent = $1 \
-                order by index",
-        );
-        context.db
-            .query_mapped(
-                &query,
-                &[&self.key],
-                |row| Self::from_row_start(&row),
-            )
-            .await?
-            .pipe(Ok)
+        let load = async {
+            let selection = Self::select();
+            let query = format!(
+                "select {selection} \
+                    from realms \
+                    {REALM_JOINS} \
+                    where realms.parent = $1 \
+                    order by index",
+            );
+            context.db
+                .query_mapped(
+                    &query,
+                    &[&self.key],
+                    |row| Self::from_row_start(&row),
+                )
+                .await?
+                .pipe(Ok)
+        };
+        context.navigation_cache.children(self.key, load).await
     }
 
     /// Returns the (content) blocks of this realm.
@@ -347,7 +361,7 @@ impl Realm {
     ///   event's series.
     /// - Otherwise, `false` is returned.
     async fn references(&self, id: Id, context: &Context) -> ApiResult<bool> {
-        if let Some(event_key) = id.key_for(Id::EVENT_KIND) {
+        if let Ok(event_key) = EventId::from_id(id) {
             let query = "select exists(\
                 select 1 \
                 from blocks \
@@ -360,7 +374,7 @@ impl Realm {
                 .await?
                 .get::<_, bool>(0)
                 .pipe(Ok)
-        } else if let Some(series_key) = id.key_for(Id::SERIES_KIND) {
+        } else if let Ok(series_key) = SeriesId::from_id(id) {
             let query = "select exists(\
                 select 1 from blocks where realm = $1 and series = $2\
             )";
@@ -1,8 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
-    api::{Context, Id, err::{ApiResult, invalid_input}},
-    db::types::Key,
+    api::{Context, Id, BlockId, RealmId, err::{ApiResult, invalid_input}},
     prelude::*,
 };
 use super::{Realm, RealmOrder};
@@ -16,7 +15,7 @@ impl Realm {
         let parent_key = id_to_key(realm.parent, "`parent`")?;
 
         // Check if the path is a reserved one.
-        let is_top_level_realm = parent_key.0 == 0;
+        let is_top_level_realm = parent_key == RealmId::ROOT;
         let path_is_reserved = context.config.general.reserved_paths()
             .any(|r| realm.path_segment == r);
         if is_top_level_realm && path_is_reserved {
@@ -24,7 +23,7 @@ impl Realm {
         }
         // TODO: validate input
 
-        let key: Key = db
+        let key: RealmId = db
             .query_one(
                 "insert into realms (parent, name, path_segment) \
                     values ($1, $2, $3) \
@@ -34,6 +33,7 @@ impl Realm {
             .await?
             .get(0);
 
+        context.navigation_cache.invalidate();
         Self::load_by_key(key, context).await.map(Option::unwrap)
     }
 
@@ -80,7 +80,7 @@ impl Realm {
 
 
             // Retrieve the current children of the given realm
-            let current_children: Vec<(_, i32)> = db
+            let current_children: Vec<(RealmId, i32)> = db
                 .query_raw("select id, index from realms where parent = $1", [parent_key])
                 .await?
                 .map_ok(|row| (row.get(0), row.get(1)))
@@ -97,7 +97,7 @@ impl Realm {
                 if !child_indices.contains_key(key) {
                     return Err(invalid_input!(
                         "child {} of realm {} is missing in children given to `setChildOrder`",
-                        Id::realm(*key),
+                        Id::from(*key),
                         parent,
                     ));
                 }
@@ -128,6 +128,7 @@ impl Realm {
             &[&child_order, &parent_key],
         ).await?;
         debug!("Set 'child_order' of realm {} to {:?}", parent, child_order);
+        context.navigation_cache.invalidate();
 
 
         // Load the updated realm. If the realm does not exist, we either
@@ -148,8 +149,8 @@ impl Realm {
             return Err(invalid_input!("exactly one of name.block and name.plain has to be set"));
         }
         let block = name.block
-            .map(|id| id.key_for(Id::BLOCK_KIND)
-                .ok_or_else(|| invalid_input!("name.block does not refer to a block")))
+            .map(|id| BlockId::from_id(id)
+                .map_err(|e| invalid_input!("name.block does not refer to a block: {e}")))
             .transpose()?;
 
         let stmt = "
@@ -164,6 +165,7 @@ impl Realm {
             return Err(invalid_input!("`id` does not refer to an existing realm"));
         }
 
+        context.navigation_cache.invalidate();
         Self::load_by_key(key, context).await.map(Option::unwrap)
     }
 
@@ -181,7 +183,7 @@ impl Realm {
                 let err = invalid_input!("path is reserved and cannot be used");
                 match parent_key {
                     // If the parent is changed to be the root realm, it's an error.
-                    Some(Key(0)) => return Err(err),
+                    Some(RealmId::ROOT) => return Err(err),
                     // If the parent is changed to something else than the root realm, it's fine.
                     Some(_) => {}
                     // If the parent is not changed, we unfortunately need to
@@ -190,9 +192,9 @@ impl Realm {
                         let real_parent = db
                             .query_one("select parent from realms where id = $1", &[&key])
                             .await?
-                            .get::<_, Key>(0);
+                            .get::<_, RealmId>(0);
 
-                        if real_parent.0 == 0 {
+                        if real_parent == RealmId::ROOT {
                             return Err(err);
                         }
                     }
@@ -214,6 +216,7 @@ impl Realm {
             return Err(invalid_input!("`id` does not refer to an existing realm"));
         }
 
+        context.navigation_cache.invalidate();
         Self::load_by_key(key, context).await.map(Option::unwrap)
     }
 
@@ -221,7 +224,7 @@ impl Realm {
         let db = context.db(context.require_moderator()?);
 
         let key = id_to_key(id, "`id`")?;
-        if key.0 == 0 {
+        if key == RealmId::ROOT {
             return Err(invalid_input!("Cannot remove the root realm"));
         }
 
@@ -229,6 +232,7 @@ impl Realm {
             .ok_or_else(|| invalid_input!("`id` does not refer to an existing realm"))?;
 
         db.execute("delete from realms where id = $1", &[&key]).await?;
+        context.navigation_cache.invalidate();
 
         // We checked above that `realm` is not the root realm, so we can unwrap.
         let parent = Self::load_by_key(realm.parent_key.expect("missing parent"), context)
@@ -240,9 +244,8 @@ impl Realm {
 }
 
 /// Makes sure the ID refers to a realm and returns its key.
-fn id_to_key(id: Id, name: &str) -> ApiResult<Key> {
-    id.key_for(Id::REALM_KIND)
-        .ok_or_else(|| invalid_input!("{} does not refer to a realm", name))
+fn id_to_key(id: Id, name: &str) -> ApiResult<RealmId> {
+    RealmId::from_id(id).map_err(|e| invalid_input!("{} does not refer to a realm: {}", name, e))
 }
 
 #[derive(juniper::GraphQLInputObject)]
@@ -61,4 +61,15 @@ impl search::Event {
     fn host_realms(&self) -> &[search::Realm] {
         &self.host_realms
     }
+
+    /// SPDX identifier of the license, `null` if unknown or not set.
+    fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Base URL of the Tobira instance this event was imported from, `null`
+    /// for local events.
+    fn external_origin(&self) -> Option<&str> {
+        self.external_origin.as_deref()
+    }
 }
@@ -9,8 +9,8 @@ use meilisearch_sdk::{
 
 use crate::{
     api::{
-        Context, NodeValue,
-        err::ApiResult,
+        Context, Id, NodeValue,
+        err::{ApiResult, invalid_input, not_authorized},
         util::impl_object_with_dummy_field,
     },
     auth::HasRoles,
@@ -98,6 +98,7 @@ macro_rules! handle_search_result {
 /// Main entry point for the main search (including all items).
 pub(crate) async fn perform(
     user_query: &str,
+    license: Option<&str>,
     context: &Context,
 ) -> ApiResult<SearchOutcome> {
     if user_query.is_empty() {
@@ -106,7 +107,16 @@ pub(crate) async fn perform(
 
     // Prepare the event search
     let mut filter = "listed = true".to_string();
-    let event_query = event_search_query(user_query, &mut filter, context);
+    if let Some(license) = license {
+        // This is interpolated into the filter expression, so we only allow
+        // characters that can appear in SPDX identifiers.
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+');
+        if license.is_empty() || !license.chars().all(valid) {
+            return Err(invalid_input!("'{}' is not a valid SPDX license identifier", license));
+        }
+        filter += &format!(" AND license = '{license}'");
+    }
+    let event_query = event_search_query(user_query, &mut filter, "read_roles", context);
 
 
     // Prepare the realm search
@@ -240,7 +250,7 @@ pub(crate) async fn all_events(user_query: &str, context: &Context) -> ApiResult
     }
 
     let mut filter = String::new();
-    let mut event_query = event_search_query(user_query, &mut filter, context);
+    let mut event_query = event_search_query(user_query, &mut filter, "read_roles", context);
     event_query.with_limit(50);
 
     let res = event_query.execute::<search::Event>().await;
@@ -251,11 +261,51 @@ pub(crate) async fn all_events(user_query: &str, context: &Context) -> ApiResult
 }
 
 
+/// Searches through all events the user has write access to, e.g. to choose
+/// one for a video block. In contrast to the other searches, an empty query
+/// returns all of these events. Requires moderator rights.
+pub(crate) async fn my_editable_events(
+    user_query: &str,
+    first: i32,
+    offset: i32,
+    context: &Context,
+) -> ApiResult<EventSearchOutcome> {
+    context.require_moderator()?;
+    let (limit, offset) = picker_pagination(first, offset)?;
+
+    let mut filter = String::new();
+    let mut event_query = event_search_query(user_query, &mut filter, "write_roles", context);
+    event_query.with_limit(limit);
+    event_query.with_offset(offset);
+
+    let res = event_query.execute::<search::Event>().await;
+    let results = handle_search_result!(res, EventSearchOutcome);
+    let items = results.hits.into_iter().map(|h| h.result).collect();
+
+    Ok(EventSearchOutcome::Results(SearchResults { items }))
+}
+
+/// Checks the pagination arguments of the searches used by pickers and
+/// returns them as limit and offset.
+pub(crate) fn picker_pagination(first: i32, offset: i32) -> ApiResult<(usize, usize)> {
+    if !(1..=50).contains(&first) {
+        return Err(invalid_input!("`first` has to be between 1 and 50"));
+    }
+    let offset = usize::try_from(offset)
+        .map_err(|_| invalid_input!("`offset` must not be negative"))?;
+
+    Ok((first as usize, offset))
+}
+
+
 /// Constructs the appropriate `Query` to search for events. Due to a bad API
 /// design of Meili, you have to pass an empty `String` as second parameter.
+/// `acl_field` is the ACL (`read_roles` or `write_roles`) that has to contain
+/// one of the user's roles.
 fn event_search_query<'a>(
     user_query: &'a str,
     filter: &'a mut String,
+    acl_field: &str,
     context: &'a Context,
 ) -> Query<'a> {
     use std::fmt::Write;
@@ -265,7 +315,7 @@ fn event_search_query<'a>(
     if !context.auth.is_admin() {
         let acl_filter = context.auth.roles()
             .iter()
-            .map(|role| format!("read_roles = '{}'", hex::encode(role)))
+            .map(|role| format!("{acl_field} = '{}'", hex::encode(role)))
             .collect::<Vec<_>>()
             .join(" OR ");
         write!(
@@ -285,3 +335,31 @@ fn event_search_query<'a>(
     query
 }
 
+
+/// Queues the search index documents of the event, series or realm with the
+/// given ID to be rebuilt. The API does not write to the search index itself,
+/// that is done by the search index worker, which also deletes documents of
+/// items that don't exist in the DB anymore. Returns the number of newly
+/// queued documents.
+pub(crate) async fn queue_for_rebuild(id: Id, context: &Context) -> ApiResult<i32> {
+    if !context.auth.is_admin() {
+        return Err(not_authorized!("only admins can rebuild search index documents"));
+    }
+
+    let (query, key) = if let Some(key) = id.key_for(Id::EVENT_KIND) {
+        ("insert into search_index_queue (item_id, kind) values ($1, 'event')", key)
+    } else if let Some(key) = id.key_for(Id::SERIES_KIND) {
+        // Series have no documents of their own, but they are part of the
+        // documents of all their events.
+        ("insert into search_index_queue (item_id, kind) \
+            select id, 'event' from events where series = $1", key)
+    } else if let Some(key) = id.key_for(Id::REALM_KIND) {
+        ("insert into search_index_queue (item_id, kind) values ($1, 'realm')", key)
+    } else {
+        return Err(invalid_input!("`id` does not refer to an event, series or realm"));
+    };
+
+    let query = format!("{query} on conflict do nothing");
+    let queued = context.db.execute(&query, &[&key]).await?;
+    Ok(queued as i32)
+}
@@ -1,47 +1,62 @@
-use juniper::{graphql_object, GraphQLObject, GraphQLInputObject};
+use chrono::{DateTime, Utc};
+use juniper::{graphql_object, GraphQLEnum, GraphQLObject, GraphQLInputObject};
 use postgres_types::ToSql;
 
 use crate::{
     api::{
         Context,
         err::ApiResult,
-        Id,
+        Id, SeriesId,
         model::{
+            license::License,
             realm::{Realm, REALM_JOINS},
-            event::{AuthorizedEvent, EventSortOrder}
+            event::{AuthorizedEvent, EventSortOrder},
+            search::picker_pagination,
         },
         Node,
     },
-    db::{types::{SeriesState as State, Key}, util::impl_from_db},
+    auth::HasRoles,
+    db::{types::SeriesState as State, util::impl_from_db},
     prelude::*,
 };
 
 
 pub(crate) struct Series {
-    pub(crate) key: Key,
+    pub(crate) key: SeriesId,
     opencast_id: String,
     synced_data: Option<SyncedSeriesData>,
     title: String,
+    event_count: i32,
+    last_event_update: Option<DateTime<Utc>>,
+    external_origin: Option<String>,
 }
 
 #[derive(GraphQLObject)]
 struct SyncedSeriesData {
     description: Option<String>,
+    license: Option<License>,
 }
 
 impl_from_db!(
     Series,
     select: {
-        series.{ id, opencast_id, state, title, description },
+        series.{
+            id, opencast_id, state, title, description, license, license_spdx,
+            event_count, last_event_update, external_origin,
+        },
     },
     |row| {
         Series {
             key: row.id(),
             opencast_id: row.opencast_id(),
             title: row.title(),
+            event_count: row.event_count(),
+            last_event_update: row.last_event_update(),
+            external_origin: row.external_origin(),
             synced_data: (State::Ready == row.state()).then(
                 || SyncedSeriesData {
                     description: row.description(),
+                    license: License::from_db(row.license(), row.license_spdx()),
                 },
             ),
         }
@@ -49,24 +64,64 @@ impl_from_db!(
 );
 
 impl Series {
-    pub(crate) async fn load_all(context: &Context) -> ApiResult<Vec<Self>> {
+    pub(crate) async fn load_all(
+        order: SeriesSortOrder,
+        context: &Context,
+    ) -> ApiResult<Vec<Self>> {
         let selection = Self::select();
-        let query = format!("select {selection} from series order by title");
+        let order = match order {
+            SeriesSortOrder::Title => "title",
+            SeriesSortOrder::RecentlyUpdated => "last_event_update desc nulls last, id",
+        };
+        let query = format!("select {selection} from series order by {order}");
         context.db(context.require_moderator()?)
             .query_mapped(&query, dbargs![], |row| Self::from_row_start(&row))
             .await?
             .pipe(Ok)
     }
 
+    /// Returns series the user has write access to whose title contains
+    /// `user_query` (case-insensitive), ordered by title. An empty query
+    /// matches all series. Series imported from another Tobira are never
+    /// returned. Series are not in the search index, so this uses the DB.
+    /// Requires moderator rights.
+    pub(crate) async fn search_writable(
+        user_query: &str,
+        first: i32,
+        offset: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<Self>> {
+        let db = context.db(context.require_moderator()?);
+        let (limit, offset) = picker_pagination(first, offset)?;
+
+        let selection = Self::select();
+        let query = format!(
+            "select {selection} from series \
+                where (write_roles || 'ROLE_ADMIN'::text) && $1 \
+                and external_origin is null \
+                and strpos(lower(title), lower($2)) > 0 \
+                order by title, id \
+                limit $3 offset $4",
+        );
+        let args = dbargs![
+            &context.auth.roles_vec(),
+            &user_query,
+            &(limit as i64),
+            &(offset as i64),
+        ];
+        db.query_mapped(&query, args, |row| Self::from_row_start(&row))
+            .await?
+            .pipe(Ok)
+    }
+
     pub(crate) async fn load_by_id(id: Id, context: &Context) -> ApiResult<Option<Self>> {
-        if let Some(key) = id.key_for(Id::SERIES_KIND) {
-            Self::load_by_key(key, context).await
-        } else {
-            Ok(None)
+        match SeriesId::from_node_id(id)? {
+            Some(key) => Self::load_by_key(key, context).await,
+            None => Ok(None),
         }
     }
 
-    pub(crate) async fn load_by_key(key: Key, context: &Context) -> ApiResult<Option<Self>> {
+    pub(crate) async fn load_by_key(key: SeriesId, context: &Context) -> ApiResult<Option<Self>> {
         Self::load_by_any_id("id", &key, context).await
     }
 
@@ -122,6 +177,24 @@ impl Series {
         &self.synced_data
     }
 
+    /// Number of events in this series, including those the current user
+    /// cannot see.
+    fn event_count(&self) -> i32 {
+        self.event_count
+    }
+
+    /// When the most recently updated event of this series was last updated.
+    /// `null` if the series has no (synced) events.
+    fn last_event_update(&self) -> Option<DateTime<Utc>> {
+        self.last_event_update
+    }
+
+    /// Base URL of the Tobira instance this series was imported from, `null`
+    /// for local series. Imported series are read-only.
+    fn external_origin(&self) -> Option<&str> {
+        self.external_origin.as_deref()
+    }
+
     async fn host_realms(&self, context: &Context) -> ApiResult<Vec<Realm>> {
         let selection = Realm::select();
         let query = format!("\
@@ -136,25 +209,34 @@ impl Series {
                 and series = $1 \
             ) \
         ");
-        let id = self.id().key_for(Id::SERIES_KIND).unwrap();
-        context.db.query_mapped(&query, dbargs![&id], |row| Realm::from_row_start(&row))
+        context.db.query_mapped(&query, dbargs![&self.key], |row| Realm::from_row_start(&row))
             .await?
             .pipe(Ok)
     }
 
     #[graphql(arguments(order(default = Default::default())))]
     async fn events(&self, order: EventSortOrder, context: &Context) -> ApiResult<Vec<AuthorizedEvent>> {
-        AuthorizedEvent::load_for_series(self.key, order, context).await
+        AuthorizedEvent::load_for_series(self.key, order, None, context).await
     }
 }
 
 impl Node for Series {
     fn id(&self) -> Id {
-        Id::series(self.key)
+        self.key.into()
     }
 }
 
 
+/// Sort order for lists of series.
+#[derive(Debug, Clone, Copy, Default, GraphQLEnum)]
+pub(crate) enum SeriesSortOrder {
+    /// Alphabetically by title.
+    #[default]
+    Title,
+    /// Series with the most recently updated events first.
+    RecentlyUpdated,
+}
+
 #[derive(GraphQLInputObject)]
 pub(crate) struct NewSeries {
     opencast_id: String,
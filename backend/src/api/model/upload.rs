@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use juniper::graphql_object;
+
+use crate::{
+    api::{
+        Context,
+        err::{ApiResult, invalid_input, not_authorized},
+        model::event::{AuthorizedEvent, Event},
+    },
+    auth::AuthContext,
+    db::{types::UploadState, util::impl_from_db},
+    prelude::*,
+};
+
+
+/// A video uploaded via Tobira's uploader, tracked until it is synced.
+pub(crate) struct UploadJob {
+    opencast_id: String,
+    title: String,
+    state: UploadState,
+    error_message: Option<String>,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+impl_from_db!(
+    UploadJob,
+    select: {
+        upload_jobs.{ opencast_id, title, state, error_message, created, updated },
+    },
+    |row| {
+        Self {
+            opencast_id: row.opencast_id(),
+            title: row.title(),
+            state: row.state(),
+            error_message: row.error_message(),
+            created: row.created(),
+            updated: row.updated(),
+        }
+    },
+);
+
+impl UploadJob {
+    /// Loads all upload jobs of the given user, newest first.
+    pub(crate) async fn load_for_user(username: &str, context: &Context) -> ApiResult<Vec<Self>> {
+        let selection = Self::select();
+        let query = format!(
+            "select {selection} from upload_jobs where uploader = $1 order by created desc",
+        );
+        context.db
+            .query_mapped(&query, dbargs![&username], |row| Self::from_row_start(&row))
+            .await?
+            .pipe(Ok)
+    }
+
+    /// Records that the current user just ingested the media package with
+    /// the given Opencast ID.
+    pub(crate) async fn register(
+        opencast_id: String,
+        title: String,
+        context: &Context,
+    ) -> ApiResult<Self> {
+        let user = match &context.auth {
+            AuthContext::User(user) => user,
+            _ => return Err(not_authorized!(
+                key = "upload.not-authorized",
+                "only logged in users can register uploads",
+            )),
+        };
+        let db = context.db(user.required_upload_permission(&context.config.auth).ok_or_else(
+            || not_authorized!(
+                key = "upload.not-authorized",
+                "user '{}' is not allowed to upload videos",
+                user.username,
+            ),
+        )?);
+
+        // The ID is later used in requests to Opencast, so we are strict here.
+        let valid_id = !opencast_id.is_empty() && opencast_id.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(invalid_input!("'{}' is not a valid Opencast ID", opencast_id));
+        }
+
+        // New uploads are not synced yet, so only the uploader knows their ID.
+        // But IDs of synced events are public, so to prevent users from
+        // claiming events of others, those require write access.
+        let write_roles = db
+            .query_opt("select write_roles from events where opencast_id = $1", &[&opencast_id])
+            .await?
+            .map(|row| row.get::<_, Vec<String>>(0));
+        if write_roles.is_some_and(|roles| !context.auth.overlaps_roles(&roles)) {
+            return Err(not_authorized!(
+                key = "upload.not-authorized",
+                "user '{}' is not allowed to write event '{}'",
+                user.username,
+                opencast_id,
+            ));
+        }
+
+        let selection = Self::select();
+        let query = format!(
+            "insert into upload_jobs (opencast_id, uploader, title) \
+                values ($1, $2, $3) \
+                on conflict (opencast_id) do nothing \
+                returning {selection}",
+        );
+        db.query_opt(&query, &[&opencast_id, &user.username, &title])
+            .await?
+            .map(|row| Self::from_row_start(&row))
+            .ok_or_else(|| invalid_input!("upload '{}' is already registered", opencast_id))
+    }
+}
+
+#[graphql_object(Context = Context)]
+impl UploadJob {
+    /// The Opencast ID of the uploaded media package.
+    fn opencast_id(&self) -> &str {
+        &self.opencast_id
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn state(&self) -> UploadState {
+        self.state
+    }
+
+    /// Describes what went wrong. Only set if `state` is `FAILED`.
+    fn error_message(&self) -> Option<&str> {
+        self.error_message.as_deref()
+    }
+
+    fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    fn updated(&self) -> DateTime<Utc> {
+        self.updated
+    }
+
+    /// The synced event. Only set if `state` is `READY`.
+    async fn event(&self, context: &Context) -> ApiResult<Option<Event>> {
+        if self.state != UploadState::Ready {
+            return Ok(None);
+        }
+        AuthorizedEvent::load_by_opencast_id(self.opencast_id.clone(), context).await
+    }
+}
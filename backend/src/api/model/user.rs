@@ -2,8 +2,12 @@ use crate::{
     api::{
         Context,
         common::Cursor,
-        err::ApiResult,
-        model::event::{AuthorizedEvent, EventConnection, EventSortOrder},
+        err::{ApiResult, invalid_input},
+        model::{
+            event::{AuthorizedEvent, EventConnection, EventSortOrder},
+            playback_position::PlaybackPosition,
+            upload::UploadJob,
+        },
     },
     auth::User,
     prelude::*,
@@ -57,4 +61,41 @@ impl User {
     ) -> ApiResult<EventConnection> {
         AuthorizedEvent::load_writable_for_user(context, order, first, after, last, before).await
     }
+
+    /// Returns the videos the user started but did not finish watching, with
+    /// their playback position, most recently watched first. Always empty if
+    /// `general.continue_watching` is disabled. `first` has to be between 1
+    /// and 100.
+    async fn continue_watching(
+        &self,
+        first: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<PlaybackPosition>> {
+        PlaybackPosition::load_for_user(&self.username, first, context).await
+    }
+
+    /// Returns all videos the user uploaded via Tobira that are still being
+    /// processed, failed or were synced recently. Newest first.
+    async fn my_uploads(&self, context: &Context) -> ApiResult<Vec<UploadJob>> {
+        UploadJob::load_for_user(&self.username, context).await
+    }
+}
+
+/// Stores the language explicitly chosen by the user in the current session,
+/// so that the server uses it as well (e.g. for the HTML shell). Returns
+/// `false` if there is no session to store that in.
+pub(crate) async fn set_language(lang: String, context: &Context) -> ApiResult<bool> {
+    if !context.config.general.languages.contains(&lang) {
+        return Err(invalid_input!("language '{}' is not enabled", lang));
+    }
+    let session_id = match &context.session_id {
+        None => return Ok(false),
+        Some(session_id) => session_id,
+    };
+
+    context.db
+        .execute("update user_sessions set lang = $1 where id = $2", &[&lang, session_id])
+        .await?;
+
+    Ok(true)
 }
@@ -7,7 +7,14 @@ use super::{
     id::Id,
     Node,
     model::{
+        announcement::{Announcement, NewAnnouncement},
+        event::AuthorizedEvent,
+        person::Person,
+        playback_position,
         series::{Series, NewSeries},
+        upload::UploadJob,
+        user,
+        search,
         realm::{
             ChildIndex,
             NewRealm,
@@ -24,11 +31,14 @@ use super::{
             NewTextBlock,
             NewSeriesBlock,
             NewVideoBlock,
+            NewTocBlock,
             UpdateTitleBlock,
             UpdateTextBlock,
             UpdateSeriesBlock,
             UpdateVideoBlock,
+            UpdateTocBlock,
             RemovedBlock,
+            VideoListLayout,
             VideoListOrder,
         },
     },
@@ -130,6 +140,18 @@ impl Mutation {
         BlockValue::add_video(realm, index, block, context).await
     }
 
+    /// Adds a table of contents block to a realm.
+    ///
+    /// See `addTitleBlock` for more details.
+    async fn add_toc_block(
+        realm: Id,
+        index: i32,
+        block: NewTocBlock,
+        context: &Context,
+    ) -> ApiResult<Realm> {
+        BlockValue::add_toc(realm, index, block, context).await
+    }
+
     /// Swap two blocks.
     async fn swap_blocks_by_index(
         realm: Id,
@@ -176,11 +198,105 @@ impl Mutation {
         BlockValue::update_video(id, set, context).await
     }
 
+    /// Update a table of contents block's data.
+    async fn update_toc_block(
+        id: Id,
+        set: UpdateTocBlock,
+        context: &Context,
+    ) -> ApiResult<BlockValue> {
+        BlockValue::update_toc(id, set, context).await
+    }
+
     /// Remove a block from a realm.
     async fn remove_block(id: Id, context: &Context) -> ApiResult<RemovedBlock> {
         BlockValue::remove(id, context).await
     }
 
+    /// Sets the global announcement shown on all pages, replacing the
+    /// previous one. Requires admin rights.
+    async fn set_announcement(
+        announcement: NewAnnouncement,
+        context: &Context,
+    ) -> ApiResult<Announcement> {
+        Announcement::set(announcement, context).await
+    }
+
+    /// Removes the global announcement. Returns `false` if there was none.
+    /// Requires admin rights.
+    async fn clear_announcement(context: &Context) -> ApiResult<bool> {
+        Announcement::clear(context).await
+    }
+
+    /// Marks the given announcement as dismissed for the current session.
+    /// Returns `false` if the user has no Tobira session to store this in.
+    async fn dismiss_announcement(id: Id, context: &Context) -> ApiResult<bool> {
+        Announcement::dismiss(id, context).await
+    }
+
+    /// Merges the person `source` into `target`, e.g. if they are different
+    /// spellings of the same name: all events of `source` are linked to
+    /// `target` instead and `source` is deleted. Future syncs also link
+    /// `source`'s names to `target`. Requires admin rights.
+    async fn merge_persons(source: Id, target: Id, context: &Context) -> ApiResult<Person> {
+        Person::merge(source, target, context).await
+    }
+
+    /// Replaces the ACL of the given event with the roles the given ACL
+    /// preset expands to (see `aclPresets`), in Opencast and in Tobira. Only
+    /// the roles are stored: later changes to the preset do not affect the
+    /// event. Requires write access to the event.
+    async fn apply_acl_preset(
+        event: Id,
+        preset: String,
+        context: &Context,
+    ) -> ApiResult<AuthorizedEvent> {
+        AuthorizedEvent::apply_acl_preset(event, preset, context).await
+    }
+
+    /// Stores the language the user chose in their current session. Has to be
+    /// one of the configured languages. Returns `false` if the user has no
+    /// Tobira session to store this in.
+    async fn set_language(lang: String, context: &Context) -> ApiResult<bool> {
+        user::set_language(lang, context).await
+    }
+
+    /// Stores where the current user is in the given event (in seconds from
+    /// the start), to continue watching later (see `User.continueWatching`).
+    /// This is cheap and can be called often: positions are buffered and only
+    /// written to the DB every few seconds if they changed by more than 10
+    /// seconds. Set `force` when the player is paused or the video ended, to
+    /// always store the position. Returns `false` if the position was dropped
+    /// due to high load. Requires the user to be logged in and
+    /// `general.continue_watching` to be enabled.
+    async fn save_playback_position(
+        event: Id,
+        seconds: f64,
+        #[graphql(default = false)]
+        force: bool,
+        context: &Context,
+    ) -> ApiResult<bool> {
+        playback_position::save(event, seconds, force, context)
+    }
+
+    /// Records that the current user ingested a new video into Opencast via
+    /// the uploader. This makes it possible to show the processing state of
+    /// that upload (see `User.myUploads`) until the event is synced.
+    async fn register_upload(
+        opencast_id: String,
+        title: String,
+        context: &Context,
+    ) -> ApiResult<UploadJob> {
+        UploadJob::register(opencast_id, title, context).await
+    }
+
+    /// Queues the search index documents of the event, series (i.e. all its
+    /// events) or realm with the given ID to be rebuilt from the DB. Stale
+    /// documents of items that don't exist anymore are deleted. Returns the
+    /// number of newly queued documents. Requires admin rights.
+    async fn rebuild_search_documents(id: Id, context: &Context) -> ApiResult<i32> {
+        search::queue_for_rebuild(id, context).await
+    }
+
     /// Atomically mount a series into an (empty) realm.
     /// Creates all the necessary realms on the path to the target
     /// and adds a block with the given series at the leaf.
@@ -228,20 +344,22 @@ impl Mutation {
                     // above.
                     name: name.unwrap_or_else(|| "temporary-dummy-name".into()),
                     path_segment,
-                    parent: Id::realm(target_realm.key),
+                    parent: target_realm.key.into(),
                 }, context).await?
             }
             target_realm
         };
 
         BlockValue::add_series(
-            Id::realm(target_realm.key),
+            target_realm.key.into(),
             0,
             NewSeriesBlock {
                 series: series.id(),
                 show_title: false,
                 show_metadata: true,
                 order: VideoListOrder::NewToOld,
+                layout: VideoListLayout::Grid,
+                metadata_filter: None,
             },
             context,
         ).await?;
@@ -7,11 +7,18 @@ use super::{
     Context,
     Id,
     NodeValue,
+    Cursor,
     err::ApiResult,
     model::{
+        acl::{AclPreset, RoleDisplayName},
+        announcement::Announcement,
+        known_user::{
+            KnownUser, KnownUserConnection, Session, SessionConnection, UserFilter, UserSortOrder,
+        },
+        person::Person,
         realm::Realm,
         event::{AuthorizedEvent, Event},
-        series::Series,
+        series::{Series, SeriesSortOrder},
         search::{self, SearchOutcome, EventSearchOutcome},
     },
     jwt::{JwtService, jwt},
@@ -28,8 +35,8 @@ impl Query {
         Realm::root(context).await
     }
 
-    /// Returns the realm with the specific ID or `None` if the ID does not
-    /// refer to a realm.
+    /// Returns the realm with the specific ID or `None` if it does not exist.
+    /// Returns an error if the ID refers to a different kind of node.
     async fn realm_by_id(id: Id, context: &Context) -> ApiResult<Option<Realm>> {
         Realm::load_by_id(id, context).await
     }
@@ -49,24 +56,44 @@ impl Query {
         AuthorizedEvent::load_by_opencast_id(id, context).await
     }
 
-    /// Returns an event by its ID.
+    /// Returns an event by its ID. Returns an error if the ID refers to a
+    /// different kind of node.
     async fn event_by_id(id: Id, context: &Context) -> ApiResult<Option<Event>> {
         AuthorizedEvent::load_by_id(id, context).await
     }
 
+    /// Returns all events readable by anyone (i.e. by `ROLE_ANONYMOUS`) that
+    /// were not imported from another Tobira themselves, ordered by Opencast
+    /// ID. Pass the Opencast ID of the last returned event as `after` to get
+    /// the next page. `first` has to be between 1 and 500. Used by other
+    /// Tobira instances to import events (see `federation.source`).
+    async fn public_events(
+        after: Option<String>,
+        #[graphql(default = 100)]
+        first: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<AuthorizedEvent>> {
+        AuthorizedEvent::load_public(after, first, context).await
+    }
+
     /// Returns a series by its Opencast ID.
     async fn series_by_opencast_id(id: String, context: &Context) -> ApiResult<Option<Series>> {
         Series::load_by_opencast_id(id, context).await
     }
 
-    /// Returns a series by its ID.
+    /// Returns a series by its ID. Returns an error if the ID refers to a
+    /// different kind of node.
     async fn series_by_id(id: Id, context: &Context) -> ApiResult<Option<Series>> {
         Series::load_by_id(id, context).await
     }
 
     /// Returns a list of all series.
-    async fn all_series(context: &Context) -> ApiResult<Vec<Series>> {
-        Series::load_all(context).await
+    async fn all_series(
+        #[graphql(default = Default::default())]
+        order_by: SeriesSortOrder,
+        context: &Context,
+    ) -> ApiResult<Vec<Series>> {
+        Series::load_all(order_by, context).await
     }
 
     /// Returns the current user.
@@ -77,6 +104,72 @@ impl Query {
         }
     }
 
+    /// Returns the path of the page that is shown when visiting `/`, which
+    /// depends on whether the user is logged in (see `general.initial_page`).
+    /// This is the same route the HTML of `/` points to.
+    fn initial_route(context: &Context) -> String {
+        let logged_in = matches!(context.auth, AuthContext::User(_));
+        context.config.general.initial_page.for_user(logged_in).route()
+    }
+
+    /// Returns all users that ever logged in (via Tobira's own session
+    /// management), filtered and paginated. `query` has to be contained
+    /// (case-insensitive) in username or display name; `role` has to be one
+    /// of the user's roles (as of their last login); `loggedInWithinDays`
+    /// restricts to users whose last login was at most that many days ago.
+    /// `first` has to be between 1 and 100. Requires admin rights.
+    async fn users(
+        query: Option<String>,
+        role: Option<String>,
+        logged_in_within_days: Option<i32>,
+        #[graphql(default = 50)]
+        first: i32,
+        after: Option<Cursor>,
+        #[graphql(default = Default::default())]
+        order_by: UserSortOrder,
+        context: &Context,
+    ) -> ApiResult<KnownUserConnection> {
+        let filter = UserFilter { query, role, logged_in_within_days };
+        KnownUser::load(filter, order_by, first, after, context).await
+    }
+
+    /// Returns all active sessions, newest first. If `user` is given, only
+    /// the sessions of the user with that username are returned. `first` has
+    /// to be between 1 and 100. Requires admin rights.
+    async fn sessions(
+        user: Option<String>,
+        #[graphql(default = 50)]
+        first: i32,
+        after: Option<Cursor>,
+        context: &Context,
+    ) -> ApiResult<SessionConnection> {
+        Session::load(user, first, after, context).await
+    }
+
+    /// Returns up to 100 persons (deduplicated creators of events) ordered by
+    /// name. If `query` is given, only persons with a name containing it are
+    /// returned. Requires admin rights.
+    async fn persons(query: Option<String>, context: &Context) -> ApiResult<Vec<Person>> {
+        Person::search(query, context).await
+    }
+
+    /// Returns the global announcement if there is one that is currently
+    /// active (according to DB time).
+    async fn announcement(context: &Context) -> ApiResult<Option<Announcement>> {
+        Announcement::load_active(context).await
+    }
+
+    /// Returns the configured ACL presets, expanded for the current user.
+    /// Empty if the user is not logged in.
+    fn acl_presets(context: &Context) -> Vec<AclPreset> {
+        AclPreset::load_all(context)
+    }
+
+    /// Returns the configured human-readable names of roles.
+    fn role_display_names(context: &Context) -> Vec<RoleDisplayName> {
+        RoleDisplayName::load_all(context)
+    }
+
     /// Returns a new JWT that can be used to authenticate against Opencast for using the given service
     fn jwt(service: JwtService, context: &Context) -> ApiResult<String> {
         jwt(service, context)
@@ -94,9 +187,14 @@ impl Query {
         }
     }
 
-    /// Returns `null` if the query is too short.
-    async fn search(query: String, context: &Context) -> ApiResult<SearchOutcome> {
-        search::perform(&query, context).await
+    /// Returns `null` if the query is too short. If `license` (an SPDX
+    /// identifier) is given, only events with that license are returned.
+    async fn search(
+        query: String,
+        license: Option<String>,
+        context: &Context,
+    ) -> ApiResult<SearchOutcome> {
+        search::perform(&query, license.as_deref(), context).await
     }
 
     /// Searches through all events (including non-listed ones). Requires
@@ -104,4 +202,33 @@ impl Query {
     async fn search_all_events(query: String, context: &Context) -> ApiResult<EventSearchOutcome> {
         search::all_events(&query, context).await
     }
+
+    /// Searches through all events the user has write access to, e.g. to
+    /// choose one for a video block. An empty query returns all of them.
+    /// `first` has to be between 1 and 50. Requires moderator rights.
+    async fn search_my_editable_events(
+        query: String,
+        #[graphql(default = 20)]
+        first: i32,
+        #[graphql(default = 0)]
+        offset: i32,
+        context: &Context,
+    ) -> ApiResult<EventSearchOutcome> {
+        search::my_editable_events(&query, first, offset, context).await
+    }
+
+    /// Returns series the user has write access to whose title contains the
+    /// query (case-insensitive), ordered by title, e.g. to choose one for a
+    /// series block. An empty query returns all of them. `first` has to be
+    /// between 1 and 50. Requires moderator rights.
+    async fn search_my_editable_series(
+        query: String,
+        #[graphql(default = 20)]
+        first: i32,
+        #[graphql(default = 0)]
+        offset: i32,
+        context: &Context,
+    ) -> ApiResult<Vec<Series>> {
+        Series::search_writable(&query, first, offset, context).await
+    }
 }
@@ -4,7 +4,11 @@ use std::path::PathBuf;
 
 use termcolor::ColorChoice;
 
-use crate::{cmd, db::cmd::DbCommand, search::cmd::SearchIndexCommand};
+use crate::{
+    cmd,
+    db::cmd::{DbCommand, OutputFormat},
+    search::cmd::SearchIndexCommand,
+};
 
 
 #[derive(Debug, clap::Parser)]
@@ -32,6 +36,13 @@ pub(crate) struct Args {
 pub(crate) enum Command {
     /// Starts the backend HTTP server.
     Serve {
+        /// Retry connecting to the database and MeiliSearch for up to this
+        /// many seconds before giving up, instead of failing immediately.
+        /// Useful in containerized deployments, where Tobira might be started
+        /// before its dependencies are ready.
+        #[clap(long, value_name = "SECS")]
+        wait_for_db: Option<u64>,
+
         #[clap(flatten)]
         shared: Shared,
     },
@@ -50,6 +61,13 @@ pub(crate) enum Command {
         #[clap(subcommand)]
         cmd: DbCommand,
 
+        /// How to print the result of the subcommand. With 'json', a single
+        /// JSON object describing what was done is printed and only errors
+        /// are logged. Only supported by subcommands that change or dump the
+        /// database.
+        #[clap(long, arg_enum, global = true, default_value = "text")]
+        output: OutputFormat,
+
         #[clap(flatten)]
         shared: Shared,
     },
@@ -69,6 +87,13 @@ pub(crate) enum Command {
     /// This currently includes: updating the search index and syncing with
     /// Opencast.
     Worker {
+        /// Retry connecting to the database and MeiliSearch for up to this
+        /// many seconds before giving up, instead of failing immediately.
+        /// Useful in containerized deployments, where Tobira might be started
+        /// before its dependencies are ready.
+        #[clap(long, value_name = "SECS")]
+        wait_for_db: Option<u64>,
+
         #[clap(flatten)]
         shared: Shared,
     },
@@ -84,6 +109,12 @@ pub(crate) enum Command {
         shared: Shared,
     },
 
+    /// Configuration file tools.
+    Config {
+        #[clap(subcommand)]
+        cmd: cmd::config::ConfigCommand,
+    },
+
     /// Outputs a template for the configuration file (which includes
     /// descriptions or all options).
     WriteConfig {
@@ -97,6 +128,29 @@ pub(crate) enum Command {
         args: cmd::export_api_schema::Args,
     },
 
+    /// Exports data, e.g. as CSV for spreadsheets.
+    Export {
+        #[clap(subcommand)]
+        cmd: cmd::export::ExportCommand,
+
+        #[clap(flatten)]
+        shared: Shared,
+    },
+
+    /// Prints key health numbers (sync lag, search index queue length,
+    /// pending migrations, ...) in the Prometheus text format or as JSON.
+    ///
+    /// Useful if the `/~metrics` endpoint cannot be scraped: the output can
+    /// be written to a file read by the node exporter's textfile collector.
+    /// Exits with a non-zero code if the database is unreachable.
+    Probe {
+        #[clap(flatten)]
+        args: cmd::probe::Args,
+
+        #[clap(flatten)]
+        shared: Shared,
+    },
+
     /// Imports a realm tree from a YAML description (internal tool, no stability guaranteed!).
     ImportRealmTree {
         #[clap(flatten)]
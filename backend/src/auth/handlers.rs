@@ -1,10 +1,29 @@
-use hyper::{Body, StatusCode};
+use hyper::{Body, StatusCode, header};
 
 use crate::{db, http::{self, Context, Request, Response}, prelude::*};
-use super::{AuthMode, SessionId, User};
+use super::{AuthMode, SessionId, User, return_to};
+
+
+/// Handles GET requests to `/~login` if `auth.login_link` is set: remembers
+/// the `return_to` parameter (if valid) in a cookie and redirects to the
+/// external login.
+pub(crate) fn handle_login_link(req: &Request<Body>, login_link: &str) -> Response {
+    let mut response = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, login_link);
+    if let Some(target) = return_to::from_query(req.uri().query()) {
+        response = response.header(header::SET_COOKIE, return_to::set_cookie(&target).to_string());
+    }
+
+    response.body(Body::empty()).unwrap()
+}
 
 
 /// Handles POST requests to `/~session`.
+///
+/// If a valid `return_to` target is given (via query parameter or the cookie
+/// set by `handle_login_link`), replies with a redirect to it instead of
+/// `204 No Content`.
 pub(crate) async fn handle_login(req: Request<Body>, ctx: &Context) -> Result<Response, Response> {
     if ctx.config.auth.mode != AuthMode::LoginProxy {
         warn!("Got POST /~session request, but due to the authentication mode, this endpoint \
@@ -33,8 +52,17 @@ pub(crate) async fn handle_login(req: Request<Body>, ctx: &Context) -> Result<Re
             })?;
             debug!("Persisted new session for '{}'", user.username);
 
-            Response::builder()
-                .status(StatusCode::NO_CONTENT)
+            let return_to = return_to::from_query(req.uri().query())
+                .or_else(|| return_to::from_headers(req.headers()));
+            let response = match return_to {
+                Some(target) => Response::builder()
+                    .status(StatusCode::SEE_OTHER)
+                    .header(header::LOCATION, target)
+                    .header("set-cookie", return_to::unset_cookie().to_string()),
+                None => Response::builder().status(StatusCode::NO_CONTENT),
+            };
+
+            response
                 .header("set-cookie", session_id.set_cookie(
                     ctx.config.auth.session_duration
                 ).to_string())
@@ -6,17 +6,22 @@ use once_cell::sync::Lazy;
 use secrecy::{Secret, ExposeSecret};
 use tokio_postgres::Error as PgError;
 
-use crate::{config::TranslatedString, prelude::*, db::util::select};
+use crate::{
+    config::{Config, TranslatedString},
+    prelude::*,
+    db::{realm_names, series_stats, util::select},
+};
 
 
 mod handlers;
 mod session_id;
 mod jwt;
+mod return_to;
 
 pub(crate) use self::{
     session_id::SessionId,
     jwt::{JwtConfig, JwtContext},
-    handlers::{handle_login, handle_logout},
+    handlers::{handle_login, handle_login_link, handle_logout},
 };
 
 
@@ -24,7 +29,8 @@ pub(crate) use self::{
 /// administrator.
 pub(crate) const ROLE_ADMIN: &str = "ROLE_ADMIN";
 
-const ROLE_ANONYMOUS: &str = "ROLE_ANONYMOUS";
+/// Role that every user (including anonymous ones) has.
+pub(crate) const ROLE_ANONYMOUS: &str = "ROLE_ANONYMOUS";
 
 const SESSION_COOKIE: &str = "tobira-session";
 
@@ -48,12 +54,20 @@ pub(crate) struct AuthConfig {
 
     /// Link of the login button. If not set, the login button internally
     /// (not via `<a>`, but through JavaScript) links to Tobira's own login page.
+    /// If set, `/~login` redirects to this link, remembering the page the
+    /// user came from. After the external login, the `POST /~session` request
+    /// then redirects back to that page.
     pub(crate) login_link: Option<String>,
 
     /// Link of the logout button. If not set, clicking the logout button will
     /// send a `DELETE` request to `/~session`.
     pub(crate) logout_link: Option<String>,
 
+    /// Where users are sent after logging out via `DELETE /~session` (i.e. if
+    /// `logout_link` is not set), e.g. the logout page of your SSO. If not
+    /// set, users are sent to the home page.
+    pub(crate) post_logout_link: Option<String>,
+
     /// The header containing a unique and stable username of the current user.
     #[config(default = "x-tobira-username")]
     pub(crate) username_header: String,
@@ -284,6 +298,20 @@ impl User {
             dbargs![&session_id, &self.username, &self.display_name, &roles],
         ).await?;
 
+        // Remember the user for admin listings. `username` is the primary key
+        // and the upsert is a single statement, so parallel first logins of
+        // the same user (e.g. in two tabs) cannot create duplicate rows: the
+        // slower one waits for the other and then takes the `update` path.
+        db.execute_raw(
+            "insert into users (username, display_name, roles) \
+                values ($1, $2, $3) \
+                on conflict (username) do update set \
+                    display_name = excluded.display_name, \
+                    roles = excluded.roles, \
+                    last_login = now()",
+            dbargs![&self.username, &self.display_name, &roles],
+        ).await?;
+
         Ok(session_id)
     }
 }
@@ -329,6 +357,11 @@ pub(crate) trait HasRoles {
         AuthToken::some_if(self.is_moderator(auth_config))
     }
 
+    /// Returns an auth token IF this user is a global Opencast administrator.
+    fn require_admin(&self) -> Option<AuthToken> {
+        AuthToken::some_if(self.is_admin())
+    }
+
     fn required_upload_permission(&self, auth_config: &AuthConfig) -> Option<AuthToken> {
         AuthToken::some_if(self.can_upload(auth_config))
     }
@@ -399,23 +432,71 @@ impl HasRoles for AuthContext {
 }
 
 /// Long running task to perform various DB maintenance.
-pub(crate) async fn db_maintenance(db: &Client, config: &AuthConfig) -> ! {
+pub(crate) async fn db_maintenance(db: &Client, config: &Config) -> ! {
     /// Delete outdated user sessions every hour. Note that the session
     /// expiration time is still checked whenever the session is validated. So
     /// this duration is not about correctness, just about how often to clean
     /// up.
     const RUN_PERIOD: Duration = Duration::from_secs(60 * 60);
 
+    /// Finished upload jobs are only interesting for users shortly after the
+    /// upload. After that, the video is listed in "my videos" anyway.
+    const FINISHED_UPLOADS_RETENTION_DAYS: f64 = 7.0;
+
     loop {
         // Remove outdated user sessions.
         let sql = "delete from user_sessions \
             where extract(epoch from now() - created) > $1::double precision";
-        match db.execute(sql, &[&config.session_duration.as_secs_f64()]).await {
+        match db.execute(sql, &[&config.auth.session_duration.as_secs_f64()]).await {
             Err(e) => error!("Error deleting outdated user sessions: {}", e),
             Ok(0) => debug!("No outdated user sessions found in DB"),
             Ok(num) => info!("Deleted {num} outdated user sessions from DB"),
         }
 
+        // Remove old upload jobs that are not processing anymore.
+        let sql = "delete from upload_jobs \
+            where state <> 'processing' \
+            and extract(epoch from now() - updated) > $1::double precision * 24 * 60 * 60";
+        match db.execute(sql, &[&FINISHED_UPLOADS_RETENTION_DAYS]).await {
+            Err(e) => error!("Error deleting old upload jobs: {}", e),
+            Ok(0) => debug!("No old upload jobs found in DB"),
+            Ok(num) => info!("Deleted {num} old upload jobs from DB"),
+        }
+
+        // Remove old playback positions. This is also done if the feature is
+        // disabled, to eventually get rid of positions stored before that.
+        let sql = "delete from playback_positions \
+            where extract(epoch from now() - updated) > $1::double precision";
+        let retention = config.general.playback_position_retention.as_secs_f64();
+        match db.execute(sql, &[&retention]).await {
+            Err(e) => error!("Error deleting old playback positions: {}", e),
+            Ok(0) => debug!("No old playback positions found in DB"),
+            Ok(num) => info!("Deleted {num} old playback positions from DB"),
+        }
+
+        // Realms deriving their name from a deleted video or series would be
+        // nameless otherwise.
+        match realm_names::fix_deleted_sources(db).await {
+            Err(e) => error!("Error fixing realm names: {e:#}"),
+            Ok(paths) => for path in paths {
+                info!("Name source of realm '{path}' was deleted, using path segment as name");
+            },
+        }
+        match realm_names::check(db).await {
+            Err(e) => error!("Error checking realm names: {e:#}"),
+            Ok(blank) => for realm in blank {
+                warn!("Inconsistency: {realm}");
+            },
+        }
+
+        // The sync keeps these up to date, but recounting is cheap and fixes
+        // any drift, e.g. after manually modifying the DB.
+        match series_stats::update(&***db, None).await {
+            Err(e) => error!("Error recounting events of series: {e}"),
+            Ok(0) => debug!("Event statistics of all series are correct"),
+            Ok(num) => warn!("Fixed outdated event statistics of {num} series"),
+        }
+
         tokio::time::sleep(RUN_PERIOD).await;
     }
 }
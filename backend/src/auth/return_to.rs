@@ -0,0 +1,129 @@
+//! The `return_to` parameter of the login routes, determining where users are
+//! sent after logging in. As an external login (`auth.login_link`) leaves
+//! Tobira, the target is stored in a short-lived cookie in the meantime.
+
+use cookie::Cookie;
+use hyper::{HeaderMap, header};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+
+
+const RETURN_TO_COOKIE: &str = "tobira-return-to";
+
+/// How long the target is remembered. Logging in should not take longer.
+const COOKIE_MAX_AGE: time::Duration = time::Duration::minutes(10);
+
+
+/// Returns `target` if it is a same-origin relative path (e.g.
+/// `/path/to?x=1#foo`) and `None` otherwise. This prevents open redirects:
+/// absolute URLs (`https://evil.com`), protocol-relative ones
+/// (`//evil.com`) and everything browsers might turn into those (e.g.
+/// `/\evil.com` or `/<tab>/evil.com`) are rejected. Non-ASCII characters have
+/// to be percent-encoded.
+pub(crate) fn validate(target: &str) -> Option<&str> {
+    let valid = target.starts_with('/')
+        && !target.starts_with("//")
+        && target.chars().all(|c| c.is_ascii_graphic() && c != '\\');
+    valid.then_some(target)
+}
+
+/// Returns the valid `return_to` parameter of the given query string.
+pub(crate) fn from_query(query: Option<&str>) -> Option<String> {
+    query?.split('&')
+        .find_map(|param| param.strip_prefix("return_to="))
+        .and_then(|v| percent_decode_str(v).decode_utf8().ok())
+        .and_then(|v| validate(&v).map(ToOwned::to_owned))
+}
+
+/// Returns the valid target stored in the `return_to` cookie, if any.
+pub(crate) fn from_headers(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(header::COOKIE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';').map(|s| s.trim()))
+        .find_map(|s| s.strip_prefix(RETURN_TO_COOKIE)?.strip_prefix('='))
+        .and_then(|v| percent_decode_str(v).decode_utf8().ok())
+        .and_then(|v| validate(&v).map(ToOwned::to_owned))
+}
+
+/// Returns a cookie for a `set-cookie` header storing the (already validated)
+/// target.
+pub(crate) fn set_cookie(target: &str) -> Cookie<'static> {
+    Cookie::build(RETURN_TO_COOKIE, utf8_percent_encode(target, NON_ALPHANUMERIC).to_string())
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        // "Lax" so that the cookie is sent when the login system redirects
+        // back to Tobira.
+        .same_site(cookie::SameSite::Lax)
+        .max_age(COOKIE_MAX_AGE)
+        .finish()
+}
+
+/// Returns a cookie for a `set-cookie` header that removes the stored target.
+pub(crate) fn unset_cookie() -> Cookie<'static> {
+    Cookie::build(RETURN_TO_COOKIE, "")
+        .path("/")
+        .max_age(time::Duration::ZERO)
+        .secure(true)
+        .http_only(true)
+        .same_site(cookie::SameSite::Lax)
+        .finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use hyper::{HeaderMap, header};
+    use super::{from_headers, from_query, set_cookie, validate};
+
+    #[test]
+    fn accepts_relative_paths() {
+        for target in ["/", "/~login", "/realm/foo?x=1&y=%20#bar", "/!v/abc", "/a//b"] {
+            assert_eq!(validate(target), Some(target), "{target}");
+        }
+    }
+
+    #[test]
+    fn rejects_malicious_urls() {
+        let malicious = [
+            "",
+            "https://evil.com",
+            "HTTPS://evil.com/",
+            "javascript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "evil.com",
+            "//evil.com",
+            "///evil.com",
+            "/\\evil.com",
+            "\\\\evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            " /foo",
+            "/foo bar",
+            "/föö",
+        ];
+        for target in malicious {
+            assert_eq!(validate(target), None, "{target:?}");
+        }
+    }
+
+    #[test]
+    fn reads_query_and_cookie() {
+        assert_eq!(from_query(None), None);
+        assert_eq!(from_query(Some("return_to=%2F%2Fevil.com")), None);
+        assert_eq!(from_query(Some("return_to=https%3A%2F%2Fevil.com")), None);
+        assert_eq!(from_query(Some("return_to=%2F%5Cevil.com")), None);
+        assert_eq!(
+            from_query(Some("lang=de&return_to=%2Fvideo%2Fabc%3Ft%3D1")).as_deref(),
+            Some("/video/abc?t=1"),
+        );
+
+        let mut headers = HeaderMap::new();
+        let cookie = set_cookie("/video/abc?t=1;x");
+        let value = format!("foo=bar; {}={}", cookie.name(), cookie.value());
+        headers.insert(header::COOKIE, value.parse().unwrap());
+        assert_eq!(from_headers(&headers).as_deref(), Some("/video/abc?t=1;x"));
+
+        headers.insert(header::COOKIE, "tobira-return-to=%2F%2Fevil.com".parse().unwrap());
+        assert_eq!(from_headers(&headers), None);
+    }
+}
@@ -9,7 +9,12 @@ use crate::{
     load_config_and_init_logger,
     args::{self, Args},
     config::Config,
-    db::{self, MigrationPlan},
+    db::{
+        self,
+        MigrationPlan,
+        collation::{self, Finding, Severity},
+        realm_names::{self, BlankRealmName},
+    },
     prelude::*,
     sync::OcClient,
 };
@@ -28,6 +33,14 @@ pub(crate) async fn run(shared: &args::Shared, args: &Args) -> Result<()> {
         Err(_) => None,
         Ok(pool) => Some(check_db_migrations(pool).await),
     };
+    let db_collation = match &db_pool {
+        Err(_) => None,
+        Ok(pool) => Some(check_db_collation(pool).await),
+    };
+    let db_realm_names = match &db_pool {
+        Err(_) => None,
+        Ok(pool) => Some(check_realm_names(pool).await),
+    };
     let meili = check_meili(&config).await;
     let opencast_sync = check_opencast_sync(&config).await;
     info!("Done verifing various things");
@@ -53,6 +66,21 @@ pub(crate) async fn run(shared: &args::Shared, args: &Args) -> Result<()> {
                 => println!("    ▸ DB is compatible, {new_migrations} new migrations will be applied"),
         }
     }
+    if let Some(db_collation) = db_collation {
+        print_outcome(&mut any_errors, "DB encoding & collation", &db_collation);
+        for warning in db_collation.iter().flatten() {
+            bunt::println!("    ▸ {$yellow+bold}warning:{/$} {}", warning);
+        }
+    }
+    if let Some(db_realm_names) = db_realm_names {
+        print_outcome(&mut any_errors, "Realm names", &db_realm_names);
+        for blank in db_realm_names.iter().flatten() {
+            bunt::println!("    ▸ {$yellow+bold}warning:{/$} {}", blank);
+            if blank.source_deleted {
+                println!("      (will be fixed automatically by 'tobira worker')");
+            }
+        }
+    }
     print_outcome(&mut any_errors, "MeiliSearch", &meili);
     match meili {
         Ok(true) => println!("    ▸ Requires rebuild (is automatically done by 'tobira worker')"),
@@ -99,14 +127,7 @@ fn print_outcome<T>(any_errors: &mut bool, label: &str, result: &Result<T>) {
 async fn check_referenced_files(config: &Config) -> Result<()> {
     // TODO: log file & unix socket?
 
-    let mut files = vec![
-        &config.theme.favicon,
-        &config.auth.jwt.secret_key,
-        &config.theme.logo.large.path,
-    ];
-    files.extend(config.theme.logo.small.as_ref().map(|l| &l.path));
-
-    for path in files {
+    for (_, path) in config.referenced_files() {
         debug!("Trying to open '{}' for reading...", path.display());
         let _ = tokio::fs::File::open(path)
             .await
@@ -154,3 +175,24 @@ async fn check_db_migrations(db_pool: &deadpool_postgres::Pool) -> Result<Migrat
     let tx = db.transaction().await?;
     MigrationPlan::build(&tx).await
 }
+
+/// Returns all warnings, or an error listing all findings with error severity.
+async fn check_db_collation(db_pool: &deadpool_postgres::Pool) -> Result<Vec<Finding>> {
+    let db = db_pool.get().await?;
+    let (errors, warnings): (Vec<_>, Vec<_>) = collation::check(&db)
+        .await?
+        .into_iter()
+        .partition(|f| f.severity == Severity::Error);
+
+    if !errors.is_empty() {
+        let list = errors.iter().map(|e| format!("\n  - {e}")).collect::<String>();
+        bail!("{} problem(s) with database encoding or collation:{list}", errors.len());
+    }
+    Ok(warnings)
+}
+
+/// Returns all realms with blank names as warnings. Does not fix anything.
+async fn check_realm_names(db_pool: &deadpool_postgres::Pool) -> Result<Vec<BlankRealmName>> {
+    let db = db_pool.get().await?;
+    realm_names::check(&db).await
+}
@@ -0,0 +1,218 @@
+//! The `config` subcommands.
+
+use std::path::{Path, PathBuf};
+use confique::{Config as _, meta::{FieldKind, Meta}};
+
+use crate::{config::Config, prelude::*};
+
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Loads and validates the given configuration file without connecting
+    /// to any service (see `tobira check` for that). Prints all problems with
+    /// their location in the file. Exits with 1 if there are errors and with
+    /// 0 otherwise (even if there are warnings).
+    Check {
+        /// Path to the configuration file.
+        path: PathBuf,
+
+        /// Do not check whether referenced files (e.g. logos or the JWT key)
+        /// exist and are readable.
+        #[clap(long)]
+        no_file_checks: bool,
+    },
+}
+
+#[derive(PartialEq, Eq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+struct Problem {
+    severity: Severity,
+    /// 1-based line and column.
+    location: Option<(usize, usize)>,
+    message: String,
+}
+
+pub(crate) fn run(cmd: &ConfigCommand) -> Result<()> {
+    match cmd {
+        ConfigCommand::Check { path, no_file_checks } => check(path, *no_file_checks),
+    }
+}
+
+fn check(path: &Path, no_file_checks: bool) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+    let problems = find_problems(path, &text, no_file_checks);
+
+    for problem in &problems {
+        let location = problem.location.map_or(String::new(), |(l, c)| format!(":{l}:{c}"));
+        match problem.severity {
+            Severity::Error => bunt::println!(
+                "{}{}: {$red+bold}error:{/$} {}",
+                path.display(), location, problem.message,
+            ),
+            Severity::Warning => bunt::println!(
+                "{}{}: {$yellow+bold}warning:{/$} {}",
+                path.display(), location, problem.message,
+            ),
+        }
+    }
+
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    let warnings = problems.len() - errors;
+    if errors > 0 {
+        bail!("configuration '{}' has {errors} error(s) and {warnings} warning(s)",
+            path.display());
+    }
+
+    bunt::println!("{$green+bold}Configuration is valid{/$} ({} warning(s))", warnings);
+    Ok(())
+}
+
+fn find_problems(path: &Path, text: &str, no_file_checks: bool) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let error = |message: String, location| Problem {
+        severity: Severity::Error,
+        location,
+        message,
+    };
+
+    // Syntax errors. Nothing else can be checked in that case.
+    let value = match toml::from_str::<toml::Value>(text) {
+        Ok(value) => value,
+        Err(e) => {
+            let location = e.line_col().map(|(l, c)| (l + 1, c + 1));
+            problems.push(error(e.to_string(), location));
+            return problems;
+        }
+    };
+
+    // Unknown keys are ignored when loading, which hides typos.
+    let mut unknown = Vec::new();
+    find_unknown_keys(&value, &<Config as confique::Config>::META, "", &mut unknown);
+    for key in unknown {
+        problems.push(Problem {
+            severity: Severity::Warning,
+            location: locate_key(text, &key),
+            message: format!("unknown key `{key}` (it is ignored)"),
+        });
+    }
+
+    // Missing values, wrong types, ...
+    let mut config = match Config::from_file(path) {
+        Ok(config) => config,
+        Err(e) => {
+            let message = error_message(&anyhow::Error::from(e));
+            let location = locate_message(text, &message);
+            problems.push(error(message, location));
+            return problems;
+        }
+    };
+
+    // Our own validation.
+    for e in config.validation_errors() {
+        let message = error_message(&e);
+        let location = locate_message(text, &message);
+        problems.push(error(message, location));
+    }
+
+    if !no_file_checks {
+        if let Err(e) = config.fix_paths(path) {
+            problems.push(error(error_message(&e), None));
+            return problems;
+        }
+
+        for (key, file) in config.referenced_files() {
+            if let Err(e) = std::fs::File::open(file) {
+                let message = format!("`{key}`: cannot open '{}' for reading: {e}", file.display());
+                problems.push(error(message, locate_key(text, key)));
+            }
+        }
+        if let Err(e) = config.db.check_server_cert() {
+            problems.push(error(error_message(&e), locate_key(text, "db.server_cert")));
+        }
+    }
+
+    problems
+}
+
+/// Collects all keys in `value` that are not fields of the config described
+/// by `meta`. Leaf fields are not descended into, as some of them are tables
+/// themselves (e.g. translated strings).
+fn find_unknown_keys(value: &toml::Value, meta: &Meta, prefix: &str, out: &mut Vec<String>) {
+    let table = match value.as_table() {
+        Some(table) => table,
+        None => return,
+    };
+
+    for (key, value) in table {
+        let full_key = format!("{prefix}{key}");
+        match meta.fields.iter().find(|f| f.name == key) {
+            None => out.push(full_key),
+            Some(field) => if let FieldKind::Nested { meta } = &field.kind {
+                find_unknown_keys(value, meta, &format!("{full_key}."), out);
+            },
+        }
+    }
+}
+
+/// Joins the error and all its causes into one line.
+fn error_message(e: &anyhow::Error) -> String {
+    e.chain().map(|cause| cause.to_string()).collect::<Vec<_>>().join(": ")
+}
+
+/// Tries to find the location of the config key mentioned (in quotes or
+/// backticks) in the error message.
+fn locate_message(text: &str, message: &str) -> Option<(usize, usize)> {
+    message.split(['`', '\'', '"'])
+        .skip(1)
+        .step_by(2)
+        .find_map(|key| locate_key(text, key))
+}
+
+/// Finds the line and column where the given dotted key (e.g. `db.password`)
+/// is defined in the TOML document. This is a simple line-based heuristic
+/// that handles table headers and dotted keys, which is all config files
+/// usually use.
+fn locate_key(text: &str, key: &str) -> Option<(usize, usize)> {
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let matches = |full_key: &str| full_key == key
+        || full_key.strip_prefix(key).is_some_and(|rest| rest.starts_with('.'));
+
+    let mut table = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let col = line.len() - trimmed.len() + 1;
+        if trimmed.starts_with('#') || trimmed.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = trimmed.strip_prefix('[') {
+            table = header.split(']').next().unwrap_or("").trim_matches('[').trim().to_owned();
+            if matches(&table) {
+                return Some((i + 1, col));
+            }
+            continue;
+        }
+
+        if let Some((line_key, _)) = trimmed.split_once('=') {
+            let line_key = line_key.split('.').map(|part| part.trim()).collect::<Vec<_>>();
+            let full_key = if table.is_empty() {
+                line_key.join(".")
+            } else {
+                format!("{table}.{}", line_key.join("."))
+            };
+            if matches(&full_key) {
+                return Some((i + 1, col));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,197 @@
+//! CLI command `export` to export data from the DB, e.g. for spreadsheets.
+
+use std::{fs::File, io::{self, BufWriter, Write}, path::PathBuf};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio_postgres::GenericClient;
+
+use crate::{config::Config, db, prelude::*};
+
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum ExportCommand {
+    /// Exports all events (that are fully synced) with their series, the
+    /// realms they are mounted in and more. Tobira does not record view
+    /// statistics, so no play counts are included.
+    Events(EventsArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct EventsArgs {
+    /// Output format.
+    #[clap(long, arg_enum, default_value = "csv")]
+    format: Format,
+
+    /// File to write to. If not specified, the export is written to stdout. In
+    /// that case, make sure logging to stdout is disabled.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Only export some events. Currently only `series=<opencast-id>` is
+    /// supported.
+    #[clap(long, value_parser = parse_filter)]
+    filter: Option<Filter>,
+
+    /// Only export events created at or after this time (e.g.
+    /// `2022-08-01T00:00:00Z`).
+    #[clap(long)]
+    created_after: Option<DateTime<Utc>>,
+
+    /// Only export events created before this time (e.g.
+    /// `2023-01-01T00:00:00Z`).
+    #[clap(long)]
+    created_before: Option<DateTime<Utc>>,
+
+    /// Start the output with a UTF-8 BOM. Excel needs that to correctly detect
+    /// the encoding.
+    #[clap(long)]
+    bom: bool,
+}
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+enum Format {
+    Csv,
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Series(String),
+}
+
+fn parse_filter(s: &str) -> Result<Filter, String> {
+    match s.split_once('=') {
+        Some(("series", id)) if !id.is_empty() => Ok(Filter::Series(id.to_owned())),
+        Some(("series", _)) => Err("series ID must not be empty".into()),
+        _ => Err("invalid filter (allowed: `series=<opencast-id>`)".into()),
+    }
+}
+
+
+pub(crate) async fn run(cmd: &ExportCommand, config: &Config) -> Result<()> {
+    let db = db::create_pool(&config.db).await
+        .context("failed to create database connection pool (database not running?)")?;
+    let conn = db.get().await?;
+
+    match cmd {
+        ExportCommand::Events(args) => export_events(&**conn, args).await,
+    }
+}
+
+async fn export_events(db: &impl GenericClient, args: &EventsArgs) -> Result<()> {
+    // CSV is the only format so far.
+    let Format::Csv = args.format;
+    if let (Some(after), Some(before)) = (args.created_after, args.created_before) {
+        if after >= before {
+            bail!("`--created-after` has to be before `--created-before`");
+        }
+    }
+
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)
+            .with_context(|| format!("failed to create '{}'", path.display()))?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(out);
+
+    if args.bom {
+        out.write_all("\u{feff}".as_bytes())?;
+    }
+    write_record(&mut out, &[
+        "id", "title", "series", "series_title", "created", "duration_seconds",
+        "listed", "realm_paths",
+    ])?;
+
+    // The rows are streamed from the DB and written immediately, so that
+    // memory usage does not depend on the number of events.
+    let series = args.filter.as_ref().map(|Filter::Series(id)| id);
+    let rows = db.query_raw(
+        "select events.opencast_id, events.title, series.opencast_id, series.title, \
+                events.created, events.duration, \
+                array( \
+                    select distinct realms.full_path \
+                    from blocks \
+                    join realms on realms.id = blocks.realm \
+                    where blocks.type = 'series' and blocks.series = events.series \
+                            and (blocks.metadata_filter is null \
+                                or events.metadata @> blocks.metadata_filter) \
+                        or blocks.type = 'video' and blocks.video = events.id \
+                    order by realms.full_path \
+                ) \
+            from events \
+            left join series on series.id = events.series \
+            where events.state = 'ready' \
+                and ($1::text is null or series.opencast_id = $1) \
+                and ($2::timestamptz is null or events.created >= $2) \
+                and ($3::timestamptz is null or events.created < $3) \
+            order by events.created, events.id",
+        dbargs![&series, &args.created_after, &args.created_before],
+    ).await.context("failed to query events")?;
+    futures::pin_mut!(rows);
+
+    let mut count = 0u64;
+    while let Some(row) = rows.try_next().await? {
+        let created: DateTime<Utc> = row.get(4);
+        let duration: Option<i32> = row.get(5);
+        let realm_paths = row.get::<_, Vec<String>>(6)
+            .into_iter()
+            .map(|path| if path.is_empty() { "/".into() } else { path })
+            .collect::<Vec<_>>();
+
+        write_record(&mut out, &[
+            row.get::<_, &str>(0),
+            row.get::<_, &str>(1),
+            row.get::<_, Option<&str>>(2).unwrap_or_default(),
+            row.get::<_, Option<&str>>(3).unwrap_or_default(),
+            &created.to_rfc3339_opts(SecondsFormat::Secs, true),
+            &duration.map(|ms| (f64::from(ms) / 1000.0).to_string()).unwrap_or_default(),
+            if realm_paths.is_empty() { "false" } else { "true" },
+            &realm_paths.join("\n"),
+        ])?;
+        count += 1;
+    }
+    out.flush()?;
+
+    // Not to stdout, as that might be the export itself.
+    eprintln!("Exported {count} events");
+    Ok(())
+}
+
+/// Writes one CSV record as specified in RFC 4180.
+pub(crate) fn write_record(out: &mut impl Write, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(escape(field).as_bytes())?;
+    }
+    out.write_all(b"\r\n")
+}
+
+/// Quotes the field if necessary, doubling all quotes inside.
+fn escape(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::write_record;
+
+    fn record(fields: &[&str]) -> String {
+        let mut out = Vec::new();
+        write_record(&mut out, fields).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escapes_fields() {
+        assert_eq!(record(&["a", "b c", ""]), "a,b c,\r\n");
+        assert_eq!(record(&["Hello, world", "x"]), "\"Hello, world\",x\r\n");
+        assert_eq!(record(&["say \"hi\""]), "\"say \"\"hi\"\"\"\r\n");
+        assert_eq!(record(&["/a\n/b", "ä"]), "\"/a\n/b\",ä\r\n");
+    }
+}
@@ -8,6 +8,7 @@ use std::{fs::File, future::Future, path::PathBuf, pin::Pin};
 use crate::{
     config::Config,
     db,
+    markdown,
     prelude::*,
 };
 
@@ -58,7 +59,7 @@ pub(crate) async fn run(args: &Args, config: &Config) -> Result<()> {
     // Open DB connection, check consistency and migrate if necessary.
     let db = db::create_pool(&config.db).await
         .context("failed to create database connection pool (database not running?)")?;
-    db::migrate(&mut *db.get().await?).await
+    db::auto_migrate(&mut *db.get().await?, &config.db).await
         .context("failed to check/run DB migrations")?;
     let conn = db.get().await?;
 
@@ -160,10 +161,11 @@ impl Block {
             }
             Block::Text(text) => {
                 let query = "
-                    insert into blocks (realm, type, index, text_content)
-                    values ($1, 'text', $2, $3)
+                    insert into blocks (realm, type, index, text_content, text_html)
+                    values ($1, 'text', $2, $3, $4)
                 ";
-                db.execute(query, &[&realm_id, &(index as i16), text]).await?;
+                let html = markdown::render(text);
+                db.execute(query, &[&realm_id, &(index as i16), text, &html]).await?;
             }
             Block::Series(series) => {
                 // Obtain the series ID
@@ -3,6 +3,9 @@ use crate::prelude::*;
 pub(crate) mod export_api_schema;
 pub(crate) mod import_realm_tree;
 pub(crate) mod check;
+pub(crate) mod config;
+pub(crate) mod export;
+pub(crate) mod probe;
 
 
 /// Reads stdin and returns an error if the trimmed input is not exactly "yes".
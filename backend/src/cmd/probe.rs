@@ -0,0 +1,164 @@
+//! CLI command `probe` that prints key health numbers, e.g. for the textfile
+//! collector of the Prometheus node exporter on hosts where the `/~metrics`
+//! endpoint cannot be scraped.
+
+use std::time::{Duration, Instant};
+
+use prometheus_client::{
+    encoding::text::encode,
+    metrics::gauge::Gauge,
+    registry::{Registry, Unit},
+};
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    db::{self, MigrationPlan},
+    metrics::{
+        self, MetricDesc, NUM_USER_SESSIONS, SEARCH_INDEX_QUEUE_LEN, SYNC_LAG, SYNC_LAG_QUERY,
+    },
+    prelude::*,
+};
+
+
+/// The probe is aborted if it takes longer than this.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const PENDING_MIGRATIONS: MetricDesc = MetricDesc {
+    name: "pending_migrations",
+    help: "Number of migrations that are not yet applied to the DB",
+    unit: None,
+};
+const DB_LATENCY: MetricDesc = MetricDesc {
+    name: "db_latency",
+    help: "Time to get a connection from the DB pool and execute a trivial query",
+    unit: Some(Unit::Seconds),
+};
+
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct Args {
+    /// Output format. 'prometheus' is the text exposition format, suitable
+    /// for the node exporter's textfile collector.
+    #[clap(long, arg_enum, default_value = "prometheus")]
+    output: Format,
+}
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+enum Format {
+    Prometheus,
+    Json,
+}
+
+/// The gathered numbers. Numbers that could not be determined (e.g. because
+/// of missing tables in a fresh DB) are `None`: `null` in JSON and omitted
+/// in the Prometheus format.
+#[derive(Debug, Default, Serialize)]
+struct Probe {
+    sync_lag_seconds: Option<f64>,
+    search_index_queue_len: Option<u64>,
+    pending_migrations: Option<u64>,
+    num_user_sessions: Option<u64>,
+    db_latency_seconds: f64,
+}
+
+
+pub(crate) async fn run(args: &Args, config: &Config) -> Result<()> {
+    let probe = tokio::time::timeout(TIMEOUT, gather(config)).await
+        .map_err(|_| anyhow!("probe did not finish within {}s", TIMEOUT.as_secs()))?
+        .context("failed to probe database")?;
+
+    match args.output {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&probe)?),
+        Format::Prometheus => print!("{}", probe.encode()),
+    }
+    Ok(())
+}
+
+async fn gather(config: &Config) -> Result<Probe> {
+    let pool = db::create_pool(&config.db).await
+        .context("failed to create database connection pool (database not running?)")?;
+
+    let before = Instant::now();
+    let mut db = pool.get().await?;
+    db.execute("select 1", &[]).await?;
+    let mut out = Probe {
+        db_latency_seconds: before.elapsed().as_secs_f64(),
+        .. Probe::default()
+    };
+
+    // Like for `/~metrics`, failing queries just lead to missing numbers.
+    let count = |table| format!("select count(*) from {table}");
+    out.sync_lag_seconds = db.query_one(SYNC_LAG_QUERY, &[]).await
+        .ok()
+        .map(|row| row.get::<_, f64>(0));
+    out.search_index_queue_len = db.query_one(&count("search_index_queue"), &[]).await
+        .ok()
+        .map(|row| row.get::<_, i64>(0) as u64);
+    out.num_user_sessions = db.query_one(&count("user_sessions"), &[]).await
+        .ok()
+        .map(|row| row.get::<_, i64>(0) as u64);
+
+    // The transaction is only used for reading and is rolled back on drop.
+    let tx = db.transaction().await?;
+    out.pending_migrations = match MigrationPlan::build(&tx).await {
+        Ok(plan) => Some(plan.pending_migrations()),
+        Err(e) => {
+            debug!("Could not determine pending migrations: {e:#}");
+            None
+        }
+    };
+
+    Ok(out)
+}
+
+impl Probe {
+    /// Encodes the numbers in the Prometheus text exposition format, with the
+    /// same names as in `/~metrics`.
+    fn encode(&self) -> String {
+        let mut reg = <Registry>::default();
+        if let Some(lag) = self.sync_lag_seconds {
+            metrics::add_gauge(&mut reg, SYNC_LAG, lag as u64);
+        }
+        if let Some(len) = self.search_index_queue_len {
+            metrics::add_gauge(&mut reg, SEARCH_INDEX_QUEUE_LEN, len);
+        }
+        if let Some(pending) = self.pending_migrations {
+            metrics::add_gauge(&mut reg, PENDING_MIGRATIONS, pending);
+        }
+        if let Some(sessions) = self.num_user_sessions {
+            metrics::add_gauge(&mut reg, NUM_USER_SESSIONS, sessions);
+        }
+        let latency = Gauge::<f64, std::sync::atomic::AtomicU64>::default();
+        latency.set(self.db_latency_seconds);
+        metrics::add_any(&mut reg, DB_LATENCY, Box::new(latency));
+
+        // Writing into a `Vec` cannot fail, see `Metrics::gather_and_encode`.
+        let mut out = Vec::new();
+        encode(&mut out, &reg).expect("failed to encode Prometheus metrics");
+        String::from_utf8(out).expect("Prometheus encoding is not valid UTF-8")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Probe;
+
+    #[test]
+    fn encodes_prometheus_format() {
+        let probe = Probe {
+            sync_lag_seconds: Some(12.7),
+            search_index_queue_len: Some(3),
+            pending_migrations: Some(0),
+            num_user_sessions: None,
+            db_latency_seconds: 0.5,
+        };
+        let out = probe.encode();
+        assert!(out.contains("\ntobira_sync_lag_seconds 12\n"), "{out}");
+        assert!(out.contains("\ntobira_search_index_queue_len 3\n"), "{out}");
+        assert!(out.contains("\ntobira_pending_migrations 0\n"), "{out}");
+        assert!(out.contains("\ntobira_db_latency_seconds 0.5\n"), "{out}");
+        assert!(!out.contains("tobira_num_user_sessions"), "{out}");
+    }
+}
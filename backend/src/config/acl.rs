@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+use super::TranslatedString;
+
+
+/// Placeholder in `acl.presets` that is replaced by the user role of the user
+/// applying the preset.
+pub(crate) const USER_ROLE_PLACEHOLDER: &str = "$USER_ROLE";
+
+#[derive(Debug, confique::Config)]
+pub(crate) struct AclConfig {
+    /// Presets that users can choose from when changing the access rights of
+    /// a video, instead of entering roles manually. Each preset expands to a
+    /// set of roles that are allowed to read and write the video. The special
+    /// role "$USER_ROLE" is replaced by the user role of the user applying
+    /// the preset (see `user_role_prefix`). Example:
+    ///
+    ///     [[acl.presets]]
+    ///     id = "only-me"
+    ///     label = { en = "Only me", de = "Nur ich" }
+    ///     read_roles = ["$USER_ROLE"]
+    ///     write_roles = ["$USER_ROLE"]
+    ///
+    /// Applying a preset sends the expanded roles to Opencast and stores them
+    /// in Tobira. Changing a preset later does not change the access rights
+    /// of videos it was applied to.
+    #[config(default = [])]
+    pub(crate) presets: Vec<AclPreset>,
+
+    /// Human-readable names for roles, shown instead of the raw role strings
+    /// when displaying access rights. Example:
+    ///
+    ///     [acl.role_display_names]
+    ///     ROLE_ANONYMOUS = { en = "Everyone", de = "Alle" }
+    ///     ROLE_GROUP_XY_2024 = { en = "Course XY (2024)", de = "Kurs XY (2024)" }
+    #[config(default = {})]
+    pub(crate) role_display_names: HashMap<String, TranslatedString>,
+
+    /// Prefix of the role that is unique to each user (the "user role"),
+    /// which is used for "$USER_ROLE" in presets.
+    #[config(default = "ROLE_USER_")]
+    pub(crate) user_role_prefix: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct AclPreset {
+    /// Unique identifier of the preset, used to apply it via the API.
+    pub(crate) id: String,
+    pub(crate) label: TranslatedString,
+    pub(crate) read_roles: Vec<String>,
+    pub(crate) write_roles: Vec<String>,
+}
+
+impl AclConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for preset in &self.presets {
+            if preset.id.is_empty() {
+                bail!("`acl.presets`: `id` must not be empty");
+            }
+            if !ids.insert(&preset.id) {
+                bail!("`acl.presets`: id '{}' is used for multiple presets", preset.id);
+            }
+            if preset.write_roles.is_empty() {
+                bail!("`acl.presets`: preset '{}' has no `write_roles`, so nobody could \
+                    change the access rights after applying it", preset.id);
+            }
+            if preset.read_roles.iter().chain(&preset.write_roles).any(|r| r.is_empty()) {
+                bail!("`acl.presets`: preset '{}' contains an empty role", preset.id);
+            }
+        }
+
+        if self.user_role_prefix.is_empty() {
+            bail!("`acl.user_role_prefix` must not be empty");
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn preset(&self, id: &str) -> Option<&AclPreset> {
+        self.presets.iter().find(|preset| preset.id == id)
+    }
+}
@@ -1,5 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, time::Duration};
 
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use tokio_postgres::GenericClient;
+
+use crate::prelude::*;
 use super::TranslatedString;
 
 
@@ -10,6 +14,14 @@ pub(crate) struct GeneralConfig {
     // TODO: fix automatically generated `site_title =` template output.
     pub(crate) site_title: TranslatedString,
 
+    /// Name of this Tobira instance, e.g. "production" or "staging". If set,
+    /// destructive commands (`db clear`, `db reset` and `db restore`) require
+    /// the operator to type this name (or pass it via `--instance`) before
+    /// doing anything, in addition to any other confirmation. That way, a
+    /// command copied from a runbook for another instance does not run by
+    /// accident.
+    pub(crate) instance_name: Option<String>,
+
     /// Links that are shown in the footer.
     ///
     /// By overwriting this default value, you can remove the default links and
@@ -66,11 +78,93 @@ pub(crate) struct GeneralConfig {
     /// Example: ["/Shibboleth.sso", "/something-else"]
     #[config(default = [])]
     pub reserved_paths: Vec<String>,
+
+    /// The maximum number of entries shown in a "table of contents" block.
+    /// If the realm subtree is larger than that, the deepest entries are cut
+    /// off and a note about that is shown instead.
+    #[config(default = 200)]
+    pub toc_block_max_entries: u32,
+
+    /// The maximum size of the Markdown content of a text block in bytes.
+    /// Saving a larger text block fails with an error.
+    #[config(default = 32768)]
+    pub text_block_max_size: u32,
+
+    /// Whether the realm data shown in the navigation is cached in-process
+    /// for a few seconds. Changes made on other Tobira nodes might take up
+    /// to 10 seconds to show up in the navigation. Only disable this for
+    /// debugging.
+    #[config(default = true)]
+    pub cache_navigation: bool,
+
+    /// The languages users can choose from, in order of preference. The
+    /// language is negotiated with the `Accept-Language` header of the
+    /// browser; if none of its languages is offered, the first one is used.
+    /// Supported languages: "en", "de".
+    #[config(default = ["en", "de"])]
+    pub languages: Vec<String>,
+
+    /// Whether playback positions of logged-in users are stored, so that they
+    /// can continue watching videos where they left off, also on other
+    /// devices. Positions are stored in Tobira's DB with the username.
+    #[config(default = false)]
+    pub continue_watching: bool,
+
+    /// How long stored playback positions are kept after they were last
+    /// changed. Only relevant if `continue_watching` is enabled.
+    #[config(default = "90d", deserialize_with = crate::config::deserialize_duration)]
+    pub playback_position_retention: Duration,
+
+    #[config(nested)]
+    pub initial_page: InitialPageConfig,
+}
+
+/// The page shown when users visit Tobira's root path `/`. Possible values:
+/// - "default": the root realm.
+/// - "realm:<path>": the realm with the given path, e.g. "realm:/lectures".
+/// - "my_videos": the "My videos" page. Requires login, so it can only be
+///   used for authenticated users.
+#[derive(Debug, confique::Config)]
+pub(crate) struct InitialPageConfig {
+    /// Initial page for users that are not logged in.
+    #[config(default = "default")]
+    pub(crate) anonymous: InitialPage,
+
+    /// Initial page for logged-in users.
+    #[config(default = "default")]
+    pub(crate) authenticated: InitialPage,
 }
 
 const INTERNAL_RESERVED_PATHS: &[&str] = &["favicon.ico", "robots.txt", ".well-known"];
 
+/// Characters encoded in realm path segments, like `encodeURIComponent` in
+/// the frontend does.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-').remove(b'_').remove(b'.').remove(b'!').remove(b'~')
+    .remove(b'*').remove(b'\'').remove(b'(').remove(b')');
+
 impl GeneralConfig {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.languages.is_empty() {
+            bail!("`general.languages` must not be empty");
+        }
+        let supported = TranslatedString::LANGUAGES;
+        if let Some(lang) = self.languages.iter().find(|l| !supported.contains(&l.as_str())) {
+            bail!("'{lang}' in `general.languages` is not a supported language \
+                (supported: {supported:?})");
+        }
+
+        // Anonymous users would be sent to the login page, which, after
+        // logging in, returns them to the page they came from.
+        if self.initial_page.anonymous.requires_login() {
+            bail!("`general.initial_page.anonymous` is '{}', which requires login and would \
+                redirect anonymous users to the login page on every visit",
+                self.initial_page.anonymous);
+        }
+
+        Ok(())
+    }
+
     /// Returns an iterator over all reserved top-level paths without leading slash.
     pub(crate) fn reserved_paths(&self) -> impl Iterator<Item = &str> {
         self.reserved_paths
@@ -80,6 +174,106 @@ impl GeneralConfig {
     }
 }
 
+impl InitialPageConfig {
+    /// Returns the initial page for anonymous or logged-in users.
+    pub(crate) fn for_user(&self, logged_in: bool) -> &InitialPage {
+        if logged_in { &self.authenticated } else { &self.anonymous }
+    }
+
+    /// Logs a warning for each configured realm that does not exist (yet).
+    /// This is not an error, as the realm might be created later.
+    pub(crate) async fn warn_about_missing_realms(&self, db: &impl GenericClient) -> Result<()> {
+        let pages = [("anonymous", &self.anonymous), ("authenticated", &self.authenticated)];
+        for (key, page) in pages {
+            let InitialPage::Realm(path) = page else {
+                continue;
+            };
+            let exists = db
+                .query_one("select exists(select from realms where full_path = $1)", &[path])
+                .await?
+                .get::<_, bool>(0);
+            if !exists {
+                warn!("`general.initial_page.{key}` refers to realm '{path}', which does \
+                    not exist. Users will see a 'not found' page until it is created.");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum InitialPage {
+    Default,
+    /// Path of a realm, starting with `/`.
+    Realm(String),
+    MyVideos,
+}
+
+impl InitialPage {
+    /// Path of the "My videos" page in the frontend.
+    const MY_VIDEOS_PATH: &'static str = "/~manage/videos";
+
+    /// The frontend route of this page.
+    pub(crate) fn route(&self) -> String {
+        match self {
+            Self::Default => "/".into(),
+            Self::Realm(path) => path.split('/')
+                .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Self::MyVideos => Self::MY_VIDEOS_PATH.into(),
+        }
+    }
+
+    fn requires_login(&self) -> bool {
+        matches!(self, Self::MyVideos)
+    }
+}
+
+impl TryFrom<String> for InitialPage {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "default" => return Ok(Self::Default),
+            "my_videos" => return Ok(Self::MyVideos),
+            _ => {}
+        }
+
+        let Some(path) = s.strip_prefix("realm:") else {
+            bail!("invalid initial page '{s}': must be \"default\", \"my_videos\" \
+                or \"realm:<path>\"");
+        };
+        if !path.starts_with('/') {
+            bail!("invalid realm path '{path}': must start with '/'");
+        }
+        let segments = path.trim_end_matches('/').split('/').skip(1).collect::<Vec<_>>();
+        if segments.iter().any(|s| s.is_empty()) {
+            bail!("invalid realm path '{path}': contains empty segment");
+        }
+        // Paths starting with these are routes of other pages, never realms.
+        if segments.first().is_some_and(|s| s.starts_with(['~', '!'])) {
+            bail!("invalid realm path '{path}': realm paths cannot start with '~' or '!'");
+        }
+
+        match path {
+            "/" => Ok(Self::Default),
+            _ => Ok(Self::Realm(path.trim_end_matches('/').to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for InitialPage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Realm(path) => write!(f, "realm:{path}"),
+            Self::MyVideos => write!(f, "my_videos"),
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(untagged)]
 pub(crate) enum FooterLink {
@@ -6,9 +6,10 @@ use std::{
 };
 use confique::Config as _;
 
-use crate::prelude::*;
+use crate::{prelude::*, http::access_log::AccessLogTarget};
 
 
+mod acl;
 mod color;
 mod general;
 mod theme;
@@ -16,6 +17,7 @@ mod translated_string;
 mod opencast;
 
 pub(crate) use self::{
+    acl::{AclConfig, AclPreset, USER_ROLE_PLACEHOLDER},
     color::{Color, Hsl},
     translated_string::TranslatedString,
     theme::ThemeConfig,
@@ -66,6 +68,9 @@ pub(crate) struct Config {
     #[config(nested)]
     pub(crate) auth: crate::auth::AuthConfig,
 
+    #[config(nested)]
+    pub(crate) acl: AclConfig,
+
     #[config(nested)]
     pub(crate) log: crate::logger::LogConfig,
 
@@ -75,6 +80,9 @@ pub(crate) struct Config {
     #[config(nested)]
     pub(crate) sync: crate::sync::SyncConfig,
 
+    #[config(nested)]
+    pub(crate) federation: crate::sync::federation::FederationConfig,
+
     #[config(nested)]
     pub(crate) meili: crate::search::MeiliConfig,
 
@@ -122,15 +130,40 @@ impl Config {
     /// illegal or conflicting values.
     fn validate(&self) -> Result<()> {
         debug!("Validating configuration...");
-        self.opencast.validate()?;
-        self.db.validate()?;
+        match self.validation_errors().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
 
-        Ok(())
+    /// Like `validate`, but returns all problems instead of just the first.
+    pub(crate) fn validation_errors(&self) -> Vec<anyhow::Error> {
+        [
+            self.general.validate(),
+            self.acl.validate(),
+            self.opencast.validate(),
+            self.db.validate(),
+        ]
+            .into_iter()
+            .filter_map(Result::err)
+            .collect()
+    }
+
+    /// Returns all files referenced by the configuration that Tobira needs to
+    /// read, together with the config key referencing them.
+    pub(crate) fn referenced_files(&self) -> Vec<(&'static str, &Path)> {
+        let mut files = vec![
+            ("theme.favicon", &*self.theme.favicon),
+            ("auth.jwt.secret_key", &*self.auth.jwt.secret_key),
+            ("theme.logo.large.path", &*self.theme.logo.large.path),
+        ];
+        files.extend(self.theme.logo.small.as_ref().map(|l| ("theme.logo.small.path", &*l.path)));
+        files
     }
 
     /// Goes through all paths in the configuration and changes relative paths
     /// to be absolute based on the path of the configuration file itself.
-    fn fix_paths(&mut self, config_path: &Path) -> Result<()> {
+    pub(crate) fn fix_paths(&mut self, config_path: &Path) -> Result<()> {
         fn fix_path(base_path: &Path, path: &mut PathBuf) {
             if path.is_relative() {
                 *path = base_path.join(&path);
@@ -149,6 +182,9 @@ impl Config {
         if let Some(p) = &mut self.log.file {
             fix_path(&base, p);
         }
+        if let Some(AccessLogTarget::File(p)) = &mut self.log.access_log {
+            fix_path(&base, p);
+        }
 
         fix_path(&base, &mut self.theme.logo.large.path);
         if let Some(logo) = &mut self.theme.logo.small {
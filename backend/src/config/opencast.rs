@@ -5,7 +5,7 @@ use serde::Deserialize;
 
 use crate::{
     prelude::*,
-    util::HttpHost,
+    util::{HttpHost, INSECURE_HTTP_SAFE_WORD},
 };
 
 
@@ -24,9 +24,17 @@ pub(crate) struct OpencastConfig {
     pub(crate) host: Option<HttpHost>,
 
     /// Explicitly set Opencast node used for data synchronization. The Tobira
-    /// module needs to run on this node.
+    /// module needs to run on this node. In clusters, this is usually the
+    /// admin node.
     pub(crate) sync_node: Option<HttpHost>,
 
+    /// Explicitly set Opencast node that serves the published data (usually
+    /// called "presentation" or "engage" node). If neither this nor `host` is
+    /// set, `sync_node` is used.
+    ///
+    /// Example: "https://engage.oc.my-uni.edu".
+    pub(crate) presentation_node: Option<HttpHost>,
+
     /// Explicitly set Opencast node used for the video uploader. Has to offer
     /// the ingest API.
     pub(crate) upload_node: Option<HttpHost>,
@@ -40,16 +48,28 @@ pub(crate) struct OpencastConfig {
     ///
     /// Example: "https://admin.oc.my-uni.edu/editor-ui/index.html".
     pub(crate) editor_url: Option<ToolBaseUri>,
+
+    /// Hosts of protected Opencast publication channels (e.g. a "static file"
+    /// channel requiring Opencast authentication). Track URLs on these hosts are
+    /// rewritten to `/~proxy/track/...`: for each request, Tobira checks the
+    /// event's ACL and then streams the file from Opencast, authenticating with
+    /// `sync.user` and `sync.password`. Note that this routes all video traffic
+    /// of these tracks through Tobira! By default, no tracks are proxied.
+    ///
+    /// Example: ["https://static.oc.my-uni.edu"]
+    #[config(default = [])]
+    pub(crate) proxy_track_hosts: Vec<HttpHost>,
 }
 
 impl OpencastConfig {
     pub(crate) fn validate(&self) -> Result<()> {
+        // `presentation_node` falls back to `sync_node`, so it is not required.
         let all_overrides_set = self.sync_node.is_some()
             && self.upload_node.is_some()
             && self.studio_url.is_some()
             && self.editor_url.is_some();
 
-        if self.host.is_some() && all_overrides_set {
+        if self.host.is_some() && all_overrides_set && self.presentation_node.is_some() {
             bail!("If all overrides in `opencast` are set, `opencast.host` is \
                 unused and thus must be unset");
         }
@@ -64,6 +84,12 @@ impl OpencastConfig {
         self.sync_node.as_ref().unwrap_or_else(|| self.unwrap_host())
     }
 
+    pub(crate) fn presentation_node(&self) -> &HttpHost {
+        self.presentation_node.as_ref()
+            .or(self.host.as_ref())
+            .unwrap_or_else(|| self.sync_node())
+    }
+
     pub(crate) fn upload_node(&self) -> &HttpHost {
         self.upload_node.as_ref().unwrap_or_else(|| self.unwrap_host())
     }
@@ -103,8 +129,9 @@ impl OpencastConfig {
     }
 }
 
-/// A base URL for tools like Studio or the editor. A URI without query and
-/// fragment.
+/// A base URL for tools like Studio or the editor. An absolute HTTP(S) URI
+/// without query and fragment. Like for `HttpHost`, HTTP is only allowed for
+/// local hosts, unless explicitly confirmed.
 #[derive(Clone, Deserialize)]
 #[serde(try_from = "String")]
 pub(crate) struct ToolBaseUri(pub(crate) Uri);
@@ -132,6 +159,10 @@ impl TryFrom<String> for ToolBaseUri {
 impl FromStr for ToolBaseUri {
     type Err = anyhow::Error;
     fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let (src, allow_insecure) = match src.strip_suffix(INSECURE_HTTP_SAFE_WORD) {
+            Some(src) => (src, true),
+            None => (src, false),
+        };
         let uri = src.parse::<hyper::http::uri::Uri>()?;
         if uri.query().is_some() {
             bail!("URL cannot have a query component!");
@@ -147,6 +178,36 @@ impl FromStr for ToolBaseUri {
             bail!("URL cannot have a fragment component!");
         }
 
+        // Scheme and host are checked like for `opencast.host`.
+        let (Some(scheme), Some(authority)) = (uri.scheme(), uri.authority()) else {
+            bail!("URL has to be absolute, e.g. 'https://admin.oc.my-uni.edu/studio'");
+        };
+        let safe_word = if allow_insecure { INSECURE_HTTP_SAFE_WORD } else { "" };
+        format!("{scheme}://{authority}{safe_word}").parse::<HttpHost>()?;
+
         Ok(Self(uri))
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::ToolBaseUri;
+
+    #[test]
+    fn tool_base_uri() {
+        let parse = |s: &str| s.parse::<ToolBaseUri>().map(|uri| uri.to_string());
+        assert_eq!(parse("https://oc.my-uni.edu/studio").unwrap(), "https://oc.my-uni.edu/studio");
+        assert_eq!(parse("http://localhost:8080/studio").unwrap(), "http://localhost:8080/studio");
+        assert_eq!(
+            parse("http://oc.my-uni.edu/studio#allow-insecure").unwrap(),
+            "http://oc.my-uni.edu/studio",
+        );
+
+        assert!(parse("/studio").is_err());
+        assert!(parse("http://oc.my-uni.edu/studio").is_err());
+        assert!(parse("ftp://oc.my-uni.edu/studio").is_err());
+        assert!(parse("https://oc.my-uni.edu/studio?a=b").is_err());
+        assert!(parse("https://oc.my-uni.edu/studio#foo").is_err());
+    }
+}
@@ -18,6 +18,16 @@ impl TranslatedString {
     pub(crate) fn en(&self) -> &str {
         &self.0["en"]
     }
+
+    /// Returns the string in the given language, falling back to English.
+    pub(crate) fn get(&self, lang: &str) -> &str {
+        self.translation(lang).unwrap_or(self.en())
+    }
+
+    /// Returns the string in the given language, if specified.
+    pub(crate) fn translation(&self, lang: &str) -> Option<&str> {
+        self.0.get(lang).map(String::as_str)
+    }
 }
 
 impl<'de> Deserialize<'de> for TranslatedString {
@@ -4,12 +4,77 @@ use std::{
     path::{Path, PathBuf},
     process::Command,
 };
-use tokio_postgres::IsolationLevel;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use regex::Regex;
+use tokio_postgres::{IsolationLevel, SimpleQueryMessage};
 
 use secrecy::ExposeSecret;
 
 use crate::{prelude::*, util::Never, config::Config, search::writer::MeiliWriter};
-use super::{Db, DbConfig, create_pool, query, migrations::unsafe_overwrite_migrations};
+use self::{
+    audit_trail::AuditAction,
+    column_encryption::EncryptionAlgorithm,
+    session_stats::GroupBy,
+    summary::Summary,
+};
+use super::{
+    Db, DbConfig, MigrateOptions, create_pool, query,
+    migrations::unsafe_overwrite_migrations,
+};
+
+
+mod audit_trail;
+mod cache_invalidate;
+mod column_add_default;
+mod column_encryption;
+mod column_histogram;
+mod column_rename;
+mod column_type_change;
+mod constraints;
+mod copy;
+mod dead_tuple_check;
+mod disk_projection;
+mod doc;
+mod event_replay;
+mod foreign_key_index_check;
+mod generate_schema_snapshot;
+mod generate_test_fixtures;
+mod lock_wait_alert;
+mod migration_annotate_performance;
+mod migration_coverage_report;
+mod migration_estimate_time;
+mod migration_export;
+mod migration_precondition_check;
+mod migration_signature_chain;
+mod pg_activity;
+mod pg_checkpoint_stats;
+mod pg_hba_test;
+mod pg_log_tail;
+mod pg_logical_slot;
+mod pg_parameter_tuning;
+mod point_in_time_restore;
+mod query_frequency;
+mod query_heatmap;
+mod query_plan_regression_test;
+mod replica_lag_check;
+mod row_level_security_check;
+mod s3_dump;
+mod search_sync_status;
+mod session_stats;
+mod snapshot_isolation_test;
+mod squash_migrations;
+mod summary;
+mod table_bloat_estimate;
+mod table_grant_matrix;
+mod table_owner_check;
+mod tablespace_usage;
+mod timeline;
+mod wait_for_version;
+mod write_amplification;
+mod zero_downtime_check;
+
+pub(crate) use self::summary::OutputFormat;
 
 
 #[derive(Debug, clap::Subcommand)]
@@ -28,7 +93,27 @@ pub(crate) enum DbCommand {
 
     /// Runs the database migrations that also automatically run when starting
     /// the server.
-    Migrate,
+    Migrate {
+        #[clap(flatten)]
+        options: MigrateOptions,
+    },
+
+    /// Waits until the database has been migrated to at least the given
+    /// version (i.e. the migration with that ID has been applied). Useful in
+    /// deployments where migrations are run by a separate job before the
+    /// Tobira nodes start. Exits with an error if the timeout is reached.
+    WaitForVersion {
+        /// The migration ID to wait for.
+        version: u32,
+
+        /// How long to wait at most, in seconds.
+        #[clap(long, default_value = "300")]
+        timeout_secs: u64,
+
+        /// How long to wait between checks, in milliseconds.
+        #[clap(long, default_value = "1000")]
+        poll_interval_ms: u64,
+    },
 
     /// Connects to the database and gives you an SQL prompt.
     /// This just starts the `psql` client, so make sure that is installed
@@ -43,8 +128,25 @@ pub(crate) enum DbCommand {
     ///
     /// This can be used while Tobira is running and reading/writing the database,
     /// and will still yield consistent results!
+    ///
+    /// With `--s3`, the dump is not written to a local file but streamed
+    /// directly to S3-compatible object storage as a multipart upload, see
+    /// `db.s3` in the configuration. Failed parts are retried and the upload
+    /// is aborted (removing all uploaded parts) if the dump fails.
     Dump {
-        path: PathBuf,
+        /// Local file to write the dump to.
+        #[clap(required_unless_present = "s3")]
+        path: Option<PathBuf>,
+
+        /// Upload the dump to this S3 location instead, e.g.
+        /// `s3://bucket/backups/`. If it ends with `/`, a file name containing
+        /// the current time is appended.
+        #[clap(long, conflicts_with = "path")]
+        s3: Option<String>,
+
+        /// Storage class of the uploaded object, e.g. `STANDARD_IA`.
+        #[clap(long, requires = "s3")]
+        storage_class: Option<String>,
     },
 
     /// Restore Tobira's database from a dump created by the `db dump` command.
@@ -57,8 +159,37 @@ pub(crate) enum DbCommand {
     /// Specifically that means you will lose data if the restoration fails!
     /// It also means that it can't be run while there are connections to the DB,
     /// e.g. when Tobira is running.
+    ///
+    /// With `--point-in-time`, this instead performs a point-in-time recovery:
+    /// `dump` then has to be a physical base backup created by
+    /// `pg_basebackup` (in plain format), which is copied into the empty
+    /// directory `--data-dir`. Postgres is then started on that directory and
+    /// replays the WAL files from `--wal-archive` up to the given time. This
+    /// has to be run on the database server as the OS user owning the data
+    /// directory, and requires WAL archiving (`archive_mode`) to have been
+    /// enabled since the base backup was taken.
     Restore {
         dump: PathBuf,
+
+        /// The configured `general.instance_name`, if set. Without this, the
+        /// name is asked for interactively.
+        #[clap(long, value_name = "NAME")]
+        instance: Option<String>,
+
+        /// Recover the database to its state at this time (e.g.
+        /// `2022-08-01T12:30:00Z`) instead of restoring a `db dump` dump.
+        #[clap(long)]
+        point_in_time: Option<DateTime<Utc>>,
+
+        /// Empty data directory for the recovered cluster. Required with
+        /// `--point-in-time`.
+        #[clap(long, requires = "point-in-time")]
+        data_dir: Option<PathBuf>,
+
+        /// Directory containing the archived WAL files. Required with
+        /// `--point-in-time`.
+        #[clap(long, requires = "point-in-time")]
+        wal_archive: Option<PathBuf>,
     },
 
     /// Equivalent to `db clear` followed by `db migrate`.
@@ -72,6 +203,747 @@ pub(crate) enum DbCommand {
     /// the table, but might delete unknown migrations. This is intended for
     /// developers only, do not use if you don't know what you're doing!
     UnsafeOverwriteMigrations,
+
+    /// Squashes all migrations starting at `--from` into a single migration
+    /// script. This is intended for developers only and is only available in
+    /// debug builds!
+    ///
+    /// The squashed script is verified to produce the exact same schema as
+    /// the individual migrations (by applying both to scratch schemas and
+    /// comparing `pg_dump --schema-only` output), then written to `--out`.
+    /// Finally, the squashed entries in `__db_migrations` are replaced by a
+    /// single one for the new script. Requires the database to be up to date.
+    SquashMigrations {
+        /// ID of the first migration to squash. All migrations from this one
+        /// to the latest one are squashed.
+        #[clap(long)]
+        from: u64,
+
+        /// Name of the resulting migration (e.g. `feature-foo`).
+        #[clap(long)]
+        name: String,
+
+        /// File to write the squashed migration script to.
+        #[clap(long)]
+        out: PathBuf,
+    },
+
+    /// Checks that all foreign key columns are covered by an index.
+    ///
+    /// Unindexed foreign keys cause sequential scans on cascading deletes and
+    /// joins. For each missing index, the impact (based on the table size)
+    /// and a `create index` statement to fix it are printed. Exits with an
+    /// error if any foreign key is not indexed.
+    ForeignKeyIndexCheck,
+
+    /// Lists tables with a high ratio of dead tuples, which likely need a
+    /// `VACUUM`. Also shows when autovacuum last processed these tables and
+    /// suggests tuning if autovacuum seems to miss them.
+    DeadTupleCheck {
+        /// Percentage of dead tuples (relative to all tuples) above which a
+        /// table is listed.
+        #[clap(long, default_value = "20")]
+        threshold_pct: f64,
+    },
+
+    /// Adds a new `not null` column with a default value to an existing table
+    /// while keeping exclusive locks as short as possible.
+    ///
+    /// Adds the column with the given default (on PostgreSQL < 11, the column
+    /// is added as nullable and existing rows are backfilled in batches), then
+    /// makes it `not null` via a separately validated check constraint and
+    /// finally drops the default again. All executed statements are printed.
+    ColumnAddDefault {
+        /// Name of the table.
+        #[clap(long)]
+        table: String,
+
+        /// Name of the new column.
+        #[clap(long)]
+        column: String,
+
+        /// SQL type of the new column, e.g. `bigint`.
+        #[clap(long = "type")]
+        type_: String,
+
+        /// SQL expression for the value of existing rows, e.g. `0`.
+        #[clap(long)]
+        default: String,
+    },
+
+    /// Changes the type of a column without rewriting the table while holding
+    /// an exclusive lock (as `alter column ... type` would do).
+    ///
+    /// Adds a shadow column with the new type that a trigger keeps in sync
+    /// with the old one, backfills existing rows in batches and finally
+    /// drops the old column and renames the shadow column in one short
+    /// transaction. `not null`, the default and the comment are carried
+    /// over. Fails early if other objects (e.g. indexes, constraints or
+    /// views) depend on the column. All executed statements are printed.
+    ColumnTypeChange {
+        /// Name of the table.
+        #[clap(long)]
+        table: String,
+
+        /// Name of the column.
+        #[clap(long)]
+        column: String,
+
+        /// SQL type the column should have, e.g. `bigint`.
+        #[clap(long)]
+        new_type: String,
+
+        /// SQL expression computing the new value from the row, like
+        /// `alter column ... using`. Defaults to a cast of the column.
+        #[clap(long)]
+        using: Option<String>,
+    },
+
+    /// Renames a column and the references to it in Tobira's source code.
+    /// This is intended for developers only and is only available in debug
+    /// builds!
+    ///
+    /// Prints the migration renaming the column. With `--update-source`, the
+    /// migration is added to the source tree and the column name is replaced
+    /// in all string literals in the Rust source that also mention the table.
+    /// As this is a heuristic, all source changes are printed as a diff and
+    /// have to be reviewed. The database has to be migrated to the latest
+    /// version.
+    ColumnRename {
+        /// Name of the table.
+        #[clap(long)]
+        table: String,
+
+        /// Current name of the column.
+        #[clap(long)]
+        old_name: String,
+
+        /// New name of the column.
+        #[clap(long)]
+        new_name: String,
+
+        /// Add the migration and update references in the source code.
+        #[clap(long)]
+        update_source: bool,
+    },
+
+    /// Generates markdown documentation of the database schema (tables,
+    /// columns, foreign keys, indexes and a relationship diagram) from the
+    /// live database. Run `db migrate` first.
+    ///
+    /// Also checks that every table and column has a comment (set via
+    /// `comment on` in migrations) and exits with an error if not.
+    Doc {
+        /// Directory to write the markdown files to. Created if it does not
+        /// exist yet.
+        #[clap(long)]
+        out: PathBuf,
+    },
+
+    /// Measures how many tuple writes (in all tables) are caused by inserting
+    /// and deleting rows in the given table, e.g. due to triggers. A factor
+    /// considerably larger than 1 hints at expensive trigger logic.
+    ///
+    /// Rows are inserted with `default values` and everything is done in a
+    /// transaction that is rolled back at the end, so no data is modified.
+    WriteAmplification {
+        /// The table to insert into and delete from.
+        #[clap(long)]
+        test_table: String,
+
+        /// Number of rows to insert and delete.
+        #[clap(long, default_value = "100")]
+        iterations: u32,
+    },
+
+    /// Checks tables with row level security (RLS) enabled for policies that
+    /// are likely misconfigured, e.g. policies that allow access to all rows
+    /// or that are bypassed because Tobira's DB user owns the table. Tobira
+    /// itself does not define RLS policies; this is for policies added by
+    /// administrators. Exits with an error if problems are found.
+    RowLevelSecurityCheck,
+
+    /// Checks that serializable transactions, which Tobira uses for critical
+    /// operations, actually prevent anomalies. Useful after upgrading or
+    /// reconfiguring PostgreSQL.
+    ///
+    /// Runs known patterns of two concurrent transactions (write skew,
+    /// phantom read) on a scratch table and verifies that PostgreSQL aborts
+    /// one of them with a serialization failure. Exits with an error if an
+    /// anomaly is not prevented.
+    SnapshotIsolationTest,
+
+    /// Shows the queries executed most often, in calls per second. Fast but
+    /// frequent queries can dominate the total load of the database.
+    ///
+    /// Samples `pg_stat_statements` twice, `--window-secs` apart, and ranks
+    /// queries by the increase of their call count. Requires the extension
+    /// `pg_stat_statements` to be installed.
+    QueryFrequency {
+        /// Number of queries to show.
+        #[clap(long, default_value = "10")]
+        top: usize,
+
+        /// Time between the two samples, in seconds.
+        #[clap(long, default_value = "60")]
+        window_secs: u64,
+    },
+
+    /// Runs the given migration against the current database and adds
+    /// comments with the number of affected rows, cost, execution time and
+    /// the strongest lock taken on existing tables (e.g. `-- estimated: 1M
+    /// rows, cost 25k, ~45s, locks: ROW EXCLUSIVE (events)`) to its statements
+    /// in the migration file. This is intended for developers only and is
+    /// only available in debug builds!
+    ///
+    /// The database has to be migrated up to the migration before the given
+    /// one. Everything is executed in a transaction that is rolled back, so
+    /// the database is not modified. Only annotate migrations that were not
+    /// applied anywhere yet, as changing the file changes the migration.
+    MigrationAnnotatePerformance {
+        /// ID of the migration to annotate.
+        version: u32,
+    },
+
+    /// Replays the changes from a logical replication log into the tables
+    /// of the given schema, e.g. to reproduce a bug from production in a test
+    /// environment. This is intended for developers only and is only
+    /// available in debug builds!
+    ///
+    /// The log has to be the output of the `wal2json` plugin with
+    /// `format-version` 2 (one JSON object per line). Each table in the log
+    /// is mapped to the table with the same name in `--target-schema`, which
+    /// has to be in the state the production database was in when the log
+    /// started. Each transaction from the log is replayed as one transaction.
+    EventReplay {
+        /// The `wal2json` output file.
+        replication_log: PathBuf,
+
+        /// Schema containing the tables to replay the changes into.
+        #[clap(long)]
+        target_schema: String,
+
+        /// Stop before the first transaction committed after this time (e.g.
+        /// `2022-08-01T12:00:00Z`). Requires `wal2json` to be run with
+        /// `include-timestamp`.
+        #[clap(long)]
+        stop_at: Option<DateTime<Utc>>,
+    },
+
+    /// Shows entries of the audit log (the table `__tobira_audit_log`),
+    /// newest last, e.g. for compliance checks or incident investigations.
+    /// Tobira does not write the audit log itself: it has to be filled by
+    /// audit triggers installed in the database.
+    AuditTrail {
+        /// Only show changes to this table.
+        #[clap(long)]
+        table: Option<String>,
+
+        /// Only show changes made by this user.
+        #[clap(long)]
+        user: Option<String>,
+
+        /// Only show changes made at or after this time (e.g.
+        /// `2022-08-01T12:00:00Z`).
+        #[clap(long)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only show changes of this kind.
+        #[clap(long, arg_enum)]
+        action: Option<AuditAction>,
+
+        /// Maximum number of entries to show. If there are more, the newest
+        /// ones are shown.
+        #[clap(long, default_value = "100")]
+        limit: usize,
+
+        /// Print the entries as JSON (including the full rows) instead of a
+        /// table.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Prints when each migration was applied, how long it took and which
+    /// Tobira version applied it, in chronological order. The output is
+    /// plain text suitable for incident reports. Durations and versions are
+    /// only known for migrations applied by Tobira versions that record them.
+    Timeline {
+        /// Only show migrations applied at or after this time (e.g.
+        /// `2022-08-01T12:00:00Z`).
+        #[clap(long)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only show migrations applied at or before this time.
+        #[clap(long)]
+        until: Option<DateTime<Utc>>,
+    },
+
+    /// Disables all triggers, including those checking foreign keys, e.g. for
+    /// bulk data imports that temporarily violate foreign keys. Without
+    /// `--table`, also sets `session_replication_role = 'replica'` for the
+    /// database user, affecting all sessions started afterwards. Requires
+    /// superuser privileges. Use `db constraint-enable` when done!
+    ConstraintDisable {
+        /// Only disable triggers of this table instead of all tables.
+        #[clap(long)]
+        table: Option<String>,
+    },
+
+    /// Re-enables the triggers disabled by `db constraint-disable`. Before
+    /// that, all foreign keys from or to the affected tables are checked. If
+    /// any are violated, nothing is changed and the command fails.
+    ConstraintEnable {
+        /// Only enable triggers of this table instead of all tables.
+        #[clap(long)]
+        table: Option<String>,
+    },
+
+    /// Estimates the size of each table and of the whole database in the
+    /// future by linear regression over the size history in the table
+    /// `__tobira_stats_history`, including 95% prediction intervals. At least
+    /// three snapshots are required, but more give better estimates.
+    DiskProjection {
+        /// How many days into the future to project.
+        #[clap(long, default_value = "365")]
+        days_ahead: u32,
+    },
+
+    /// Recommends values for important Postgres parameters based on the specs
+    /// of the database server and Tobira's typical queries, and compares them
+    /// to the current settings. Assumes the server is dedicated to Postgres.
+    PgParameterTuning {
+        /// RAM of the database server in GB.
+        #[clap(long)]
+        system_ram_gb: f64,
+
+        /// Number of CPU cores of the database server.
+        #[clap(long)]
+        cpu_count: u32,
+
+        /// Whether the database is stored on an SSD.
+        #[clap(long)]
+        ssd: bool,
+
+        /// Instead of comparing, print an SQL script with `alter system`
+        /// statements applying the recommendations.
+        #[clap(long)]
+        script: bool,
+    },
+
+    /// Shows the activity of all connections to Tobira's database (from
+    /// `pg_stat_activity`) as a continuously updated table, like `top`. The
+    /// selected backend can be killed by pressing `k`. Quit with `q`.
+    PgActivity {
+        /// Time between two updates, in seconds.
+        #[clap(long, default_value = "2")]
+        refresh_secs: f64,
+
+        /// Only show backends whose state, user, application name or query
+        /// contains this string (case-insensitive).
+        #[clap(long)]
+        filter: Option<String>,
+    },
+
+    /// Shows statistics about the connections to Tobira's database (from
+    /// `pg_stat_activity`), grouped by application name or client address:
+    /// the number of connections, how many of those are active, idle or idle
+    /// in a transaction, and the longest running query per group. Tobira
+    /// connects with the application name `Tobira (<subcommand>)`, e.g.
+    /// `Tobira (worker)`, so this shows which Tobira process uses the most
+    /// connections.
+    SessionStats {
+        /// How to group the connections.
+        #[clap(long, arg_enum, default_value = "application-name")]
+        group_by: GroupBy,
+    },
+
+    /// Verifies the migration history as a whole by computing a hash chain
+    /// over all applied migrations: each link hashes the previous one together
+    /// with the ID, name and checksum (SHA-256 of the script) of a migration.
+    /// Thus, tampering with any earlier migration changes the final chain
+    /// hash, even if later migrations are unchanged. Also shows the first
+    /// migration at which the chain deviates from the one of this Tobira
+    /// binary.
+    MigrationSignatureChain {
+        /// Published chain hash (hex) to compare against. If it does not match
+        /// the chain hash of the database, the command fails.
+        #[clap(long)]
+        expected: Option<String>,
+    },
+
+    /// Shows which privileges (S = `SELECT`, I = `INSERT`, U = `UPDATE`,
+    /// D = `DELETE`) each role has on each table, as a matrix with roles as
+    /// rows and tables as columns. Based on
+    /// `information_schema.role_table_grants`, so only grants to roles the
+    /// current user is a member of (or granted by it) are shown.
+    TableGrantMatrix {
+        /// Only show privileges granted to this role.
+        #[clap(long)]
+        role: Option<String>,
+
+        /// Print the matrix as CSV instead.
+        #[clap(long)]
+        csv: bool,
+    },
+
+    /// Estimates how long the given migrations (default: all pending ones)
+    /// will take on this database, e.g. to plan a maintenance window.
+    ///
+    /// Each statement is classified (simple DDL, index creation, table
+    /// rewrite, table scan or data modification) and its duration estimated
+    /// from the approximate row count of the affected table with a simple
+    /// cost model. The estimates come with a (generous) confidence interval,
+    /// but can still be off by a lot: use `db migration-annotate-performance`
+    /// on a copy of the database for actual measurements.
+    MigrationEstimateTime {
+        /// IDs of the migrations to estimate.
+        versions: Vec<u32>,
+    },
+
+    /// Estimates the bloat (space wasted by dead or deleted rows) of each
+    /// table, without requiring the `pgstattuple` extension.
+    ///
+    /// The expected size of each table is derived from its row count, the
+    /// average column widths from the planner statistics and the fillfactor,
+    /// and then compared to its actual size. The results are only
+    /// approximate: tables need to be analyzed and TOAST is ignored.
+    TableBloatEstimate {
+        /// Minimum estimated bloat (in percent of the table size) for a table
+        /// to be listed.
+        #[clap(long, default_value = "0")]
+        threshold_pct: f64,
+    },
+
+    /// Shows how many queries are executed per hour of the week (in UTC) as
+    /// a colored heatmap, e.g. to find a good time for maintenance windows.
+    ///
+    /// Requires regular snapshots in `__tobira_stats_history` (see
+    /// `disk-projection`) with a `query_count` column containing the
+    /// cumulative number of executed statements (e.g. the sum of `calls` in
+    /// `pg_stat_statements`). The queries between two snapshots are spread
+    /// evenly over the hours in between.
+    QueryHeatmap {
+        /// How many days of snapshots to consider.
+        #[clap(long, default_value = "28")]
+        days: u32,
+    },
+
+    /// Generates SQL `insert` statements with random data for the given
+    /// tables, e.g. as fixtures for integration tests.
+    ///
+    /// Values respect the column types, `not null`, unique constraints and
+    /// foreign keys: referenced rows are taken from the generated rows of the
+    /// other given tables (which are ordered accordingly) or from existing
+    /// rows in the database. Check constraints and triggers are not
+    /// considered. The output is deterministic for the same seed and schema.
+    GenerateTestFixtures {
+        /// File to write the SQL statements to.
+        #[clap(long)]
+        out: PathBuf,
+
+        /// Tables to generate rows for.
+        #[clap(required = true)]
+        tables: Vec<String>,
+
+        /// Number of rows per table.
+        #[clap(long, default_value = "10")]
+        n_rows: u32,
+
+        /// Seed for the random number generator.
+        #[clap(long, default_value = "0")]
+        seed: u64,
+    },
+
+    /// Creates a logical replication slot (`pg_create_logical_replication_slot`)
+    /// for streaming changes of this database, e.g. to a CDC pipeline, and
+    /// prints its confirmed LSN. Requires `wal_level = logical` and the
+    /// replication privilege. Note that, unlike `CREATE_REPLICATION_SLOT` of
+    /// the replication protocol, this does not export a snapshot.
+    PgLogicalSlotCreate {
+        /// Name of the slot.
+        name: String,
+
+        /// Output plugin used to decode the WAL.
+        #[clap(long, default_value = "pgoutput")]
+        plugin: String,
+    },
+
+    /// Drops a logical replication slot of this database
+    /// (`pg_drop_replication_slot`). Warns if the slot has WAL that was not
+    /// consumed yet. Requires `wal_level = logical`.
+    PgLogicalSlotDrop {
+        /// Name of the slot.
+        name: String,
+    },
+
+    /// Checks the preconditions of the given migrations (default: all pending
+    /// ones) without applying them. Migration scripts can declare conditions
+    /// on the existing data with `-- precondition: <query>` comment lines,
+    /// where the query returns `true` if the migration can be applied. These
+    /// are also checked before each migration is applied, aborting the
+    /// migration if one is not met.
+    MigrationPreconditionCheck {
+        /// IDs of the migrations to check.
+        versions: Vec<u32>,
+    },
+
+    /// Makes all running Tobira nodes clear their in-process caches, e.g.
+    /// after the database was modified directly. For each table, a
+    /// `NOTIFY tobira_cache_invalidate, '<table>'` is sent. If no Tobira node
+    /// is running, nothing is sent: the caches expire within a few seconds
+    /// anyway.
+    CacheInvalidate {
+        /// The tables that were modified (default: all cached ones, i.e.
+        /// announcements, blocks, events, realms and series).
+        #[clap(long, value_delimiter = ',')]
+        tables: Option<Vec<String>>,
+
+        /// Why the caches are invalidated. Logged for audit purposes.
+        #[clap(long)]
+        reason: String,
+    },
+
+    /// Checks whether `pg_hba.conf` allows the configured connection, by
+    /// finding the rule in `pg_hba_file_rules` that matches the configured
+    /// user, database and address. If none does, a line to add to
+    /// `pg_hba.conf` is suggested. Reading `pg_hba_file_rules` requires
+    /// superuser privileges or an explicit grant.
+    PgHbaTest {
+        /// Check the rules for this client address instead of the address of
+        /// this machine, e.g. if Tobira runs on a different host.
+        #[clap(long)]
+        address: Option<std::net::IpAddr>,
+    },
+
+    /// Shows the distribution of the values of a column as a bar chart, e.g.
+    /// to get a feeling for the data when writing queries. Numeric columns
+    /// are grouped into ranges of equal width between the minimum and
+    /// maximum value, all other columns by value, showing the most frequent
+    /// ones.
+    ColumnHistogram {
+        /// Name of the table.
+        table: String,
+
+        /// Name of the column.
+        column: String,
+
+        /// Number of ranges for numeric columns or number of values shown
+        /// for other columns.
+        #[clap(long, default_value = "10")]
+        buckets: u32,
+    },
+
+    /// Generates the schema snapshot (`schema.sql`) that empty databases are
+    /// set up with instead of running all migrations one by one. This is
+    /// intended for developers only and is only available in debug builds!
+    ///
+    /// All migrations are applied to a scratch database, which is then dumped
+    /// with `pg_dump`. This requires the `CREATEDB` privilege. Run this
+    /// whenever a migration is added or changed.
+    GenerateSchemaSnapshot {
+        /// File to write the snapshot to.
+        #[clap(long, default_value = "src/db/schema.sql")]
+        out: PathBuf,
+
+        /// Only check that the snapshot compiled into this binary was
+        /// generated from the migrations of this binary and exit with an
+        /// error if not, e.g. in CI. Does not connect to the database and
+        /// also works in release builds.
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Shows the disk usage of each tablespace: its location, its size, the
+    /// tables and indexes of Tobira stored in it, and how full the disk it is
+    /// on is. The latter only works if the database runs on this machine.
+    TablespaceUsage {
+        /// Disk usage (in percent) from which a disk is highlighted as almost
+        /// full.
+        #[clap(long, default_value = "90")]
+        threshold_pct: f64,
+    },
+
+    /// Summarizes how quickly the schema changed in a time range: the number
+    /// of migrations applied per day and the tables changed by most of them.
+    /// Note that this is based on when migrations were applied to this
+    /// database, not when they were written.
+    MigrationCoverageReport {
+        /// Only count migrations applied at or after this time (e.g.
+        /// `2022-08-01T12:00:00Z`).
+        #[clap(long)]
+        since: DateTime<Utc>,
+
+        /// Only count migrations applied at or before this time. Defaults to
+        /// now.
+        #[clap(long)]
+        until: Option<DateTime<Utc>>,
+    },
+
+    /// Prints the last lines of the log file Postgres currently writes to.
+    /// By default, only lines of Tobira's connections are shown (if the
+    /// application name is logged, e.g. via `%a` in `log_line_prefix`).
+    /// Requires the database to run on this machine, `logging_collector` to
+    /// be enabled, and privileges to read the log settings and files.
+    PgLogTail {
+        /// Number of lines to print.
+        #[clap(short = 'n', long, default_value = "50")]
+        lines: usize,
+
+        /// Keep printing new lines as they are written, like `tail -f`.
+        #[clap(short, long)]
+        follow: bool,
+
+        /// Only show lines matching this regex instead of lines of Tobira's
+        /// connections.
+        #[clap(long)]
+        filter: Option<Regex>,
+    },
+
+    /// Prints the script of an applied migration as stored in the database.
+    /// If it was archived via `db migrate --compress-logs`, it is read from
+    /// the archive instead and verified against the checksum in the database.
+    MigrationExport {
+        /// ID of the migration.
+        id: u64,
+
+        /// The archive file that was passed to `db migrate --compress-logs`.
+        #[clap(long)]
+        archive: Option<PathBuf>,
+    },
+
+    /// Applies all pending migrations and reports how that changed the query
+    /// plans of the given queries: changed plan nodes (e.g. a sequential
+    /// scan that became an index scan), estimated cost and join order. Fails
+    /// if the estimated cost of any query increased by more than 10%. The
+    /// queries are only explained, not executed, and must not contain
+    /// parameters like `$1`.
+    QueryPlanRegressionTest {
+        /// Path to a file containing the queries, separated by `;`.
+        queries_file: PathBuf,
+    },
+
+    /// Prints a migration that encrypts a column in place with pgcrypto: it
+    /// adds an encrypted `bytea` shadow column, backfills it, drops the
+    /// original column and renames the shadow column. Fails early if other
+    /// objects (e.g. indexes or views) depend on the column.
+    ///
+    /// The key is not part of the migration: it is read from the Postgres
+    /// setting `tobira.key_<key-ref>` when the migration is applied.
+    ColumnEncryption {
+        /// Name of the table.
+        #[clap(long)]
+        table: String,
+
+        /// Name of the column.
+        #[clap(long)]
+        column: String,
+
+        /// Name of the key (lowercase letters, digits and `_`).
+        #[clap(long)]
+        key_ref: String,
+
+        #[clap(long, arg_enum, default_value = "aes256")]
+        algorithm: EncryptionAlgorithm,
+    },
+
+    /// Checks whether the search index is in sync with the DB: compares the
+    /// number of events and realms in the DB with the number of documents in
+    /// the search index and checks when queued changes were last indexed.
+    /// Series have no documents of their own. Prints instructions and exits
+    /// with an error if the index is out of sync.
+    SearchSyncStatus {
+        /// Number of hours after which queued changes that were not indexed
+        /// yet are reported.
+        #[clap(long, default_value = "24")]
+        max_age_hours: u32,
+    },
+
+    /// Shows how many checkpoints Postgres performed and how long they took,
+    /// both since the statistics were last reset and within a sampling
+    /// window. If most checkpoints were requested (e.g. because
+    /// `max_wal_size` was reached) instead of timed, a lot of WAL is
+    /// generated and increasing `max_wal_size` is suggested.
+    PgCheckpointStats {
+        /// Time over which checkpoints are sampled, in seconds. With 0, only
+        /// the statistics since the last reset are shown.
+        #[clap(long, default_value = "300")]
+        window_secs: u64,
+    },
+
+    /// Checks how far a streaming replica lags behind the primary, by
+    /// comparing received and replayed WAL. Exits with an error if either
+    /// limit is exceeded, so that backup scripts taking a dump from the
+    /// replica can stop instead of backing up stale data. Connects with the
+    /// configured credentials, but to `--host`/`--port` if given.
+    ReplicaLagCheck {
+        /// Host of the replica. Defaults to `db.host`.
+        #[clap(long)]
+        host: Option<String>,
+
+        /// Port of the replica. Defaults to `db.port`.
+        #[clap(long)]
+        port: Option<u16>,
+
+        /// Maximum amount of received but not yet replayed WAL, in bytes.
+        #[clap(long, default_value = "16777216")]
+        max_lag_bytes: u64,
+
+        /// Maximum age of the last replayed transaction, in seconds. Ignored
+        /// if the replica has replayed everything it received.
+        #[clap(long, default_value = "60")]
+        max_lag_secs: u64,
+    },
+
+    /// Copies the whole database to another Tobira instance, e.g. to refresh
+    /// a staging system with production data. `pg_dump` is streamed directly
+    /// into `pg_restore` of the target database, replacing everything in it.
+    /// Afterwards, the data is optionally anonymized and a custom script is
+    /// run, and finally the target's search index is rebuilt (only with
+    /// `--to`). Refuses to copy to the same host as the source database.
+    ///
+    /// Like `db dump` and `db restore`, this requires `pg_dump` and
+    /// `pg_restore` compatible with both databases.
+    Copy {
+        /// Configuration file of the target instance.
+        #[clap(long, value_name = "CONFIG", required_unless_present = "target-uri")]
+        to: Option<PathBuf>,
+
+        /// Connection URI of the target database, e.g.
+        /// `postgresql://tobira:pw@staging-db/tobira`, as alternative to
+        /// `--to`. Other settings (e.g. TLS) are taken from this configuration.
+        #[clap(long, value_name = "URI", conflicts_with = "to")]
+        target_uri: Option<String>,
+
+        /// The `general.instance_name` configured for the target, if set.
+        /// Without this, the name is asked for interactively.
+        #[clap(long, value_name = "NAME", requires = "to")]
+        instance: Option<String>,
+
+        /// If specified, skips the "Are you sure?" question.
+        #[clap(long)]
+        yes_absolutely_overwrite_target: bool,
+
+        /// Anonymizes the copy: deletes sessions, dismissed announcements and
+        /// playback positions, and replaces user names with pseudonyms.
+        #[clap(long)]
+        anonymize: bool,
+
+        /// SQL script to run against the target after copying (and
+        /// anonymizing), e.g. to adjust data for the staging system.
+        #[clap(long, value_name = "PATH")]
+        post_copy_script: Option<PathBuf>,
+    },
+
+    /// Checks that all tables are owned by the user running migrations
+    /// (`db.migration_user.user` or `db.user`), as only the owner can alter
+    /// them. Tables can end up with another owner after manual operations,
+    /// e.g. restoring a dump as a different user. For each such table, the
+    /// SQL to fix it is printed, and the command exits with an error.
+    TableOwnerCheck {
+        /// Check against this owner instead of the configured user.
+        #[clap(long)]
+        expected_owner: Option<String>,
+    },
 }
 
 #[derive(Debug, clap::Args)]
@@ -79,49 +951,357 @@ pub(crate) struct ClearOptions {
     /// If specified, skips the "Are you sure?" question.
     #[clap(long)]
     pub(crate) yes_absolutely_clear_db: bool,
+
+    /// The configured `general.instance_name`, if set. Without this, the
+    /// name is asked for interactively. Unlike the "Are you sure?" question,
+    /// this cannot be skipped.
+    #[clap(long, value_name = "NAME")]
+    pub(crate) instance: Option<String>,
 }
 
 /// Entry point for `db` commands.
-pub(crate) async fn run(cmd: &DbCommand, config: &Config) -> Result<()> {
+pub(crate) async fn run(cmd: &DbCommand, config: &Config, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json && !cmd.has_summary() {
+        bail!("`--output json` is not supported by this subcommand");
+    }
+
+    let summary = execute(cmd, config).await?;
+    summary.print(output)
+}
+
+impl DbCommand {
+    /// Whether `execute` returns anything other than `Summary::Report` for
+    /// this subcommand.
+    fn has_summary(&self) -> bool {
+        matches!(
+            self,
+            Self::Clear { .. }
+                | Self::Reset { .. }
+                | Self::Migrate { .. }
+                | Self::WaitForVersion { .. }
+                | Self::Script { .. }
+                | Self::Dump { .. }
+                | Self::UnsafeOverwriteMigrations
+                | Self::PgLogicalSlotCreate { .. }
+                | Self::PgLogicalSlotDrop { .. }
+                | Self::CacheInvalidate { .. }
+        )
+    }
+}
+
+/// Runs the given subcommand and returns what it did, without printing a
+/// summary. Analyzing subcommands still print their report.
+pub(crate) async fn execute(cmd: &DbCommand, config: &Config) -> Result<Summary> {
+    // Destructive commands have to be confirmed before connecting anywhere.
+    match cmd {
+        DbCommand::Clear { options: ClearOptions { instance, .. } }
+        | DbCommand::Reset { clear: ClearOptions { instance, .. } }
+        | DbCommand::Restore { instance, .. } => confirm_instance(config, instance.as_deref())?,
+        _ => {}
+    }
+
     // Some subcommands fork out to other processes that establish their own connection
     match cmd {
-        DbCommand::Console => { return console(&config.db).map(|_| ()); },
-        DbCommand::Dump { path } => { return dump(&config.db, path).map(|_| ()); },
-        DbCommand::Restore { dump } => { return restore(&config.db, dump).map(|_| ()); },
+        DbCommand::Console => { return console(&config.db).map(|_| Summary::Report); },
+        DbCommand::Dump { s3: Some(target), storage_class, .. } => {
+            return s3_dump::run(&config.db, target, storage_class.as_deref()).await;
+        }
+        DbCommand::Dump { path, .. } => {
+            let path = path.as_deref().ok_or_else(|| anyhow!("no dump path specified"))?;
+            return dump(&config.db, path);
+        }
+        DbCommand::Restore { dump, point_in_time: Some(target), data_dir, wal_archive, .. } => {
+            let data_dir = data_dir.as_deref()
+                .ok_or_else(|| anyhow!("`--point-in-time` requires `--data-dir`"))?;
+            let wal_archive = wal_archive.as_deref()
+                .ok_or_else(|| anyhow!("`--point-in-time` requires `--wal-archive`"))?;
+            return point_in_time_restore::run(dump, data_dir, wal_archive, *target)
+                .map(|_| Summary::Report);
+        }
+        DbCommand::Restore { dump, .. } => {
+            return restore(&config.db, dump).map(|_| Summary::Report);
+        },
+        DbCommand::GenerateSchemaSnapshot { check: true, .. } => {
+            return generate_schema_snapshot::check().map(|_| Summary::Report);
+        }
+        // Has to handle connection failures itself.
+        DbCommand::PgHbaTest { address } => {
+            return pg_hba_test::run(&config.db, *address).await.map(|_| Summary::Report);
+        }
+        DbCommand::Copy {
+            to,
+            target_uri,
+            instance,
+            yes_absolutely_overwrite_target: yes,
+            anonymize,
+            post_copy_script,
+        } => {
+            return copy::run(
+                config,
+                to.as_deref(),
+                target_uri.as_deref(),
+                instance.as_deref(),
+                *yes,
+                *anonymize,
+                post_copy_script.as_deref(),
+            ).await.map(|_| Summary::Report);
+        }
+        // Connects to the replica instead.
+        DbCommand::ReplicaLagCheck { host, port, max_lag_bytes, max_lag_secs } => {
+            return replica_lag_check::run(
+                &config.db,
+                host.as_deref(),
+                *port,
+                *max_lag_bytes,
+                *max_lag_secs,
+            ).await.map(|_| Summary::Report);
+        }
         _ => {},
     }
 
-    // Connect to database
-    let pool = create_pool(&config.db).await?;
+    // Connect to database. Migrations might use separate credentials.
+    let db_config = match cmd {
+        DbCommand::Migrate { .. } | DbCommand::QueryPlanRegressionTest { .. } => {
+            config.db.for_migrations()
+        }
+        _ => config.db.clone(),
+    };
+    let pool = create_pool(&db_config).await?;
     let mut db = pool.get().await?;
 
     // Dispatch command
-    match cmd {
-        DbCommand::Clear { options: ClearOptions { yes_absolutely_clear_db: yes } }
-            => clear(&mut db, config, *yes).await?,
-        DbCommand::Migrate => super::migrate(&mut db).await?,
-        DbCommand::Reset { clear: ClearOptions { yes_absolutely_clear_db: yes } } => {
-            clear(&mut db, config, *yes).await?;
-            super::migrate(&mut db).await?;
-        }
-        DbCommand::Script { script } => run_script(&db, &script).await?,
+    let summary = match cmd {
+        DbCommand::Clear { options: ClearOptions { yes_absolutely_clear_db: yes, .. } } => {
+            Summary::Clear { tables_dropped: clear(&mut db, config, *yes).await? }
+        }
+        DbCommand::Migrate { options } if options.zero_downtime_check => {
+            zero_downtime_check::run(&db, &options.exclude).await?;
+            Summary::Migrate { migrations_applied: 0 }
+        }
+        DbCommand::Migrate { options } => {
+            let lock_wait_alert = match options.alert_on_lock_wait {
+                Some(0) => bail!("`--alert-on-lock-wait` has to be at least 1 second"),
+                Some(secs) => {
+                    let threshold = std::time::Duration::from_secs(secs);
+                    let webhook = options.webhook.clone();
+                    Some(lock_wait_alert::spawn(&pool, &db, threshold, webhook).await?)
+                }
+                None => None,
+            };
+            let migrations_applied = super::migrate(&mut db, options).await;
+            if let Some(task) = lock_wait_alert {
+                task.abort();
+            }
+            Summary::Migrate { migrations_applied: migrations_applied? }
+        }
+        DbCommand::WaitForVersion { version, timeout_secs, poll_interval_ms } => {
+            wait_for_version::run(&db, *version, *timeout_secs, *poll_interval_ms).await?
+        }
+        DbCommand::Reset { clear: ClearOptions { yes_absolutely_clear_db: yes, .. } } => {
+            let tables_dropped = clear(&mut db, config, *yes).await?;
+            let migrations_applied = super::migrate(&mut db, &MigrateOptions::default()).await?;
+            Summary::Reset { tables_dropped, migrations_applied }
+        }
+        DbCommand::Script { script } => Summary::Script {
+            rows_affected: run_script(&db, script).await?,
+        },
         DbCommand::Console | DbCommand::Dump { .. } | DbCommand::Restore { .. } => {
             unreachable!("already handled above");
         },
-        DbCommand::UnsafeOverwriteMigrations => unsafe_overwrite_migrations(&mut db).await?,
+        DbCommand::UnsafeOverwriteMigrations => {
+            let (updated, deleted) = unsafe_overwrite_migrations(&mut db).await?;
+            Summary::UnsafeOverwriteMigrations { updated, deleted }
+        }
+        DbCommand::PgLogicalSlotCreate { name, plugin } => {
+            pg_logical_slot::create(&db, name, plugin).await?
+        }
+        DbCommand::PgLogicalSlotDrop { name } => pg_logical_slot::drop(&db, name).await?,
+        DbCommand::CacheInvalidate { tables, reason } => {
+            cache_invalidate::run(&db, tables.as_deref(), reason).await?
+        }
+        other => {
+            run_report(other, config, &pool, &mut db).await?;
+            Summary::Report
+        }
+    };
+
+    Ok(summary)
+}
+
+/// Dispatches the subcommands that print their own report.
+async fn run_report(cmd: &DbCommand, config: &Config, pool: &Pool, db: &mut Db) -> Result<()> {
+    match cmd {
+        DbCommand::SquashMigrations { from, name, out } => {
+            squash_migrations::run(db, &config.db, *from, name, out).await?;
+        }
+        DbCommand::ForeignKeyIndexCheck => foreign_key_index_check::run(db).await?,
+        DbCommand::DeadTupleCheck { threshold_pct } => {
+            dead_tuple_check::run(db, *threshold_pct).await?;
+        }
+        DbCommand::ColumnAddDefault { table, column, type_, default } => {
+            column_add_default::run(db, table, column, type_, default).await?;
+        }
+        DbCommand::ColumnTypeChange { table, column, new_type, using } => {
+            column_type_change::run(db, table, column, new_type, using.as_deref()).await?;
+        }
+        DbCommand::ColumnRename { table, old_name, new_name, update_source } => {
+            column_rename::run(db, table, old_name, new_name, *update_source).await?;
+        }
+        DbCommand::Doc { out } => doc::run(db, out).await?,
+        DbCommand::WriteAmplification { test_table, iterations } => {
+            write_amplification::run(db, test_table, *iterations).await?;
+        }
+        DbCommand::RowLevelSecurityCheck => row_level_security_check::run(db).await?,
+        DbCommand::SnapshotIsolationTest => snapshot_isolation_test::run(pool).await?,
+        DbCommand::QueryFrequency { top, window_secs } => {
+            query_frequency::run(db, *top, *window_secs).await?;
+        }
+        DbCommand::MigrationAnnotatePerformance { version } => {
+            migration_annotate_performance::run(db, *version).await?;
+        }
+        DbCommand::EventReplay { replication_log, target_schema, stop_at } => {
+            event_replay::run(db, replication_log, target_schema, *stop_at).await?;
+        }
+        DbCommand::Timeline { since, until } => timeline::run(db, *since, *until).await?,
+        DbCommand::AuditTrail { table, user, since, action, limit, json } => {
+            audit_trail::run(
+                db,
+                table.as_deref(),
+                user.as_deref(),
+                *since,
+                *action,
+                *limit,
+                *json,
+            ).await?;
+        }
+        DbCommand::ConstraintDisable { table } => {
+            constraints::disable(db, table.as_deref()).await?;
+        }
+        DbCommand::ConstraintEnable { table } => {
+            constraints::enable(db, table.as_deref()).await?;
+        }
+        DbCommand::DiskProjection { days_ahead } => disk_projection::run(db, *days_ahead).await?,
+        DbCommand::PgParameterTuning { system_ram_gb, cpu_count, ssd, script } => {
+            pg_parameter_tuning::run(db, *system_ram_gb, *cpu_count, *ssd, *script).await?;
+        }
+        DbCommand::PgActivity { refresh_secs, filter } => {
+            pg_activity::run(db, *refresh_secs, filter.as_deref()).await?;
+        }
+        DbCommand::SessionStats { group_by } => session_stats::run(db, *group_by).await?,
+        DbCommand::MigrationSignatureChain { expected } => {
+            migration_signature_chain::run(db, expected.as_deref()).await?;
+        }
+        DbCommand::TableGrantMatrix { role, csv } => {
+            table_grant_matrix::run(db, role.as_deref(), *csv).await?;
+        }
+        DbCommand::MigrationEstimateTime { versions } => {
+            migration_estimate_time::run(db, versions).await?;
+        }
+        DbCommand::TableBloatEstimate { threshold_pct } => {
+            table_bloat_estimate::run(db, *threshold_pct).await?;
+        }
+        DbCommand::QueryHeatmap { days } => query_heatmap::run(db, *days).await?,
+        DbCommand::GenerateTestFixtures { out, tables, n_rows, seed } => {
+            generate_test_fixtures::run(db, out, tables, *n_rows, *seed).await?;
+        }
+        DbCommand::MigrationPreconditionCheck { versions } => {
+            migration_precondition_check::run(db, versions).await?;
+        }
+        DbCommand::ColumnHistogram { table, column, buckets } => {
+            column_histogram::run(db, table, column, *buckets).await?;
+        }
+        DbCommand::GenerateSchemaSnapshot { out, .. } => {
+            generate_schema_snapshot::run(db, &config.db, out).await?;
+        }
+        DbCommand::TablespaceUsage { threshold_pct } => {
+            tablespace_usage::run(db, &config.db, *threshold_pct).await?;
+        }
+        DbCommand::MigrationCoverageReport { since, until } => {
+            migration_coverage_report::run(db, *since, *until).await?;
+        }
+        DbCommand::PgLogTail { lines, follow, filter } => {
+            pg_log_tail::run(db, &config.db, *lines, *follow, filter.as_ref()).await?;
+        }
+        DbCommand::MigrationExport { id, archive } => {
+            migration_export::run(db, *id, archive.as_deref()).await?;
+        }
+        DbCommand::QueryPlanRegressionTest { queries_file } => {
+            query_plan_regression_test::run(db, queries_file).await?;
+        }
+        DbCommand::ColumnEncryption { table, column, key_ref, algorithm } => {
+            column_encryption::run(db, table, column, key_ref, *algorithm).await?;
+        }
+        DbCommand::SearchSyncStatus { max_age_hours } => {
+            search_sync_status::run(db, config, *max_age_hours).await?;
+        }
+        DbCommand::PgCheckpointStats { window_secs } => {
+            pg_checkpoint_stats::run(db, *window_secs).await?;
+        }
+        DbCommand::TableOwnerCheck { expected_owner } => {
+            let configured = config.db.for_migrations().user;
+            table_owner_check::run(db, expected_owner.as_ref().unwrap_or(&configured)).await?;
+        }
+        DbCommand::Clear { .. }
+        | DbCommand::Migrate { .. }
+        | DbCommand::WaitForVersion { .. }
+        | DbCommand::Reset { .. }
+        | DbCommand::Script { .. }
+        | DbCommand::Console
+        | DbCommand::Dump { .. }
+        | DbCommand::Restore { .. }
+        | DbCommand::UnsafeOverwriteMigrations
+        | DbCommand::PgLogicalSlotCreate { .. }
+        | DbCommand::PgLogicalSlotDrop { .. }
+        | DbCommand::CacheInvalidate { .. }
+        | DbCommand::PgHbaTest { .. }
+        | DbCommand::ReplicaLagCheck { .. }
+        | DbCommand::Copy { .. } => unreachable!("handled in `execute`"),
     }
 
     Ok(())
 }
 
 
+/// If `general.instance_name` is configured, makes sure the operator knows
+/// which instance they are about to destroy: the name has to be passed via
+/// `--instance` or typed in. Otherwise, an error is returned.
+fn confirm_instance(config: &Config, given: Option<&str>) -> Result<()> {
+    let Some(expected) = &config.general.instance_name else {
+        return Ok(());
+    };
+
+    println!();
+    bunt::println!("Instance name: {[yellow+bold+intense]}", expected);
+    if let Ok(Ok(hostname)) = hostname::get().map(|n| n.into_string()) {
+        bunt::println!("Hostname: {[yellow+bold+intense]}", hostname);
+    }
+    bunt::println!("Database host: {[yellow+bold+intense]}", config.db.host);
+    println!();
+
+    let answer = match given {
+        Some(name) => name.to_owned(),
+        None => {
+            println!("This operation is destructive. Type the name of this instance to proceed.");
+            let mut line = String::new();
+            io::stdin().read_line(&mut line).context("could not read from stdin")?;
+            line.trim().to_owned()
+        }
+    };
+    if answer != *expected {
+        bail!("given instance name '{answer}' does not match the configured \
+            `general.instance_name` '{expected}': operation was aborted.");
+    }
+
+    Ok(())
+}
+
 /// Clears the whole database by removing and re-creating the `public` schema.
 ///
 /// This also has a interactive check, asking the user to confirm the removal.
-/// If the user did not confirm and the database is not changed, `false` is
-/// returned; `true` otherwise.
-async fn clear(db: &mut Db, config: &Config, yes: bool) -> Result<()> {
-    let tx = db.build_transaction()
+/// If the user did not confirm, an error is returned and the database is not
+/// changed. Returns the names of all tables that were dropped.
+async fn clear(db: &mut Db, config: &Config, yes: bool) -> Result<Vec<String>> {
+    let mut tx = db.build_transaction()
         .isolation_level(IsolationLevel::Serializable)
         .start()
         .await?;
@@ -141,9 +1321,21 @@ async fn clear(db: &mut Db, config: &Config, yes: bool) -> Result<()> {
     println!("The database currently holds these tables:");
     let tables = query::all_table_names(&*tx).await?;
     for name in &tables {
-        let num_rows = tx.query_one(&*format!("select count(*) from {}", name), &[])
-            .await?
-            .get::<_, i64>(0);
+        // Counting might fail (e.g. if we are not allowed to read the table).
+        // A savepoint makes sure that does not abort the whole transaction.
+        let savepoint = tx.transaction().await?;
+        let sql = format!("select count(*) from {}", query::qualified(name));
+        let num_rows = match savepoint.query_one(&sql, &[]).await {
+            Ok(row) => {
+                savepoint.commit().await?;
+                row.get::<_, i64>(0).to_string()
+            }
+            Err(e) => {
+                debug!("Failed to count rows of '{name}': {e}");
+                savepoint.rollback().await?;
+                "n/a".into()
+            }
+        };
         bunt::println!(" - {} ({[blue+intense]} rows)", name, num_rows);
     }
 
@@ -181,18 +1373,27 @@ async fn clear(db: &mut Db, config: &Config, yes: bool) -> Result<()> {
     crate::search::clear(&MeiliWriter::without_lock(&meili)).await.context("failed to clear search index")?;
     info!("Cleared search index");
 
-    Ok(())
+    Ok(tables)
 }
 
-async fn run_script(db: &Db, script_path: &Path) -> Result<()> {
+/// Runs the script and returns the number of rows affected by all its
+/// statements.
+async fn run_script(db: &Db, script_path: &Path) -> Result<u64> {
     let script = tokio::fs::read_to_string(script_path)
         .await
         .context(format!("failed to read script file '{}'", script_path.display()))?;
 
-    db.batch_execute(&script).await.context("failed to execute script")?;
-    info!("Successfully ran SQL script");
+    let rows_affected = db.simple_query(&script)
+        .await
+        .context("failed to execute script")?
+        .iter()
+        .map(|msg| match msg {
+            SimpleQueryMessage::CommandComplete(rows) => *rows,
+            _ => 0,
+        })
+        .sum();
 
-    Ok(())
+    Ok(rows_affected)
 }
 
 fn console(config: &DbConfig) -> Result<Never> {
@@ -202,16 +1403,24 @@ fn console(config: &DbConfig) -> Result<Never> {
     )
 }
 
-fn dump(config: &DbConfig, path: &Path) -> Result<Never> {
-    fork_command(
-        Command::new("pg_dump")
-            .arg("--dbname")
-            .arg(connection_uri(config))
-            .arg("--format")
-            .arg("custom")
-            .arg("--file")
-            .arg(path)
-    )
+fn dump(config: &DbConfig, path: &Path) -> Result<Summary> {
+    let mut command = Command::new("pg_dump");
+    command
+        .arg("--dbname")
+        .arg(connection_uri(config))
+        .arg("--format")
+        .arg("custom")
+        .arg("--file")
+        .arg(path);
+    let status = command.status().map_err(|e| command_error(&command, e))?;
+    if !status.success() {
+        bail!("`pg_dump` failed ({status})");
+    }
+
+    let bytes = std::fs::metadata(path)
+        .with_context(|| format!("failed to read size of dump '{}'", path.display()))?
+        .len();
+    Ok(Summary::Dump { path: path.to_owned(), bytes })
 }
 
 fn restore(config: &DbConfig, dump: &Path) -> Result<Never> {
@@ -228,13 +1437,17 @@ fn restore(config: &DbConfig, dump: &Path) -> Result<Never> {
 
 fn fork_command(command: &mut Command) -> Result<Never> {
     let error = command.exec();
+    Err(command_error(command, error))
+}
+
+fn command_error(command: &Command, error: io::Error) -> anyhow::Error {
     let program = command.get_program().to_string_lossy();
     let message = match error.kind() {
         io::ErrorKind::NotFound => format!("`{program}` was not found in your `PATH`"),
         io::ErrorKind::PermissionDenied => format!("you don't have sufficient permissions to execute `{program}`"),
         _ => format!("an error occured while trying to execute `{program}`"),
     };
-    Err(error).context(message)
+    anyhow::Error::new(error).context(message)
 }
 
 fn connection_uri(config: &DbConfig) -> String {
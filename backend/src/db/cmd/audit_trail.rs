@@ -0,0 +1,165 @@
+//! The `db audit-trail` subcommand.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::{prelude::*, db::{Db, query}};
+
+
+/// Name of the table containing the audit log. Tobira itself does not write
+/// to it: it has to be filled by audit triggers. Expected columns:
+///
+/// - `occurred_at timestamp with time zone not null`
+/// - `table_name text not null`
+/// - `action text not null` (`insert`, `update` or `delete`)
+/// - `username text`: the user responsible for the change, if known
+/// - `old_row jsonb`, `new_row jsonb`: the row before and after the change
+const TABLE: &str = "__tobira_audit_log";
+
+/// Kind of change recorded in the audit log.
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+pub(crate) enum AuditAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+struct Entry {
+    occurred_at: DateTime<Utc>,
+    table: String,
+    action: String,
+    user: Option<String>,
+    old_row: Option<Value>,
+    new_row: Option<Value>,
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    table: Option<&str>,
+    user: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    action: Option<AuditAction>,
+    limit: usize,
+    json: bool,
+) -> Result<()> {
+    if !query::does_table_exist(&**db, TABLE).await? {
+        bail!("table '{TABLE}' does not exist: no audit triggers are installed");
+    }
+
+    let limit = i64::try_from(limit).context("`--limit` too large")?;
+    let mut entries = db
+        .query(
+            &format!(
+                "select occurred_at, table_name, action, username, old_row, new_row \
+                    from {TABLE} \
+                    where ($1::text is null or table_name = $1) \
+                        and ($2::text is null or username = $2) \
+                        and ($3::timestamptz is null or occurred_at >= $3) \
+                        and ($4::text is null or action = $4) \
+                    order by occurred_at desc \
+                    limit $5",
+            ),
+            &[&table, &user, &since, &action.map(AuditAction::as_str), &limit],
+        )
+        .await
+        .with_context(|| format!("failed to query '{TABLE}'"))?
+        .into_iter()
+        .map(|row| Entry {
+            occurred_at: row.get(0),
+            table: row.get(1),
+            action: row.get(2),
+            user: row.get(3),
+            old_row: row.get(4),
+            new_row: row.get(5),
+        })
+        .collect::<Vec<_>>();
+
+    // We query the newest entries, but print them chronologically.
+    entries.reverse();
+
+    if json {
+        let out = entries.iter()
+            .map(|e| json!({
+                "occurredAt": e.occurred_at,
+                "table": e.table,
+                "action": e.action,
+                "user": e.user,
+                "oldRow": e.old_row,
+                "newRow": e.new_row,
+            }))
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No matching entries in the audit log.");
+        return Ok(());
+    }
+
+    let header = ["Time", "Action", "Table", "User", "Row"];
+    let rows = entries.iter()
+        .map(|e| [
+            e.occurred_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            e.action.clone(),
+            e.table.clone(),
+            e.user.clone().unwrap_or_else(|| "-".into()),
+            describe_change(e),
+        ])
+        .collect::<Vec<_>>();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| cells.iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{cell:width$}"))
+        .collect::<Vec<_>>()
+        .join("  ")
+        .trim_end()
+        .to_owned();
+    bunt::println!("{$bold}{}{/$}", line(&header.map(String::from)));
+    for row in &rows {
+        println!("{}", line(row));
+    }
+    println!();
+    println!("{} entries (newest last, at most {limit})", rows.len());
+
+    Ok(())
+}
+
+/// Returns a short description of the changed row: its ID (if it has an `id`
+/// column) and, for updates, the names of all changed columns.
+fn describe_change(entry: &Entry) -> String {
+    let id = entry.new_row.as_ref().or(entry.old_row.as_ref())
+        .and_then(|row| row.get("id"))
+        .map(|id| format!("id={id}"));
+
+    let changed = match (&entry.old_row, &entry.new_row) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let columns = new.iter()
+                .filter(|(column, value)| old.get(*column) != Some(value))
+                .map(|(column, _)| column.as_str())
+                .collect::<Vec<_>>();
+            Some(format!("changed: {}", columns.join(", ")))
+        }
+        _ => None,
+    };
+
+    [id, changed].into_iter().flatten().collect::<Vec<_>>().join(" ")
+}
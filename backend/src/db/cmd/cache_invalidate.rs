@@ -0,0 +1,49 @@
+//! The `db cache-invalidate` subcommand.
+
+use crate::{
+    prelude::*,
+    api::cache_invalidation::{CACHED_TABLES, CHANNEL},
+    db::Db,
+};
+use super::summary::Summary;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, tables: Option<&[String]>, reason: &str) -> Result<Summary> {
+    let tables = match tables {
+        Some(tables) => {
+            if let Some(table) = tables.iter().find(|t| !CACHED_TABLES.contains(&t.as_str())) {
+                bail!("table '{table}' is not cached by Tobira (cached tables: {})",
+                    CACHED_TABLES.join(", "));
+            }
+            tables.to_vec()
+        }
+        None => CACHED_TABLES.iter().map(|&table| table.to_owned()).collect(),
+    };
+
+    info!("Cache invalidation of {} requested. Reason: {reason}", tables.join(", "));
+
+    // Each running Tobira node has one dedicated connection that is idle
+    // after executing `LISTEN`. Only the queries of connections of the same
+    // DB user are visible, which is fine as Tobira always uses one user.
+    let nodes = db
+        .query_one(
+            "select count(*) from pg_stat_activity \
+                where datname = current_database() and state = 'idle' and query = $1",
+            &[&format!("listen {CHANNEL}")],
+        )
+        .await
+        .context("failed to query `pg_stat_activity`")?
+        .get::<_, i64>(0) as u64;
+
+    if nodes > 0 {
+        for table in &tables {
+            db.execute("select pg_notify($1, $2)", &[&CHANNEL, table])
+                .await
+                .context("failed to send notification")?;
+        }
+        info!("Sent cache invalidation requests to {nodes} running Tobira node(s)");
+    }
+
+    Ok(Summary::CacheInvalidate { tables, notified_nodes: nodes })
+}
@@ -0,0 +1,117 @@
+//! The `db column-add-default` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// Number of rows updated per statement when backfilling on old PostgreSQL
+/// versions. Each batch is its own transaction, so locks are held only briefly.
+const BACKFILL_BATCH_SIZE: i64 = 10_000;
+
+/// Starting with PostgreSQL 11, adding a column with a constant default value
+/// does not rewrite the table anymore.
+const PG_FAST_DEFAULT_VERSION: i32 = 110000;
+
+/// Starting with PostgreSQL 12, `set not null` can skip the full table scan if
+/// a valid check constraint already proves the column is not null.
+const PG_NOT_NULL_FROM_CHECK_VERSION: i32 = 120000;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    table: &str,
+    column: &str,
+    ty: &str,
+    default: &str,
+) -> Result<()> {
+    let column_exists = db
+        .query_one(
+            "select exists(\
+                select from information_schema.columns \
+                where table_schema = 'public' and table_name = $1 and column_name = $2\
+            )",
+            &[&table, &column],
+        )
+        .await?
+        .get::<_, bool>(0);
+    if column_exists {
+        bail!("column '{column}' already exists in table '{table}'");
+    }
+
+    let version: i32 = db.query_one("select current_setting('server_version_num')::int", &[])
+        .await?
+        .get(0);
+
+    let table = quote_ident(table);
+    let column_name = column;
+    let column = quote_ident(column);
+    let check_name = quote_ident(&format!("{column_name}_not_null_tmp"));
+
+    // Step 1: add the column with default value.
+    if version >= PG_FAST_DEFAULT_VERSION {
+        execute(db, &format!("alter table {table} add column {column} {ty} default {default}"))
+            .await?;
+    } else {
+        // On old versions, adding a column with a default rewrites the whole
+        // table while holding an exclusive lock. Adding a nullable column and
+        // setting the default afterwards is fast, but only applies to new rows.
+        execute(db, &format!("alter table {table} add column {column} {ty}")).await?;
+        execute(db, &format!("alter table {table} alter column {column} set default {default}"))
+            .await?;
+
+        // Step 2: backfill existing rows in small batches.
+        info!("Backfilling existing rows in batches of {BACKFILL_BATCH_SIZE}...");
+        let query = format!(
+            "update {table} set {column} = default \
+                where ctid = any(array(\
+                    select ctid from {table} where {column} is null limit {BACKFILL_BATCH_SIZE}\
+                ))",
+        );
+        let mut total = 0;
+        loop {
+            let updated = db.execute(&query, &[]).await
+                .context("failed to backfill column")?;
+            if updated == 0 {
+                break;
+            }
+            total += updated;
+            debug!("Backfilled {total} rows so far");
+        }
+        info!("Backfilled {total} rows");
+    }
+
+    // Step 3: make the column `not null`. Validating a `not valid` check
+    // constraint only requires a `share update exclusive` lock, so reads and
+    // writes can continue during the table scan.
+    execute(db, &format!(
+        "alter table {table} add constraint {check_name} check ({column} is not null) not valid",
+    )).await?;
+    execute(db, &format!("alter table {table} validate constraint {check_name}")).await?;
+    if version < PG_NOT_NULL_FROM_CHECK_VERSION {
+        warn!("PostgreSQL < 12: `set not null` requires a full table scan with an exclusive lock");
+    }
+    execute(db, &format!("alter table {table} alter column {column} set not null")).await?;
+    execute(db, &format!("alter table {table} drop constraint {check_name}")).await?;
+
+    // Step 4: remove default again.
+    execute(db, &format!("alter table {table} alter column {column} drop default")).await?;
+
+    bunt::println!(
+        "{$green+intense}Successfully added column {} to table {}.{/$}",
+        column,
+        table,
+    );
+
+    Ok(())
+}
+
+async fn execute(db: &Db, sql: &str) -> Result<()> {
+    bunt::println!("{$dimmed}> {}{/$}", sql);
+    db.batch_execute(sql).await.with_context(|| format!("failed to execute '{sql}'"))?;
+    Ok(())
+}
+
+/// Quotes the given identifier for use in SQL.
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
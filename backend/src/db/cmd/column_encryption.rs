@@ -0,0 +1,157 @@
+//! The `db column-encryption` subcommand.
+
+use crate::{prelude::*, db::{Db, migrations::MIGRATIONS, query::quote_ident}};
+
+
+/// Prefix of the Postgres setting the key is read from, see `key_setting`.
+const KEY_SETTING_PREFIX: &str = "tobira.key_";
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+pub(crate) enum EncryptionAlgorithm {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl EncryptionAlgorithm {
+    /// The value of the `cipher-algo` option of `pgp_sym_encrypt`.
+    fn cipher_algo(self) -> &'static str {
+        match self {
+            Self::Aes128 => "aes128",
+            Self::Aes192 => "aes192",
+            Self::Aes256 => "aes256",
+        }
+    }
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    table_name: &str,
+    column_name: &str,
+    key_ref: &str,
+    algorithm: EncryptionAlgorithm,
+) -> Result<()> {
+    // The reference becomes part of a setting name, which cannot be quoted.
+    let valid = key_ref.starts_with(|c: char| c.is_ascii_lowercase())
+        && key_ref.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        bail!("'{key_ref}' is not a valid key reference (only lowercase ASCII letters, digits \
+            and '_' allowed)");
+    }
+
+    let table = quote_ident(table_name);
+    let column = quote_ident(column_name);
+    let shadow = quote_ident(&format!("{column_name}__encrypted"));
+
+    // ----- Checks -------------------------------------------------------------------------------
+    let row = db
+        .query_opt(
+            "select format_type(a.atttypid, a.atttypmod), a.attnotnull \
+                from pg_attribute a \
+                where a.attrelid = to_regclass($1) and a.attname = $2 \
+                    and a.attnum > 0 and not a.attisdropped",
+            &[&table, &column_name],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("column '{column_name}' does not exist in table '{table_name}' \
+            (is the database migrated?)"))?;
+    let old_type: String = row.get(0);
+    let not_null: bool = row.get(1);
+    if old_type == "bytea" {
+        bail!("column '{column_name}' already has type 'bytea': is it encrypted already?");
+    }
+
+    // The original column is dropped, which would silently drop indexes and
+    // fail for views.
+    let dependents = db
+        .query(
+            "select pg_describe_object(d.classid, d.objid, d.objsubid) \
+                from pg_depend d \
+                join pg_attribute a on a.attrelid = d.refobjid and a.attnum = d.refobjsubid \
+                where d.refclassid = 'pg_class'::regclass \
+                    and d.refobjid = $1::text::regclass \
+                    and a.attname = $2 \
+                    and d.classid <> 'pg_attrdef'::regclass",
+            &[&table, &column_name],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<Vec<_>>();
+    if !dependents.is_empty() {
+        bail!("the following objects depend on column '{column_name}' and would have to be \
+            dropped and recreated manually: {}", dependents.join(", "));
+    }
+
+    // ----- Migration ----------------------------------------------------------------------------
+    let id = MIGRATIONS.keys().last().map_or(1, |id| id + 1);
+    let name = format!("encrypt-{table_name}-{column_name}").replace('_', "-");
+    let sql = migration(&table, &column, &shadow, &old_type, not_null, key_ref, algorithm);
+    bunt::println!("{$dimmed}-- Migration {}: {}{/$}", id, name);
+    print!("{sql}");
+
+    println!();
+    bunt::println!("{$yellow+bold}Note:{/$} Tobira does not manage encryption keys itself. The \
+        migration reads the key from the setting '{}{}', which has to be set for the role \
+        running migrations (e.g. `alter role ... set`) before it is applied. Values can be \
+        decrypted with `pgp_sym_decrypt({}, <key>)::{}`.",
+        KEY_SETTING_PREFIX, key_ref, column, old_type);
+
+    Ok(())
+}
+
+/// Returns the migration replacing `column` with an encrypted `bytea` column.
+fn migration(
+    table: &str,
+    column: &str,
+    shadow: &str,
+    old_type: &str,
+    not_null: bool,
+    key_ref: &str,
+    algorithm: EncryptionAlgorithm,
+) -> String {
+    let key = format!("current_setting('{KEY_SETTING_PREFIX}{key_ref}')");
+    let options = format!("'cipher-algo={}'", algorithm.cipher_algo());
+
+    let mut sql = format!(
+        "-- Encrypts {table}.{column} (previously '{old_type}') with pgcrypto. The key is \
+            read from the\n\
+        -- setting '{KEY_SETTING_PREFIX}{key_ref}', so it is not part of this script.\n\
+        alter table {table} add column {shadow} bytea;\n\
+        update {table} set {shadow} = pgp_sym_encrypt({column}::text, {key}, {options})\n    \
+            where {column} is not null;\n\
+        alter table {table} drop column {column};\n\
+        alter table {table} rename column {shadow} to {column};\n",
+    );
+    if not_null {
+        sql += &format!("alter table {table} alter column {column} set not null;\n");
+    }
+    sql
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{migration, EncryptionAlgorithm};
+
+    #[test]
+    fn generates_migration() {
+        let sql = migration(
+            "users", "email", "email__encrypted", "text", true, "pii", EncryptionAlgorithm::Aes256,
+        );
+        assert!(sql.contains("alter table users add column email__encrypted bytea;\n"), "{sql}");
+        assert!(sql.contains("set email__encrypted = pgp_sym_encrypt(email::text, \
+            current_setting('tobira.key_pii'), 'cipher-algo=aes256')"), "{sql}");
+        assert!(sql.ends_with("alter table users drop column email;\n\
+            alter table users rename column email__encrypted to email;\n\
+            alter table users alter column email set not null;\n"), "{sql}");
+
+        let sql = migration(
+            "users", "email", "email__encrypted", "text", false, "pii", EncryptionAlgorithm::Aes128,
+        );
+        assert!(sql.contains("'cipher-algo=aes128'"), "{sql}");
+        assert!(!sql.contains("set not null"), "{sql}");
+    }
+}
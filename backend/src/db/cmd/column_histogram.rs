@@ -0,0 +1,266 @@
+//! The `db column-histogram` subcommand.
+
+use crate::{prelude::*, db::{Db, query::{self, qualified, quote_ident}}};
+
+
+/// Column types for which values are grouped into ranges of equal width.
+/// Values of all other types are grouped by their text representation.
+const NUMERIC_TYPES: &[&str] = &[
+    "smallint", "integer", "bigint", "numeric", "real", "double precision",
+];
+
+/// Width of the longest bar in characters.
+const BAR_WIDTH: usize = 50;
+
+/// Labels longer than this are truncated.
+const MAX_LABEL_LEN: usize = 40;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, table: &str, column: &str, buckets: u32) -> Result<()> {
+    if buckets == 0 {
+        bail!("number of buckets has to be at least 1");
+    }
+    if !query::does_table_exist(&**db, table).await? {
+        bail!("table '{table}' does not exist");
+    }
+    let data_type = db
+        .query_opt(
+            "select data_type from information_schema.columns \
+                where table_schema = 'public' and table_name = $1 and column_name = $2",
+            &[&table, &column],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("column '{column}' does not exist in table '{table}'"))?
+        .get::<_, String>(0);
+
+    let (bars, omitted) = if NUMERIC_TYPES.contains(&data_type.as_str()) {
+        (numeric_bars(db, table, column, buckets).await?, None)
+    } else {
+        text_bars(db, table, column, buckets).await?
+    };
+    let total_rows = match &omitted {
+        Some(omitted) => omitted.total_rows,
+        None => bars.iter().map(|b| b.count).sum(),
+    };
+
+    bunt::println!("{$bold}Distribution of {}.{} ({}):{/$}", table, column, data_type);
+    println!();
+    if bars.is_empty() {
+        bunt::println!("{$dimmed}The table is empty.{/$}");
+        return Ok(());
+    }
+    print_bars(&bars, total_rows);
+    if let Some(Omitted { values, rows, .. }) = omitted {
+        println!();
+        bunt::println!("{$dimmed}{} more distinct values in {} rows are not shown.{/$}",
+            values, rows);
+    }
+
+    Ok(())
+}
+
+/// One row of the histogram. A label of `None` stands for `null` values.
+#[derive(Debug, PartialEq)]
+struct Bar {
+    label: Option<String>,
+    count: i64,
+}
+
+/// The values that are not shown as they are not among the most frequent.
+#[derive(Debug)]
+struct Omitted {
+    values: i64,
+    rows: i64,
+    total_rows: i64,
+}
+
+/// Groups the values into `buckets` ranges of equal width between the
+/// minimum and maximum value.
+async fn numeric_bars(db: &Db, table: &str, column: &str, buckets: u32) -> Result<Vec<Bar>> {
+    let (table, column) = (qualified(table), quote_ident(column));
+    let row = db
+        .query_one(
+            &format!("select min({column})::float8, max({column})::float8, \
+                count(*) filter (where {column} is null) \
+                from {table}"),
+            &[],
+        )
+        .await?;
+    let (min, max, nulls) = (
+        row.get::<_, Option<f64>>(0),
+        row.get::<_, Option<f64>>(1),
+        row.get::<_, i64>(2),
+    );
+
+    let mut bars = match (min, max) {
+        (Some(min), Some(max)) if min < max => {
+            // `width_bucket` puts the maximum into bucket `buckets + 1`, as
+            // the upper bound is exclusive.
+            let rows = db
+                .query(
+                    &format!("select least(width_bucket({column}::float8, $1, $2, $3), $3), \
+                        count(*) \
+                        from {table} \
+                        where {column} is not null \
+                        group by 1"),
+                    &[&min, &max, &(buckets as i32)],
+                )
+                .await?;
+            let mut counts = vec![0; buckets as usize];
+            for row in rows {
+                counts[row.get::<_, i32>(0) as usize - 1] = row.get::<_, i64>(1);
+            }
+
+            let width = (max - min) / buckets as f64;
+            counts.into_iter()
+                .enumerate()
+                .map(|(i, count)| {
+                    let from = min + width * i as f64;
+                    let to = if i + 1 == buckets as usize { max } else { from + width };
+                    let close = if i + 1 == buckets as usize { ']' } else { ')' };
+                    let label = format!("[{}, {}{close}", format_num(from), format_num(to));
+                    Bar { label: Some(label), count }
+                })
+                .collect()
+        }
+
+        // All values are equal, so there is only one bucket.
+        (Some(value), Some(_)) => {
+            let count = db
+                .query_one(&format!("select count({column}) from {table}"), &[])
+                .await?
+                .get::<_, i64>(0);
+            vec![Bar { label: Some(format_num(value)), count }]
+        }
+
+        _ => vec![],
+    };
+
+    if nulls > 0 {
+        bars.push(Bar { label: None, count: nulls });
+    }
+    Ok(bars)
+}
+
+/// Returns the `buckets` most frequent values, plus information about the
+/// remaining values if there are any.
+async fn text_bars(
+    db: &Db,
+    table: &str,
+    column: &str,
+    buckets: u32,
+) -> Result<(Vec<Bar>, Option<Omitted>)> {
+    let (table, column) = (qualified(table), quote_ident(column));
+    let total = db
+        .query_one(&format!("select count(*), count(distinct {column}) from {table}"), &[])
+        .await?;
+    let (total_rows, distinct) = (total.get::<_, i64>(0), total.get::<_, i64>(1));
+
+    let bars = db
+        .query(
+            &format!("select {column}::text, count(*) \
+                from {table} \
+                group by 1 \
+                order by 2 desc, 1 \
+                limit $1"),
+            &[&i64::from(buckets)],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Bar { label: row.get(0), count: row.get(1) })
+        .collect::<Vec<_>>();
+
+    let shown_rows = bars.iter().map(|b| b.count).sum::<i64>();
+    let shown_values = bars.iter().filter(|b| b.label.is_some()).count() as i64;
+    let omitted = (shown_rows < total_rows).then(|| Omitted {
+        values: distinct - shown_values,
+        rows: total_rows - shown_rows,
+        total_rows,
+    });
+
+    Ok((bars, omitted))
+}
+
+/// Prints the bars with their count and percentage of `total_rows`.
+fn print_bars(bars: &[Bar], total_rows: i64) {
+    let labels = bars.iter()
+        .map(|bar| match &bar.label {
+            None => "NULL".to_owned(),
+            Some(label) if label.chars().count() > MAX_LABEL_LEN => {
+                let truncated: String = label.chars().take(MAX_LABEL_LEN - 1).collect();
+                format!("{truncated}…")
+            }
+            Some(label) => label.clone(),
+        })
+        .collect::<Vec<_>>();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let max = bars.iter().map(|b| b.count).max().unwrap_or(0);
+
+    for (bar, label) in bars.iter().zip(labels) {
+        let padded = format!("{label:>label_width$}");
+        if bar.label.is_none() {
+            bunt::print!("{[dimmed]}", padded);
+        } else {
+            print!("{padded}");
+        }
+        let pct = 100.0 * bar.count as f64 / total_rows as f64;
+        bunt::println!(
+            " │{[blue]} {} {[dimmed]}",
+            render_bar(bar.count, max, BAR_WIDTH),
+            bar.count,
+            format!("({pct:.1}%)"),
+        );
+    }
+}
+
+/// Renders a bar for `count` that is `width` characters long for `max`.
+/// Partial characters are used to make small differences visible.
+pub(super) fn render_bar(count: i64, max: i64, width: usize) -> String {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+    if max <= 0 {
+        return String::new();
+    }
+    let eighths = (count as f64 / max as f64 * (width * 8) as f64).round() as usize;
+    let mut out = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        out.push(PARTIAL[eighths % 8]);
+    }
+    out
+}
+
+/// Formats bucket bounds without unnecessary decimal places.
+fn format_num(x: f64) -> String {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        format!("{x:.0}")
+    } else {
+        format!("{x:.3}").trim_end_matches('0').trim_end_matches('.').to_owned()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{format_num, render_bar};
+
+    #[test]
+    fn bars() {
+        assert_eq!(render_bar(10, 10, 4), "████");
+        assert_eq!(render_bar(5, 10, 4), "██");
+        assert_eq!(render_bar(1, 10, 4), "▍");
+        assert_eq!(render_bar(3, 10, 4), "█▎");
+        assert_eq!(render_bar(0, 10, 4), "");
+        assert_eq!(render_bar(0, 0, 4), "");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format_num(3.0), "3");
+        assert_eq!(format_num(-120.0), "-120");
+        assert_eq!(format_num(2.5), "2.5");
+        assert_eq!(format_num(0.1 + 0.2), "0.3");
+        assert_eq!(format_num(1.23456), "1.235");
+        assert_eq!(format_num(2.0001), "2");
+    }
+}
@@ -0,0 +1,308 @@
+//! The `db column-rename` subcommand. Developer tool only.
+
+use std::{ops::Range, path::{Path, PathBuf}};
+
+use crate::{prelude::*, db::{Db, migrations::MIGRATIONS}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    table: &str,
+    old_name: &str,
+    new_name: &str,
+    update_source: bool,
+) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        bail!("`db column-rename` is a developer tool and only available in debug builds");
+    }
+
+    // Only plain identifiers, so that we neither need to quote them in the
+    // migration nor worry about quoting when searching the source code.
+    for name in [table, old_name, new_name] {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            bail!("'{name}' is not a plain identifier (only lowercase ASCII letters, digits \
+                and '_' allowed)");
+        }
+    }
+
+    // Check against the database, which therefore has to be up to date.
+    let columns = db
+        .query(
+            "select column_name::text from information_schema.columns \
+                where table_schema = 'public' and table_name = $1",
+            &[&table],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        bail!("table '{table}' does not exist (is the database migrated?)");
+    }
+    if !columns.iter().any(|c| c == old_name) {
+        bail!("column '{old_name}' does not exist in table '{table}'");
+    }
+    if columns.iter().any(|c| c == new_name) {
+        bail!("column '{new_name}' already exists in table '{table}'");
+    }
+
+    // ----- Migration -----------------------------------------------------------------------------
+    let id = MIGRATIONS.keys().last().map_or(1, |id| id + 1);
+    let name = format!("rename-{table}-{old_name}").replace('_', "-");
+    let sql = format!("alter table {table} rename column {old_name} to {new_name};\n");
+    bunt::println!("{$dimmed}-- Migration {}: {}{/$}", id, name);
+    print!("{sql}");
+
+    if !update_source {
+        println!();
+        println!("Run again with `--update-source` to add this migration and update references \
+            in the source code.");
+        return Ok(());
+    }
+
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let migration_path = src.join(format!("db/migrations/{id:02}-{name}.sql"));
+    std::fs::write(&migration_path, &sql)
+        .with_context(|| format!("failed to write '{}'", migration_path.display()))?;
+    info!("Wrote migration to '{}'", migration_path.display());
+
+    let registry = src.join("db/migrations.rs");
+    let original = read(&registry)?;
+    let end = original.find("include_migrations![")
+        .and_then(|start| original[start..].find("];").map(|end| start + end))
+        .ok_or_else(|| anyhow!("no migration list found in '{}'", registry.display()))?;
+    let mut modified = original.clone();
+    modified.insert_str(end, &format!("    {id:02}: \"{name}\",\n"));
+    print_diff(&registry, &original, &modified);
+    write(&registry, &modified)?;
+
+
+    // ----- Source code ---------------------------------------------------------------------------
+    //
+    // This is a heuristic: we replace `old_name` in all string literals that
+    // also mention `table`. Queries building their column list elsewhere are
+    // not found and unrelated columns with the same name might be changed, so
+    // the diff needs to be reviewed.
+    let mut files = Vec::new();
+    collect_rust_files(&src, &mut files)?;
+    files.sort();
+
+    let mut changed_files = 0;
+    let mut changed_literals = 0;
+    for path in files {
+        let original = read(&path)?;
+        let mut modified = original.clone();
+
+        // Backwards, so that earlier spans stay valid.
+        for span in string_literals(&original).into_iter().rev() {
+            let literal = &original[span.clone()];
+            if find_word(literal, table).is_empty() {
+                continue;
+            }
+            let occurrences = find_word(literal, old_name)
+                .into_iter()
+                // Skip format arguments like `{old_name}`, which are Rust variables.
+                .filter(|&pos| !literal[..pos].ends_with('{'))
+                .collect::<Vec<_>>();
+            for &pos in occurrences.iter().rev() {
+                let start = span.start + pos;
+                modified.replace_range(start..start + old_name.len(), new_name);
+            }
+            if !occurrences.is_empty() {
+                changed_literals += 1;
+            }
+        }
+
+        if modified != original {
+            changed_files += 1;
+            print_diff(&path, &original, &modified);
+            write(&path, &modified)?;
+        }
+    }
+
+    println!();
+    info!("Changed {changed_literals} string literals in {changed_files} files");
+    bunt::println!("{$yellow+bold}Review these changes!{/$} Only string literals mentioning \
+        '{}' were considered: column names in other places (e.g. in `const`s or format \
+        arguments) have to be updated manually.", table);
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("failed to read '{}'", path.display()))
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content).with_context(|| format!("failed to write '{}'", path.display()))
+}
+
+fn collect_rust_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read directory '{}'", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_rust_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the changed lines in a format similar to a unified diff. Renaming
+/// never adds or removes lines, so lines can simply be compared pairwise.
+/// Otherwise (i.e. when adding the migration), the differing block between the
+/// common first and last lines is printed.
+fn print_diff(path: &Path, original: &str, modified: &str) {
+    println!();
+    bunt::println!("{$bold}--- a/{}{/$}", path.display());
+    bunt::println!("{$bold}+++ b/{}{/$}", path.display());
+
+    let original = original.lines().collect::<Vec<_>>();
+    let modified = modified.lines().collect::<Vec<_>>();
+    let hunks = if original.len() == modified.len() {
+        (0..original.len())
+            .filter(|&i| original[i] != modified[i])
+            .map(|i| (i, i..i + 1, i..i + 1))
+            .collect::<Vec<_>>()
+    } else {
+        let prefix = original.iter().zip(&modified).take_while(|(a, b)| a == b).count();
+        let suffix = original[prefix..].iter().rev()
+            .zip(modified[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        vec![(prefix, prefix..original.len() - suffix, prefix..modified.len() - suffix)]
+    };
+
+    for (line, removed, added) in hunks {
+        bunt::println!("{$cyan}@@ line {} @@{/$}", line + 1);
+        for old in &original[removed] {
+            bunt::println!("{$red}-{}{/$}", old);
+        }
+        for new in &modified[added] {
+            bunt::println!("{$green}+{}{/$}", new);
+        }
+    }
+}
+
+/// Returns the byte ranges of the contents (without quotes) of all string
+/// literals in the given Rust source, including raw and byte strings. Comments
+/// and char literals are skipped.
+fn string_literals(src: &str) -> Vec<Range<usize>> {
+    let bytes = src.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = src[i..].find('\n').map_or(bytes.len(), |end| i + end);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // Block comments can be nested.
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            b'r' if (i == 0 || !is_ident(bytes[i - 1]) || bytes[i - 1] == b'b')
+                && matches!(bytes.get(i + 1), Some(b'"' | b'#')) =>
+            {
+                let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                let quote = i + 1 + hashes;
+                if bytes.get(quote) != Some(&b'"') {
+                    // E.g. `r#ident`.
+                    i += 1;
+                    continue;
+                }
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let start = quote + 1;
+                let end = src[start..].find(&closing).map_or(bytes.len(), |end| start + end);
+                out.push(start..end);
+                i = end + closing.len();
+            }
+            b'"' => {
+                let start = i + 1;
+                i = start;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                out.push(start..i.min(bytes.len()));
+                i += 1;
+            }
+            b'\'' => {
+                // Char literal or lifetime.
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    i = src[i + 2..].find('\'').map_or(bytes.len(), |end| i + 2 + end + 1);
+                } else {
+                    let len = src[i + 1..].chars().next().map_or(0, char::len_utf8);
+                    i += if bytes.get(i + 1 + len) == Some(&b'\'') { len + 2 } else { 1 };
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    out
+}
+
+/// Returns the positions where `word` occurs in `s` as a whole SQL identifier.
+fn find_word(s: &str, word: &str) -> Vec<usize> {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    s.match_indices(word)
+        .filter(|(pos, _)| {
+            !s[..*pos].ends_with(is_ident) && !s[pos + word.len()..].starts_with(is_ident)
+        })
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{find_word, string_literals};
+
+    fn literals(src: &str) -> Vec<&str> {
+        string_literals(src).into_iter().map(|span| &src[span]).collect()
+    }
+
+    #[test]
+    fn finds_string_literals() {
+        assert_eq!(literals(r#"let x = "a"; f("b\"c", b"d");"#), ["a", r#"b\"c"#, "d"]);
+        assert_eq!(literals(r###"r"a" r#"b"c"# br##"d"##"###), ["a", r#"b"c"#, "d"]);
+        assert_eq!(literals("\"select \\\n    x\""), ["select \\\n    x"]);
+    }
+
+    #[test]
+    fn skips_comments_and_chars() {
+        assert_eq!(literals("// \"a\"\n\"b\""), ["b"]);
+        assert_eq!(literals("/* /* \"a\" */ \"b\" */ \"c\""), ["c"]);
+        assert_eq!(literals("let c = '\"'; let d = '\\''; \"e\""), ["e"]);
+        assert_eq!(literals("fn f<'a>(x: &'a str) -> &'static str { \"x\" }"), ["x"]);
+        assert_eq!(literals("r#type; \"a\""), ["a"]);
+    }
+
+    #[test]
+    fn finds_whole_words() {
+        assert_eq!(find_word("select title from events", "title"), [7]);
+        assert!(find_word("select title_lower, subtitle from events", "title").is_empty());
+        assert_eq!(find_word("events.title,title", "title"), [7, 13]);
+    }
+}
@@ -0,0 +1,266 @@
+//! The `db column-type-change` subcommand.
+
+use tokio_postgres::GenericClient;
+
+use crate::{prelude::*, db::Db};
+
+
+/// Number of rows updated per statement when backfilling the shadow column.
+/// Each batch is its own transaction, so locks are held only briefly.
+const BACKFILL_BATCH_SIZE: i64 = 10_000;
+
+/// Starting with PostgreSQL 12, `set not null` can skip the full table scan if
+/// a valid check constraint already proves the column is not null.
+const PG_NOT_NULL_FROM_CHECK_VERSION: i32 = 120000;
+
+/// How long the final step waits for the exclusive lock before giving up.
+/// Without this, all other queries on the table would queue up behind it.
+const SWAP_LOCK_TIMEOUT: &str = "5s";
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &mut Db,
+    table_name: &str,
+    column_name: &str,
+    new_type: &str,
+    using: Option<&str>,
+) -> Result<()> {
+    let table = quote_ident(table_name);
+    let column = quote_ident(column_name);
+    let shadow_name = format!("{column_name}__new");
+    let shadow = quote_ident(&shadow_name);
+    let sync_fn = quote_ident(&format!("{table_name}_{column_name}__sync"));
+    let check_name = quote_ident(&format!("{shadow_name}_not_null"));
+    let using = using.map_or_else(|| format!("{column}::{new_type}"), |u| u.to_owned());
+
+    // ----- Checks -------------------------------------------------------------------------------
+    let table_exists = db
+        .query_one(
+            "select exists(\
+                select from information_schema.tables \
+                where table_schema = 'public' and table_name = $1\
+            )",
+            &[&table_name],
+        )
+        .await?
+        .get::<_, bool>(0);
+    if !table_exists {
+        bail!("table '{table_name}' does not exist");
+    }
+
+    let row = db
+        .query_opt(
+            "select format_type(a.atttypid, a.atttypmod), a.attnotnull, a.attidentity <> '', \
+                    pg_get_expr(d.adbin, d.adrelid), col_description(a.attrelid, a.attnum) \
+                from pg_attribute a \
+                left join pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum \
+                where a.attrelid = $1::text::regclass and a.attname = $2 \
+                    and a.attnum > 0 and not a.attisdropped",
+            &[&table, &column_name],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("column '{column_name}' does not exist in table '{table_name}'"))?;
+    let old_type: String = row.get(0);
+    let not_null: bool = row.get(1);
+    let is_identity: bool = row.get(2);
+    let default: Option<String> = row.get(3);
+    let comment: Option<String> = row.get(4);
+    if is_identity {
+        bail!("column '{column_name}' is an identity column, which is not supported");
+    }
+
+    let shadow_exists = db
+        .query_one(
+            "select exists(\
+                select from information_schema.columns \
+                where table_schema = 'public' and table_name = $1 and column_name = $2\
+            )",
+            &[&table_name, &shadow_name],
+        )
+        .await?
+        .get::<_, bool>(0);
+    if shadow_exists {
+        bail!("column '{shadow_name}' already exists in table '{table_name}' (left over from \
+            a previous run?)");
+    }
+
+    // The old column is dropped at the end, so nothing else may depend on it.
+    // Defaults are carried over to the new column.
+    let dependents = db
+        .query(
+            "select pg_describe_object(d.classid, d.objid, d.objsubid) \
+                from pg_depend d \
+                join pg_attribute a on a.attrelid = d.refobjid and a.attnum = d.refobjsubid \
+                where d.refclassid = 'pg_class'::regclass \
+                    and d.refobjid = $1::text::regclass \
+                    and a.attname = $2 \
+                    and d.classid <> 'pg_attrdef'::regclass",
+            &[&table, &column_name],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<Vec<_>>();
+    if !dependents.is_empty() {
+        bail!("the following objects depend on column '{column_name}' and would have to be \
+            dropped and recreated manually: {}", dependents.join(", "));
+    }
+
+    // Backfilling is done in batches ordered by the primary key.
+    let pk = db
+        .query(
+            "select a.attname::text, format_type(a.atttypid, a.atttypmod) \
+                from pg_index i \
+                join pg_attribute a on a.attrelid = i.indrelid and a.attnum = any(i.indkey) \
+                where i.indrelid = $1::text::regclass and i.indisprimary",
+            &[&table],
+        )
+        .await?;
+    let (pk, pk_type) = match &*pk {
+        [row] => (quote_ident(&row.get::<_, String>(0)), row.get::<_, String>(1)),
+        _ => bail!("table '{table_name}' needs a primary key consisting of a single column"),
+    };
+
+    info!("Changing type of {table}.{column} from '{old_type}' to '{new_type}' (using \
+        `{using}`)");
+
+
+    // ----- Step 1: add shadow column and keep it in sync ----------------------------------------
+    //
+    // The trigger evaluates `using` for the new row. The subquery is aliased
+    // as the table, so that the expression can refer to columns just like in
+    // `alter column ... type ... using`.
+    let tx = db.transaction().await?;
+    execute(&*tx, &format!("alter table {table} add column {shadow} {new_type}")).await?;
+    execute(&*tx, &format!(
+        "create function {sync_fn}() returns trigger language plpgsql as $$\n\
+        begin\n    \
+            new.{shadow} := (select {using} from (select new.*) as {table});\n    \
+            return new;\n\
+        end;\n\
+        $$",
+    )).await?;
+    execute(&*tx, &format!(
+        "create trigger {sync_fn} before insert or update of {column} on {table} \
+            for each row execute procedure {sync_fn}()",
+    )).await?;
+    tx.commit().await.context("failed to add shadow column")?;
+
+    // If anything fails from here on, we remove the shadow column and trigger
+    // again. Otherwise, the trigger could break writes to the table.
+    let cleanup = [
+        format!("drop trigger if exists {sync_fn} on {table}"),
+        format!("drop function if exists {sync_fn}()"),
+        format!("alter table {table} drop column if exists {shadow}"),
+    ];
+    let res = async {
+        // ----- Step 2: backfill existing rows -----------------------------------------------
+        info!("Backfilling existing rows in batches of {BACKFILL_BATCH_SIZE}...");
+        let query = format!(
+            "with batch as (\
+                select {pk} from {table} \
+                    where $1::text is null or {pk} > $1::text::{pk_type} \
+                    order by {pk} \
+                    limit {BACKFILL_BATCH_SIZE}\
+            ), updated as (\
+                update {table} set {shadow} = {using} \
+                    where {pk} in (select {pk} from batch) \
+                    returning {pk}\
+            ) \
+            select count(*), max({pk})::text from updated",
+        );
+        let mut last: Option<String> = None;
+        let mut total = 0;
+        loop {
+            let row = db.query_one(&query, &[&last]).await
+                .context("failed to backfill shadow column")?;
+            let updated: i64 = row.get(0);
+            if updated == 0 {
+                break;
+            }
+            total += updated;
+            last = row.get(1);
+            debug!("Backfilled {total} rows so far");
+        }
+        info!("Backfilled {total} rows");
+
+        // ----- Step 3: prove `not null` without an exclusive lock ---------------------------
+        if not_null {
+            execute(&**db, &format!(
+                "alter table {table} add constraint {check_name} \
+                    check ({shadow} is not null) not valid",
+            )).await?;
+            execute(&**db, &format!("alter table {table} validate constraint {check_name}"))
+                .await?;
+        }
+
+        // ----- Step 4: swap columns atomically ----------------------------------------------
+        let version: i32 = db.query_one("select current_setting('server_version_num')::int", &[])
+            .await?
+            .get(0);
+        if not_null && version < PG_NOT_NULL_FROM_CHECK_VERSION {
+            warn!("PostgreSQL < 12: `set not null` requires a full table scan with an \
+                exclusive lock");
+        }
+
+        let tx = db.transaction().await?;
+        let mut statements = vec![
+            format!("set local lock_timeout = '{SWAP_LOCK_TIMEOUT}'"),
+            format!("drop trigger {sync_fn} on {table}"),
+            format!("drop function {sync_fn}()"),
+            format!("alter table {table} drop column {column}"),
+            format!("alter table {table} rename column {shadow} to {column}"),
+        ];
+        if not_null {
+            statements.push(format!("alter table {table} alter column {column} set not null"));
+            statements.push(format!("alter table {table} drop constraint {check_name}"));
+        }
+        if let Some(default) = &default {
+            statements.push(
+                format!("alter table {table} alter column {column} set default {default}"),
+            );
+        }
+        if let Some(comment) = &comment {
+            statements.push(format!(
+                "comment on column {table}.{column} is '{}'",
+                comment.replace('\'', "''"),
+            ));
+        }
+        for sql in &statements {
+            execute(&*tx, sql).await?;
+        }
+        tx.commit().await.context("failed to swap columns")?;
+
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    if let Err(e) = res {
+        warn!("Failed, removing shadow column and trigger again...");
+        for sql in &cleanup {
+            if let Err(e) = execute(&**db, sql).await {
+                error!("{e:#}");
+            }
+        }
+        return Err(e);
+    }
+
+    bunt::println!(
+        "{$green+intense}Successfully changed type of column {} in table {} to '{}'.{/$}",
+        column,
+        table,
+        new_type,
+    );
+
+    Ok(())
+}
+
+async fn execute(db: &impl GenericClient, sql: &str) -> Result<()> {
+    bunt::println!("{$dimmed}> {}{/$}", sql);
+    db.execute(sql, &[]).await.with_context(|| format!("failed to execute '{sql}'"))?;
+    Ok(())
+}
+
+/// Quotes the given identifier for use in SQL.
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
@@ -0,0 +1,176 @@
+//! The `db constraint-disable` and `db constraint-enable` subcommands.
+
+use tokio_postgres::GenericClient;
+
+use crate::{prelude::*, db::{Db, query}};
+
+
+/// A foreign key constraint that is violated by some rows.
+struct Violation {
+    constraint: String,
+    table: String,
+    referenced_table: String,
+    rows: i64,
+}
+
+
+/// Implementation of `db constraint-disable`, see that for docs.
+pub(crate) async fn disable(db: &mut Db, table: Option<&str>) -> Result<()> {
+    let tx = db.transaction().await?;
+    let tables = resolve_tables(&*tx, table).await?;
+
+    for table in &tables {
+        let sql = format!("alter table {} disable trigger all", query::qualified(table));
+        tx.batch_execute(&sql)
+            .await
+            .with_context(|| format!("failed to disable triggers of '{table}' \
+                (this requires superuser privileges)"))?;
+    }
+
+    // Disabling the triggers is sufficient for the existing tables. Setting
+    // this for the role also covers new sessions, e.g. those of the import
+    // tool. It only has an effect on sessions started after this.
+    if table.is_none() {
+        tx.batch_execute("alter role current_user set session_replication_role = 'replica'")
+            .await
+            .context("failed to set `session_replication_role` \
+                (this requires superuser privileges)")?;
+    }
+
+    tx.commit().await?;
+
+    info!("Disabled all triggers (including foreign key checks) of {} tables", tables.len());
+    bunt::println!("{$yellow+bold}Warning:{/$} Tobira's own triggers are disabled as well, so \
+        derived data (e.g. the search index queue) is not updated while loading. Run \
+        `db constraint-enable` with the same arguments when done.");
+
+    Ok(())
+}
+
+/// Implementation of `db constraint-enable`, see that for docs.
+pub(crate) async fn enable(db: &mut Db, table: Option<&str>) -> Result<()> {
+    let tx = db.transaction().await?;
+    let tables = resolve_tables(&*tx, table).await?;
+
+    for table in &tables {
+        let sql = format!("alter table {} enable trigger all", query::qualified(table));
+        tx.batch_execute(&sql)
+            .await
+            .with_context(|| format!("failed to enable triggers of '{table}'"))?;
+    }
+    if table.is_none() {
+        tx.batch_execute("alter role current_user reset session_replication_role")
+            .await
+            .context("failed to reset `session_replication_role`")?;
+    }
+
+    // Re-enabling the triggers does not check the rows that were written in
+    // the meantime, so we do that manually before committing.
+    info!("Checking foreign keys of {} tables...", tables.len());
+    let violations = find_violations(&*tx, &tables).await?;
+    if !violations.is_empty() {
+        tx.rollback().await?;
+
+        bunt::println!("{$red+bold}Found {} violated foreign key constraints:{/$}",
+            violations.len());
+        for v in &violations {
+            bunt::println!(
+                " - {[blue+intense]}: {[bold]} rows in '{}' reference missing rows in '{}'",
+                v.constraint,
+                v.rows,
+                v.table,
+                v.referenced_table,
+            );
+        }
+        println!();
+        bail!("refusing to enable constraints: fix the violations above first \
+            (constraints are still disabled)");
+    }
+
+    tx.commit().await?;
+    info!("Enabled all triggers of {} tables, no foreign key violations found", tables.len());
+    println!("Note: data written while triggers were disabled did not run through Tobira's \
+        triggers. You might want to run `search-index rebuild`.");
+
+    Ok(())
+}
+
+/// Returns the given table (after checking it exists) or all tables.
+async fn resolve_tables(db: &impl GenericClient, table: Option<&str>) -> Result<Vec<String>> {
+    match table {
+        Some(table) => {
+            if !query::does_table_exist(db, table).await? {
+                bail!("table '{table}' does not exist");
+            }
+            Ok(vec![table.to_owned()])
+        }
+        None => query::all_table_names(db).await,
+    }
+}
+
+/// Checks all foreign keys from or to the given tables. Like Postgres
+/// (`match simple`), rows with `null` in any of the key columns are ignored.
+async fn find_violations(db: &impl GenericClient, tables: &[String]) -> Result<Vec<Violation>> {
+    let constraints = db
+        .query(
+            "select c.conname::text, child.relname::text, parent.relname::text,
+                    array(
+                        select a.attname::text
+                        from unnest(c.conkey) with ordinality as k(attnum, ord)
+                        join pg_attribute a on a.attrelid = c.conrelid and a.attnum = k.attnum
+                        order by k.ord
+                    ),
+                    array(
+                        select a.attname::text
+                        from unnest(c.confkey) with ordinality as k(attnum, ord)
+                        join pg_attribute a on a.attrelid = c.confrelid and a.attnum = k.attnum
+                        order by k.ord
+                    )
+                from pg_constraint c
+                join pg_class child on child.oid = c.conrelid
+                join pg_class parent on parent.oid = c.confrelid
+                where c.contype = 'f'
+                    and c.connamespace = 'public'::regnamespace
+                    and (child.relname = any($1) or parent.relname = any($1))
+                order by 2, 1",
+            &[&tables],
+        )
+        .await
+        .context("failed to query foreign key constraints")?;
+
+    let mut out = Vec::new();
+    for row in constraints {
+        let constraint: String = row.get(0);
+        let table: String = row.get(1);
+        let referenced_table: String = row.get(2);
+        let columns: Vec<String> = row.get(3);
+        let referenced_columns: Vec<String> = row.get(4);
+
+        let not_null = columns.iter()
+            .map(|c| format!("c.{} is not null", query::quote_ident(c)))
+            .collect::<Vec<_>>()
+            .join(" and ");
+        let matches = columns.iter().zip(&referenced_columns)
+            .map(|(c, p)| {
+                format!("p.{} = c.{}", query::quote_ident(p), query::quote_ident(c))
+            })
+            .collect::<Vec<_>>()
+            .join(" and ");
+        let sql = format!(
+            "select count(*) from {} c where {not_null} and not exists (\
+                select from {} p where {matches})",
+            query::qualified(&table),
+            query::qualified(&referenced_table),
+        );
+        let rows: i64 = db.query_one(&sql, &[])
+            .await
+            .with_context(|| format!("failed to check constraint '{constraint}'"))?
+            .get(0);
+
+        if rows > 0 {
+            out.push(Violation { constraint, table, referenced_table, rows });
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,89 @@
+//! The `db dead-tuple-check` subcommand.
+
+use chrono::{DateTime, Utc};
+
+use crate::{prelude::*, db::Db};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, threshold_pct: f64) -> Result<()> {
+    if !(0.0..=100.0).contains(&threshold_pct) {
+        bail!("threshold has to be between 0 and 100, but is {threshold_pct}");
+    }
+
+    let query = "
+        select
+            relname::text,
+            n_live_tup,
+            n_dead_tup,
+            (100.0 * n_dead_tup / nullif(n_live_tup + n_dead_tup, 0))::float8,
+            autovacuum_count,
+            last_autovacuum,
+            last_vacuum
+        from pg_stat_user_tables
+        where schemaname = 'public'
+            and 100.0 * n_dead_tup / nullif(n_live_tup + n_dead_tup, 0) > $1::float8
+        order by 4 desc
+    ";
+    let rows = db.query(query, &[&threshold_pct])
+        .await
+        .context("failed to query table statistics")?;
+
+    if rows.is_empty() {
+        bunt::println!(
+            "{$green+intense}No table has more than {}% dead tuples.{/$}",
+            threshold_pct,
+        );
+        return Ok(());
+    }
+
+    bunt::println!("{$bold}Tables with more than {}% dead tuples:{/$}", threshold_pct);
+    println!();
+
+    let mut suggest_tuning = false;
+    for row in &rows {
+        let table: String = row.get(0);
+        let live: i64 = row.get(1);
+        let dead: i64 = row.get(2);
+        let ratio: f64 = row.get(3);
+        let autovacuum_count: i64 = row.get(4);
+        let last_autovacuum: Option<DateTime<Utc>> = row.get(5);
+        let last_vacuum: Option<DateTime<Utc>> = row.get(6);
+
+        bunt::println!(
+            " - {[bold]}: {[red+intense]} {$dimmed}({} dead, {} live){/$}",
+            table,
+            format!("{ratio:.1}%"),
+            dead,
+            live,
+        );
+        bunt::println!("   {$dimmed}autovacuum count:{/$} {}", autovacuum_count);
+        let format_time = |t: Option<DateTime<Utc>>| t.map_or("never".into(), |t| t.to_string());
+        bunt::println!("   {$dimmed}last autovacuum:{/$}  {}", format_time(last_autovacuum));
+        bunt::println!("   {$dimmed}last vacuum:{/$}      {}", format_time(last_vacuum));
+
+        // If autovacuum has never run on this table (or not in the last day),
+        // it likely never reaches its threshold for this table.
+        let autovacuum_stale = last_autovacuum
+            .is_none_or(|t| Utc::now() - t > chrono::Duration::days(1));
+        if autovacuum_stale {
+            suggest_tuning = true;
+            println!(
+                "   autovacuum seems to be missing this table, consider: \
+                    alter table {table} set (autovacuum_vacuum_scale_factor = 0.05);",
+            );
+        }
+    }
+
+    if suggest_tuning {
+        println!();
+        println!(
+            "Note: autovacuum only processes a table once its dead tuples exceed \
+                `autovacuum_vacuum_threshold + autovacuum_vacuum_scale_factor * n_live_tup`. \
+                Lowering the scale factor for large tables makes autovacuum run more often. \
+                You can also run `vacuum analyze <table>` manually right now.",
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! The `db disk-projection` subcommand.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::{prelude::*, db::{Db, query}};
+
+
+/// Name of the table containing historical table sizes. Expected columns:
+///
+/// - `captured_at timestamp with time zone not null`: when the snapshot was
+///   taken. All rows of one snapshot have the same value.
+/// - `table_name text not null`
+/// - `total_bytes bigint not null`: as returned by `pg_total_relation_size`
+const TABLE: &str = "__tobira_stats_history";
+
+/// Minimum number of snapshots required for a projection. With fewer, no
+/// confidence interval can be computed.
+const MIN_SNAPSHOTS: usize = 3;
+
+/// Two-sided 97.5% quantiles of Student's t-distribution for 1 to 30 degrees
+/// of freedom, used for 95% prediction intervals.
+const T_QUANTILES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, days_ahead: u32) -> Result<()> {
+    if !query::does_table_exist(&**db, TABLE).await? {
+        bail!("table '{TABLE}' does not exist: no size history has been recorded");
+    }
+
+    let rows = db
+        .query(
+            &format!("select captured_at, table_name, total_bytes from {TABLE} \
+                order by captured_at"),
+            &[],
+        )
+        .await
+        .with_context(|| format!("failed to query '{TABLE}'"))?;
+
+    // All times are relative to now, in days, so that the projection is
+    // simply the value at `days_ahead`.
+    let now = Utc::now();
+    let days = |t: DateTime<Utc>| (t - now).num_seconds() as f64 / (24.0 * 60.0 * 60.0);
+
+    let mut per_table = BTreeMap::<String, Vec<(f64, f64)>>::new();
+    let mut totals = BTreeMap::<DateTime<Utc>, f64>::new();
+    for row in rows {
+        let captured_at: DateTime<Utc> = row.get(0);
+        let bytes = row.get::<_, i64>(2) as f64;
+        per_table.entry(row.get(1)).or_default().push((days(captured_at), bytes));
+        *totals.entry(captured_at).or_default() += bytes;
+    }
+    let totals = totals.into_iter().map(|(t, bytes)| (days(t), bytes)).collect::<Vec<_>>();
+
+    let total_fit = Fit::new(&totals).ok_or_else(|| anyhow!(
+        "not enough snapshots in '{TABLE}' (at least {MIN_SNAPSHOTS} at different times \
+            are required)",
+    ))?;
+
+    let x = f64::from(days_ahead);
+    let mut projections = Vec::new();
+    let mut insufficient = Vec::new();
+    for (table, points) in &per_table {
+        match Fit::new(points) {
+            Some(fit) => projections.push((table.as_str(), points, fit)),
+            None => insufficient.push(table.as_str()),
+        }
+    }
+    projections.sort_by(|(_, _, a), (_, _, b)| b.predict(x).0.total_cmp(&a.predict(x).0));
+
+    bunt::println!("{$bold}Projected sizes in {} days (95% prediction interval):{/$}", days_ahead);
+    println!();
+    for (table, points, fit) in &projections {
+        print_projection(table, points, fit, x);
+    }
+    println!();
+    print_projection("total", &totals, &total_fit, x);
+
+    if !insufficient.is_empty() {
+        println!();
+        bunt::println!(
+            "{$dimmed}Not enough snapshots for a projection: {}{/$}",
+            insufficient.join(", "),
+        );
+    }
+
+    Ok(())
+}
+
+fn print_projection(name: &str, points: &[(f64, f64)], fit: &Fit, x: f64) {
+    let (projected, margin) = fit.predict(x);
+    let current = points.last().map_or(0.0, |(_, bytes)| *bytes);
+    bunt::println!(
+        " - {[bold]}: {} → {[blue+intense]} {$dimmed}({} – {}, {}/day){/$}",
+        name,
+        human_bytes(current),
+        human_bytes(projected.max(0.0)),
+        human_bytes((projected - margin).max(0.0)),
+        human_bytes(projected + margin),
+        human_bytes(fit.slope),
+    );
+}
+
+/// Result of a simple linear regression.
+#[derive(Debug)]
+struct Fit {
+    slope: f64,
+    intercept: f64,
+    n: usize,
+    mean_x: f64,
+    /// Sum of squared deviations of x from its mean.
+    sxx: f64,
+    /// Residual standard error.
+    s: f64,
+}
+
+impl Fit {
+    /// Returns `None` if there are too few points or they all have the same x.
+    fn new(points: &[(f64, f64)]) -> Option<Self> {
+        let n = points.len();
+        if n < MIN_SNAPSHOTS {
+            return None;
+        }
+
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n as f64;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n as f64;
+        let sxx = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum::<f64>();
+        let sxy = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum::<f64>();
+        if sxx == 0.0 {
+            return None;
+        }
+
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let sse = points.iter()
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum::<f64>();
+        let s = (sse / (n - 2) as f64).sqrt();
+
+        Some(Self { slope, intercept, n, mean_x, sxx, s })
+    }
+
+    /// Returns the predicted value at `x` and the half width of the 95%
+    /// prediction interval around it.
+    fn predict(&self, x: f64) -> (f64, f64) {
+        let n = self.n as f64;
+        let t = T_QUANTILES.get(self.n - 3).copied().unwrap_or(1.96);
+        let margin = t * self.s * (1.0 + 1.0 / n + (x - self.mean_x).powi(2) / self.sxx).sqrt();
+        (self.intercept + self.slope * x, margin)
+    }
+}
+
+/// Formats a number of bytes like `pg_size_pretty` does.
+pub(super) fn human_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "kB", "MB", "GB", "TB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Fit, human_bytes};
+
+    #[test]
+    fn exact_line() {
+        let fit = Fit::new(&[(-2.0, 100.0), (-1.0, 200.0), (0.0, 300.0)]).unwrap();
+        let (projected, margin) = fit.predict(10.0);
+        assert!((projected - 1300.0).abs() < 1e-9);
+        assert!(margin.abs() < 1e-9);
+    }
+
+    #[test]
+    fn noisy_line() {
+        let points = [(-4.0, 90.0), (-3.0, 210.0), (-2.0, 290.0), (-1.0, 410.0), (0.0, 500.0)];
+        let fit = Fit::new(&points).unwrap();
+        let (near, near_margin) = fit.predict(1.0);
+        let (far, far_margin) = fit.predict(30.0);
+        assert!((fit.slope - 102.0).abs() < 1e-9);
+        assert!(near < far);
+        assert!(near_margin > 0.0 && near_margin < far_margin);
+    }
+
+    #[test]
+    fn too_few_points() {
+        assert!(Fit::new(&[(0.0, 1.0), (1.0, 2.0)]).is_none());
+        assert!(Fit::new(&[(1.0, 1.0), (1.0, 2.0), (1.0, 3.0)]).is_none());
+    }
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(human_bytes(512.0), "512 bytes");
+        assert_eq!(human_bytes(1536.0), "1.5 kB");
+        assert_eq!(human_bytes(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0 GB");
+    }
+}
@@ -0,0 +1,186 @@
+//! The `db doc` subcommand.
+
+use std::{fmt::Write, path::Path};
+
+use crate::{prelude::*, db::Db};
+
+
+struct Table {
+    oid: u32,
+    name: String,
+    comment: Option<String>,
+}
+
+struct ForeignKey {
+    name: String,
+    definition: String,
+    target: String,
+}
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, out: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(out)
+        .await
+        .with_context(|| format!("failed to create directory '{}'", out.display()))?;
+
+    let tables = db
+        .query(
+            "select oid, relname::text, obj_description(oid, 'pg_class') \
+                from pg_class \
+                where relkind = 'r' and relnamespace = 'public'::regnamespace \
+                order by relname",
+            &[],
+        )
+        .await
+        .context("failed to query tables")?
+        .into_iter()
+        .map(|row| Table { oid: row.get(0), name: row.get(1), comment: row.get(2) })
+        .collect::<Vec<_>>();
+
+    if tables.is_empty() {
+        bail!("database has no tables: run `db migrate` first");
+    }
+
+    let mut missing = Vec::new();
+    let mut relationships = Vec::new();
+    for table in &tables {
+        if table.comment.is_none() {
+            missing.push(format!("table {}", table.name));
+        }
+
+        let mut md = format!("# Table `{}`\n\n", table.name);
+        if let Some(comment) = &table.comment {
+            writeln!(md, "{comment}\n")?;
+        }
+
+        // Columns
+        let columns = db
+            .query(
+                "select \
+                    a.attname::text, \
+                    format_type(a.atttypid, a.atttypmod), \
+                    a.attnotnull, \
+                    pg_get_expr(d.adbin, d.adrelid), \
+                    col_description(a.attrelid, a.attnum) \
+                from pg_attribute a \
+                left join pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum \
+                where a.attrelid = $1 and a.attnum > 0 and not a.attisdropped \
+                order by a.attnum",
+                &[&table.oid],
+            )
+            .await
+            .with_context(|| format!("failed to query columns of '{}'", table.name))?;
+
+        md += "## Columns\n\n";
+        md += "| Name | Type | Nullable | Default | Description |\n";
+        md += "| ---- | ---- | -------- | ------- | ----------- |\n";
+        for row in &columns {
+            let name: String = row.get(0);
+            let ty: String = row.get(1);
+            let not_null: bool = row.get(2);
+            let default: Option<String> = row.get(3);
+            let comment: Option<String> = row.get(4);
+
+            if comment.is_none() {
+                missing.push(format!("column {}.{}", table.name, name));
+            }
+            writeln!(
+                md,
+                "| `{}` | `{}` | {} | {} | {} |",
+                name,
+                ty,
+                if not_null { "no" } else { "yes" },
+                default.map_or(String::new(), |d| format!("`{}`", table_cell(&d))),
+                comment.as_deref().map(table_cell).unwrap_or_default(),
+            )?;
+        }
+
+        // Foreign keys
+        let foreign_keys = db
+            .query(
+                "select conname::text, pg_get_constraintdef(oid), confrelid::regclass::text \
+                    from pg_constraint \
+                    where conrelid = $1 and contype = 'f' \
+                    order by conname",
+                &[&table.oid],
+            )
+            .await
+            .with_context(|| format!("failed to query foreign keys of '{}'", table.name))?
+            .into_iter()
+            .map(|row| ForeignKey { name: row.get(0), definition: row.get(1), target: row.get(2) })
+            .collect::<Vec<_>>();
+
+        if !foreign_keys.is_empty() {
+            md += "\n## Foreign keys\n\n";
+            for fk in &foreign_keys {
+                writeln!(md, "- `{}`: `{}`", fk.name, fk.definition)?;
+                relationships.push((table.name.clone(), fk.target.clone(), fk.name.clone()));
+            }
+        }
+
+        // Indexes
+        let indexes = db
+            .query(
+                "select indexrelid::regclass::text, pg_get_indexdef(indexrelid) \
+                    from pg_index \
+                    where indrelid = $1 \
+                    order by 1",
+                &[&table.oid],
+            )
+            .await
+            .with_context(|| format!("failed to query indexes of '{}'", table.name))?;
+
+        if !indexes.is_empty() {
+            md += "\n## Indexes\n\n";
+            for row in &indexes {
+                let name: String = row.get(0);
+                let definition: String = row.get(1);
+                writeln!(md, "- `{name}`: `{definition}`")?;
+            }
+        }
+
+        let path = out.join(format!("{}.md", table.name));
+        tokio::fs::write(&path, md)
+            .await
+            .with_context(|| format!("failed to write '{}'", path.display()))?;
+    }
+
+    // Overview page with a relationship diagram.
+    let mut md = String::from("# Database schema\n\n");
+    for table in &tables {
+        writeln!(
+            md,
+            "- [`{0}`]({0}.md): {1}",
+            table.name,
+            table.comment.as_deref().unwrap_or(""),
+        )?;
+    }
+    md += "\n## Relationships\n\n```mermaid\nerDiagram\n";
+    for (from, to, name) in &relationships {
+        writeln!(md, "    {from} }}o--o| {to} : \"{name}\"")?;
+    }
+    md += "```\n";
+    let path = out.join("README.md");
+    tokio::fs::write(&path, md)
+        .await
+        .with_context(|| format!("failed to write '{}'", path.display()))?;
+
+    info!("Wrote documentation for {} tables to '{}'", tables.len(), out.display());
+
+    if !missing.is_empty() {
+        bunt::println!("{$bold}{} tables/columns have no comment:{/$}", missing.len());
+        for item in &missing {
+            bunt::println!(" - {[yellow+intense]}", item);
+        }
+        println!();
+        println!("Add `comment on` statements for these in a migration.");
+        bail!("{} tables/columns are not documented", missing.len());
+    }
+
+    Ok(())
+}
+
+/// Makes the given text safe to use inside a markdown table cell.
+fn table_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,311 @@
+//! The `db event-replay` subcommand. Developer tool only.
+
+use std::{collections::HashMap, path::Path};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio_postgres::types::ToSql;
+
+use crate::{prelude::*, db::Db};
+
+
+/// A single line of `wal2json` output with `format-version` 2.
+#[derive(Deserialize)]
+struct Message {
+    /// `B` (begin), `C` (commit), `I`, `U`, `D` (insert, update, delete),
+    /// `T` (truncate) or `M` (logical decoding message).
+    action: String,
+    schema: Option<String>,
+    table: Option<String>,
+    #[serde(default)]
+    columns: Vec<Column>,
+    /// The replica identity (usually the primary key) of the old row for
+    /// updates and deletes.
+    #[serde(default)]
+    identity: Vec<Column>,
+    /// Only present if `wal2json` was run with `include-timestamp`.
+    timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Column {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// A change together with the line it was read from, for error messages.
+struct Change {
+    line: usize,
+    message: Message,
+}
+
+struct Transaction {
+    /// Line of the first message belonging to this transaction.
+    line: usize,
+    timestamp: Option<DateTime<Utc>>,
+    changes: Vec<Change>,
+}
+
+/// A table in the target schema: maps column names to their SQL type.
+type TargetTable = HashMap<String, String>;
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &mut Db,
+    replication_log: &Path,
+    target_schema: &str,
+    stop_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        bail!("`db event-replay` is a developer tool and only available in debug builds");
+    }
+
+    let log = tokio::fs::read_to_string(replication_log)
+        .await
+        .with_context(|| format!("failed to read '{}'", replication_log.display()))?;
+    let transactions = parse_log(&log)?;
+    if stop_at.is_some() && transactions.iter().any(|tx| tx.timestamp.is_none()) {
+        bail!("`--stop-at` requires timestamps in the replication log: \
+            run `wal2json` with `include-timestamp`");
+    }
+
+    let tables = load_target_tables(db, target_schema).await?;
+
+    // The log already contains all changes done by triggers, so triggers must
+    // not fire again. Disabling them requires a superuser though.
+    let is_superuser = db
+        .query_one("select rolsuper from pg_roles where rolname = current_user", &[])
+        .await
+        .context("failed to check whether DB user is superuser")?
+        .get::<_, bool>(0);
+    if !is_superuser {
+        warn!("The DB user is not a superuser, so triggers cannot be disabled during the \
+            replay. Changes done by triggers might be applied twice!");
+    }
+
+    let mut replayed_transactions = 0;
+    let mut replayed_changes = 0;
+    for transaction in &transactions {
+        if let (Some(stop_at), Some(timestamp)) = (stop_at, transaction.timestamp) {
+            if timestamp > stop_at {
+                info!("Stopping before transaction committed at {timestamp} (line {})",
+                    transaction.line);
+                break;
+            }
+        }
+
+        let tx = db.transaction().await?;
+        if is_superuser {
+            tx.execute("set local session_replication_role = replica", &[]).await?;
+        }
+        for change in &transaction.changes {
+            replay_change(&tx, change, target_schema, &tables)
+                .await
+                .with_context(|| format!("failed to replay change in line {}", change.line))?;
+        }
+        tx.commit().await
+            .with_context(|| format!("failed to commit transaction (line {})", transaction.line))?;
+
+        replayed_transactions += 1;
+        replayed_changes += transaction.changes.len();
+    }
+
+    info!("Replayed {replayed_transactions} of {} transactions ({replayed_changes} changes) \
+        into schema '{target_schema}'", transactions.len());
+
+    Ok(())
+}
+
+/// Groups the messages of the log into transactions. Changes outside of
+/// `B`/`C` messages (`wal2json` with `include-transaction` disabled) are
+/// treated as a transaction each.
+fn parse_log(log: &str) -> Result<Vec<Transaction>> {
+    let mut transactions = Vec::new();
+    let mut current: Option<Transaction> = None;
+    for (i, line) in log.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Message = serde_json::from_str(line)
+            .with_context(|| format!("invalid JSON in line {line_no}"))?;
+        let timestamp = message.timestamp.as_deref()
+            .map(parse_timestamp)
+            .transpose()
+            .with_context(|| format!("invalid timestamp in line {line_no}"))?;
+
+        match message.action.as_str() {
+            "B" => {
+                if current.is_some() {
+                    bail!("unexpected begin of transaction in line {line_no}: previous \
+                        transaction was not committed");
+                }
+                current = Some(Transaction { line: line_no, timestamp, changes: vec![] });
+            }
+            "C" => {
+                let mut transaction = current.take().ok_or_else(|| {
+                    anyhow!("unexpected commit in line {line_no}: no transaction was started")
+                })?;
+                // The commit timestamp is the relevant one, if present.
+                transaction.timestamp = timestamp.or(transaction.timestamp);
+                transactions.push(transaction);
+            }
+            "I" | "U" | "D" | "T" => {
+                if message.schema.is_none() || message.table.is_none() {
+                    bail!("change in line {line_no} has no schema or table");
+                }
+                let change = Change { line: line_no, message };
+                match &mut current {
+                    Some(transaction) => transaction.changes.push(change),
+                    None => transactions.push(Transaction {
+                        line: line_no,
+                        timestamp,
+                        changes: vec![change],
+                    }),
+                }
+            }
+            "M" => debug!("Skipping logical decoding message in line {line_no}"),
+            other => bail!("unknown action '{other}' in line {line_no}"),
+        }
+    }
+
+    if let Some(transaction) = current {
+        warn!("Transaction starting in line {} is not committed in the log, ignoring it",
+            transaction.line);
+    }
+
+    Ok(transactions)
+}
+
+/// `wal2json` prints timestamps like `2022-08-01 12:34:56.789+02`.
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
+        .or_else(|_| DateTime::parse_from_rfc3339(s))
+        .map(|dt| dt.with_timezone(&Utc))
+        .with_context(|| format!("cannot parse '{s}'"))
+}
+
+/// Loads all tables of the target schema. Tables from the replication log
+/// are mapped to the table with the same name in that schema.
+async fn load_target_tables(db: &Db, schema: &str) -> Result<HashMap<String, TargetTable>> {
+    let rows = db
+        .query(
+            "select c.relname::text, a.attname::text, format_type(a.atttypid, a.atttypmod) \
+                from pg_class c \
+                join pg_namespace n on n.oid = c.relnamespace \
+                join pg_attribute a on a.attrelid = c.oid \
+                where n.nspname = $1 and c.relkind = 'r' and a.attnum > 0 \
+                    and not a.attisdropped",
+            &[&schema],
+        )
+        .await
+        .context("failed to query tables of target schema")?;
+
+    let mut tables = HashMap::<_, TargetTable>::new();
+    for row in rows {
+        tables.entry(row.get::<_, String>(0)).or_default().insert(row.get(1), row.get(2));
+    }
+    if tables.is_empty() {
+        bail!("schema '{schema}' does not exist or has no tables");
+    }
+
+    Ok(tables)
+}
+
+async fn replay_change(
+    tx: &deadpool_postgres::Transaction<'_>,
+    change: &Change,
+    target_schema: &str,
+    tables: &HashMap<String, TargetTable>,
+) -> Result<()> {
+    let message = &change.message;
+    let source_schema = message.schema.as_deref().expect("checked when parsing");
+    let table_name = message.table.as_deref().expect("checked when parsing");
+    let table = tables.get(table_name).ok_or_else(|| {
+        anyhow!("table '{source_schema}.{table_name}' has no counterpart in schema \
+            '{target_schema}'")
+    })?;
+    let qualified = format!("{}.{}", quote_ident(target_schema), quote_ident(table_name));
+
+    // All values are passed as text and cast to the column type in the target
+    // schema, which is also how `wal2json` printed them.
+    let mut params = Vec::<Option<String>>::new();
+    let mut param = |column: &Column| -> Result<String> {
+        let ty = table.get(&column.name).ok_or_else(|| {
+            anyhow!("column '{}' does not exist in '{qualified}'", column.name)
+        })?;
+        params.push(json_to_text(&column.value));
+        Ok(format!("${}::text::{ty}", params.len()))
+    };
+
+    let (query, expected_rows) = match message.action.as_str() {
+        "I" => {
+            let names = message.columns.iter()
+                .map(|c| quote_ident(&c.name))
+                .collect::<Vec<_>>();
+            let values = message.columns.iter().map(&mut param).collect::<Result<Vec<_>>>()?;
+            let query = format!(
+                "insert into {qualified} ({}) values ({})",
+                names.join(", "),
+                values.join(", "),
+            );
+            (query, Some(1))
+        }
+        "U" => {
+            let assignments = message.columns.iter()
+                .map(|c| Ok(format!("{} = {}", quote_ident(&c.name), param(c)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let condition = identity_condition(&message.identity, &mut param)?;
+            let query = format!(
+                "update {qualified} set {} where {condition}",
+                assignments.join(", "),
+            );
+            (query, Some(1))
+        }
+        "D" => {
+            let condition = identity_condition(&message.identity, &mut param)?;
+            (format!("delete from {qualified} where {condition}"), Some(1))
+        }
+        "T" => (format!("truncate {qualified}"), None),
+        _ => unreachable!("filtered when parsing"),
+    };
+
+    trace!("Executing: {query}");
+    let params = params.iter().map(|p| p as &(dyn ToSql + Sync)).collect::<Vec<_>>();
+    let affected = tx.execute(&query, &params).await?;
+    if let Some(expected) = expected_rows {
+        if affected != expected {
+            bail!("expected change to affect {expected} row in '{qualified}', but it \
+                affected {affected}: the target schema is not in the state the log expects");
+        }
+    }
+
+    Ok(())
+}
+
+fn identity_condition(
+    identity: &[Column],
+    param: &mut impl FnMut(&Column) -> Result<String>,
+) -> Result<String> {
+    if identity.is_empty() {
+        bail!("update/delete without replica identity: the table needs a primary key or \
+            `replica identity full`");
+    }
+    let conditions = identity.iter()
+        .map(|c| Ok(format!("{} is not distinct from {}", quote_ident(&c.name), param(c)?)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(conditions.join(" and "))
+}
+
+/// `wal2json` prints numbers and booleans as JSON values and everything else
+/// (including arrays and JSON columns) as strings in Postgres' text format.
+fn json_to_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
@@ -0,0 +1,86 @@
+//! The `db foreign-key-index-check` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// Tables with fewer rows than this are considered small enough that a
+/// sequential scan does not really hurt.
+const LOW_IMPACT_ROWS: i64 = 1_000;
+
+/// Tables with more rows than this make a missing index very noticeable.
+const HIGH_IMPACT_ROWS: i64 = 100_000;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db) -> Result<()> {
+    // For each foreign key constraint in the `public` schema we check whether
+    // there is any index on the referencing table whose leading columns are
+    // exactly the foreign key columns (in any order). `indkey` is an
+    // `int2vector`, which is zero-indexed when cast to an array.
+    let query = "
+        select
+            c.conrelid::regclass::text,
+            c.conname::text,
+            array_agg(a.attname::text order by k.ord),
+            coalesce(s.n_live_tup, 0),
+            pg_size_pretty(pg_total_relation_size(c.conrelid))
+        from pg_constraint c
+        cross join lateral unnest(c.conkey) with ordinality as k(attnum, ord)
+        join pg_attribute a on a.attrelid = c.conrelid and a.attnum = k.attnum
+        left join pg_stat_user_tables s on s.relid = c.conrelid
+        where c.contype = 'f'
+            and c.connamespace = 'public'::regnamespace
+            and not exists (
+                select from pg_index i
+                where i.indrelid = c.conrelid
+                    and (i.indkey::int2[])[0:cardinality(c.conkey) - 1] @> c.conkey
+                    and (i.indkey::int2[])[0:cardinality(c.conkey) - 1] <@ c.conkey
+            )
+        group by c.conrelid, c.conname, s.n_live_tup
+        order by coalesce(s.n_live_tup, 0) desc, 1, 2
+    ";
+
+    let missing = db.query(query, &[])
+        .await
+        .context("failed to query foreign key constraints")?;
+
+    if missing.is_empty() {
+        bunt::println!("{$green+intense}All foreign key columns are indexed.{/$}");
+        return Ok(());
+    }
+
+    bunt::println!("{$bold}Found {} foreign keys without a covering index:{/$}", missing.len());
+    println!();
+    for row in &missing {
+        let table: String = row.get(0);
+        let constraint: String = row.get(1);
+        let columns: Vec<String> = row.get(2);
+        let num_rows: i64 = row.get(3);
+        let size: String = row.get(4);
+
+        bunt::println!(
+            " - {[blue+intense]} ({}) on table {[bold]}",
+            constraint,
+            columns.join(", "),
+            table,
+        );
+        if num_rows >= HIGH_IMPACT_ROWS {
+            bunt::print!("   {$dimmed}impact:{/$} {$red+bold}high{/$}");
+        } else if num_rows >= LOW_IMPACT_ROWS {
+            bunt::print!("   {$dimmed}impact:{/$} {$yellow+bold}medium{/$}");
+        } else {
+            bunt::print!("   {$dimmed}impact:{/$} low");
+        }
+        bunt::println!(" {$dimmed}(~{} rows, {}){/$}", num_rows, size);
+        println!(
+            "   create index {}_{}_idx on {} ({});",
+            table,
+            columns.join("_"),
+            table,
+            columns.join(", "),
+        );
+    }
+    println!();
+
+    bail!("{} foreign keys are not covered by an index", missing.len());
+}
@@ -0,0 +1,226 @@
+//! The `db generate-schema-snapshot` subcommand. Developer tool only.
+
+use std::{path::Path, process::Command};
+
+use crate::{
+    prelude::*,
+    db::{Db, DbConfig, create_pool, migrations::MIGRATIONS, schema_snapshot},
+};
+
+
+/// Name of the temporary database all migrations are applied to. It is
+/// dropped again at the end. A separate database is required (instead of a
+/// schema), as some migrations look up constraints by table name only.
+const SCRATCH: &str = "__tobira_schema_snapshot";
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, config: &DbConfig, out: &Path) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        bail!("`db generate-schema-snapshot` is a developer tool and only available \
+            in debug builds");
+    }
+
+    drop_scratch_db(db).await?;
+    db.batch_execute(&format!("create database {SCRATCH} template template0 encoding 'UTF8'"))
+        .await
+        .context("failed to create scratch database (is the DB user allowed to do that?)")?;
+    let snapshot = generate(&DbConfig { database: SCRATCH.into(), ..config.clone() }).await;
+    drop_scratch_db(db).await?;
+    let snapshot = snapshot?;
+
+    tokio::fs::write(out, &snapshot)
+        .await
+        .with_context(|| format!("failed to write schema snapshot to '{}'", out.display()))?;
+    info!("Wrote schema snapshot of {} migrations to '{}'", MIGRATIONS.len(), out.display());
+
+    Ok(())
+}
+
+/// Implementation of `--check`: makes sure the snapshot compiled into this
+/// binary was generated from its migrations. Does not need a database.
+pub(crate) fn check() -> Result<()> {
+    if schema_snapshot::current().is_none() {
+        let generated_from = schema_snapshot::migration_count()
+            .map_or("unknown migrations".into(), |n| format!("{n} migrations"));
+        bail!("the schema snapshot is outdated: it was generated from {generated_from}, \
+            which do not match the {} migrations of this binary. Run \
+            `tobira db generate-schema-snapshot` to update it.", MIGRATIONS.len());
+    }
+
+    bunt::println!("{$green+intense}The schema snapshot is up to date ({} migrations).{/$}",
+        MIGRATIONS.len());
+    Ok(())
+}
+
+/// Applies all migrations to the scratch database and returns the snapshot.
+async fn generate(scratch: &DbConfig) -> Result<String> {
+    // The pool is dropped at the end of this block, so that no connection to
+    // the scratch database is left when dumping and dropping it.
+    let (xtea_entities, meta_table_comments) = {
+        let pool = create_pool(scratch).await?;
+        let mut db = pool.get().await?;
+        let tx = db.transaction().await?;
+
+        // Some migrations refer to the meta table (e.g. to comment it), so it
+        // has to exist.
+        tx.batch_execute(include_str!("../db-migrations.sql")).await?;
+        for (id, migration) in MIGRATIONS.iter() {
+            tx.batch_execute(migration.script)
+                .await
+                .with_context(|| format!("failed to apply migration '{id}-{}'", migration.name))?;
+        }
+
+        let xtea_entities = tx
+            .query("select entity from __xtea_keys order by entity", &[])
+            .await?
+            .into_iter()
+            .map(|row| row.get::<_, String>(0))
+            .collect::<Vec<_>>();
+        let meta_table_comments = tx
+            .query(
+                "select format('comment on table public.__db_migrations is %L;', \
+                        obj_description(c.oid, 'pg_class')) \
+                    from pg_class c \
+                    where c.oid = '__db_migrations'::regclass \
+                        and obj_description(c.oid, 'pg_class') is not null \
+                union all ( \
+                    select format('comment on column public.__db_migrations.%I is %L;', \
+                            a.attname, col_description(a.attrelid, a.attnum)) \
+                        from pg_attribute a \
+                        where a.attrelid = '__db_migrations'::regclass \
+                            and col_description(a.attrelid, a.attnum) is not null \
+                        order by a.attnum \
+                )",
+                &[],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.get::<_, String>(0))
+            .collect::<Vec<_>>();
+        tx.commit().await?;
+
+        (xtea_entities, meta_table_comments)
+    };
+
+    let dump = dump(scratch)?;
+    Ok(render(&dump, &xtea_entities, &meta_table_comments))
+}
+
+/// Dumps the scratch database via `pg_dump`, including data (e.g. the root
+/// realm), but excluding the meta table and the XTEA keys. The latter have to
+/// be different for each installation.
+fn dump(scratch: &DbConfig) -> Result<String> {
+    let output = Command::new("pg_dump")
+        .arg("--dbname")
+        .arg(super::connection_uri(scratch))
+        .arg("--exclude-table")
+        .arg("public.__db_migrations")
+        .arg("--exclude-table-data")
+        .arg("public.__xtea_keys")
+        .arg("--no-owner")
+        .arg("--no-privileges")
+        .arg("--inserts")
+        .output()
+        .context("failed to execute `pg_dump` (is it installed and in your `PATH`?)")?;
+
+    if !output.status.success() {
+        bail!(
+            "`pg_dump` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+
+    String::from_utf8(output.stdout).context("`pg_dump` output is not UTF8")
+}
+
+/// Turns the dump of the scratch database into a snapshot that can be
+/// applied in the migration transaction.
+fn render(dump: &str, xtea_entities: &[String], meta_table_comments: &[String]) -> String {
+    let mut out = schema_snapshot::header();
+    out.push('\n');
+
+    for line in dump.lines() {
+        // Meta-commands like `\restrict` are only understood by `psql`, and
+        // the versions of PostgreSQL and `pg_dump` would only cause noise.
+        // Commenting extensions requires owning them, which the DB user of
+        // Tobira might not.
+        let skip = line.starts_with('\\')
+            || line.starts_with("-- Dumped ")
+            || line.starts_with("COMMENT ON EXTENSION ");
+        if skip {
+            continue;
+        }
+
+        // Settings must not outlive the migration transaction, as the
+        // connection is reused afterwards.
+        if let Some(setting) = line.strip_prefix("SET ") {
+            out.push_str("SET LOCAL ");
+            out.push_str(setting);
+        } else if line.starts_with("SELECT pg_catalog.set_config(") {
+            out.push_str(&line.replace(", false);", ", true);"));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+
+    if !xtea_entities.is_empty() {
+        out.push_str("\n-- New keys are generated for each database.\n");
+        let values = xtea_entities.iter()
+            .map(|entity| format!("('{}')", entity.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("INSERT INTO public.__xtea_keys (entity) VALUES {values};\n"));
+    }
+    if !meta_table_comments.is_empty() {
+        out.push_str("\n-- The meta table is created before the snapshot is applied.\n");
+        for comment in meta_table_comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+async fn drop_scratch_db(db: &Db) -> Result<()> {
+    db.batch_execute(&format!("drop database if exists {SCRATCH}"))
+        .await
+        .context("failed to drop scratch database")?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn renders_dump_for_migration_transaction() {
+        let dump = "\
+            \\restrict abc\n\
+            -- Dumped from database version 15.4\n\
+            -- Dumped by pg_dump version 15.4\n\
+            SET statement_timeout = 0;\n\
+            SELECT pg_catalog.set_config('search_path', '', false);\n\
+            CREATE EXTENSION IF NOT EXISTS pgcrypto WITH SCHEMA public;\n\
+            COMMENT ON EXTENSION pgcrypto IS 'cryptographic functions';\n\
+            CREATE TABLE public.realms (id bigint);\n\
+            INSERT INTO public.realms VALUES (0);\n\
+            \\unrestrict abc\n";
+        let snapshot = render(dump, &["realm".into()], &[]);
+
+        assert!(snapshot.starts_with(&super::schema_snapshot::header()));
+        assert!(snapshot.contains("\nSET LOCAL statement_timeout = 0;\n\
+            SELECT pg_catalog.set_config('search_path', '', true);\n\
+            CREATE EXTENSION IF NOT EXISTS pgcrypto WITH SCHEMA public;\n\
+            CREATE TABLE public.realms (id bigint);\n\
+            INSERT INTO public.realms VALUES (0);\n"));
+        assert!(snapshot.ends_with("INSERT INTO public.__xtea_keys (entity) VALUES ('realm');\n"));
+        assert!(!snapshot.contains("restrict"));
+        assert!(!snapshot.contains("Dumped"));
+        assert!(!snapshot.contains("COMMENT ON EXTENSION"));
+    }
+}
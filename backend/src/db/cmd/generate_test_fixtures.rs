@@ -0,0 +1,458 @@
+//! The `db generate-test-fixtures` subcommand.
+
+use std::{collections::{HashMap, HashSet}, fmt::Write as _, path::Path};
+
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::{prelude::*, db::{Db, query::{qualified, quote_ident}}};
+
+
+/// How often we try to generate a row that does not violate any unique
+/// constraint before giving up.
+const MAX_ATTEMPTS: u32 = 100;
+
+/// Maximum number of existing rows loaded per referenced table that is not
+/// part of the generated fixtures.
+const MAX_EXISTING_REFS: i64 = 1000;
+
+/// Probability of generating `null` for nullable columns.
+const NULL_PROBABILITY: f64 = 0.1;
+
+
+#[derive(Debug)]
+struct Column {
+    name: String,
+    /// Full SQL type, e.g. `character varying(20)` or `bigint[]`.
+    sql_type: String,
+    /// Name of the type in `pg_type`, e.g. `varchar` or `_int8`.
+    type_name: String,
+    is_array: bool,
+    not_null: bool,
+    /// Labels if the column has an enum type.
+    enum_labels: Vec<String>,
+    /// Maximum length for `varchar(n)` and `char(n)` columns.
+    max_len: Option<usize>,
+}
+
+#[derive(Debug)]
+struct ForeignKey {
+    columns: Vec<String>,
+    ref_table: String,
+    ref_columns: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Table {
+    name: String,
+    columns: Vec<Column>,
+    foreign_keys: Vec<ForeignKey>,
+    /// Column sets of the primary key and all unique constraints.
+    unique: Vec<Vec<String>>,
+    has_identity_always: bool,
+}
+
+/// A generated row: column name to SQL literal.
+type Row = HashMap<String, String>;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    output: &Path,
+    tables: &[String],
+    n_rows: u32,
+    seed: u64,
+) -> Result<()> {
+    if tables.is_empty() {
+        bail!("no tables given");
+    }
+
+    let mut loaded = Vec::new();
+    for name in tables {
+        loaded.push(load_table(db, name).await?);
+    }
+    let ordered = order_by_dependencies(loaded)?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut generated = HashMap::<String, Vec<Row>>::new();
+    let mut out = format!(
+        "-- Generated by `tobira db generate-test-fixtures` with seed {seed}.\n\
+            -- Check constraints and triggers were not taken into account.\n",
+    );
+
+    for table in &ordered {
+        // Candidates for each foreign key: rows generated before (including
+        // rows of this table for self references) and rows already existing
+        // in the database.
+        let mut candidates = Vec::new();
+        for fk in &table.foreign_keys {
+            let existing = load_existing(db, &fk.ref_table, &fk.ref_columns).await?;
+            candidates.push(existing);
+        }
+
+        let mut rows = Vec::<Row>::new();
+        let mut seen = vec![HashSet::<Vec<String>>::new(); table.unique.len()];
+        for i in 0..n_rows {
+            let mut attempts = 0;
+            let row = loop {
+                let row = generate_row(table, &candidates, &generated, &rows, &mut rng)?;
+                // Null values never violate unique constraints.
+                let is_unique = table.unique.iter().zip(&seen).all(|(cols, seen)| {
+                    let key = cols.iter().map(|c| row[c].clone()).collect::<Vec<_>>();
+                    key.iter().any(|v| v == "null") || !seen.contains(&key)
+                });
+                if is_unique {
+                    break row;
+                }
+
+                attempts += 1;
+                if attempts == MAX_ATTEMPTS {
+                    bail!(
+                        "could not generate row {} of table '{}' without violating a unique \
+                            constraint (not enough referenced rows?)",
+                        i + 1,
+                        table.name,
+                    );
+                }
+            };
+
+            for (cols, seen) in table.unique.iter().zip(&mut seen) {
+                seen.insert(cols.iter().map(|c| row[c].clone()).collect());
+            }
+            rows.push(row);
+        }
+
+        write_inserts(&mut out, table, &rows);
+        generated.insert(table.name.clone(), rows);
+    }
+
+    std::fs::write(output, out)
+        .with_context(|| format!("failed to write '{}'", output.display()))?;
+    info!(
+        "Wrote {} rows for {} tables to '{}'",
+        n_rows as usize * ordered.len(),
+        ordered.len(),
+        output.display(),
+    );
+
+    Ok(())
+}
+
+async fn load_table(db: &Db, name: &str) -> Result<Table> {
+    let oid = db
+        .query_opt(
+            "select oid from pg_class \
+                where relnamespace = 'public'::regnamespace \
+                    and relname = $1 \
+                    and relkind in ('r', 'p')",
+            &[&name],
+        )
+        .await?
+        .map(|row| row.get::<_, u32>(0))
+        .ok_or_else(|| anyhow!("table '{name}' does not exist"))?;
+
+    let rows = db
+        .query(
+            "select a.attname::text, format_type(a.atttypid, a.atttypmod), t.typname::text, \
+                    t.typcategory = 'A', a.attnotnull, a.attidentity = 'a', \
+                    case when t.typname in ('varchar', 'bpchar') and a.atttypmod > 4 \
+                        then a.atttypmod - 4 end, \
+                    array(select e.enumlabel::text from pg_enum e \
+                        where e.enumtypid = t.oid order by e.enumsortorder) \
+                from pg_attribute a \
+                join pg_type t on t.oid = a.atttypid \
+                where a.attrelid = $1 and a.attnum > 0 and not a.attisdropped \
+                    and a.attgenerated = '' \
+                order by a.attnum",
+            &[&oid],
+        )
+        .await
+        .with_context(|| format!("failed to query columns of '{name}'"))?;
+
+    let mut has_identity_always = false;
+    let columns = rows.iter()
+        .map(|row| {
+            has_identity_always |= row.get::<_, bool>(5);
+            Column {
+                name: row.get(0),
+                sql_type: row.get(1),
+                type_name: row.get(2),
+                is_array: row.get(3),
+                not_null: row.get(4),
+                max_len: row.get::<_, Option<i32>>(6).map(|n| n as usize),
+                enum_labels: row.get(7),
+            }
+        })
+        .collect();
+
+    let constraints = db
+        .query(
+            "select c.contype::text, \
+                    array(select a.attname::text from unnest(c.conkey) with ordinality k(n, i) \
+                        join pg_attribute a on a.attrelid = c.conrelid and a.attnum = k.n \
+                        order by k.i), \
+                    (select relname::text from pg_class where oid = c.confrelid), \
+                    array(select a.attname::text from unnest(c.confkey) with ordinality k(n, i) \
+                        join pg_attribute a on a.attrelid = c.confrelid and a.attnum = k.n \
+                        order by k.i) \
+                from pg_constraint c \
+                where c.conrelid = $1 and c.contype in ('p', 'u', 'f') \
+                order by c.conname",
+            &[&oid],
+        )
+        .await
+        .with_context(|| format!("failed to query constraints of '{name}'"))?;
+
+    let mut unique = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for row in constraints {
+        let columns: Vec<String> = row.get(1);
+        match row.get::<_, String>(0).as_str() {
+            "f" => foreign_keys.push(ForeignKey {
+                columns,
+                ref_table: row.get(2),
+                ref_columns: row.get(3),
+            }),
+            _ => unique.push(columns),
+        }
+    }
+
+    Ok(Table { name: name.to_owned(), columns, foreign_keys, unique, has_identity_always })
+}
+
+/// Sorts the tables such that referenced tables come before the tables
+/// referencing them. Self references are fine, other cycles are not.
+fn order_by_dependencies(mut tables: Vec<Table>) -> Result<Vec<Table>> {
+    let mut ordered = Vec::<Table>::new();
+    while !tables.is_empty() {
+        let ready = tables.iter().position(|t| {
+            t.foreign_keys.iter().all(|fk| {
+                fk.ref_table == t.name
+                    || ordered.iter().any(|o| o.name == fk.ref_table)
+                    || !tables.iter().any(|o| o.name == fk.ref_table)
+            })
+        });
+        match ready {
+            Some(i) => ordered.push(tables.remove(i)),
+            None => {
+                let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+                bail!("cyclic foreign keys between tables: {}", names.join(", "));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Loads the given columns of existing rows as SQL literals.
+async fn load_existing(db: &Db, table: &str, columns: &[String]) -> Result<Vec<Vec<String>>> {
+    let selection = columns.iter()
+        .map(|c| format!("quote_nullable({}::text)", quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let query = format!(
+        "select {selection} from {} order by {} limit {MAX_EXISTING_REFS}",
+        qualified(table),
+        (1..=columns.len()).map(|i| i.to_string()).collect::<Vec<_>>().join(", "),
+    );
+    let rows = db.query(&query, &[])
+        .await
+        .with_context(|| format!("failed to load existing rows of '{table}'"))?;
+
+    Ok(rows.iter().map(|row| (0..columns.len()).map(|i| row.get(i)).collect()).collect())
+}
+
+fn generate_row(
+    table: &Table,
+    existing: &[Vec<Vec<String>>],
+    generated: &HashMap<String, Vec<Row>>,
+    this_table: &[Row],
+    rng: &mut StdRng,
+) -> Result<Row> {
+    let mut row = Row::new();
+    for col in &table.columns {
+        let nullable = !col.not_null && rng.gen_bool(NULL_PROBABILITY);
+        let value = if nullable { Some("null".to_owned()) } else { random_value(col, rng) };
+        match value {
+            Some(value) => row.insert(col.name.clone(), value),
+            None if !col.not_null => row.insert(col.name.clone(), "null".to_owned()),
+            None => bail!(
+                "cannot generate values of type '{}' for column '{}.{}' (not null)",
+                col.sql_type,
+                table.name,
+                col.name,
+            ),
+        };
+    }
+
+    // Overwrite foreign key columns with referenced values.
+    for (fk, existing) in table.foreign_keys.iter().zip(existing) {
+        let generated_refs = if fk.ref_table == table.name {
+            this_table
+        } else {
+            generated.get(&fk.ref_table).map_or(&[][..], |rows| rows.as_slice())
+        };
+        let candidates = generated_refs.iter()
+            .map(|r| fk.ref_columns.iter().map(|c| r[c].clone()).collect::<Vec<_>>())
+            .chain(existing.iter().cloned())
+            .filter(|values| values.iter().all(|v| v != "null"))
+            .collect::<Vec<_>>();
+
+        let all_nullable = fk.columns.iter()
+            .all(|c| table.columns.iter().any(|col| col.name == *c && !col.not_null));
+        let values = match candidates.choose(rng) {
+            Some(values) if !(all_nullable && rng.gen_bool(NULL_PROBABILITY)) => values.clone(),
+            _ if all_nullable => vec!["null".to_owned(); fk.columns.len()],
+            _ => bail!(
+                "no rows in '{}' to reference from '{}' (include it in the tables or \
+                    insert rows first)",
+                fk.ref_table,
+                table.name,
+            ),
+        };
+        for (col, value) in fk.columns.iter().zip(values) {
+            row.insert(col.clone(), value);
+        }
+    }
+
+    Ok(row)
+}
+
+/// Returns a random SQL literal for the column or `None` if values of that
+/// type are not supported.
+fn random_value(col: &Column, rng: &mut StdRng) -> Option<String> {
+    if col.is_array {
+        return Some(format!("'{{}}'::{}", col.sql_type));
+    }
+    if !col.enum_labels.is_empty() {
+        return col.enum_labels.choose(rng).map(|label| quote_literal(label));
+    }
+
+    let value = match col.type_name.as_str() {
+        "bool" => rng.gen::<bool>().to_string(),
+        "int2" => rng.gen_range(0..i16::MAX).to_string(),
+        "int4" => rng.gen_range(1..i32::MAX).to_string(),
+        "int8" => rng.gen_range(1..1i64 << 53).to_string(),
+        "float4" | "float8" | "numeric" => format!("{:.2}", rng.gen_range(0.0..10_000.0)),
+        "text" | "varchar" | "bpchar" | "citext" | "name" => {
+            let mut s = format!("{}_{}", col.name, random_alphanumeric(rng, 8));
+            if let Some(max) = col.max_len {
+                // Keep the random part if possible.
+                s = s[s.len().saturating_sub(max)..].to_owned();
+            }
+            quote_literal(&s)
+        }
+        "bytea" => format!("'\\x{}'", random_hex(rng, 16)),
+        "uuid" => {
+            let h = random_hex(rng, 16);
+            format!("'{}-{}-{}-{}-{}'", &h[..8], &h[8..12], &h[12..16], &h[16..20], &h[20..])
+        }
+        "date" | "timestamp" | "timestamptz" => {
+            let secs = 1_577_836_800 + rng.gen_range(0..3 * 365 * 24 * 60 * 60);
+            let dt = chrono::NaiveDateTime::from_timestamp(secs, 0);
+            match col.type_name.as_str() {
+                "date" => format!("'{}'::date", dt.date()),
+                "timestamp" => format!("'{dt}'::timestamp"),
+                _ => format!("'{dt}+00'::timestamptz"),
+            }
+        }
+        "time" => format!(
+            "'{:02}:{:02}:{:02}'::time",
+            rng.gen_range(0..24),
+            rng.gen_range(0..60),
+            rng.gen_range(0..60),
+        ),
+        "interval" => format!("'{} seconds'::interval", rng.gen_range(0..100_000)),
+        "json" | "jsonb" => format!("'{{}}'::{}", col.type_name),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn random_alphanumeric(rng: &mut StdRng, len: usize) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    (0..len).map(|_| *CHARS.choose(rng).unwrap() as char).collect()
+}
+
+fn random_hex(rng: &mut StdRng, bytes: usize) -> String {
+    (0..bytes).map(|_| format!("{:02x}", rng.gen::<u8>())).collect()
+}
+
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn write_inserts(out: &mut String, table: &Table, rows: &[Row]) {
+    if rows.is_empty() {
+        return;
+    }
+
+    let columns = table.columns.iter()
+        .map(|c| quote_ident(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let overriding = if table.has_identity_always { " overriding system value" } else { "" };
+    writeln!(out, "\ninsert into {} ({columns}){overriding} values", qualified(&table.name))
+        .unwrap();
+    for (i, row) in rows.iter().enumerate() {
+        let values = table.columns.iter()
+            .map(|c| row[&c.name].as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sep = if i + 1 == rows.len() { ";" } else { "," };
+        writeln!(out, "    ({values}){sep}").unwrap();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+    use super::{Column, random_value};
+
+    fn column(type_name: &str, max_len: Option<usize>) -> Column {
+        Column {
+            name: "title".into(),
+            sql_type: type_name.into(),
+            type_name: type_name.into(),
+            is_array: false,
+            not_null: true,
+            enum_labels: vec![],
+            max_len,
+        }
+    }
+
+    #[test]
+    fn values_are_deterministic_and_valid() {
+        let generate = |col: &Column| {
+            let mut rng = StdRng::seed_from_u64(7);
+            (0..20).map(|_| random_value(col, &mut rng)).collect::<Vec<_>>()
+        };
+
+        let text = column("text", None);
+        assert_eq!(generate(&text), generate(&text));
+        assert!(generate(&text).iter().all(|v| v.as_ref().unwrap().starts_with("'title_")));
+
+        let short = column("varchar", Some(5));
+        for v in generate(&short) {
+            assert_eq!(v.unwrap().len(), 5 + 2);
+        }
+
+        let int = column("int4", None);
+        for v in generate(&int) {
+            assert!(v.unwrap().parse::<i32>().unwrap() > 0);
+        }
+
+        let labels = vec!["a'b".to_owned()];
+        let enum_col = Column { enum_labels: labels, ..column("my_enum", None) };
+        assert_eq!(generate(&enum_col)[0].as_deref(), Some("'a''b'"));
+
+        let array = Column {
+            is_array: true,
+            sql_type: "event_track[]".into(),
+            ..column("_event_track", None)
+        };
+        assert_eq!(generate(&array)[0].as_deref(), Some("'{}'::event_track[]"));
+
+        assert_eq!(generate(&column("event_track", None))[0], None);
+    }
+}
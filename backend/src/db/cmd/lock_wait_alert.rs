@@ -0,0 +1,170 @@
+//! Alerts for `db migrate --alert-on-lock-wait`.
+
+use std::time::{Duration, Instant};
+use deadpool_postgres::Pool;
+use hyper::Uri;
+use tokio::task::JoinHandle;
+
+use crate::{prelude::*, db::{Db, migrations::post_webhook}};
+
+
+/// How often the migration session is checked for lock waits.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Queries longer than this are truncated in alerts.
+const MAX_QUERY_LEN: usize = 200;
+
+
+/// Starts watching the session of `db` for lock waits in the background,
+/// using a separate connection from `pool`. An alert is emitted once per lock
+/// wait that takes longer than `threshold`. The task runs until it is
+/// aborted.
+pub(super) async fn spawn(
+    pool: &Pool,
+    db: &Db,
+    threshold: Duration,
+    webhook: Option<Uri>,
+) -> Result<JoinHandle<()>> {
+    let pid = db.query_one("select pg_backend_pid()", &[]).await?.get::<_, i32>(0);
+    let monitor = pool.get().await
+        .context("failed to get DB connection for monitoring lock waits")?;
+
+    Ok(tokio::spawn(async move {
+        // When the current wait started and whether it was alerted already.
+        let mut current_wait: Option<(Instant, bool)> = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let waiting = monitor
+                .query_opt(
+                    "select from pg_stat_activity where pid = $1 and wait_event_type = 'Lock'",
+                    &[&pid],
+                )
+                .await;
+            match waiting {
+                Err(e) => warn!("Failed to check migration for lock waits: {e:#}"),
+                Ok(None) => current_wait = None,
+                Ok(Some(_)) => {
+                    let (since, alerted) = current_wait
+                        .get_or_insert_with(|| (Instant::now(), false));
+                    if *alerted || since.elapsed() < threshold {
+                        continue;
+                    }
+                    *alerted = true;
+                    let res = alert(&monitor, pid, since.elapsed(), webhook.as_ref()).await;
+                    if let Err(e) = res {
+                        warn!("Failed to alert about lock wait of migration: {e:#}");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Prints which lock the migration waits for and which sessions hold it, and
+/// sends the same information to the webhook.
+async fn alert(db: &Db, pid: i32, waited: Duration, webhook: Option<&Uri>) -> Result<()> {
+    let lock = db
+        .query_opt(
+            "select mode, coalesce(relation::regclass::text, locktype) \
+                from pg_locks \
+                where pid = $1 and not granted \
+                limit 1",
+            &[&pid],
+        )
+        .await?
+        .map(|row| format!("{} on {}", row.get::<_, String>(0), row.get::<_, String>(1)))
+        .unwrap_or_else(|| "unknown lock".into());
+    let blockers = db
+        .query(
+            "select pid, usename::text, application_name, \
+                    extract(epoch from now() - xact_start)::float8, query \
+                from pg_stat_activity \
+                where pid = any(pg_blocking_pids($1)) \
+                order by pid",
+            &[&pid],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Blocker {
+            pid: row.get(0),
+            user: row.get(1),
+            application: row.get(2),
+            transaction_secs: row.get(3),
+            query: row.get::<_, Option<String>>(4).map(|q| shorten_query(&q)),
+        })
+        .collect::<Vec<_>>();
+
+    bunt::eprintln!(
+        "{$yellow+bold}warning:{/$} migration has been waiting for {} for {}s",
+        lock,
+        waited.as_secs(),
+    );
+    for b in &blockers {
+        bunt::eprintln!(
+            "  ▸ blocked by PID {} (user '{}', application '{}', transaction open for {}s): \
+                {[dimmed]}",
+            b.pid,
+            b.user.as_deref().unwrap_or("?"),
+            b.application.as_deref().unwrap_or(""),
+            b.transaction_secs.map_or("?".into(), |secs| format!("{secs:.0}")),
+            b.query.as_deref().unwrap_or("?"),
+        );
+    }
+    if blockers.is_empty() {
+        bunt::eprintln!("  ▸ {$dimmed}the sessions holding the lock could not be determined{/$}");
+    }
+
+    if let Some(url) = webhook {
+        let body = serde_json::json!({
+            "alert": "lockWait",
+            "waitingSecs": waited.as_secs(),
+            "lock": lock,
+            "blockers": blockers.iter().map(|b| serde_json::json!({
+                "pid": b.pid,
+                "user": b.user,
+                "application": b.application,
+                "transactionSecs": b.transaction_secs,
+                "query": b.query,
+            })).collect::<Vec<_>>(),
+            "tobiraVersion": crate::version::identifier(),
+        });
+        post_webhook(url, &body).await.context("failed to send alert to webhook")?;
+    }
+
+    Ok(())
+}
+
+/// A session holding a lock the migration waits for.
+struct Blocker {
+    pid: i32,
+    user: Option<String>,
+    application: Option<String>,
+    transaction_secs: Option<f64>,
+    query: Option<String>,
+}
+
+/// Collapses whitespace and truncates the query to `MAX_QUERY_LEN` chars.
+fn shorten_query(query: &str) -> String {
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    match query.char_indices().nth(MAX_QUERY_LEN) {
+        Some((idx, _)) => format!("{}…", &query[..idx]),
+        None => query,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{shorten_query, MAX_QUERY_LEN};
+
+    #[test]
+    fn shortens_queries() {
+        assert_eq!(shorten_query("select *\n    from  events\n"), "select * from events");
+
+        let long = "x".repeat(MAX_QUERY_LEN + 10);
+        let short = shorten_query(&long);
+        assert_eq!(short.chars().count(), MAX_QUERY_LEN + 1);
+        assert!(short.ends_with('…'));
+        assert_eq!(shorten_query(&"ä".repeat(MAX_QUERY_LEN)), "ä".repeat(MAX_QUERY_LEN));
+    }
+}
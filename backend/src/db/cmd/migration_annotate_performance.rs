@@ -0,0 +1,321 @@
+//! The `db migration-annotate-performance` subcommand. Developer tool only.
+
+use std::{
+    collections::HashSet,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use deadpool_postgres::Transaction;
+use tokio_postgres::IsolationLevel;
+
+use crate::{prelude::*, db::Db};
+
+
+/// Prefix of the comments this command adds. Existing comments with this
+/// prefix directly above a statement are replaced.
+const ANNOTATION_PREFIX: &str = "-- estimated:";
+
+/// Lock modes in order of increasing strength.
+const LOCK_MODES: &[&str] = &[
+    "AccessShareLock",
+    "RowShareLock",
+    "RowExclusiveLock",
+    "ShareUpdateExclusiveLock",
+    "ShareLock",
+    "ShareRowExclusiveLock",
+    "ExclusiveLock",
+    "AccessExclusiveLock",
+];
+
+/// What we found out about a single statement.
+struct Annotation {
+    /// Byte range of the statement in the script.
+    span: Range<usize>,
+    rows: Option<f64>,
+    cost: Option<f64>,
+    duration: Duration,
+    /// Strongest lock mode taken on pre-existing tables, with those tables.
+    lock: Option<(&'static str, Vec<String>)>,
+}
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &mut Db, version: u32) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        bail!("`db migration-annotate-performance` is a developer tool and only available \
+            in debug builds");
+    }
+
+    let path = find_migration_file(version)?;
+    let script = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    // The migration has to be the next one to apply. Otherwise it either
+    // cannot run (already applied) or would run against an unexpected schema.
+    let current = db
+        .query_one(
+            "select case when to_regclass('__db_migrations') is null then 0 \
+                else (select coalesce(max(id), 0) from __db_migrations) end",
+            &[],
+        )
+        .await?
+        .get::<_, i64>(0);
+    if current != i64::from(version) - 1 {
+        bail!("the database is at migration {current}, but has to be at {} to annotate \
+            migration {version}", i64::from(version) - 1);
+    }
+
+    // Everything happens in a transaction that is rolled back at the end, so
+    // that the database is not modified. Note that `explain analyze` does
+    // execute the statement, which is necessary for later statements.
+    let tx = db.build_transaction()
+        .isolation_level(IsolationLevel::Serializable)
+        .start()
+        .await?;
+    let existing_relations = tx
+        .query("select oid from pg_class where relnamespace = 'public'::regnamespace", &[])
+        .await?
+        .into_iter()
+        .map(|row| row.get::<_, u32>(0))
+        .collect::<HashSet<_>>();
+
+    let mut annotations = Vec::new();
+    for span in split_statements(&script) {
+        let statement = &script[span.clone()];
+        trace!("Executing:\n{statement}");
+        let is_dml = first_keyword(statement)
+            .is_some_and(|kw| ["insert", "update", "delete", "with"].contains(&kw.as_str()));
+
+        let locks_before = held_locks(&tx).await?;
+        let start = Instant::now();
+        let (rows, cost) = if is_dml {
+            let plan = tx
+                .query_one(&format!("explain (analyze, costs, format json) {statement}"), &[])
+                .await
+                .with_context(|| format!("failed to explain statement:\n{statement}"))?
+                .get::<_, serde_json::Value>(0);
+            plan_estimates(&plan)
+        } else {
+            tx.batch_execute(statement)
+                .await
+                .with_context(|| format!("failed to execute statement:\n{statement}"))?;
+            (None, None)
+        };
+        let duration = start.elapsed();
+
+        let new_locks = held_locks(&tx).await?
+            .into_iter()
+            .filter(|lock| existing_relations.contains(&lock.0) && !locks_before.contains(lock))
+            .collect::<Vec<_>>();
+        let lock = LOCK_MODES.iter().rev()
+            .find(|mode| new_locks.iter().any(|lock| lock.2 == **mode))
+            .map(|mode| {
+                let mut tables = new_locks.iter()
+                    .filter(|lock| lock.2 == *mode)
+                    .map(|lock| lock.1.clone())
+                    .collect::<Vec<_>>();
+                tables.sort();
+                tables.dedup();
+                (*mode, tables)
+            });
+
+        if is_dml || lock.is_some() {
+            annotations.push(Annotation { span, rows, cost, duration, lock });
+        }
+    }
+    tx.rollback().await?;
+
+    let annotated = annotate(&script, &annotations);
+    std::fs::write(&path, annotated)
+        .with_context(|| format!("failed to write '{}'", path.display()))?;
+    info!("Annotated {} statements in '{}'", annotations.len(), path.display());
+    println!("Note: the estimates are based on the data in the current database.");
+
+    Ok(())
+}
+
+/// Returns the source file of the migration with the given ID.
+fn find_migration_file(version: u32) -> Result<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/db/migrations");
+    let prefix = format!("{version:02}-");
+    std::fs::read_dir(&dir)
+        .with_context(|| format!("failed to read directory '{}'", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with(&prefix) && name.ends_with(".sql")
+        })
+        .ok_or_else(|| anyhow!("no migration file for ID {version} in '{}'", dir.display()))
+}
+
+/// Returns all relation locks of the current backend as (oid, name, mode).
+async fn held_locks(tx: &Transaction<'_>) -> Result<Vec<(u32, String, String)>> {
+    let rows = tx
+        .query(
+            "select relation, relation::regclass::text, mode \
+                from pg_locks \
+                where pid = pg_backend_pid() and locktype = 'relation'",
+            &[],
+        )
+        .await
+        .context("failed to query locks")?;
+    Ok(rows.into_iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect())
+}
+
+/// Extracts the number of affected rows and the total cost from the JSON
+/// output of `explain analyze`. For data modifying statements, the top node
+/// (`ModifyTable`) does not report rows, so we look at its child.
+fn plan_estimates(explain: &serde_json::Value) -> (Option<f64>, Option<f64>) {
+    let plan = &explain[0]["Plan"];
+    let cost = plan["Total Cost"].as_f64();
+    let rows = match plan["Node Type"].as_str() {
+        Some("ModifyTable") => plan["Plans"][0]["Actual Rows"].as_f64(),
+        _ => plan["Actual Rows"].as_f64(),
+    };
+    (rows, cost)
+}
+
+/// Inserts the annotation comments into the script, replacing old ones.
+fn annotate(script: &str, annotations: &[Annotation]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for annotation in annotations {
+        // Find the start of the statement's line and check for an old
+        // annotation directly above it.
+        let line_start = script[..annotation.span.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &script[line_start..annotation.span.start];
+        if !indent.trim().is_empty() {
+            // Another statement precedes this one on the same line.
+            out.push_str(&script[pos..annotation.span.start]);
+            out.push('\n');
+            out.push_str(&format_annotation(annotation));
+            out.push('\n');
+            pos = annotation.span.start;
+            continue;
+        }
+
+        let mut insert_at = line_start;
+        let before = script[..line_start].strip_suffix('\n').unwrap_or("");
+        let prev_line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        if line_start > 0 && before[prev_line_start..].trim_start().starts_with(ANNOTATION_PREFIX) {
+            insert_at = prev_line_start;
+        }
+
+        out.push_str(&script[pos..insert_at]);
+        out.push_str(indent);
+        out.push_str(&format_annotation(annotation));
+        out.push('\n');
+        pos = line_start;
+    }
+    out.push_str(&script[pos..]);
+    out
+}
+
+fn format_annotation(annotation: &Annotation) -> String {
+    let mut parts = Vec::new();
+    if let Some(rows) = annotation.rows {
+        parts.push(format!("{} rows", human_number(rows)));
+    }
+    if let Some(cost) = annotation.cost {
+        parts.push(format!("cost {}", human_number(cost)));
+    }
+    parts.push(format!("~{}", human_duration(annotation.duration)));
+    if let Some((mode, tables)) = &annotation.lock {
+        parts.push(format!("locks: {} ({})", lock_mode_sql_name(mode), tables.join(", ")));
+    }
+    format!("{ANNOTATION_PREFIX} {}", parts.join(", "))
+}
+
+/// `AccessExclusiveLock` -> `ACCESS EXCLUSIVE`
+fn lock_mode_sql_name(mode: &str) -> String {
+    let mode = mode.strip_suffix("Lock").unwrap_or(mode);
+    let mut out = String::new();
+    for c in mode.chars() {
+        if c.is_uppercase() && !out.is_empty() {
+            out.push(' ');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
+
+fn human_number(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.1}G", n / 1e9),
+        n if n >= 1e6 => format!("{:.1}M", n / 1e6),
+        n if n >= 1e3 => format!("{:.1}k", n / 1e3),
+        n => format!("{n:.0}"),
+    }.replace(".0", "")
+}
+
+fn human_duration(d: Duration) -> String {
+    match d.as_secs_f64() {
+        s if s >= 60.0 => format!("{:.0}min", s / 60.0),
+        s if s >= 1.0 => format!("{s:.0}s"),
+        s => format!("{:.0}ms", s * 1000.0),
+    }
+}
+
+/// Returns the first keyword of the statement in lowercase.
+pub(super) fn first_keyword(statement: &str) -> Option<String> {
+    statement.split(|c: char| !c.is_ascii_alphabetic())
+        .find(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+}
+
+/// Splits the script into statements, returning the byte range of each
+/// (without leading whitespace/comments, including the trailing `;`). Knows
+/// about comments, string literals, quoted identifiers and dollar quoting,
+/// which is all our migrations use.
+pub(super) fn split_statements(script: &str) -> Vec<Range<usize>> {
+    let bytes = script.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &script[i..];
+        if rest.starts_with("--") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("/*") {
+            i += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            continue;
+        }
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let statement_start = *start.get_or_insert(i);
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                i += 1 + rest[1..].find(quote as char).map_or(rest.len() - 1, |end| end + 1);
+            }
+            b'$' => {
+                // Dollar quote `$tag$ ... $tag$`. A `$` not starting such a
+                // tag is a parameter or part of an identifier.
+                let tag_len = rest[1..].find(|c: char| !(c.is_alphanumeric() || c == '_'));
+                match tag_len.filter(|&len| rest.as_bytes()[len + 1] == b'$') {
+                    Some(len) => {
+                        let tag = &rest[..len + 2];
+                        i += tag.len() + rest[tag.len()..].find(tag)
+                            .map_or(rest.len() - tag.len(), |end| end + tag.len());
+                    }
+                    None => i += 1,
+                }
+            }
+            b';' => {
+                statements.push(statement_start..i + 1);
+                start = None;
+                i += 1;
+            }
+            _ => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if let Some(start) = start {
+        statements.push(start..script.len());
+    }
+    statements
+}
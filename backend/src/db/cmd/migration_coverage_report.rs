@@ -0,0 +1,180 @@
+//! The `db migration-coverage-report` subcommand.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{prelude::*, db::{Db, query}};
+use super::{
+    column_histogram::render_bar,
+    migration_annotate_performance::{first_keyword, split_statements},
+};
+
+
+/// Number of tables shown in the list of most frequently changed tables.
+const TOP_TABLES: usize = 10;
+
+/// Width of the longest bar in characters.
+const BAR_WIDTH: usize = 30;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if !query::does_table_exist(&**db, "__db_migrations").await? {
+        bail!("table '__db_migrations' does not exist: no migrations were applied yet");
+    }
+    let until = until.unwrap_or_else(Utc::now);
+    if until < since {
+        bail!("`--until` is before `--since`");
+    }
+
+    // `applied_on` is stored as `timestamp` in UTC.
+    let rows = db
+        .query(
+            "select id, applied_on::date, script, skipped, \
+                    to_jsonb(__db_migrations) ->> 'script_sha256' is not null \
+                from __db_migrations \
+                where applied_on >= $1 and applied_on <= $2 \
+                order by applied_on, id",
+            &[&since.naive_utc(), &until.naive_utc()],
+        )
+        .await
+        .context("failed to query '__db_migrations'")?;
+
+    let mut per_day = BTreeMap::<NaiveDate, i64>::new();
+    let mut per_table = HashMap::<String, i64>::new();
+    let mut skipped = 0;
+    let mut archived = 0;
+    for row in &rows {
+        // Skipped migrations did not change anything.
+        if row.get::<_, bool>(3) {
+            skipped += 1;
+            continue;
+        }
+        *per_day.entry(row.get(1)).or_default() += 1;
+
+        // Archived scripts are not available, see `db migrate --compress-logs`.
+        if row.get::<_, bool>(4) {
+            archived += 1;
+            continue;
+        }
+
+        // Each table is only counted once per migration.
+        let script = row.get::<_, String>(2);
+        let tables = split_statements(&script)
+            .into_iter()
+            .filter_map(|range| changed_table(&script[range]))
+            .collect::<HashSet<_>>();
+        for table in tables {
+            *per_table.entry(table).or_default() += 1;
+        }
+    }
+    let applied = per_day.values().sum::<i64>();
+
+    bunt::println!(
+        "{$bold}Schema changes between {} and {}{/$}",
+        since.format("%Y-%m-%d"),
+        until.format("%Y-%m-%d"),
+    );
+    println!();
+    let days = ((until - since).num_seconds() as f64 / (24.0 * 60.0 * 60.0)).max(1.0);
+    println!(
+        "Migrations: {applied} on {} days ({:.2} per day on average)",
+        per_day.len(),
+        applied as f64 / days,
+    );
+    if skipped > 0 {
+        bunt::println!("{$dimmed}{} more migrations were skipped and are not counted.{/$}",
+            skipped);
+    }
+    if applied == 0 {
+        return Ok(());
+    }
+
+    println!();
+    bunt::println!("{$bold}Migrations per day:{/$}");
+    let max = per_day.values().copied().max().unwrap_or(0);
+    for (day, count) in &per_day {
+        bunt::println!("  {} │{[blue]} {}", day, render_bar(*count, max, BAR_WIDTH), count);
+    }
+
+    println!();
+    bunt::println!("{$bold}Most frequently changed tables:{/$}");
+    if archived > 0 {
+        bunt::println!("{$dimmed}The scripts of {} migrations are archived, so the tables \
+            they changed are not counted.{/$}", archived);
+    }
+    let mut tables = per_table.into_iter().collect::<Vec<_>>();
+    tables.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    let name_width = tables.iter().take(TOP_TABLES).map(|(name, _)| name.len()).max();
+    for (table, count) in tables.iter().take(TOP_TABLES) {
+        let pct = 100.0 * *count as f64 / applied as f64;
+        println!(
+            "  {table:<width$}  changed by {count} migration(s) ({pct:.0}%)",
+            width = name_width.unwrap_or(0),
+        );
+    }
+    if tables.len() > TOP_TABLES {
+        bunt::println!("  {$dimmed}… and {} more tables{/$}", tables.len() - TOP_TABLES);
+    }
+
+    Ok(())
+}
+
+/// Returns the table whose schema the statement changes, if any: tables
+/// that are created, altered or dropped, and tables that get an index or
+/// trigger.
+fn changed_table(statement: &str) -> Option<String> {
+    let normalized = statement.to_ascii_lowercase()
+        .replace('"', "")
+        .replace(['(', ')', ',', ';'], " ");
+    let words = normalized.split_whitespace().collect::<Vec<_>>();
+    let after = |word: &str| {
+        const SKIPPED: &[&str] = &["only", "if", "not", "exists", "concurrently"];
+        words.iter()
+            .position(|w| *w == word)
+            .and_then(|i| words[i + 1..].iter().find(|w| !SKIPPED.contains(*w)))
+            .map(|table| table.trim_start_matches("public.").to_owned())
+    };
+
+    // The object type is the first word after `create`/`alter`/`drop` that
+    // is not a modifier like `unique` or `or replace`.
+    const MODIFIERS: &[&str] = &["or", "replace", "unique", "unlogged", "temporary", "temp"];
+    let object = words.iter().skip(1).find(|w| !MODIFIERS.contains(*w))?;
+    match (first_keyword(statement)?.as_str(), *object) {
+        ("create" | "alter" | "drop", "table") => after("table"),
+        ("create", "index") | ("create" | "drop", "trigger") => after("on"),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::changed_table;
+
+    #[test]
+    fn finds_changed_tables() {
+        let cases = [
+            ("create table events (id bigint);", Some("events")),
+            ("create unlogged table if not exists foo(id int);", Some("foo")),
+            ("alter table only public.\"blocks\" add column x int;", Some("blocks")),
+            ("drop table if exists old;", Some("old")),
+            ("create unique index concurrently idx on series (title);", Some("series")),
+            ("create index on events using gin (metadata);", Some("events")),
+            ("create or replace trigger t after insert on realms for each row \
+                execute procedure f();", Some("realms")),
+            ("drop trigger queue_blocks_for_reindex on blocks;", Some("blocks")),
+            ("create function f() returns trigger as $$ begin end $$ language plpgsql;", None),
+            ("create view v as select * from events;", None),
+            ("update events set title = '';", None),
+            ("comment on table events is 'x';", None),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(changed_table(statement).as_deref(), expected, "{statement}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! The `db migration-estimate-time` subcommand.
+
+use std::collections::HashMap;
+
+use crate::{
+    prelude::*,
+    db::{Db, query, migrations::MIGRATIONS},
+};
+use super::migration_annotate_performance::{first_keyword, split_statements};
+
+
+/// Assumed duration of statements not touching existing rows (e.g. `create
+/// table`, `alter table ... add column`, `create function`), in seconds.
+const SIMPLE_DDL_SECS: f64 = 0.01;
+
+/// Upper bound for simple DDL. Mostly waiting for locks.
+const SIMPLE_DDL_MAX_SECS: f64 = 1.0;
+
+/// Seconds per row and `log2(rows)` for building an index.
+const INDEX_SECS_PER_ROW_LOG: f64 = 0.5e-6;
+
+/// Seconds per row for statements rewriting the table (e.g. changing the
+/// type of a column).
+const REWRITE_SECS_PER_ROW: f64 = 5e-6;
+
+/// Seconds per row for statements scanning the table without modifying it
+/// (e.g. validating a new constraint).
+const SCAN_SECS_PER_ROW: f64 = 1e-6;
+
+/// Seconds per row for DML statements, assuming they touch all rows.
+const DML_SECS_PER_ROW: f64 = 10e-6;
+
+
+/// What a statement does, as far as the cost model is concerned.
+#[derive(Debug, PartialEq)]
+pub(super) enum Kind {
+    SimpleDdl,
+    Index(String),
+    Rewrite(String),
+    Scan(String),
+    Dml(String),
+}
+
+/// Estimated duration of a statement or migration in seconds: the expected
+/// value and the bounds of the confidence interval.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Estimate {
+    expected: f64,
+    low: f64,
+    high: f64,
+}
+
+impl std::ops::AddAssign for Estimate {
+    fn add_assign(&mut self, other: Self) {
+        self.expected += other.expected;
+        self.low += other.low;
+        self.high += other.high;
+    }
+}
+
+impl Kind {
+    pub(super) fn estimate(&self, rows: &HashMap<String, f64>) -> Estimate {
+        let rows_of = |table: &str| rows.get(table).copied().unwrap_or(0.0);
+        // The factors are rough: actual durations depend heavily on the
+        // hardware, row width and the load of the database.
+        let (secs, low, high) = match self {
+            Self::SimpleDdl => return Estimate {
+                expected: SIMPLE_DDL_SECS,
+                low: 0.0,
+                high: SIMPLE_DDL_MAX_SECS,
+            },
+            Self::Index(table) => {
+                let n = rows_of(table);
+                (n * n.max(2.0).log2() * INDEX_SECS_PER_ROW_LOG, 0.5, 3.0)
+            }
+            Self::Rewrite(table) => (rows_of(table) * REWRITE_SECS_PER_ROW, 0.5, 3.0),
+            Self::Scan(table) => (rows_of(table) * SCAN_SECS_PER_ROW, 0.5, 3.0),
+            // We don't know how many rows a `where` clause selects.
+            Self::Dml(table) => (rows_of(table) * DML_SECS_PER_ROW, 0.0, 2.0),
+        };
+
+        Estimate {
+            expected: secs + SIMPLE_DDL_SECS,
+            low: secs * low,
+            high: secs * high + SIMPLE_DDL_MAX_SECS,
+        }
+    }
+
+    /// Returns the affected table and a description of what happens to it.
+    fn describe(&self) -> Option<(&str, &'static str)> {
+        match self {
+            Self::SimpleDdl => None,
+            Self::Index(t) => Some((t.as_str(), "builds index on")),
+            Self::Rewrite(t) => Some((t.as_str(), "rewrites")),
+            Self::Scan(t) => Some((t.as_str(), "scans")),
+            Self::Dml(t) => Some((t.as_str(), "modifies rows of")),
+        }
+    }
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, versions: &[u32]) -> Result<()> {
+    let versions = if versions.is_empty() {
+        let pending = pending_migrations(db).await?;
+        if pending.is_empty() {
+            bunt::println!("{$green+intense}No pending migrations.{/$}");
+            return Ok(());
+        }
+        pending
+    } else {
+        versions.iter().map(|&v| u64::from(v)).collect()
+    };
+
+    // `reltuples` is only an estimate (updated by `analyze`/`vacuum`), but it
+    // is good enough here and does not require scanning all tables.
+    let rows = db
+        .query(
+            "select relname::text, greatest(reltuples, 0)::float8 \
+                from pg_class \
+                where relnamespace = 'public'::regnamespace and relkind in ('r', 'p')",
+            &[],
+        )
+        .await
+        .context("failed to query table sizes")?
+        .into_iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, f64>(1)))
+        .collect::<HashMap<_, _>>();
+
+    let mut total = Estimate::default();
+    for version in versions {
+        let migration = MIGRATIONS.get(&version)
+            .ok_or_else(|| anyhow!("migration {version} does not exist in this Tobira binary"))?;
+
+        let mut sum = Estimate::default();
+        let mut details = Vec::new();
+        for span in split_statements(migration.script) {
+            let kind = classify(&migration.script[span]);
+            let estimate = kind.estimate(&rows);
+            sum += estimate;
+            if let Some((table, what)) = kind.describe() {
+                let n = rows.get(table).copied().unwrap_or(0.0);
+                let estimate = format_estimate(estimate);
+                details.push(format!("{what} '{table}' ({n:.0} rows): {estimate}"));
+            }
+        }
+        total += sum;
+
+        bunt::println!(
+            "{$bold}{}-{}{/$}: {[blue+intense]}",
+            version,
+            migration.name,
+            format_estimate(sum),
+        );
+        for detail in details {
+            bunt::println!("   {$dimmed}{}{/$}", detail);
+        }
+    }
+
+    println!();
+    bunt::println!("{$bold}Total:{/$} {[blue+intense]}", format_estimate(total));
+    bunt::println!("{$dimmed}Estimates are based on a simple cost model and the table sizes \
+        estimated by PostgreSQL. Tables created by earlier migrations count as empty.{/$}");
+
+    Ok(())
+}
+
+/// Returns the IDs of all migrations of this binary not yet applied.
+pub(super) async fn pending_migrations(db: &Db) -> Result<Vec<u64>> {
+    let current = if query::does_table_exist(&**db, "__db_migrations").await? {
+        db.query_one("select coalesce(max(id), 0) from __db_migrations", &[])
+            .await?
+            .get::<_, i64>(0) as u64
+    } else {
+        0
+    };
+    Ok(MIGRATIONS.keys().copied().filter(|&id| id > current).collect())
+}
+
+/// Determines what the statement does and which table it affects.
+pub(super) fn classify(statement: &str) -> Kind {
+    let normalized = statement.to_ascii_lowercase().replace(['(', ')', ',', ';', '"'], " ");
+    let words = normalized.split_whitespace().collect::<Vec<_>>();
+    let has = |word: &str| words.contains(&word);
+    let has_seq = |seq: &[&str]| words.windows(seq.len()).any(|w| w == seq);
+    let after = |word: &str| {
+        words.iter()
+            .position(|w| *w == word)
+            .and_then(|i| words[i + 1..].iter().find(|w| !["only", "if", "exists"].contains(*w)))
+            .map(|table| table.trim_start_matches("public.").to_owned())
+    };
+
+    let table = match first_keyword(statement).as_deref() {
+        Some("create") if has("index") => return after("on").map_or(Kind::SimpleDdl, Kind::Index),
+        Some("alter") if has_seq(&["alter", "table"]) => match after("table") {
+            Some(table) => table,
+            None => return Kind::SimpleDdl,
+        },
+        Some("update") => return after("update").map_or(Kind::SimpleDdl, Kind::Dml),
+        Some("delete") => return after("from").map_or(Kind::SimpleDdl, Kind::Dml),
+        Some("insert") => return after("from").map_or(Kind::SimpleDdl, Kind::Dml),
+        _ => return Kind::SimpleDdl,
+    };
+
+    // Only `alter table` is left. Adding a column (even with a non-volatile
+    // default) does not touch existing rows.
+    let changes_type = words.windows(4)
+        .any(|w| w[0] == "alter" && w[1] == "column" && w[3] == "type")
+        || words.windows(3).any(|w| w[0] == "alter" && w[1] != "column" && w[2] == "type")
+        || has_seq(&["set", "data", "type"]);
+    if changes_type {
+        Kind::Rewrite(table)
+    } else if has_seq(&["primary", "key"]) || has("unique") {
+        Kind::Index(table)
+    } else if has_seq(&["set", "not", "null"])
+        || (has_seq(&["add", "constraint"]) && !has_seq(&["not", "valid"]))
+        || has_seq(&["validate", "constraint"])
+    {
+        Kind::Scan(table)
+    } else {
+        Kind::SimpleDdl
+    }
+}
+
+pub(super) fn format_estimate(estimate: Estimate) -> String {
+    format!(
+        "~{} ({} – {})",
+        human_duration(estimate.expected),
+        human_duration(estimate.low),
+        human_duration(estimate.high),
+    )
+}
+
+fn human_duration(secs: f64) -> String {
+    match secs {
+        s if s >= 3600.0 => format!("{:.1}h", s / 3600.0),
+        s if s >= 60.0 => format!("{:.0}min", s / 60.0),
+        s if s >= 1.0 => format!("{s:.0}s"),
+        s => format!("{:.0}ms", s * 1000.0),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, Kind};
+
+    #[test]
+    fn classifies_statements() {
+        let cases = [
+            ("create table foo (id bigint);", Kind::SimpleDdl),
+            ("create index idx_foo on public.events (created);", Kind::Index("events".into())),
+            ("create unique index on only \"realms\"(path);", Kind::Index("realms".into())),
+            ("alter table events add column foo text;", Kind::SimpleDdl),
+            ("alter table events alter column foo type bigint;", Kind::Rewrite("events".into())),
+            ("alter table events alter foo set data type int;", Kind::Rewrite("events".into())),
+            ("alter table events add column type text;", Kind::SimpleDdl),
+            ("alter table series alter column t set not null;", Kind::Scan("series".into())),
+            (
+                "alter table blocks add constraint c check (x > 0) not valid;",
+                Kind::SimpleDdl,
+            ),
+            ("alter table blocks add primary key (id);", Kind::Index("blocks".into())),
+            ("update realms set name = null where id = 1;", Kind::Dml("realms".into())),
+            ("delete from only events;", Kind::Dml("events".into())),
+            ("insert into foo select * from series;", Kind::Dml("series".into())),
+            ("insert into foo values (1);", Kind::SimpleDdl),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(classify(statement), expected, "{statement}");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! The `db migration-export` subcommand.
+
+use std::path::Path;
+
+use crate::{prelude::*, db::{Db, query, migrations::{checksum, script_archive}}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, id: u64, archive: Option<&Path>) -> Result<()> {
+    if !query::does_table_exist(&**db, "__db_migrations").await? {
+        bail!("table '__db_migrations' does not exist: no migrations were applied yet");
+    }
+
+    let row = db
+        .query_opt(
+            // `script_sha256` does not exist in tables created by older versions.
+            "select script, to_jsonb(__db_migrations) ->> 'script_sha256' \
+                from __db_migrations \
+                where id = $1",
+            &[&(id as i64)],
+        )
+        .await
+        .context("failed to query '__db_migrations'")?
+        .ok_or_else(|| anyhow!("migration {id} is not applied to the database"))?;
+
+    let script = match row.get::<_, Option<String>>(1) {
+        None => row.get::<_, String>(0),
+        Some(sha256) => {
+            let path = archive.ok_or_else(|| anyhow!(
+                "the script of migration {id} was archived: pass the archive file via \
+                    `--archive`",
+            ))?;
+            let entry = script_archive::read(path)?
+                .remove(&id)
+                .ok_or_else(|| anyhow!("migration {id} is not in '{}'", path.display()))?;
+            if checksum(&entry.script) != sha256 {
+                bail!("the script of migration {id} in '{}' does not match the checksum \
+                    in the database", path.display());
+            }
+            entry.script
+        }
+    };
+
+    print!("{script}");
+    Ok(())
+}
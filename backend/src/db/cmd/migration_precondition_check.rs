@@ -0,0 +1,70 @@
+//! The `db migration-precondition-check` subcommand.
+
+use crate::{
+    prelude::*,
+    db::{Db, migrations::{MIGRATIONS, evaluate_precondition}},
+};
+use super::migration_estimate_time::pending_migrations;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &mut Db, versions: &[u32]) -> Result<()> {
+    let versions = if versions.is_empty() {
+        let pending = pending_migrations(db).await?;
+        if pending.is_empty() {
+            bunt::println!("{$green+intense}No pending migrations.{/$}");
+            return Ok(());
+        }
+        pending
+    } else {
+        versions.iter().map(|&v| u64::from(v)).collect()
+    };
+
+    let mut violated = 0;
+    let mut failed = 0;
+    for version in versions {
+        let migration = MIGRATIONS.get(&version)
+            .ok_or_else(|| anyhow!("migration {version} does not exist in this Tobira binary"))?;
+        let preconditions = migration.preconditions();
+        if preconditions.is_empty() {
+            bunt::println!("{[bold]}: {$dimmed}no preconditions{/$}",
+                format!("{version}-{}", migration.name));
+            continue;
+        }
+
+        bunt::println!("{[bold]}:", format!("{version}-{}", migration.name));
+        for precondition in preconditions {
+            // Each query runs in its own read only transaction, so that
+            // neither errors nor (accidental) modifications have any effect.
+            let tx = db.build_transaction().read_only(true).start().await?;
+            match evaluate_precondition(&*tx, precondition).await {
+                Ok(true) => bunt::println!("  {$green+intense}✔{/$} {}", precondition),
+                Ok(false) => {
+                    violated += 1;
+                    bunt::println!("  {$red+intense}✘{/$} {}", precondition);
+                }
+                Err(e) => {
+                    failed += 1;
+                    let error = format!("{e:#}");
+                    bunt::println!("  {$yellow}?{/$} {} {[dimmed]}", precondition, error);
+                }
+            }
+            tx.rollback().await?;
+        }
+    }
+
+    println!();
+    if failed > 0 {
+        bunt::println!("{$dimmed}Preconditions are checked against the current schema. \
+            Queries referring to objects created by earlier pending migrations fail here, \
+            but are checked again when migrating.{/$}");
+    }
+    if violated > 0 {
+        bail!("{violated} precondition(s) not met: fix the data before migrating");
+    }
+    if failed == 0 {
+        bunt::println!("{$green+intense}All preconditions are met.{/$}");
+    }
+
+    Ok(())
+}
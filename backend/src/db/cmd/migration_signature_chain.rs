@@ -0,0 +1,113 @@
+//! The `db migration-signature-chain` subcommand.
+
+use crate::{prelude::*, db::{Db, migrations::{Chain, MIGRATIONS}}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, expected: Option<&str>) -> Result<()> {
+    let rows = db
+        .query(
+            // `script_sha256` does not exist in tables created by older versions.
+            "select id, name, script, to_jsonb(__db_migrations) ->> 'script_sha256' \
+                from __db_migrations \
+                order by id",
+            &[],
+        )
+        .await
+        .context("failed to fetch from __db_migrations")?;
+    let applied = rows.iter()
+        .map(|row| {
+            let id = row.get::<_, i64>(0) as u64;
+            let script = match row.get::<_, Option<String>>(3) {
+                Some(sha256) => Script::Archived { sha256 },
+                None => Script::Stored(row.get(2)),
+            };
+            (id, row.get::<_, String>(1), script)
+        })
+        .collect::<Vec<_>>();
+    if applied.is_empty() {
+        bail!("no migrations have been applied to the database");
+    }
+
+    // Compare each link of the chain with the migrations of this binary to
+    // find the first one that differs.
+    bunt::println!("{$bold}Chain of applied migrations:{/$}");
+    let mut db_chain = Chain::new();
+    let mut binary_chain = Chain::new();
+    let mut diverged_at = None;
+    for (id, name, script) in &applied {
+        let checksum = match script {
+            Script::Stored(script) => db_chain.push(*id, name, script),
+            Script::Archived { sha256 } => {
+                let bytes = hex::decode(sha256)
+                    .with_context(|| format!("invalid `script_sha256` of migration {id}"))?;
+                db_chain.push_checksum(*id, name, &bytes);
+                sha256.clone()
+            }
+        };
+        let matches_binary = match MIGRATIONS.get(id) {
+            Some(m) if diverged_at.is_none() => {
+                binary_chain.push(*id, m.name, m.script);
+                binary_chain.hash() == db_chain.hash()
+            }
+            _ => false,
+        };
+        if !matches_binary && diverged_at.is_none() {
+            diverged_at = Some(*id);
+        }
+
+        let (checksum, chain) = (&checksum[..16], db_chain.hash());
+        if matches_binary {
+            bunt::println!(
+                "  {$green}✔{/$} {}-{} {$dimmed}checksum {} → chain {}{/$}",
+                id, name, checksum, &chain[..16],
+            );
+        } else {
+            bunt::println!(
+                "  {$red+intense}✘{/$} {}-{} {$dimmed}checksum {} → chain {}{/$}",
+                id, name, checksum, &chain[..16],
+            );
+        }
+    }
+
+    let final_hash = db_chain.hash();
+    println!();
+    bunt::println!("{$bold}Chain hash of the database:{/$} {}", final_hash);
+    let mut binary_full = Chain::new();
+    for (id, m) in MIGRATIONS.iter() {
+        binary_full.push(*id, m.name, m.script);
+    }
+    bunt::println!(
+        "{$dimmed}Chain hash of all {} migrations of this Tobira binary: {}{/$}",
+        MIGRATIONS.len(),
+        binary_full.hash(),
+    );
+
+    if let Some(id) = diverged_at {
+        println!();
+        bunt::println!(
+            "{$yellow+intense}The chain differs from this Tobira binary starting at migration \
+                {}. All later links are affected as well.{/$}",
+            id,
+        );
+    }
+
+    if let Some(expected) = expected {
+        println!();
+        if expected.trim().eq_ignore_ascii_case(&final_hash) {
+            bunt::println!("{$green+intense}Chain hash matches the expected value.{/$}");
+        } else {
+            bail!("chain hash of the database does NOT match the expected value '{expected}': \
+                the migration history has been modified");
+        }
+    }
+
+    Ok(())
+}
+
+/// The script of an applied migration as stored in `__db_migrations`.
+enum Script {
+    Stored(String),
+    /// Archived via `db migrate --compress-logs`, only the checksum is left.
+    Archived { sha256: String },
+}
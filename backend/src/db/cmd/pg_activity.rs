@@ -0,0 +1,305 @@
+//! The `db pg-activity` subcommand.
+
+use std::{io::{self, Write}, time::Duration};
+
+use crossterm::{
+    cursor,
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use futures::StreamExt;
+
+use crate::{prelude::*, db::Db};
+
+
+/// One row of `pg_stat_activity`.
+struct Backend {
+    pid: i32,
+    /// Seconds since the current query (or the last one, if idle) started.
+    duration: Option<f64>,
+    state: Option<String>,
+    wait_event: Option<String>,
+    query: String,
+}
+
+/// What the UI currently shows.
+struct State {
+    backends: Vec<Backend>,
+    /// Index into `backends`.
+    selected: usize,
+    /// Backend that the user is asked to kill.
+    kill_candidate: Option<i32>,
+    /// Message shown in the last line, e.g. the result of killing a backend.
+    status: Option<String>,
+}
+
+/// Restores the terminal when dropped, also on errors and panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<Self> {
+        terminal::enable_raw_mode().context("failed to enable raw mode of terminal")?;
+        crossterm::execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, refresh_secs: f64, filter: Option<&str>) -> Result<()> {
+    if !refresh_secs.is_finite() || refresh_secs < 0.1 {
+        bail!("`--refresh-secs` has to be at least 0.1");
+    }
+    let refresh = Duration::from_secs_f64(refresh_secs);
+
+    // Loading once before touching the terminal gives a proper error message
+    // if the DB is not reachable.
+    let mut state = State {
+        backends: load(db, filter).await?,
+        selected: 0,
+        kill_candidate: None,
+        status: None,
+    };
+
+    let _guard = TerminalGuard::new()?;
+    let mut events = EventStream::new();
+    let mut next_refresh = tokio::time::Instant::now() + refresh;
+    loop {
+        render(&state, refresh_secs, filter)?;
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_refresh) => {
+                let selected_pid = state.backends.get(state.selected).map(|b| b.pid);
+                state.backends = load(db, filter).await?;
+                state.selected = selected_pid
+                    .and_then(|pid| state.backends.iter().position(|b| b.pid == pid))
+                    .unwrap_or(state.selected)
+                    .min(state.backends.len().saturating_sub(1));
+                next_refresh = tokio::time::Instant::now() + refresh;
+            }
+            event = events.next() => {
+                let key = match event {
+                    None => return Ok(()),
+                    Some(Err(e)) => return Err(e).context("failed to read terminal event"),
+                    Some(Ok(Event::Key(key))) => key,
+                    Some(Ok(_)) => continue,
+                };
+                if !handle_key(db, &mut state, key).await? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Loads all backends connected to Tobira's database, except our own. Active
+/// ones come first, each group ordered by duration (longest first).
+async fn load(db: &Db, filter: Option<&str>) -> Result<Vec<Backend>> {
+    let rows = db
+        .query(
+            "select pid, extract(epoch from now() - query_start)::double precision, \
+                    state, concat_ws(': ', wait_event_type, wait_event), query \
+                from pg_stat_activity \
+                where datname = current_database() \
+                    and pid <> pg_backend_pid() \
+                    and ($1::text is null \
+                        or concat_ws(' ', state, usename, application_name, query) \
+                            ilike '%' || $1 || '%') \
+                order by state = 'active' desc, query_start nulls last",
+            &[&filter],
+        )
+        .await
+        .context("failed to query `pg_stat_activity`")?;
+
+    let backends = rows.into_iter()
+        .map(|row| Backend {
+            pid: row.get(0),
+            duration: row.get(1),
+            state: row.get(2),
+            wait_event: Some(row.get::<_, String>(3)).filter(|s| !s.is_empty()),
+            query: row.get::<_, Option<String>>(4).unwrap_or_default(),
+        })
+        .collect();
+    Ok(backends)
+}
+
+/// Handles a key press. Returns `false` if the program should exit.
+async fn handle_key(db: &Db, state: &mut State, key: KeyEvent) -> Result<bool> {
+    let KeyEvent { code, modifiers, .. } = key;
+    if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+        return Ok(false);
+    }
+
+    // While asking for confirmation, all other keys abort the kill.
+    if let Some(pid) = state.kill_candidate.take() {
+        let (function, action) = match code {
+            KeyCode::Char('y') => ("pg_terminate_backend", "Terminated"),
+            KeyCode::Char('c') => ("pg_cancel_backend", "Canceled query of"),
+            _ => {
+                state.status = Some("Aborted".into());
+                return Ok(true);
+            }
+        };
+
+        let query = format!("select {function}($1)");
+        state.status = Some(match db.query_one(&query, &[&pid]).await {
+            Ok(row) if row.get::<_, bool>(0) => format!("{action} backend {pid}"),
+            Ok(_) => format!("Backend {pid} does not exist anymore"),
+            Err(e) => format!("Failed to kill backend {pid}: {e}"),
+        });
+        return Ok(true);
+    }
+
+    state.status = None;
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+        KeyCode::Up => state.selected = state.selected.saturating_sub(1),
+        KeyCode::Down => {
+            state.selected = (state.selected + 1).min(state.backends.len().saturating_sub(1));
+        }
+        KeyCode::Char('k') => {
+            if let Some(backend) = state.backends.get(state.selected) {
+                state.kill_candidate = Some(backend.pid);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+fn render(state: &State, refresh_secs: f64, filter: Option<&str>) -> Result<()> {
+    const PID_WIDTH: usize = 8;
+    const DURATION_WIDTH: usize = 10;
+    const STATE_WIDTH: usize = 20;
+    const WAIT_WIDTH: usize = 24;
+    const FIXED_WIDTH: usize = PID_WIDTH + DURATION_WIDTH + STATE_WIDTH + WAIT_WIDTH + 4;
+
+    let (width, height) = terminal::size()?;
+    let (width, height) = (usize::from(width), usize::from(height));
+    let query_width = width.saturating_sub(FIXED_WIDTH);
+    let line = |pid: &str, duration: &str, state: &str, wait: &str, query: &str| format!(
+        "{:>PID_WIDTH$} {:>DURATION_WIDTH$} {:STATE_WIDTH$} {:WAIT_WIDTH$} {}",
+        truncate(pid, PID_WIDTH),
+        truncate(duration, DURATION_WIDTH),
+        truncate(state, STATE_WIDTH),
+        truncate(wait, WAIT_WIDTH),
+        truncate(query, query_width),
+    );
+
+    let mut out = io::stdout().lock();
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+    // Header
+    let filter = filter.map(|f| format!(", filter: '{f}'")).unwrap_or_default();
+    let title = format!(
+        "{} backends (refreshing every {refresh_secs}s{filter})",
+        state.backends.len(),
+    );
+    queue!(out, SetAttribute(Attribute::Bold), Print(truncate(&title, width)))?;
+    queue!(out, cursor::MoveTo(0, 2), SetAttribute(Attribute::Underlined))?;
+    let header = line("pid", "duration", "state", "wait event", "query");
+    queue!(out, Print(format!("{header:width$}")), SetAttribute(Attribute::Reset))?;
+
+    // Rows: scrolled such that the selected one is visible. Four lines are
+    // taken by the header and one by the footer.
+    let visible = height.saturating_sub(5);
+    let first = (state.selected + 1).saturating_sub(visible);
+    for (i, backend) in state.backends.iter().enumerate().skip(first).take(visible) {
+        let row = line(
+            &backend.pid.to_string(),
+            &backend.duration.map(format_duration).unwrap_or_default(),
+            backend.state.as_deref().unwrap_or_default(),
+            backend.wait_event.as_deref().unwrap_or_default(),
+            &backend.query,
+        );
+        queue!(out, cursor::MoveTo(0, (3 + i - first) as u16))?;
+        if i == state.selected {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(out, Print(format!("{row:width$}")), SetAttribute(Attribute::Reset))?;
+    }
+
+    // Footer
+    let footer = match (state.kill_candidate, &state.status) {
+        (Some(pid), _) => format!(
+            "Kill backend {pid}? [y] terminate backend, [c] only cancel query, other key: abort",
+        ),
+        (None, Some(status)) => status.clone(),
+        (None, None) => "[↑/↓] select  [k] kill selected backend  [q] quit".into(),
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, height.saturating_sub(1) as u16),
+        SetAttribute(Attribute::Bold),
+        Print(truncate(&footer, width)),
+        SetAttribute(Attribute::Reset),
+    )?;
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Formats a duration in seconds in a compact way, e.g. `1h 2m 3s`.
+fn format_duration(secs: f64) -> String {
+    // Clocks of Postgres backends might be slightly ahead.
+    let secs = secs.max(0.0);
+    if secs < 10.0 {
+        return format!("{secs:.2}s");
+    }
+
+    let secs = secs as u64;
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+/// Collapses all whitespace (e.g. line breaks in queries) to single spaces
+/// and truncates the string to at most `max` characters.
+fn truncate(s: &str, max: usize) -> String {
+    let collapsed = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= max {
+        return collapsed;
+    }
+
+    let mut out = collapsed.chars().take(max.saturating_sub(1)).collect::<String>();
+    if max > 0 {
+        out.push('…');
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, truncate};
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(-0.01), "0.00s");
+        assert_eq!(format_duration(1.234), "1.23s");
+        assert_eq!(format_duration(42.9), "42s");
+        assert_eq!(format_duration(125.0), "2m 5s");
+        assert_eq!(format_duration(3.0 * 3600.0 + 7.0), "3h 0m 7s");
+    }
+
+    #[test]
+    fn truncates() {
+        assert_eq!(truncate("select 1", 20), "select 1");
+        assert_eq!(truncate("select *\n    from  events", 30), "select * from events");
+        assert_eq!(truncate("select * from events", 10), "select * …");
+        assert_eq!(truncate("äöü", 2), "ä…");
+        assert_eq!(truncate("abc", 0), "");
+    }
+}
@@ -0,0 +1,178 @@
+//! The `db pg-checkpoint-stats` subcommand.
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+use crate::{prelude::*, db::Db};
+
+
+/// Share of requested checkpoints above which increasing `max_wal_size` is
+/// suggested.
+const REQUESTED_THRESHOLD: f64 = 0.5;
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, window_secs: u64) -> Result<()> {
+    let version: i32 = db.query_one("select current_setting('server_version_num')::int", &[])
+        .await?
+        .get(0);
+    let settings = db
+        .query_one(
+            "select current_setting('max_wal_size'), current_setting('checkpoint_timeout')",
+            &[],
+        )
+        .await?;
+    let max_wal_size: String = settings.get(0);
+    let checkpoint_timeout: String = settings.get(1);
+
+    let total = sample(db, version).await?;
+    let since = total.stats_reset.map_or("server start".into(), |t| t.to_string());
+    bunt::println!("{$bold}Checkpoints since {}:{/$}", since);
+    print_stats(&total, total.stats_reset.map(|t| elapsed_secs(t, Utc::now())));
+
+    let mut assessed = &total;
+    let window;
+    if window_secs > 0 {
+        info!("Sampling checkpoint statistics over {window_secs}s...");
+        tokio::time::sleep(Duration::from_secs(window_secs)).await;
+        window = sample(db, version).await?.since(&total);
+        println!();
+        bunt::println!("{$bold}Checkpoints in the last {}s:{/$}", window_secs);
+        print_stats(&window, Some(window_secs as f64));
+
+        // Without checkpoints in the window, it says nothing about the ratio.
+        if window.count() > 0 {
+            assessed = &window;
+        }
+    }
+
+    println!();
+    bunt::println!(
+        "{$dimmed}max_wal_size = {}, checkpoint_timeout = {}{/$}",
+        max_wal_size,
+        checkpoint_timeout,
+    );
+    match assessed.requested_share() {
+        Some(share) if share > REQUESTED_THRESHOLD => {
+            bunt::println!(
+                "{$yellow+bold}Warning:{/$} {} of checkpoints were requested (e.g. because \
+                    `max_wal_size` was reached) instead of timed.",
+                format!("{:.0}%", share * 100.0),
+            );
+            println!("This indicates a lot of WAL being written between checkpoints. Consider \
+                increasing `max_wal_size` (currently {max_wal_size}), so that most checkpoints \
+                are triggered by `checkpoint_timeout` and their I/O is spread out.");
+        }
+        Some(_) => bunt::println!("{$green+intense}Most checkpoints are timed.{/$}"),
+        None => println!("No checkpoints yet."),
+    }
+
+    Ok(())
+}
+
+/// Cumulative checkpoint statistics of the server.
+#[derive(Debug, Clone, Copy)]
+struct Stats {
+    timed: i64,
+    requested: i64,
+    /// Total time spent writing and syncing files, in milliseconds.
+    write_ms: f64,
+    sync_ms: f64,
+    stats_reset: Option<DateTime<Utc>>,
+}
+
+impl Stats {
+    fn count(&self) -> i64 {
+        self.timed + self.requested
+    }
+
+    fn requested_share(&self) -> Option<f64> {
+        (self.count() > 0).then(|| self.requested as f64 / self.count() as f64)
+    }
+
+    /// Returns the difference to an earlier sample. If the statistics were
+    /// reset in between, all values are new.
+    fn since(self, earlier: &Stats) -> Stats {
+        if self.stats_reset != earlier.stats_reset {
+            return self;
+        }
+        Stats {
+            timed: self.timed - earlier.timed,
+            requested: self.requested - earlier.requested,
+            write_ms: self.write_ms - earlier.write_ms,
+            sync_ms: self.sync_ms - earlier.sync_ms,
+            stats_reset: self.stats_reset,
+        }
+    }
+}
+
+async fn sample(db: &Db, server_version: i32) -> Result<Stats> {
+    // Postgres 17 moved these statistics to their own view.
+    let query = if server_version >= 170000 {
+        "select num_timed, num_requested, write_time, sync_time, stats_reset \
+            from pg_stat_checkpointer"
+    } else {
+        "select checkpoints_timed, checkpoints_req, checkpoint_write_time, \
+            checkpoint_sync_time, stats_reset \
+            from pg_stat_bgwriter"
+    };
+    let row = db.query_one(query, &[]).await.context("failed to query checkpoint statistics")?;
+    Ok(Stats {
+        timed: row.get(0),
+        requested: row.get(1),
+        write_ms: row.get(2),
+        sync_ms: row.get(3),
+        stats_reset: row.get(4),
+    })
+}
+
+fn elapsed_secs(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+/// Prints `stats` collected over `secs` seconds (if known).
+fn print_stats(stats: &Stats, secs: Option<f64>) {
+    bunt::println!(" - {$dimmed}timed:{/$}     {}", stats.timed);
+    bunt::println!(" - {$dimmed}requested:{/$} {}", stats.requested);
+    if let Some(secs) = secs.filter(|&s| s > 0.0) {
+        let per_hour = stats.count() as f64 / secs * 3600.0;
+        bunt::println!(" - {$dimmed}per hour:{/$}  {}", format!("{per_hour:.1}"));
+    }
+    if stats.count() > 0 {
+        let avg = |ms: f64| format!("{:.1}s", ms / stats.count() as f64 / 1000.0);
+        bunt::println!(
+            " - {$dimmed}avg duration:{/$} {} writing, {} syncing",
+            avg(stats.write_ms),
+            avg(stats.sync_ms),
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use super::Stats;
+
+    #[test]
+    fn difference() {
+        let reset = Some(Utc.ymd(2024, 1, 1).and_hms(0, 0, 0));
+        let before = Stats {
+            timed: 10,
+            requested: 2,
+            write_ms: 1000.0,
+            sync_ms: 100.0,
+            stats_reset: reset,
+        };
+        let after = Stats { timed: 11, requested: 6, write_ms: 1500.0, ..before };
+
+        let window = after.since(&before);
+        assert_eq!((window.timed, window.requested), (1, 4));
+        assert_eq!(window.write_ms, 500.0);
+        assert_eq!(window.requested_share(), Some(0.8));
+        assert_eq!(before.requested_share().map(|s| s < 0.5), Some(true));
+
+        let reset_in_between = Stats { stats_reset: None, ..after };
+        assert_eq!(reset_in_between.since(&before).timed, 11);
+        assert_eq!(Stats { timed: 0, requested: 0, ..before }.requested_share(), None);
+    }
+}
@@ -0,0 +1,352 @@
+//! The `db pg-hba-test` subcommand.
+
+use std::net::IpAddr;
+
+use crate::{prelude::*, db::{create_pool, DbConfig, TlsMode}};
+
+
+/// How the client connects, as relevant for the connection type of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// Unix domain socket.
+    Local,
+    Tcp { ssl: bool },
+}
+
+/// The connection that `pg_hba.conf` has to allow.
+#[derive(Debug)]
+struct Client {
+    user: String,
+    database: String,
+    /// All roles the user is a member of (including itself).
+    roles: Vec<String>,
+    transport: Transport,
+    /// `None` if unknown or for Unix sockets.
+    address: Option<IpAddr>,
+}
+
+/// A row of `pg_hba_file_rules`.
+#[derive(Debug)]
+struct Rule {
+    line: i32,
+    type_: String,
+    databases: Vec<String>,
+    users: Vec<String>,
+    address: Option<String>,
+    netmask: Option<String>,
+    auth_method: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Match {
+    Yes,
+    No,
+    /// Depends on things we cannot check, e.g. host names or `samenet`.
+    Unknown,
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(config: &DbConfig, address: Option<IpAddr>) -> Result<()> {
+    let configured_transport = if config.host.starts_with('/') {
+        Transport::Local
+    } else {
+        Transport::Tcp { ssl: config.tls_mode != TlsMode::Off }
+    };
+
+    let pool = match create_pool(config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            bunt::println!("{$red+intense}✘ Cannot connect:{/$} {}", format!("{e:#}"));
+            println!();
+            print_suggestion(config, configured_transport, address, "scram-sha-256");
+            bail!("failed to connect to the database (see above)");
+        }
+    };
+    let db = pool.get().await?;
+    bunt::println!("{$green}Connecting with the configured settings works.{/$}");
+
+    // Determine everything about our connection as the server sees it.
+    let row = db
+        .query_one(
+            "select current_user::text, current_database()::text, inet_client_addr()::text, \
+                (select ssl from pg_stat_ssl where pid = pg_backend_pid()), \
+                array(select rolname::text from pg_roles \
+                    where pg_has_role(current_user, oid, 'member')), \
+                current_setting('password_encryption')",
+            &[],
+        )
+        .await
+        .context("failed to query connection information")?;
+    let actual_address = row.get::<_, Option<String>>(2)
+        .map(|addr| addr.split('/').next().unwrap_or_default().parse::<IpAddr>())
+        .transpose()
+        .context("server returned invalid client address")?;
+    let transport = match (address, actual_address) {
+        (Some(_), _) => configured_transport,
+        (None, Some(_)) => Transport::Tcp { ssl: row.get::<_, Option<bool>>(3).unwrap_or(false) },
+        (None, None) => Transport::Local,
+    };
+    let client = Client {
+        user: row.get(0),
+        database: row.get(1),
+        roles: row.get(4),
+        transport,
+        address: address.or(actual_address),
+    };
+    let password_encryption: String = row.get(5);
+
+    let rows = db
+        .query(
+            "select line_number, type, database, user_name, address, netmask, auth_method, error \
+                from pg_hba_file_rules \
+                order by line_number",
+            &[],
+        )
+        .await
+        .context("failed to read `pg_hba_file_rules` (this requires superuser privileges or \
+            an explicit grant, e.g. `grant select on pg_hba_file_rules to <user>`)")?;
+
+    let mut rules = Vec::new();
+    for row in rows {
+        let line: i32 = row.get(0);
+        if let Some(error) = row.get::<_, Option<String>>(7) {
+            bunt::println!("{$yellow}⚠ Line {} of `pg_hba.conf` is invalid:{/$} {}", line, error);
+            continue;
+        }
+        rules.push(Rule {
+            line,
+            type_: row.get(1),
+            databases: row.get(2),
+            users: row.get(3),
+            address: row.get(4),
+            netmask: row.get(5),
+            auth_method: row.get(6),
+        });
+    }
+
+    let from = match (client.transport, client.address) {
+        (Transport::Local, _) => "a Unix socket".to_owned(),
+        (Transport::Tcp { ssl }, Some(addr)) => {
+            format!("{addr} ({})", if ssl { "TCP with TLS" } else { "TCP without TLS" })
+        }
+        (Transport::Tcp { .. }, None) => "an unknown address".to_owned(),
+    };
+    bunt::println!(
+        "Checking rules for user {[bold]} connecting to database {[bold]} from {[bold]}",
+        client.user,
+        client.database,
+        from,
+    );
+    println!();
+
+    // Like Postgres, we use the first matching rule.
+    for rule in &rules {
+        match rule_matches(rule, &client) {
+            Match::No => {}
+            Match::Unknown => {
+                bunt::println!(
+                    "{$yellow}?{/$} Line {}: {[dimmed]} {$dimmed}might match (cannot be checked \
+                        here), in which case it is used{/$}",
+                    rule.line,
+                    rule.to_string(),
+                );
+            }
+            Match::Yes if rule.auth_method == "reject" => {
+                bunt::println!(
+                    "{$red+intense}✘{/$} Line {} matches, but rejects the connection: {[dimmed]}",
+                    rule.line,
+                    rule.to_string(),
+                );
+                println!();
+                bunt::println!("Add a rule before line {} that allows the connection:", rule.line);
+                print_suggestion(config, client.transport, client.address, &password_encryption);
+                return Ok(());
+            }
+            Match::Yes => {
+                bunt::println!(
+                    "{$green+intense}✔{/$} Line {} matches (auth method {[bold]}): {[dimmed]}",
+                    rule.line,
+                    rule.auth_method,
+                    rule.to_string(),
+                );
+                return Ok(());
+            }
+        }
+    }
+
+    bunt::println!("{$red+intense}✘ No rule of `pg_hba.conf` matches.{/$}");
+    println!();
+    print_suggestion(config, client.transport, client.address, &password_encryption);
+    Ok(())
+}
+
+/// Prints a `pg_hba.conf` line that allows the configured connection.
+fn print_suggestion(
+    config: &DbConfig,
+    transport: Transport,
+    address: Option<IpAddr>,
+    auth_method: &str,
+) {
+    let line = match transport {
+        Transport::Local => format!("local  {}  {}  {auth_method}", config.database, config.user),
+        Transport::Tcp { ssl } => {
+            let address = match address {
+                Some(IpAddr::V4(addr)) => format!("{addr}/32"),
+                Some(IpAddr::V6(addr)) => format!("{addr}/128"),
+                None => "<address-of-tobira-host>/32".to_owned(),
+            };
+            format!(
+                "{}  {}  {}  {address}  {auth_method}",
+                if ssl { "hostssl" } else { "host" },
+                config.database,
+                config.user,
+            )
+        }
+    };
+    bunt::println!("Suggested line for `pg_hba.conf` (reload Postgres afterwards):");
+    bunt::println!("    {[bold]}", line);
+}
+
+fn rule_matches(rule: &Rule, client: &Client) -> Match {
+    let type_matches = match (rule.type_.as_str(), client.transport) {
+        ("local", Transport::Local) => true,
+        ("host" | "hostnogssenc", Transport::Tcp { .. }) => true,
+        ("hostssl", Transport::Tcp { ssl }) => ssl,
+        ("hostnossl", Transport::Tcp { ssl }) => !ssl,
+        _ => false,
+    };
+
+    let database_matches = rule.databases.iter().any(|db| match db.as_str() {
+        "all" => true,
+        "sameuser" => client.database == client.user,
+        "samerole" | "samegroup" => client.roles.contains(&client.database),
+        // Only for physical replication connections.
+        "replication" => false,
+        name => name == client.database,
+    });
+
+    let user_matches = rule.users.iter().any(|user| match user.strip_prefix('+') {
+        Some(role) => client.roles.iter().any(|r| r == role),
+        None => user == "all" || *user == client.user,
+    });
+
+    if !type_matches || !database_matches || !user_matches {
+        return Match::No;
+    }
+
+    let Some(rule_address) = &rule.address else {
+        // Only `local` rules have no address.
+        return Match::Yes;
+    };
+    let Some(client_address) = client.address else {
+        return Match::Unknown;
+    };
+    match rule_address.as_str() {
+        "all" => Match::Yes,
+        "samehost" | "samenet" => Match::Unknown,
+        addr => match (addr.parse(), rule.netmask.as_deref().map(str::parse::<IpAddr>)) {
+            (Ok(addr), Some(Ok(mask))) => match masked(addr, mask) {
+                Some(net) if Some(net) == masked(client_address, mask) => Match::Yes,
+                _ => Match::No,
+            },
+            (Ok(addr), None) => if addr == client_address { Match::Yes } else { Match::No },
+            // Host names and invalid net masks.
+            _ => Match::Unknown,
+        },
+    }
+}
+
+/// Applies the net mask to the address, `None` if their IP versions differ.
+fn masked(addr: IpAddr, mask: IpAddr) -> Option<u128> {
+    match (addr, mask) {
+        (IpAddr::V4(addr), IpAddr::V4(mask)) => Some((u32::from(addr) & u32::from(mask)).into()),
+        (IpAddr::V6(addr), IpAddr::V6(mask)) => Some(u128::from(addr) & u128::from(mask)),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.type_, self.databases.join(","), self.users.join(","))?;
+        if let Some(address) = &self.address {
+            write!(f, " {address}")?;
+        }
+        if let Some(netmask) = &self.netmask {
+            write!(f, " {netmask}")?;
+        }
+        write!(f, " {}", self.auth_method)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{rule_matches, Client, Match, Rule, Transport};
+
+    fn rule(type_: &str, database: &str, user: &str, address: Option<&str>) -> Rule {
+        let (address, netmask) = match address.and_then(|a| a.split_once(' ')) {
+            Some((address, netmask)) => (Some(address.to_owned()), Some(netmask.to_owned())),
+            None => (address.map(str::to_owned), None),
+        };
+        Rule {
+            line: 1,
+            type_: type_.into(),
+            databases: database.split(',').map(Into::into).collect(),
+            users: user.split(',').map(Into::into).collect(),
+            address,
+            netmask,
+            auth_method: "scram-sha-256".into(),
+        }
+    }
+
+    fn client(transport: Transport, address: Option<&str>) -> Client {
+        Client {
+            user: "tobira".into(),
+            database: "tobira".into(),
+            roles: vec!["tobira".into(), "web".into()],
+            transport,
+            address: address.map(|a| a.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn connection_type() {
+        let local = client(Transport::Local, None);
+        let tls = client(Transport::Tcp { ssl: true }, Some("10.1.2.3"));
+        let plain = client(Transport::Tcp { ssl: false }, Some("10.1.2.3"));
+
+        assert_eq!(rule_matches(&rule("local", "all", "all", None), &local), Match::Yes);
+        assert_eq!(rule_matches(&rule("local", "all", "all", None), &tls), Match::No);
+        assert_eq!(rule_matches(&rule("host", "all", "all", Some("all")), &local), Match::No);
+        assert_eq!(rule_matches(&rule("hostssl", "all", "all", Some("all")), &tls), Match::Yes);
+        assert_eq!(rule_matches(&rule("hostssl", "all", "all", Some("all")), &plain), Match::No);
+        assert_eq!(rule_matches(&rule("hostnossl", "all", "all", Some("all")), &plain), Match::Yes);
+    }
+
+    #[test]
+    fn database_and_user() {
+        let c = client(Transport::Local, None);
+        assert_eq!(rule_matches(&rule("local", "sameuser", "all", None), &c), Match::Yes);
+        assert_eq!(rule_matches(&rule("local", "other,tobira", "all", None), &c), Match::Yes);
+        assert_eq!(rule_matches(&rule("local", "replication", "all", None), &c), Match::No);
+        assert_eq!(rule_matches(&rule("local", "all", "+web", None), &c), Match::Yes);
+        assert_eq!(rule_matches(&rule("local", "all", "+admins", None), &c), Match::No);
+        assert_eq!(rule_matches(&rule("local", "all", "postgres", None), &c), Match::No);
+    }
+
+    #[test]
+    fn address() {
+        let v4 = client(Transport::Tcp { ssl: true }, Some("10.1.2.3"));
+        let v6 = client(Transport::Tcp { ssl: true }, Some("fd00::5"));
+        let host = |addr| rule("host", "all", "all", Some(addr));
+
+        assert_eq!(rule_matches(&host("10.0.0.0 255.0.0.0"), &v4), Match::Yes);
+        assert_eq!(rule_matches(&host("10.1.2.3 255.255.255.255"), &v4), Match::Yes);
+        assert_eq!(rule_matches(&host("192.168.0.0 255.255.0.0"), &v4), Match::No);
+        assert_eq!(rule_matches(&host("10.0.0.0 255.0.0.0"), &v6), Match::No);
+        assert_eq!(rule_matches(&host("fd00:: ffff::"), &v6), Match::Yes);
+        assert_eq!(rule_matches(&host("db.example.com"), &v4), Match::Unknown);
+        assert_eq!(rule_matches(&host("samenet"), &v4), Match::Unknown);
+    }
+}
@@ -0,0 +1,244 @@
+//! The `db pg-log-tail` subcommand.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use regex::Regex;
+
+use crate::{prelude::*, db::{Db, DbConfig}};
+
+
+/// How often the log file is checked for new lines with `--follow`.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The file based formats of `log_destination`, most readable first.
+const FORMATS: &[&str] = &["stderr", "csvlog", "jsonlog"];
+
+/// Prefix of the `application_name` of all Tobira connections, see
+/// `db::set_application_name`.
+const APPLICATION_NAME: &str = "Tobira";
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    config: &DbConfig,
+    lines: usize,
+    follow: bool,
+    filter: Option<&Regex>,
+) -> Result<()> {
+    if !config.is_local() {
+        bail!("the database runs on another host ('{}'), so its log files cannot be read",
+            config.host);
+    }
+
+    let location = LogLocation::load(db).await
+        .context("failed to determine location of Postgres log files (reading these \
+            settings requires superuser privileges or the role 'pg_read_all_settings')")?;
+
+    // Without a user-provided filter, only lines of Tobira connections are
+    // shown, if the log contains the application name at all.
+    let filter = match filter {
+        Some(regex) => Some(regex.clone()),
+        None if location.has_application_name => {
+            Some(Regex::new(&regex::escape(APPLICATION_NAME)).expect("invalid regex"))
+        }
+        None => {
+            bunt::eprintln!("{$yellow}Note:{/$} `log_line_prefix` does not contain `%a`, so \
+                lines cannot be filtered by application name. Showing all lines.");
+            None
+        }
+    };
+    let matches = |line: &str| filter.as_ref().is_none_or(|regex| regex.is_match(line));
+
+    let mut path = location.current_file(db).await?;
+    bunt::eprintln!("{$dimmed}Reading '{}' ({}){/$}", path.display(), location.format);
+
+    let mut last_lines = VecDeque::new();
+    let mut offset = read_lines(&path, 0, |line| {
+        if lines > 0 && matches(&line) {
+            if last_lines.len() == lines {
+                last_lines.pop_front();
+            }
+            last_lines.push_back(line);
+        }
+    })?;
+    for line in last_lines {
+        println!("{line}");
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let len = std::fs::metadata(&path)
+            .with_context(|| format!("failed to read metadata of '{}'", path.display()))?
+            .len();
+        if len < offset {
+            bunt::eprintln!("{$dimmed}'{}' was truncated{/$}", path.display());
+            offset = 0;
+        }
+        if len > offset {
+            offset = read_lines(&path, offset, |line| {
+                if matches(&line) {
+                    println!("{line}");
+                }
+            })?;
+            continue;
+        }
+
+        // No new lines: maybe Postgres started a new log file.
+        let current = location.current_file(db).await?;
+        if current != path {
+            bunt::eprintln!("{$dimmed}Continuing with new log file '{}'{/$}", current.display());
+            path = current;
+            offset = 0;
+        }
+    }
+}
+
+/// Where Postgres writes its log files to.
+struct LogLocation {
+    /// One of `FORMATS`.
+    format: &'static str,
+    /// Absolute path of `log_directory`.
+    directory: PathBuf,
+    data_directory: Option<PathBuf>,
+    /// Whether log lines contain the application name of the connection.
+    has_application_name: bool,
+}
+
+impl LogLocation {
+    async fn load(db: &Db) -> Result<Self> {
+        let show = |setting: &'static str| async move {
+            db.query_one(&format!("show {setting}"), &[]).await
+                .with_context(|| format!("failed to read setting '{setting}'"))
+                .map(|row| row.get::<_, String>(0))
+        };
+
+        if show("logging_collector").await? != "on" {
+            bail!("`logging_collector` is disabled, so Postgres does not write log files \
+                itself. Its output is likely handled by your service manager (e.g. see \
+                `journalctl -u postgresql`).");
+        }
+        let destination = show("log_destination").await?;
+        let format = choose_format(&destination).ok_or_else(|| anyhow!(
+            "`log_destination` is '{destination}', but only {} are supported",
+            FORMATS.join(", "),
+        ))?;
+
+        // Relative paths are relative to the data directory.
+        let data_directory = show("data_directory").await.ok().map(PathBuf::from);
+        let directory = PathBuf::from(show("log_directory").await?);
+        let directory = match &data_directory {
+            _ if directory.is_absolute() => directory,
+            Some(data_directory) => data_directory.join(directory),
+            None => bail!("`log_directory` is relative, but `data_directory` cannot be read"),
+        };
+
+        let has_application_name = format != "stderr"
+            || show("log_line_prefix").await?.contains("%a");
+
+        Ok(Self { format, directory, data_directory, has_application_name })
+    }
+
+    /// Returns the path of the log file Postgres currently writes to.
+    async fn current_file(&self, db: &Db) -> Result<PathBuf> {
+        // Executing this function is only allowed for superusers by default.
+        let current = db
+            .query_one("select pg_current_logfile($1)", &[&self.format])
+            .await
+            .ok()
+            .and_then(|row| row.get::<_, Option<String>>(0));
+        if let Some(current) = current {
+            let current = PathBuf::from(current);
+            return match &self.data_directory {
+                Some(data_directory) if current.is_relative() => Ok(data_directory.join(current)),
+                _ => Ok(current),
+            };
+        }
+
+        // Otherwise, we just use the most recently modified file.
+        let newest = std::fs::read_dir(&self.directory)
+            .with_context(|| format!("failed to read directory '{}'", self.directory.display()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| is_in_format(&entry.file_name().to_string_lossy(), self.format))
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, entry.path()))
+            })
+            .max_by_key(|(modified, _)| *modified)
+            .map(|(_, path)| path);
+        newest.ok_or_else(|| anyhow!("no {} log file found in '{}'",
+            self.format, self.directory.display()))
+    }
+}
+
+/// Returns the preferred format of all file based ones in `log_destination`.
+fn choose_format(log_destination: &str) -> Option<&'static str> {
+    let destinations = log_destination.split(',').map(str::trim).collect::<Vec<_>>();
+    FORMATS.iter().copied().find(|format| destinations.contains(format))
+}
+
+/// Whether the file name belongs to a log file of the given format, judging
+/// by its extension. Postgres replaces `.log` with `.csv` or `.json` for the
+/// other formats.
+fn is_in_format(file_name: &str, format: &str) -> bool {
+    let extension = Path::new(file_name).extension().and_then(|e| e.to_str());
+    match format {
+        "csvlog" => extension == Some("csv"),
+        "jsonlog" => extension == Some("json"),
+        _ => !matches!(extension, Some("csv" | "json")),
+    }
+}
+
+/// Calls `f` for every complete line of the file starting at byte `offset`.
+/// Returns the offset after the last complete line, i.e. where to continue
+/// reading once Postgres wrote more.
+fn read_lines(path: &Path, offset: u64, mut f: impl FnMut(String)) -> Result<u64> {
+    let mut file = File::open(path)
+        .with_context(|| format!("failed to open '{}'", path.display()))?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut reader = BufReader::new(file);
+    let mut offset = offset;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)
+            .with_context(|| format!("failed to read '{}'", path.display()))?;
+        if read == 0 || buf.last() != Some(&b'\n') {
+            return Ok(offset);
+        }
+        offset += read as u64;
+
+        let line = String::from_utf8_lossy(&buf);
+        f(line.trim_end_matches(['\n', '\r']).to_owned());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{choose_format, is_in_format};
+
+    #[test]
+    fn chooses_file_based_format() {
+        assert_eq!(choose_format("stderr"), Some("stderr"));
+        assert_eq!(choose_format("csvlog, stderr"), Some("stderr"));
+        assert_eq!(choose_format("syslog,jsonlog"), Some("jsonlog"));
+        assert_eq!(choose_format("syslog"), None);
+
+        assert!(is_in_format("postgresql-2023-05-01_000000.log", "stderr"));
+        assert!(is_in_format("postgresql-Mon", "stderr"));
+        assert!(!is_in_format("postgresql-2023-05-01_000000.csv", "stderr"));
+        assert!(is_in_format("postgresql-2023-05-01_000000.csv", "csvlog"));
+        assert!(!is_in_format("postgresql-2023-05-01_000000.log", "jsonlog"));
+    }
+}
@@ -0,0 +1,80 @@
+//! The `db pg-logical-slot-create` and `db pg-logical-slot-drop` subcommands.
+
+use crate::{prelude::*, db::Db};
+use super::{disk_projection::human_bytes, summary::Summary};
+
+
+/// Implementation of `db pg-logical-slot-create`, see that for docs.
+pub(crate) async fn create(db: &Db, name: &str, plugin: &str) -> Result<Summary> {
+    check_wal_level(db).await?;
+
+    let row = db
+        .query_one(
+            "select slot_name::text, lsn::text \
+                from pg_create_logical_replication_slot($1, $2)",
+            &[&name, &plugin],
+        )
+        .await
+        .with_context(|| format!("failed to create logical replication slot '{name}' \
+            (this requires the replication privilege)"))?;
+
+    Ok(Summary::PgLogicalSlotCreate {
+        slot: row.get(0),
+        plugin: plugin.to_owned(),
+        confirmed_lsn: row.get(1),
+    })
+}
+
+/// Implementation of `db pg-logical-slot-drop`, see that for docs.
+pub(crate) async fn drop(db: &Db, name: &str) -> Result<Summary> {
+    check_wal_level(db).await?;
+
+    let row = db
+        .query_opt(
+            "select slot_type, active, \
+                    pg_wal_lsn_diff(pg_current_wal_lsn(), confirmed_flush_lsn)::int8 \
+                from pg_replication_slots \
+                where slot_name = $1 and database = current_database()",
+            &[&name],
+        )
+        .await
+        .context("failed to query replication slots")?
+        .ok_or_else(|| anyhow!("replication slot '{name}' does not exist in this database"))?;
+
+    if row.get::<_, String>(0) != "logical" {
+        bail!("replication slot '{name}' is not a logical slot");
+    }
+    if row.get::<_, bool>(1) {
+        bail!("replication slot '{name}' is still in use by a consumer: stop it first");
+    }
+
+    let unconsumed_bytes = row.get::<_, Option<i64>>(2).unwrap_or(0).max(0);
+    if unconsumed_bytes > 0 {
+        warn!(
+            "Replication slot '{name}' has ~{} of WAL that was not consumed yet. \
+                These changes are lost for the consumer of this slot.",
+            human_bytes(unconsumed_bytes as f64),
+        );
+    }
+
+    db.execute("select pg_drop_replication_slot($1)", &[&name])
+        .await
+        .with_context(|| format!("failed to drop replication slot '{name}'"))?;
+
+    Ok(Summary::PgLogicalSlotDrop {
+        slot: name.to_owned(),
+        unconsumed_bytes: unconsumed_bytes as u64,
+    })
+}
+
+async fn check_wal_level(db: &Db) -> Result<()> {
+    let wal_level = db.query_one("select current_setting('wal_level')", &[])
+        .await
+        .context("failed to query `wal_level`")?
+        .get::<_, String>(0);
+    if wal_level != "logical" {
+        bail!("`wal_level` is '{wal_level}', but has to be 'logical' for logical replication \
+            (changing it requires a restart of PostgreSQL)");
+    }
+    Ok(())
+}
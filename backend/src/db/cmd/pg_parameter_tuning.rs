@@ -0,0 +1,245 @@
+//! The `db pg-parameter-tuning` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// Recommended value for a Postgres parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    /// Amount of memory in kB, which is the base unit of most memory
+    /// parameters.
+    Memory(u64),
+    Number(f64),
+}
+
+impl Value {
+    /// Formats the value the way Postgres accepts it in `alter system`.
+    fn to_sql(self) -> String {
+        match self {
+            Value::Memory(kb) if kb % (1024 * 1024) == 0 => format!("'{}GB'", kb / (1024 * 1024)),
+            Value::Memory(kb) if kb % 1024 == 0 => format!("'{}MB'", kb / 1024),
+            Value::Memory(kb) => format!("'{kb}kB'"),
+            Value::Number(n) => n.to_string(),
+        }
+    }
+}
+
+struct Recommendation {
+    name: &'static str,
+    value: Value,
+    reason: &'static str,
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    system_ram_gb: f64,
+    cpu_count: u32,
+    ssd: bool,
+    script: bool,
+) -> Result<()> {
+    if system_ram_gb.is_nan() || system_ram_gb <= 0.0 {
+        bail!("`--system-ram-gb` has to be positive");
+    }
+    if cpu_count == 0 {
+        bail!("`--cpu-count` has to be positive");
+    }
+
+    let max_connections = db.query_one("select current_setting('max_connections')::int", &[])
+        .await?
+        .get::<_, i32>(0);
+    let recommendations = recommend(system_ram_gb, cpu_count, ssd, max_connections);
+
+    if script {
+        println!("-- Recommended settings for {system_ram_gb} GB RAM, {cpu_count} CPUs, {}.",
+            if ssd { "SSD" } else { "HDD" });
+        println!("-- Apply with `tobira db script`, then restart Postgres.");
+        for r in &recommendations {
+            println!("alter system set {} = {}; -- {}", r.name, r.value.to_sql(), r.reason);
+        }
+        return Ok(());
+    }
+
+    let mut changes = 0;
+    for r in &recommendations {
+        let row = db
+            .query_one(
+                "select current_setting($1), setting, unit from pg_settings where name = $1",
+                &[&r.name],
+            )
+            .await
+            .with_context(|| format!("failed to read current value of '{}'", r.name))?;
+        let display: String = row.get(0);
+        let current = parse_setting(row.get(1), row.get(2));
+
+        if current == Some(r.value) {
+            bunt::println!("  {[bold]} = {} {$dimmed}(unchanged){/$}", r.name, display);
+        } else {
+            changes += 1;
+            let recommended = r.value.to_sql();
+            bunt::println!(
+                "{$yellow}~{/$} {[bold]} = {[red]} → {[green+intense]}",
+                r.name,
+                display,
+                recommended.trim_matches('\''),
+            );
+            bunt::println!("    {$dimmed}{}{/$}", r.reason);
+        }
+    }
+
+    println!();
+    if changes == 0 {
+        bunt::println!("{$green+intense}All settings already match the recommendations.{/$}");
+    } else {
+        println!("{changes} settings differ from the recommendations. Run again with `--script` \
+            to get an `alter system` script.");
+        println!("Note: these are starting points. Monitor your database and adjust as needed.");
+    }
+
+    Ok(())
+}
+
+/// Computes the recommended settings. Tobira's queries are mostly small,
+/// index-based reads, so a large cache is most important. `work_mem` is
+/// computed conservatively since each connection can use it multiple times.
+fn recommend(
+    system_ram_gb: f64,
+    cpu_count: u32,
+    ssd: bool,
+    max_connections: i32,
+) -> Vec<Recommendation> {
+    const MB: u64 = 1024;
+    const GB: u64 = 1024 * MB;
+
+    // Rounded down to full MB to get nice values.
+    let ram_kb = (system_ram_gb * GB as f64) as u64;
+    let round = |kb: u64| (kb / MB).max(1) * MB;
+
+    let shared_buffers = round(ram_kb / 4);
+    let work_mem = round((ram_kb - shared_buffers) / (3 * max_connections.max(1) as u64))
+        .clamp(4 * MB, 256 * MB);
+    let parallel_per_gather = (cpu_count / 2).clamp(1, 4);
+
+    vec![
+        Recommendation {
+            name: "shared_buffers",
+            value: Value::Memory(shared_buffers),
+            reason: "25% of RAM is the common recommendation for dedicated servers",
+        },
+        Recommendation {
+            name: "effective_cache_size",
+            value: Value::Memory(round(ram_kb * 3 / 4)),
+            reason: "estimate of memory available for caching, including the OS cache",
+        },
+        Recommendation {
+            name: "maintenance_work_mem",
+            value: Value::Memory(round(ram_kb / 16).min(2 * GB)),
+            reason: "speeds up `vacuum` and index creation (e.g. in migrations)",
+        },
+        Recommendation {
+            name: "work_mem",
+            value: Value::Memory(work_mem),
+            reason: "memory per sort/hash operation, with room for all connections",
+        },
+        Recommendation {
+            name: "wal_buffers",
+            value: Value::Memory(16 * MB),
+            reason: "the maximum useful value of the default (auto-tuned) setting",
+        },
+        Recommendation {
+            name: "checkpoint_completion_target",
+            value: Value::Number(0.9),
+            reason: "spreads checkpoint writes out to avoid I/O spikes",
+        },
+        Recommendation {
+            name: "random_page_cost",
+            value: Value::Number(if ssd { 1.1 } else { 4.0 }),
+            reason: if ssd {
+                "random reads are almost as cheap as sequential ones on SSDs"
+            } else {
+                "random reads are expensive on HDDs"
+            },
+        },
+        Recommendation {
+            name: "effective_io_concurrency",
+            value: Value::Number(if ssd { 200.0 } else { 2.0 }),
+            reason: "number of concurrent I/O operations the storage handles well",
+        },
+        Recommendation {
+            name: "max_worker_processes",
+            value: Value::Number(cpu_count.max(8).into()),
+            reason: "one per CPU, but at least the default",
+        },
+        Recommendation {
+            name: "max_parallel_workers",
+            value: Value::Number(cpu_count.into()),
+            reason: "one per CPU",
+        },
+        Recommendation {
+            name: "max_parallel_workers_per_gather",
+            value: Value::Number(parallel_per_gather.into()),
+            reason: "Tobira's queries are small, so few workers per query suffice",
+        },
+        Recommendation {
+            name: "max_parallel_maintenance_workers",
+            value: Value::Number(parallel_per_gather.into()),
+            reason: "speeds up index creation",
+        },
+    ]
+}
+
+/// Parses a value from `pg_settings` into a `Value` comparable with the
+/// recommendations. Returns `None` for unknown units.
+fn parse_setting(setting: String, unit: Option<String>) -> Option<Value> {
+    let factor_kb = match unit.as_deref() {
+        None => return setting.parse().ok().map(Value::Number),
+        Some("B") => return setting.parse::<u64>().ok().map(|b| Value::Memory(b / 1024)),
+        Some("kB") => 1,
+        Some("8kB") => 8,
+        Some("MB") => 1024,
+        Some(_) => return None,
+    };
+
+    setting.parse::<u64>().ok().map(|n| Value::Memory(n * factor_kb))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_setting, recommend, Value};
+
+    fn value(recs: &[super::Recommendation], name: &str) -> Value {
+        recs.iter().find(|r| r.name == name).unwrap().value
+    }
+
+    #[test]
+    fn recommendations_for_medium_server() {
+        let recs = recommend(16.0, 8, true, 100);
+        assert_eq!(value(&recs, "shared_buffers").to_sql(), "'4GB'");
+        assert_eq!(value(&recs, "effective_cache_size").to_sql(), "'12GB'");
+        assert_eq!(value(&recs, "maintenance_work_mem").to_sql(), "'1GB'");
+        assert_eq!(value(&recs, "work_mem").to_sql(), "'40MB'");
+        assert_eq!(value(&recs, "random_page_cost").to_sql(), "1.1");
+        assert_eq!(value(&recs, "max_parallel_workers_per_gather").to_sql(), "4");
+    }
+
+    #[test]
+    fn recommendations_for_small_server() {
+        let recs = recommend(1.0, 1, false, 100);
+        assert_eq!(value(&recs, "shared_buffers").to_sql(), "'256MB'");
+        assert_eq!(value(&recs, "work_mem").to_sql(), "'4MB'");
+        assert_eq!(value(&recs, "random_page_cost").to_sql(), "4");
+        assert_eq!(value(&recs, "max_worker_processes").to_sql(), "8");
+        assert_eq!(value(&recs, "max_parallel_workers_per_gather").to_sql(), "1");
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert_eq!(parse_setting("16384".into(), Some("8kB".into())), Some(Value::Memory(131072)));
+        assert_eq!(parse_setting("4096".into(), Some("kB".into())), Some(Value::Memory(4096)));
+        assert_eq!(parse_setting("64".into(), Some("MB".into())), Some(Value::Memory(65536)));
+        assert_eq!(parse_setting("0.9".into(), None), Some(Value::Number(0.9)));
+        assert_eq!(parse_setting("200".into(), Some("ms".into())), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Point-in-time recovery for `db restore --point-in-time`.
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::Command,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{prelude::*, util::Never};
+use super::fork_command;
+
+
+/// Restores the physical base backup `base_backup` into `data_dir`, configures
+/// Postgres to replay WAL from `wal_archive` up to `target` and starts it via
+/// `pg_ctl`. Has to be run on the database server as the OS user owning
+/// Postgres' data directories.
+pub(super) fn run(
+    base_backup: &Path,
+    data_dir: &Path,
+    wal_archive: &Path,
+    target: DateTime<Utc>,
+) -> Result<Never> {
+    let version = fs::read_to_string(base_backup.join("PG_VERSION"))
+        .with_context(|| format!(
+            "'{}' is not a base backup created by `pg_basebackup` (no `PG_VERSION` file). \
+                Note: dumps created by `db dump` cannot be used for point-in-time recovery.",
+            base_backup.display(),
+        ))?;
+    let major = parse_major_version(&version)
+        .ok_or_else(|| anyhow!("invalid `PG_VERSION` in base backup: '{}'", version.trim()))?;
+
+    if !wal_archive.is_dir() {
+        bail!("WAL archive '{}' is not a directory", wal_archive.display());
+    }
+    let is_empty = match fs::read_dir(data_dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(e) => return Err(e)
+            .with_context(|| format!("failed to read '{}'", data_dir.display())),
+    };
+    if !is_empty {
+        bail!("data directory '{}' is not empty: refusing to overwrite it (make sure \
+            Postgres is stopped and move the old directory away)", data_dir.display());
+    }
+
+    info!("Copying base backup (Postgres {major}) to '{}'...", data_dir.display());
+    copy_dir(base_backup, data_dir)?;
+    // Postgres refuses to start if the data directory is accessible by others.
+    fs::set_permissions(data_dir, fs::Permissions::from_mode(0o700))?;
+
+    // Postgres 12 replaced `recovery.conf` by normal config parameters plus a
+    // `recovery.signal` file.
+    let settings = recovery_settings(wal_archive, target);
+    if major >= 12 {
+        let auto_conf = data_dir.join("postgresql.auto.conf");
+        let mut content = fs::read_to_string(&auto_conf).unwrap_or_default();
+        content.push_str("\n# Added by `tobira db restore --point-in-time`. Remove after \
+            recovery has finished.\n");
+        content.push_str(&settings);
+        fs::write(&auto_conf, content)
+            .with_context(|| format!("failed to write '{}'", auto_conf.display()))?;
+        fs::write(data_dir.join("recovery.signal"), "")
+            .context("failed to create `recovery.signal`")?;
+    } else {
+        fs::write(data_dir.join("recovery.conf"), settings)
+            .context("failed to write `recovery.conf`")?;
+    }
+
+    let log = data_dir.join("recovery.log");
+    info!("Starting Postgres in recovery mode, replaying WAL up to {target}. \
+        Postgres' log is written to '{}'.", log.display());
+    println!("Once Postgres has reached the target time, it ends recovery and accepts \
+        writes again. Afterwards, remove the recovery settings (`recovery_target_*` and \
+        `restore_command`) from the configuration.");
+    fork_command(
+        Command::new("pg_ctl")
+            .arg("--pgdata")
+            .arg(data_dir)
+            .arg("--log")
+            .arg(log)
+            .arg("start")
+    )
+}
+
+/// Returns the recovery parameters in `postgresql.conf` syntax.
+fn recovery_settings(wal_archive: &Path, target: DateTime<Utc>) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let archive = wal_archive.display().to_string();
+    let restore_command = format!("cp \"{}/%f\" \"%p\"", archive.trim_end_matches('/'));
+
+    format!(
+        "restore_command = {}\nrecovery_target_time = {}\nrecovery_target_action = 'promote'\n",
+        quote(&restore_command),
+        quote(&target.format("%Y-%m-%d %H:%M:%S%.f+00").to_string()),
+    )
+}
+
+/// Parses the content of a `PG_VERSION` file, e.g. `14` or `9.6`.
+fn parse_major_version(version: &str) -> Option<u32> {
+    let version = version.trim();
+    match version.split_once('.') {
+        // Before Postgres 10, the major version consisted of two numbers.
+        Some(("9", _)) => Some(9),
+        Some(_) => None,
+        None => version.parse().ok(),
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("failed to create '{}'", to.display()))?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("failed to copy '{}'", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use chrono::{DateTime, Utc};
+
+    use super::{parse_major_version, recovery_settings};
+
+    #[test]
+    fn settings() {
+        let target: DateTime<Utc> = "2022-08-01T14:30:05.25+02:00".parse().unwrap();
+        assert_eq!(
+            recovery_settings(Path::new("/var/lib/wal's/"), target),
+            "restore_command = 'cp \"/var/lib/wal''s/%f\" \"%p\"'\n\
+                recovery_target_time = '2022-08-01 12:30:05.250+00'\n\
+                recovery_target_action = 'promote'\n",
+        );
+    }
+
+    #[test]
+    fn major_versions() {
+        assert_eq!(parse_major_version("14\n"), Some(14));
+        assert_eq!(parse_major_version("9.6\n"), Some(9));
+        assert_eq!(parse_major_version("10.1"), None);
+        assert_eq!(parse_major_version("foo"), None);
+    }
+}
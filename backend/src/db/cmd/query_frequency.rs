@@ -0,0 +1,97 @@
+//! The `db query-frequency` subcommand.
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::{prelude::*, db::Db};
+
+
+/// Maximum number of characters of a query that are printed.
+const MAX_QUERY_LEN: usize = 200;
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, top: usize, window_secs: u64) -> Result<()> {
+    if window_secs == 0 {
+        bail!("window has to be at least one second");
+    }
+
+    let has_extension = db
+        .query_opt("select from pg_extension where extname = 'pg_stat_statements'", &[])
+        .await
+        .context("failed to check for extension 'pg_stat_statements'")?
+        .is_some();
+    if !has_extension {
+        bail!("extension 'pg_stat_statements' is not installed: add it to \
+            `shared_preload_libraries` and run `create extension pg_stat_statements`");
+    }
+
+    info!("Sampling 'pg_stat_statements' over {window_secs}s...");
+    let before = sample(db).await?;
+    tokio::time::sleep(Duration::from_secs(window_secs)).await;
+    let after = sample(db).await?;
+
+    // Statements that were evicted from `pg_stat_statements` in between are
+    // ignored. If a statement's counter was reset, all its calls are new.
+    let mut rates = after.into_iter()
+        .filter_map(|(key, (calls, query))| {
+            let new_calls = match before.get(&key) {
+                Some((old_calls, _)) if *old_calls <= calls => calls - old_calls,
+                _ => calls,
+            };
+            (new_calls > 0).then_some((new_calls, query))
+        })
+        .collect::<Vec<_>>();
+    rates.sort_by_key(|(calls, _)| std::cmp::Reverse(*calls));
+
+    if rates.is_empty() {
+        println!("No queries were executed in the last {window_secs}s.");
+        return Ok(());
+    }
+
+    bunt::println!(
+        "{$bold}Top {} of {} queries by calls per second (over {}s):{/$}",
+        top.min(rates.len()),
+        rates.len(),
+        window_secs,
+    );
+    println!();
+    for (calls, query) in rates.iter().take(top) {
+        let per_sec = *calls as f64 / window_secs as f64;
+        bunt::println!(
+            " - {[bold+intense]} {$dimmed}({} calls, {}/min){/$}",
+            format!("{per_sec:.2}/s"),
+            calls,
+            format!("{:.0}", per_sec * 60.0),
+        );
+        bunt::println!("   {[dimmed]}", shorten(query));
+    }
+
+    Ok(())
+}
+
+/// Returns `calls` and the query text of all statements in the current
+/// database, keyed by user and query ID.
+async fn sample(db: &Db) -> Result<HashMap<(u32, i64), (i64, String)>> {
+    let rows = db
+        .query(
+            "select userid, queryid, calls, query \
+                from pg_stat_statements \
+                where dbid = (select oid from pg_database where datname = current_database()) \
+                    and queryid is not null",
+            &[],
+        )
+        .await
+        .context("failed to query 'pg_stat_statements'")?;
+
+    Ok(rows.into_iter()
+        .map(|row| ((row.get(0), row.get(1)), (row.get(2), row.get(3))))
+        .collect())
+}
+
+/// Puts the query on a single line and cuts it off if it's too long.
+fn shorten(query: &str) -> String {
+    let single_line = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    match single_line.char_indices().nth(MAX_QUERY_LEN) {
+        Some((idx, _)) => format!("{}…", &single_line[..idx]),
+        None => single_line,
+    }
+}
@@ -0,0 +1,211 @@
+//! The `db query-heatmap` subcommand.
+
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+
+use crate::{prelude::*, db::{Db, query}};
+
+
+/// Name of the table containing the snapshots. Besides the columns described
+/// in `disk_projection`, this command expects:
+///
+/// - `query_count bigint`: cumulative number of executed statements at the
+///   time of the snapshot (e.g. `sum(calls)` of `pg_stat_statements` or
+///   `xact_commit + xact_rollback` of `pg_stat_database`). All rows of one
+///   snapshot have the same value.
+const TABLE: &str = "__tobira_stats_history";
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Number of different colors of the heatmap cells.
+const LEVELS: usize = 5;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, days: u32) -> Result<()> {
+    if days == 0 {
+        bail!("number of days has to be at least 1");
+    }
+    if !query::does_table_exist(&**db, TABLE).await? {
+        bail!("table '{TABLE}' does not exist: no statistics have been recorded");
+    }
+    let has_column = db
+        .query_opt(
+            "select from information_schema.columns \
+                where table_schema = 'public' and table_name = $1 and column_name = 'query_count'",
+            &[&TABLE],
+        )
+        .await?
+        .is_some();
+    if !has_column {
+        bail!("table '{TABLE}' has no column 'query_count': no query counts have been recorded");
+    }
+
+    let snapshots = db
+        .query(
+            &format!("select captured_at, max(query_count) from {TABLE} \
+                where captured_at > now() - make_interval(days => $1) \
+                    and query_count is not null \
+                group by captured_at \
+                order by captured_at"),
+            &[&(days as i32)],
+        )
+        .await
+        .with_context(|| format!("failed to query '{TABLE}'"))?
+        .into_iter()
+        .map(|row| (row.get::<_, DateTime<Utc>>(0), row.get::<_, i64>(1)))
+        .collect::<Vec<_>>();
+
+    let mut heatmap = Heatmap::default();
+    for pair in snapshots.windows(2) {
+        let ((start, before), (end, after)) = (pair[0], pair[1]);
+        // A smaller count means the statistics were reset in between. We
+        // don't know how many queries were executed then.
+        if after >= before {
+            heatmap.add(start, end, (after - before) as f64);
+        }
+    }
+
+    if heatmap.is_empty() {
+        bail!("not enough snapshots with query counts in the last {days} days \
+            (at least two are required)");
+    }
+
+    heatmap.print(days);
+    Ok(())
+}
+
+/// Queries per hour of the week, accumulated over all intervals between
+/// snapshots. Indexed by weekday (Monday = 0) and hour, both in UTC.
+#[derive(Debug, Default)]
+struct Heatmap {
+    queries: [[f64; 24]; 7],
+    /// How many hours of observations each cell is based on.
+    hours: [[f64; 24]; 7],
+}
+
+impl Heatmap {
+    /// Distributes the queries executed between `start` and `end` evenly over
+    /// the hours in that interval.
+    fn add(&mut self, start: DateTime<Utc>, end: DateTime<Utc>, queries: f64) {
+        let total = (end - start).num_seconds() as f64;
+        if total <= 0.0 {
+            return;
+        }
+
+        let mut t = start;
+        while t < end {
+            let next_hour = t.date().and_hms(t.hour(), 0, 0) + Duration::hours(1);
+            let segment_end = next_hour.min(end);
+            let secs = (segment_end - t).num_seconds() as f64;
+
+            let (day, hour) = (t.weekday().num_days_from_monday() as usize, t.hour() as usize);
+            self.queries[day][hour] += queries * secs / total;
+            self.hours[day][hour] += secs / 3600.0;
+            t = segment_end;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.hours.iter().flatten().all(|h| *h == 0.0)
+    }
+
+    /// Returns the average number of queries per hour, or `None` if there
+    /// are no observations for that cell.
+    fn rate(&self, day: usize, hour: usize) -> Option<f64> {
+        let hours = self.hours[day][hour];
+        (hours > 0.0).then(|| self.queries[day][hour] / hours)
+    }
+
+    fn max_rate(&self) -> f64 {
+        (0..7)
+            .flat_map(|day| (0..24).filter_map(move |hour| self.rate(day, hour)))
+            .fold(0.0, f64::max)
+    }
+
+    /// Returns the color level (`0..LEVELS`) of the given rate.
+    fn level(rate: f64, max: f64) -> usize {
+        if max == 0.0 {
+            return 0;
+        }
+        ((rate / max * LEVELS as f64) as usize).min(LEVELS - 1)
+    }
+
+    fn print(&self, days: u32) {
+        let max = self.max_rate();
+
+        bunt::println!("{$bold}Queries per hour over the last {} days (UTC):{/$}", days);
+        println!();
+        print!("     ");
+        for hour in 0..24 {
+            print!("{hour:<3}");
+        }
+        println!();
+
+        for (day, name) in WEEKDAYS.iter().enumerate() {
+            print!("{name}  ");
+            for hour in 0..24 {
+                match self.rate(day, hour) {
+                    None => bunt::print!("{$dimmed}·  {/$}"),
+                    Some(rate) => print_cell(Self::level(rate, max), "██ "),
+                }
+            }
+            println!();
+        }
+
+        println!();
+        print!("     ");
+        for level in 0..LEVELS {
+            let from = max * level as f64 / LEVELS as f64;
+            print_cell(level, "██");
+            print!(" ≥{from:.0}  ");
+        }
+        println!();
+        bunt::println!("{$dimmed}· = no snapshots for that hour{/$}");
+    }
+}
+
+fn print_cell(level: usize, s: &str) {
+    match level {
+        0 => bunt::print!("{[dimmed]}", s),
+        1 => bunt::print!("{[blue]}", s),
+        2 => bunt::print!("{[green]}", s),
+        3 => bunt::print!("{[yellow]}", s),
+        _ => bunt::print!("{[red+intense]}", s),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use super::Heatmap;
+
+    #[test]
+    fn distributes_queries_over_hours() {
+        // 2024-01-01 is a Monday.
+        let start = Utc.ymd(2024, 1, 1).and_hms(10, 30, 0);
+        let end = Utc.ymd(2024, 1, 1).and_hms(12, 30, 0);
+
+        let mut heatmap = Heatmap::default();
+        heatmap.add(start, end, 400.0);
+        assert_eq!(heatmap.rate(0, 9), None);
+        assert_eq!(heatmap.rate(0, 10), Some(200.0));
+        assert_eq!(heatmap.rate(0, 11), Some(200.0));
+        assert_eq!(heatmap.rate(0, 12), Some(200.0));
+        assert_eq!(heatmap.queries[0][11], 200.0);
+        assert_eq!(heatmap.hours[0][12], 0.5);
+
+        // Across midnight from Sunday to Monday.
+        let mut heatmap = Heatmap::default();
+        let sunday_night = Utc.ymd(2024, 1, 7).and_hms(23, 0, 0);
+        heatmap.add(sunday_night, Utc.ymd(2024, 1, 8).and_hms(1, 0, 0), 0.0);
+        assert_eq!(heatmap.rate(6, 23), Some(0.0));
+        assert_eq!(heatmap.rate(0, 0), Some(0.0));
+        assert_eq!(heatmap.rate(0, 1), None);
+        assert_eq!(heatmap.max_rate(), 0.0);
+
+        assert_eq!(Heatmap::level(0.0, 100.0), 0);
+        assert_eq!(Heatmap::level(50.0, 100.0), 2);
+        assert_eq!(Heatmap::level(100.0, 100.0), 4);
+    }
+}
@@ -0,0 +1,255 @@
+//! The `db query-plan-regression-test` subcommand.
+
+use std::path::Path;
+use serde_json::Value;
+
+use crate::{prelude::*, db::{Db, MigrateOptions, MigrationPlan}};
+use super::migration_annotate_performance::split_statements;
+
+
+/// Relative change of the estimated total cost that is reported.
+const COST_CHANGE_THRESHOLD: f64 = 0.1;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &mut Db, queries_file: &Path) -> Result<()> {
+    let script = std::fs::read_to_string(queries_file)
+        .with_context(|| format!("failed to read '{}'", queries_file.display()))?;
+    let queries = split_statements(&script)
+        .into_iter()
+        .map(|range| {
+            let line = script[..range.start].matches('\n').count() + 1;
+            (line, script[range].trim_end_matches(';').to_owned())
+        })
+        .collect::<Vec<_>>();
+    if queries.is_empty() {
+        bail!("'{}' does not contain any queries", queries_file.display());
+    }
+
+    let tx = db.build_transaction().read_only(true).start().await?;
+    let pending = MigrationPlan::build(&tx).await?.pending_migrations();
+    tx.commit().await?;
+    if pending == 0 {
+        bail!("there are no pending migrations, so the query plans cannot change");
+    }
+
+    let before = explain_all(db, &queries).await;
+    let applied = crate::db::migrate(db, &MigrateOptions::default()).await?;
+    info!("Applied {applied} migrations");
+    let after = explain_all(db, &queries).await;
+
+    let mut regressions = 0;
+    for (((line, query), before), after) in queries.iter().zip(before).zip(after) {
+        let first_line = query.lines().next().unwrap_or_default();
+        bunt::println!("{$bold}Query at line {}:{/$} {[dimmed]}", line, first_line);
+
+        let (before, after) = match (before, after) {
+            (Ok(before), Ok(after)) => (before, after),
+            (before, after) => {
+                for (label, result) in [("before", before), ("after", after)] {
+                    if let Err(e) = result {
+                        bunt::println!("  {$red}Failed to explain {}:{/$} {}", label, e);
+                    }
+                }
+                println!();
+                continue;
+            }
+        };
+
+        let changes = compare(&before, &after);
+        if changes.is_empty() {
+            bunt::println!("  {$green}Unchanged{/$} (cost {:.2})", after.total_cost);
+        }
+        for change in &changes {
+            match change {
+                Change::Cost { before, after } if after > before => {
+                    regressions += 1;
+                    bunt::println!("  {$red+bold}Cost increased:{/$} {:.2} → {:.2} ({:+.0}%)",
+                        before, after, (after / before - 1.0) * 100.0);
+                }
+                Change::Cost { before, after } => {
+                    bunt::println!("  {$green}Cost decreased:{/$} {:.2} → {:.2} ({:+.0}%)",
+                        before, after, (after / before - 1.0) * 100.0);
+                }
+                Change::Nodes { removed, added } => {
+                    bunt::println!("  {$yellow+bold}Plan changed:{/$}");
+                    for node in removed {
+                        bunt::println!("    {$red}- {}{/$}", node);
+                    }
+                    for node in added {
+                        bunt::println!("    {$green}+ {}{/$}", node);
+                    }
+                }
+                Change::JoinOrder { before, after } => {
+                    bunt::println!("  {$yellow+bold}Join order changed:{/$} {} → {}",
+                        before.join(", "), after.join(", "));
+                }
+            }
+        }
+        println!();
+    }
+
+    if regressions > 0 {
+        bail!("the estimated cost of {regressions} queries increased by more than {:.0}%",
+            COST_CHANGE_THRESHOLD * 100.0);
+    }
+    Ok(())
+}
+
+/// Explains all queries without executing them. Errors are returned per
+/// query, as queries might only work before or after the migration.
+async fn explain_all(db: &Db, queries: &[(usize, String)]) -> Vec<Result<PlanSummary, String>> {
+    let mut out = Vec::new();
+    for (_, query) in queries {
+        let result = db.query_one(&format!("explain (format json) {query}"), &[]).await
+            .map(|row| summarize(&row.get::<_, Value>(0)))
+            .map_err(|e| e.as_db_error().map_or(e.to_string(), |e| e.message().to_owned()));
+        out.push(result);
+    }
+    out
+}
+
+/// The aspects of a query plan that are compared.
+#[derive(Debug)]
+struct PlanSummary {
+    total_cost: f64,
+    /// All nodes in pre-order, e.g. `Index Scan using events_pkey on events`.
+    nodes: Vec<String>,
+    /// Scanned relations in the order in which they appear in the plan,
+    /// which (for nested joins) is the join order.
+    relations: Vec<String>,
+}
+
+/// Summarizes the output of `explain (format json)`.
+fn summarize(explain: &Value) -> PlanSummary {
+    fn visit(plan: &Value, out: &mut PlanSummary) {
+        let mut desc = plan["Node Type"].as_str().unwrap_or("?").to_owned();
+        if let Some(index) = plan["Index Name"].as_str() {
+            desc += &format!(" using {index}");
+        }
+        if let Some(relation) = plan["Relation Name"].as_str() {
+            desc += &format!(" on {relation}");
+            out.relations.push(relation.to_owned());
+        }
+        out.nodes.push(desc);
+
+        for child in plan["Plans"].as_array().into_iter().flatten() {
+            visit(child, out);
+        }
+    }
+
+    let plan = &explain[0]["Plan"];
+    let mut out = PlanSummary {
+        total_cost: plan["Total Cost"].as_f64().unwrap_or(0.0),
+        nodes: Vec::new(),
+        relations: Vec::new(),
+    };
+    visit(plan, &mut out);
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Cost { before: f64, after: f64 },
+    /// Nodes that only exist before or after, e.g. when a seq scan became an
+    /// index scan.
+    Nodes { removed: Vec<String>, added: Vec<String> },
+    JoinOrder { before: Vec<String>, after: Vec<String> },
+}
+
+fn compare(before: &PlanSummary, after: &PlanSummary) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let relative = (after.total_cost - before.total_cost).abs() / before.total_cost.max(1.0);
+    if relative > COST_CHANGE_THRESHOLD {
+        changes.push(Change::Cost { before: before.total_cost, after: after.total_cost });
+    }
+
+    // Multiset difference, so that the order does not matter here.
+    let difference = |a: &[String], b: &[String]| {
+        let mut rest = b.to_vec();
+        a.iter()
+            .filter(|node| match rest.iter().position(|n| n == *node) {
+                Some(i) => {
+                    rest.remove(i);
+                    false
+                }
+                None => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let removed = difference(&before.nodes, &after.nodes);
+    let added = difference(&after.nodes, &before.nodes);
+    if !removed.is_empty() || !added.is_empty() {
+        changes.push(Change::Nodes { removed, added });
+    }
+
+    // Only reported if the same relations are scanned, otherwise the node
+    // changes already say it all.
+    let mut sorted_before = before.relations.clone();
+    let mut sorted_after = after.relations.clone();
+    sorted_before.sort();
+    sorted_after.sort();
+    if before.relations != after.relations && sorted_before == sorted_after {
+        changes.push(Change::JoinOrder {
+            before: before.relations.clone(),
+            after: after.relations.clone(),
+        });
+    }
+
+    changes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::{compare, summarize, Change};
+
+    fn join(outer: &str, inner: serde_json::Value, cost: f64) -> serde_json::Value {
+        json!([{ "Plan": {
+            "Node Type": "Nested Loop",
+            "Total Cost": cost,
+            "Plans": [
+                { "Node Type": "Seq Scan", "Relation Name": outer, "Total Cost": 1.0 },
+                inner,
+            ],
+        }}])
+    }
+
+    #[test]
+    fn detects_index_scan_and_cost() {
+        let seq = json!({ "Node Type": "Seq Scan", "Relation Name": "events" });
+        let idx = json!({
+            "Node Type": "Index Scan",
+            "Index Name": "idx_events_series",
+            "Relation Name": "events",
+        });
+        let before = summarize(&join("series", seq, 100.0));
+        let after = summarize(&join("series", idx, 20.0));
+        assert_eq!(before.nodes, ["Nested Loop", "Seq Scan on series", "Seq Scan on events"]);
+
+        assert_eq!(compare(&before, &after), [
+            Change::Cost { before: 100.0, after: 20.0 },
+            Change::Nodes {
+                removed: vec!["Seq Scan on events".into()],
+                added: vec!["Index Scan using idx_events_series on events".into()],
+            },
+        ]);
+        assert!(compare(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn detects_join_order() {
+        let events = json!({ "Node Type": "Seq Scan", "Relation Name": "events" });
+        let series = json!({ "Node Type": "Seq Scan", "Relation Name": "series" });
+        let before = summarize(&join("series", events, 100.0));
+        let after = summarize(&join("events", series, 105.0));
+
+        assert_eq!(compare(&before, &after), [Change::JoinOrder {
+            before: vec!["series".into(), "events".into()],
+            after: vec!["events".into(), "series".into()],
+        }]);
+    }
+}
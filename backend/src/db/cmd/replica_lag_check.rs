@@ -0,0 +1,130 @@
+//! The `db replica-lag-check` subcommand.
+
+use crate::{prelude::*, db::{create_pool, DbConfig}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    config: &DbConfig,
+    host: Option<&str>,
+    port: Option<u16>,
+    max_lag_bytes: u64,
+    max_lag_secs: u64,
+) -> Result<()> {
+    let config = DbConfig {
+        host: host.map_or_else(|| config.host.clone(), ToOwned::to_owned),
+        port: port.unwrap_or(config.port),
+        ..config.clone()
+    };
+    let pool = create_pool(&config).await
+        .with_context(|| format!("failed to connect to replica '{}'", config.host))?;
+    let db = pool.get().await?;
+
+    let row = db
+        .query_one(
+            "select \
+                pg_is_in_recovery(), \
+                pg_wal_lsn_diff(pg_last_wal_receive_lsn(), pg_last_wal_replay_lsn())::float8, \
+                pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn(), \
+                extract(epoch from now() - pg_last_xact_replay_timestamp())::float8",
+            &[],
+        )
+        .await
+        .context("failed to query replication status")?;
+    if !row.get::<_, bool>(0) {
+        bail!("'{}:{}' is not a replica (not in recovery)", config.host, config.port);
+    }
+    let lag = Lag {
+        // `null` if WAL is not streamed, but only restored from an archive.
+        bytes: row.get::<_, Option<f64>>(1).map(|b| b.max(0.0) as u64),
+        caught_up: row.get::<_, Option<bool>>(2).unwrap_or(false),
+        secs: row.get(3),
+    };
+
+    let format_lag = |v: Option<String>| v.unwrap_or_else(|| "unknown".into());
+    bunt::println!("{$bold}Replication lag of {}:{}:{/$}", config.host, config.port);
+    bunt::println!(
+        " - {$dimmed}received, not replayed:{/$} {} (max {} bytes)",
+        format_lag(lag.bytes.map(|b| format!("{b} bytes"))),
+        max_lag_bytes,
+    );
+    bunt::println!(
+        " - {$dimmed}last replayed transaction:{/$} {} (max {}s)",
+        format_lag(lag.effective_secs().map(|s| format!("{s:.1}s ago"))),
+        max_lag_secs,
+    );
+    println!();
+
+    let problems = lag.problems(max_lag_bytes, max_lag_secs);
+    if problems.is_empty() {
+        bunt::println!("{$green+intense}Replica lag is within the limits.{/$}");
+        return Ok(());
+    }
+    for problem in &problems {
+        bunt::println!("{$red+bold}Error:{/$} {}", problem);
+    }
+    bail!("replica lags behind, a backup taken from it might be stale");
+}
+
+/// Replication lag as reported by the replica.
+struct Lag {
+    /// WAL received from the primary but not replayed yet.
+    bytes: Option<u64>,
+    /// Whether all received WAL has been replayed.
+    caught_up: bool,
+    /// Age of the last replayed transaction.
+    secs: Option<f64>,
+}
+
+impl Lag {
+    /// The time lag. If everything received has been replayed, there is no
+    /// lag, even if the last transaction is old: the primary might just not
+    /// have written anything since.
+    fn effective_secs(&self) -> Option<f64> {
+        match self.caught_up {
+            true => Some(0.0),
+            false => self.secs.map(|s| s.max(0.0)),
+        }
+    }
+
+    fn problems(&self, max_bytes: u64, max_secs: u64) -> Vec<String> {
+        let mut out = Vec::new();
+        match self.bytes {
+            Some(bytes) if bytes > max_bytes => out.push(format!(
+                "{bytes} bytes of WAL are not replayed yet (max {max_bytes})",
+            )),
+            _ => {}
+        }
+        match self.effective_secs() {
+            Some(secs) if secs > max_secs as f64 => out.push(format!(
+                "last replayed transaction is {secs:.0}s old (max {max_secs}s)",
+            )),
+            Some(_) => {}
+            // Nothing was replayed since the replica started, so its state is
+            // unknown.
+            None => out.push("no transaction was replayed yet".into()),
+        }
+        out
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Lag;
+
+    #[test]
+    fn problems() {
+        let mut lag = Lag { bytes: Some(100), caught_up: false, secs: Some(5.0) };
+        assert!(lag.problems(1000, 60).is_empty());
+        assert_eq!(lag.problems(50, 60).len(), 1);
+        assert_eq!(lag.problems(50, 1).len(), 2);
+
+        // An idle primary does not cause lag.
+        lag = Lag { bytes: Some(0), caught_up: true, secs: Some(3600.0) };
+        assert!(lag.problems(1000, 60).is_empty());
+
+        lag = Lag { bytes: None, caught_up: false, secs: None };
+        assert_eq!(lag.problems(1000, 60), ["no transaction was replayed yet"]);
+    }
+}
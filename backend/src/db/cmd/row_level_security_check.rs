@@ -0,0 +1,113 @@
+//! The `db row-level-security-check` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db) -> Result<()> {
+    // Tobira itself does not use RLS (access checks happen in the API), but
+    // administrators might add policies on top. The table owner (which is
+    // the user Tobira connects as) bypasses RLS unless it is forced.
+    let tables = db
+        .query(
+            "select c.relname::text, c.relforcerowsecurity, \
+                    pg_get_userbyid(c.relowner)::text = current_user \
+                from pg_class c \
+                where c.relkind = 'r' \
+                    and c.relnamespace = 'public'::regnamespace \
+                    and c.relrowsecurity \
+                order by c.relname",
+            &[],
+        )
+        .await
+        .context("failed to query tables")?;
+
+    if tables.is_empty() {
+        bunt::println!("{$green+intense}No table has row level security enabled.{/$}");
+        return Ok(());
+    }
+
+    let mut problems = 0;
+    for table in &tables {
+        let name: String = table.get(0);
+        let forced: bool = table.get(1);
+        let owned_by_us: bool = table.get(2);
+
+        bunt::println!("{$bold}Table {[blue+intense]}{/$}", name);
+        if owned_by_us && !forced {
+            problems += 1;
+            bunt::println!(
+                "   {$red+bold}problem:{/$} policies do not apply to the current user as it \
+                    owns the table. Use `alter table {} force row level security`.",
+                name,
+            );
+        }
+
+        let policies = db
+            .query(
+                "select policyname::text, permissive::text, roles::text[], cmd::text, \
+                        qual::text, with_check::text \
+                    from pg_policies \
+                    where schemaname = 'public' and tablename = $1 \
+                    order by policyname",
+                &[&name],
+            )
+            .await
+            .with_context(|| format!("failed to query policies of '{name}'"))?;
+
+        if policies.is_empty() {
+            bunt::println!("   {$yellow+bold}warning:{/$} RLS enabled, but no policies: \
+                all rows are hidden for non-owners");
+        }
+
+        for policy in &policies {
+            let policy_name: String = policy.get(0);
+            let permissive: String = policy.get(1);
+            let roles: Vec<String> = policy.get(2);
+            let cmd: String = policy.get(3);
+            let qual: Option<String> = policy.get(4);
+            let with_check: Option<String> = policy.get(5);
+
+            bunt::println!(
+                "   - {[bold]} {$dimmed}({}, {} for {}){/$}",
+                policy_name,
+                permissive.to_lowercase(),
+                cmd,
+                roles.join(", "),
+            );
+            bunt::println!("     {$dimmed}using:{/$}      {}", qual.as_deref().unwrap_or("-"));
+            let with_check_str = with_check.as_deref().unwrap_or("-");
+            bunt::println!("     {$dimmed}with check:{/$} {}", with_check_str);
+
+            // A permissive policy that is always true makes all other
+            // permissive policies for the same command pointless and
+            // exposes all rows.
+            let always_true = |expr: &Option<String>| expr.as_deref() == Some("true");
+            let is_permissive = permissive == "PERMISSIVE";
+            if is_permissive && always_true(&qual) {
+                problems += 1;
+                bunt::println!("     {$red+bold}problem:{/$} `using (true)` allows reading any row");
+            }
+            if is_permissive && always_true(&with_check) && cmd != "SELECT" {
+                problems += 1;
+                bunt::println!(
+                    "     {$red+bold}problem:{/$} `with check (true)` allows writing any row",
+                );
+            }
+            if is_permissive && roles.iter().any(|r| r == "public") && qual.is_none() {
+                problems += 1;
+                bunt::println!(
+                    "     {$red+bold}problem:{/$} policy for `public` without `using` clause",
+                );
+            }
+        }
+        println!();
+    }
+
+    if problems > 0 {
+        bail!("found {problems} problems with row level security policies");
+    }
+
+    bunt::println!("{$green+intense}No problems found.{/$}");
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! The `db search-sync-status` subcommand.
+
+use chrono::{DateTime, Duration, Utc};
+use meilisearch_sdk::{errors::{Error as MeiliError, ErrorCode}, indexes::Index};
+
+use crate::{prelude::*, config::Config, db::Db};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, config: &Config, max_age_hours: u32) -> Result<()> {
+    let meili = config.meili.connect().await?;
+
+    let row = db
+        .query_one(
+            "select \
+                (select count(*) from events where state <> 'waiting'), \
+                (select count(*) from search_realms where name is not null), \
+                (select count(*) from search_index_queue), \
+                (select last_indexed_at from search_index_status)",
+            &[],
+        )
+        .await
+        .context("failed to query DB (is it migrated?)")?;
+    let status = Status {
+        events: (row.get::<_, i64>(0) as u64, document_count(&meili.event_index).await?),
+        realms: (row.get::<_, i64>(1) as u64, document_count(&meili.realm_index).await?),
+        queued: row.get::<_, i64>(2) as u64,
+        last_indexed_at: row.get(3),
+    };
+
+    let format_count = |count: Option<u64>| count.map_or("no index".into(), |c| c.to_string());
+    bunt::println!("{$bold}Search index sync status:{/$}");
+    for (label, (in_db, in_index)) in [("events", status.events), ("realms", status.realms)] {
+        bunt::println!(
+            " - {[bold]}: {} in DB, {} in search index",
+            label,
+            in_db,
+            format_count(in_index),
+        );
+    }
+    bunt::println!(" - {$bold}queued for indexing{/$}: {}", status.queued);
+    let last_indexed = status.last_indexed_at.map_or("never".into(), |t| t.to_string());
+    bunt::println!(" - {$bold}last indexed at{/$}: {}", last_indexed);
+    println!();
+
+    let max_age = Duration::hours(max_age_hours.into());
+    let problems = status.problems(Utc::now(), max_age);
+    if problems.is_empty() {
+        bunt::println!("{$green+intense}Search index is in sync with the DB.{/$}");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        match problem {
+            Problem::CountMismatch(label) => bunt::println!(
+                "{$yellow+bold}Warning:{/$} number of {} differs between DB and search index.",
+                label,
+            ),
+            Problem::Stale => bunt::println!(
+                "{$yellow+bold}Warning:{/$} {} items are queued, but nothing was indexed in \
+                    the last {} hours.",
+                status.queued,
+                max_age_hours,
+            ),
+        }
+    }
+    println!();
+    if problems.contains(&Problem::Stale) {
+        println!("Make sure the worker (`tobira worker`) is running, or process the queue \
+            once with `tobira search-index update`.");
+    }
+    if problems.iter().any(|p| matches!(p, Problem::CountMismatch(_))) {
+        println!("If the counts still differ after the queue has been processed (and Meili \
+            finished indexing), rebuild the index with `tobira search-index rebuild`.");
+    }
+
+    bail!("search index is out of sync with the DB");
+}
+
+/// Returns the number of documents in the given index or `None` if it does
+/// not exist.
+async fn document_count(index: &Index) -> Result<Option<u64>> {
+    match index.get_stats().await {
+        Ok(stats) => Ok(Some(stats.number_of_documents as u64)),
+        Err(MeiliError::Meilisearch(e)) if e.error_code == ErrorCode::IndexNotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to get stats of index '{}'", index.uid)),
+    }
+}
+
+struct Status {
+    /// Number of items in the DB and the search index.
+    events: (u64, Option<u64>),
+    realms: (u64, Option<u64>),
+    queued: u64,
+    last_indexed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Problem {
+    CountMismatch(&'static str),
+    /// Items are waiting in the queue, but nothing was indexed for too long.
+    /// Without queued items, an old `last_indexed_at` just means that nothing
+    /// changed.
+    Stale,
+}
+
+impl Status {
+    fn problems(&self, now: DateTime<Utc>, max_age: Duration) -> Vec<Problem> {
+        let mut out = Vec::new();
+        for (label, (in_db, in_index)) in [("events", self.events), ("realms", self.realms)] {
+            if in_index != Some(in_db) {
+                out.push(Problem::CountMismatch(label));
+            }
+        }
+
+        let stale = self.last_indexed_at.is_none_or(|t| now - t > max_age);
+        if self.queued > 0 && stale {
+            out.push(Problem::Stale);
+        }
+
+        out
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use super::{Problem, Status};
+
+    #[test]
+    fn problems() {
+        let now = Utc.ymd(2022, 8, 1).and_hms(12, 0, 0);
+        let max_age = Duration::hours(24);
+        let mut status = Status {
+            events: (10, Some(10)),
+            realms: (3, Some(3)),
+            queued: 0,
+            last_indexed_at: Some(now - Duration::days(30)),
+        };
+        assert!(status.problems(now, max_age).is_empty());
+
+        status.queued = 2;
+        assert_eq!(status.problems(now, max_age), [Problem::Stale]);
+        status.last_indexed_at = Some(now - Duration::hours(1));
+        assert!(status.problems(now, max_age).is_empty());
+        status.last_indexed_at = None;
+        assert_eq!(status.problems(now, max_age), [Problem::Stale]);
+
+        status.queued = 0;
+        status.events = (10, Some(9));
+        status.realms = (3, None);
+        assert_eq!(status.problems(now, max_age), [
+            Problem::CountMismatch("events"),
+            Problem::CountMismatch("realms"),
+        ]);
+    }
+}
@@ -0,0 +1,78 @@
+//! The `db session-stats` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// By what to group connections.
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+pub(crate) enum GroupBy {
+    /// Tobira sets this to e.g. `Tobira (worker)`, see `db::set_application_name`.
+    ApplicationName,
+    ClientAddr,
+}
+
+impl GroupBy {
+    /// SQL expression of the group key.
+    fn sql(self) -> &'static str {
+        match self {
+            Self::ApplicationName => "coalesce(nullif(application_name, ''), '<none>')",
+            Self::ClientAddr => "coalesce(host(client_addr), '<local socket>')",
+        }
+    }
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, group_by: GroupBy) -> Result<()> {
+    // Backends without `datname` (e.g. the autovacuum launcher) and our own
+    // connection are excluded.
+    let query = format!("
+        select
+            {group},
+            count(*),
+            count(*) filter (where state = 'active'),
+            count(*) filter (where state = 'idle'),
+            count(*) filter (where state like 'idle in transaction%'),
+            max(extract(epoch from now() - query_start)::float8)
+                filter (where state = 'active'),
+            (array_agg(query order by query_start) filter (where state = 'active'))[1]
+        from pg_stat_activity
+        where datname = current_database() and pid <> pg_backend_pid()
+        group by 1
+        order by 2 desc, 1
+    ", group = group_by.sql());
+    let rows = db.query(&query, &[])
+        .await
+        .context("failed to query `pg_stat_activity`")?;
+
+    let total: i64 = rows.iter().map(|row| row.get::<_, i64>(1)).sum();
+    bunt::println!("{$bold}{} connections to this database (excluding this one):{/$}", total);
+
+    for row in &rows {
+        let group: String = row.get(0);
+        let count: i64 = row.get(1);
+        let active: i64 = row.get(2);
+        let idle: i64 = row.get(3);
+        let idle_in_tx: i64 = row.get(4);
+        let longest: Option<f64> = row.get(5);
+        let longest_query: Option<String> = row.get(6);
+
+        println!();
+        bunt::println!(
+            " - {[bold]}: {[blue+intense]} {$dimmed}({} active, {} idle, {} idle in \
+                transaction, {} other){/$}",
+            group,
+            count,
+            active,
+            idle,
+            idle_in_tx,
+            count - active - idle - idle_in_tx,
+        );
+        if let (Some(secs), Some(query)) = (longest, longest_query) {
+            let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+            bunt::println!("   {$dimmed}longest running query ({:.1}s):{/$} {}", secs, query);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! The `db snapshot-isolation-test` subcommand.
+
+use deadpool_postgres::Pool;
+use tokio_postgres::{IsolationLevel, error::SqlState};
+
+use crate::{prelude::*, db::Db};
+
+
+/// Scratch table used by all scenarios. Created at the start and dropped at
+/// the end.
+const TABLE: &str = "__snapshot_isolation_test";
+
+/// One of the two concurrent transactions.
+#[derive(Clone, Copy)]
+enum Tx {
+    A,
+    B,
+}
+
+/// A pattern of two concurrent transactions that would result in an anomaly
+/// if they were both allowed to commit.
+struct Scenario {
+    name: &'static str,
+    /// Rows inserted into `TABLE` (as `(id, grp, value)`) before starting.
+    setup: &'static str,
+    /// Statements executed in this order. Afterwards, A and then B commit.
+    steps: &'static [(Tx, &'static str)],
+}
+
+const SCENARIOS: &[Scenario] = &[
+    // Both transactions check that the sum of group 1 stays positive, then
+    // each sets a different row to 0. Each is fine on its own, but together
+    // they violate the invariant.
+    Scenario {
+        name: "write skew",
+        setup: "(1, 1, 1), (2, 1, 1)",
+        steps: &[
+            (Tx::A, "select sum(value) from __snapshot_isolation_test where grp = 1"),
+            (Tx::B, "select sum(value) from __snapshot_isolation_test where grp = 1"),
+            (Tx::A, "update __snapshot_isolation_test set value = 0 where id = 1"),
+            (Tx::B, "update __snapshot_isolation_test set value = 0 where id = 2"),
+        ],
+    },
+    // Each transaction reads a group and inserts a new row into the group the
+    // other one read. In any serial order, one of them would see the row
+    // inserted by the other.
+    Scenario {
+        name: "phantom read",
+        setup: "(1, 1, 10), (2, 2, 20)",
+        steps: &[
+            (Tx::A, "select sum(value) from __snapshot_isolation_test where grp = 1"),
+            (Tx::B, "select sum(value) from __snapshot_isolation_test where grp = 2"),
+            (Tx::A, "insert into __snapshot_isolation_test values (3, 2, 10)"),
+            (Tx::B, "insert into __snapshot_isolation_test values (4, 1, 20)"),
+        ],
+    },
+];
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(pool: &Pool) -> Result<()> {
+    let mut a = pool.get().await?;
+    let mut b = pool.get().await?;
+
+    // Check that we actually get what we ask for. Proxies or Postgres
+    // compatible databases might silently use a weaker level.
+    let tx = a.build_transaction().isolation_level(IsolationLevel::Serializable).start().await?;
+    let level: String = tx.query_one("select current_setting('transaction_isolation')", &[])
+        .await?
+        .get(0);
+    tx.rollback().await?;
+    let mut failures = 0;
+    if level == "serializable" {
+        bunt::println!("{$green}✔{/$} Transactions are run with isolation level 'serializable'");
+    } else {
+        bunt::println!("{$red+bold}✘ Requested isolation level 'serializable', but got '{}'{/$}",
+            level);
+        failures += 1;
+    }
+
+    a.batch_execute(&format!("drop table if exists {TABLE}; \
+        create table {TABLE} (id int primary key, grp int not null, value int not null)")).await
+        .context("failed to create scratch table")?;
+    let res = async {
+        for scenario in SCENARIOS {
+            if run_scenario(&mut a, &mut b, scenario).await? {
+                bunt::println!("{$green}✔{/$} {}: prevented", scenario.name);
+            } else {
+                bunt::println!("{$red+bold}✘ {}: both transactions committed!{/$}",
+                    scenario.name);
+                failures += 1;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    if let Err(e) = a.batch_execute(&format!("drop table if exists {TABLE}")).await {
+        warn!("Failed to drop scratch table '{TABLE}': {e}");
+    }
+    res?;
+
+    if failures > 0 {
+        bail!("serializable isolation does not work correctly ({failures} problem(s) found)");
+    }
+    Ok(())
+}
+
+/// Runs the scenario and returns whether Postgres prevented the anomaly by
+/// aborting one of the transactions with a serialization failure.
+async fn run_scenario(a: &mut Db, b: &mut Db, scenario: &Scenario) -> Result<bool> {
+    a.batch_execute(&format!("truncate {TABLE}; insert into {TABLE} values {}", scenario.setup))
+        .await
+        .with_context(|| format!("failed to set up scenario '{}'", scenario.name))?;
+
+    let tx_a = a.build_transaction().isolation_level(IsolationLevel::Serializable).start().await?;
+    let tx_b = b.build_transaction().isolation_level(IsolationLevel::Serializable).start().await?;
+    let res = async {
+        for (tx, sql) in scenario.steps {
+            trace!("{}: {sql}", match tx { Tx::A => "A", Tx::B => "B" });
+            match tx {
+                Tx::A => tx_a.batch_execute(sql).await?,
+                Tx::B => tx_b.batch_execute(sql).await?,
+            }
+        }
+        tx_a.commit().await?;
+        tx_b.commit().await
+    }.await;
+
+    match res {
+        Ok(()) => Ok(false),
+        Err(e) if e.code() == Some(&SqlState::T_R_SERIALIZATION_FAILURE) => {
+            debug!("Scenario '{}' aborted: {e}", scenario.name);
+            Ok(true)
+        }
+        Err(e) => Err(e).with_context(|| format!("scenario '{}' failed", scenario.name)),
+    }
+}
@@ -0,0 +1,194 @@
+//! The `db squash-migrations` subcommand. Developer tool only.
+
+use std::{path::Path, process::Command};
+use tokio_postgres::IsolationLevel;
+
+use crate::{
+    prelude::*,
+    db::{Db, DbConfig, MigrationPlan, migrations::MIGRATIONS},
+};
+
+
+/// Names of the temporary schemas used to verify the squashed script. They
+/// are dropped again at the end.
+const SCRATCH_ORIGINAL: &str = "__tobira_squash_original";
+const SCRATCH_SQUASHED: &str = "__tobira_squash_squashed";
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &mut Db,
+    config: &DbConfig,
+    from: u64,
+    name: &str,
+    out: &Path,
+) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        bail!("`db squash-migrations` is a developer tool and only available in debug builds");
+    }
+
+    let latest = MIGRATIONS.len() as u64;
+    if from == 0 || from > latest {
+        bail!("there is no migration with ID {from} (known migrations: 1 to {latest})");
+    }
+    if from == latest {
+        bail!("migration {from} is the latest migration: there is nothing to squash it with");
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("migration name '{name}' is invalid: only ASCII letters, digits and '-' allowed");
+    }
+
+    // We only squash if the DB is in the exact state this binary expects.
+    // Otherwise the rows we would replace in `__db_migrations` do not
+    // correspond to the scripts we are about to concatenate.
+    {
+        let tx = db.transaction().await?;
+        let plan = MigrationPlan::build(&tx).await.context(
+            "the migrations in the database do not match the ones in this binary. \
+                Squashing cannot resolve that, fix the database first \
+                (e.g. via `db reset` or `db unsafe-overwrite-migrations`)",
+        )?;
+        match plan {
+            MigrationPlan::UpToDate => {}
+            MigrationPlan::EmptyDb => bail!(
+                "database is empty: run `db migrate` first, then squash",
+            ),
+            MigrationPlan::Migrate { new_migrations } => bail!(
+                "database is missing {new_migrations} migrations: run `db migrate` \
+                    first, then squash",
+            ),
+        }
+    }
+
+    let squashed = squash(from);
+    let squashed_count = latest - from + 1;
+    info!("Squashing {squashed_count} migrations ({from} to {latest}) into one");
+
+    // Verify that both paths result in the same schema.
+    let verification = verify(db, config, from, &squashed).await;
+    drop_scratch_schemas(db).await?;
+    verification?;
+    info!("Verified that the squashed script produces an identical schema");
+
+    tokio::fs::write(out, &squashed)
+        .await
+        .with_context(|| format!("failed to write squashed script to '{}'", out.display()))?;
+    info!("Wrote squashed migration to '{}'", out.display());
+
+    // Replace all squashed migrations in the meta table by a single entry. We
+    // lock the table just like `migrate` does, so that no Tobira node can run
+    // migrations concurrently.
+    let tx = db.build_transaction()
+        .isolation_level(IsolationLevel::Serializable)
+        .start()
+        .await?;
+    tx.execute("lock table __db_migrations in share update exclusive mode", &[])
+        .await
+        .context("failed to lock table '__db_migrations'")?;
+    tx.execute("delete from __db_migrations where id >= $1", &[&(from as i64)]).await?;
+    tx.execute(
+        "insert into __db_migrations (id, name, applied_on, script) \
+            values ($1, $2, now() at time zone 'utc', $3)",
+        &[&(from as i64), &name, &squashed],
+    ).await?;
+    tx.commit().await.context("failed to update '__db_migrations'")?;
+    info!("Replaced migrations {from} to {latest} in '__db_migrations' with '{from}-{name}'");
+
+    println!();
+    println!("Now replace the squashed migration files with '{}' and update \
+        `include_migrations!` accordingly.", out.display());
+
+    Ok(())
+}
+
+/// Concatenates all migrations starting at `from` into one script.
+fn squash(from: u64) -> String {
+    let mut out = String::new();
+    for (id, migration) in MIGRATIONS.range(from..) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("-- ===== Squashed from '{id}-{}' =====\n\n", migration.name));
+        out.push_str(migration.script);
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Applies all original migrations to one scratch schema and the migrations
+/// before `from` plus the squashed script to another. Then compares the
+/// `pg_dump --schema-only` output of both.
+async fn verify(db: &mut Db, config: &DbConfig, from: u64, squashed: &str) -> Result<()> {
+    drop_scratch_schemas(db).await?;
+
+    let original = MIGRATIONS.values().map(|m| m.script);
+    apply_in_schema(db, SCRATCH_ORIGINAL, original).await
+        .context("failed to apply original migrations to scratch schema")?;
+
+    let squashed = MIGRATIONS.range(..from).map(|(_, m)| m.script)
+        .chain(std::iter::once(squashed));
+    apply_in_schema(db, SCRATCH_SQUASHED, squashed).await
+        .context("failed to apply squashed migration to scratch schema")?;
+
+    let original = dump_schema(config, SCRATCH_ORIGINAL)?;
+    let squashed = dump_schema(config, SCRATCH_SQUASHED)?;
+    if original != squashed {
+        debug!("Schema from original migrations:\n{original}");
+        debug!("Schema from squashed migration:\n{squashed}");
+        bail!("the squashed script results in a different schema than the original \
+            migrations (run with debug logging to see both)");
+    }
+
+    Ok(())
+}
+
+async fn apply_in_schema<'a>(
+    db: &mut Db,
+    schema: &str,
+    scripts: impl Iterator<Item = &'a str>,
+) -> Result<()> {
+    let tx = db.transaction().await?;
+    tx.batch_execute(&format!("create schema {schema}; set local search_path to {schema}, public"))
+        .await?;
+    for script in scripts {
+        tx.batch_execute(script).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn drop_scratch_schemas(db: &Db) -> Result<()> {
+    db.batch_execute(&format!(
+        "drop schema if exists {SCRATCH_ORIGINAL} cascade; \
+            drop schema if exists {SCRATCH_SQUASHED} cascade;",
+    )).await.context("failed to drop scratch schemas")?;
+    Ok(())
+}
+
+/// Runs `pg_dump --schema-only` for the given schema and returns the output
+/// with all mentions of the schema name normalized, so that dumps of
+/// different schemas can be compared.
+fn dump_schema(config: &DbConfig, schema: &str) -> Result<String> {
+    let output = Command::new("pg_dump")
+        .arg("--dbname")
+        .arg(super::connection_uri(config))
+        .arg("--schema-only")
+        .arg("--no-owner")
+        .arg("--schema")
+        .arg(schema)
+        .output()
+        .context("failed to execute `pg_dump` (is it installed and in your `PATH`?)")?;
+
+    if !output.status.success() {
+        bail!(
+            "`pg_dump` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+
+    let dump = String::from_utf8(output.stdout).context("`pg_dump` output is not UTF8")?;
+    Ok(dump.replace(schema, "__scratch"))
+}
@@ -0,0 +1,179 @@
+//! Typed results of `db` subcommands and how they are printed.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::prelude::*;
+
+
+/// How the result of a `db` subcommand is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub(crate) enum OutputFormat {
+    /// Human readable text.
+    Text,
+    /// A single JSON object on stdout, useful for scripts. Only supported by
+    /// subcommands that change the database or dump it.
+    Json,
+}
+
+/// What a `db` subcommand did. Subcommands that analyze the database print
+/// their own report and return `Report`.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub(crate) enum Summary {
+    Clear {
+        tables_dropped: Vec<String>,
+    },
+    Reset {
+        tables_dropped: Vec<String>,
+        migrations_applied: u64,
+    },
+    Migrate {
+        migrations_applied: u64,
+    },
+    WaitForVersion {
+        version: i64,
+    },
+    Script {
+        rows_affected: u64,
+    },
+    Dump {
+        path: PathBuf,
+        bytes: u64,
+    },
+    S3Dump {
+        location: String,
+        bytes: u64,
+    },
+    UnsafeOverwriteMigrations {
+        updated: Vec<u64>,
+        deleted: Vec<u64>,
+    },
+    PgLogicalSlotCreate {
+        slot: String,
+        plugin: String,
+        confirmed_lsn: String,
+    },
+    PgLogicalSlotDrop {
+        slot: String,
+        unconsumed_bytes: u64,
+    },
+    CacheInvalidate {
+        tables: Vec<String>,
+        notified_nodes: u64,
+    },
+    #[serde(skip)]
+    Report,
+}
+
+impl Summary {
+    pub(crate) fn print(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Text => self.print_text(),
+        }
+        Ok(())
+    }
+
+    fn print_text(&self) {
+        match self {
+            Self::Clear { tables_dropped } => print_dropped(tables_dropped),
+            Self::Reset { tables_dropped, migrations_applied } => {
+                print_dropped(tables_dropped);
+                bunt::println!("Applied {[blue+intense]} migrations", migrations_applied);
+            }
+            // Applying migrations and waiting for them is already logged in
+            // detail.
+            Self::Migrate { .. } | Self::WaitForVersion { .. } => {}
+            Self::Script { rows_affected } => {
+                bunt::println!("Ran SQL script ({[blue+intense]} rows affected)", rows_affected);
+            }
+            Self::Dump { path, bytes } => {
+                bunt::println!(
+                    "Wrote dump ({[blue+intense]}) to {[bold]}",
+                    human_bytes(*bytes),
+                    path.display(),
+                );
+            }
+            Self::S3Dump { location, bytes } => {
+                bunt::println!(
+                    "Uploaded dump ({[blue+intense]}) to {[bold]}",
+                    human_bytes(*bytes),
+                    location,
+                );
+            }
+            Self::UnsafeOverwriteMigrations { updated, deleted } => {
+                bunt::println!(
+                    "Updated {[blue+intense]} and deleted {[blue+intense]} migrations",
+                    updated.len(),
+                    deleted.len(),
+                );
+            }
+            Self::PgLogicalSlotCreate { slot, plugin, confirmed_lsn } => {
+                bunt::println!(
+                    "Created logical replication slot {[bold]} (plugin {[bold]}) \
+                        at confirmed LSN {[blue+intense]}",
+                    slot,
+                    plugin,
+                    confirmed_lsn,
+                );
+                bunt::println!("{$dimmed}No snapshot was exported: use the replication \
+                    protocol (`CREATE_REPLICATION_SLOT ... EXPORT_SNAPSHOT`) if the initial \
+                    data has to be copied consistently with the slot.{/$}");
+            }
+            Self::PgLogicalSlotDrop { slot, .. } => {
+                bunt::println!("Dropped logical replication slot {[bold]}", slot);
+            }
+            Self::CacheInvalidate { notified_nodes: 0, .. } => {
+                bunt::println!("{$yellow}No running Tobira node found.{/$} Nothing to \
+                    invalidate: caches of Tobira nodes started later are empty, and \
+                    all caches expire within a few seconds anyway.");
+            }
+            Self::CacheInvalidate { tables, notified_nodes } => {
+                bunt::println!(
+                    "Requested {[blue+intense]} Tobira node(s) to invalidate caches of: {[bold]}",
+                    notified_nodes,
+                    tables.join(", "),
+                );
+            }
+            Self::Report => {}
+        }
+    }
+}
+
+fn print_dropped(tables: &[String]) {
+    bunt::println!("Dropped schema 'public' with {[blue+intense]} tables", tables.len());
+}
+
+fn human_bytes(bytes: u64) -> String {
+    super::disk_projection::human_bytes(bytes as f64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use serde_json::json;
+    use super::Summary;
+
+    #[test]
+    fn serializes_with_command_tag() {
+        let summary = Summary::Reset {
+            tables_dropped: vec!["realms".into(), "events".into()],
+            migrations_applied: 25,
+        };
+        assert_eq!(serde_json::to_value(&summary).unwrap(), json!({
+            "command": "reset",
+            "tables_dropped": ["realms", "events"],
+            "migrations_applied": 25,
+        }));
+
+        let summary = Summary::Dump { path: PathBuf::from("/tmp/tobira.dump"), bytes: 1024 };
+        assert_eq!(serde_json::to_value(&summary).unwrap(), json!({
+            "command": "dump",
+            "path": "/tmp/tobira.dump",
+            "bytes": 1024,
+        }));
+    }
+}
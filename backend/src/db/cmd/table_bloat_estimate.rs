@@ -0,0 +1,218 @@
+//! The `db table-bloat-estimate` subcommand.
+
+use crate::{prelude::*, db::Db};
+use super::disk_projection::human_bytes;
+
+
+/// Size of the header of each heap page in bytes.
+const PAGE_HEADER: f64 = 24.0;
+
+/// Size of the line pointer each tuple has in the page in bytes.
+const ITEM_POINTER: f64 = 4.0;
+
+/// Size of the fixed part of each tuple header in bytes.
+const TUPLE_HEADER: f64 = 23.0;
+
+/// Alignment of tuple headers and data (`MAXALIGN` on 64 bit platforms).
+const MAX_ALIGN: f64 = 8.0;
+
+
+/// The inputs of the estimate for one table, all taken from the catalog
+/// (`pg_class`, `pg_attribute`) and the planner statistics (`pg_stats`).
+#[derive(Debug)]
+struct TableStats {
+    name: String,
+    pages: i64,
+    tuples: f64,
+    fillfactor: i32,
+    /// Sum of the average widths of all columns, weighted by their non-null
+    /// fraction.
+    data_width: f64,
+    columns: i64,
+    /// Number of columns that have statistics.
+    analyzed_columns: i64,
+    has_nulls: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Bloat {
+    expected_pages: i64,
+    wasted_bytes: i64,
+    percent: f64,
+}
+
+impl TableStats {
+    /// Estimates how many pages the table would need without bloat and
+    /// compares that to the actual number of pages. Returns `None` if the
+    /// table is empty or has no statistics.
+    fn estimate(&self, block_size: i64) -> Option<Bloat> {
+        if self.pages == 0 || self.analyzed_columns == 0 {
+            return None;
+        }
+
+        let align = |x: f64| (x / MAX_ALIGN).ceil() * MAX_ALIGN;
+        let null_bitmap = if self.has_nulls { (self.columns as f64 / 8.0).ceil() } else { 0.0 };
+        let tuple_size = align(TUPLE_HEADER + null_bitmap) + align(self.data_width) + ITEM_POINTER;
+        let usable = (block_size as f64 - PAGE_HEADER) * f64::from(self.fillfactor) / 100.0;
+        let tuples_per_page = (usable / tuple_size).floor().max(1.0);
+        let expected_pages = (self.tuples / tuples_per_page).ceil() as i64;
+
+        let wasted_pages = (self.pages - expected_pages).max(0);
+        Some(Bloat {
+            expected_pages,
+            wasted_bytes: wasted_pages * block_size,
+            percent: 100.0 * wasted_pages as f64 / self.pages as f64,
+        })
+    }
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, threshold_pct: f64) -> Result<()> {
+    if !(0.0..=100.0).contains(&threshold_pct) {
+        bail!("threshold has to be between 0 and 100, but is {threshold_pct}");
+    }
+
+    let block_size = db.query_one("select current_setting('block_size')::int8", &[])
+        .await
+        .context("failed to query block size")?
+        .get::<_, i64>(0);
+
+    let query = "
+        select
+            c.relname::text,
+            c.relpages::int8,
+            greatest(c.reltuples, 0)::float8,
+            coalesce(
+                (select substring(o from '^fillfactor=(\\d+)$')::int4
+                    from unnest(c.reloptions) as o
+                    where o like 'fillfactor=%'),
+                100
+            ),
+            coalesce(sum((1 - s.null_frac) * s.avg_width), 0)::float8,
+            count(*),
+            count(s.attname),
+            coalesce(bool_or(s.null_frac > 0), false)
+        from pg_class c
+        join pg_attribute a on a.attrelid = c.oid and a.attnum > 0 and not a.attisdropped
+        left join pg_stats s
+            on s.schemaname = 'public' and s.tablename = c.relname and s.attname = a.attname
+        where c.relnamespace = 'public'::regnamespace
+            and c.relkind = 'r'
+            and not exists (
+                select from pg_depend d
+                    where d.classid = 'pg_class'::regclass and d.objid = c.oid and d.deptype = 'e'
+            )
+        group by c.oid, c.relname, c.relpages, c.reltuples, c.reloptions
+        order by c.relname
+    ";
+    let tables = db.query(query, &[])
+        .await
+        .context("failed to query table statistics")?
+        .into_iter()
+        .map(|row| TableStats {
+            name: row.get(0),
+            pages: row.get(1),
+            tuples: row.get(2),
+            fillfactor: row.get(3),
+            data_width: row.get(4),
+            columns: row.get(5),
+            analyzed_columns: row.get(6),
+            has_nulls: row.get(7),
+        })
+        .collect::<Vec<_>>();
+
+    let mut estimates = tables.iter()
+        .filter_map(|t| t.estimate(block_size).map(|bloat| (t, bloat)))
+        .filter(|(_, bloat)| bloat.percent >= threshold_pct)
+        .collect::<Vec<_>>();
+    estimates.sort_by_key(|(_, bloat)| std::cmp::Reverse(bloat.wasted_bytes));
+
+    let unanalyzed = tables.iter()
+        .filter(|t| t.pages > 0 && t.analyzed_columns == 0)
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+
+    if estimates.is_empty() {
+        bunt::println!(
+            "{$green+intense}No table has an estimated bloat of at least {}%.{/$}",
+            threshold_pct,
+        );
+    } else {
+        bunt::println!("{$bold}Estimated table bloat (approximate!):{/$}");
+        println!();
+        for (table, bloat) in &estimates {
+            let pct = format!("~{:.0}%", bloat.percent);
+            let details = format!(
+                "~{} wasted, {} pages, ~{} expected, {:.0} rows",
+                human_bytes(bloat.wasted_bytes as f64),
+                table.pages,
+                bloat.expected_pages,
+                table.tuples,
+            );
+            if bloat.percent >= 50.0 {
+                bunt::println!(" - {[bold]}: {[red+intense]} {[dimmed]}", table.name, pct, details);
+            } else if bloat.percent >= 20.0 {
+                bunt::println!(" - {[bold]}: {[yellow]} {[dimmed]}", table.name, pct, details);
+            } else {
+                bunt::println!(" - {[bold]}: {} {[dimmed]}", table.name, pct, details);
+            }
+        }
+    }
+
+    if !unanalyzed.is_empty() {
+        println!();
+        bunt::println!(
+            "{$yellow}No statistics for these tables, run `analyze` first:{/$} {}",
+            unanalyzed.join(", "),
+        );
+    }
+
+    println!();
+    bunt::println!("{$dimmed}These numbers are estimated from the table size, the row count \
+        and the average column widths as recorded by the last `analyze`. They ignore TOAST and \
+        can be off, especially for small tables or outdated statistics. Use the \
+        `pgstattuple` extension for exact values.{/$}");
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::TableStats;
+
+    fn stats(pages: i64, tuples: f64) -> TableStats {
+        TableStats {
+            name: "events".into(),
+            pages,
+            tuples,
+            fillfactor: 100,
+            data_width: 100.0,
+            columns: 10,
+            analyzed_columns: 10,
+            has_nulls: false,
+        }
+    }
+
+    #[test]
+    fn estimates_bloat() {
+        // Each tuple takes 24 (header) + 104 (data) + 4 (item pointer) bytes,
+        // so 61 of them fit into an 8 KiB page.
+        let bloat = stats(100, 6100.0).estimate(8192).unwrap();
+        assert_eq!(bloat.expected_pages, 100);
+        assert_eq!(bloat.wasted_bytes, 0);
+
+        let bloat = stats(400, 6100.0).estimate(8192).unwrap();
+        assert_eq!(bloat.expected_pages, 100);
+        assert_eq!(bloat.wasted_bytes, 300 * 8192);
+        assert_eq!(bloat.percent, 75.0);
+
+        // A lower fillfactor leaves room on purpose, which is not bloat.
+        let bloat = TableStats { fillfactor: 50, ..stats(200, 6100.0) }.estimate(8192).unwrap();
+        assert_eq!(bloat.wasted_bytes, 0);
+
+        assert_eq!(stats(0, 0.0).estimate(8192), None);
+        assert_eq!(TableStats { analyzed_columns: 0, ..stats(10, 100.0) }.estimate(8192), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! The `db table-grant-matrix` subcommand.
+
+use std::{collections::BTreeMap, io::{self, Write}};
+
+use crate::{prelude::*, cmd::export::write_record, db::Db};
+
+
+/// The privileges shown in the matrix, in the order of the cell letters.
+const PRIVILEGES: [(&str, char); 4] = [
+    ("SELECT", 'S'),
+    ("INSERT", 'I'),
+    ("UPDATE", 'U'),
+    ("DELETE", 'D'),
+];
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, role: Option<&str>, csv: bool) -> Result<()> {
+    let tables = db
+        .query(
+            "select table_name::text from information_schema.tables \
+                where table_schema = 'public' \
+                order by table_name",
+            &[],
+        )
+        .await
+        .context("failed to query tables")?
+        .into_iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<Vec<_>>();
+
+    let rows = db
+        .query(
+            "select grantee::text, table_name::text, privilege_type::text \
+                from information_schema.role_table_grants \
+                where table_schema = 'public' and ($1::text is null or grantee = $1)",
+            &[&role],
+        )
+        .await
+        .context("failed to query `information_schema.role_table_grants`")?;
+
+    let mut matrix = Matrix::default();
+    for row in &rows {
+        matrix.grant(row.get(0), row.get(1), row.get(2));
+    }
+
+    if matrix.0.is_empty() {
+        match role {
+            Some(role) => bail!("no privileges on any table granted to role '{role}'"),
+            None => bail!("no privileges on any table found"),
+        }
+    }
+
+    if csv {
+        let mut out = io::stdout().lock();
+        matrix.write_csv(&mut out, &tables)?;
+        out.flush()?;
+    } else {
+        matrix.print(&tables);
+    }
+
+    Ok(())
+}
+
+/// Maps role name to a map from table name to privilege bitmask (bit `i`
+/// corresponds to `PRIVILEGES[i]`).
+#[derive(Default)]
+struct Matrix(BTreeMap<String, BTreeMap<String, u8>>);
+
+impl Matrix {
+    fn grant(&mut self, role: String, table: String, privilege: &str) {
+        let entry = self.0.entry(role).or_default().entry(table).or_default();
+        if let Some(i) = PRIVILEGES.iter().position(|(name, _)| *name == privilege) {
+            *entry |= 1 << i;
+        }
+    }
+
+    /// Returns the cell for the given role and table, e.g. `SI--`.
+    fn cell(&self, role: &str, table: &str) -> String {
+        let mask = self.0.get(role).and_then(|t| t.get(table)).copied().unwrap_or(0);
+        PRIVILEGES.iter()
+            .enumerate()
+            .map(|(i, (_, letter))| if mask & (1 << i) != 0 { *letter } else { '-' })
+            .collect()
+    }
+
+    fn print(&self, tables: &[String]) {
+        let role_width = self.0.keys().map(|r| r.len()).max().unwrap_or(0).max("role".len());
+        let width = |table: &str| table.len().max(PRIVILEGES.len());
+
+        bunt::print!("{$bold}{}{/$}", format!("{:w$}", "role", w = role_width));
+        for table in tables {
+            bunt::print!("  {$bold}{}{/$}", format!("{:w$}", table, w = width(table)));
+        }
+        println!();
+
+        for role in self.0.keys() {
+            bunt::print!("{[blue+intense]}", format!("{:w$}", role, w = role_width));
+            for table in tables {
+                let cell = self.cell(role, table);
+                let padded = format!("{:w$}", cell, w = width(table));
+                if cell.chars().all(|c| c == '-') {
+                    bunt::print!("  {[dimmed]}", padded);
+                } else {
+                    print!("  {}", padded);
+                }
+            }
+            println!();
+        }
+
+        println!();
+        bunt::println!("{$dimmed}S = SELECT, I = INSERT, U = UPDATE, D = DELETE{/$}");
+    }
+
+    fn write_csv(&self, out: &mut impl Write, tables: &[String]) -> io::Result<()> {
+        let header = std::iter::once("role").chain(tables.iter().map(|t| t.as_str()));
+        write_record(out, &header.collect::<Vec<_>>())?;
+
+        for role in self.0.keys() {
+            let cells = tables.iter().map(|t| self.cell(role, t)).collect::<Vec<_>>();
+            let record = std::iter::once(role.as_str())
+                .chain(cells.iter().map(|c| c.as_str()))
+                .collect::<Vec<_>>();
+            write_record(out, &record)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Matrix;
+
+    #[test]
+    fn cells_and_csv() {
+        let mut matrix = Matrix::default();
+        matrix.grant("tobira".into(), "realms".into(), "SELECT");
+        matrix.grant("tobira".into(), "realms".into(), "DELETE");
+        matrix.grant("tobira".into(), "realms".into(), "TRIGGER");
+        matrix.grant("reader".into(), "events".into(), "SELECT");
+
+        assert_eq!(matrix.cell("tobira", "realms"), "S--D");
+        assert_eq!(matrix.cell("tobira", "events"), "----");
+        assert_eq!(matrix.cell("unknown", "events"), "----");
+
+        let mut out = Vec::new();
+        matrix.write_csv(&mut out, &["events".into(), "realms".into()]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "role,events,realms\r\nreader,S---,----\r\ntobira,----,S--D\r\n",
+        );
+    }
+}
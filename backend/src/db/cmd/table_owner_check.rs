@@ -0,0 +1,70 @@
+//! The `db table-owner-check` subcommand.
+
+use crate::{prelude::*, db::{Db, query::{qualified, quote_ident}}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, expected_owner: &str) -> Result<()> {
+    let wrong = db
+        .query(
+            "select tablename::text, tableowner::text \
+                from pg_tables \
+                where schemaname = 'public' and tableowner <> $1 \
+                order by tablename",
+            &[&expected_owner],
+        )
+        .await
+        .context("failed to query table owners")?
+        .into_iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect::<Vec<_>>();
+
+    if wrong.is_empty() {
+        bunt::println!(
+            "{$green+intense}All tables are owned by '{}'.{/$}",
+            expected_owner,
+        );
+        return Ok(());
+    }
+
+    bunt::println!(
+        "{$bold}Found {} tables not owned by '{}':{/$}",
+        wrong.len(),
+        expected_owner,
+    );
+    for (table, owner) in &wrong {
+        bunt::println!(" - {[bold]} {$dimmed}(owned by '{}'){/$}", table, owner);
+    }
+    println!();
+    println!("Migrations might fail to alter these tables. To fix this, run the following \
+        as superuser or as the current owners:");
+    println!();
+    for (table, _) in &wrong {
+        println!("{}", fix_statement(table, expected_owner));
+    }
+    println!();
+
+    bail!("{} tables have the wrong owner", wrong.len());
+}
+
+fn fix_statement(table: &str, owner: &str) -> String {
+    format!("alter table {} owner to {};", qualified(table), quote_ident(owner))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::fix_statement;
+
+    #[test]
+    fn statement() {
+        assert_eq!(
+            fix_statement("events", "tobira"),
+            r#"alter table public."events" owner to "tobira";"#,
+        );
+        assert_eq!(
+            fix_statement("weird\"name", "Tobira User"),
+            r#"alter table public."weird""name" owner to "Tobira User";"#,
+        );
+    }
+}
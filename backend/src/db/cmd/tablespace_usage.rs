@@ -0,0 +1,214 @@
+//! The `db tablespace-usage` subcommand.
+
+use std::{collections::BTreeMap, path::Path, process::Command};
+
+use crate::{prelude::*, db::{Db, DbConfig}};
+use super::disk_projection::human_bytes;
+
+
+/// Relation kinds that are listed, with their label.
+const RELATION_KINDS: &[(&str, &str)] = &[
+    ("r", "table"),
+    ("p", "table"),
+    ("m", "materialized view"),
+    ("i", "index"),
+    ("I", "index"),
+];
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &Db, config: &DbConfig, threshold_pct: f64) -> Result<()> {
+    let tablespaces = db
+        .query(
+            "select oid, spcname::text, pg_tablespace_location(oid) \
+                from pg_tablespace \
+                order by spcname",
+            &[],
+        )
+        .await
+        .context("failed to query tablespaces")?;
+
+    // The built-in tablespaces have no location, they are stored in the data
+    // directory. Reading that setting requires special privileges.
+    let data_directory = db.query_one("show data_directory", &[]).await
+        .map(|row| row.get::<_, String>(0))
+        .ok();
+
+    let relations = relations_per_tablespace(db).await?;
+
+    // Disk usage can only be determined if the database runs on this machine.
+    let is_local = config.is_local();
+
+    for (i, row) in tablespaces.iter().enumerate() {
+        let oid: u32 = row.get(0);
+        let name: String = row.get(1);
+        let location = Some(row.get::<_, String>(2))
+            .filter(|location| !location.is_empty())
+            .or_else(|| data_directory.clone());
+
+        // Requires the `CREATE` privilege on the tablespace or being a member
+        // of `pg_read_all_stats`.
+        let size = db.query_one("select pg_tablespace_size($1::oid)", &[&oid]).await
+            .map(|row| row.get::<_, i64>(0))
+            .ok();
+
+        if i > 0 {
+            println!();
+        }
+        bunt::println!("{$bold}{}{/$} {$dimmed}({}){/$}",
+            name, location.as_deref().unwrap_or("unknown location"));
+        match size {
+            Some(size) => println!("  Size: {}", human_bytes(size as f64)),
+            None => bunt::println!("  Size: {$dimmed}unknown (insufficient privileges){/$}"),
+        }
+
+        let disk = location.as_deref()
+            .filter(|_| is_local)
+            .and_then(|location| disk_usage(Path::new(location)));
+        match disk {
+            Some(disk) => {
+                let used_pct = disk.used_pct();
+                let line = format!(
+                    "  Disk: {} of {} used ({used_pct:.0}%), {} available",
+                    human_bytes(disk.used as f64),
+                    human_bytes(disk.total() as f64),
+                    human_bytes(disk.available as f64),
+                );
+                if used_pct >= threshold_pct {
+                    bunt::println!("{[red+bold]}  {$red}← disk almost full!{/$}", line);
+                } else {
+                    println!("{line}");
+                }
+            }
+            None if !is_local => {
+                bunt::println!("  Disk: {$dimmed}unknown (database runs on another host){/$}");
+            }
+            None => bunt::println!("  Disk: {$dimmed}unknown{/$}"),
+        }
+
+        match relations.get(&oid) {
+            None => bunt::println!("  {$dimmed}No tables or indexes of Tobira.{/$}"),
+            Some(relations) => {
+                let name_width = relations.iter().map(|r| r.name.len()).max().unwrap_or(0);
+                for r in relations {
+                    bunt::println!(
+                        "  - {}  {[dimmed]} {}",
+                        format!("{:<name_width$}", r.name),
+                        format!("{:<17}", r.kind),
+                        human_bytes(r.size as f64),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A table or index of Tobira.
+struct Relation {
+    name: String,
+    kind: &'static str,
+    size: i64,
+}
+
+/// Returns all tables and indexes of Tobira by tablespace OID, largest first.
+async fn relations_per_tablespace(db: &Db) -> Result<BTreeMap<u32, Vec<Relation>>> {
+    let kinds = RELATION_KINDS.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+    let rows = db
+        .query(
+            "select \
+                    coalesce(nullif(c.reltablespace, 0), d.dattablespace), \
+                    c.relname::text, \
+                    c.relkind::text, \
+                    case when c.relkind in ('i', 'I') \
+                        then pg_relation_size(c.oid) \
+                        else pg_table_size(c.oid) \
+                    end \
+                from pg_class c \
+                join pg_namespace n on n.oid = c.relnamespace \
+                join pg_database d on d.datname = current_database() \
+                where n.nspname = 'public' and c.relkind::text = any($1) \
+                order by 4 desc, 2",
+            &[&kinds],
+        )
+        .await
+        .context("failed to query tables and indexes")?;
+
+    let mut out = BTreeMap::<u32, Vec<Relation>>::new();
+    for row in rows {
+        let kind = row.get::<_, String>(2);
+        let kind = RELATION_KINDS.iter()
+            .find(|(k, _)| *k == kind)
+            .map_or("relation", |(_, label)| *label);
+        out.entry(row.get(0)).or_default().push(Relation {
+            name: row.get(1),
+            kind,
+            size: row.get(3),
+        });
+    }
+
+    Ok(out)
+}
+
+/// Usage of a file system in bytes.
+#[derive(Debug, PartialEq)]
+struct DiskUsage {
+    used: u64,
+    available: u64,
+}
+
+impl DiskUsage {
+    fn total(&self) -> u64 {
+        self.used + self.available
+    }
+
+    /// Like `df`, space reserved for root is not counted as available.
+    fn used_pct(&self) -> f64 {
+        if self.total() == 0 {
+            return 0.0;
+        }
+        100.0 * self.used as f64 / self.total() as f64
+    }
+}
+
+/// Returns the usage of the file system `path` is on via `df`, or `None` if
+/// that fails (e.g. because the directory is not readable).
+fn disk_usage(path: &Path) -> Option<DiskUsage> {
+    let output = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    if !output.status.success() {
+        debug!("`df` failed for '{}': {}", path.display(),
+            String::from_utf8_lossy(&output.stderr).trim());
+        return None;
+    }
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the output of `df -Pk` for a single path.
+fn parse_df(output: &str) -> Option<DiskUsage> {
+    // Columns: file system, total, used, available, capacity, mount point.
+    let line = output.lines().nth(1)?;
+    let mut fields = line.split_whitespace().skip(2);
+    let used = fields.next()?.parse::<u64>().ok()?;
+    let available = fields.next()?.parse::<u64>().ok()?;
+    Some(DiskUsage { used: used * 1024, available: available * 1024 })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{DiskUsage, parse_df};
+
+    #[test]
+    fn parses_df_output() {
+        let output = "\
+            Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+            /dev/nvme0n1p2   479079112 380520696  74149796      84% /\n";
+        let usage = parse_df(output).unwrap();
+        assert_eq!(usage, DiskUsage { used: 380520696 * 1024, available: 74149796 * 1024 });
+        assert_eq!(usage.used_pct().round(), 84.0);
+
+        assert_eq!(parse_df("Filesystem 1024-blocks Used Available Capacity Mounted on\n"), None);
+        assert_eq!(parse_df(""), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! The `db timeline` subcommand.
+
+use std::time::Duration;
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{prelude::*, db::{Db, query}};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if !query::does_table_exist(&**db, "__db_migrations").await? {
+        bail!("table '__db_migrations' does not exist: no migrations were applied yet");
+    }
+
+    // `applied_on` is stored as `timestamp` in UTC.
+    let rows = db
+        .query(
+            "select id, name, applied_on, duration_ms, tobira_version, skipped \
+                from __db_migrations \
+                where ($1::timestamp is null or applied_on >= $1) \
+                    and ($2::timestamp is null or applied_on <= $2) \
+                order by applied_on, id",
+            &[&since.map(|t| t.naive_utc()), &until.map(|t| t.naive_utc())],
+        )
+        .await
+        .context("failed to query '__db_migrations'")?;
+
+    let range = match (since, until) {
+        (None, None) => String::new(),
+        (Some(since), None) => format!(" since {}", format_time(since.naive_utc())),
+        (None, Some(until)) => format!(" until {}", format_time(until.naive_utc())),
+        (Some(since), Some(until)) => format!(
+            " between {} and {}",
+            format_time(since.naive_utc()),
+            format_time(until.naive_utc()),
+        ),
+    };
+    println!("Database migrations applied{range} ({} total):", rows.len());
+    println!();
+
+    for row in &rows {
+        let id: i64 = row.get(0);
+        let name: String = row.get(1);
+        let applied_on: NaiveDateTime = row.get(2);
+        let duration_ms: Option<i64> = row.get(3);
+        let version: Option<String> = row.get(4);
+        let skipped: bool = row.get(5);
+
+        let duration = duration_ms.map_or("duration unknown".into(), |ms| {
+            format!("took {:.2?}", Duration::from_millis(ms as u64))
+        });
+        let version = version.map_or("unknown Tobira version".into(), |v| format!("Tobira {v}"));
+        if skipped {
+            bunt::println!("- {}: migration {} `{}` {$yellow+bold}SKIPPED{/$} (by {})",
+                format_time(applied_on), id, name, version);
+        } else {
+            println!("- {}: migration {id} `{name}` ({duration}, applied by {version})",
+                format_time(applied_on));
+        }
+    }
+
+    Ok(())
+}
+
+fn format_time(t: NaiveDateTime) -> String {
+    t.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
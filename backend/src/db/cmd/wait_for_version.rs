@@ -0,0 +1,49 @@
+//! The `db wait-for-version` subcommand.
+
+use std::time::{Duration, Instant};
+
+use crate::{prelude::*, db::{Db, query}};
+use super::summary::Summary;
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: &Db,
+    version: u32,
+    timeout_secs: u64,
+    poll_interval_ms: u64,
+) -> Result<Summary> {
+    let timeout = Duration::from_secs(timeout_secs);
+    let poll_interval = Duration::from_millis(poll_interval_ms);
+    let start = Instant::now();
+
+    info!("Waiting for database to reach migration version {version}...");
+    loop {
+        // Errors are not fatal here: the migrating process might hold a lock
+        // or the DB might be briefly unavailable during a deployment.
+        match current_version(db).await {
+            Ok(current) if current >= version.into() => {
+                info!("Database is at migration version {current} (≥ {version})");
+                return Ok(Summary::WaitForVersion { version: current });
+            }
+            Ok(current) => debug!("Database is at migration version {current}, waiting..."),
+            Err(e) => warn!("Failed to query migration version (will retry): {e:#}"),
+        }
+
+        if start.elapsed() + poll_interval > timeout {
+            bail!("timeout: database did not reach migration version {version} \
+                within {timeout_secs}s");
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Returns the ID of the latest applied migration, or 0 if there is none.
+async fn current_version(db: &Db) -> Result<i64> {
+    if !query::does_table_exist(&**db, "__db_migrations").await? {
+        return Ok(0);
+    }
+
+    let row = db.query_one("select coalesce(max(id), 0) from __db_migrations", &[]).await?;
+    Ok(row.get(0))
+}
@@ -0,0 +1,89 @@
+//! The `db write-amplification` subcommand.
+
+use crate::{prelude::*, db::Db};
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(db: &mut Db, test_table: &str, iterations: u32) -> Result<()> {
+    if iterations == 0 {
+        bail!("`iterations` has to be at least 1");
+    }
+
+    // Everything happens in a transaction that is rolled back at the end, so
+    // the database is not modified. `pg_stat_xact_user_tables` shows the
+    // counters of the current transaction only, so concurrent activity does
+    // not disturb the measurement.
+    let tx = db.transaction().await?;
+    let table = format!("\"{}\"", test_table.replace('"', "\"\""));
+
+    let before = tuple_writes(&tx).await?;
+    let insert = format!("insert into {table} default values");
+    for _ in 0..iterations {
+        tx.execute(&insert, &[]).await.with_context(|| format!(
+            "failed to insert into '{test_table}'. Note: all columns of the test table \
+                need a default value (or be nullable)",
+        ))?;
+    }
+    let after_insert = tuple_writes(&tx).await?;
+
+    // We delete only the rows inserted above: they are the only ones with our
+    // transaction ID as `xmin`. `txid_current` includes the epoch, `xmin` not.
+    let delete = format!(
+        "delete from {table} where xmin::text::bigint = txid_current() % 4294967296",
+    );
+    tx.execute(&delete, &[])
+        .await
+        .with_context(|| format!("failed to delete from '{test_table}'"))?;
+    let after_delete = tuple_writes(&tx).await?;
+
+    tx.rollback().await?;
+
+    let direct = u64::from(iterations);
+    report("insert", test_table, direct, &before, &after_insert);
+    report("delete", test_table, direct, &after_insert, &after_delete);
+
+    Ok(())
+}
+
+/// Number of inserted, updated and deleted tuples per table in the current
+/// transaction.
+async fn tuple_writes(tx: &deadpool_postgres::Transaction<'_>) -> Result<Vec<(String, i64)>> {
+    let rows = tx
+        .query(
+            "select relname::text, n_tup_ins + n_tup_upd + n_tup_del \
+                from pg_stat_xact_user_tables \
+                order by relname",
+            &[],
+        )
+        .await
+        .context("failed to query table statistics")?;
+
+    Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+fn report(
+    op: &str,
+    test_table: &str,
+    direct: u64,
+    before: &[(String, i64)],
+    after: &[(String, i64)],
+) {
+    let mut total = 0;
+    bunt::println!("{$bold}Tuple writes caused by {} {} on '{}':{/$}", direct, op, test_table);
+    for (table, count) in after {
+        let prev = before.iter().find(|(t, _)| t == table).map_or(0, |(_, c)| *c);
+        let diff = count - prev;
+        if diff > 0 {
+            total += diff;
+            bunt::println!(" - {[blue+intense]}: {}", table, diff);
+        }
+    }
+
+    let factor = total as f64 / direct as f64;
+    if factor > 1.0 {
+        bunt::println!("Write amplification factor: {[yellow+bold]}", format!("{factor:.2}"));
+    } else {
+        bunt::println!("Write amplification factor: {[green+bold]}", format!("{factor:.2}"));
+    }
+    println!();
+}
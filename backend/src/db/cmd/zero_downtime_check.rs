@@ -0,0 +1,229 @@
+//! `db migrate --zero-downtime-check`: lists statements of pending
+//! migrations that block access to existing tables.
+
+use std::collections::HashMap;
+
+use crate::{prelude::*, db::{Db, migrations::MIGRATIONS}};
+use super::{
+    migration_annotate_performance::split_statements,
+    migration_estimate_time::{classify, format_estimate, pending_migrations},
+};
+
+
+/// The lock a statement takes on a table, as far as it matters for downtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lock {
+    /// `ACCESS EXCLUSIVE`: blocks all reads and writes.
+    AccessExclusive,
+    /// `SHARE` or `SHARE ROW EXCLUSIVE`: blocks writes, reads still work.
+    BlocksWrites,
+}
+
+impl Lock {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::AccessExclusive => "ACCESS EXCLUSIVE lock (blocks reads and writes)",
+            Self::BlocksWrites => "SHARE lock (blocks writes)",
+        }
+    }
+}
+
+/// Size of an existing table.
+struct TableSize {
+    rows: f64,
+    bytes: f64,
+}
+
+
+/// Implementation of `db migrate --zero-downtime-check`, see that for docs.
+/// Migrations skipped via `--exclude` are not checked.
+pub(crate) async fn run(db: &Db, exclude: &[u64]) -> Result<()> {
+    let pending = pending_migrations(db).await?
+        .into_iter()
+        .filter(|id| !exclude.contains(id))
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        bunt::println!("{$green+intense}No pending migrations.{/$}");
+        return Ok(());
+    }
+
+    // Tables created by pending migrations don't exist yet and count as
+    // empty, just like existing tables without any data.
+    let tables = db
+        .query(
+            "select relname::text, greatest(reltuples, 0)::float8, pg_relation_size(oid)::float8 \
+                from pg_class \
+                where relnamespace = 'public'::regnamespace and relkind in ('r', 'p')",
+            &[],
+        )
+        .await
+        .context("failed to query table sizes")?
+        .into_iter()
+        .map(|row| (row.get::<_, String>(0), TableSize { rows: row.get(1), bytes: row.get(2) }))
+        .collect::<HashMap<_, _>>();
+    let index_tables = db
+        .query(
+            "select i.relname::text, t.relname::text \
+                from pg_index \
+                join pg_class i on i.oid = indexrelid \
+                join pg_class t on t.oid = indrelid \
+                where t.relnamespace = 'public'::regnamespace",
+            &[],
+        )
+        .await
+        .context("failed to query indexes")?
+        .into_iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect::<HashMap<_, _>>();
+    let rows = tables.iter().map(|(name, size)| (name.clone(), size.rows)).collect();
+
+    let mut flagged = 0;
+    for id in pending {
+        let migration = &MIGRATIONS[&id];
+        let mut findings = Vec::new();
+        for span in split_statements(migration.script) {
+            let statement = &migration.script[span];
+            let Some((lock, target)) = locked_table(statement) else {
+                continue;
+            };
+            let table = index_tables.get(&target).cloned().unwrap_or(target);
+            let Some(size) = tables.get(&table).filter(|s| s.bytes > 0.0) else {
+                continue;
+            };
+            findings.push((lock, table, size, classify(statement).estimate(&rows), statement));
+        }
+
+        if findings.is_empty() {
+            bunt::println!("{$bold}{}-{}{/$}: {$green}ok{/$}", id, migration.name);
+            continue;
+        }
+        bunt::println!("{$bold}{}-{}{/$}: {$yellow+bold}blocks tables{/$}", id, migration.name);
+        for (lock, table, size, estimate, statement) in findings {
+            flagged += 1;
+            bunt::println!(
+                "   {} on '{}' ({:.0} rows, {:.1} MiB) for {[blue+intense]}",
+                lock.describe(),
+                table,
+                size.rows,
+                size.bytes / (1024.0 * 1024.0),
+                format_estimate(estimate),
+            );
+            let first_line = statement.lines().next().unwrap_or_default();
+            bunt::println!("     {[dimmed]}", first_line);
+        }
+    }
+
+    println!();
+    if flagged == 0 {
+        bunt::println!("{$green+intense}No pending migration blocks existing data.{/$}");
+    } else {
+        bunt::println!(
+            "{$yellow+bold}Warning:{/$} {} statements block access to non-empty tables while \
+                they run. Requests touching these tables will wait (or time out) during that \
+                time, so consider migrating during a maintenance window.",
+            flagged,
+        );
+        bunt::println!("{$dimmed}Durations are estimated from the table sizes, see \
+            `db migration-estimate-time`. Waiting for the lock itself (e.g. behind long \
+            running queries) comes on top.{/$}");
+    }
+    println!("No migrations were applied.");
+
+    Ok(())
+}
+
+/// Returns the lock the statement takes and the table (or index) it takes it
+/// on, if the statement blocks reads or writes for more than an instant.
+fn locked_table(statement: &str) -> Option<(Lock, String)> {
+    let normalized = statement.to_ascii_lowercase().replace(['(', ')', ',', ';', '"'], " ");
+    let words = normalized.split_whitespace().collect::<Vec<_>>();
+    let starts_with = |seq: &[&str]| words.starts_with(seq);
+    let has_seq = |seq: &[&str]| words.windows(seq.len()).any(|w| w == seq);
+    let after = |word: &str| {
+        words.iter()
+            .position(|w| *w == word)
+            .and_then(|i| {
+                words[i + 1..].iter().find(|w| !["only", "if", "exists", "table"].contains(*w))
+            })
+            .map(|name| name.trim_start_matches("public.").to_owned())
+    };
+
+    let (lock, name) = if starts_with(&["alter", "table"]) {
+        // Validating constraints only takes a `SHARE UPDATE EXCLUSIVE` lock,
+        // which is why constraints are added as `not valid` first.
+        if has_seq(&["validate", "constraint"]) {
+            return None;
+        }
+        (Lock::AccessExclusive, after("table"))
+    } else if starts_with(&["create", "index"]) || starts_with(&["create", "unique", "index"]) {
+        if words.contains(&"concurrently") {
+            return None;
+        }
+        (Lock::BlocksWrites, after("on"))
+    } else if starts_with(&["drop", "index"]) {
+        if words.contains(&"concurrently") {
+            return None;
+        }
+        (Lock::AccessExclusive, after("index"))
+    } else if starts_with(&["create", "trigger"])
+        || starts_with(&["create", "or", "replace", "trigger"])
+        || starts_with(&["create", "constraint", "trigger"])
+    {
+        (Lock::BlocksWrites, after("on"))
+    } else if starts_with(&["drop", "trigger"]) {
+        (Lock::AccessExclusive, after("on"))
+    } else if starts_with(&["drop", "table"]) {
+        (Lock::AccessExclusive, after("drop"))
+    } else if starts_with(&["truncate"]) {
+        (Lock::AccessExclusive, after("truncate"))
+    } else if starts_with(&["lock"]) {
+        // Without explicit mode, `lock` takes an `ACCESS EXCLUSIVE` lock.
+        let lock = match words.contains(&"mode") && !has_seq(&["access", "exclusive"]) {
+            true => Lock::BlocksWrites,
+            false => Lock::AccessExclusive,
+        };
+        (lock, after("lock"))
+    } else if starts_with(&["vacuum", "full"]) || starts_with(&["cluster"]) {
+        (Lock::AccessExclusive, words.last().map(|t| t.trim_start_matches("public.").to_owned()))
+    } else if starts_with(&["reindex", "table"]) && !words.contains(&"concurrently") {
+        (Lock::BlocksWrites, after("table"))
+    } else {
+        return None;
+    };
+
+    name.map(|name| (lock, name))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{locked_table, Lock};
+
+    #[test]
+    fn detects_locks() {
+        let exclusive = |table: &str| Some((Lock::AccessExclusive, table.to_owned()));
+        let writes = |table: &str| Some((Lock::BlocksWrites, table.to_owned()));
+        let cases = [
+            ("alter table events add column foo text not null default '';", exclusive("events")),
+            ("alter table only public.series drop column x;", exclusive("series")),
+            ("alter table blocks validate constraint c;", None),
+            ("create index idx_foo on events (created);", writes("events")),
+            ("create unique index on \"realms\"(path);", writes("realms")),
+            ("create index concurrently idx on events (x);", None),
+            ("drop index idx_events_series;", exclusive("idx_events_series")),
+            ("drop table if exists foo, bar;", exclusive("foo")),
+            ("truncate table search_index_queue;", exclusive("search_index_queue")),
+            ("create trigger t after update on realms for each row execute \
+                procedure f();", writes("realms")),
+            ("lock table events in share mode;", writes("events")),
+            ("lock events;", exclusive("events")),
+            ("create table foo (id bigint);", None),
+            ("create function f() returns void language sql as $$ \
+                create index i on events (x); $$;", None),
+            ("update events set title = '';", None),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(locked_table(statement), expected, "{statement}");
+        }
+    }
+}
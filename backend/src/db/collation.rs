@@ -0,0 +1,130 @@
+//! Checks regarding encoding and collation of the database. A bad collation
+//! (e.g. `C`) breaks sorting of non-ASCII characters, and collation version
+//! changes (e.g. after OS or PostgreSQL upgrades) can corrupt indexes.
+
+use std::fmt;
+
+use crate::prelude::*;
+use super::Db;
+
+
+/// `pg_database.datcollversion` only exists since PostgreSQL 15.
+const PG_DATABASE_COLLVERSION_VERSION: i32 = 150000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Warning,
+    Error,
+}
+
+pub(crate) struct Finding {
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Inspects encoding, locale and collation versions of the current database.
+pub(crate) async fn check(db: &Db) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let mut add = |severity, message: String| findings.push(Finding { severity, message });
+
+    let row = db
+        .query_one(
+            "select pg_encoding_to_char(encoding)::text, datcollate::text, datctype::text, \
+                    current_setting('server_version_num')::int \
+                from pg_database \
+                where datname = current_database()",
+            &[],
+        )
+        .await
+        .context("failed to query database encoding and locale")?;
+    let encoding: String = row.get(0);
+    let collate: String = row.get(1);
+    let ctype: String = row.get(2);
+    let version: i32 = row.get(3);
+
+    if encoding != "UTF8" {
+        add(Severity::Error, format!("database encoding is '{encoding}', but has to be 'UTF8'"));
+    }
+    for (name, value) in [("LC_COLLATE", &collate), ("LC_CTYPE", &ctype)] {
+        if !is_recommended_locale(value) {
+            add(Severity::Warning, format!(
+                "{name} of the database is '{value}': sorting and case-insensitive matching \
+                    of non-ASCII characters (e.g. umlauts) will likely be wrong. Consider \
+                    recreating the database with a UTF-8 locale, e.g. 'en_US.UTF-8'.",
+            ));
+        }
+    }
+
+    // Collations whose version recorded at creation differs from the one the
+    // OS/ICU library currently provides. Indexes using those might be corrupt.
+    let mismatches = db
+        .query(
+            "select collname::text, collversion, pg_collation_actual_version(oid) \
+                from pg_collation \
+                where collversion is not null \
+                    and collversion is distinct from pg_collation_actual_version(oid)",
+            &[],
+        )
+        .await
+        .context("failed to query collation versions")?;
+    for row in mismatches {
+        let name: String = row.get(0);
+        let recorded: String = row.get(1);
+        let actual: Option<String> = row.get(2);
+        add(Severity::Error, format!(
+            "collation '{name}' has version {} but was created with version {recorded}: \
+                indexes using it may be corrupted. Run `reindex database` and then \
+                `alter collation \"{name}\" refresh version`.",
+            actual.as_deref().unwrap_or("(unknown)"),
+        ));
+    }
+
+    if version >= PG_DATABASE_COLLVERSION_VERSION {
+        let row = db
+            .query_one(
+                "select datcollversion, pg_database_collation_actual_version(oid) \
+                    from pg_database \
+                    where datname = current_database()",
+                &[],
+            )
+            .await
+            .context("failed to query database collation version")?;
+        let recorded: Option<String> = row.get(0);
+        let actual: Option<String> = row.get(1);
+        if let (Some(recorded), Some(actual)) = (recorded, actual) {
+            if recorded != actual {
+                add(Severity::Error, format!(
+                    "the database's default collation has version {actual} but was \
+                        created with version {recorded}: indexes may be corrupted. Run \
+                        `reindex database` and then `alter database refresh collation version`.",
+                ));
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Logs all findings of `check`. Used on startup; does not fail.
+pub(crate) async fn log_findings(db: &Db) {
+    match check(db).await {
+        Err(e) => warn!("Could not check database encoding/collation: {e:#}"),
+        Ok(findings) => for finding in findings {
+            match finding.severity {
+                Severity::Warning => warn!("{finding}"),
+                Severity::Error => error!("{finding}"),
+            }
+        },
+    }
+}
+
+fn is_recommended_locale(locale: &str) -> bool {
+    let lower = locale.to_lowercase();
+    lower != "c" && lower != "posix" && (lower.contains("utf-8") || lower.contains("utf8"))
+}
@@ -7,3 +7,15 @@ create table if not exists __db_migrations (
     applied_on timestamp not null,
     script text not null
 );
+
+-- These were added later, so they might be missing in existing tables. They
+-- are null for migrations applied before that (or marked as applied without
+-- running them).
+alter table __db_migrations
+    add column if not exists duration_ms bigint,
+    add column if not exists tobira_version text,
+    -- Migrations skipped via `db migrate --exclude` are recorded with `true`.
+    add column if not exists skipped boolean not null default false,
+    -- Set if the script was moved to an archive via `db migrate
+    -- --compress-logs`. `script` is empty then.
+    add column if not exists script_sha256 text;
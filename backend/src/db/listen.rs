@@ -0,0 +1,67 @@
+//! Receiving notifications sent via `NOTIFY`.
+
+use futures::{channel::mpsc, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::{AsyncMessage, Client, Connection, NoTls};
+
+use crate::prelude::*;
+use super::{pool_config, tls_config, DbConfig};
+
+
+/// Opens a dedicated connection (notifications cannot be received via the
+/// pool), listens on `channel` and calls `on_notification` with the payload
+/// of each notification. Only returns if the connection fails, so callers
+/// usually want to call this again after some time.
+pub(crate) async fn listen(
+    config: &DbConfig,
+    channel: &str,
+    mut on_notification: impl FnMut(&str),
+) -> Result<()> {
+    let pg_config = pool_config(config).get_pg_config()
+        .context("invalid DB configuration")?;
+    match tls_config(config)? {
+        None => {
+            let connection = pg_config.connect(NoTls).await?;
+            listen_on(connection, channel, &mut on_notification).await
+        }
+        Some(tls_config) => {
+            let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
+            let connection = pg_config.connect(tls).await?;
+            listen_on(connection, channel, &mut on_notification).await
+        }
+    }
+}
+
+async fn listen_on<S, T>(
+    (client, mut connection): (Client, Connection<S, T>),
+    channel: &str,
+    on_notification: &mut impl FnMut(&str),
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // The connection has to be polled for the client to make progress, which
+    // is also how notifications are received.
+    let (tx, mut rx) = mpsc::unbounded();
+    let driver = tokio::spawn(async move {
+        while let Some(message) = futures::future::poll_fn(|cx| connection.poll_message(cx)).await {
+            if let AsyncMessage::Notification(notification) = message? {
+                let _ = tx.unbounded_send(notification.payload().to_owned());
+            }
+        }
+        Ok::<_, tokio_postgres::Error>(())
+    });
+
+    client.batch_execute(&format!("listen {channel}")).await
+        .with_context(|| format!("failed to listen on channel '{channel}'"))?;
+    debug!("Listening for notifications on channel '{channel}'");
+
+    // Ends when the connection is closed, as that drops the sender.
+    while let Some(payload) = rx.next().await {
+        on_notification(&payload);
+    }
+
+    driver.await?.context("DB connection for notifications failed")?;
+    bail!("DB connection for notifications was closed");
+}
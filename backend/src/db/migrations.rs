@@ -1,18 +1,136 @@
 use chrono::{DateTime, Utc, offset::TimeZone};
 use deadpool_postgres::Transaction;
+use hyper::Uri;
 use once_cell::sync::Lazy;
-use std::{collections::BTreeMap, time::Duration, num::NonZeroU64};
-use tokio_postgres::{IsolationLevel, error::SqlState};
+use ring::digest::{self, Context, SHA256};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio_postgres::{GenericClient, IsolationLevel, error::SqlState, types::ToSql};
 
 use crate::{prelude::*, db::util::select};
-use super::Db;
+use super::{Db, DbConfig, schema_snapshot};
 
 
+/// Options for running migrations. The defaults are used when migrations run
+/// automatically at server start.
+#[derive(Debug, Default, clap::Args)]
+pub(crate) struct MigrateOptions {
+    /// If specified, the before-image of every row that is updated or deleted
+    /// by a migration is written to this file as JSON Lines. Each line
+    /// contains the migration ID, table name, operation and the old row.
+    ///
+    /// Rows affected by DDL (e.g. `drop table` or `truncate`) are not
+    /// captured as no row-level triggers fire for those.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) generate_undo_log: Option<PathBuf>,
+
+    /// If specified, each migration is committed in its own transaction and
+    /// followed by a `CHECKPOINT`. This limits how much WAL accumulates when
+    /// applying many migrations, but means that a failing migration does not
+    /// roll back the ones before it. Requires superuser privileges: without
+    /// them, a warning is emitted and migrations run as usual.
+    #[clap(long)]
+    pub(crate) checkpoint_after_each: bool,
+
+    /// If specified, all migrations that are not yet recorded in
+    /// `__db_migrations` are recorded as applied *without executing them*.
+    /// This is for adopting a database whose schema was set up outside of
+    /// Tobira. Only use this if you are sure the schema exactly matches
+    /// what the migrations would produce!
+    #[clap(long, conflicts_with_all = &["generate-undo-log", "checkpoint-after-each", "exclude"])]
+    pub(crate) mark_all_done: bool,
+
+    /// Comma-separated IDs of migrations to skip, e.g. because they import
+    /// data from a system that does not exist in this deployment. Skipped
+    /// migrations are recorded as such in `__db_migrations` and are never
+    /// applied later. Dangerous: later migrations might rely on what the
+    /// skipped ones create, and the schema might end up incomplete!
+    #[clap(long, value_name = "VERSIONS", value_delimiter = ',')]
+    pub(crate) exclude: Vec<u64>,
+
+    /// If specified, an empty database is set up by running all migrations
+    /// one by one instead of applying the schema snapshot (`schema.sql`)
+    /// generated from them, which is a lot faster. Mainly useful to test
+    /// the migrations themselves.
+    #[clap(long)]
+    pub(crate) no_schema_snapshot: bool,
+
+    /// If specified, a JSON object is sent to this URL via `POST` after all
+    /// migrations were applied successfully, e.g. to notify a deployment
+    /// tracker. It contains the number of applied migrations (`applied`),
+    /// the resulting schema version (`schemaVersion`) and the Tobira version
+    /// (`tobiraVersion`). If sending fails, only a warning is emitted. Alerts
+    /// of `--alert-on-lock-wait` are sent to this URL as well.
+    #[clap(long, value_name = "URL", value_parser = parse_webhook_url)]
+    pub(crate) webhook: Option<Uri>,
+
+    /// If specified, an alert is printed to stderr (and sent to `--webhook`,
+    /// if given) when a migration waits for a lock for longer than this many
+    /// seconds, e.g. because another client keeps a transaction open. The
+    /// alert names the sessions holding the lock and their current query.
+    #[clap(long, value_name = "SECS")]
+    pub(crate) alert_on_lock_wait: Option<u64>,
+
+    /// If specified, the scripts of all but the latest `--keep-scripts`
+    /// migrations are moved from `__db_migrations` to this zstd-compressed
+    /// file after migrating. Only their checksums stay in the database, which
+    /// is enough to verify them. The file is merged with if it already
+    /// exists, so always pass the same file. Archived scripts can be printed
+    /// with `db migration-export`. This only shrinks the database: the binary
+    /// still contains all scripts, as fresh databases are migrated with them.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) compress_logs: Option<PathBuf>,
+
+    /// Number of latest migrations whose scripts are kept in the database
+    /// with `--compress-logs`.
+    #[clap(long, value_name = "N", default_value = "10", requires = "compress-logs")]
+    pub(crate) keep_scripts: u64,
+
+    /// If specified, `NOTIFY <CHANNEL>, 'version=N'` is sent after migrations
+    /// were applied and committed successfully, where `N` is the resulting
+    /// schema version. That way, other applications can `LISTEN` for schema
+    /// changes. Nothing is sent if there were no migrations to apply. The
+    /// channel name is case-sensitive (like a quoted identifier).
+    #[clap(long, value_name = "CHANNEL", value_parser = parse_notify_channel)]
+    pub(crate) notify_channel: Option<String>,
+
+    /// If specified, no migrations are applied. Instead, statements of pending
+    /// migrations that lock non-empty tables are listed, e.g. `alter table`
+    /// or `create index` without `concurrently`, together with the lock
+    /// they take and how long they are estimated to hold it based on the
+    /// table sizes. Use this to decide whether migrating needs downtime.
+    #[clap(long, conflicts_with_all = &["mark-all-done", "generate-undo-log", "compress-logs"])]
+    pub(crate) zero_downtime_check: bool,
+}
+
+fn parse_webhook_url(s: &str) -> Result<Uri, String> {
+    let uri = s.parse::<Uri>().map_err(|e| e.to_string())?;
+    match uri.scheme_str() {
+        Some("http" | "https") if uri.authority().is_some() => Ok(uri),
+        _ => Err("has to be an absolute HTTP or HTTPS URL".into()),
+    }
+}
+
+fn parse_notify_channel(s: &str) -> Result<String, String> {
+    // Channel names are identifiers, which Postgres truncates to 63 bytes.
+    match s.len() {
+        0 => Err("must not be empty".into()),
+        1..=63 => Ok(s.to_owned()),
+        _ => Err("must not be longer than 63 bytes".into()),
+    }
+}
+
 /// Describes the actions needed to bring the database into a state that we
 /// expect.
 pub(crate) enum MigrationPlan {
-    /// The database is completely empty: we need to create the meta table and
-    /// apply all migrations.
+    /// The database is completely empty (apart from an empty meta table): we
+    /// need to create the meta table and apply all migrations.
     EmptyDb,
 
     /// The database is completely up to date and all migrations match.
@@ -51,12 +169,20 @@ impl MigrationPlan {
             name: String,
             applied_on: DateTime<Utc>,
             script: String,
+            script_sha256: Option<String>,
         }
 
         debug!("Checking DB migrations");
 
         // Retrieve all active migrations from the DB.
-        let (selection, mapping) = select!(id, name, applied_on, script);
+        // `script_sha256` does not exist in tables created by older versions.
+        let (selection, mapping) = select!(
+            id,
+            name,
+            applied_on,
+            script,
+            script_sha256: "to_jsonb(__db_migrations) ->> 'script_sha256'",
+        );
         let query = format!("select {selection} from __db_migrations");
         let active_migrations = tx
             .query_raw(&query, dbargs![])
@@ -68,12 +194,22 @@ impl MigrationPlan {
                     name: mapping.name.of(&row),
                     applied_on: Utc.from_utc_datetime(&mapping.applied_on.of(&row)),
                     script: mapping.script.of(&row),
+                    script_sha256: mapping.script_sha256.of(&row),
                 }
             ))
             .try_collect::<BTreeMap<_, _>>()
             .await?;
 
 
+        // `migrate` creates the meta table before building the plan. Without
+        // any migrations and other tables, the database is still empty.
+        if active_migrations.is_empty() {
+            let tables = super::query::all_table_names(&**tx).await?;
+            if tables.iter().all(|table| table == "__db_migrations") {
+                return Ok(Self::EmptyDb);
+            }
+        }
+
         // Make sure the IDs are consecutive
         if !active_migrations.keys().copied().eq(1..active_migrations.len() as u64 + 1) {
             bail!("The IDs of the active migrations are not consecutive. This is unexpected.");
@@ -89,7 +225,12 @@ impl MigrationPlan {
                 actual_migration.applied_on,
             ))?;
 
-            if actual_migration.script != expected_migration.script {
+            let matches = match &actual_migration.script_sha256 {
+                // The script was archived via `--compress-logs`.
+                Some(sha256) => *sha256 == checksum(expected_migration.script),
+                None => actual_migration.script == expected_migration.script,
+            };
+            if !matches {
                 debug!(
                     "Expected script for '{}-{}':\n{}",
                     id,
@@ -121,43 +262,154 @@ impl MigrationPlan {
         }
     }
 
+    /// Returns the number of migrations that are not applied yet.
+    pub(crate) fn pending_migrations(&self) -> u64 {
+        match self {
+            Self::EmptyDb => MIGRATIONS.len() as u64,
+            Self::UpToDate => 0,
+            Self::Migrate { new_migrations } => new_migrations.get(),
+        }
+    }
+
     /// Executes this plan on the database, bringing it into the state we expect.
-    pub(crate) async fn execute(&self, tx: &Transaction<'_>) -> Result<()> {
+    /// If `limit` is given, at most that many migrations are applied. Returns
+    /// the number of applied migrations, including skipped ones.
+    pub(crate) async fn execute(
+        &self,
+        tx: &Transaction<'_>,
+        options: &MigrateOptions,
+        limit: Option<u64>,
+    ) -> Result<u64> {
         let new_migrations = match self {
             Self::UpToDate => {
                 info!("All migrations are already applied: database schema is up to date.");
-                return Ok(());
+                return Ok(0);
             }
             Self::EmptyDb => {
                 create_meta_table_if_missing(tx).await?;
+                let use_snapshot = limit.is_none()
+                    && options.exclude.is_empty()
+                    && !options.mark_all_done
+                    && !options.no_schema_snapshot;
+                if use_snapshot {
+                    match schema_snapshot::current() {
+                        Some(snapshot) => return apply_schema_snapshot(tx, snapshot).await,
+                        None => warn!("The schema snapshot does not match the migrations: \
+                            applying all migrations one by one instead. Run \
+                            `tobira db generate-schema-snapshot` to update it."),
+                    }
+                }
                 MIGRATIONS.len() as u64
             }
             Self::Migrate { new_migrations } => new_migrations.get(),
         };
 
         // Apply missing migrations in order.
-        info!("The database is missing {new_migrations} migrations. Applying them now.");
-        for (id, migration) in MIGRATIONS.range(MIGRATIONS.len() as u64 - new_migrations + 1..) {
+        let to_apply = limit.map_or(new_migrations, |limit| limit.min(new_migrations));
+        let first = MIGRATIONS.len() as u64 - new_migrations + 1;
+        if options.mark_all_done {
+            warn!("Marking {to_apply} migrations as applied without executing them \
+                (`--mark-all-done`)");
+        } else {
+            info!("The database is missing {new_migrations} migrations. Applying {to_apply} now.");
+        }
+        for (id, migration) in MIGRATIONS.range(first..first + to_apply) {
+            if options.mark_all_done {
+                debug!("Marking migration '{}-{}' as applied", id, migration.name);
+                record_migration(tx, *id, migration, None, false).await?;
+                continue;
+            }
+
+            if options.exclude.contains(id) {
+                warn!("SKIPPING migration '{}-{}' as requested via `--exclude`! It will never \
+                    be applied to this database. The schema might be incomplete now.",
+                    id, migration.name);
+                record_migration(tx, *id, migration, None, true).await?;
+                continue;
+            }
+
             debug!("Applying migration '{}-{}' ...", id, migration.name);
+            check_preconditions(tx, *id, migration).await?;
             trace!("Executing:\n{}", migration.script);
 
+            if options.generate_undo_log.is_some() {
+                undo_log::install_triggers(tx, *id).await?;
+            }
+
+            let before = Instant::now();
             tx.batch_execute(migration.script)
                 .await
                 .context(format!("failed to run script for '{}-{}'", id, migration.name))?;
+            let duration = before.elapsed();
 
-            let query = "insert into __db_migrations (id, name, applied_on, script) \
-                values ($1, $2, now() at time zone 'utc', $3)";
-            tx.execute(query, &[&(*id as i64), &migration.name, &migration.script])
-                .await
-                .context("failed to update __db_migrations")?;
+            if options.generate_undo_log.is_some() {
+                undo_log::remove_triggers(tx).await?;
+            }
+
+            record_migration(tx, *id, migration, Some(duration), false).await?;
         }
 
-        info!("Applied {new_migrations} migrations. DB is up to date now.");
+        if options.mark_all_done {
+            info!("Marked {to_apply} migrations as applied. DB is considered up to date now.");
+        } else if to_apply == new_migrations {
+            info!("Applied {new_migrations} migrations. DB is up to date now.");
+        } else {
+            info!("Applied {to_apply} migrations, {} remaining.", new_migrations - to_apply);
+        }
 
-        Ok(())
+        Ok(to_apply)
     }
 }
 
+/// Sets up the empty database by applying the schema snapshot and records
+/// all migrations as applied. Returns the number of migrations.
+async fn apply_schema_snapshot(tx: &Transaction<'_>, snapshot: &str) -> Result<u64> {
+    info!("The database is empty. Applying schema snapshot of all {} migrations now.",
+        MIGRATIONS.len());
+    let before = Instant::now();
+    tx.batch_execute(snapshot).await.context("failed to apply schema snapshot")?;
+    debug!("Applied schema snapshot in {:?}", before.elapsed());
+
+    // Like `pg_dump` output, the snapshot clears the search path, as it only
+    // uses qualified names.
+    tx.batch_execute("reset search_path").await?;
+    for (id, migration) in MIGRATIONS.iter() {
+        record_migration(tx, *id, migration, None, false).await?;
+    }
+
+    info!("Applied {} migrations. DB is up to date now.", MIGRATIONS.len());
+    Ok(MIGRATIONS.len() as u64)
+}
+
+/// Adds the given migration to `__db_migrations`, together with how long it
+/// took to run (`None` if it was not actually run), whether it was skipped
+/// and the Tobira version.
+async fn record_migration(
+    tx: &Transaction<'_>,
+    id: u64,
+    migration: &Migration,
+    duration: Option<Duration>,
+    skipped: bool,
+) -> Result<()> {
+    let query = "insert into __db_migrations \
+        (id, name, applied_on, script, duration_ms, tobira_version, skipped) \
+        values ($1, $2, now() at time zone 'utc', $3, $4, $5, $6)";
+    let duration_ms = duration.map(|d| d.as_millis() as i64);
+    let version = crate::version::identifier();
+    let args: &[&(dyn ToSql + Sync)] = &[
+        &(id as i64),
+        &migration.name,
+        &migration.script,
+        &duration_ms,
+        &version,
+        &skipped,
+    ];
+    tx.execute(query, args)
+        .await
+        .context("failed to update __db_migrations")?;
+    Ok(())
+}
+
 async fn create_meta_table_if_missing(tx: &Transaction<'_>) -> Result<()> {
     debug!("Creating table '__db_migrations' if it does not exist yet...");
     tx.batch_execute(include_str!("db-migrations.sql"))
@@ -166,12 +418,230 @@ async fn create_meta_table_if_missing(tx: &Transaction<'_>) -> Result<()> {
     Ok(())
 }
 
+/// Runs `migrate` if `db.auto_migrate` is enabled. Otherwise, only checks
+/// that all migrations are applied and returns an error if not. That check
+/// only reads from the DB, so it works without any schema modification rights.
+pub(crate) async fn auto_migrate(db: &mut Db, config: &DbConfig) -> Result<()> {
+    if config.auto_migrate {
+        return migrate(db, &MigrateOptions::default()).await.map(|_| ());
+    }
+
+    let tx = db.build_transaction().read_only(true).start().await?;
+    let plan = MigrationPlan::build(&tx).await?;
+    tx.commit().await?;
+    match plan {
+        MigrationPlan::UpToDate => {
+            info!("All migrations are applied: database schema is up to date.");
+            Ok(())
+        }
+        MigrationPlan::EmptyDb => bail!(
+            "the database is empty and `db.auto_migrate` is disabled: run \
+                `tobira db migrate` (with appropriate credentials) first",
+        ),
+        MigrationPlan::Migrate { new_migrations } => bail!(
+            "the database is missing {new_migrations} migrations and `db.auto_migrate` is \
+                disabled: run `tobira db migrate` (with appropriate credentials) first",
+        ),
+    }
+}
+
 /// Makes sure the database schema is up to date by checking the active
 /// migrations and applying all missing ones.
 ///
 /// If anything unexpected is noticed, an error is returned to notify the user
-/// they have to manually deal with it.
-pub async fn migrate(db: &mut Db) -> Result<()> {
+/// they have to manually deal with it. Returns the number of applied
+/// migrations.
+pub async fn migrate(db: &mut Db, options: &MigrateOptions) -> Result<u64> {
+    let applied = apply_migrations(db, options).await?;
+    backfill_text_html(db).await.context("failed to render HTML of text blocks")?;
+    if let Some(path) = &options.compress_logs {
+        script_archive::archive(db, path, options.keep_scripts).await
+            .context("failed to archive migration scripts")?;
+    }
+    if let Some(url) = &options.webhook {
+        notify_webhook(db, url, applied).await;
+    }
+    if let (Some(channel), true) = (&options.notify_channel, applied > 0) {
+        notify_channel(db, channel).await;
+    }
+    Ok(applied)
+}
+
+/// Renders the HTML of text blocks saved before migration 29 added it. This
+/// can't be done in the migration itself, as Markdown is rendered in Rust.
+/// Once all blocks are rendered, this does nothing.
+async fn backfill_text_html(db: &Db) -> Result<()> {
+    let blocks = db
+        .query(
+            "select id, text_content from blocks where type = 'text' and text_html is null",
+            &[],
+        )
+        .await?;
+    for row in &blocks {
+        let html = crate::markdown::render(row.get(1));
+        db.execute(
+            "update blocks set text_html = $2 where id = $1",
+            &[&row.get::<_, i64>(0), &html],
+        ).await?;
+    }
+    if !blocks.is_empty() {
+        info!("Rendered HTML of {} text blocks", blocks.len());
+    }
+    Ok(())
+}
+
+/// Returns the number of applied migrations.
+async fn apply_migrations(db: &mut Db, options: &MigrateOptions) -> Result<u64> {
+    check_excluded(db, &options.exclude).await?;
+
+    // The undo log file is created before anything is applied, so that an
+    // unwritable path does not leave us with applied migrations but no log.
+    let mut undo_log = options.generate_undo_log.as_deref()
+        .map(undo_log::Log::create)
+        .transpose()?;
+
+    if options.checkpoint_after_each {
+        let is_superuser = db
+            .query_one("select rolsuper from pg_roles where rolname = current_user", &[])
+            .await
+            .context("failed to check whether DB user is superuser")?
+            .get::<_, bool>(0);
+
+        if is_superuser {
+            return migrate_with_checkpoints(db, options, undo_log.as_mut()).await;
+        }
+        warn!("`--checkpoint-after-each` requires the DB user to be a superuser, \
+            which it is not. Applying migrations without checkpoints.");
+    }
+
+    migrate_tx(db, options, None, undo_log.as_mut()).await
+}
+
+/// Makes sure all migrations passed to `--exclude` exist and are not applied
+/// yet, as excluding them would have no effect otherwise.
+async fn check_excluded(db: &Db, exclude: &[u64]) -> Result<()> {
+    if exclude.is_empty() {
+        return Ok(());
+    }
+    if let Some(id) = exclude.iter().find(|id| !MIGRATIONS.contains_key(id)) {
+        bail!("cannot exclude migration {id}: it does not exist");
+    }
+
+    if super::query::does_table_exist(&**db, "__db_migrations").await? {
+        let ids = exclude.iter().map(|&id| id as i64).collect::<Vec<_>>();
+        let applied = db
+            .query("select id from __db_migrations where id = any($1) order by id", &[&ids])
+            .await
+            .context("failed to query __db_migrations")?;
+        if let Some(row) = applied.first() {
+            bail!("cannot exclude migration {}: it is already applied", row.get::<_, i64>(0));
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies one migration per transaction, issuing a `CHECKPOINT` after each
+/// commit. The undo log is rewritten with each transaction so that it is
+/// complete for all committed migrations even if a later one fails. Returns
+/// the number of applied migrations.
+async fn migrate_with_checkpoints(
+    db: &mut Db,
+    options: &MigrateOptions,
+    mut undo_log: Option<&mut undo_log::Log>,
+) -> Result<u64> {
+    let mut total = 0;
+    loop {
+        let applied = migrate_tx(db, options, Some(1), undo_log.as_deref_mut()).await?;
+        if applied == 0 {
+            return Ok(total);
+        }
+        total += applied;
+
+        debug!("Issuing checkpoint...");
+        db.batch_execute("checkpoint").await.context("failed to issue checkpoint")?;
+    }
+}
+
+/// Sends the notification configured via `--webhook`. Failures are only
+/// logged, as the migrations themselves succeeded.
+async fn notify_webhook(db: &Db, url: &Uri, applied: u64) {
+    match send_webhook(db, url, applied).await {
+        Ok(()) => info!("Sent migration notification to webhook '{url}'"),
+        Err(e) => warn!("Failed to send migration notification to webhook '{url}': {e:#}"),
+    }
+}
+
+async fn send_webhook(db: &Db, url: &Uri, applied: u64) -> Result<()> {
+    let schema_version = schema_version(db).await?;
+    let body = serde_json::json!({
+        "applied": applied,
+        "schemaVersion": schema_version,
+        "tobiraVersion": crate::version::identifier(),
+    });
+
+    post_webhook(url, &body).await
+}
+
+async fn notify_channel(db: &Db, channel: &str) {
+    let result = async {
+        let version = schema_version(db).await?
+            .ok_or_else(|| anyhow!("no migrations in '__db_migrations'"))?;
+        // `pg_notify` is equivalent to `NOTIFY` with a quoted channel name.
+        db.execute("select pg_notify($1, $2)", &[&channel, &format!("version={version}")])
+            .await?;
+        Ok::<_, anyhow::Error>(version)
+    }.await;
+
+    // Like with the webhook, migrations were already committed, so there is
+    // no point in failing.
+    match result {
+        Ok(version) => info!("Sent 'version={version}' to notification channel '{channel}'"),
+        Err(e) => warn!("Failed to notify channel '{channel}' about migrations: {e:#}"),
+    }
+}
+
+async fn schema_version(db: &Db) -> Result<Option<i64>> {
+    let version = db.query_one("select max(id) from __db_migrations", &[])
+        .await
+        .context("failed to query schema version")?
+        .get::<_, Option<i64>>(0);
+    Ok(version)
+}
+
+/// Sends `body` as JSON to the URL given via `--webhook`.
+pub(super) async fn post_webhook(url: &Uri, body: &serde_json::Value) -> Result<()> {
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+    let req = hyper::Request::post(url.clone())
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string().into())
+        .expect("failed to build webhook request");
+    let response = tokio::time::timeout(TIMEOUT, client.request(req))
+        .await
+        .map_err(|_| anyhow!("timed out after {TIMEOUT:?}"))??;
+
+    if !response.status().is_success() {
+        bail!("server responded with status {}", response.status());
+    }
+    Ok(())
+}
+
+/// Runs the migration transaction, applying at most `limit` migrations.
+/// Returns the number of applied migrations. If given, the undo log is
+/// written before committing.
+async fn migrate_tx(
+    db: &mut Db,
+    options: &MigrateOptions,
+    limit: Option<u64>,
+    mut undo_log: Option<&mut undo_log::Log>,
+) -> Result<u64> {
     // The whole migration process is wrapped in one serializable transaction.
     // This guarantees that only one Tobira node ever does the migrations. As
     // this only happens during startup, the potential slow down from such a
@@ -222,10 +692,23 @@ pub async fn migrate(db: &mut Db) -> Result<()> {
         // We are now the only process allowed to tinker with migrations. First
         // build a plan of what needs to be done and then execute it.
         let plan = MigrationPlan::build(&tx).await?;
-        plan.execute(&tx).await?;
+        if undo_log.is_some() {
+            undo_log::prepare(&tx).await?;
+        }
+        let applied = plan.execute(&tx, options, limit).await?;
+        if let Some(log) = &mut undo_log {
+            // Written before committing, so that the migrations are rolled
+            // back if writing fails.
+            log.write(undo_log::finish(&tx).await?)?;
+        }
 
         match tx.commit().await {
-            Ok(_) => return Ok(()),
+            Ok(_) => {
+                if let Some(log) = &mut undo_log {
+                    log.commit();
+                }
+                return Ok(applied);
+            }
 
             Err(e) if e.code() == Some(&SqlState::T_R_SERIALIZATION_FAILURE) => {
                 let backoff_duration = Duration::from_millis(500);
@@ -245,41 +728,367 @@ pub async fn migrate(db: &mut Db) -> Result<()> {
     }
 }
 
-/// Implementation of subcommand with same name, see that for docs.
-pub(crate) async fn unsafe_overwrite_migrations(db: &mut Db) -> Result<()> {
+/// Capturing the before-image of all rows modified by migrations. This works
+/// by installing a row-level trigger on every table before each migration
+/// that copies the old row into a log table. Everything happens inside the
+/// migration transaction, so no helper objects outlive it.
+mod undo_log {
+    use std::io::Seek;
+
+    use super::*;
+
+    const TRIGGER: &str = "__tobira_undo_log";
+    const TABLE: &str = "__tobira_undo_log_rows";
+
+    /// One row that was updated or deleted by a migration.
+    pub(super) struct Entry {
+        migration: i64,
+        table: String,
+        op: String,
+        old_row: serde_json::Value,
+    }
+
+    /// Creates the log table and trigger function.
+    pub(super) async fn prepare(tx: &Transaction<'_>) -> Result<()> {
+        tx.batch_execute(&format!("
+            create table {TABLE} (
+                seq bigint generated always as identity primary key,
+                migration bigint not null,
+                table_name text not null,
+                op text not null,
+                old_row jsonb not null
+            );
+
+            create function {TRIGGER}() returns trigger language plpgsql as $$
+            begin
+                insert into {TABLE} (migration, table_name, op, old_row)
+                    values (tg_argv[0]::bigint, tg_table_name, tg_op, to_jsonb(old));
+                return null;
+            end;
+            $$;
+        ")).await.context("failed to prepare undo log")?;
+        Ok(())
+    }
+
+    /// Installs the trigger on all tables that currently exist. Needs to be
+    /// called before each migration, as earlier migrations might have created
+    /// new tables.
+    pub(super) async fn install_triggers(tx: &Transaction<'_>, migration: u64) -> Result<()> {
+        let tables = crate::db::query::all_table_names(&**tx).await?;
+        for table in tables.iter().filter(|t| *t != TABLE && *t != "__db_migrations") {
+            tx.execute(
+                &format!("create trigger {TRIGGER} after update or delete on {} \
+                    for each row execute function {TRIGGER}('{migration}')",
+                    crate::db::query::qualified(table)),
+                &[],
+            ).await.with_context(|| format!("failed to install undo log trigger on '{table}'"))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the triggers from all tables that still have one. Tables might
+    /// have been renamed or dropped by the migration.
+    pub(super) async fn remove_triggers(tx: &Transaction<'_>) -> Result<()> {
+        let rows = tx.query(
+            "select tgrelid::regclass::text from pg_trigger where tgname = $1",
+            &[&TRIGGER],
+        ).await?;
+        for row in rows {
+            let table: String = row.get(0);
+            tx.execute(&format!("drop trigger {TRIGGER} on {table}"), &[]).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads all captured rows and removes the log table and function again.
+    pub(super) async fn finish(tx: &Transaction<'_>) -> Result<Vec<Entry>> {
+        let query = format!("select migration, table_name, op, old_row from {TABLE} order by seq");
+        let entries = tx.query(&query, &[])
+            .await
+            .context("failed to read undo log")?
+            .into_iter()
+            .map(|row| Entry {
+                migration: row.get(0),
+                table: row.get(1),
+                op: row.get(2),
+                old_row: row.get(3),
+            })
+            .collect();
+
+        tx.batch_execute(&format!("drop table {TABLE}; drop function {TRIGGER}();")).await?;
+        Ok(entries)
+    }
+
+    /// The file the undo log is written to. It contains the rows of all
+    /// migration transactions so far.
+    pub(super) struct Log {
+        path: PathBuf,
+        file: File,
+        committed: Vec<Entry>,
+        pending: Vec<Entry>,
+    }
+
+    impl Log {
+        /// Creates the file at `path`, truncating it if it exists.
+        pub(super) fn create(path: &Path) -> Result<Self> {
+            let file = File::create(path)
+                .with_context(|| format!("failed to create undo log '{}'", path.display()))?;
+            Ok(Self { path: path.to_owned(), file, committed: vec![], pending: vec![] })
+        }
+
+        /// Replaces the content of the file with the rows of all committed
+        /// transactions plus `entries` of the current one, as JSON Lines.
+        pub(super) fn write(&mut self, entries: Vec<Entry>) -> Result<()> {
+            self.pending = entries;
+
+            let path = &self.path;
+            let context = || format!("failed to write undo log to '{}'", path.display());
+            self.file.set_len(0).and_then(|_| (&self.file).rewind()).with_context(context)?;
+            let mut out = BufWriter::new(&self.file);
+            for entry in self.committed.iter().chain(&self.pending) {
+                let line = serde_json::json!({
+                    "migration": entry.migration,
+                    "table": entry.table,
+                    "op": entry.op,
+                    "row": entry.old_row,
+                });
+                serde_json::to_writer(&mut out, &line).with_context(context)?;
+                out.write_all(b"\n").with_context(context)?;
+            }
+            out.flush().with_context(context)?;
+
+            let rows = self.committed.len() + self.pending.len();
+            info!("Wrote {rows} rows to undo log '{}'", path.display());
+            Ok(())
+        }
+
+        /// Marks the rows of the last `write` as committed.
+        pub(super) fn commit(&mut self) {
+            self.committed.append(&mut self.pending);
+        }
+    }
+}
+
+/// Archiving old migration scripts via `db migrate --compress-logs`. The
+/// archive is a zstd-compressed JSON Lines file with one entry per migration.
+/// Only the copies in `__db_migrations` are archived, `MIGRATIONS` is not
+/// affected.
+pub(crate) mod script_archive {
+    use std::{fs, io::{BufRead, BufReader}};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    /// Compression level of the archive. It is rarely written, so we prefer
+    /// small files over speed.
+    const LEVEL: i32 = 19;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(crate) struct Entry {
+        pub(crate) id: u64,
+        pub(crate) name: String,
+        pub(crate) applied_on: DateTime<Utc>,
+        /// Checksum of `script` as stored in `__db_migrations`.
+        pub(crate) sha256: String,
+        pub(crate) script: String,
+    }
+
+    /// Moves the scripts of all but the latest `keep` migrations from
+    /// `__db_migrations` to the archive at `path`.
+    pub(super) async fn archive(db: &mut Db, path: &Path, keep: u64) -> Result<()> {
+        let tx = db.build_transaction()
+            .isolation_level(IsolationLevel::Serializable)
+            .start()
+            .await?;
+        tx.execute("lock table __db_migrations in share update exclusive mode", &[])
+            .await
+            .context("failed to lock table '__db_migrations'")?;
+
+        let rows = tx
+            .query(
+                "select id, name, applied_on, script from __db_migrations \
+                    where script_sha256 is null \
+                        and id <= (select max(id) from __db_migrations) - $1 \
+                    order by id",
+                &[&(keep as i64)],
+            )
+            .await
+            .context("failed to fetch from __db_migrations")?;
+        if rows.is_empty() {
+            info!("No migration scripts to archive");
+            return Ok(());
+        }
+
+        let mut entries = if path.exists() { read(path)? } else { BTreeMap::new() };
+        let new = rows.iter()
+            .map(|row| {
+                let script = row.get::<_, String>(3);
+                Entry {
+                    id: row.get::<_, i64>(0) as u64,
+                    name: row.get(1),
+                    applied_on: Utc.from_utc_datetime(&row.get(2)),
+                    sha256: checksum(&script),
+                    script,
+                }
+            })
+            .collect::<Vec<_>>();
+        for entry in &new {
+            tx.execute(
+                "update __db_migrations set script = '', script_sha256 = $2 where id = $1",
+                &[&(entry.id as i64), &entry.sha256],
+            ).await?;
+        }
+        let archived = new.len();
+        entries.extend(new.into_iter().map(|entry| (entry.id, entry)));
+
+        // The archive is written before committing, so that no script is lost
+        // if writing fails.
+        write(path, &entries)?;
+        tx.commit().await.context("failed to update '__db_migrations'")?;
+        info!("Archived scripts of {archived} migrations to '{}'", path.display());
+
+        Ok(())
+    }
+
+    /// Reads all entries of the archive at `path`.
+    pub(crate) fn read(path: &Path) -> Result<BTreeMap<u64, Entry>> {
+        let context = || format!("failed to read migration archive '{}'", path.display());
+        let file = File::open(path).with_context(context)?;
+        let decoder = zstd::stream::read::Decoder::new(file).with_context(context)?;
+
+        let mut out = BTreeMap::new();
+        for line in BufReader::new(decoder).lines() {
+            let entry: Entry = serde_json::from_str(&line.with_context(context)?)
+                .with_context(context)?;
+            out.insert(entry.id, entry);
+        }
+        Ok(out)
+    }
+
+    /// Atomically replaces the archive at `path` with the given entries.
+    fn write(path: &Path, entries: &BTreeMap<u64, Entry>) -> Result<()> {
+        let context = || format!("failed to write migration archive '{}'", path.display());
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let file = File::create(&tmp_path).with_context(context)?;
+        let mut encoder = zstd::stream::write::Encoder::new(BufWriter::new(file), LEVEL)
+            .with_context(context)?;
+        for entry in entries.values() {
+            serde_json::to_writer(&mut encoder, entry).with_context(context)?;
+            encoder.write_all(b"\n").with_context(context)?;
+        }
+        encoder.finish().and_then(|mut out| out.flush()).with_context(context)?;
+        fs::rename(&tmp_path, path).with_context(context)?;
+        Ok(())
+    }
+}
+
+/// Implementation of subcommand with same name, see that for docs. Returns the
+/// IDs of the updated and of the deleted migrations.
+pub(crate) async fn unsafe_overwrite_migrations(db: &mut Db) -> Result<(Vec<u64>, Vec<u64>)> {
     let tx = db.build_transaction()
         .isolation_level(IsolationLevel::Serializable)
         .start()
         .await?;
 
-    let (selection, mapping) = select!(id, name, script);
+    // Makes sure `script_sha256` exists.
+    create_meta_table_if_missing(&tx).await?;
+
+    let (selection, mapping) = select!(id, name, script, script_sha256);
     let query = format!("select {selection} from __db_migrations");
     let rows = tx.query(&query, &[]).await.context("failed to fetch from __db_migrations")?;
 
+    let (mut updated, mut deleted) = (vec![], vec![]);
     for row in rows {
         let id: i64 = mapping.id.of(&row);
         let name: String = mapping.name.of(&row);
         let script: String = mapping.script.of(&row);
+        let script_sha256: Option<String> = mapping.script_sha256.of(&row);
 
         if let Some(migration) = MIGRATIONS.get(&(id as u64)) {
-            if migration.script != script || migration.name != name {
+            // Archived scripts stay archived if they match.
+            let script_matches = match script_sha256 {
+                Some(sha256) => sha256 == checksum(migration.script),
+                None => migration.script == script,
+            };
+            if !script_matches || migration.name != name {
                 tx.execute(
-                    "update __db_migrations set name = $1, script = $2 where id = $3",
+                    "update __db_migrations \
+                        set name = $1, script = $2, script_sha256 = null \
+                        where id = $3",
                     &[&migration.name, &migration.script, &id],
                 ).await?;
                 info!("Updated name & script for migration {} {}", id as u64, migration.name);
+                updated.push(id as u64);
             }
         } else {
             // We don't know about the migration, so we delete it.
             tx.execute("delete from __db_migrations where id = $1", &[&id]).await?;
             info!("Deleted migration {} ({}) as it's unknown", id as u64, name);
+            deleted.push(id as u64);
         }
     }
     tx.commit().await?;
 
-    Ok(())
+    Ok((updated, deleted))
+}
+
+
+/// Hash chain over migrations: each link hashes the previous link together
+/// with ID, name and checksum (SHA-256 of the script) of one migration. So
+/// the final hash changes if any migration in the history changes.
+pub(super) struct Chain {
+    hash: [u8; 32],
 }
 
+impl Chain {
+    pub(super) fn new() -> Self {
+        Self { hash: [0; 32] }
+    }
+
+    /// Adds a migration to the chain and returns its checksum as hex.
+    pub(super) fn push(&mut self, id: u64, name: &str, script: &str) -> String {
+        let checksum = digest::digest(&SHA256, script.as_bytes());
+        self.push_checksum(id, name, checksum.as_ref());
+        hex::encode(checksum)
+    }
+
+    /// Like `push`, but with the checksum of the script (e.g. of an archived
+    /// one) instead of the script itself.
+    pub(super) fn push_checksum(&mut self, id: u64, name: &str, checksum: &[u8]) {
+        let mut ctx = Context::new(&SHA256);
+        ctx.update(&self.hash);
+        ctx.update(&id.to_be_bytes());
+        ctx.update(name.as_bytes());
+        // Separator to make the encoding unambiguous, as names never contain
+        // a null byte.
+        ctx.update(&[0]);
+        ctx.update(checksum);
+        self.hash.copy_from_slice(ctx.finish().as_ref());
+    }
+
+    pub(super) fn hash(&self) -> String {
+        hex::encode(self.hash)
+    }
+}
+
+
+/// Returns the SHA-256 checksum of a migration script as hex.
+pub(super) fn checksum(script: &str) -> String {
+    hex::encode(digest::digest(&SHA256, script.as_bytes()))
+}
+
+/// Hash of the chain over all migrations of this binary.
+pub(super) fn chain_hash() -> String {
+    let mut chain = Chain::new();
+    for (id, migration) in MIGRATIONS.iter() {
+        chain.push(*id, migration.name, migration.script);
+    }
+    chain.hash()
+}
 
 // Helper macro to include migrations in the `migations` folder and add them to
 // a map. The `assert!` and `panic!` in there should ideally be compile errors,
@@ -310,12 +1119,62 @@ macro_rules! include_migrations {
 }
 
 #[derive(Debug)]
-struct Migration {
-    name: &'static str,
-    script: &'static str,
+pub(super) struct Migration {
+    pub(super) name: &'static str,
+    pub(super) script: &'static str,
 }
 
-static MIGRATIONS: Lazy<BTreeMap<u64, Migration>> = include_migrations![
+/// Prefix of comment lines in migration scripts declaring a precondition,
+/// e.g. `-- precondition: select count(*) = 0 from events where series is
+/// null`. The query has to return a single boolean, `true` meaning the
+/// migration can be applied. Preconditions are checked right before the
+/// migration runs, i.e. after all previous migrations were applied.
+const PRECONDITION_PREFIX: &str = "-- precondition:";
+
+impl Migration {
+    /// Returns the queries of all preconditions declared in the script.
+    pub(super) fn preconditions(&self) -> Vec<&'static str> {
+        parse_preconditions(self.script)
+    }
+}
+
+fn parse_preconditions(script: &str) -> Vec<&str> {
+    script.lines()
+        .filter_map(|line| line.trim_start().strip_prefix(PRECONDITION_PREFIX))
+        .map(str::trim)
+        .filter(|query| !query.is_empty())
+        .collect()
+}
+
+/// Runs the precondition query and returns whether it is satisfied.
+pub(super) async fn evaluate_precondition(
+    db: &impl GenericClient,
+    precondition: &str,
+) -> Result<bool> {
+    db.query_one(precondition, &[])
+        .await
+        .context("failed to run precondition query")?
+        .try_get::<_, bool>(0)
+        .context("precondition query has to return a single boolean")
+}
+
+async fn check_preconditions(tx: &Transaction<'_>, id: u64, migration: &Migration) -> Result<()> {
+    for precondition in migration.preconditions() {
+        let satisfied = evaluate_precondition(&**tx, precondition)
+            .await
+            .with_context(|| format!("failed to check precondition of migration '{id}-{}': \
+                `{precondition}`", migration.name))?;
+        if !satisfied {
+            bail!("precondition of migration '{id}-{}' is not met: `{precondition}`. \
+                Fix the data (see the migration script for details) and try again. \
+                `tobira db migration-precondition-check` checks all pending migrations.",
+                migration.name);
+        }
+    }
+    Ok(())
+}
+
+pub(super) static MIGRATIONS: Lazy<BTreeMap<u64, Migration>> = include_migrations![
     01: "xtea",
     02: "id-generation",
     03: "realms",
@@ -332,4 +1191,79 @@ static MIGRATIONS: Lazy<BTreeMap<u64, Migration>> = include_migrations![
     14: "event-captions",
     15: "fix-event-constraints",
     16: "master-track",
+    17: "upload-jobs",
+    18: "toc-blocks",
+    19: "schema-comments",
+    20: "announcements",
+    21: "series-block-options",
+    22: "sync-skipped-items",
+    23: "session-language",
+    24: "playback-positions",
+    25: "known-users",
+    26: "series-event-stats",
+    27: "licenses",
+    28: "series-block-metadata-filter",
+    29: "text-block-html",
+    30: "persons",
+    31: "event-processing",
+    32: "sync-backfill",
+    33: "federation",
+    34: "search-index-status",
 ];
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Chain, MIGRATIONS, parse_preconditions};
+
+    #[test]
+    fn parses_preconditions() {
+        let script = "\
+            -- Moves all events to a series.\n\
+            -- precondition: select count(*) = 0 from events where series is null\n\
+            --precondition: select false\n\
+               -- precondition:   select exists(select from series)  \n\
+            -- precondition:\n\
+            alter table events alter column series set not null;\n";
+        assert_eq!(parse_preconditions(script), [
+            "select count(*) = 0 from events where series is null",
+            "select exists(select from series)",
+        ]);
+
+        for migration in MIGRATIONS.values() {
+            for precondition in migration.preconditions() {
+                assert!(
+                    precondition.to_lowercase().starts_with("select"),
+                    "precondition of '{}' is not a query: {precondition}",
+                    migration.name,
+                );
+            }
+        }
+    }
+
+    fn chain(migrations: &[(u64, &str, &str)]) -> String {
+        let mut chain = Chain::new();
+        for (id, name, script) in migrations {
+            chain.push(*id, name, script);
+        }
+        chain.hash()
+    }
+
+    #[test]
+    fn earlier_changes_affect_final_hash() {
+        let original = chain(&[(1, "a", "create table a();"), (2, "b", "create table b();")]);
+        assert_eq!(
+            original,
+            chain(&[(1, "a", "create table a();"), (2, "b", "create table b();")]),
+        );
+        assert_ne!(
+            original,
+            chain(&[(1, "a", "create table x();"), (2, "b", "create table b();")]),
+        );
+        assert_ne!(
+            original,
+            chain(&[(1, "ab", "create table a();"), (2, "b", "create table b();")]),
+        );
+        assert_ne!(original, chain(&[(1, "a", "create table a();")]));
+    }
+}
@@ -0,0 +1,35 @@
+-- Uploads that were ingested into Opencast via Tobira's uploader. Opencast
+-- needs a while to process new uploads and the event only appears in Tobira
+-- after the next harvest. Until then, this table is used to show users the
+-- processing state of their uploads.
+--
+-- `'processing'`: the upload was ingested and Opencast is still processing it
+--     or Tobira has not yet synced the event.
+-- `'failed'`: the Opencast workflow for this upload failed.
+-- `'ready'`: the event was synced into Tobira.
+create type upload_state as enum ('processing', 'failed', 'ready');
+
+create table upload_jobs (
+    id bigint primary key generated always as identity,
+
+    -- The Opencast UUID of the ingested media package.
+    opencast_id text not null unique,
+
+    -- Username of the user who uploaded the video.
+    uploader text not null,
+
+    title text not null,
+    state upload_state not null default 'processing',
+
+    -- Human readable description of what went wrong if `state = 'failed'`.
+    error_message text,
+
+    created timestamp with time zone not null default now(),
+    updated timestamp with time zone not null default now(),
+
+    constraint error_message_only_when_failed check (
+        (state = 'failed') = (error_message is not null)
+    )
+);
+
+create index idx_upload_jobs_uploader on upload_jobs (uploader);
@@ -0,0 +1,84 @@
+-- Adds "table of contents" blocks, which show a navigation tree of the realm
+-- subtree below the realm they are in.
+--
+-- We cannot use `alter type ... add value` here as that is not allowed inside
+-- a transaction for PostgreSQL < 12. So instead, we recreate `block_type`.
+-- Unfortunately, that requires us to temporarily remove everything that
+-- references `blocks.type`.
+
+drop view search_events;
+drop trigger check_name_source_block_on_block_update on blocks;
+alter table blocks
+    drop constraint title_block_has_fields,
+    drop constraint text_block_has_fields,
+    drop constraint series_block_has_fields,
+    drop constraint video_block_has_fields;
+
+alter type block_type rename to block_type_old;
+create type block_type as enum ('title', 'text', 'series', 'video', 'toc');
+alter table blocks alter column type type block_type using type::text::block_type;
+drop type block_type_old;
+
+
+-- Everything that was removed above is recreated exactly as before.
+alter table blocks
+    add constraint title_block_has_fields check (type <> 'title' or (
+        text_content is not null
+    )),
+    add constraint text_block_has_fields check (type <> 'text' or (
+        text_content is not null
+    )),
+    add constraint series_block_has_fields check (type <> 'series' or (
+        videolist_order is not null and
+        show_title is not null and
+        show_metadata is not null
+    )),
+    add constraint video_block_has_fields check (type <> 'video' or (
+        show_title is not null
+    ));
+
+create trigger check_name_source_block_on_block_update
+    before update on blocks
+    for each row
+    when (new.realm is distinct from old.realm
+        or new.type is distinct from old.type)
+    execute procedure check_block_as_name_source();
+
+create view search_events as
+    select
+        events.id, events.state,
+        events.series, series.title as series_title,
+        events.title, events.description, events.creators,
+        events.thumbnail, events.duration,
+        events.is_live, events.created, events.start_time, events.end_time,
+        events.read_roles, events.write_roles,
+        coalesce(
+            array_agg(
+                distinct
+                row(search_realms.id, name, full_path, ancestor_names)::search_realms
+            ) filter(where search_realms.id is not null),
+            '{}'
+        ) as host_realms
+    from events
+    left join series on events.series = series.id
+    left join blocks on (
+        type = 'series' and blocks.series = events.series
+        or type = 'video' and blocks.video = events.id
+    )
+    left join search_realms on search_realms.id = blocks.realm
+    group by events.id, series.id;
+
+
+-- The actual new fields for TOC blocks.
+create type toc_order as enum ('inherit', 'alphabetic:asc', 'alphabetic:desc');
+
+alter table blocks
+    add column toc_depth smallint,
+    add column toc_show_counts boolean,
+    add column toc_order toc_order,
+    add constraint toc_block_has_fields check (type <> 'toc' or (
+        toc_depth is not null and
+        toc_show_counts is not null and
+        toc_order is not null
+    )),
+    add constraint toc_depth_in_range check (toc_depth between 1 and 4);
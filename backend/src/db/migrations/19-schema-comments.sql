@@ -0,0 +1,126 @@
+-- Adds comments to all tables and columns. These are used by `db doc` to
+-- generate the schema documentation, which also checks that nothing is left
+-- undocumented. So when adding new tables or columns, add comments, too!
+
+comment on table __xtea_keys is
+    'Secret keys used to scramble sequential IDs into random looking ones, one per entity type';
+comment on column __xtea_keys.entity is 'Entity type the key is used for, e.g. ''event''';
+comment on column __xtea_keys.key is 'The 128 bit XTEA key';
+
+
+comment on table realms is
+    'The page hierarchy of Tobira. Each realm is one page with a path and content blocks';
+comment on column realms.id is 'Randomized ID. The root realm has ID 0';
+comment on column realms.parent is 'Parent realm. Only `null` for the root realm';
+comment on column realms.path_segment is
+    'Last segment of the realm''s path. Empty for the root realm';
+comment on column realms.name is
+    'Explicitly set name of the realm. `null` if the name is derived from a block';
+comment on column realms.index is
+    'Position among its siblings. Only used if the parent''s `child_order` is ''by_index''';
+comment on column realms.child_order is 'How the children of this realm are ordered';
+comment on column realms.full_path is
+    'Full path of the realm, calculated by triggers. Empty for the root realm';
+comment on column realms.name_from_block is
+    'Block of this realm (series or video) whose title is used as realm name';
+
+
+comment on table series is 'Opencast series synced from Opencast';
+comment on column series.id is 'Randomized ID';
+comment on column series.state is
+    '''waiting'' if the series is only known via an event so far, ''ready'' once synced';
+comment on column series.opencast_id is 'The Opencast UUID of the series';
+comment on column series.read_roles is 'Roles that are allowed to read the series';
+comment on column series.write_roles is 'Roles that are allowed to write the series';
+comment on column series.title is 'Title of the series';
+comment on column series.description is 'Description of the series';
+comment on column series.updated is 'When the series was last modified in Opencast';
+
+
+comment on table events is 'Opencast events (videos) synced from Opencast';
+comment on column events.id is 'Randomized ID';
+comment on column events.state is '''waiting'' if the event is not fully synced yet, ''ready'' otherwise';
+comment on column events.opencast_id is 'The Opencast UUID of the event';
+comment on column events.series is
+    'Series of this event. `null` if the event has no series or the series is not known yet';
+comment on column events.part_of is 'The raw Opencast series ID of this event';
+comment on column events.is_live is
+    'Whether this is a live event. If so, `created` is the planned start date';
+comment on column events.read_roles is 'Roles that are allowed to read the event';
+comment on column events.write_roles is 'Roles that are allowed to write the event';
+comment on column events.title is 'Title of the event';
+comment on column events.description is 'Description of the event';
+comment on column events.duration is 'Duration in milliseconds';
+comment on column events.created is 'Creation date of the event';
+comment on column events.updated is 'When the event was last modified in Opencast';
+comment on column events.start_time is 'Start time of the recording';
+comment on column events.end_time is 'End time of the recording';
+comment on column events.creators is 'Names of the creators of the event';
+comment on column events.metadata is
+    'Additional metadata as JSON object (namespace -> field -> array of strings)';
+comment on column events.thumbnail is 'URL to a thumbnail image';
+comment on column events.tracks is 'Media tracks of the event';
+comment on column events.captions is 'Caption tracks of the event';
+
+
+comment on table blocks is
+    'Content blocks of realms. Columns not used by a block''s type are `null`';
+comment on column blocks.id is 'Randomized ID';
+comment on column blocks.realm is 'The realm this block belongs to';
+comment on column blocks.type is 'The kind of block, determining which other columns are used';
+comment on column blocks.index is 'Position of the block within its realm, starting at 0';
+comment on column blocks.text_content is 'Content of title and text blocks';
+comment on column blocks.series is 'Series shown by series blocks';
+comment on column blocks.videolist_order is 'Order of videos in series blocks';
+comment on column blocks.video is 'Event shown by video blocks';
+comment on column blocks.show_title is 'Whether series and video blocks show their title';
+comment on column blocks.show_metadata is 'Whether series blocks show the series description';
+comment on column blocks.toc_depth is 'Number of realm levels shown by TOC blocks';
+comment on column blocks.toc_show_counts is 'Whether TOC blocks show video counts';
+comment on column blocks.toc_order is 'Order of entries in TOC blocks';
+
+
+comment on table sync_status is 'Single row table holding the state of the Opencast sync';
+comment on column sync_status.harvested_until is
+    'Exclusive upper limit (UTC) until which everything has been harvested';
+
+
+comment on table user_sessions is 'Sessions of logged in users';
+comment on column user_sessions.id is 'Random session ID, stored in the session cookie';
+comment on column user_sessions.username is 'Unique username of the user';
+comment on column user_sessions.display_name is 'Name of the user for display purposes';
+comment on column user_sessions.roles is 'Roles of the user';
+comment on column user_sessions.created is 'When the session was created (UTC)';
+
+
+comment on table search_index_queue is 'Items that need to be (re)indexed in the search index';
+comment on column search_index_queue.id is 'Auto incrementing ID used for queue ordering';
+comment on column search_index_queue.item_id is 'ID of the realm/event that needs reindexing';
+comment on column search_index_queue.kind is 'Kind of the item referenced by `item_id`';
+
+
+comment on table deleted_items is
+    'Items deleted in Opencast, so that they can be removed from the search index';
+comment on column deleted_items.opencast_id is 'The Opencast UUID of the deleted item';
+comment on column deleted_items.kind is 'Kind of the deleted item';
+comment on column deleted_items.our_id is 'The Tobira ID the deleted item had';
+
+
+comment on table upload_jobs is 'Videos uploaded via Tobira, tracked until they are synced';
+comment on column upload_jobs.id is 'Auto incrementing ID';
+comment on column upload_jobs.opencast_id is 'The Opencast UUID of the ingested media package';
+comment on column upload_jobs.uploader is 'Username of the uploading user';
+comment on column upload_jobs.title is 'Title given at upload time';
+comment on column upload_jobs.state is 'Processing state of the upload';
+comment on column upload_jobs.error_message is
+    'Description of what went wrong. Only set if `state` is ''failed''';
+comment on column upload_jobs.created is 'When the upload was registered';
+comment on column upload_jobs.updated is 'When the state was last changed';
+
+
+-- This table is not created by a migration, but we comment it here anyway.
+comment on table __db_migrations is 'Migrations that have been applied to this database';
+comment on column __db_migrations.id is 'ID of the migration';
+comment on column __db_migrations.name is 'Name of the migration';
+comment on column __db_migrations.applied_on is 'When the migration was applied (UTC)';
+comment on column __db_migrations.script is 'The SQL script of the migration';
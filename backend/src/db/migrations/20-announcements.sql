@@ -0,0 +1,47 @@
+-- A global announcement shown on all pages, e.g. to announce maintenance
+-- windows. There is at most one announcement at any time.
+create type announcement_severity as enum ('info', 'warning', 'critical');
+
+create table announcements (
+    id bigint primary key generated always as identity,
+    title text not null,
+
+    -- Markdown.
+    body text not null,
+
+    severity announcement_severity not null,
+
+    -- The announcement is only shown between these two points in time. `null`
+    -- means "no restriction".
+    active_from timestamp with time zone,
+    active_until timestamp with time zone,
+
+    constraint title_not_empty check (title <> ''),
+    constraint active_range_valid check (active_from < active_until)
+);
+
+-- Makes sure there is only one announcement.
+create unique index only_one_announcement on announcements ((true));
+
+-- Which sessions dismissed which announcement. Rows are removed automatically
+-- with the announcement or session.
+create table announcement_dismissals (
+    announcement bigint not null references announcements on delete cascade,
+    session bytea not null references user_sessions on delete cascade,
+    primary key (announcement, session)
+);
+
+create index idx_announcement_dismissals_session on announcement_dismissals (session);
+
+
+comment on type announcement_severity is 'How important an announcement is';
+comment on table announcements is 'The global announcement shown on all pages (at most one row)';
+comment on column announcements.id is 'Primary key';
+comment on column announcements.title is 'Short title of the announcement';
+comment on column announcements.body is 'Markdown text of the announcement';
+comment on column announcements.severity is 'How important the announcement is';
+comment on column announcements.active_from is 'Announcement is not shown before this (if set)';
+comment on column announcements.active_until is 'Announcement is not shown after this (if set)';
+comment on table announcement_dismissals is 'Sessions in which an announcement was dismissed';
+comment on column announcement_dismissals.announcement is 'The dismissed announcement';
+comment on column announcement_dismissals.session is 'The session (`user_sessions.id`)';
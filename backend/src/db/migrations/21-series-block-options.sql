@@ -0,0 +1,33 @@
+-- Adds more display options for series blocks: ordering videos alphabetically
+-- by title and choosing a layout other than the grid. Existing blocks keep
+-- looking exactly like before.
+--
+-- Like in `18-toc-blocks.sql`, we cannot use `alter type ... add value`, so
+-- `video_list_order` is recreated instead.
+alter table blocks drop constraint series_block_has_fields;
+
+alter type video_list_order rename to video_list_order_old;
+create type video_list_order as enum ('new_to_old', 'old_to_new', 'alphabetic');
+alter table blocks alter column videolist_order type video_list_order
+    using videolist_order::text::video_list_order;
+drop type video_list_order_old;
+
+create type video_list_layout as enum ('grid', 'list', 'slider');
+
+alter table blocks
+    add column videolist_layout video_list_layout default 'grid',
+    add constraint series_block_has_fields check (type <> 'series' or (
+        videolist_order is not null and
+        videolist_layout is not null and
+        show_title is not null and
+        show_metadata is not null
+    ));
+
+comment on column blocks.videolist_layout is 'How videos in series blocks are arranged';
+
+
+-- The events of series blocks are loaded ordered by one of these. The new
+-- indexes make `idx_events_series` redundant.
+drop index idx_events_series;
+create index idx_events_series_created on events (series, created);
+create index idx_events_series_title on events (series, title);
@@ -0,0 +1,16 @@
+-- Items of the harvest API that could not be deserialized and were therefore
+-- skipped by the sync. Rows are removed once the item is synced successfully.
+create table sync_skipped_items (
+    opencast_id text primary key,
+    kind text,
+    error text not null,
+    skipped_at timestamp with time zone not null default now()
+);
+
+comment on table sync_skipped_items is
+    'Harvest items skipped by the sync because they are malformed';
+comment on column sync_skipped_items.opencast_id is 'Opencast ID of the skipped item';
+comment on column sync_skipped_items.kind is 'Kind of the item (e.g. `event`), if known';
+comment on column sync_skipped_items.error is
+    'Deserialization error, including the path to the offending field';
+comment on column sync_skipped_items.skipped_at is 'When the item was last skipped';
@@ -0,0 +1,6 @@
+-- The language explicitly chosen by the user, overriding the one negotiated
+-- via `Accept-Language`.
+alter table user_sessions add column lang text;
+
+comment on column user_sessions.lang is
+    'Language chosen by the user (e.g. `de`), null if none was chosen explicitly';
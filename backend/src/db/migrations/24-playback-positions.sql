@@ -0,0 +1,28 @@
+-- Where users stopped watching events, to continue watching later (possibly
+-- on another device). Only used if `general.continue_watching` is enabled.
+-- Old positions are removed by the worker after
+-- `general.playback_position_retention`.
+create table playback_positions (
+    username text not null,
+    event bigint not null references events on delete cascade,
+
+    -- In seconds from the start of the video.
+    position double precision not null,
+    updated timestamp with time zone not null default now(),
+
+    primary key (username, event),
+    constraint position_not_negative check (position >= 0)
+);
+
+-- For `User.continueWatching`.
+create index idx_playback_positions_user_updated on playback_positions (username, updated desc);
+
+-- For pruning old positions.
+create index idx_playback_positions_updated on playback_positions (updated);
+
+
+comment on table playback_positions is 'Per user playback positions to continue watching';
+comment on column playback_positions.username is 'Unique username of the user';
+comment on column playback_positions.event is 'The event that was watched';
+comment on column playback_positions.position is 'Seconds from the start of the video';
+comment on column playback_positions.updated is 'When the position was last changed';
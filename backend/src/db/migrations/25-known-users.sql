@@ -0,0 +1,48 @@
+-- All users that ever logged in via Tobira's session management, so that
+-- admins can list them (e.g. to decide which data to clean up). Sessions
+-- alone are not enough as they are deleted on logout and expiry.
+create table users (
+    username text primary key,
+    display_name text not null,
+    roles text[] not null,
+    first_login timestamp with time zone not null default now(),
+    last_login timestamp with time zone not null default now()
+);
+
+-- Users who currently have a session are known already.
+insert into users (username, display_name, roles, first_login, last_login)
+    select distinct on (username)
+        username,
+        display_name,
+        roles,
+        min(created) over(partition by username) at time zone 'utc',
+        created at time zone 'utc'
+    from user_sessions
+    order by username, created desc;
+
+-- For the sort orders of `Query.users`. Sorting by username uses the primary
+-- key. The `last_login` index is also used by the "logged in within" filter.
+create index idx_users_display_name on users (display_name, username);
+create index idx_users_last_login on users (last_login, username);
+
+-- For filtering by role.
+create index idx_users_roles on users using gin (roles);
+
+
+-- Sessions are paginated newest first. The session ID is secret and thus
+-- cannot be used as tie breaker in cursors, so we add a sequential number.
+alter table user_sessions add column seq bigint generated always as identity;
+
+create index idx_user_sessions_created on user_sessions (created desc, seq desc);
+create index idx_user_sessions_username_created
+    on user_sessions (username, created desc, seq desc);
+
+
+comment on table users is 'Users that logged in at least once';
+comment on column users.username is 'Unique username of the user';
+comment on column users.display_name is 'Name of the user for display purposes (as of last login)';
+comment on column users.roles is 'Roles of the user (as of last login)';
+comment on column users.first_login is 'When the user first logged in';
+comment on column users.last_login is 'When the user last logged in';
+comment on column user_sessions.seq is
+    'Sequential number for pagination, as the session ID must not be exposed';
@@ -0,0 +1,32 @@
+-- Number of events of each series and when the last of them was updated,
+-- e.g. for "24 videos · updated 3 days ago" on series cards. Computing
+-- these per series when loading a list of series would require one query
+-- per series, so they are stored here. They are updated by the sync code
+-- whenever events are inserted, moved or removed, and recounted
+-- periodically by the worker to fix any drift (see `db::series_stats`).
+alter table series
+    add column event_count int not null default 0,
+    add column last_event_update timestamp with time zone;
+
+update series set
+    event_count = stats.count,
+    last_event_update = stats.last_update
+from (
+    -- Waiting events have `updated = '-infinity'`.
+    select
+        series,
+        count(*) as count,
+        max(updated) filter (where updated <> '-infinity') as last_update
+    from events
+    where series is not null
+    group by series
+) as stats
+where series.id = stats.series;
+
+-- For sorting series by `RECENTLY_UPDATED`.
+create index idx_series_last_event_update on series (last_event_update desc nulls last, id);
+
+
+comment on column series.event_count is 'Number of events in this series (denormalized)';
+comment on column series.last_event_update is
+    'Latest `updated` of all synced events in this series, null if none (denormalized)';
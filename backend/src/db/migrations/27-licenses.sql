@@ -0,0 +1,49 @@
+-- The license of events and series: the raw value from Opencast and the SPDX
+-- identifier it was normalized to (see `sync::license`). The latter is null
+-- if the license is unknown. For events, the raw license is also part of
+-- `metadata`, from which it is copied here. The normalized values are filled
+-- in by the sync process on its next start.
+alter table events
+    add column license text,
+    add column license_spdx text;
+
+alter table series
+    add column license text,
+    add column license_spdx text;
+
+update events set license = metadata #>> '{dcterms,license,0}';
+
+
+-- To filter by license in the search index.
+create or replace view search_events as
+    select
+        events.id, events.state,
+        events.series, series.title as series_title,
+        events.title, events.description, events.creators,
+        events.thumbnail, events.duration,
+        events.is_live, events.created, events.start_time, events.end_time,
+        events.read_roles, events.write_roles,
+        coalesce(
+            array_agg(
+                distinct
+                row(search_realms.id, name, full_path, ancestor_names)::search_realms
+            ) filter(where search_realms.id is not null),
+            '{}'
+        ) as host_realms,
+        events.license_spdx
+    from events
+    left join series on events.series = series.id
+    left join blocks on (
+        type = 'series' and blocks.series = events.series
+        or type = 'video' and blocks.video = events.id
+    )
+    left join search_realms on search_realms.id = blocks.realm
+    group by events.id, series.id;
+
+
+comment on column events.license is 'License as stored in Opencast';
+comment on column events.license_spdx is
+    'SPDX identifier of `license`, null if unknown or not set';
+comment on column series.license is 'License as stored in Opencast';
+comment on column series.license_spdx is
+    'SPDX identifier of `license`, null if unknown or not set';
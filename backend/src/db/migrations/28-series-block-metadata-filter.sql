@@ -0,0 +1,51 @@
+-- Series blocks can be restricted to events with specific metadata values.
+-- The filter has the same shape as `events.metadata` (namespace -> field ->
+-- values) and an event is shown if its metadata contains the filter, i.e.
+-- `events.metadata @> blocks.metadata_filter`.
+alter table blocks
+    add column metadata_filter jsonb,
+    add constraint metadata_filter_only_for_series
+        check (type = 'series' or metadata_filter is null);
+
+-- Changing the filter changes which events are listed by the block.
+drop trigger queue_blocks_for_reindex on blocks;
+create trigger queue_blocks_for_reindex
+after insert or delete or update of video, series, metadata_filter
+on blocks
+for each row
+execute procedure queue_blocks_for_reindex();
+
+-- Makes filtering events with `metadata @> ...` fast.
+create index idx_events_metadata on events using gin (metadata jsonb_path_ops);
+
+
+-- Events filtered out by a series block are not hosted by that block's realm.
+create or replace view search_events as
+    select
+        events.id, events.state,
+        events.series, series.title as series_title,
+        events.title, events.description, events.creators,
+        events.thumbnail, events.duration,
+        events.is_live, events.created, events.start_time, events.end_time,
+        events.read_roles, events.write_roles,
+        coalesce(
+            array_agg(
+                distinct
+                row(search_realms.id, name, full_path, ancestor_names)::search_realms
+            ) filter(where search_realms.id is not null),
+            '{}'
+        ) as host_realms,
+        events.license_spdx
+    from events
+    left join series on events.series = series.id
+    left join blocks on (
+        type = 'series' and blocks.series = events.series
+            and (blocks.metadata_filter is null or events.metadata @> blocks.metadata_filter)
+        or type = 'video' and blocks.video = events.id
+    )
+    left join search_realms on search_realms.id = blocks.realm
+    group by events.id, series.id;
+
+
+comment on column blocks.metadata_filter is
+    'Only for series blocks: only events whose metadata contains this are shown';
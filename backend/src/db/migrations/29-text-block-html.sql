@@ -0,0 +1,8 @@
+-- Text blocks are rendered from Markdown to sanitized HTML when they are
+-- saved, so that clients don't have to do that themselves. Text blocks
+-- saved before this migration have no HTML yet: they are rendered the first
+-- time they are read via the API.
+alter table blocks
+    add column text_html text,
+    add constraint text_html_only_for_text
+        check (type = 'text' or text_html is null);
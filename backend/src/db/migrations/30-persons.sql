@@ -0,0 +1,88 @@
+-- Creators of events as deduplicated persons. `events.creators` keeps the
+-- names exactly as stored in Opencast. The sync process normalizes each of
+-- them (see `sync::persons`) and links the event to the person with that
+-- normalized name, creating the person if necessary. Persons can be merged
+-- by admins, e.g. if Opencast contains different spellings of one name.
+select prepare_randomized_ids('person');
+
+create table persons (
+    id bigint primary key default randomized_id('person'),
+    -- The name shown for this person: the first normalized name seen.
+    name text not null
+);
+
+-- All normalized names that refer to a person. After merging persons, the
+-- names of both refer to the remaining one, so that the sync keeps linking
+-- events with either spelling to it.
+create table person_names (
+    -- Lowercase, so that names are matched case-insensitively.
+    name text primary key check (name = lower(name)),
+    person bigint not null references persons on delete cascade
+);
+
+create table event_persons (
+    event bigint not null references events on delete cascade,
+    person bigint not null references persons on delete cascade,
+    -- Index of the first of the event's `creators` that refers to this
+    -- person, to keep the original order.
+    position smallint not null,
+    primary key (event, person)
+);
+
+create index idx_event_persons_person on event_persons (person);
+
+
+-- The names of the persons linked to an event, in the order of `creators`.
+-- Null for events that were not linked yet: they are linked by the sync
+-- process on its next start.
+alter table events add column creator_names text[];
+
+-- Updates `creator_names` of the given events from `event_persons`. As this
+-- updates the events, they are queued for reindexing.
+create function update_creator_names(event_ids bigint[]) returns void language sql as $$
+    update events set creator_names = array(
+        select persons.name
+        from event_persons
+        join persons on persons.id = event_persons.person
+        where event_persons.event = events.id
+        order by event_persons.position
+    )
+    where events.id = any(event_ids)
+$$;
+
+
+-- The search index contains the names of the linked persons.
+create or replace view search_events as
+    select
+        events.id, events.state,
+        events.series, series.title as series_title,
+        events.title, events.description,
+        coalesce(events.creator_names, events.creators) as creators,
+        events.thumbnail, events.duration,
+        events.is_live, events.created, events.start_time, events.end_time,
+        events.read_roles, events.write_roles,
+        coalesce(
+            array_agg(
+                distinct
+                row(search_realms.id, name, full_path, ancestor_names)::search_realms
+            ) filter(where search_realms.id is not null),
+            '{}'
+        ) as host_realms,
+        events.license_spdx
+    from events
+    left join series on events.series = series.id
+    left join blocks on (
+        type = 'series' and blocks.series = events.series
+            and (blocks.metadata_filter is null or events.metadata @> blocks.metadata_filter)
+        or type = 'video' and blocks.video = events.id
+    )
+    left join search_realms on search_realms.id = blocks.realm
+    group by events.id, series.id;
+
+
+comment on table persons is 'Deduplicated creators of events';
+comment on table person_names is 'Normalized names (lowercase) referring to a person';
+comment on table event_persons is 'Links events to the persons of their `creators`';
+comment on column events.creators is 'Creators as stored in Opencast';
+comment on column events.creator_names is
+    'Names of the persons linked to this event, null if not linked yet';
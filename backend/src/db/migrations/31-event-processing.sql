@@ -0,0 +1,6 @@
+-- Opencast retracts the publications of an event while it is reprocessed.
+-- Newer versions of the Tobira module tell us whether a workflow is running
+-- for an event. While that is the case, the last known tracks, captions and
+-- thumbnail are kept (see `sync::harvest`) and this flag is set, so that
+-- users can be told that the event is being processed.
+alter table events add column is_processing boolean not null default false;
@@ -0,0 +1,14 @@
+-- Progress of `tobira sync backfill` runs, so that they can be resumed after
+-- being interrupted. Events are processed in order of their ID.
+create table sync_backfill_progress (
+    job text primary key,
+    last_event_id bigint not null,
+    updated_at timestamp with time zone not null default now()
+);
+
+comment on table sync_backfill_progress is 'Progress of `tobira sync backfill` runs';
+comment on column sync_backfill_progress.job is
+    'Comma-separated, sorted list of the backfilled fields, e.g. `duration,resolution`';
+comment on column sync_backfill_progress.last_event_id is
+    'ID of the last event that was processed';
+comment on column sync_backfill_progress.updated_at is 'When progress was last saved';
@@ -0,0 +1,40 @@
+-- Events and series imported from another Tobira instance (see the
+-- `federation` configuration). These are read-only in this Tobira: they are
+-- only changed and deleted by the federation job.
+alter table events add column external_origin text;
+alter table series add column external_origin text;
+
+comment on column events.external_origin is
+    'Base URL of the Tobira instance this event was imported from, `null` for Opencast events';
+comment on column series.external_origin is
+    'Base URL of the Tobira instance this series was imported from, `null` for Opencast series';
+
+
+-- The search index shows where imported events come from.
+create or replace view search_events as
+    select
+        events.id, events.state,
+        events.series, series.title as series_title,
+        events.title, events.description,
+        coalesce(events.creator_names, events.creators) as creators,
+        events.thumbnail, events.duration,
+        events.is_live, events.created, events.start_time, events.end_time,
+        events.read_roles, events.write_roles,
+        coalesce(
+            array_agg(
+                distinct
+                row(search_realms.id, name, full_path, ancestor_names)::search_realms
+            ) filter(where search_realms.id is not null),
+            '{}'
+        ) as host_realms,
+        events.license_spdx,
+        events.external_origin
+    from events
+    left join series on events.series = series.id
+    left join blocks on (
+        type = 'series' and blocks.series = events.series
+            and (blocks.metadata_filter is null or events.metadata @> blocks.metadata_filter)
+        or type = 'video' and blocks.video = events.id
+    )
+    left join search_realms on search_realms.id = blocks.realm
+    group by events.id, series.id;
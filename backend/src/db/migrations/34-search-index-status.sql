@@ -0,0 +1,13 @@
+-- Table containing meta information about the search index, similar to
+-- `sync_status`. Only has a single row.
+create table search_index_status (
+    -- When items from the search index queue were last sent to the search
+    -- index (or the whole index was rebuilt). `null` if that never happened.
+    last_indexed_at timestamp with time zone
+);
+
+insert into search_index_status (last_indexed_at) values (null);
+
+comment on table search_index_status is 'Meta information about the search index (single row)';
+comment on column search_index_status.last_indexed_at is
+    'When items were last sent to the search index, `null` if never';
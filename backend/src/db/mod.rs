@@ -1,6 +1,7 @@
 //! Database related things.
 
 use deadpool_postgres::{Config as PoolConfig, Pool, Runtime};
+use once_cell::sync::OnceCell;
 use secrecy::{ExposeSecret, Secret};
 use rustls::{
     Error,
@@ -15,19 +16,25 @@ use std::{
 };
 use tokio_postgres::NoTls;
 
-use crate::{http::{self, Response}, prelude::*};
+use crate::{http::{self, Response}, prelude::*, util::HttpHost};
 
 
 pub(crate) mod cmd;
+pub(crate) mod collation;
+mod listen;
 mod migrations;
 mod query;
+pub(crate) mod realm_names;
+mod schema_snapshot;
+pub(crate) mod series_stats;
 mod tx;
 pub(crate) mod types;
 pub(crate) mod util;
 
 pub(crate) use self::{
+    listen::listen,
     tx::Transaction,
-    migrations::{migrate, MigrationPlan},
+    migrations::{auto_migrate, migrate, MigrateOptions, MigrationPlan},
 };
 
 
@@ -69,6 +76,60 @@ pub(crate) struct DbConfig {
     /// install the certificate globally on the system. Has to be a PEM encoded
     /// file containing one or more X509 certificates.
     server_cert: Option<PathBuf>,
+
+    /// Whether Tobira automatically runs missing database migrations when
+    /// starting. If `false`, Tobira only checks whether all migrations are
+    /// applied and refuses to start if not. You then have to run
+    /// `tobira db migrate` manually, e.g. with `migration_user` (see below).
+    #[config(default = true)]
+    pub(crate) auto_migrate: bool,
+
+    /// Separate credentials only used by `tobira db migrate`. Useful if the
+    /// normal DB user is not allowed to modify the schema. Make sure the normal
+    /// user is granted access to all objects created by migrations, e.g. via
+    /// `alter default privileges`.
+    #[config(nested)]
+    migration_user: MigrationUserConfig,
+
+    /// S3-compatible object storage used by `tobira db dump --s3`. If the
+    /// credentials are not set here, they are taken from the environment
+    /// variables `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// `AWS_SESSION_TOKEN` or, if those are not set either, from the EC2
+    /// instance metadata service.
+    #[config(nested)]
+    pub(crate) s3: S3Config,
+}
+
+#[derive(Debug, confique::Config, Clone)]
+pub(crate) struct MigrationUserConfig {
+    /// The username of the database user used for migrations. If not set,
+    /// `db.user` is used.
+    user: Option<String>,
+
+    /// The password of the database user used for migrations. Has to be set
+    /// if and only if `user` is set.
+    password: Option<Secret<String>>,
+}
+
+#[derive(Debug, confique::Config, Clone)]
+pub(crate) struct S3Config {
+    /// Base URL of the S3 API. Buckets are addressed in path style, i.e.
+    /// `<endpoint>/<bucket>/<key>`. If not set, the AWS endpoint of `region`
+    /// is used.
+    ///
+    /// Example: "https://minio.my-uni.edu".
+    pub(crate) endpoint: Option<HttpHost>,
+
+    /// The region of the bucket. If not set, the environment variables
+    /// `AWS_REGION` and `AWS_DEFAULT_REGION` are checked, falling back to
+    /// "us-east-1".
+    pub(crate) region: Option<String>,
+
+    /// Access key ID. Has to be set if and only if `secret_access_key` is set.
+    pub(crate) access_key_id: Option<String>,
+
+    /// Secret access key. Has to be set if and only if `access_key_id` is set.
+    pub(crate) secret_access_key: Option<Secret<String>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
@@ -84,10 +145,37 @@ impl DbConfig {
         if self.server_cert.is_some() && self.tls_mode != TlsMode::On {
             bail!(r#"`db.server_cert` is set, but TLS mode is NOT "on", which makes no sense"#);
         }
+        if self.migration_user.user.is_some() != self.migration_user.password.is_some() {
+            bail!("`db.migration_user.user` and `db.migration_user.password` have to be \
+                specified both or not at all");
+        }
+        if self.s3.access_key_id.is_some() != self.s3.secret_access_key.is_some() {
+            bail!("`db.s3.access_key_id` and `db.s3.secret_access_key` have to be \
+                specified both or not at all");
+        }
 
         Ok(())
     }
 
+    /// Returns the config that should be used for running migrations, i.e.
+    /// with `migration_user` credentials if those are configured.
+    pub(crate) fn for_migrations(&self) -> Self {
+        let mut out = self.clone();
+        let MigrationUserConfig { user, password } = &self.migration_user;
+        if let (Some(user), Some(password)) = (user, password) {
+            out.user = user.clone();
+            out.password = password.clone();
+        }
+        out
+    }
+
+    /// Whether the database runs on this machine (according to `host`), i.e.
+    /// whether its files might be accessible.
+    pub(crate) fn is_local(&self) -> bool {
+        self.host.starts_with('/')
+            || ["localhost", "127.0.0.1", "::1"].contains(&self.host.as_str())
+    }
+
     /// Checks that the server certificate file, if given, exists and is valid.
     /// Basically only for the `check` subcommand.
     pub(crate) fn check_server_cert(&self) -> Result<()> {
@@ -109,21 +197,20 @@ pub(crate) type DbConnection = deadpool::managed::Object<deadpool_postgres::Mana
 
 
 /// Creates a new database connection pool.
+/// `application_name` of all DB connections of this process, see
+/// `set_application_name`.
+static APPLICATION_NAME: OnceCell<String> = OnceCell::new();
+
+/// Sets the `application_name` of all DB connections created afterwards to
+/// `Tobira (<subcommand>)`. This makes it possible to tell apart connections
+/// of different Tobira processes in `pg_stat_activity`, e.g. via
+/// `tobira db session-stats`. Should be called once at startup.
+pub(crate) fn set_application_name(subcommand: &str) {
+    let _ = APPLICATION_NAME.set(format!("Tobira ({subcommand})"));
+}
+
 pub(crate) async fn create_pool(config: &DbConfig) -> Result<Pool> {
-    let pool_config = PoolConfig {
-        user: Some(config.user.clone()),
-        password: Some(config.password.expose_secret().clone()),
-        host: Some(config.host.clone()),
-        port: Some(config.port),
-        dbname: Some(config.database.clone()),
-        ssl_mode: Some(if config.tls_mode == TlsMode::Off {
-            deadpool_postgres::SslMode::Disable
-        } else {
-            deadpool_postgres::SslMode::Require
-        }),
-        application_name: Some("Tobira".into()),
-        .. PoolConfig::default()
-    };
+    let pool_config = pool_config(config);
 
     debug!(
         "Connecting to 'postgresql://{}:*****@{}:{}/{}' (TLS: {:?})",
@@ -135,43 +222,12 @@ pub(crate) async fn create_pool(config: &DbConfig) -> Result<Pool> {
     );
 
     // Handle TLS and create pool.
-    let pool = if config.tls_mode == TlsMode::Off {
-        pool_config.create_pool(Some(Runtime::Tokio1), NoTls)?
-    } else {
-        // Prepare certificate store. If we do not verify the certificate, it's
-        // just empty. Otherwise we load system-wide root CAs.
-        let mut root_certs = rustls::RootCertStore::empty();
-        if config.tls_mode == TlsMode::On {
-            let system_certs = rustls_native_certs::load_native_certs()
-                .context("failed to load all system-wide certificates")?;
-
-            let system_count = system_certs.len();
-            for cert in system_certs {
-                root_certs.add(&rustls::Certificate(cert.0))
-                    .context("failed to load system-wide certificate")?;
-            }
-            debug!("Loaded {system_count} system-wide certificates");
-
-            // If a custom cert is given, we try to load it.
-            if let Some(cert_path) = &config.server_cert {
-                let custom_count = load_pem_file(cert_path, &mut root_certs)
-                    .with_context(|| format!("failed to load '{}'", cert_path.display()))?;
-                debug!("Loaded {} certificates from '{}'", custom_count, cert_path.display());
-            }
-        }
-
-        let mut tls_config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_certs)
-            .with_no_client_auth();
-
-        // Disable certificate validation if requested.
-        if config.tls_mode == TlsMode::WithoutVerifyCert {
-            tls_config.dangerous().set_certificate_verifier(Arc::new(DangerousAlwaysAcceptCerts));
+    let pool = match tls_config(config)? {
+        None => pool_config.create_pool(Some(Runtime::Tokio1), NoTls)?,
+        Some(tls_config) => {
+            let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
+            pool_config.create_pool(Some(Runtime::Tokio1), tls)?
         }
-
-        let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
-        pool_config.create_pool(Some(Runtime::Tokio1), tls)?
     };
     info!("Created database pool");
 
@@ -197,6 +253,67 @@ pub(crate) async fn create_pool(config: &DbConfig) -> Result<Pool> {
     Ok(pool)
 }
 
+/// Connection parameters shared by the pool and dedicated connections.
+fn pool_config(config: &DbConfig) -> PoolConfig {
+    PoolConfig {
+        user: Some(config.user.clone()),
+        password: Some(config.password.expose_secret().clone()),
+        host: Some(config.host.clone()),
+        port: Some(config.port),
+        dbname: Some(config.database.clone()),
+        ssl_mode: Some(if config.tls_mode == TlsMode::Off {
+            deadpool_postgres::SslMode::Disable
+        } else {
+            deadpool_postgres::SslMode::Require
+        }),
+        application_name: Some(
+            APPLICATION_NAME.get().cloned().unwrap_or_else(|| "Tobira".into()),
+        ),
+        .. PoolConfig::default()
+    }
+}
+
+/// Returns the TLS configuration for DB connections, `None` if TLS is off.
+fn tls_config(config: &DbConfig) -> Result<Option<rustls::ClientConfig>> {
+    if config.tls_mode == TlsMode::Off {
+        return Ok(None);
+    }
+
+    // Prepare certificate store. If we do not verify the certificate, it's
+    // just empty. Otherwise we load system-wide root CAs.
+    let mut root_certs = rustls::RootCertStore::empty();
+    if config.tls_mode == TlsMode::On {
+        let system_certs = rustls_native_certs::load_native_certs()
+            .context("failed to load all system-wide certificates")?;
+
+        let system_count = system_certs.len();
+        for cert in system_certs {
+            root_certs.add(&rustls::Certificate(cert.0))
+                .context("failed to load system-wide certificate")?;
+        }
+        debug!("Loaded {system_count} system-wide certificates");
+
+        // If a custom cert is given, we try to load it.
+        if let Some(cert_path) = &config.server_cert {
+            let custom_count = load_pem_file(cert_path, &mut root_certs)
+                .with_context(|| format!("failed to load '{}'", cert_path.display()))?;
+            debug!("Loaded {} certificates from '{}'", custom_count, cert_path.display());
+        }
+    }
+
+    let mut tls_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_certs)
+        .with_no_client_auth();
+
+    // Disable certificate validation if requested.
+    if config.tls_mode == TlsMode::WithoutVerifyCert {
+        tls_config.dangerous().set_certificate_verifier(Arc::new(DangerousAlwaysAcceptCerts));
+    }
+
+    Ok(Some(tls_config))
+}
+
 /// Checks out one DB connection from the pool or returns `Err` with a "service
 /// unavailable" response.
 pub(crate) async fn get_conn_or_service_unavailable(pool: &Pool) -> Result<DbConnection, Response> {
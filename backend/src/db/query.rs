@@ -3,11 +3,21 @@ use tokio_postgres::GenericClient;
 use crate::prelude::*;
 
 
-/// Returns meta information about all tables in the `public` schema.
+/// Returns the names of all tables in the `public` schema, sorted. Tables
+/// belonging to extensions (e.g. `spatial_ref_sys` of `postgis`) are excluded,
+/// as they are not managed by Tobira.
 pub(super) async fn all_table_names(db: &impl GenericClient) -> Result<Vec<String>> {
     let rows = db.query_raw(
-            "select table_name from information_schema.tables \
-                where table_schema = 'public' and table_type = 'BASE TABLE'",
+            "select c.relname::text from pg_class c \
+                where c.relnamespace = 'public'::regnamespace \
+                    and c.relkind in ('r', 'p') \
+                    and not exists ( \
+                        select from pg_depend d \
+                            where d.classid = 'pg_class'::regclass \
+                                and d.objid = c.oid \
+                                and d.deptype = 'e' \
+                    ) \
+                order by c.relname",
             dbargs![],
         )
         .await?
@@ -28,3 +38,26 @@ pub(super) async fn does_table_exist(db: &impl GenericClient, table_name: &str)
 
     Ok(row.get::<_, bool>(0))
 }
+
+/// Quotes the given identifier (e.g. a column name) for use in SQL.
+pub(super) fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Returns the quoted, schema-qualified name of a table in the `public`
+/// schema for use in SQL.
+pub(super) fn qualified(table: &str) -> String {
+    format!("public.{}", quote_ident(table))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::qualified;
+
+    #[test]
+    fn qualifies_and_quotes() {
+        assert_eq!(qualified("realms"), r#"public."realms""#);
+        assert_eq!(qualified(r#"we"ird"#), r#"public."we""ird""#);
+    }
+}
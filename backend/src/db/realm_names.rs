@@ -0,0 +1,77 @@
+//! Consistency checks for realms that derive their name from one of their
+//! blocks (see `07-realm-names.sql`). These names are resolved on the fly, so
+//! they cannot become outdated. But they end up blank if the event or series
+//! of the block is deleted (the reference is set to `null` then) or has no
+//! title.
+
+use std::fmt;
+
+use crate::prelude::*;
+use super::Db;
+
+
+/// A realm whose name resolves to `null`.
+pub(crate) struct BlankRealmName {
+    pub(crate) path: String,
+    /// If `true`, the event or series of the name source block was deleted.
+    /// Otherwise, it just has no title (e.g. a series that was not synced
+    /// yet).
+    pub(crate) source_deleted: bool,
+}
+
+impl fmt::Display for BlankRealmName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = if self.source_deleted {
+            "the video/series it takes its name from was deleted"
+        } else {
+            "the video/series it takes its name from has no title"
+        };
+        write!(f, "realm '{}' has no name: {reason}", self.path)
+    }
+}
+
+/// Returns all realms deriving their name from a block whose name resolves
+/// to `null`.
+pub(crate) async fn check(db: &Db) -> Result<Vec<BlankRealmName>> {
+    let rows = db
+        .query(
+            "select realms.full_path, coalesce(blocks.series, blocks.video) is null \
+                from realms \
+                join blocks on blocks.id = realms.name_from_block \
+                left join events on blocks.video = events.id \
+                left join series on blocks.series = series.id \
+                where coalesce(series.title, events.title) is null \
+                order by realms.full_path",
+            &[],
+        )
+        .await
+        .context("failed to query realm names")?;
+
+    Ok(rows.into_iter()
+        .map(|row| BlankRealmName {
+            path: row.get(0),
+            source_deleted: row.get(1),
+        })
+        .collect())
+}
+
+/// Fixes the trivial case: realms whose name source references a deleted
+/// event or series get their path segment as plain name. Realms whose source
+/// just has no title (yet) are left alone. Returns the paths of all fixed
+/// realms.
+pub(crate) async fn fix_deleted_sources(db: &Db) -> Result<Vec<String>> {
+    let rows = db
+        .query(
+            "update realms \
+                set name = realms.path_segment, name_from_block = null \
+                from blocks \
+                where blocks.id = realms.name_from_block \
+                    and coalesce(blocks.series, blocks.video) is null \
+                returning realms.full_path",
+            &[],
+        )
+        .await
+        .context("failed to fix realm names")?;
+
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
@@ -0,0 +1,2957 @@
+-- Snapshot of the database after applying all migrations. Empty databases
+-- are set up by applying this instead of running all migrations.
+--
+-- GENERATED by `tobira db generate-schema-snapshot`: do not edit manually!
+--
+-- migrations: 34
+-- migrations-chain: 7eba5880c40e919ec220cc23732725acd7d31cc56e8adf576b878709d0509abd
+
+--
+-- PostgreSQL database dump
+--
+
+
+
+SET LOCAL statement_timeout = 0;
+SET LOCAL lock_timeout = 0;
+SET LOCAL idle_in_transaction_session_timeout = 0;
+SET LOCAL client_encoding = 'UTF8';
+SET LOCAL standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', true);
+SET LOCAL check_function_bodies = false;
+SET LOCAL xmloption = content;
+SET LOCAL client_min_messages = warning;
+SET LOCAL row_security = off;
+
+--
+-- Name: pgcrypto; Type: EXTENSION; Schema: -; Owner: -
+--
+
+CREATE EXTENSION IF NOT EXISTS pgcrypto WITH SCHEMA public;
+
+
+--
+-- Name: EXTENSION pgcrypto; Type: COMMENT; Schema: -; Owner: -
+--
+
+
+
+--
+-- Name: announcement_severity; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.announcement_severity AS ENUM (
+    'info',
+    'warning',
+    'critical'
+);
+
+
+--
+-- Name: TYPE announcement_severity; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TYPE public.announcement_severity IS 'How important an announcement is';
+
+
+--
+-- Name: block_type; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.block_type AS ENUM (
+    'title',
+    'text',
+    'series',
+    'video',
+    'toc'
+);
+
+
+--
+-- Name: event_caption; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.event_caption AS (
+	uri text,
+	lang text
+);
+
+
+--
+-- Name: event_state; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.event_state AS ENUM (
+    'waiting',
+    'ready'
+);
+
+
+--
+-- Name: event_track; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.event_track AS (
+	uri text,
+	flavor text,
+	mimetype text,
+	resolution integer[],
+	is_master boolean
+);
+
+
+--
+-- Name: opencast_item_kind; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.opencast_item_kind AS ENUM (
+    'event',
+    'series'
+);
+
+
+--
+-- Name: realm_order; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.realm_order AS ENUM (
+    'by_index',
+    'alphabetic:asc',
+    'alphabetic:desc'
+);
+
+
+--
+-- Name: search_index_item_kind; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.search_index_item_kind AS ENUM (
+    'realm',
+    'event'
+);
+
+
+--
+-- Name: series_state; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.series_state AS ENUM (
+    'waiting',
+    'ready'
+);
+
+
+--
+-- Name: toc_order; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.toc_order AS ENUM (
+    'inherit',
+    'alphabetic:asc',
+    'alphabetic:desc'
+);
+
+
+--
+-- Name: upload_state; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.upload_state AS ENUM (
+    'processing',
+    'failed',
+    'ready'
+);
+
+
+--
+-- Name: video_list_layout; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.video_list_layout AS ENUM (
+    'grid',
+    'list',
+    'slider'
+);
+
+
+--
+-- Name: video_list_order; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.video_list_order AS ENUM (
+    'new_to_old',
+    'old_to_new',
+    'alphabetic'
+);
+
+
+--
+-- Name: randomized_id(text); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.randomized_id(entity text) RETURNS bigint
+    LANGUAGE sql
+    AS $_$
+    select xtea(
+        nextval(('__' || $1 || '_ids')::regclass),
+        (select key from __xtea_keys where entity = $1),
+        true
+    );
+$_$;
+
+
+SET LOCAL default_tablespace = '';
+
+SET LOCAL default_table_access_method = heap;
+
+--
+-- Name: realms; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.realms (
+    id bigint DEFAULT public.randomized_id('realm'::text) NOT NULL,
+    parent bigint,
+    path_segment text NOT NULL,
+    name text,
+    index integer DEFAULT 2147483647 NOT NULL,
+    child_order public.realm_order DEFAULT 'alphabetic:asc'::public.realm_order NOT NULL,
+    full_path text NOT NULL,
+    name_from_block bigint,
+    CONSTRAINT has_parent CHECK (((id = 0) OR (parent IS NOT NULL))),
+    CONSTRAINT no_empty_name CHECK ((name <> ''::text)),
+    CONSTRAINT root_no_path CHECK (((id <> 0) OR ((parent IS NULL) AND (path_segment = ''::text) AND (full_path = ''::text)))),
+    CONSTRAINT valid_name_source CHECK ((((id = 0) AND (name IS NULL) AND (name_from_block IS NULL)) OR ((id <> 0) AND ((name IS NULL) <> (name_from_block IS NULL))))),
+    CONSTRAINT valid_path CHECK (((id = 0) OR ((path_segment !~ '[\u0000-\u001F\u007F-\u009F]'::text) AND (path_segment !~ '[\u0020\u00A0\u1680\u2000-\u200A\u2028\u2029\u202F\u205F\u3000]'::text) AND (path_segment !~ '["<>[\\\]^`{|}#%/?]'::text) AND (path_segment !~ '^[-+~@_!$&;:.,=*''()]'::text) AND (octet_length(path_segment) >= 2))))
+);
+
+
+--
+-- Name: TABLE realms; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.realms IS 'The page hierarchy of Tobira. Each realm is one page with a path and content blocks';
+
+
+--
+-- Name: COLUMN realms.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.id IS 'Randomized ID. The root realm has ID 0';
+
+
+--
+-- Name: COLUMN realms.parent; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.parent IS 'Parent realm. Only `null` for the root realm';
+
+
+--
+-- Name: COLUMN realms.path_segment; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.path_segment IS 'Last segment of the realm''s path. Empty for the root realm';
+
+
+--
+-- Name: COLUMN realms.name; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.name IS 'Explicitly set name of the realm. `null` if the name is derived from a block';
+
+
+--
+-- Name: COLUMN realms.index; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.index IS 'Position among its siblings. Only used if the parent''s `child_order` is ''by_index''';
+
+
+--
+-- Name: COLUMN realms.child_order; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.child_order IS 'How the children of this realm are ordered';
+
+
+--
+-- Name: COLUMN realms.full_path; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.full_path IS 'Full path of the realm, calculated by triggers. Empty for the root realm';
+
+
+--
+-- Name: COLUMN realms.name_from_block; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.realms.name_from_block IS 'Block of this realm (series or video) whose title is used as realm name';
+
+
+--
+-- Name: ancestors_of_realm(bigint); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.ancestors_of_realm(realm_id bigint) RETURNS SETOF public.realms
+    LANGUAGE sql
+    AS $$
+with recursive ancestors as (
+    select realms, 1 as height
+    from realms
+    where id = (select parent from realms where id = realm_id)
+  union all
+    select r, a.height + 1 as height
+    from ancestors a
+    join realms r on (a.realms).parent = r.id
+    where (a.realms).id <> 0
+)
+select (ancestors.realms).* from ancestors order by height desc
+$$;
+
+
+--
+-- Name: check_block_as_name_source(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.check_block_as_name_source() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    -- If the updated block is not used as name source, all is good.
+    if (select name_from_block from realms where id = new.realm) <> new.id then
+        return new;
+    end if;
+
+    perform check_name_source_block_of_realm(new.id, new.realm);
+    return new;
+end;
+$$;
+
+
+--
+-- Name: check_metadata_format(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.check_metadata_format() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+declare
+    col text := 'events.metadata';
+    namespace record;
+    field record;
+    element jsonb;
+begin
+    if jsonb_typeof(new.metadata) <> 'object' then
+        raise exception '% is %, but should be a JSON object', col, jsonb_typeof(new.metadata);
+    end if;
+
+    for namespace in select * from jsonb_each(new.metadata) loop
+        if jsonb_typeof(namespace.value) <> 'object' then
+            raise exception '%: type of top level field "%" is %, but should be object',
+                col,
+                namespace.key,
+                jsonb_typeof(namespace.value);
+        end if;
+
+        for field in select * from jsonb_each(namespace.value) loop
+            if jsonb_typeof(field.value) <> 'array' then
+                raise exception '%: type of field "%.%" is %, but should be array',
+                    col,
+                    namespace.key,
+                    field.key,
+                    jsonb_typeof(field.value);
+            end if;
+
+            for element in select * from jsonb_array_elements(field.value) loop
+                if jsonb_typeof(element) <> 'string' then
+                    raise exception '%: found non-string element "%" in "%.%", but that field should be a string array',
+                        col,
+                        element,
+                        namespace.key,
+                        field.key;
+                end if;
+            end loop;
+        end loop;
+    end loop;
+    return new;
+end;
+$$;
+
+
+--
+-- Name: check_name_source_block_of_realm(bigint, bigint); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.check_name_source_block_of_realm(block bigint, realm bigint) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if (select blocks.realm from blocks where id = block) <> realm then
+        raise exception
+            'a realm can only use its own blocks as name source (block %, realm %)',
+            block, realm;
+    end if;
+
+    if (select type from blocks where id = block) != all(array['series', 'video']::block_type[]) then
+        raise exception
+            'a block that is used as realm name source must have the type "series" or "video" (block %, realm %)',
+            block, realm;
+    end if;
+end;
+$$;
+
+
+--
+-- Name: check_name_source_block_on_realm_change(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.check_name_source_block_on_realm_change() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    perform check_name_source_block_of_realm(new.name_from_block, new.id);
+    return new;
+end;
+$$;
+
+
+--
+-- Name: illegal_root_modification(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.illegal_root_modification() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    raise exception 'Deleting the root or changing its ID realm is not allowed';
+end;
+$$;
+
+
+--
+-- Name: prepare_randomized_ids(text); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.prepare_randomized_ids(entity text) RETURNS void
+    LANGUAGE plpgsql
+    AS $$ begin
+    execute 'create sequence '
+        || quote_ident('__' || entity || '_ids')
+        || ' minvalue -9223372036854775808 cycle;';
+    insert into __xtea_keys (entity) values (entity);
+end; $$;
+
+
+--
+-- Name: series; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.series (
+    id bigint DEFAULT public.randomized_id('series'::text) NOT NULL,
+    state public.series_state NOT NULL,
+    opencast_id text NOT NULL,
+    read_roles text[],
+    write_roles text[],
+    title text,
+    description text,
+    updated timestamp with time zone NOT NULL,
+    event_count integer DEFAULT 0 NOT NULL,
+    last_event_update timestamp with time zone,
+    license text,
+    license_spdx text,
+    external_origin text,
+    CONSTRAINT ready_series_has_fields CHECK (((state <> 'ready'::public.series_state) OR ((title IS NOT NULL) AND (read_roles IS NOT NULL) AND (write_roles IS NOT NULL) AND (updated <> '-infinity'::timestamp with time zone)))),
+    CONSTRAINT waiting_series_not_updated CHECK (((state <> 'waiting'::public.series_state) OR (updated = '-infinity'::timestamp with time zone)))
+);
+
+
+--
+-- Name: TABLE series; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.series IS 'Opencast series synced from Opencast';
+
+
+--
+-- Name: COLUMN series.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.id IS 'Randomized ID';
+
+
+--
+-- Name: COLUMN series.state; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.state IS '''waiting'' if the series is only known via an event so far, ''ready'' once synced';
+
+
+--
+-- Name: COLUMN series.opencast_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.opencast_id IS 'The Opencast UUID of the series';
+
+
+--
+-- Name: COLUMN series.read_roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.read_roles IS 'Roles that are allowed to read the series';
+
+
+--
+-- Name: COLUMN series.write_roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.write_roles IS 'Roles that are allowed to write the series';
+
+
+--
+-- Name: COLUMN series.title; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.title IS 'Title of the series';
+
+
+--
+-- Name: COLUMN series.description; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.description IS 'Description of the series';
+
+
+--
+-- Name: COLUMN series.updated; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.updated IS 'When the series was last modified in Opencast';
+
+
+--
+-- Name: COLUMN series.event_count; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.event_count IS 'Number of events in this series (denormalized)';
+
+
+--
+-- Name: COLUMN series.last_event_update; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.last_event_update IS 'Latest `updated` of all synced events in this series, null if none (denormalized)';
+
+
+--
+-- Name: COLUMN series.license; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.license IS 'License as stored in Opencast';
+
+
+--
+-- Name: COLUMN series.license_spdx; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.license_spdx IS 'SPDX identifier of `license`, null if unknown or not set';
+
+
+--
+-- Name: COLUMN series.external_origin; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.series.external_origin IS 'Base URL of the Tobira instance this series was imported from, `null` for Opencast series';
+
+
+--
+-- Name: queue_all_events_of_series_for_reindex(public.series); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_all_events_of_series_for_reindex(series public.series) RETURNS void
+    LANGUAGE sql
+    AS $$
+    insert into search_index_queue (item_id, kind)
+    select events.id, 'event'
+    from events
+    where events.series = series.id
+    on conflict do nothing
+$$;
+
+
+--
+-- Name: queue_all_events_of_touched_series_for_reindex(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_all_events_of_touched_series_for_reindex() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if tg_op <> 'INSERT' then
+        perform queue_all_events_of_series_for_reindex(old);
+    end if;
+    if tg_op <> 'DELETE' then
+        perform queue_all_events_of_series_for_reindex(new);
+    end if;
+    return null;
+end;
+$$;
+
+
+--
+-- Name: blocks; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.blocks (
+    id bigint DEFAULT public.randomized_id('block'::text) NOT NULL,
+    realm bigint NOT NULL,
+    type public.block_type NOT NULL,
+    index smallint NOT NULL,
+    text_content text,
+    series bigint,
+    videolist_order public.video_list_order,
+    video bigint,
+    show_title boolean DEFAULT true,
+    show_metadata boolean DEFAULT false,
+    toc_depth smallint,
+    toc_show_counts boolean,
+    toc_order public.toc_order,
+    videolist_layout public.video_list_layout DEFAULT 'grid'::public.video_list_layout,
+    metadata_filter jsonb,
+    text_html text,
+    CONSTRAINT index_positive CHECK ((index >= 0)),
+    CONSTRAINT metadata_filter_only_for_series CHECK (((type = 'series'::public.block_type) OR (metadata_filter IS NULL))),
+    CONSTRAINT series_block_has_fields CHECK (((type <> 'series'::public.block_type) OR ((videolist_order IS NOT NULL) AND (videolist_layout IS NOT NULL) AND (show_title IS NOT NULL) AND (show_metadata IS NOT NULL)))),
+    CONSTRAINT text_block_has_fields CHECK (((type <> 'text'::public.block_type) OR (text_content IS NOT NULL))),
+    CONSTRAINT text_html_only_for_text CHECK (((type = 'text'::public.block_type) OR (text_html IS NULL))),
+    CONSTRAINT title_block_has_fields CHECK (((type <> 'title'::public.block_type) OR (text_content IS NOT NULL))),
+    CONSTRAINT toc_block_has_fields CHECK (((type <> 'toc'::public.block_type) OR ((toc_depth IS NOT NULL) AND (toc_show_counts IS NOT NULL) AND (toc_order IS NOT NULL)))),
+    CONSTRAINT toc_depth_in_range CHECK (((toc_depth >= 1) AND (toc_depth <= 4))),
+    CONSTRAINT video_block_has_fields CHECK (((type <> 'video'::public.block_type) OR (show_title IS NOT NULL)))
+);
+
+
+--
+-- Name: TABLE blocks; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.blocks IS 'Content blocks of realms. Columns not used by a block''s type are `null`';
+
+
+--
+-- Name: COLUMN blocks.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.id IS 'Randomized ID';
+
+
+--
+-- Name: COLUMN blocks.realm; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.realm IS 'The realm this block belongs to';
+
+
+--
+-- Name: COLUMN blocks.type; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.type IS 'The kind of block, determining which other columns are used';
+
+
+--
+-- Name: COLUMN blocks.index; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.index IS 'Position of the block within its realm, starting at 0';
+
+
+--
+-- Name: COLUMN blocks.text_content; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.text_content IS 'Content of title and text blocks';
+
+
+--
+-- Name: COLUMN blocks.series; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.series IS 'Series shown by series blocks';
+
+
+--
+-- Name: COLUMN blocks.videolist_order; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.videolist_order IS 'Order of videos in series blocks';
+
+
+--
+-- Name: COLUMN blocks.video; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.video IS 'Event shown by video blocks';
+
+
+--
+-- Name: COLUMN blocks.show_title; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.show_title IS 'Whether series and video blocks show their title';
+
+
+--
+-- Name: COLUMN blocks.show_metadata; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.show_metadata IS 'Whether series blocks show the series description';
+
+
+--
+-- Name: COLUMN blocks.toc_depth; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.toc_depth IS 'Number of realm levels shown by TOC blocks';
+
+
+--
+-- Name: COLUMN blocks.toc_show_counts; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.toc_show_counts IS 'Whether TOC blocks show video counts';
+
+
+--
+-- Name: COLUMN blocks.toc_order; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.toc_order IS 'Order of entries in TOC blocks';
+
+
+--
+-- Name: COLUMN blocks.videolist_layout; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.videolist_layout IS 'How videos in series blocks are arranged';
+
+
+--
+-- Name: COLUMN blocks.metadata_filter; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.blocks.metadata_filter IS 'Only for series blocks: only events whose metadata contains this are shown';
+
+
+--
+-- Name: queue_block_for_reindex(public.blocks); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_block_for_reindex(block public.blocks) RETURNS void
+    LANGUAGE sql
+    AS $$
+    with listed_events as (
+        select id from events where id = block.video
+        union all select id from events where series = block.series
+    )
+    insert into search_index_queue (item_id, kind)
+    select id, 'event' from listed_events
+    on conflict do nothing;
+$$;
+
+
+--
+-- Name: queue_blocks_for_reindex(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_blocks_for_reindex() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if tg_op <> 'INSERT' then
+        perform queue_block_for_reindex(old);
+    end if;
+    if tg_op <> 'DELETE' then
+        perform queue_block_for_reindex(new);
+    end if;
+    return null;
+end;
+$$;
+
+
+--
+-- Name: events; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.events (
+    id bigint DEFAULT public.randomized_id('event'::text) NOT NULL,
+    state public.event_state NOT NULL,
+    opencast_id text NOT NULL,
+    series bigint,
+    part_of text,
+    is_live boolean NOT NULL,
+    read_roles text[] NOT NULL,
+    write_roles text[] NOT NULL,
+    title text NOT NULL,
+    description text,
+    duration integer,
+    created timestamp with time zone NOT NULL,
+    updated timestamp with time zone NOT NULL,
+    start_time timestamp with time zone,
+    end_time timestamp with time zone,
+    creators text[] DEFAULT '{}'::text[] NOT NULL,
+    metadata jsonb NOT NULL,
+    thumbnail text,
+    tracks public.event_track[],
+    captions public.event_caption[],
+    license text,
+    license_spdx text,
+    creator_names text[],
+    is_processing boolean DEFAULT false NOT NULL,
+    external_origin text,
+    CONSTRAINT no_null_caption_items CHECK ((array_position(captions, NULL::public.event_caption) IS NULL)),
+    CONSTRAINT no_null_creators CHECK ((array_position(creators, NULL::text) IS NULL)),
+    CONSTRAINT no_null_read_roles CHECK ((array_position(read_roles, NULL::text) IS NULL)),
+    CONSTRAINT no_null_tracks CHECK ((array_position(tracks, NULL::public.event_track) IS NULL)),
+    CONSTRAINT no_null_write_roles CHECK ((array_position(write_roles, NULL::text) IS NULL)),
+    CONSTRAINT ready_event_has_fields CHECK (((state <> 'ready'::public.event_state) OR ((duration IS NOT NULL) AND (tracks IS NOT NULL) AND (array_length(tracks, 1) > 0) AND (captions IS NOT NULL)))),
+    CONSTRAINT waiting_event_not_updated CHECK (((state <> 'waiting'::public.event_state) OR (updated = '-infinity'::timestamp with time zone)))
+);
+
+
+--
+-- Name: TABLE events; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.events IS 'Opencast events (videos) synced from Opencast';
+
+
+--
+-- Name: COLUMN events.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.id IS 'Randomized ID';
+
+
+--
+-- Name: COLUMN events.state; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.state IS '''waiting'' if the event is not fully synced yet, ''ready'' otherwise';
+
+
+--
+-- Name: COLUMN events.opencast_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.opencast_id IS 'The Opencast UUID of the event';
+
+
+--
+-- Name: COLUMN events.series; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.series IS 'Series of this event. `null` if the event has no series or the series is not known yet';
+
+
+--
+-- Name: COLUMN events.part_of; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.part_of IS 'The raw Opencast series ID of this event';
+
+
+--
+-- Name: COLUMN events.is_live; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.is_live IS 'Whether this is a live event. If so, `created` is the planned start date';
+
+
+--
+-- Name: COLUMN events.read_roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.read_roles IS 'Roles that are allowed to read the event';
+
+
+--
+-- Name: COLUMN events.write_roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.write_roles IS 'Roles that are allowed to write the event';
+
+
+--
+-- Name: COLUMN events.title; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.title IS 'Title of the event';
+
+
+--
+-- Name: COLUMN events.description; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.description IS 'Description of the event';
+
+
+--
+-- Name: COLUMN events.duration; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.duration IS 'Duration in milliseconds';
+
+
+--
+-- Name: COLUMN events.created; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.created IS 'Creation date of the event';
+
+
+--
+-- Name: COLUMN events.updated; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.updated IS 'When the event was last modified in Opencast';
+
+
+--
+-- Name: COLUMN events.start_time; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.start_time IS 'Start time of the recording';
+
+
+--
+-- Name: COLUMN events.end_time; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.end_time IS 'End time of the recording';
+
+
+--
+-- Name: COLUMN events.creators; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.creators IS 'Creators as stored in Opencast';
+
+
+--
+-- Name: COLUMN events.metadata; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.metadata IS 'Additional metadata as JSON object (namespace -> field -> array of strings)';
+
+
+--
+-- Name: COLUMN events.thumbnail; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.thumbnail IS 'URL to a thumbnail image';
+
+
+--
+-- Name: COLUMN events.tracks; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.tracks IS 'Media tracks of the event';
+
+
+--
+-- Name: COLUMN events.captions; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.captions IS 'Caption tracks of the event';
+
+
+--
+-- Name: COLUMN events.license; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.license IS 'License as stored in Opencast';
+
+
+--
+-- Name: COLUMN events.license_spdx; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.license_spdx IS 'SPDX identifier of `license`, null if unknown or not set';
+
+
+--
+-- Name: COLUMN events.creator_names; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.creator_names IS 'Names of the persons linked to this event, null if not linked yet';
+
+
+--
+-- Name: COLUMN events.external_origin; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.events.external_origin IS 'Base URL of the Tobira instance this event was imported from, `null` for Opencast events';
+
+
+--
+-- Name: queue_event_for_reindex(public.events); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_event_for_reindex(event public.events) RETURNS void
+    LANGUAGE sql
+    AS $$
+    insert into search_index_queue (item_id, kind)
+    values (event.id, 'event')
+    on conflict do nothing
+$$;
+
+
+--
+-- Name: queue_realm_for_reindex(public.realms); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_realm_for_reindex(realm public.realms) RETURNS void
+    LANGUAGE sql
+    AS $$
+    insert into search_index_queue (item_id, kind)
+    values (realm.id, 'realm')
+    on conflict do nothing
+$$;
+
+
+--
+-- Name: queue_realm_on_updated_title(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_realm_on_updated_title() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    -- A realm is affected if it is a decendant of a realm which resolved names
+    -- has changed due to the series/video title change.
+    with affected_realms as (
+        select affected.*
+        from blocks
+        inner join realms on blocks.id = realms.name_from_block
+        inner join realms as affected on affected.full_path like realms.full_path || '%'
+        -- Ho ho ho, this is interesting. To deduplicate some code, we use this
+        -- function with both, events and series. And we don't even care which kind
+        -- this function is called with. We just accept both. This is fine
+        -- because: (a) a series and event having the same ID is exceeeeedingly
+        -- rare, and (b) if this virtually impossible case actually arises, we just
+        -- unnecessarily queue some events -> no harm done.
+        where blocks.series = new.id or blocks.video = new.id
+    )
+    insert into search_index_queue (item_id, kind)
+    -- The realms themselves have to be queued.
+    select id, 'realm'::search_index_item_kind
+    from affected_realms
+    union all
+    -- But also all events included somewhere in those realms.
+    select events.id, 'event'::search_index_item_kind
+    from affected_realms
+    inner join blocks on affected_realms.id = blocks.realm
+    inner join events on (
+        type = 'series' and blocks.series = events.series
+        or type = 'video' and blocks.video = events.id
+    )
+    on conflict do nothing;
+
+    return null;
+end;
+$$;
+
+
+--
+-- Name: queue_touched_event_for_reindex(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_touched_event_for_reindex() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if tg_op <> 'INSERT' then
+        perform queue_event_for_reindex(old);
+    end if;
+    if tg_op <> 'DELETE' then
+        perform queue_event_for_reindex(new);
+    end if;
+    return null;
+end;
+$$;
+
+
+--
+-- Name: queue_touched_realm_for_reindex(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.queue_touched_realm_for_reindex() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if tg_op <> 'INSERT' then
+        perform queue_realm_for_reindex(old);
+    end if;
+    if tg_op <> 'DELETE' then
+        perform queue_realm_for_reindex(new);
+    end if;
+
+    -- If the name of this realm has changed, we also need to queue all child
+    -- realms as their 'ancestor_names' have changed.
+    if tg_op = 'UPDATE' and (
+        old.name is distinct from new.name or
+        old.name_from_block is distinct from new.name_from_block
+    ) then
+        insert into search_index_queue (item_id, kind)
+        select id, 'realm'
+        from realms
+        where full_path like new.full_path || '/%'
+        on conflict do nothing;
+
+        -- We also need to queue all events that are included on any of those
+        -- sub realms as they store their host realms. This time, we include
+        -- the realm itself (note the '%' instead of '/%' above).
+        insert into search_index_queue (item_id, kind)
+        select events.id, 'event'
+        from events
+        inner join blocks on (
+            type = 'series' and blocks.series = events.series
+            or type = 'video' and blocks.video = events.id
+        )
+        inner join realms on realms.id = blocks.realm
+        where full_path like new.full_path || '%'
+        on conflict do nothing;
+    end if;
+    return null;
+end;
+$$;
+
+
+--
+-- Name: remember_deleted_opencast_items(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.remember_deleted_opencast_items() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    insert into deleted_items (opencast_id, kind, our_id)
+        values (old.opencast_id, tg_argv[0]::opencast_item_kind, old.id)
+        on conflict do nothing;
+    return null;
+end;
+$$;
+
+
+--
+-- Name: resolved_name(public.realms); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.resolved_name(realm public.realms) RETURNS text
+    LANGUAGE sql
+    AS $$
+    select coalesce(series.title, events.title)
+    from blocks
+    left join events on blocks.video = events.id
+    left join series on blocks.series = series.id
+    where blocks.id = realm.name_from_block
+    union
+    select realm.name where realm.name_from_block is null
+$$;
+
+
+--
+-- Name: reuse_existing_id_on_insert(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.reuse_existing_id_on_insert() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+declare
+    remembered_id bigint;
+begin
+    delete from deleted_items
+        where opencast_id = new.opencast_id and kind = tg_argv[0]::opencast_item_kind
+        returning our_id into remembered_id;
+    new.id := coalesce(remembered_id, new.id);
+    return new;
+end;
+$$;
+
+
+--
+-- Name: set_full_realm_path(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.set_full_realm_path() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    if NEW.full_path is not null then
+        raise exception 'do not set the full path of a realm directly (for realm %)', NEW.id;
+    end if;
+
+    NEW.full_path := (select full_path from realms where id = NEW.parent) || '/' || NEW.path_segment;
+    return NEW;
+end;
+$$;
+
+
+--
+-- Name: update_creator_names(bigint[]); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.update_creator_names(event_ids bigint[]) RETURNS void
+    LANGUAGE sql
+    AS $$
+    update events set creator_names = array(
+        select persons.name
+        from event_persons
+        join persons on persons.id = event_persons.person
+        where event_persons.event = events.id
+        order by event_persons.position
+    )
+    where events.id = any(event_ids)
+$$;
+
+
+--
+-- Name: update_full_realm_path(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.update_full_realm_path() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+    -- If only the name changed, we don't need to update anything.
+    if
+        NEW.path_segment is not distinct from OLD.path_segment and
+        NEW.parent is not distinct from OLD.parent and
+        NEW.full_path is not distinct from OLD.full_path
+    then
+        return NEW;
+    end if;
+
+    if TG_WHEN = 'BEFORE' then
+        -- If there was an attempt to change the full path directly and it wasn't
+        -- us, we raise an exception.
+        if NEW.full_path <> OLD.full_path and pg_trigger_depth() = 1 then
+            raise exception 'do not change the full path directly (for realm %)', OLD.id;
+        end if;
+
+        -- If we are in the "before" handler, we set the correct full path.
+        NEW.full_path := (select full_path from realms where id = NEW.parent)
+            || '/' || NEW.path_segment;
+        return NEW;
+    else
+        -- In the "after" handler, we update all children to recursively fire
+        -- this trigger.
+        update realms set full_path = '' where parent = NEW.id;
+        return null;
+    end if;
+end;
+$$;
+
+
+--
+-- Name: xtea(bigint, integer[], boolean); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.xtea(val bigint, key128 integer[], encrypt boolean) RETURNS bigint
+    LANGUAGE plpgsql IMMUTABLE STRICT
+    AS $$
+declare
+  -- we use bigint (int8) to implement unsigned 32 bits with modulo 32 arithmetic
+  -- (in C, uint32_t is used but pg's int4 is signed and would overflow).
+  -- the most significant halves of v0,v1,_sum must always be zero
+  -- they're AND'ed with 0xffffffff after every operation
+  v0 bigint;
+  v1 bigint;
+  _sum bigint:=0;
+  cr_key bigint[4]:=array[
+     case when key128[1]<0 then key128[1]+4294967296 else key128[1] end,
+     case when key128[2]<0 then key128[2]+4294967296 else key128[2] end,
+     case when key128[3]<0 then key128[3]+4294967296 else key128[3] end,
+     case when key128[4]<0 then key128[4]+4294967296 else key128[4] end
+   ];
+begin
+  v0 := (val>>32)&4294967295;
+  v1 := val&4294967295;
+  IF encrypt THEN
+    FOR i in 0..63 LOOP
+      v0 := (v0 + ((
+         ((v1<<4)&4294967295 # (v1>>5))
+           + v1)&4294967295
+           #
+           (_sum + cr_key[1+(_sum&3)::int])&4294967295
+           ))&4294967295;
+      _sum := (_sum + 2654435769) & 4294967295;
+      v1 := (v1 + ((
+             ((v0<<4)&4294967295 # (v0>>5))
+           + v0)&4294967295
+          #
+          (_sum + cr_key[1+((_sum>>11)&3)::int])&4294967295
+          ))&4294967295;
+    END LOOP;
+  ELSE
+    _sum := (2654435769 * 64)&4294967295;
+    FOR i in 0..63 LOOP
+      v1 := (v1 - ((
+          ((v0<<4)&4294967295 # (v0>>5))
+          + v0)&4294967295
+          #
+          (_sum + cr_key[1+((_sum>>11)&3)::int])&4294967295
+          ))&4294967295;
+
+      _sum := (_sum - 2654435769)& 4294967295;
+
+      v0 := (v0 - ((
+         ((v1<<4)&4294967295 # (v1>>5))
+           + v1)&4294967295
+           #
+           (_sum + cr_key[1+(_sum&3)::int])&4294967295
+           ))&4294967295;
+
+    END LOOP;
+  END IF;
+  return (v0<<32)|v1;
+end
+$$;
+
+
+--
+-- Name: xtea(bigint, bytea, boolean); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.xtea(val bigint, cr_key bytea, encrypt boolean) RETURNS bigint
+    LANGUAGE plpgsql IMMUTABLE
+    AS $$
+declare
+  bk int[4];
+  b bigint; -- unsigned 32 bits
+begin
+  if octet_length(cr_key)<>16 then
+     raise exception 'XTEA crypt key must be 16 bytes long.';
+  end if;
+  for i in 1..4 loop
+    b:=0;
+    for j in 0..3 loop
+      -- interpret cr_key as 4 big-endian signed 32 bits numbers
+      b:= (b<<8) | get_byte(cr_key, (i-1)*4+j);
+    end loop;
+    bk[i] := case when b>2147483647 then b-4294967296 else b end;
+  end loop;
+  return xtea(val, bk, encrypt);
+end
+$$;
+
+
+--
+-- Name: __block_ids; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.__block_ids
+    START WITH -9223372036854775808
+    INCREMENT BY 1
+    MINVALUE -9223372036854775808
+    NO MAXVALUE
+    CACHE 1
+    CYCLE;
+
+
+--
+-- Name: __event_ids; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.__event_ids
+    START WITH -9223372036854775808
+    INCREMENT BY 1
+    MINVALUE -9223372036854775808
+    NO MAXVALUE
+    CACHE 1
+    CYCLE;
+
+
+--
+-- Name: __person_ids; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.__person_ids
+    START WITH -9223372036854775808
+    INCREMENT BY 1
+    MINVALUE -9223372036854775808
+    NO MAXVALUE
+    CACHE 1
+    CYCLE;
+
+
+--
+-- Name: __realm_ids; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.__realm_ids
+    START WITH -9223372036854775808
+    INCREMENT BY 1
+    MINVALUE -9223372036854775808
+    NO MAXVALUE
+    CACHE 1
+    CYCLE;
+
+
+--
+-- Name: __series_ids; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.__series_ids
+    START WITH -9223372036854775808
+    INCREMENT BY 1
+    MINVALUE -9223372036854775808
+    NO MAXVALUE
+    CACHE 1
+    CYCLE;
+
+
+--
+-- Name: __xtea_keys; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.__xtea_keys (
+    entity text NOT NULL,
+    key bytea DEFAULT public.gen_random_bytes(16) NOT NULL,
+    CONSTRAINT valid_key CHECK ((octet_length(key) = 16))
+);
+
+
+--
+-- Name: TABLE __xtea_keys; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.__xtea_keys IS 'Secret keys used to scramble sequential IDs into random looking ones, one per entity type';
+
+
+--
+-- Name: COLUMN __xtea_keys.entity; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.__xtea_keys.entity IS 'Entity type the key is used for, e.g. ''event''';
+
+
+--
+-- Name: COLUMN __xtea_keys.key; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.__xtea_keys.key IS 'The 128 bit XTEA key';
+
+
+--
+-- Name: announcement_dismissals; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.announcement_dismissals (
+    announcement bigint NOT NULL,
+    session bytea NOT NULL
+);
+
+
+--
+-- Name: TABLE announcement_dismissals; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.announcement_dismissals IS 'Sessions in which an announcement was dismissed';
+
+
+--
+-- Name: COLUMN announcement_dismissals.announcement; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcement_dismissals.announcement IS 'The dismissed announcement';
+
+
+--
+-- Name: COLUMN announcement_dismissals.session; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcement_dismissals.session IS 'The session (`user_sessions.id`)';
+
+
+--
+-- Name: announcements; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.announcements (
+    id bigint NOT NULL,
+    title text NOT NULL,
+    body text NOT NULL,
+    severity public.announcement_severity NOT NULL,
+    active_from timestamp with time zone,
+    active_until timestamp with time zone,
+    CONSTRAINT active_range_valid CHECK ((active_from < active_until)),
+    CONSTRAINT title_not_empty CHECK ((title <> ''::text))
+);
+
+
+--
+-- Name: TABLE announcements; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.announcements IS 'The global announcement shown on all pages (at most one row)';
+
+
+--
+-- Name: COLUMN announcements.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.id IS 'Primary key';
+
+
+--
+-- Name: COLUMN announcements.title; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.title IS 'Short title of the announcement';
+
+
+--
+-- Name: COLUMN announcements.body; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.body IS 'Markdown text of the announcement';
+
+
+--
+-- Name: COLUMN announcements.severity; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.severity IS 'How important the announcement is';
+
+
+--
+-- Name: COLUMN announcements.active_from; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.active_from IS 'Announcement is not shown before this (if set)';
+
+
+--
+-- Name: COLUMN announcements.active_until; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.announcements.active_until IS 'Announcement is not shown after this (if set)';
+
+
+--
+-- Name: announcements_id_seq; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+ALTER TABLE public.announcements ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY (
+    SEQUENCE NAME public.announcements_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1
+);
+
+
+--
+-- Name: deleted_items; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.deleted_items (
+    opencast_id text NOT NULL,
+    kind public.opencast_item_kind NOT NULL,
+    our_id bigint NOT NULL
+);
+
+
+--
+-- Name: TABLE deleted_items; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.deleted_items IS 'Items deleted in Opencast, so that they can be removed from the search index';
+
+
+--
+-- Name: COLUMN deleted_items.opencast_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.deleted_items.opencast_id IS 'The Opencast UUID of the deleted item';
+
+
+--
+-- Name: COLUMN deleted_items.kind; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.deleted_items.kind IS 'Kind of the deleted item';
+
+
+--
+-- Name: COLUMN deleted_items.our_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.deleted_items.our_id IS 'The Tobira ID the deleted item had';
+
+
+--
+-- Name: event_persons; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.event_persons (
+    event bigint NOT NULL,
+    person bigint NOT NULL,
+    "position" smallint NOT NULL
+);
+
+
+--
+-- Name: TABLE event_persons; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.event_persons IS 'Links events to the persons of their `creators`';
+
+
+--
+-- Name: person_names; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.person_names (
+    name text NOT NULL,
+    person bigint NOT NULL,
+    CONSTRAINT person_names_name_check CHECK ((name = lower(name)))
+);
+
+
+--
+-- Name: TABLE person_names; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.person_names IS 'Normalized names (lowercase) referring to a person';
+
+
+--
+-- Name: persons; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.persons (
+    id bigint DEFAULT public.randomized_id('person'::text) NOT NULL,
+    name text NOT NULL
+);
+
+
+--
+-- Name: TABLE persons; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.persons IS 'Deduplicated creators of events';
+
+
+--
+-- Name: playback_positions; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.playback_positions (
+    username text NOT NULL,
+    event bigint NOT NULL,
+    "position" double precision NOT NULL,
+    updated timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT position_not_negative CHECK (("position" >= (0)::double precision))
+);
+
+
+--
+-- Name: TABLE playback_positions; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.playback_positions IS 'Per user playback positions to continue watching';
+
+
+--
+-- Name: COLUMN playback_positions.username; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.playback_positions.username IS 'Unique username of the user';
+
+
+--
+-- Name: COLUMN playback_positions.event; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.playback_positions.event IS 'The event that was watched';
+
+
+--
+-- Name: COLUMN playback_positions."position"; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.playback_positions."position" IS 'Seconds from the start of the video';
+
+
+--
+-- Name: COLUMN playback_positions.updated; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.playback_positions.updated IS 'When the position was last changed';
+
+
+--
+-- Name: search_realms; Type: VIEW; Schema: public; Owner: -
+--
+
+CREATE VIEW public.search_realms AS
+ SELECT realms.id,
+    COALESCE(realms.name, series.title, events.title) AS name,
+    realms.full_path,
+    ARRAY( SELECT public.resolved_name(ancestors.*) AS resolved_name
+           FROM public.ancestors_of_realm(realms.id) ancestors(id, parent, path_segment, name, index, child_order, full_path, name_from_block)
+         OFFSET 1) AS ancestor_names
+   FROM (((public.realms
+     LEFT JOIN public.blocks ON ((blocks.id = realms.name_from_block)))
+     LEFT JOIN public.events ON ((blocks.video = events.id)))
+     LEFT JOIN public.series ON ((blocks.series = series.id)));
+
+
+--
+-- Name: search_events; Type: VIEW; Schema: public; Owner: -
+--
+
+CREATE VIEW public.search_events AS
+SELECT
+    NULL::bigint AS id,
+    NULL::public.event_state AS state,
+    NULL::bigint AS series,
+    NULL::text AS series_title,
+    NULL::text AS title,
+    NULL::text AS description,
+    NULL::text[] AS creators,
+    NULL::text AS thumbnail,
+    NULL::integer AS duration,
+    NULL::boolean AS is_live,
+    NULL::timestamp with time zone AS created,
+    NULL::timestamp with time zone AS start_time,
+    NULL::timestamp with time zone AS end_time,
+    NULL::text[] AS read_roles,
+    NULL::text[] AS write_roles,
+    NULL::public.search_realms[] AS host_realms,
+    NULL::text AS license_spdx,
+    NULL::text AS external_origin;
+
+
+--
+-- Name: search_index_queue; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.search_index_queue (
+    id bigint NOT NULL,
+    item_id bigint NOT NULL,
+    kind public.search_index_item_kind NOT NULL
+);
+
+
+--
+-- Name: TABLE search_index_queue; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.search_index_queue IS 'Items that need to be (re)indexed in the search index';
+
+
+--
+-- Name: COLUMN search_index_queue.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.search_index_queue.id IS 'Auto incrementing ID used for queue ordering';
+
+
+--
+-- Name: COLUMN search_index_queue.item_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.search_index_queue.item_id IS 'ID of the realm/event that needs reindexing';
+
+
+--
+-- Name: COLUMN search_index_queue.kind; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.search_index_queue.kind IS 'Kind of the item referenced by `item_id`';
+
+
+--
+-- Name: search_index_queue_id_seq; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+ALTER TABLE public.search_index_queue ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY (
+    SEQUENCE NAME public.search_index_queue_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1
+);
+
+
+--
+-- Name: search_index_status; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.search_index_status (
+    last_indexed_at timestamp with time zone
+);
+
+
+--
+-- Name: TABLE search_index_status; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.search_index_status IS 'Meta information about the search index (single row)';
+
+
+--
+-- Name: COLUMN search_index_status.last_indexed_at; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.search_index_status.last_indexed_at IS 'When items were last sent to the search index, `null` if never';
+
+
+--
+-- Name: sync_backfill_progress; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.sync_backfill_progress (
+    job text NOT NULL,
+    last_event_id bigint NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+--
+-- Name: TABLE sync_backfill_progress; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.sync_backfill_progress IS 'Progress of `tobira sync backfill` runs';
+
+
+--
+-- Name: COLUMN sync_backfill_progress.job; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_backfill_progress.job IS 'Comma-separated, sorted list of the backfilled fields, e.g. `duration,resolution`';
+
+
+--
+-- Name: COLUMN sync_backfill_progress.last_event_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_backfill_progress.last_event_id IS 'ID of the last event that was processed';
+
+
+--
+-- Name: COLUMN sync_backfill_progress.updated_at; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_backfill_progress.updated_at IS 'When progress was last saved';
+
+
+--
+-- Name: sync_skipped_items; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.sync_skipped_items (
+    opencast_id text NOT NULL,
+    kind text,
+    error text NOT NULL,
+    skipped_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+--
+-- Name: TABLE sync_skipped_items; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.sync_skipped_items IS 'Harvest items skipped by the sync because they are malformed';
+
+
+--
+-- Name: COLUMN sync_skipped_items.opencast_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_skipped_items.opencast_id IS 'Opencast ID of the skipped item';
+
+
+--
+-- Name: COLUMN sync_skipped_items.kind; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_skipped_items.kind IS 'Kind of the item (e.g. `event`), if known';
+
+
+--
+-- Name: COLUMN sync_skipped_items.error; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_skipped_items.error IS 'Deserialization error, including the path to the offending field';
+
+
+--
+-- Name: COLUMN sync_skipped_items.skipped_at; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_skipped_items.skipped_at IS 'When the item was last skipped';
+
+
+--
+-- Name: sync_status; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.sync_status (
+    harvested_until timestamp without time zone NOT NULL
+);
+
+
+--
+-- Name: TABLE sync_status; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.sync_status IS 'Single row table holding the state of the Opencast sync';
+
+
+--
+-- Name: COLUMN sync_status.harvested_until; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.sync_status.harvested_until IS 'Exclusive upper limit (UTC) until which everything has been harvested';
+
+
+--
+-- Name: upload_jobs; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.upload_jobs (
+    id bigint NOT NULL,
+    opencast_id text NOT NULL,
+    uploader text NOT NULL,
+    title text NOT NULL,
+    state public.upload_state DEFAULT 'processing'::public.upload_state NOT NULL,
+    error_message text,
+    created timestamp with time zone DEFAULT now() NOT NULL,
+    updated timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT error_message_only_when_failed CHECK (((state = 'failed'::public.upload_state) = (error_message IS NOT NULL)))
+);
+
+
+--
+-- Name: TABLE upload_jobs; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.upload_jobs IS 'Videos uploaded via Tobira, tracked until they are synced';
+
+
+--
+-- Name: COLUMN upload_jobs.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.id IS 'Auto incrementing ID';
+
+
+--
+-- Name: COLUMN upload_jobs.opencast_id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.opencast_id IS 'The Opencast UUID of the ingested media package';
+
+
+--
+-- Name: COLUMN upload_jobs.uploader; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.uploader IS 'Username of the uploading user';
+
+
+--
+-- Name: COLUMN upload_jobs.title; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.title IS 'Title given at upload time';
+
+
+--
+-- Name: COLUMN upload_jobs.state; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.state IS 'Processing state of the upload';
+
+
+--
+-- Name: COLUMN upload_jobs.error_message; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.error_message IS 'Description of what went wrong. Only set if `state` is ''failed''';
+
+
+--
+-- Name: COLUMN upload_jobs.created; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.created IS 'When the upload was registered';
+
+
+--
+-- Name: COLUMN upload_jobs.updated; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.upload_jobs.updated IS 'When the state was last changed';
+
+
+--
+-- Name: upload_jobs_id_seq; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+ALTER TABLE public.upload_jobs ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY (
+    SEQUENCE NAME public.upload_jobs_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1
+);
+
+
+--
+-- Name: user_sessions; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.user_sessions (
+    id bytea NOT NULL,
+    username text NOT NULL,
+    display_name text NOT NULL,
+    roles text[] NOT NULL,
+    created timestamp without time zone DEFAULT now() NOT NULL,
+    lang text,
+    seq bigint NOT NULL
+);
+
+
+--
+-- Name: TABLE user_sessions; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.user_sessions IS 'Sessions of logged in users';
+
+
+--
+-- Name: COLUMN user_sessions.id; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.id IS 'Random session ID, stored in the session cookie';
+
+
+--
+-- Name: COLUMN user_sessions.username; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.username IS 'Unique username of the user';
+
+
+--
+-- Name: COLUMN user_sessions.display_name; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.display_name IS 'Name of the user for display purposes';
+
+
+--
+-- Name: COLUMN user_sessions.roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.roles IS 'Roles of the user';
+
+
+--
+-- Name: COLUMN user_sessions.created; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.created IS 'When the session was created (UTC)';
+
+
+--
+-- Name: COLUMN user_sessions.lang; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.lang IS 'Language chosen by the user (e.g. `de`), null if none was chosen explicitly';
+
+
+--
+-- Name: COLUMN user_sessions.seq; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.user_sessions.seq IS 'Sequential number for pagination, as the session ID must not be exposed';
+
+
+--
+-- Name: user_sessions_seq_seq; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+ALTER TABLE public.user_sessions ALTER COLUMN seq ADD GENERATED ALWAYS AS IDENTITY (
+    SEQUENCE NAME public.user_sessions_seq_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1
+);
+
+
+--
+-- Name: users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.users (
+    username text NOT NULL,
+    display_name text NOT NULL,
+    roles text[] NOT NULL,
+    first_login timestamp with time zone DEFAULT now() NOT NULL,
+    last_login timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+--
+-- Name: TABLE users; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON TABLE public.users IS 'Users that logged in at least once';
+
+
+--
+-- Name: COLUMN users.username; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.users.username IS 'Unique username of the user';
+
+
+--
+-- Name: COLUMN users.display_name; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.users.display_name IS 'Name of the user for display purposes (as of last login)';
+
+
+--
+-- Name: COLUMN users.roles; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.users.roles IS 'Roles of the user (as of last login)';
+
+
+--
+-- Name: COLUMN users.first_login; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.users.first_login IS 'When the user first logged in';
+
+
+--
+-- Name: COLUMN users.last_login; Type: COMMENT; Schema: public; Owner: -
+--
+
+COMMENT ON COLUMN public.users.last_login IS 'When the user last logged in';
+
+
+--
+-- Data for Name: announcement_dismissals; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: announcements; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: blocks; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: deleted_items; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: event_persons; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: events; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: person_names; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: persons; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: playback_positions; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: realms; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+INSERT INTO public.realms VALUES (0, NULL, '', NULL, 2147483647, 'alphabetic:asc', '', NULL);
+
+
+--
+-- Data for Name: search_index_queue; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: search_index_status; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+INSERT INTO public.search_index_status VALUES (NULL);
+
+
+--
+-- Data for Name: series; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: sync_backfill_progress; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: sync_skipped_items; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: sync_status; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+INSERT INTO public.sync_status VALUES ('1970-01-01 00:00:00');
+
+
+--
+-- Data for Name: upload_jobs; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: user_sessions; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Data for Name: users; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+
+
+--
+-- Name: __block_ids; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.__block_ids', -9223372036854775808, false);
+
+
+--
+-- Name: __event_ids; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.__event_ids', -9223372036854775808, false);
+
+
+--
+-- Name: __person_ids; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.__person_ids', -9223372036854775808, false);
+
+
+--
+-- Name: __realm_ids; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.__realm_ids', -291722038368258759, true);
+
+
+--
+-- Name: __series_ids; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.__series_ids', -9223372036854775808, false);
+
+
+--
+-- Name: announcements_id_seq; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.announcements_id_seq', 1, false);
+
+
+--
+-- Name: search_index_queue_id_seq; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.search_index_queue_id_seq', 1, false);
+
+
+--
+-- Name: upload_jobs_id_seq; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.upload_jobs_id_seq', 1, false);
+
+
+--
+-- Name: user_sessions_seq_seq; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.user_sessions_seq_seq', 1, false);
+
+
+--
+-- Name: __xtea_keys __xtea_keys_key_key; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.__xtea_keys
+    ADD CONSTRAINT __xtea_keys_key_key UNIQUE (key);
+
+
+--
+-- Name: __xtea_keys __xtea_keys_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.__xtea_keys
+    ADD CONSTRAINT __xtea_keys_pkey PRIMARY KEY (entity);
+
+
+--
+-- Name: announcement_dismissals announcement_dismissals_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.announcement_dismissals
+    ADD CONSTRAINT announcement_dismissals_pkey PRIMARY KEY (announcement, session);
+
+
+--
+-- Name: announcements announcements_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.announcements
+    ADD CONSTRAINT announcements_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: blocks blocks_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: deleted_items deleted_items_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.deleted_items
+    ADD CONSTRAINT deleted_items_pkey PRIMARY KEY (opencast_id, kind);
+
+
+--
+-- Name: event_persons event_persons_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.event_persons
+    ADD CONSTRAINT event_persons_pkey PRIMARY KEY (event, person);
+
+
+--
+-- Name: events events_opencast_id_key; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.events
+    ADD CONSTRAINT events_opencast_id_key UNIQUE (opencast_id);
+
+
+--
+-- Name: events events_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.events
+    ADD CONSTRAINT events_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: search_index_queue id_type_unique; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.search_index_queue
+    ADD CONSTRAINT id_type_unique UNIQUE (item_id, kind);
+
+
+--
+-- Name: blocks index_unique_in_realm; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT index_unique_in_realm UNIQUE (realm, index) DEFERRABLE;
+
+
+--
+-- Name: person_names person_names_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.person_names
+    ADD CONSTRAINT person_names_pkey PRIMARY KEY (name);
+
+
+--
+-- Name: persons persons_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.persons
+    ADD CONSTRAINT persons_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: playback_positions playback_positions_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.playback_positions
+    ADD CONSTRAINT playback_positions_pkey PRIMARY KEY (username, event);
+
+
+--
+-- Name: realms realms_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.realms
+    ADD CONSTRAINT realms_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: search_index_queue search_index_queue_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.search_index_queue
+    ADD CONSTRAINT search_index_queue_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: series series_opencast_id_key; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.series
+    ADD CONSTRAINT series_opencast_id_key UNIQUE (opencast_id);
+
+
+--
+-- Name: series series_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.series
+    ADD CONSTRAINT series_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: sync_backfill_progress sync_backfill_progress_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.sync_backfill_progress
+    ADD CONSTRAINT sync_backfill_progress_pkey PRIMARY KEY (job);
+
+
+--
+-- Name: sync_skipped_items sync_skipped_items_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.sync_skipped_items
+    ADD CONSTRAINT sync_skipped_items_pkey PRIMARY KEY (opencast_id);
+
+
+--
+-- Name: upload_jobs upload_jobs_opencast_id_key; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.upload_jobs
+    ADD CONSTRAINT upload_jobs_opencast_id_key UNIQUE (opencast_id);
+
+
+--
+-- Name: upload_jobs upload_jobs_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.upload_jobs
+    ADD CONSTRAINT upload_jobs_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: user_sessions user_sessions_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.user_sessions
+    ADD CONSTRAINT user_sessions_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: users users_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (username);
+
+
+--
+-- Name: idx_announcement_dismissals_session; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_announcement_dismissals_session ON public.announcement_dismissals USING btree (session);
+
+
+--
+-- Name: idx_block_realm; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_block_realm ON public.blocks USING btree (realm);
+
+
+--
+-- Name: idx_block_series; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_block_series ON public.blocks USING btree (series);
+
+
+--
+-- Name: idx_block_video; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_block_video ON public.blocks USING btree (video);
+
+
+--
+-- Name: idx_event_persons_person; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_event_persons_person ON public.event_persons USING btree (person);
+
+
+--
+-- Name: idx_events_metadata; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_events_metadata ON public.events USING gin (metadata jsonb_path_ops);
+
+
+--
+-- Name: idx_events_series_created; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_events_series_created ON public.events USING btree (series, created);
+
+
+--
+-- Name: idx_events_series_title; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_events_series_title ON public.events USING btree (series, title);
+
+
+--
+-- Name: idx_events_write_roles; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_events_write_roles ON public.events USING gin (write_roles);
+
+
+--
+-- Name: idx_playback_positions_updated; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_playback_positions_updated ON public.playback_positions USING btree (updated);
+
+
+--
+-- Name: idx_playback_positions_user_updated; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_playback_positions_user_updated ON public.playback_positions USING btree (username, updated DESC);
+
+
+--
+-- Name: idx_realm_parent; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_realm_parent ON public.realms USING btree (parent);
+
+
+--
+-- Name: idx_realm_path; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE UNIQUE INDEX idx_realm_path ON public.realms USING btree (full_path text_pattern_ops);
+
+
+--
+-- Name: idx_series_last_event_update; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_series_last_event_update ON public.series USING btree (last_event_update DESC NULLS LAST, id);
+
+
+--
+-- Name: idx_series_opencast_id; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_series_opencast_id ON public.series USING btree (opencast_id);
+
+
+--
+-- Name: idx_upload_jobs_uploader; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_upload_jobs_uploader ON public.upload_jobs USING btree (uploader);
+
+
+--
+-- Name: idx_user_sessions_created; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_user_sessions_created ON public.user_sessions USING btree (created DESC, seq DESC);
+
+
+--
+-- Name: idx_user_sessions_username_created; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_user_sessions_username_created ON public.user_sessions USING btree (username, created DESC, seq DESC);
+
+
+--
+-- Name: idx_users_display_name; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_users_display_name ON public.users USING btree (display_name, username);
+
+
+--
+-- Name: idx_users_last_login; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_users_last_login ON public.users USING btree (last_login, username);
+
+
+--
+-- Name: idx_users_roles; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX idx_users_roles ON public.users USING gin (roles);
+
+
+--
+-- Name: only_one_announcement; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE UNIQUE INDEX only_one_announcement ON public.announcements USING btree ((true));
+
+
+--
+-- Name: search_events _RETURN; Type: RULE; Schema: public; Owner: -
+--
+
+CREATE OR REPLACE VIEW public.search_events AS
+ SELECT events.id,
+    events.state,
+    events.series,
+    series.title AS series_title,
+    events.title,
+    events.description,
+    COALESCE(events.creator_names, events.creators) AS creators,
+    events.thumbnail,
+    events.duration,
+    events.is_live,
+    events.created,
+    events.start_time,
+    events.end_time,
+    events.read_roles,
+    events.write_roles,
+    COALESCE(array_agg(DISTINCT ROW(search_realms.id, search_realms.name, search_realms.full_path, search_realms.ancestor_names)::public.search_realms) FILTER (WHERE (search_realms.id IS NOT NULL)), '{}'::public.search_realms[]) AS host_realms,
+    events.license_spdx,
+    events.external_origin
+   FROM (((public.events
+     LEFT JOIN public.series ON ((events.series = series.id)))
+     LEFT JOIN public.blocks ON ((((blocks.type = 'series'::public.block_type) AND (blocks.series = events.series) AND ((blocks.metadata_filter IS NULL) OR (events.metadata @> blocks.metadata_filter))) OR ((blocks.type = 'video'::public.block_type) AND (blocks.video = events.id)))))
+     LEFT JOIN public.search_realms ON ((search_realms.id = blocks.realm)))
+  GROUP BY events.id, series.id;
+
+
+--
+-- Name: events check_metadata_format_on_insert; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER check_metadata_format_on_insert BEFORE INSERT OR UPDATE ON public.events FOR EACH ROW EXECUTE FUNCTION public.check_metadata_format();
+
+
+--
+-- Name: blocks check_name_source_block_on_block_update; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER check_name_source_block_on_block_update BEFORE UPDATE ON public.blocks FOR EACH ROW WHEN (((new.realm IS DISTINCT FROM old.realm) OR (new.type IS DISTINCT FROM old.type))) EXECUTE FUNCTION public.check_block_as_name_source();
+
+
+--
+-- Name: realms check_name_source_block_on_realm_insert; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER check_name_source_block_on_realm_insert BEFORE INSERT ON public.realms FOR EACH ROW WHEN ((new.name_from_block IS NOT NULL)) EXECUTE FUNCTION public.check_name_source_block_on_realm_change();
+
+
+--
+-- Name: realms check_name_source_block_on_realm_update; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER check_name_source_block_on_realm_update BEFORE UPDATE ON public.realms FOR EACH ROW WHEN (((new.name_from_block IS NOT NULL) AND (new.name_from_block IS DISTINCT FROM old.name_from_block))) EXECUTE FUNCTION public.check_name_source_block_on_realm_change();
+
+
+--
+-- Name: realms fix_full_path_after_update; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER fix_full_path_after_update AFTER UPDATE ON public.realms FOR EACH ROW EXECUTE FUNCTION public.update_full_realm_path();
+
+
+--
+-- Name: realms fix_full_path_before_update; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER fix_full_path_before_update BEFORE UPDATE ON public.realms FOR EACH ROW EXECUTE FUNCTION public.update_full_realm_path();
+
+
+--
+-- Name: realms prevent_root_deletion; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER prevent_root_deletion BEFORE DELETE ON public.realms FOR EACH ROW WHEN ((old.id = 0)) EXECUTE FUNCTION public.illegal_root_modification();
+
+
+--
+-- Name: realms prevent_root_id_change; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER prevent_root_id_change BEFORE UPDATE ON public.realms FOR EACH ROW WHEN (((old.id = 0) AND (new.id <> 0))) EXECUTE FUNCTION public.illegal_root_modification();
+
+
+--
+-- Name: series queue_all_events_of_touched_series_for_reindex; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_all_events_of_touched_series_for_reindex AFTER INSERT OR DELETE OR UPDATE ON public.series FOR EACH ROW EXECUTE FUNCTION public.queue_all_events_of_touched_series_for_reindex();
+
+
+--
+-- Name: blocks queue_blocks_for_reindex; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_blocks_for_reindex AFTER INSERT OR DELETE OR UPDATE OF video, series, metadata_filter ON public.blocks FOR EACH ROW EXECUTE FUNCTION public.queue_blocks_for_reindex();
+
+
+--
+-- Name: events queue_realm_on_updated_event_title; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_realm_on_updated_event_title AFTER UPDATE OF title ON public.events FOR EACH ROW EXECUTE FUNCTION public.queue_realm_on_updated_title();
+
+
+--
+-- Name: series queue_realm_on_updated_series_title; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_realm_on_updated_series_title AFTER UPDATE OF title ON public.series FOR EACH ROW EXECUTE FUNCTION public.queue_realm_on_updated_title();
+
+
+--
+-- Name: events queue_touched_event_for_reindex; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_touched_event_for_reindex AFTER INSERT OR DELETE OR UPDATE ON public.events FOR EACH ROW EXECUTE FUNCTION public.queue_touched_event_for_reindex();
+
+
+--
+-- Name: realms queue_touched_realm_for_reindex; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER queue_touched_realm_for_reindex AFTER INSERT OR DELETE OR UPDATE OF id, parent, full_path, name, name_from_block ON public.realms FOR EACH ROW EXECUTE FUNCTION public.queue_touched_realm_for_reindex();
+
+
+--
+-- Name: events remember_deleted_opencast_events; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER remember_deleted_opencast_events AFTER DELETE ON public.events FOR EACH ROW EXECUTE FUNCTION public.remember_deleted_opencast_items('event');
+
+
+--
+-- Name: series remember_deleted_opencast_series; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER remember_deleted_opencast_series AFTER DELETE ON public.series FOR EACH ROW EXECUTE FUNCTION public.remember_deleted_opencast_items('series');
+
+
+--
+-- Name: events reuse_existing_id_on_event_insert; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER reuse_existing_id_on_event_insert BEFORE INSERT ON public.events FOR EACH ROW EXECUTE FUNCTION public.reuse_existing_id_on_insert('event');
+
+
+--
+-- Name: series reuse_existing_id_on_series_insert; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER reuse_existing_id_on_series_insert BEFORE INSERT ON public.series FOR EACH ROW EXECUTE FUNCTION public.reuse_existing_id_on_insert('series');
+
+
+--
+-- Name: realms set_full_path_on_insert; Type: TRIGGER; Schema: public; Owner: -
+--
+
+CREATE TRIGGER set_full_path_on_insert BEFORE INSERT ON public.realms FOR EACH ROW EXECUTE FUNCTION public.set_full_realm_path();
+
+
+--
+-- Name: announcement_dismissals announcement_dismissals_announcement_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.announcement_dismissals
+    ADD CONSTRAINT announcement_dismissals_announcement_fkey FOREIGN KEY (announcement) REFERENCES public.announcements(id) ON DELETE CASCADE;
+
+
+--
+-- Name: announcement_dismissals announcement_dismissals_session_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.announcement_dismissals
+    ADD CONSTRAINT announcement_dismissals_session_fkey FOREIGN KEY (session) REFERENCES public.user_sessions(id) ON DELETE CASCADE;
+
+
+--
+-- Name: blocks blocks_realm_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_realm_fkey FOREIGN KEY (realm) REFERENCES public.realms(id) ON DELETE CASCADE;
+
+
+--
+-- Name: blocks blocks_series_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_series_fkey FOREIGN KEY (series) REFERENCES public.series(id) ON DELETE SET NULL;
+
+
+--
+-- Name: blocks blocks_video_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_video_fkey FOREIGN KEY (video) REFERENCES public.events(id) ON DELETE SET NULL;
+
+
+--
+-- Name: event_persons event_persons_event_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.event_persons
+    ADD CONSTRAINT event_persons_event_fkey FOREIGN KEY (event) REFERENCES public.events(id) ON DELETE CASCADE;
+
+
+--
+-- Name: event_persons event_persons_person_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.event_persons
+    ADD CONSTRAINT event_persons_person_fkey FOREIGN KEY (person) REFERENCES public.persons(id) ON DELETE CASCADE;
+
+
+--
+-- Name: events events_series_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.events
+    ADD CONSTRAINT events_series_fkey FOREIGN KEY (series) REFERENCES public.series(id) ON DELETE SET NULL;
+
+
+--
+-- Name: person_names person_names_person_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.person_names
+    ADD CONSTRAINT person_names_person_fkey FOREIGN KEY (person) REFERENCES public.persons(id) ON DELETE CASCADE;
+
+
+--
+-- Name: playback_positions playback_positions_event_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.playback_positions
+    ADD CONSTRAINT playback_positions_event_fkey FOREIGN KEY (event) REFERENCES public.events(id) ON DELETE CASCADE;
+
+
+--
+-- Name: realms realms_name_from_block_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.realms
+    ADD CONSTRAINT realms_name_from_block_fkey FOREIGN KEY (name_from_block) REFERENCES public.blocks(id) ON DELETE RESTRICT;
+
+
+--
+-- Name: realms realms_parent_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.realms
+    ADD CONSTRAINT realms_parent_fkey FOREIGN KEY (parent) REFERENCES public.realms(id) ON DELETE CASCADE;
+
+
+--
+-- PostgreSQL database dump complete
+--
+
+
+
+-- New keys are generated for each database.
+INSERT INTO public.__xtea_keys (entity) VALUES ('block'), ('event'), ('person'), ('realm'), ('series');
+
+-- The meta table is created before the snapshot is applied.
+comment on table public.__db_migrations is 'Migrations that have been applied to this database';
+comment on column public.__db_migrations.id is 'ID of the migration';
+comment on column public.__db_migrations.name is 'Name of the migration';
+comment on column public.__db_migrations.applied_on is 'When the migration was applied (UTC)';
+comment on column public.__db_migrations.script is 'The SQL script of the migration';
@@ -0,0 +1,78 @@
+//! The schema snapshot `schema.sql`: the schema (and data) that results from
+//! applying all migrations to an empty database. Applying it in one go is a
+//! lot faster than running all migrations one by one, so it is used for
+//! empty databases. Existing databases are always migrated incrementally.
+//!
+//! The snapshot is generated by `db generate-schema-snapshot`. Its header
+//! contains the hash chain over all migrations it was generated from, so an
+//! outdated snapshot can be detected (and is ignored).
+
+use super::migrations::{MIGRATIONS, chain_hash};
+
+
+/// The snapshot compiled into this binary.
+const SNAPSHOT: &str = include_str!("schema.sql");
+
+/// Prefix of the header line containing the hash chain of the migrations.
+const CHAIN_PREFIX: &str = "-- migrations-chain: ";
+
+/// Prefix of the header line containing the number of migrations.
+const COUNT_PREFIX: &str = "-- migrations: ";
+
+
+/// Returns the header for a snapshot of the migrations of this binary.
+pub(super) fn header() -> String {
+    format!(
+        "-- Snapshot of the database after applying all migrations. Empty databases\n\
+            -- are set up by applying this instead of running all migrations.\n\
+            --\n\
+            -- GENERATED by `tobira db generate-schema-snapshot`: do not edit manually!\n\
+            --\n\
+            {COUNT_PREFIX}{}\n\
+            {CHAIN_PREFIX}{}\n",
+        MIGRATIONS.len(),
+        chain_hash(),
+    )
+}
+
+/// Returns the snapshot compiled into this binary if it was generated from
+/// exactly the migrations of this binary.
+pub(super) fn current() -> Option<&'static str> {
+    (chain_of(SNAPSHOT) == Some(chain_hash().as_str())).then_some(SNAPSHOT)
+}
+
+/// Returns the number of migrations the snapshot compiled into this binary
+/// was generated from, if it says so in the header.
+pub(super) fn migration_count() -> Option<u64> {
+    SNAPSHOT.lines()
+        .take_while(|line| line.starts_with("--"))
+        .find_map(|line| line.strip_prefix(COUNT_PREFIX)?.trim().parse().ok())
+}
+
+fn chain_of(snapshot: &str) -> Option<&str> {
+    snapshot.lines()
+        .take_while(|line| line.starts_with("--"))
+        .find_map(|line| line.strip_prefix(CHAIN_PREFIX))
+        .map(str::trim)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{chain_of, current, header};
+
+    #[test]
+    fn parses_header() {
+        let snapshot = format!("{}\nSET LOCAL lock_timeout = 0;\n", header());
+        assert_eq!(chain_of(&snapshot), Some(super::chain_hash().as_str()));
+        assert_eq!(chain_of("SET LOCAL lock_timeout = 0;\n-- migrations-chain: abc\n"), None);
+    }
+
+    #[test]
+    fn snapshot_is_up_to_date() {
+        assert!(
+            current().is_some(),
+            "`schema.sql` is outdated: run `tobira db generate-schema-snapshot`",
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! The denormalized `event_count` and `last_event_update` columns of
+//! `series` (see `26-series-event-stats.sql`).
+
+use tokio_postgres::{Error as PgError, GenericClient};
+
+
+/// Recomputes the statistics of the given series, or of all series if `None`
+/// is passed. Only rows whose values actually change are written. Returns the
+/// number of series that were fixed.
+pub(crate) async fn update(
+    db: &impl GenericClient,
+    series: Option<&[i64]>,
+) -> Result<u64, PgError> {
+    let sql = "update series set \
+            event_count = stats.count, \
+            last_event_update = stats.last_update \
+        from ( \
+            select \
+                series.id, \
+                count(events.id)::int as count, \
+                max(events.updated) filter (where events.updated <> '-infinity') \
+                    as last_update \
+            from series \
+            left join events on events.series = series.id \
+            where $1::bigint[] is null or series.id = any($1) \
+            group by series.id \
+        ) as stats \
+        where series.id = stats.id \
+            and (series.event_count, series.last_event_update) \
+                is distinct from (stats.count, stats.last_update)";
+    db.execute(sql, &[&series]).await
+}
@@ -91,6 +91,33 @@ pub enum SeriesState {
 }
 
 
+/// Represents the `upload_state` type defined in `17-upload-jobs.sql`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSql, ToSql, GraphQLEnum)]
+#[postgres(name = "upload_state")]
+#[graphql(description = "Processing state of a video uploaded via Tobira")]
+pub enum UploadState {
+    #[postgres(name = "processing")]
+    Processing,
+    #[postgres(name = "failed")]
+    Failed,
+    #[postgres(name = "ready")]
+    Ready,
+}
+
+/// Represents the `announcement_severity` type defined in `20-announcements.sql`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromSql, ToSql, GraphQLEnum)]
+#[postgres(name = "announcement_severity")]
+#[graphql(description = "How important an announcement is")]
+pub enum AnnouncementSeverity {
+    #[postgres(name = "info")]
+    Info,
+    #[postgres(name = "warning")]
+    Warning,
+    #[postgres(name = "critical")]
+    Critical,
+}
+
+
 /// Represents extra metadata in the DB. Is a map from "namespace" to a
 /// `string -> string array` map.
 ///
@@ -0,0 +1,454 @@
+//! The optional access log, containing one line per HTTP request.
+//!
+//! Lines are formatted in the request handler, but written by a separate
+//! thread, so that a slow disk never delays responses. If that thread cannot
+//! keep up, lines are dropped instead.
+
+use chrono::{DateTime, Local};
+use hyper::{Body, HeaderMap, http::HeaderValue};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    net::IpAddr,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{logger::{LogConfig, LogFile}, prelude::*};
+use super::{Request, Response};
+
+
+/// Number of lines that can be queued before lines are dropped.
+const QUEUE_CAPACITY: usize = 16_384;
+
+/// The writer thread collects queued lines into a single write up to roughly
+/// this size.
+const MAX_BATCH_SIZE: usize = 64 * 1024;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+
+/// Where the access log is written to.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum AccessLogTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl TryFrom<String> for AccessLogTarget {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "" => Err("access log target must not be empty".into()),
+            "stdout" => Ok(Self::Stdout),
+            _ => Ok(Self::File(value.into())),
+        }
+    }
+}
+
+/// A parsed access log format string, see `log.access_log_format`.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct AccessLogFormat(Vec<Part>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    RemoteAddr,
+    Time,
+    Method,
+    Path,
+    Route,
+    Status,
+    Bytes,
+    Duration,
+    User,
+    RequestId,
+}
+
+impl TryFrom<String> for AccessLogFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+
+            let field = match chars.next() {
+                Some('%') => {
+                    literal.push('%');
+                    continue;
+                }
+                Some('a') => Field::RemoteAddr,
+                Some('t') => Field::Time,
+                Some('m') => Field::Method,
+                Some('U') => Field::Path,
+                Some('R') => Field::Route,
+                Some('s') => Field::Status,
+                Some('b') => Field::Bytes,
+                Some('D') => Field::Duration,
+                Some('u') => Field::User,
+                Some('L') => Field::RequestId,
+                Some(other) => return Err(format!("unknown access log field '%{other}'")),
+                None => return Err("access log format must not end with a single '%'".into()),
+            };
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Field(field));
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self(parts))
+    }
+}
+
+
+/// The address of the peer of a TCP connection, attached to requests as
+/// extension. Not present for requests received via Unix socket.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PeerAddr(pub(crate) IpAddr);
+
+/// Information only known to specific handlers, attached to responses as
+/// extension.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Annotations {
+    pub(crate) graphql_operation: Option<String>,
+    pub(crate) username: Option<String>,
+}
+
+impl Annotations {
+    pub(crate) fn attach(self, mut response: Response) -> Response {
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Everything about a request that is known before handling it.
+#[derive(Debug)]
+pub(crate) struct PendingEntry {
+    start: Instant,
+    time: DateTime<Local>,
+    remote_addr: Option<String>,
+    method: String,
+    path: String,
+    request_id: String,
+}
+
+/// Everything needed to format one line.
+#[derive(Debug)]
+struct Entry {
+    request: PendingEntry,
+    status: u16,
+    bytes: Option<u64>,
+    duration: Duration,
+    user: Option<String>,
+    graphql_operation: Option<String>,
+}
+
+
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+    hash_users: bool,
+    sender: SyncSender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl AccessLog {
+    /// Opens the access log and starts the writer thread, if an access log is
+    /// configured. Must be called inside the tokio runtime.
+    pub(crate) fn new(config: &LogConfig) -> Result<Option<Self>> {
+        let Some(target) = &config.access_log else {
+            return Ok(None);
+        };
+
+        let file = match target {
+            AccessLogTarget::Stdout => None,
+            AccessLogTarget::File(path) => Some(LogFile::open(path)?),
+        };
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_clone = dropped.clone();
+        std::thread::Builder::new()
+            .name("access-log-writer".into())
+            .spawn(move || {
+                let dropped = &dropped_clone;
+                match file {
+                    None => write_lines(receiver, dropped, |b| io::stdout().lock().write_all(b)),
+                    Some(file) => write_lines(receiver, dropped, |b| file.lock().write_all(b)),
+                }
+            })
+            .context("failed to start access log thread")?;
+
+        Ok(Some(Self {
+            format: config.access_log_format.clone(),
+            hash_users: config.access_log_hash_users,
+            sender,
+            dropped,
+        }))
+    }
+
+    /// Collects information about the request before it is handled.
+    pub(crate) fn begin(&self, req: &Request<Body>) -> PendingEntry {
+        let peer = req.extensions().get::<PeerAddr>().map(|p| p.0);
+        PendingEntry {
+            start: Instant::now(),
+            time: Local::now(),
+            remote_addr: remote_addr(req.headers(), peer),
+            method: req.method().to_string(),
+            path: req.uri().path().to_owned(),
+            request_id: req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .filter(|id| is_valid_request_id(id))
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>())),
+        }
+    }
+
+    /// Queues the line for this request and adds the request ID to the
+    /// response.
+    pub(crate) fn finish(&self, request: PendingEntry, response: &mut Response) {
+        let duration = request.start.elapsed();
+        let id = HeaderValue::from_str(&request.request_id)
+            .expect("request ID contains invalid characters");
+        response.headers_mut().insert(REQUEST_ID_HEADER, id);
+
+        let bytes = hyper::body::HttpBody::size_hint(response.body()).exact().or_else(|| {
+            response.headers()
+                .get(hyper::header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse().ok())
+        });
+        let annotations = response.extensions_mut().remove::<Annotations>().unwrap_or_default();
+        let user = annotations.username.map(|name| match self.hash_users {
+            true => hash_username(&name),
+            false => name,
+        });
+
+        let entry = Entry {
+            request,
+            status: response.status().as_u16(),
+            bytes,
+            duration,
+            user,
+            graphql_operation: annotations.graphql_operation,
+        };
+        match self.sender.try_send(format_line(&self.format, &entry)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            // Only happens if the writer thread panicked, nothing we can do.
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// Runs in the writer thread until the sender is dropped.
+fn write_lines(
+    receiver: Receiver<String>,
+    dropped: &AtomicU64,
+    mut write: impl FnMut(&[u8]) -> io::Result<()>,
+) {
+    let mut buf = String::new();
+    while let Ok(line) = receiver.recv() {
+        // Write everything that is queued at once, but not more than that,
+        // so that lines show up immediately if there is little traffic.
+        buf.clear();
+        buf.push_str(&line);
+        while buf.len() < MAX_BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(line) => buf.push_str(&line),
+                Err(_) => break,
+            }
+        }
+
+        if let Err(e) = write(buf.as_bytes()) {
+            error!("Failed to write to access log: {e}");
+        }
+
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Access log could not keep up: dropped {dropped} lines");
+        }
+    }
+}
+
+fn format_line(format: &AccessLogFormat, entry: &Entry) -> String {
+    let mut out = String::new();
+    for part in &format.0 {
+        let field = match part {
+            Part::Literal(s) => {
+                out.push_str(s);
+                continue;
+            }
+            Part::Field(field) => field,
+        };
+
+        // Writing to a `String` cannot fail.
+        let request = &entry.request;
+        let _ = match field {
+            Field::RemoteAddr => write_escaped(&mut out, request.remote_addr.as_deref()),
+            Field::Time => write!(out, "[{}]", request.time.format("%d/%b/%Y:%H:%M:%S %z")),
+            Field::Method => write_escaped(&mut out, Some(&request.method)),
+            Field::Path => write_escaped(&mut out, Some(&request.path)),
+            Field::Route => match &entry.graphql_operation {
+                Some(operation) => {
+                    out.push_str("graphql:");
+                    write_escaped(&mut out, Some(operation))
+                }
+                None => write_escaped(&mut out, Some(&request.path)),
+            },
+            Field::Status => write!(out, "{}", entry.status),
+            Field::Bytes => match entry.bytes {
+                Some(bytes) => write!(out, "{bytes}"),
+                None => write!(out, "-"),
+            },
+            Field::Duration => write!(out, "{}", entry.duration.as_micros()),
+            Field::User => write_escaped(&mut out, entry.user.as_deref()),
+            Field::RequestId => write_escaped(&mut out, Some(&request.request_id)),
+        };
+    }
+    out.push('\n');
+    out
+}
+
+/// Writes `s` such that it cannot break the line format: quotes, backslashes
+/// and control characters are escaped. Missing or empty values are written
+/// as `-`.
+fn write_escaped(out: &mut String, s: Option<&str>) -> std::fmt::Result {
+    let s = match s {
+        Some(s) if !s.is_empty() => s,
+        _ => return write!(out, "-"),
+    };
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\x{:02x}", c as u32)?,
+            c => out.push(c),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the address of the client. Behind a reverse proxy, the peer is the
+/// proxy, which appends the actual client address to `X-Forwarded-For`. Only
+/// the last entry is used, as all others are sent by the client and thus
+/// cannot be trusted.
+fn remote_addr(headers: &HeaderMap, peer: Option<IpAddr>) -> Option<String> {
+    let forwarded = headers.get_all("x-forwarded-for")
+        .iter()
+        .next_back()
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty());
+
+    match forwarded {
+        Some(addr) => Some(addr.to_owned()),
+        None => peer.map(|addr| addr.to_string()),
+    }
+}
+
+/// Request IDs from clients are only used if they cannot mess up the log.
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+}
+
+fn hash_username(username: &str) -> String {
+    let hash = ring::digest::digest(&ring::digest::SHA256, username.as_bytes());
+    hex::encode(&hash.as_ref()[..8])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use hyper::HeaderMap;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn parse(format: &str) -> Result<AccessLogFormat, String> {
+        AccessLogFormat::try_from(format.to_owned())
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!(parse("%a 100%% %D").unwrap(), AccessLogFormat(vec![
+            Part::Field(Field::RemoteAddr),
+            Part::Literal(" 100% ".into()),
+            Part::Field(Field::Duration),
+        ]));
+        assert!(parse("%a %x").is_err());
+        assert!(parse("%a %").is_err());
+    }
+
+    #[test]
+    fn format_entry() {
+        let entry = Entry {
+            request: PendingEntry {
+                start: Instant::now(),
+                time: Local.ymd(2026, 10, 15).and_hms(14, 3, 12),
+                remote_addr: Some("10.0.0.1".into()),
+                method: "POST".into(),
+                path: "/graphql".into(),
+                request_id: "abc".into(),
+            },
+            status: 200,
+            bytes: None,
+            duration: Duration::from_micros(1234),
+            user: Some("peter \"admin\"".into()),
+            graphql_operation: Some("VideoPageInRealmQuery".into()),
+        };
+        let format = parse("%a %u \"%m %R\" %U %s %b %D %L").unwrap();
+        assert_eq!(
+            format_line(&format, &entry),
+            "10.0.0.1 peter \\\"admin\\\" \"POST graphql:VideoPageInRealmQuery\" /graphql \
+                200 - 1234 abc\n",
+        );
+
+        let time = format_line(&parse("%t").unwrap(), &entry);
+        assert!(time.starts_with("[15/Oct/2026:14:03:12 "), "{time}");
+    }
+
+    #[test]
+    fn remote_addr_behind_proxy() {
+        let peer = Some([127, 0, 0, 1].into());
+        let mut headers = HeaderMap::new();
+        assert_eq!(remote_addr(&headers, peer).as_deref(), Some("127.0.0.1"));
+        assert_eq!(remote_addr(&headers, None), None);
+
+        headers.insert("x-forwarded-for", "1.2.3.4, 10.0.0.1".parse().unwrap());
+        assert_eq!(remote_addr(&headers, peer).as_deref(), Some("10.0.0.1"));
+    }
+
+    #[test]
+    fn request_ids() {
+        assert!(is_valid_request_id("f81d4fae-7dec-11d0-a765-00a0c91e6bf6"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("a b"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 
 use bstr::ByteSlice;
-use hyper::{Body, StatusCode};
+use hyper::{Body, HeaderMap, StatusCode};
 use reinda::{assets, Setup};
 use secrecy::ExposeSecret;
 use serde_json::json;
 
 use crate::{config::Config, prelude::*};
-use super::{Response, handlers::CommonHeadersExt};
+use super::{Response, handlers::CommonHeadersExt, noscript::escape, range::RangeResponse};
 
 
 const ASSETS: Setup = assets! {
@@ -83,21 +83,27 @@ impl Assets {
         variables.insert("auth".into(), json!({
             "loginLink": config.auth.login_link,
             "logoutLink": config.auth.logout_link,
+            "postLogoutLink": config.auth.post_logout_link,
             "userIdLabel": config.auth.login_page.user_id_label,
             "passwordLabel": config.auth.login_page.password_label,
             "loginPageNote": config.auth.login_page.note,
             "preAuthExternalLinks": config.auth.pre_auth_external_links,
         }).to_string());
 
-        // Note the mismatch between presentation and sync node;
-        // these might not be the same forever!
-        variables.insert("presentation-node".into(), config.opencast.sync_node().to_string());
+        variables.insert(
+            "presentation-node".into(),
+            config.opencast.presentation_node().to_string(),
+        );
         variables.insert("upload-node".into(), config.opencast.upload_node().to_string());
         variables.insert("studio-url".into(), config.opencast.studio_url().to_string());
         variables.insert("editor-url".into(), config.opencast.editor_url().to_string());
 
-        variables.insert("html-title".into(), config.general.site_title.en().into());
         variables.insert("site-title".into(), config.general.site_title.to_json());
+        variables.insert("languages".into(), json!(config.general.languages).to_string());
+        variables.insert(
+            "continue-watching".into(),
+            json!(config.general.continue_watching).to_string(),
+        );
         variables.insert("footer-links".into(), json!(config.general.footer_links).to_string());
         variables.insert("metadata-labels".into(), json!(config.general.metadata).to_string());
         variables.insert(
@@ -128,7 +134,10 @@ impl Assets {
 
     /// Responds with the asset identified by the given path. If there exists no
     /// asset with `path` or `path` is `INDEX_FILE`, `None` is returned.
-    pub(crate) async fn serve(&self, path: &str) -> Option<Response> {
+    ///
+    /// Single byte ranges requested via `Range` are respected. The asset data
+    /// is reference counted, so neither full nor partial responses copy it.
+    pub(crate) async fn serve(&self, path: &str, req_headers: &HeaderMap) -> Option<Response> {
         // The `index.html` here is not intended to be served directly. It is
         // modified and sent on many other routes.
         if path == INDEX_FILE {
@@ -139,9 +148,19 @@ impl Assets {
             panic!("failed to read asset '{}': {}", path, e);
         })?;
 
+        // Assets with hashed filenames never change, so the path is a fine
+        // strong entity tag. Other assets (e.g. the logo) do not get one.
+        // We can unwrap the `lookup` call as we know from above that the path
+        // is valid.
+        let is_hashed = self.assets.asset_info(self.assets.lookup(path).unwrap())
+            .is_filename_hashed();
+        let etag = is_hashed.then(|| format!("\"{path}\""));
+
         // Prepare HTTP headers
-        let mut builder = Response::builder();
-        builder = builder.header("content-length", data.len());
+        let mut builder = Response::builder().header("accept-ranges", "bytes");
+        if let Some(etag) = &etag {
+            builder = builder.header("etag", etag);
+        }
 
         // Mime type
         let mime_guess = mime_guess::from_path(path).first();
@@ -149,21 +168,62 @@ impl Assets {
             builder = builder.header("content-type", mime.to_string())
         }
 
-        // Caching header if the filename contains a content hash. We can unwrap
-        // the `lookup` call as we know from above that the path is valid.
-        if self.assets.asset_info(self.assets.lookup(path).unwrap()).is_filename_hashed() {
+        // Caching header if the filename contains a content hash.
+        if is_hashed {
             // This is one year in seconds.
             builder = builder.header("cache-control", "public, max-age=31536000, immutable");
         }
 
-        let body = Body::from(data);
-        Some(builder.body(body).expect("bug: invalid response"))
+        let len = data.len();
+        let body = match RangeResponse::evaluate(req_headers, etag.as_deref(), len) {
+            RangeResponse::Full => data,
+            RangeResponse::Partial(range) => {
+                builder = builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header("content-range", format!(
+                        "bytes {}-{}/{len}",
+                        range.start,
+                        range.end - 1,
+                    ));
+                data.slice(range)
+            }
+            RangeResponse::NotSatisfiable => {
+                builder = builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header("content-range", format!("bytes */{len}"));
+                Default::default()
+            }
+        };
+
+        builder = builder.header("content-length", body.len());
+        Some(builder.body(Body::from(body)).expect("bug: invalid response"))
     }
 
     /// Serves the main entry point of the application. This is replied to `/`
     /// and other "public routes", like `/lectures`. Basically everywhere where
-    /// the user is supposed to see the website.
-    pub(crate) async fn serve_index(&self, status: StatusCode, config: &Config) -> Response {
+    /// the user is supposed to see the website. `lang` is the negotiated
+    /// language, used for the title and the `lang` attribute.
+    pub(crate) async fn serve_index(
+        &self,
+        status: StatusCode,
+        config: &Config,
+        lang: &str,
+    ) -> Response {
+        self.serve_index_with_noscript(status, config, lang, None, None).await
+    }
+
+    /// Like `serve_index`, but additionally puts the given HTML into a
+    /// `<noscript>` block for users without JavaScript. `initial_route` is
+    /// passed to the frontend as the page to show instead of `/`, see
+    /// `general.initial_page`.
+    pub(crate) async fn serve_index_with_noscript(
+        &self,
+        status: StatusCode,
+        config: &Config,
+        lang: &str,
+        noscript: Option<&str>,
+        initial_route: Option<&str>,
+    ) -> Response {
         let bytes = self.assets.get(INDEX_FILE).await
             .expect("failed to read 'index.html'")
             .expect("`index.html` missing in internal assets");
@@ -171,7 +231,13 @@ impl Assets {
         // Generate nonce and put it into the HTML response.
         let nonce_bytes = crate::util::gen_random_bytes_crypto::<16>();
         let nonce = hex::encode(&nonce_bytes.expose_secret());
-        let body = bytes.replace("{{ nonce }}", &nonce);
+        let noscript = noscript.map_or(String::new(), |html| format!("<noscript>{html}</noscript>"));
+        let body = bytes
+            .replace("{{ nonce }}", &nonce)
+            .replace("{{ lang }}", lang)
+            .replace("{{ html-title }}", escape(config.general.site_title.get(lang)))
+            .replace("{{ noscript }}", &noscript)
+            .replace("{{ initial-route }}", json!(initial_route).to_string());
 
         // Build response
         Response::builder()
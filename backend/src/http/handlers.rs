@@ -9,13 +9,13 @@ use std::{
 
 use crate::{
     api,
-    auth::{self, AuthContext},
+    auth::{self, AuthContext, AuthMode, SessionId},
     Config,
     db::{self, Transaction},
     metrics::HttpReqCategory,
     prelude::*,
 };
-use super::{Context, Request, Response, response};
+use super::{Context, Request, Response, access_log::Annotations, response};
 
 
 /// This is the main HTTP entry point, called for each incoming request.
@@ -34,6 +34,7 @@ pub(super) async fn handle(req: Request<Body>, ctx: Arc<Context>) -> Response {
         }
         trace!("HTTP Headers: {}", out);
     }
+    let access_log_entry = ctx.access_log.as_ref().map(|log| log.begin(&req));
 
     let method = req.method().clone();
     let path = req.uri().path().trim_end_matches('/');
@@ -87,7 +88,7 @@ pub(super) async fn handle(req: Request<Body>, ctx: Arc<Context>) -> Response {
 
         "/~metrics" => {
             register_req!(HttpReqCategory::Metrics);
-            let out = ctx.metrics.gather_and_encode(&ctx.db_pool).await;
+            let out = ctx.metrics.gather_and_encode(&ctx.db_pool, &ctx.navigation_cache).await;
             Response::builder()
                 .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
                 .body(out.into())
@@ -98,12 +99,27 @@ pub(super) async fn handle(req: Request<Body>, ctx: Arc<Context>) -> Response {
         path if path.starts_with(ASSET_PREFIX) => {
             register_req!(HttpReqCategory::Assets);
             let asset_path = &path[ASSET_PREFIX.len()..];
-            match ctx.assets.serve(asset_path).await {
-                Some(r) => r,
-                None => reply_404(&ctx, &method, path).await,
+            match ctx.assets.serve(asset_path, req.headers()).await {
+                Some(r) => {
+                    ctx.metrics.register_asset_bytes(r.headers()
+                        .get(header::CONTENT_LENGTH)
+                        .and_then(|v| v.to_str().ok()?.parse().ok())
+                        .unwrap_or(0));
+                    r
+                }
+                None => {
+                    let lang = super::lang::resolve(req.headers(), None, &ctx).await;
+                    reply_404(&ctx, &method, path, &lang).await
+                }
             }
         }
 
+        // Tracks on protected Opencast channels
+        path if path.starts_with(super::proxy::PREFIX) => {
+            register_req!(HttpReqCategory::TrackProxy);
+            super::proxy::handle(req, &ctx).await
+        }
+
         // Some browser automatically request this in certain situations. As we
         // serve our favicon differently, it's best to reply 404 here
         // (and without our frontend!).
@@ -124,6 +140,24 @@ pub(super) async fn handle(req: Request<Body>, ctx: Arc<Context>) -> Response {
                 .unwrap()
         }
 
+        // With an external login, our own login page is not used. But links to
+        // it carry the `return_to` parameter, so we handle them here.
+        "/~login" if ctx.config.auth.login_link.is_some() => {
+            register_req!(HttpReqCategory::Other);
+            let login_link = ctx.config.auth.login_link.as_deref().unwrap();
+            auth::handle_login_link(&req, login_link)
+        }
+
+        // Allows browsers to register Tobira as search engine.
+        super::opensearch::DESCRIPTION_PATH => {
+            register_req!(HttpReqCategory::Other);
+            super::opensearch::description(req.headers(), &ctx).await
+        }
+        super::opensearch::SUGGESTIONS_PATH => {
+            register_req!(HttpReqCategory::Other);
+            super::opensearch::suggestions(req.uri().query(), req.headers(), &ctx).await
+        }
+
         // The interactive GraphQL API explorer/IDE. We actually keep this in
         // production as it does not hurt and in particular: does not expose any
         // information that isn't already exposed by the API itself.
@@ -153,20 +187,103 @@ pub(super) async fn handle(req: Request<Body>, ctx: Arc<Context>) -> Response {
             let noindex = path.starts_with("/!")
                 || (path.starts_with("/~") && !path.starts_with("/~about"));
 
+            let lang = super::lang::resolve(req.headers(), req.uri().query(), &ctx).await;
+            let noscript = super::noscript::for_path(path, &lang, &ctx).await;
+
+            // `path` has its trailing slash removed, so this is `/`.
+            let initial_route = if path.is_empty() {
+                Some(initial_route(&req, &ctx).await)
+            } else {
+                None
+            };
             ctx.assets
-                .serve_index(StatusCode::OK, &ctx.config)
+                .serve_index_with_noscript(
+                    StatusCode::OK,
+                    &ctx.config,
+                    &lang,
+                    noscript.as_deref(),
+                    initial_route.as_deref(),
+                )
                 .await
                 .make_noindex(noindex)
         }
     };
-    
+    let mut response = finalize_response(&method, category, response);
+
     let response_time = time_incoming.elapsed();
     ctx.metrics.observe_response_time(category, response_time);
+    if let (Some(log), Some(entry)) = (&ctx.access_log, access_log_entry) {
+        log.finish(entry, &mut response);
+    }
+    response
+}
+
+/// Resolves `general.initial_page` for the user of this request, see
+/// `Query::initial_route` in the API, which has to return the same.
+async fn initial_route(req: &Request<Body>, ctx: &Context) -> String {
+    let pages = &ctx.config.general.initial_page;
+
+    // Only if the pages differ we need to know whether the user is logged in,
+    // which requires a DB query.
+    if pages.anonymous == pages.authenticated {
+        return pages.anonymous.route();
+    }
+
+    let logged_in = match ctx.db_pool.get().await {
+        Ok(db) => match AuthContext::new(req.headers(), &ctx.config.auth, &db).await {
+            Ok(auth) => matches!(auth, AuthContext::User(_)),
+            Err(e) => {
+                warn!("DB error when checking user session for initial page: {e}");
+                false
+            }
+        },
+        Err(e) => {
+            warn!("Could not get DB connection to determine initial page: {e}");
+            false
+        }
+    };
+    pages.for_user(logged_in).route()
+}
+
+/// Adjusts headers and body that depend on the kind of request rather than on
+/// the individual route, so that new routes get them right automatically.
+///
+/// - Everything except assets and proxied tracks depends on the session or
+///   on the DB, so it must not be cached (by browsers or proxies). Assets set
+///   their own caching headers and the proxy forwards Opencast's.
+/// - Responses to `HEAD` requests have the same headers as the `GET`
+///   response (including `Content-Length`), but no body.
+fn finalize_response(
+    method: &Method,
+    category: HttpReqCategory,
+    mut response: Response,
+) -> Response {
+    let has_own_caching = matches!(category, HttpReqCategory::Assets | HttpReqCategory::TrackProxy)
+        && response.status().is_success();
+    if !has_own_caching {
+        response.headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+
+    if method == Method::HEAD {
+        if !response.headers().contains_key(header::CONTENT_LENGTH) {
+            if let Some(len) = hyper::body::HttpBody::size_hint(response.body()).exact() {
+                response.headers_mut().insert(header::CONTENT_LENGTH, len.into());
+            }
+        }
+        *response.body_mut() = Body::empty();
+    }
+
     response
 }
 
 /// Replies with a 404 Not Found.
-pub(super) async fn reply_404(ctx: &Context, method: &Method, path: &str) -> Response {
+pub(super) async fn reply_404(
+    ctx: &Context,
+    method: &Method,
+    path: &str,
+    lang: &str,
+) -> Response {
     debug!("Responding with 404 to {:?} '{}'", method, path);
 
     // We simply send the normal index and let the frontend router determinate
@@ -177,7 +294,7 @@ pub(super) async fn reply_404(ctx: &Context, method: &Method, path: &str) -> Res
     // frontend is the same as the backend router. Maybe we want to indicate to
     // the frontend explicitly to show a 404 page? However, without redirecting
     // to like `/404` because that's annoying for users.
-    ctx.assets.serve_index(StatusCode::NOT_FOUND, &ctx.config).await
+    ctx.assets.serve_index(StatusCode::NOT_FOUND, &ctx.config, lang).await
 }
 
 /// Handles a request to `/graphql`. Method has to be POST.
@@ -222,6 +339,21 @@ async fn handle_api(req: Request<Body>, ctx: &Context) -> Result<Response, Respo
         },
     };
 
+    // For the access log.
+    let annotations = Annotations {
+        graphql_operation: gql_request.operation_name().map(ToOwned::to_owned),
+        username: match &auth {
+            AuthContext::User(user) => Some(user.username.clone()),
+            _ => None,
+        },
+    };
+
+    // Only sessions we manage ourselves can be referenced in the DB.
+    let session_id = match (&auth, ctx.config.auth.mode) {
+        (AuthContext::User(_), AuthMode::LoginProxy) => SessionId::from_headers(&parts.headers),
+        _ => None,
+    };
+
     let tx = match connection.transaction().await {
         Ok(tx) => tx,
         Err(e) => {
@@ -264,9 +396,14 @@ async fn handle_api(req: Request<Body>, ctx: &Context) -> Result<Response, Respo
     let api_context = Arc::new(api::Context {
         db: Transaction::new(tx.clone()),
         auth,
+        session_id,
         config: ctx.config.clone(),
         jwt: ctx.jwt.clone(),
         search: ctx.search.clone(),
+        announcement_cache: ctx.announcement_cache.clone(),
+        navigation_cache: ctx.navigation_cache.clone(),
+        playback_positions: ctx.playback_positions.clone(),
+        oc_client: ctx.oc_client.clone(),
     });
     let gql_response = gql_request.execute(&ctx.api_root, &api_context).await;
 
@@ -320,12 +457,12 @@ async fn handle_api(req: Request<Body>, ctx: &Context) -> Result<Response, Respo
                 should be rolled back automatically since it won't be committed.");
         }
 
-        return Ok(response::internal_server_error());
+        return Ok(annotations.attach(response::internal_server_error()));
     }
 
     let out = match tx.commit().await {
         // If the transaction succeeded we can return the generated response.
-        Ok(_) => Ok(out),
+        Ok(_) => Ok(annotations.attach(out)),
 
         // Otherwise, we would like to retry a couple times, but for now
         // we just immediately reply 5xx.
@@ -334,7 +471,7 @@ async fn handle_api(req: Request<Body>, ctx: &Context) -> Result<Response, Respo
         Err(e) => {
             error!("Failed to commit transaction for API request: {}", e);
             debug_graphql_req(&raw_body);
-            Err(response::service_unavailable())
+            Err(annotations.attach(response::service_unavailable()))
         }
     };
 
@@ -457,3 +594,57 @@ impl MakeNoindexExt for hyper::Response<hyper::Body> {
         self
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Method, StatusCode, header};
+
+    use crate::metrics::HttpReqCategory;
+    use super::{Response, finalize_response};
+
+    fn response(status: StatusCode, cache_control: Option<&str>) -> Response {
+        let mut builder = Response::builder().status(status);
+        if let Some(value) = cache_control {
+            builder = builder.header(header::CACHE_CONTROL, value);
+        }
+        builder.body(Body::from("<!DOCTYPE html>")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn header_matrix() {
+        use HttpReqCategory::*;
+        const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+        // (category, status, cache-control set by the handler, expected cache-control)
+        let cases = [
+            (GraphQL, StatusCode::OK, None, Some("no-store")),
+            (Login, StatusCode::NO_CONTENT, None, Some("no-store")),
+            (Logout, StatusCode::NO_CONTENT, None, Some("no-store")),
+            (Metrics, StatusCode::OK, None, Some("no-store")),
+            (App, StatusCode::OK, None, Some("no-store")),
+            (App, StatusCode::NOT_FOUND, None, Some("no-store")),
+            (Other, StatusCode::OK, None, Some("no-store")),
+            (Assets, StatusCode::OK, Some(IMMUTABLE), Some(IMMUTABLE)),
+            (Assets, StatusCode::OK, None, None),
+            (Assets, StatusCode::NOT_FOUND, None, Some("no-store")),
+            (TrackProxy, StatusCode::OK, Some("max-age=60"), Some("max-age=60")),
+        ];
+
+        for (category, status, own, expected) in cases {
+            let get = finalize_response(&Method::GET, category, response(status, own));
+            let head = finalize_response(&Method::HEAD, category, response(status, own));
+
+            let cache_control = get.headers().get(header::CACHE_CONTROL);
+            assert_eq!(cache_control.map(|v| v.to_str().unwrap()), expected, "{category:?}");
+            assert_eq!(get.status(), head.status());
+            assert_eq!(head.headers().get(header::CACHE_CONTROL), cache_control);
+            assert_eq!(head.headers()[header::CONTENT_LENGTH], "15", "{category:?}");
+
+            let get_body = hyper::body::to_bytes(get.into_body()).await.unwrap();
+            let head_body = hyper::body::to_bytes(head.into_body()).await.unwrap();
+            assert_eq!(get_body, "<!DOCTYPE html>");
+            assert!(head_body.is_empty());
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Server-side language negotiation, used for everything the backend renders
+//! itself (e.g. the HTML shell and the no-JS fallback). The frontend uses the
+//! language hint in the HTML shell unless the user chose a language before.
+
+use hyper::{HeaderMap, header};
+
+use crate::{auth::{AuthMode, SessionId}, prelude::*};
+use super::Context;
+
+
+/// Returns the language to use for a request. In order of precedence:
+/// - the `lang` query parameter (which is then also stored in the session),
+/// - the language stored in the user's session,
+/// - the best match for the `Accept-Language` header,
+/// - the first language in `general.languages`.
+pub(super) async fn resolve(headers: &HeaderMap, query: Option<&str>, ctx: &Context) -> String {
+    let supported = &ctx.config.general.languages;
+    let is_supported = |lang: &str| supported.iter().any(|l| l == lang);
+
+    // Only sessions we manage ourselves can store a language.
+    let session_id = match ctx.config.auth.mode {
+        AuthMode::LoginProxy => SessionId::from_headers(headers),
+        _ => None,
+    };
+
+    let requested = query.into_iter()
+        .flat_map(|q| q.split('&'))
+        .find_map(|param| param.strip_prefix("lang="))
+        .filter(|lang| is_supported(lang));
+    if let Some(lang) = requested {
+        if let Some(session_id) = &session_id {
+            if let Err(e) = store_in_session(session_id, lang, ctx).await {
+                warn!("Failed to store language in session: {e:#}");
+            }
+        }
+        return lang.to_owned();
+    }
+
+    if let Some(session_id) = &session_id {
+        match load_from_session(session_id, ctx).await {
+            Ok(Some(lang)) if is_supported(&lang) => return lang,
+            Ok(_) => {}
+            Err(e) => warn!("Failed to load language from session: {e:#}"),
+        }
+    }
+
+    headers.get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| negotiate(v, supported))
+        .unwrap_or(&supported[0])
+        .to_owned()
+}
+
+async fn load_from_session(session_id: &SessionId, ctx: &Context) -> Result<Option<String>> {
+    let db = ctx.db_pool.get().await?;
+    let row = db.query_opt("select lang from user_sessions where id = $1", &[session_id]).await?;
+    Ok(row.and_then(|row| row.get(0)))
+}
+
+async fn store_in_session(session_id: &SessionId, lang: &str, ctx: &Context) -> Result<()> {
+    let db = ctx.db_pool.get().await?;
+    db.execute("update user_sessions set lang = $1 where id = $2", &[&lang, session_id]).await?;
+    Ok(())
+}
+
+/// Returns the supported language that best matches the given
+/// `Accept-Language` header value (e.g. `de-CH, de;q=0.9, en;q=0.8`), or
+/// `None` if none of them is acceptable. Only the primary language subtag is
+/// compared, so `de-CH` matches `de`.
+fn negotiate<'a>(accept_language: &str, supported: &'a [String]) -> Option<&'a str> {
+    let mut ranges = accept_language.split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let range = parts.next().filter(|r| !r.is_empty())?;
+            let quality = match parts.find_map(|p| p.strip_prefix("q=")) {
+                None => 1.0,
+                Some(q) => q.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?,
+            };
+            Some((range, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect::<Vec<_>>();
+
+    // Stable sort, so that the order of the header is kept for equal values.
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges.into_iter().find_map(|(range, _)| {
+        if range == "*" {
+            return supported.first().map(String::as_str);
+        }
+        let primary = range.split('-').next().unwrap_or(range);
+        supported.iter().find(|l| l.eq_ignore_ascii_case(primary)).map(String::as_str)
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::negotiate;
+
+    fn check(accept_language: &str, expected: Option<&str>) {
+        let supported = vec!["en".to_owned(), "de".to_owned()];
+        assert_eq!(negotiate(accept_language, &supported), expected, "{accept_language}");
+    }
+
+    #[test]
+    fn simple() {
+        check("de", Some("de"));
+        check("en", Some("en"));
+        check("de-CH", Some("de"));
+        check("DE-de", Some("de"));
+        check("en-US,en;q=0.5", Some("en"));
+    }
+
+    #[test]
+    fn quality_values() {
+        check("en;q=0.8, de", Some("de"));
+        check("en;q=0.8, de;q=0.9", Some("de"));
+        check("de;q=0.1, en;q=0.2", Some("en"));
+        check("fr, de;q=0.7, en;q=0.9", Some("en"));
+        check("de, en", Some("de"));
+        check("en ; q=0.5, de ; q=0.5", Some("en"));
+        check("de;q=0, en;q=0.1", Some("en"));
+    }
+
+    #[test]
+    fn invalid_quality_values() {
+        check("de;q=2, en;q=0.5", Some("en"));
+        check("de;q=abc, en;q=0.5", Some("en"));
+    }
+
+    #[test]
+    fn wildcard() {
+        check("fr, *;q=0.5", Some("en"));
+        check("fr, *;q=0.5, de;q=0.6", Some("de"));
+    }
+
+    #[test]
+    fn unsupported() {
+        check("fr", None);
+        check("fr-CH, it;q=0.9", None);
+        check("de;q=0", None);
+        check("", None);
+        check(",,;", None);
+    }
+}
@@ -20,15 +20,30 @@ use std::{
     sync::Arc,
 };
 
-use crate::{api, auth::JwtContext, config::Config, metrics, prelude::*, search};
+use crate::{
+    api::{self, AnnouncementCache, NavigationCache, PlaybackPositionBuffer},
+    auth::JwtContext,
+    config::Config,
+    metrics,
+    prelude::*,
+    search,
+    sync::OcClient,
+};
 use self::{
+    access_log::{AccessLog, PeerAddr},
     assets::Assets,
     handlers::handle,
 };
 
 
+pub(crate) mod access_log;
 mod assets;
 mod handlers;
+mod lang;
+mod noscript;
+mod opensearch;
+pub(crate) mod proxy;
+mod range;
 pub(crate) mod response;
 
 
@@ -67,6 +82,11 @@ pub(crate) struct Context {
     pub(crate) jwt: Arc<JwtContext>,
     pub(crate) search: Arc<search::Client>,
     pub(crate) metrics: Arc<metrics::Metrics>,
+    pub(crate) announcement_cache: Arc<AnnouncementCache>,
+    pub(crate) navigation_cache: Arc<NavigationCache>,
+    pub(crate) playback_positions: Arc<PlaybackPositionBuffer>,
+    pub(crate) oc_client: Arc<OcClient>,
+    pub(crate) access_log: Option<AccessLog>,
 }
 
 
@@ -80,6 +100,9 @@ pub(crate) async fn serve(
 ) -> Result<()> {
     let assets = Assets::init(&config).await.context("failed to initialize assets")?;
     let http_config = config.http.clone();
+    let navigation_cache = NavigationCache::new(config.general.cache_navigation);
+    let oc_client = OcClient::new(&config);
+    let access_log = AccessLog::new(&config.log).context("failed to open access log")?;
     let ctx = Arc::new(Context {
         api_root: Arc::new(api_root),
         db_pool: db,
@@ -88,8 +111,25 @@ pub(crate) async fn serve(
         config: Arc::new(config),
         search: Arc::new(search),
         metrics: Arc::new(metrics::Metrics::new()),
+        announcement_cache: Arc::new(AnnouncementCache::default()),
+        navigation_cache: Arc::new(navigation_cache),
+        playback_positions: Arc::new(PlaybackPositionBuffer::default()),
+        oc_client: Arc::new(oc_client),
+        access_log,
     });
 
+    tokio::spawn(api::cache_invalidation::run(
+        ctx.config.db.clone(),
+        ctx.navigation_cache.clone(),
+        ctx.announcement_cache.clone(),
+    ));
+
+    if ctx.config.general.continue_watching {
+        let playback_positions = ctx.playback_positions.clone();
+        let db_pool = ctx.db_pool.clone();
+        tokio::spawn(async move { playback_positions.flush_periodically(db_pool).await });
+    }
+
     // This sets up all the hyper server stuff. It's a bit of magic and touching
     // this code likely results in strange lifetime errors.
     //
@@ -108,13 +148,18 @@ pub(crate) async fn serve(
     // different factories. One for binding to a unix socket and one for
     // binding to a TCP socket. The code for defining the factory is exactly
     // the same, but due to type inference, it results in a different type. The
-    // macro avoids code duplication.
+    // macro avoids code duplication. For TCP connections, the peer address is
+    // attached to each request (for the access log).
     macro_rules! factory {
-        () => {
-            make_service_fn(move |_| {
+        ($conn:ty, $peer:expr $(,)?) => {
+            make_service_fn(move |conn: &$conn| {
                 let ctx = Arc::clone(&ctx);
-                async {
-                    Ok::<_, Infallible>(service_fn(move |req| {
+                let peer: Option<IpAddr> = $peer(conn);
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |mut req| {
+                        if let Some(peer) = peer {
+                            req.extensions_mut().insert(PeerAddr(peer));
+                        }
                         handle_internal_errors(handle(req, Arc::clone(&ctx)))
                     }))
                 }
@@ -129,7 +174,8 @@ pub(crate) async fn serve(
         if unix_socket.exists() {
             fs::remove_file(unix_socket)?;
         }
-        let server = Server::bind_unix(&unix_socket)?.serve(factory!());
+        let server = Server::bind_unix(unix_socket)?
+            .serve(factory!(tokio::net::UnixStream, |_| None));
         info!("Listening on unix://{}", unix_socket.display());
         let permissions = fs::Permissions::from_mode(http_config.unix_socket_permissions);
         fs::set_permissions(unix_socket, permissions)?;
@@ -137,7 +183,10 @@ pub(crate) async fn serve(
     } else {
         // Bind to TCP socket.
         let addr = SocketAddr::new(http_config.address, http_config.port);
-        let server = Server::bind(&addr).serve(factory!());
+        let server = Server::bind(&addr).serve(factory!(
+            hyper::server::conn::AddrStream,
+            |conn: &hyper::server::conn::AddrStream| Some(conn.remote_addr().ip()),
+        ));
         info!("Listening on http://{}", server.local_addr());
         server.await?;
     }
@@ -0,0 +1,123 @@
+//! Minimal HTML fallback for users without JavaScript (or with very old
+//! browsers). It is put into a `<noscript>` block of the `index.html`.
+
+use crate::{
+    api::{Id, EventId},
+    auth::ROLE_ANONYMOUS,
+    db::types::EventTrack,
+    prelude::*,
+};
+use super::Context;
+
+
+/// Returns the fallback HTML for the given request path or `None` if there is
+/// none for this route. Currently only event routes (`/!v/<id>` and
+/// `/<realm>/v/<id>`) have a fallback.
+pub(super) async fn for_path(path: &str, lang: &str, ctx: &Context) -> Option<String> {
+    let key = event_key_from_path(path)?;
+
+    let db = match ctx.db_pool.get().await {
+        Ok(db) => db,
+        Err(e) => {
+            warn!("Could not get DB connection for no-JS fallback: {e}");
+            return None;
+        }
+    };
+    let query = "select title, description, tracks, read_roles from events where id = $1";
+    let row = match db.query_opt(query, &[&key]).await {
+        Ok(row) => row?,
+        Err(e) => {
+            warn!("Failed to load event for no-JS fallback: {e}");
+            return None;
+        }
+    };
+
+    // For non-public events we must not leak any metadata, not even whether
+    // the event exists.
+    let read_roles: Vec<String> = row.get("read_roles");
+    if !read_roles.iter().any(|role| role == ROLE_ANONYMOUS) {
+        return Some(login_hint(path, lang));
+    }
+
+    let title: String = row.get("title");
+    let description: Option<String> = row.get("description");
+    let tracks: Vec<EventTrack> = row.get("tracks");
+
+    let mut out = format!("<h1>{}</h1>", escape(&title));
+    if let Some((index, track)) = preferred_track(&tracks) {
+        let uri = super::proxy::track_uri(&ctx.config, key, index, &track.uri)
+            .unwrap_or_else(|| track.uri.clone());
+        out += &format!(
+            "<video controls src=\"{}\"{}></video>",
+            escape(&uri),
+            track.resolution.map_or(String::new(), |[w, h]| {
+                format!(" width=\"{w}\" height=\"{h}\"")
+            }),
+        );
+    }
+    if let Some(description) = description {
+        for paragraph in description.split("\n\n") {
+            out += &format!("<p>{}</p>", escape(paragraph));
+        }
+    }
+
+    Some(out)
+}
+
+/// Extracts the event key from `/!v/<id>` or `/<realm path>/v/<id>`.
+fn event_key_from_path(path: &str) -> Option<EventId> {
+    let (prefix, id) = path.rsplit_once('/')?;
+    if prefix != "/!v" && !prefix.ends_with("/v") {
+        return None;
+    }
+
+    // IDs in URLs are without the kind prefix.
+    EventId::from_id(format!("ev{id}").parse::<Id>().ok()?).ok()
+}
+
+/// Picks the track most likely to play in any browser: progressive MP4 is
+/// preferred over other formats. HLS is never chosen as it needs JS in most
+/// browsers. Among equally suitable tracks, the master/highest resolution wins.
+/// Returns the track together with its index.
+fn preferred_track(tracks: &[EventTrack]) -> Option<(usize, &EventTrack)> {
+    tracks.iter()
+        .enumerate()
+        .filter(|(_, t)| !t.uri.ends_with(".m3u8"))
+        .max_by_key(|(_, t)| (
+            t.mimetype.as_deref() == Some("video/mp4"),
+            t.is_master == Some(true),
+            t.resolution.map_or(0, |[w, h]| w * h),
+        ))
+}
+
+fn login_hint(path: &str, lang: &str) -> String {
+    // `/~login` redirects to `auth.login_link` if that is set.
+    let return_to = percent_encoding::utf8_percent_encode(path, percent_encoding::NON_ALPHANUMERIC);
+    let login_link = escape(&format!("/~login?return_to={return_to}"));
+    match lang {
+        "de" => format!(
+            "<p>Dieses Video ist nicht öffentlich. Bitte <a href=\"{login_link}\">melden Sie \
+                sich an</a>, um es anzusehen.</p>",
+        ),
+        _ => format!(
+            "<p>This video is not public. Please <a href=\"{login_link}\">log in</a> to watch \
+                it.</p>",
+        ),
+    }
+}
+
+/// Escapes a string so that it can be used inside HTML text and attribute values.
+pub(super) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,198 @@
+//! OpenSearch description and suggestions, allowing browsers to register
+//! Tobira as search engine. See <https://github.com/dewitt/opensearch>.
+
+use hyper::{Body, HeaderMap, StatusCode, header, http::uri::Authority};
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+
+use crate::{auth::ROLE_ANONYMOUS, prelude::*, search};
+use super::{Context, Response, noscript::escape};
+
+
+pub(super) const DESCRIPTION_PATH: &str = "/~opensearch.xml";
+pub(super) const SUGGESTIONS_PATH: &str = "/~opensearch/suggestions";
+
+/// Maximum number of suggestions per kind (events, series, realms).
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Replies with the OpenSearch description document, named after the
+/// (localized) site title.
+pub(super) async fn description(headers: &HeaderMap, ctx: &Context) -> Response {
+    let Some(origin) = origin(headers) else {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body("Bad request: missing or invalid 'Host' header".into())
+            .unwrap();
+    };
+    let lang = super::lang::resolve(headers, None, ctx).await;
+    let title = ctx.config.general.site_title.get(&lang);
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/opensearchdescription+xml; charset=UTF-8")
+        .header(header::VARY, "Accept-Language")
+        .body(description_xml(&origin, title, &lang).into())
+        .unwrap()
+}
+
+fn description_xml(origin: &str, title: &str, lang: &str) -> String {
+    let description = match lang {
+        "de" => format!("Videos auf {title} suchen"),
+        _ => format!("Search videos on {title}"),
+    };
+
+    // The short name must not be longer than 16 characters.
+    let short_name = match title.char_indices().nth(16) {
+        Some((i, _)) => title[..i].trim_end(),
+        None => title,
+    };
+
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>{}</ShortName>
+  <Description>{}</Description>
+  <Language>{}</Language>
+  <InputEncoding>UTF-8</InputEncoding>
+  <Url type="text/html" method="get" template="{origin}/~search?q={{searchTerms}}"/>
+  <Url type="application/x-suggestions+json" method="get"
+    template="{origin}{SUGGESTIONS_PATH}?q={{searchTerms}}"/>
+  <Url type="application/opensearchdescription+xml" rel="self"
+    template="{origin}{DESCRIPTION_PATH}"/>
+</OpenSearchDescription>
+"#,
+        escape(short_name),
+        escape(&description),
+        escape(lang),
+    )
+}
+
+/// Replies with suggestions in the format of the OpenSearch suggestions
+/// extension: titles of events and series and names of realms matching the
+/// query, with links to them. As browsers might cache these, only public
+/// events are considered, regardless of who is logged in.
+pub(super) async fn suggestions(
+    query: Option<&str>,
+    headers: &HeaderMap,
+    ctx: &Context,
+) -> Response {
+    let user_query = query.into_iter()
+        .flat_map(|q| q.split('&'))
+        .find_map(|param| param.strip_prefix("q="))
+        .map(|v| v.replace('+', " "))
+        .and_then(|v| Some(percent_decode_str(&v).decode_utf8().ok()?.trim().to_owned()))
+        .unwrap_or_default();
+
+    let mut out = Vec::new();
+    if !user_query.is_empty() {
+        match load_suggestions(&user_query, ctx).await {
+            Ok(suggestions) => out = suggestions,
+            // Suggestions are not important enough to show an error.
+            Err(e) => warn!("Failed to load search suggestions: {e:#}"),
+        }
+    }
+
+    let origin = origin(headers).unwrap_or_default();
+    let (texts, urls): (Vec<_>, Vec<_>) = out.into_iter()
+        .map(|s| (s.text, format!("{origin}{}", s.path)))
+        .unzip();
+    let body = json!([user_query, texts, vec![""; urls.len()], urls]);
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-suggestions+json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[derive(Debug)]
+struct Suggestion {
+    text: String,
+    path: String,
+}
+
+async fn load_suggestions(user_query: &str, ctx: &Context) -> Result<Vec<Suggestion>> {
+    let filter = format!("listed = true AND read_roles = '{}'", hex::encode(ROLE_ANONYMOUS));
+    let mut event_query = ctx.search.event_index.search();
+    event_query.with_query(user_query);
+    event_query.with_limit(2 * MAX_SUGGESTIONS);
+    event_query.with_filter(&filter);
+
+    let mut realm_query = ctx.search.realm_index.search();
+    realm_query.with_query(user_query);
+    realm_query.with_limit(MAX_SUGGESTIONS);
+
+    let (events, realms) = tokio::try_join!(
+        event_query.execute::<search::Event>(),
+        realm_query.execute::<search::Realm>(),
+    )?;
+
+    let events = events.hits.into_iter().map(|hit| hit.result).collect::<Vec<_>>();
+    let realms = realms.hits.into_iter().map(|hit| hit.result).collect::<Vec<_>>();
+    Ok(merge(events, realms))
+}
+
+/// Combines search results into suggestions: first events, then their
+/// series (each only once), then realms.
+fn merge(events: Vec<search::Event>, realms: Vec<search::Realm>) -> Vec<Suggestion> {
+    let mut series = Vec::<Suggestion>::new();
+    for event in &events {
+        if let (Some(id), Some(title)) = (event.series_id, &event.series_title) {
+            let path = format!("/!s/{id}");
+            if series.len() < MAX_SUGGESTIONS && !series.iter().any(|s| s.path == path) {
+                series.push(Suggestion { text: title.clone(), path });
+            }
+        }
+    }
+
+    let events = events.into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|e| Suggestion { text: e.title, path: format!("/!v/{}", e.id) });
+    let realms = realms.into_iter()
+        .filter_map(|r| Some(Suggestion { text: r.name?, path: r.full_path }));
+
+    events.chain(series).chain(realms).collect()
+}
+
+/// Returns the origin of the request (e.g. `https://tobira.example.com`), as
+/// documents for browsers need absolute URLs. Behind a reverse proxy, the
+/// scheme is taken from `X-Forwarded-Proto`, otherwise HTTPS is assumed for
+/// all hosts except `localhost`.
+fn origin(headers: &HeaderMap) -> Option<String> {
+    let host = headers.get(header::HOST)?.to_str().ok()?.parse::<Authority>().ok()?;
+    let scheme = match headers.get("x-forwarded-proto").and_then(|v| v.to_str().ok()) {
+        Some(proto @ ("http" | "https")) => proto,
+        _ if matches!(host.host(), "localhost" | "127.0.0.1" | "[::1]") => "http",
+        _ => "https",
+    };
+    Some(format!("{scheme}://{host}"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use hyper::{HeaderMap, header::HeaderValue};
+    use super::{description_xml, origin};
+
+    #[test]
+    fn origin_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(origin(&headers), None);
+
+        headers.insert("host", HeaderValue::from_static("tobira.example.com"));
+        assert_eq!(origin(&headers).as_deref(), Some("https://tobira.example.com"));
+        headers.insert("host", HeaderValue::from_static("localhost:3080"));
+        assert_eq!(origin(&headers).as_deref(), Some("http://localhost:3080"));
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        assert_eq!(origin(&headers).as_deref(), Some("https://localhost:3080"));
+
+        headers.insert("host", HeaderValue::from_static("evil.com/path"));
+        assert_eq!(origin(&headers), None);
+    }
+
+    #[test]
+    fn description() {
+        let xml = description_xml("https://videos.uni.edu", "Videoportal der Uni <X>", "de");
+        assert!(xml.contains("<ShortName>Videoportal der</ShortName>"), "{xml}");
+        assert!(xml.contains("<Description>Videos auf Videoportal der Uni &lt;X&gt; suchen"));
+        assert!(xml.contains(r#"template="https://videos.uni.edu/~search?q={searchTerms}""#));
+        assert!(xml.contains("template=\"https://videos.uni.edu/~opensearch/suggestions?q="));
+    }
+}
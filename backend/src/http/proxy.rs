@@ -0,0 +1,189 @@
+//! Proxying of tracks published on protected Opencast channels, see
+//! `opencast.proxy_track_hosts`.
+
+use hyper::{Body, HeaderMap, StatusCode, Uri, header};
+
+use crate::{
+    api::{Id, EventId},
+    auth::{AuthContext, HasRoles},
+    config::Config,
+    db::{self, types::EventTrack},
+    prelude::*,
+};
+use super::{Context, Request, Response};
+
+
+/// Path prefix of proxied tracks, followed by `<event>/<track index>`.
+pub(super) const PREFIX: &str = "/~proxy/track/";
+
+/// Request headers that are forwarded to Opencast. These make seeking
+/// (`Range`) and conditional requests work.
+const FORWARDED_REQUEST_HEADERS: &[header::HeaderName] = &[
+    header::RANGE,
+    header::IF_RANGE,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+];
+
+/// Response headers that are forwarded from Opencast to the client.
+const FORWARDED_RESPONSE_HEADERS: &[header::HeaderName] = &[
+    header::ACCEPT_RANGES,
+    header::CACHE_CONTROL,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::CONTENT_TYPE,
+    header::ETAG,
+    header::EXPIRES,
+    header::LAST_MODIFIED,
+];
+
+
+/// Returns the URI under which Tobira proxies the given track, or `None` if
+/// the track is not on one of the configured proxy hosts.
+pub(crate) fn track_uri(
+    config: &Config,
+    event: EventId,
+    index: usize,
+    uri: &str,
+) -> Option<String> {
+    if !is_proxied(config, uri) {
+        return None;
+    }
+
+    let mut buf = [0; 11];
+    Some(format!("{PREFIX}{}/{index}", event.0.to_base64(&mut buf)))
+}
+
+fn is_proxied(config: &Config, uri: &str) -> bool {
+    let Ok(uri) = uri.parse::<Uri>() else {
+        return false;
+    };
+    config.opencast.proxy_track_hosts.iter().any(|host| {
+        uri.scheme() == Some(&host.scheme) && uri.authority() == Some(&host.authority)
+    })
+}
+
+/// Handles a request to `/~proxy/track/<event>/<index>`: checks that the
+/// user can read the event and streams the track from Opencast. The body is
+/// passed through without buffering it.
+pub(super) async fn handle(req: Request<Body>, ctx: &Context) -> Response {
+    let path = req.uri().path().trim_end_matches('/');
+    let Some((key, index)) = parse_path(&path[PREFIX.len()..]) else {
+        return not_found();
+    };
+    if ctx.config.opencast.proxy_track_hosts.is_empty() {
+        return not_found();
+    }
+
+    let db = match db::get_conn_or_service_unavailable(&ctx.db_pool).await {
+        Ok(db) => db,
+        Err(response) => return response,
+    };
+    let auth = match AuthContext::new(req.headers(), &ctx.config.auth, &db).await {
+        Ok(auth) => auth,
+        Err(e) => {
+            error!("DB error when checking user session: {e}");
+            return super::response::internal_server_error();
+        }
+    };
+
+    let query = "select tracks, read_roles from events where id = $1";
+    let row = match db.query_opt(query, &[&key]).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return not_found(),
+        Err(e) => {
+            error!("Failed to load event for track proxy: {e}");
+            return super::response::internal_server_error();
+        }
+    };
+    drop(db);
+
+    // Unauthorized users get the same response as for non-existing events to
+    // not leak whether an event exists.
+    if !auth.overlaps_roles(row.get::<_, Vec<String>>("read_roles")) {
+        return not_found();
+    }
+    let tracks: Vec<EventTrack> = row.get("tracks");
+    let Some(track) = tracks.into_iter().nth(index) else {
+        return not_found();
+    };
+
+    // Never send our credentials to hosts not configured as proxy hosts.
+    if !is_proxied(&ctx.config, &track.uri) {
+        return not_found();
+    }
+    let uri = match track.uri.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return not_found(),
+    };
+
+    let mut headers = HeaderMap::new();
+    for name in FORWARDED_REQUEST_HEADERS {
+        if let Some(value) = req.headers().get(name) {
+            headers.insert(name, value.clone());
+        }
+    }
+
+    let upstream = match ctx.oc_client.request_authenticated(req.method().clone(), uri, headers)
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to proxy track: {e:#}");
+            return Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body("Bad gateway".into())
+                .unwrap();
+        }
+    };
+
+    let (parts, body) = upstream.into_parts();
+    if parts.status.is_server_error() || parts.status == StatusCode::UNAUTHORIZED
+        || parts.status == StatusCode::FORBIDDEN
+    {
+        warn!("Opencast responded {} to proxied track request for event {key:?}", parts.status);
+    }
+
+    let mut response = Response::new(body);
+    *response.status_mut() = parts.status;
+    for name in FORWARDED_RESPONSE_HEADERS {
+        if let Some(value) = parts.headers.get(name) {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+    response
+}
+
+/// Parses `<event>/<track index>`, where `<event>` is an event ID without its
+/// kind prefix.
+fn parse_path(path: &str) -> Option<(EventId, usize)> {
+    let (event, index) = path.split_once('/')?;
+    let key = EventId::from_id(format!("ev{event}").parse::<Id>().ok()?).ok()?;
+    let index = index.parse().ok()?;
+    Some((key, index))
+}
+
+fn not_found() -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body("Not found".into())
+        .unwrap()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::EventId, db::types::Key};
+    use super::parse_path;
+
+    #[test]
+    fn paths() {
+        let mut buf = [0; 11];
+        let id = Key(1234).to_base64(&mut buf).to_owned();
+        assert_eq!(parse_path(&format!("{id}/0")), Some((EventId(Key(1234)), 0)));
+        assert_eq!(parse_path(&format!("{id}/17")), Some((EventId(Key(1234)), 17)));
+        assert_eq!(parse_path(&format!("{id}/-1")), None);
+        assert_eq!(parse_path(&id), None);
+        assert_eq!(parse_path("foo/0"), None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Handling of `Range` requests (RFC 9110, section 14). Only single byte
+//! ranges are supported. Requests for multiple ranges are answered with the
+//! full body, which is allowed by the spec.
+
+use std::ops::Range;
+
+use hyper::{HeaderMap, header};
+
+
+/// How to respond to a request, depending on its `Range` and `If-Range`
+/// headers.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum RangeResponse {
+    /// Respond with the full body and `200 OK`.
+    Full,
+
+    /// Respond with only the given part of the body and `206 Partial Content`.
+    Partial(Range<usize>),
+
+    /// Respond with `416 Range Not Satisfiable`.
+    NotSatisfiable,
+}
+
+impl RangeResponse {
+    /// Evaluates the request headers for a resource with `len` bytes. `etag`
+    /// is the strong entity tag of the resource, if it has one.
+    pub(super) fn evaluate(headers: &HeaderMap, etag: Option<&str>, len: usize) -> Self {
+        let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+            Some(range) => range,
+            None => return Self::Full,
+        };
+
+        // `If-Range` means: "only send the range if the resource has not
+        // changed, otherwise send all of it". We do not send `Last-Modified`,
+        // so only (strong) entity tags can match.
+        if let Some(if_range) = headers.get(header::IF_RANGE) {
+            let matches = etag.is_some_and(|etag| {
+                !etag.starts_with("W/") && if_range.as_bytes() == etag.as_bytes()
+            });
+            if !matches {
+                return Self::Full;
+            }
+        }
+
+        Self::from_header(range, len)
+    }
+
+    fn from_header(range: &str, len: usize) -> Self {
+        // Range headers we don't understand have to be ignored.
+        let spec = match range.trim().strip_prefix("bytes=") {
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return Self::Full,
+        };
+        let (start, end) = match spec.split_once('-') {
+            Some(parts) => parts,
+            None => return Self::Full,
+        };
+        let parse = |s: &str| -> Option<Option<usize>> {
+            match s {
+                "" => Some(None),
+                s if s.bytes().all(|b| b.is_ascii_digit()) => s.parse().ok().map(Some),
+                _ => None,
+            }
+        };
+        let (start, end) = match (parse(start), parse(end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Self::Full,
+        };
+
+        match (start, end) {
+            // `bytes=-500`: the last 500 bytes.
+            (None, Some(suffix_len)) => {
+                if suffix_len == 0 || len == 0 {
+                    Self::NotSatisfiable
+                } else {
+                    Self::Partial(len.saturating_sub(suffix_len)..len)
+                }
+            }
+
+            // `bytes=500-` and `bytes=500-999`.
+            (Some(start), end) => {
+                if end.is_some_and(|end| end < start) {
+                    // Syntactically invalid: has to be ignored.
+                    Self::Full
+                } else if start >= len {
+                    Self::NotSatisfiable
+                } else {
+                    let end = end.map_or(len, |end| (end + 1).min(len));
+                    Self::Partial(start..end)
+                }
+            }
+
+            (None, None) => Self::Full,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use hyper::{HeaderMap, header};
+    use super::RangeResponse;
+
+    const ETAG: &str = "\"main.bundle.1234.js\"";
+
+    fn evaluate(headers: &[(header::HeaderName, &str)], len: usize) -> RangeResponse {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(name, value.parse().unwrap());
+        }
+        RangeResponse::evaluate(&map, Some(ETAG), len)
+    }
+
+    #[test]
+    fn no_range() {
+        assert_eq!(evaluate(&[], 1000), RangeResponse::Full);
+    }
+
+    #[test]
+    fn single_range() {
+        let check = |range, expected| {
+            assert_eq!(evaluate(&[(header::RANGE, range)], 1000), expected, "{range}");
+        };
+        check("bytes=0-499", RangeResponse::Partial(0..500));
+        check("bytes=500-999", RangeResponse::Partial(500..1000));
+        check("bytes=500-", RangeResponse::Partial(500..1000));
+        check("bytes=-200", RangeResponse::Partial(800..1000));
+        check("bytes=0-0", RangeResponse::Partial(0..1));
+        check("bytes=900-5000", RangeResponse::Partial(900..1000));
+        check("bytes=-5000", RangeResponse::Partial(0..1000));
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        let check = |range, len| {
+            assert_eq!(
+                evaluate(&[(header::RANGE, range)], len),
+                RangeResponse::NotSatisfiable,
+                "{range}",
+            );
+        };
+        check("bytes=1000-", 1000);
+        check("bytes=1000-1999", 1000);
+        check("bytes=-0", 1000);
+        check("bytes=-10", 0);
+    }
+
+    #[test]
+    fn ignored_range() {
+        let check = |range| {
+            assert_eq!(evaluate(&[(header::RANGE, range)], 1000), RangeResponse::Full, "{range}");
+        };
+        check("bytes=500-100");
+        check("bytes=0-10,20-30");
+        check("items=0-10");
+        check("bytes=abc-");
+        check("bytes=-");
+        check("bytes=+1-2");
+    }
+
+    #[test]
+    fn if_range() {
+        let range = (header::RANGE, "bytes=0-9");
+        assert_eq!(
+            evaluate(&[range.clone(), (header::IF_RANGE, ETAG)], 1000),
+            RangeResponse::Partial(0..10),
+        );
+        assert_eq!(
+            evaluate(&[range.clone(), (header::IF_RANGE, "\"other\"")], 1000),
+            RangeResponse::Full,
+        );
+        assert_eq!(
+            evaluate(&[range.clone(), (header::IF_RANGE, "Wed, 21 Oct 2015 07:28:00 GMT")], 1000),
+            RangeResponse::Full,
+        );
+
+        // Without an entity tag, `If-Range` can never match.
+        let mut map = HeaderMap::new();
+        map.insert(header::RANGE, "bytes=0-9".parse().unwrap());
+        map.insert(header::IF_RANGE, ETAG.parse().unwrap());
+        assert_eq!(RangeResponse::evaluate(&map, None, 1000), RangeResponse::Full);
+    }
+}
@@ -1,12 +1,18 @@
 use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
 use std::{
     fs::{File, OpenOptions},
-    path::PathBuf,
-    sync::Mutex,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, Once, Weak},
 };
 use termcolor::{NoColor, StandardStream, WriteColor};
 
-use crate::{prelude::*, args::Args};
+use crate::{
+    prelude::*,
+    args::Args,
+    http::access_log::{AccessLogFormat, AccessLogTarget},
+};
 
 
 #[derive(Debug, confique::Config)]
@@ -17,8 +23,9 @@ pub(crate) struct LogConfig {
     #[config(default = "debug")]
     pub(crate) level: log::LevelFilter,
 
-    /// If this is set, log messages are also written to this file.
-    /// Example: "/var/log/tobira.log".
+    /// If this is set, log messages are also written to this file. The file
+    /// is reopened when Tobira receives `SIGHUP`, so that it can be rotated
+    /// (e.g. with `logrotate`). Example: "/var/log/tobira.log".
     pub(crate) file: Option<PathBuf>,
 
     /// If this is set to `false`, log messages are not written to stdout.
@@ -29,13 +36,46 @@ pub(crate) struct LogConfig {
     /// (with 'trace' level).
     #[config(default = false)]
     pub(crate) log_http_headers: bool,
+
+    /// If this is set, one line per HTTP request is written to this access
+    /// log, separate from the log messages above. Either a file path or
+    /// "stdout". Like `file`, the file is reopened on `SIGHUP`.
+    /// Example: "/var/log/tobira-access.log".
+    pub(crate) access_log: Option<AccessLogTarget>,
+
+    /// Format of the access log lines. Supported fields:
+    /// - `%a`: client IP address. Behind a reverse proxy, this is the last
+    ///   address in the `X-Forwarded-For` header.
+    /// - `%t`: time the request was received, like `[15/Oct/2026:14:03:12 +0200]`
+    /// - `%m`: request method
+    /// - `%U`: request path
+    /// - `%R`: like `%U`, but for GraphQL requests, the operation name prefixed
+    ///   with `graphql:`, e.g. `graphql:VideoPageInRealmQuery`
+    /// - `%s`: response status code
+    /// - `%b`: size of the response body in bytes (`-` if unknown)
+    /// - `%D`: time to handle the request in microseconds
+    /// - `%u`: username (`-` if not known to be logged in)
+    /// - `%L`: request ID, taken from the `X-Request-Id` header or generated.
+    ///   It is also sent back in the `X-Request-Id` response header.
+    /// - `%%`: a literal `%`
+    ///
+    /// Note that users are only known for requests that check the session,
+    /// most importantly API requests.
+    #[config(default = "%a %u %t \"%m %R\" %s %b %D %L")]
+    pub(crate) access_log_format: AccessLogFormat,
+
+    /// If set to `true`, usernames in the access log are replaced by a hash.
+    /// Note that this is pseudonymization, not anonymization: hashes of known
+    /// usernames can simply be computed.
+    #[config(default = false)]
+    pub(crate) access_log_hash_users: bool,
 }
 
 
 /// Our own `Log` implementation.
 struct Logger {
     level_filter: LevelFilter,
-    file: Option<Mutex<File>>,
+    file: Option<Arc<LogFile>>,
     stdout: Option<Mutex<StandardStream>>,
 }
 
@@ -46,10 +86,7 @@ pub(crate) fn init(config: &LogConfig, args: &Args) -> Result<()> {
         false => None,
     };
 
-    let file = config.file.as_ref()
-        .map(|path| OpenOptions::new().append(true).create(true).open(path))
-        .transpose()?
-        .map(Mutex::new);
+    let file = config.file.as_deref().map(LogFile::open).transpose()?;
 
     let logger = Logger {
         level_filter: config.level,
@@ -88,14 +125,77 @@ impl Log for Logger {
 
         if let Some(file) = &self.file {
             // See comment above about stdout.
-            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
-            let _ = write(record, &mut NoColor::new(&mut *file));
+            let _ = write(record, &mut NoColor::new(&mut *file.lock()));
         }
     }
 
     fn flush(&self) {}
 }
 
+/// All opened `LogFile`s, to reopen them on `SIGHUP`.
+static LOG_FILES: Lazy<Mutex<Vec<Weak<LogFile>>>> = Lazy::new(Default::default);
+
+/// A file that log lines are appended to. It is reopened when Tobira receives
+/// `SIGHUP`. That way, log files can be rotated by moving them and sending
+/// that signal afterwards, which is what e.g. `logrotate` does.
+pub(crate) struct LogFile {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl LogFile {
+    /// Opens the file in append mode, creating it if it does not exist.
+    /// Must be called inside the tokio runtime.
+    pub(crate) fn open(path: &Path) -> Result<Arc<Self>> {
+        let file = Self::open_file(path)
+            .with_context(|| format!("failed to open log file '{}'", path.display()))?;
+        let out = Arc::new(Self { path: path.to_owned(), file: Mutex::new(file) });
+        LOG_FILES.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::downgrade(&out));
+
+        static LISTEN_FOR_SIGHUP: Once = Once::new();
+        LISTEN_FOR_SIGHUP.call_once(|| {
+            tokio::spawn(async {
+                if let Err(e) = reopen_on_sighup().await {
+                    error!("Failed to listen for SIGHUP, log files won't be reopened: {e}");
+                }
+            });
+        });
+
+        Ok(out)
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().append(true).create(true).open(path)
+    }
+
+    /// Returns the file to write to. Like for stdout, a poisoned mutex is
+    /// ignored, see `Logger::log`.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, File> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn reopen_on_sighup() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::hangup())?;
+    while signals.recv().await.is_some() {
+        let files = LOG_FILES.lock().unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        for log_file in files {
+            // On error, we just keep writing to the old file.
+            match LogFile::open_file(&log_file.path) {
+                Ok(file) => *log_file.lock() = file,
+                Err(e) => error!("Failed to reopen log file '{}': {e}", log_file.path.display()),
+            }
+        }
+        info!("Received SIGHUP: reopened log files");
+    }
+    Ok(())
+}
+
 fn write(record: &Record, out: &mut impl WriteColor) -> Result<()> {
     // Figure out styles/colors for the parts of the message.
     let dim_style = bunt::style!("dimmed");
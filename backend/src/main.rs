@@ -3,7 +3,8 @@
 use clap::{FromArgMatches, IntoApp};
 use deadpool_postgres::Pool;
 use util::Never;
-use std::env;
+use std::{env, time::Duration};
+use tokio::time::Instant;
 
 use crate::{
     args::{Args, Command},
@@ -19,12 +20,14 @@ mod cmd;
 mod db;
 mod http;
 mod logger;
+mod markdown;
 mod metrics;
 mod prelude;
 mod search;
 mod sync;
 mod util;
 mod version;
+mod wait;
 
 
 #[tokio::main]
@@ -66,11 +69,13 @@ async fn run() -> Result<()> {
     // Parse CLI args.
     // This is a bit roundabout because we want to override the version
     // using some runtime code.
-    let args = Args::from_arg_matches(
-        &Args::command()
-            .version(&*version::full())
-            .get_matches(),
-    )?;
+    let matches = Args::command()
+        .version(&*version::full())
+        .get_matches();
+    let args = Args::from_arg_matches(&matches)?;
+    if let Some(subcommand) = matches.subcommand_name() {
+        db::set_application_name(subcommand);
+    }
 
     // Configure output via `bunt`
     bunt::set_stdout_color_choice(args.stdout_color());
@@ -79,29 +84,44 @@ async fn run() -> Result<()> {
 
     // Dispatch subcommand.
     match &args.cmd {
-        Command::Serve { shared } => {
+        Command::Serve { shared, wait_for_db } => {
             let config = load_config_and_init_logger(shared, &args)?;
-            start_server(config).await?;
+            start_server(config, wait_deadline(*wait_for_db)).await?;
         }
         Command::Sync { args: sync_args, shared } => {
             let config = load_config_and_init_logger(shared, &args)?;
             sync::cmd::run(sync_args, &config).await?;
         }
-        Command::Db { cmd, shared } => {
+        Command::Db { cmd, output, shared } => {
             let config = load_config_and_init_logger(shared, &args)?;
-            db::cmd::run(cmd, &config).await?;
+            // Keep stdout parsable.
+            if *output == db::cmd::OutputFormat::Json {
+                log::set_max_level(log::max_level().min(log::LevelFilter::Error));
+            }
+            db::cmd::run(cmd, &config, *output).await?;
         }
         Command::SearchIndex { cmd, shared } => {
             let config = load_config_and_init_logger(shared, &args)?;
             search::cmd::run(cmd, &config).await?;
         }
-        Command::Worker { shared } => {
+        Command::Worker { shared, wait_for_db } => {
             let config = load_config_and_init_logger(shared, &args)?;
-            start_worker(config).await?;
+            start_worker(config, wait_deadline(*wait_for_db)).await?;
         }
         Command::Check { shared } => cmd::check::run(shared, &args).await?,
+        Command::Config { cmd } => cmd::config::run(cmd)?,
         Command::WriteConfig { target } => config::write_template(target.as_ref())?,
         Command::ExportApiSchema { args } => cmd::export_api_schema::run(args)?,
+        Command::Export { cmd, shared } => {
+            let config = load_config_and_init_logger(shared, &args)?;
+            cmd::export::run(cmd, &config).await?;
+        }
+        Command::Probe { args: probe_args, shared } => {
+            let config = load_config_and_init_logger(shared, &args)?;
+            // Keep stdout parsable.
+            log::set_max_level(log::max_level().min(log::LevelFilter::Error));
+            cmd::probe::run(probe_args, &config).await?;
+        }
         Command::ImportRealmTree { options, shared } => {
             let config = load_config_and_init_logger(shared, &args)?;
             cmd::import_realm_tree::run(options, &config).await?;
@@ -111,15 +131,27 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
-async fn start_server(config: Config) -> Result<()> {
+async fn start_server(config: Config, wait_deadline: Option<Instant>) -> Result<()> {
     info!("Starting Tobira backend ...");
     trace!("Configuration: {:#?}", config);
-    let db = connect_and_migrate_db(&config).await?;
+    let db = connect_and_migrate_db(&config, wait_deadline).await?;
     let search = search::Client::new(config.meili.clone());
-    if let Err(e) = search.check_connection().await {
+    let search_check = match wait_deadline {
+        Some(deadline) => wait::for_meili(&search, deadline).await,
+        None => search.check_connection().await,
+    };
+    if let Err(e) = search_check {
         warn!("Could not connect to Meili search index: {e:?}");
     }
 
+    {
+        let conn = db.get().await?;
+        let initial_page = &config.general.initial_page;
+        if let Err(e) = initial_page.warn_about_missing_realms(&**conn).await {
+            warn!("Could not check realms of `general.initial_page`: {e:#}");
+        }
+    }
+
     // Start web server
     let root_node = api::root_node();
     http::serve(config, root_node, db, search).await
@@ -128,30 +160,45 @@ async fn start_server(config: Config) -> Result<()> {
     Ok(())
 }
 
-async fn start_worker(config: Config) -> Result<Never> {
+async fn start_worker(config: Config, wait_deadline: Option<Instant>) -> Result<Never> {
     info!("Starting Tobira worker ...");
 
-    let db = connect_and_migrate_db(&config).await?;
-    let search = config.meili.connect().await.context("failed to connect to MeiliSearch")?;
+    let db = connect_and_migrate_db(&config, wait_deadline).await?;
+    let search = match wait_deadline {
+        Some(deadline) => {
+            let search = search::Client::new(config.meili.clone());
+            wait::for_meili(&search, deadline).await
+                .context("failed to connect to MeiliSearch")?;
+            search
+        }
+        None => config.meili.connect().await.context("failed to connect to MeiliSearch")?,
+    };
 
     let mut search_conn = db.get().await?;
     let sync_conn = db.get().await?;
     let db_maintenance_conn = db.get().await?;
-    let auth_config = config.auth.clone();
+    let uploads_conn = db.get().await?;
+    let mut federation_conn = db.get().await?;
 
     tokio::select! {
         res = search::update_index_daemon(&search, &mut search_conn) => {
             res.context("error updating the search index")
         }
-        res = sync::run(true, sync_conn, &config) => {
+        res = sync::run_daemon(sync_conn, uploads_conn, &config) => {
             res.map(|()| unreachable!("sync task unexpectedly stopped"))
                 .context("error synchronizing with Opencast")
         }
-        never = auth::db_maintenance(&db_maintenance_conn, &auth_config) => { never }
+        never = auth::db_maintenance(&db_maintenance_conn, &config) => { never }
+        never = sync::federation::run_daemon(&mut federation_conn, &config) => { never }
     }
 }
 
 
+/// Converts the `--wait-for-db` argument into a deadline.
+fn wait_deadline(wait_for_db: Option<u64>) -> Option<Instant> {
+    wait_for_db.map(|secs| Instant::now() + Duration::from_secs(secs))
+}
+
 fn load_config_and_init_logger(shared: &args::Shared, args: &Args) -> Result<Config> {
     // Load configuration.
     let (config, path) = match &shared.config {
@@ -171,10 +218,16 @@ fn load_config_and_init_logger(shared: &args::Shared, args: &Args) -> Result<Con
     Ok(config)
 }
 
-async fn connect_and_migrate_db(config: &Config) -> Result<Pool> {
-    let db = db::create_pool(&config.db).await
-        .context("failed to create database connection pool (database not running?)")?;
-    db::migrate(&mut *db.get().await?).await
+/// Connects to the DB and runs migrations. If `wait_deadline` is set,
+/// connecting is retried until then.
+async fn connect_and_migrate_db(config: &Config, wait_deadline: Option<Instant>) -> Result<Pool> {
+    let db = match wait_deadline {
+        Some(deadline) => wait::for_db(&config.db, deadline).await,
+        None => db::create_pool(&config.db).await,
+    };
+    let db = db.context("failed to create database connection pool (database not running?)")?;
+    db::auto_migrate(&mut *db.get().await?, &config.db).await
         .context("failed to check/run DB migrations")?;
+    db::collation::log_findings(&*db.get().await?).await;
     Ok(db)
 }
@@ -0,0 +1,95 @@
+//! Rendering Markdown (e.g. of text blocks) to sanitized HTML.
+
+use std::collections::{HashMap, HashSet};
+
+use ammonia::UrlRelative;
+use pulldown_cmark::{Event, Parser, html};
+
+
+/// HTML elements that are allowed in the output. Notably missing are
+/// headings: if we allowed them, we would need to map `#` to `h2` or `h3` so
+/// that they don't interfere with other headings on the page. The content of
+/// all other elements is kept.
+const ALLOWED_TAGS: &[&str] = &[
+    "p", "br", "blockquote", "pre", "ul", "ol", "li", "a", "em", "strong", "code", "hr", "img",
+];
+
+/// Attributes that are allowed in the output, per element.
+const ALLOWED_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "title"]),
+    ("img", &["src", "alt", "title"]),
+    ("ol", &["start"]),
+];
+
+/// URL schemes that are allowed in links and image sources. Relative URLs are
+/// allowed as well.
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+
+/// Renders the given Markdown to HTML that can be safely embedded into a
+/// page. Raw HTML in the Markdown is escaped, i.e. shown as text, and links
+/// with other than the allowed URL schemes (e.g. `javascript:`) are removed.
+pub(crate) fn render(markdown: &str) -> String {
+    let events = Parser::new(markdown).map(|event| match event {
+        Event::Html(html) => Event::Text(html),
+        other => other,
+    });
+    let mut unsanitized = String::new();
+    html::push_html(&mut unsanitized, events);
+
+    // Escaping raw HTML above already prevents most problems, but the HTML
+    // generated from Markdown itself can contain dangerous URLs.
+    ammonia::Builder::empty()
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .tag_attributes(ALLOWED_ATTRIBUTES.iter()
+            .map(|(tag, attrs)| (*tag, attrs.iter().copied().collect::<HashSet<_>>()))
+            .collect::<HashMap<_, _>>())
+        .url_schemes(ALLOWED_URL_SCHEMES.iter().copied().collect())
+        .url_relative(UrlRelative::PassThrough)
+        .link_rel(Some("noopener noreferrer"))
+        .clean(&unsanitized)
+        .to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!(render("Hello *world*!"), "<p>Hello <em>world</em>!</p>\n");
+        assert_eq!(
+            render("[Tobira](https://example.com)"),
+            "<p><a href=\"https://example.com\" rel=\"noopener noreferrer\">Tobira</a></p>\n",
+        );
+        assert_eq!(render("# Heading"), "Heading\n");
+        assert_eq!(render("- a\n- b"), "<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        assert_eq!(
+            render("<script>alert(1)</script>\n"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;\n",
+        );
+        assert_eq!(
+            render("a <img src=x onerror=alert(1)> b"),
+            "<p>a &lt;img src=x onerror=alert(1)&gt; b</p>\n",
+        );
+    }
+
+    #[test]
+    fn removes_dangerous_links() {
+        assert_eq!(
+            render("[click](javascript:alert(1))"),
+            "<p><a rel=\"noopener noreferrer\">click</a></p>\n",
+        );
+        assert_eq!(render("![x](data:image/png;base64,AAAA)"), "<p><img alt=\"x\"></p>\n");
+        assert_eq!(
+            render("[mail](mailto:a@example.com) [rel](/path)"),
+            "<p><a href=\"mailto:a@example.com\" rel=\"noopener noreferrer\">mail</a> \
+                <a href=\"/path\" rel=\"noopener noreferrer\">rel</a></p>\n",
+        );
+    }
+}
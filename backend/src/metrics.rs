@@ -7,14 +7,16 @@ use prometheus_client::{
     encoding::text::{encode, Encode, SendSyncEncodeMetric},
 };
 
+use crate::api::NavigationCache;
 
-struct MetricDesc {
-    name: &'static str,
-    help: &'static str,
-    unit: Option<Unit>,
+
+pub(crate) struct MetricDesc {
+    pub(crate) name: &'static str,
+    pub(crate) help: &'static str,
+    pub(crate) unit: Option<Unit>,
 }
 
-const SYNC_LAG: MetricDesc = MetricDesc {
+pub(crate) const SYNC_LAG: MetricDesc = MetricDesc {
     name: "sync_lag",
     help: "Number of seconds which the Tobira database is behind the Opencast data",
     unit: Some(Unit::Seconds),
@@ -35,32 +37,53 @@ const RESPONSE_TIMES: MetricDesc = MetricDesc {
     help: "How long Tobira took to send a response",
     unit: Some(Unit::Seconds),
 };
+const ASSET_BYTES_SENT: MetricDesc = MetricDesc {
+    name: "asset_bytes_sent",
+    help: "Number of body bytes sent in responses to asset requests",
+    unit: Some(Unit::Bytes),
+};
+const NAVIGATION_CACHE_LOOKUPS: MetricDesc = MetricDesc {
+    name: "navigation_cache_lookups",
+    help: "Number of lookups in the in-process cache for navigation realm data",
+    unit: None,
+};
 const BUILD_INFO: MetricDesc = MetricDesc {
     name: "build_info",
     help: "Information about the app",
     unit: None,
 };
-const SEARCH_INDEX_QUEUE_LEN: MetricDesc = MetricDesc {
+pub(crate) const SEARCH_INDEX_QUEUE_LEN: MetricDesc = MetricDesc {
     name: "search_index_queue_len",
     help: "Number of items queued to be reindexed for search",
     unit: None,
 };
-const NUM_USER_SESSIONS: MetricDesc = MetricDesc {
+pub(crate) const NUM_USER_SESSIONS: MetricDesc = MetricDesc {
     name: "num_user_sessions",
     help: "Number of user sessions in the DB",
     unit: None,
 };
+const UNKNOWN_LICENSES: MetricDesc = MetricDesc {
+    name: "unknown_licenses",
+    help: "Number of events and series with a license that could not be mapped to an SPDX \
+        identifier (see `sync.license_aliases`)",
+    unit: None,
+};
 const NUM_ITEMS: MetricDesc = MetricDesc {
     name: "num_items",
     help: "Number of different kinds of items in the DB",
     unit: None,
 };
 
+/// Returns the sync lag in seconds.
+pub(crate) const SYNC_LAG_QUERY: &str = "select extract(epoch from now() at time zone 'UTC' \
+    - harvested_until)::double precision from sync_status";
+
 const RESPONSE_TIMES_BASKETS: [f64; 9] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5];
 
 pub(crate) struct Metrics {
     http_requests: Family<HttpReqCategory, Counter>,
     response_times: Family<HttpReqCategory, Histogram>,
+    asset_bytes_sent: Counter,
 }
 
 impl Metrics {
@@ -70,6 +93,7 @@ impl Metrics {
             response_times: Family::new_with_constructor(|| {
                 Histogram::new(RESPONSE_TIMES_BASKETS.into_iter())
             }),
+            asset_bytes_sent: Default::default(),
         }
     }
 
@@ -81,11 +105,21 @@ impl Metrics {
         self.response_times.get_or_create(&category).observe(duration.as_secs_f64());
     }
 
-    pub(crate) async fn gather_and_encode(&self, db_pool: &Pool) -> Vec<u8> {
+    pub(crate) fn register_asset_bytes(&self, bytes: u64) {
+        self.asset_bytes_sent.inc_by(bytes);
+    }
+
+    pub(crate) async fn gather_and_encode(
+        &self,
+        db_pool: &Pool,
+        navigation_cache: &NavigationCache,
+    ) -> Vec<u8> {
         let mut reg = <Registry>::default();
 
         add_any(&mut reg, HTTP_REQUESTS, Box::new(self.http_requests.clone()));
         add_any(&mut reg, RESPONSE_TIMES, Box::new(self.response_times.clone()));
+        add_any(&mut reg, ASSET_BYTES_SENT, Box::new(self.asset_bytes_sent.clone()));
+        add_any(&mut reg, NAVIGATION_CACHE_LOOKUPS, Box::new(navigation_cache.lookups()));
 
         // Add build information
         let info = <Family<Vec<(String, String)>, Gauge>>::default();
@@ -100,9 +134,7 @@ impl Metrics {
         // TODO: Do all of that in parallel?
         if let Ok(db) = db_pool.get().await {
             // Sync lag
-            let sql = "select extract(epoch from now() at time zone 'UTC' \
-                - harvested_until)::double precision from sync_status";
-            if let Ok(row) = db.query_one(sql, &[]).await {
+            if let Ok(row) = db.query_one(SYNC_LAG_QUERY, &[]).await {
                 add_gauge(&mut reg, SYNC_LAG, row.get::<_, f64>(0) as u64);
             }
 
@@ -116,6 +148,14 @@ impl Metrics {
                 add_gauge(&mut reg, NUM_USER_SESSIONS, row.get::<_, i64>(0) as u64);
             }
 
+            // Events and series with unknown license
+            let sql = "select \
+                (select count(*) from events where license is not null and license_spdx is null) \
+                + (select count(*) from series where license is not null and license_spdx is null)";
+            if let Ok(row) = db.query_one(sql, &[]).await {
+                add_gauge(&mut reg, UNKNOWN_LICENSES, row.get::<_, i64>(0) as u64);
+            }
+
             // Number of important entities in DB
             let item_count = <Family<ItemKind, Gauge>>::default();
             let items = [
@@ -153,7 +193,11 @@ impl Metrics {
     }
 }
 
-fn add_any(reg: &mut Registry, metric: MetricDesc, value: Box<dyn SendSyncEncodeMetric>) {
+pub(crate) fn add_any(
+    reg: &mut Registry,
+    metric: MetricDesc,
+    value: Box<dyn SendSyncEncodeMetric>,
+) {
     let name = format!("tobira_{}", metric.name);
     match metric.unit {
         Some(unit) => reg.register_with_unit(name, metric.help, unit, value),
@@ -161,7 +205,7 @@ fn add_any(reg: &mut Registry, metric: MetricDesc, value: Box<dyn SendSyncEncode
     }
 }
 
-fn add_gauge(reg: &mut Registry, metric: MetricDesc, value: u64) {
+pub(crate) fn add_gauge(reg: &mut Registry, metric: MetricDesc, value: u64) {
     let gauge = <Gauge>::default();
     gauge.set(value);
     add_any(reg, metric, Box::new(gauge));
@@ -221,6 +265,8 @@ pub(crate) enum HttpReqCategory {
     Assets,
     /// `GET /~metrics`
     Metrics,
+    /// `GET /~proxy/track/*`
+    TrackProxy,
     /// Everything else that ends up serving our index HTML (the app basically).
     App,
     /// Everything else
@@ -235,6 +281,7 @@ impl Encode for HttpReqCategory {
             HttpReqCategory::Logout => b"logout",
             HttpReqCategory::Assets => b"assets",
             HttpReqCategory::Metrics => b"metrics",
+            HttpReqCategory::TrackProxy => b"track_proxy",
             HttpReqCategory::App => b"app",
             HttpReqCategory::Other => b"other",
         };
@@ -245,6 +292,25 @@ impl Encode for HttpReqCategory {
     }
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub(crate) enum CacheLookup {
+    Hit,
+    Miss,
+}
+
+impl Encode for CacheLookup {
+    fn encode(&self, writer: &mut dyn std::io::Write) -> Result<(), std::io::Error> {
+        let s = match self {
+            CacheLookup::Hit => b"hit" as &[_],
+            CacheLookup::Miss => b"miss",
+        };
+        writer.write_all(b"result=\"")?;
+        writer.write_all(s)?;
+        writer.write_all(b"\"")?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub(crate) enum ItemKind {
     Realms,
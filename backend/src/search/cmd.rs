@@ -1,10 +1,16 @@
-use std::time::Instant;
+use std::{collections::HashSet, time::Instant};
 
-use meilisearch_sdk::{indexes::Index, errors::ErrorCode};
+use meilisearch_sdk::{documents::DocumentsQuery, indexes::Index, errors::ErrorCode};
 
-use crate::{prelude::*, config::Config, db, search::meta::IndexState};
+use crate::{
+    api::Id,
+    prelude::*,
+    config::Config,
+    db::{self, DbConnection, types::Key},
+    search::meta::IndexState,
+};
 
-use super::Client;
+use super::{Client, IndexItemKind, RebuildTarget, SearchId};
 
 
 #[derive(Debug, clap::Subcommand)]
@@ -24,11 +30,43 @@ pub(crate) enum SearchIndexCommand {
     Rebuild,
 
     /// Reads queued updates from the DB and pushes them into the search index.
+    /// With `--event`, `--series` or `--realm`, the queue is ignored and only
+    /// the documents of that item are rebuilt from the DB instead.
     Update {
         /// If specified, will not stop after clearing the queue, but runs
         /// forever regularly updating.
-        #[clap(long)]
+        #[clap(long, conflicts_with_all = &["event", "series", "realm"])]
         daemon: bool,
+
+        /// Rebuilds the document of the event with the given Tobira or
+        /// Opencast ID. If the event does not exist in the DB, its document is
+        /// deleted from the index.
+        #[clap(long, conflicts_with_all = &["series", "realm"])]
+        event: Option<String>,
+
+        /// Rebuilds the documents of all events of the series with the given
+        /// Tobira or Opencast ID.
+        #[clap(long, conflicts_with = "realm")]
+        series: Option<String>,
+
+        /// Rebuilds the document of the realm with the given path (e.g.
+        /// `/lectures/math`).
+        #[clap(long)]
+        realm: Option<String>,
+
+        /// Prints the rebuilt documents as JSON.
+        #[clap(long)]
+        show: bool,
+    },
+
+    /// Deletes documents from the search index that have no corresponding
+    /// item in the DB anymore. These can remain if deleting them failed, e.g.
+    /// because the search index was not reachable. Meant to be run
+    /// periodically as a maintenance task (e.g. once a week).
+    Gc {
+        /// If specified, orphaned documents are only listed, not deleted.
+        #[clap(long)]
+        dry_run: bool,
     },
 }
 
@@ -40,8 +78,17 @@ pub(crate) async fn run(cmd: &SearchIndexCommand, config: &Config) -> Result<()>
         SearchIndexCommand::Status => status(&meili).await?,
         SearchIndexCommand::Clear { yes_absolutely_clear_index: yes }
             => clear(meili, config, *yes).await?,
-        SearchIndexCommand::Update { daemon } => update(&meili, config, *daemon).await?,
+        SearchIndexCommand::Update { daemon, event, series, realm, show } => {
+            let item = match (event, series, realm) {
+                (Some(id), _, _) => Some(Item::Event(id)),
+                (_, Some(id), _) => Some(Item::Series(id)),
+                (_, _, Some(path)) => Some(Item::Realm(path)),
+                _ => None,
+            };
+            update(&meili, config, *daemon, item, *show).await?
+        }
         SearchIndexCommand::Rebuild => rebuild(&meili, config).await?,
+        SearchIndexCommand::Gc { dry_run } => gc(&meili, config, *dry_run).await?,
     }
 
     Ok(())
@@ -74,12 +121,44 @@ async fn rebuild(meili: &Client, config: &Config) -> Result<()> {
 
 // ===== Update ================================================================================
 
-async fn update(meili: &Client, config: &Config, daemon: bool) -> Result<()> {
+/// A single item specified via `update --event/--series/--realm`.
+enum Item<'a> {
+    Event(&'a str),
+    Series(&'a str),
+    Realm(&'a str),
+}
+
+async fn update(
+    meili: &Client,
+    config: &Config,
+    daemon: bool,
+    item: Option<Item<'_>>,
+    show: bool,
+) -> Result<()> {
     let pool = db::create_pool(&config.db).await?;
     let mut db = pool.get().await?;
     meili.prepare_and_rebuild_if_necessary(&mut db).await?;
 
-    if daemon {
+    if let Some(item) = item {
+        let target = resolve_item(&db, item).await?;
+        let documents = super::writer::with_write_lock(&mut db, meili, |tx, meili| {
+            Box::pin(async move { super::rebuild_documents(tx, &meili, target).await })
+        }).await?;
+
+        if documents.is_empty() {
+            info!("{target:?} has no documents in the DB: deleted stale documents (if any) \
+                from the search index");
+        } else {
+            info!("Sent {} rebuilt document(s) of {target:?} to the search index",
+                documents.len());
+        }
+        if show {
+            for document in &documents {
+                println!("{}", serde_json::to_string_pretty(document)?);
+            }
+        }
+        Ok(())
+    } else if daemon {
         super::update_index_daemon(meili, &mut db).await.map(|_| ())
     } else {
         super::update_index(meili, &mut db).await?;
@@ -89,6 +168,132 @@ async fn update(meili: &Client, config: &Config, daemon: bool) -> Result<()> {
 }
 
 
+/// Resolves the IDs or path given on the command line to DB keys.
+async fn resolve_item(db: &DbConnection, item: Item<'_>) -> Result<RebuildTarget> {
+    /// Tobira IDs are used as is, even if no such item exists, as that is
+    /// exactly the case in which a stale document needs to be deleted.
+    /// Opencast IDs have to be looked up.
+    async fn resolve_id(
+        db: &DbConnection,
+        id: &str,
+        kind: [u8; 2],
+        table: &str,
+    ) -> Result<Key> {
+        if let Some(key) = id.parse::<Id>().ok().and_then(|id| id.key_for(kind)) {
+            return Ok(key);
+        }
+
+        let query = format!("select id from {table} where opencast_id = $1");
+        db.query_opt(&query, &[&id])
+            .await?
+            .map(|row| row.get(0))
+            .ok_or_else(|| anyhow!("'{id}' is neither a Tobira ID nor the Opencast ID of \
+                an item in table '{table}'"))
+    }
+
+    let target = match item {
+        Item::Event(id) => {
+            RebuildTarget::Event(resolve_id(db, id, Id::EVENT_KIND, "events").await?)
+        }
+        Item::Series(id) => {
+            RebuildTarget::Series(resolve_id(db, id, Id::SERIES_KIND, "series").await?)
+        }
+        Item::Realm(path) => {
+            // The root realm has the empty path.
+            let path = path.trim_end_matches('/');
+            let key = db.query_opt("select id from realms where full_path = $1", &[&path])
+                .await?
+                .map(|row| row.get(0))
+                .ok_or_else(|| anyhow!("realm '{path}' does not exist"))?;
+            RebuildTarget::Realm(key)
+        }
+    };
+
+    Ok(target)
+}
+
+
+// ===== Gc ====================================================================================
+
+/// Number of document IDs fetched from Meili per request.
+const GC_PAGE_SIZE: usize = 1000;
+
+async fn gc(meili: &Client, config: &Config, dry_run: bool) -> Result<()> {
+    let pool = db::create_pool(&config.db).await?;
+    let mut db = pool.get().await?;
+
+    // With the lock, no documents can be added to the index while we compare,
+    // and all documents in the index exist in the DB snapshot of `tx` (unless
+    // orphaned, of course).
+    super::writer::with_write_lock(&mut db, meili, |tx, meili| Box::pin(async move {
+        let indexes = [
+            (IndexItemKind::Event, &meili.event_index),
+            (IndexItemKind::Realm, &meili.realm_index),
+        ];
+        for (kind, index) in indexes {
+            let ids = fetch_document_ids(index).await?;
+            let keys = ids.iter().filter_map(|(_, key)| *key).collect::<Vec<_>>();
+            let query = format!("select id from {} where id = any($1)", kind.plural_name());
+            let existing = tx.query(&query, &[&keys])
+                .await?
+                .into_iter()
+                .map(|row| row.get::<_, Key>(0))
+                .collect::<HashSet<_>>();
+
+            // Documents with unparsable IDs cannot belong to any item either.
+            let orphans = ids.into_iter()
+                .filter(|(_, key)| key.is_none_or(|key| !existing.contains(&key)))
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+
+            if orphans.is_empty() {
+                info!("No orphaned {} in search index", kind.plural_name());
+                continue;
+            }
+            if dry_run {
+                info!("Found {} orphaned {}: {}", orphans.len(), kind.plural_name(),
+                    orphans.join(", "));
+                continue;
+            }
+            let task = index.delete_documents(&orphans).await?;
+            super::util::wait_on_task(task, &meili).await?;
+            info!("Deleted {} orphaned {} from search index", orphans.len(), kind.plural_name());
+        }
+
+        Ok(())
+    })).await
+}
+
+/// Returns the IDs of all documents in `index`, as sent by Meili and parsed.
+async fn fetch_document_ids(index: &Index) -> Result<Vec<(String, Option<Key>)>> {
+    #[derive(serde::Deserialize)]
+    struct Document {
+        id: String,
+    }
+
+    let mut out = Vec::new();
+    loop {
+        let page = DocumentsQuery::new(index)
+            .with_offset(out.len())
+            .with_limit(GC_PAGE_SIZE)
+            .with_fields(["id"])
+            .execute::<Document>()
+            .await
+            .context("failed to fetch document IDs from search index")?;
+        if page.results.is_empty() {
+            break;
+        }
+
+        out.extend(page.results.into_iter().map(|doc| {
+            let key = SearchId::try_from(doc.id.as_str()).ok().map(|id| id.0);
+            (doc.id, key)
+        }));
+    }
+
+    Ok(out)
+}
+
+
 // ===== Clear =================================================================================
 
 async fn clear(meili: Client, config: &Config, yes: bool) -> Result<()> {
@@ -44,6 +44,12 @@ pub(crate) struct Event {
     // store it explicitly to filter for this condition in Meili.
     pub(crate) listed: bool,
     pub(crate) host_realms: Vec<Realm>,
+
+    /// SPDX identifier of the license, if known. Filterable.
+    pub(crate) license: Option<String>,
+
+    /// Base URL of the Tobira instance this event was imported from.
+    pub(crate) external_origin: Option<String>,
 }
 
 impl IndexItem for Event {
@@ -59,6 +65,7 @@ impl_from_db!(
         search_events.{
             id, series, series_title, title, description, creators, thumbnail,
             duration, is_live, created, start_time, end_time, read_roles, write_roles, host_realms,
+            license_spdx, external_origin,
         },
     },
     |row| {
@@ -80,6 +87,8 @@ impl_from_db!(
             write_roles: util::encode_acl(&row.write_roles::<Vec<String>>()),
             listed: !host_realms.is_empty(),
             host_realms,
+            license: row.license_spdx(),
+            external_origin: row.external_origin(),
         }
     }
 );
@@ -95,10 +104,19 @@ impl Event {
             .context("failed to load events from DB")
     }
 
-    pub(crate) async fn load_all(db: &impl GenericClient) -> Result<Vec<Self>> {
+    /// Loads up to `limit` events ordered by ID, starting after `after`. Used
+    /// to load all events in batches.
+    pub(crate) async fn load_batch(
+        db: &impl GenericClient,
+        after: Option<Key>,
+        limit: u32,
+    ) -> Result<Vec<Self>> {
         let selection = Self::select();
-        let query = format!("select {selection} from search_events where state <> 'waiting'");
-        let rows = db.query_raw(&query, dbargs![]);
+        let query = format!("select {selection} from search_events \
+            where state <> 'waiting' and ($1::bigint is null or id > $1) \
+            order by id \
+            limit {limit}");
+        let rows = db.query_raw(&query, dbargs![&after]);
         collect_rows_mapped(rows, |row| Self::from_row_start(&row))
             .await
             .context("failed to load events from DB")
@@ -110,6 +128,6 @@ pub(super) async fn prepare_index(index: &Index) -> Result<()> {
         index,
         "event",
         &["title", "creators", "description", "series_title"],
-        &["listed", "read_roles", "write_roles"],
+        &["listed", "read_roles", "write_roles", "license"],
     ).await
 }
@@ -1,4 +1,4 @@
-use std::{time::{Duration, Instant}, fmt};
+use std::{time::{Duration, Instant}, fmt, future::Future};
 
 use deadpool_postgres::ClientWrapper;
 use meilisearch_sdk::{
@@ -30,13 +30,13 @@ pub(crate) use self::{
     event::Event,
     meta::IndexState,
     realm::Realm,
-    update::{update_index, update_index_daemon},
+    update::{RebuildTarget, rebuild_documents, update_index, update_index_daemon},
 };
 
 
 /// The version of search index schema. Increase whenever there is a change that
 /// requires an index rebuild.
-const VERSION: u32 = 1;
+const VERSION: u32 = 3;
 
 
 // ===== Configuration ============================================================================
@@ -321,6 +321,10 @@ pub(crate) async fn clear(meili: &MeiliWriter<'_>) -> Result<()> {
     Ok(())
 }
 
+/// Number of items loaded from the DB and sent to Meili at once when indexing
+/// all data.
+const BATCH_SIZE: u32 = 5000;
+
 /// Loads all data from the DB and adding it to the index. Old entries that are
 /// in the index, but not in the DB anymore, are not removed. Thus, to cleanly
 /// rebuild, clear all indexes before.
@@ -328,37 +332,93 @@ pub(crate) async fn index_all_data(
     meili: &MeiliWriter<'_>,
     tx: &deadpool_postgres::Transaction<'_>,
 ) -> Result<Vec<TaskInfo>> {
-    let mut tasks = Vec::new();
-
-    macro_rules! rebuild_index {
-        ($plural:literal, $ty:ty, $index:expr) => {
-            let items = <$ty>::load_all(&**tx).await?;
-            debug!("Loaded {} {} from DB", items.len(), $plural);
-
-            if items.is_empty() {
-                debug!("No {} in the DB -> Not sending anything to Meili", $plural);
-            } else {
-                let task = $index.add_documents(&items, None).await?;
-                debug!("Sent {} {} to Meili for indexing", items.len(), $plural);
-                tasks.push(task);
-            }
-        }
-    }
-
     let before = Instant::now();
-    rebuild_index!("events", Event, meili.event_index);
-    rebuild_index!("realms", Realm, meili.realm_index);
+    let mut tasks = index_in_batches(&meili.event_index, |after| {
+        Event::load_batch(&**tx, after, BATCH_SIZE)
+    }).await?;
+    tasks.extend(index_in_batches(&meili.realm_index, |after| {
+        Realm::load_batch(&**tx, after, BATCH_SIZE)
+    }).await?);
     info!("Sent all data to Meili in {:.1?}", before.elapsed());
 
     // We can clear the search index queue as we just sent all items to Meili.
     // This is all in one DB transaction and we submitted all data to Meili.
     tx.execute("delete from search_index_queue", &[]).await
         .context("failed to clear search index queue")?;
+    tx.execute("update search_index_status set last_indexed_at = now()", &[]).await
+        .context("failed to update search index status")?;
     info!("Cleared search index queue");
 
     Ok(tasks)
 }
 
+/// Loads all items of one kind from the DB in batches (via `load`, which gets
+/// the ID of the last item of the previous batch) and sends them to Meili.
+/// Loading the next batch and sending the current one happen concurrently.
+async fn index_in_batches<T, F, Fut>(index: &Index, mut load: F) -> Result<Vec<TaskInfo>>
+where
+    T: IndexItem,
+    F: FnMut(Option<Key>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    async fn timed<O>(f: impl Future<Output = O>) -> (O, Duration) {
+        let before = Instant::now();
+        let out = f.await;
+        (out, before.elapsed())
+    }
+
+    let plural = T::KIND.plural_name();
+    let before = Instant::now();
+    let mut tasks = Vec::new();
+    let mut count = 0;
+
+    let (batch, mut db_time) = timed(load(None)).await;
+    let mut batch = batch?;
+    let mut meili_time = Duration::ZERO;
+    while !batch.is_empty() {
+        // A batch smaller than the limit is the last one.
+        let next = if batch.len() < BATCH_SIZE as usize {
+            None
+        } else {
+            batch.last().map(|item| item.id().0)
+        };
+        let load_next = async {
+            match next {
+                Some(after) => load(Some(after)).await,
+                None => Ok(vec![]),
+            }
+        };
+        let send = async {
+            index.add_documents(&batch, None).await.map_err(anyhow::Error::from)
+        };
+
+        let ((next_batch, load_time), (task, send_time)) = tokio::join!(
+            timed(load_next),
+            timed(send),
+        );
+        db_time += load_time;
+        meili_time += send_time;
+        tasks.push(task?);
+        count += batch.len();
+        trace!("Sent {} {plural} to Meili ({count} so far)", batch.len());
+        batch = next_batch?;
+    }
+
+    if count == 0 {
+        debug!("No {plural} in the DB -> Not sending anything to Meili");
+    } else {
+        info!(
+            "Sent {count} {plural} to Meili in {:.1?} (DB: {:.1?}, Meili: {:.1?}, \
+                running concurrently)",
+            before.elapsed(),
+            db_time,
+            meili_time,
+        );
+    }
+
+    Ok(tasks)
+}
+
 /// Clears and then rebuilds all indexes. It's `clear` + `prepare_indexes` +
 /// `index_all_data`.
 pub(crate) async fn rebuild(
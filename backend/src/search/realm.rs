@@ -54,10 +54,19 @@ impl Realm {
         collect_rows_mapped(rows, |row| Self::from_row_start(&row)).await.map_err(Into::into)
     }
 
-    pub(crate) async fn load_all(db: &impl GenericClient) -> Result<Vec<Self>> {
+    /// Loads up to `limit` realms ordered by ID, starting after `after`. Used
+    /// to load all realms in batches.
+    pub(crate) async fn load_batch(
+        db: &impl GenericClient,
+        after: Option<Key>,
+        limit: u32,
+    ) -> Result<Vec<Self>> {
         let selection = Self::select();
-        let query = format!("select {selection} from search_realms where name is not null");
-        let rows = db.query_raw(&query, dbargs![]);
+        let query = format!("select {selection} from search_realms \
+            where name is not null and ($1::bigint is null or id > $1) \
+            order by id \
+            limit {limit}");
+        let rows = db.query_raw(&query, dbargs![&after]);
         collect_rows_mapped(rows, |row| Self::from_row_start(&row)).await.map_err(Into::into)
     }
 }
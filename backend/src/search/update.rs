@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    db::{DbConnection, types::Key, util::select},
+    db::{DbConnection, types::Key, util::{collect_rows_mapped, select}},
     prelude::*,
     util::Never,
 };
@@ -88,6 +88,9 @@ pub(crate) async fn update_index(meili: &Client, db: &mut DbConnection) -> Resul
                     but deleted {affected}");
             }
 
+            tx.execute("update search_index_status set last_indexed_at = now()", &[]).await
+                .context("failed to update search index status")?;
+
             Ok(count < CHUNK_SIZE as usize)
         })).await?;
 
@@ -100,6 +103,53 @@ pub(crate) async fn update_index(meili: &Client, db: &mut DbConnection) -> Resul
     Ok(())
 }
 
+/// Something whose search index documents can be rebuilt individually, see
+/// [`rebuild_documents`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RebuildTarget {
+    Event(Key),
+    /// Series have no documents of their own, but they are part of the
+    /// documents of all their events.
+    Series(Key),
+    Realm(Key),
+}
+
+/// Rebuilds the search index documents of `target` from the current data in
+/// the DB and sends them to Meili. Documents of items that do not exist in
+/// the DB (anymore) are deleted from the index. Returns the documents that
+/// were sent.
+pub(crate) async fn rebuild_documents(
+    tx: &deadpool_postgres::Transaction<'_>,
+    meili: &MeiliWriter<'_>,
+    target: RebuildTarget,
+) -> Result<Vec<serde_json::Value>> {
+    let (realm_ids, event_ids) = match target {
+        RebuildTarget::Event(key) => (vec![], vec![key]),
+        RebuildTarget::Realm(key) => (vec![key], vec![]),
+        RebuildTarget::Series(key) => {
+            let rows = tx.query_raw("select id from events where series = $1", dbargs![&key]);
+            let event_ids = collect_rows_mapped(rows, |row| row.get::<_, Key>(0))
+                .await
+                .context("failed to load events of series")?;
+            (vec![], event_ids)
+        }
+    };
+
+    let realms = Realm::load_by_ids(&**tx, &realm_ids).await?;
+    let events = Event::load_by_ids(&**tx, &event_ids).await?;
+    let documents = realms.iter()
+        .map(serde_json::to_value)
+        .chain(events.iter().map(serde_json::to_value))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    meili.update(&realm_ids, || async { Ok(realms) }).await
+        .context("failed to send realms to search index")?;
+    meili.update(&event_ids, || async { Ok(events) }).await
+        .context("failed to send events to search index")?;
+
+    Ok(documents)
+}
+
 impl MeiliWriter<'_> {
     /// Loads items from the DB with the given loader and then adds them to
     /// Meili. All items that were not returned by `loader` but are present in
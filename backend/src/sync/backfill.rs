@@ -0,0 +1,239 @@
+//! The `sync backfill` subcommand: re-fetches media information of events for
+//! which it is missing, e.g. because they were synced by an old Tobira.
+
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    config::Config,
+    db::{DbConnection, types::EventTrack},
+    prelude::*,
+};
+use super::{OcClient, client::MediaInfo};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ArgEnum)]
+pub(crate) enum BackfillField {
+    /// `events.duration`
+    Duration,
+    /// The resolution of video tracks in `events.tracks`.
+    Resolution,
+}
+
+impl BackfillField {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Duration => "duration",
+            Self::Resolution => "resolution",
+        }
+    }
+
+    /// SQL condition selecting events for which this field is missing.
+    fn missing_condition(self) -> &'static str {
+        match self {
+            Self::Duration => "duration is null",
+            Self::Resolution => "exists (select from unnest(tracks) as t \
+                where t.resolution is null \
+                    and (t.mimetype is null or t.mimetype like 'video/%'))",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    updated: u64,
+    unchanged: u64,
+    not_found: u64,
+}
+
+
+/// Implementation of subcommand with same name, see that for docs.
+pub(crate) async fn run(
+    db: DbConnection,
+    config: &Config,
+    fields: &[BackfillField],
+    batch_size: u32,
+    rate: u32,
+    restart: bool,
+) -> Result<()> {
+    if rate == 0 || batch_size == 0 {
+        bail!("'--rate' and '--batch-size' must be greater than 0");
+    }
+    let mut fields = fields.to_vec();
+    fields.sort();
+    fields.dedup();
+
+    // Progress is stored per set of fields, as other sets select other events.
+    let job = fields.iter().map(|f| f.name()).collect::<Vec<_>>().join(",");
+    if restart {
+        db.execute("delete from sync_backfill_progress where job = $1", &[&job]).await?;
+    }
+    let mut last_id = db
+        .query_opt("select last_event_id from sync_backfill_progress where job = $1", &[&job])
+        .await?
+        .map_or(0, |row| row.get::<_, i64>(0));
+    if last_id > 0 {
+        info!("Resuming backfill of '{job}' after event {last_id}");
+    }
+
+    let condition = fields.iter()
+        .map(|f| format!("({})", f.missing_condition()))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    let query = format!(
+        "select id, opencast_id, duration, tracks from events \
+            where id > $1 and ({condition}) \
+            order by id \
+            limit $2",
+    );
+
+    let client = OcClient::new(config);
+    let mut ticks = tokio::time::interval(Duration::from_secs(1) / rate);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut stats = Stats::default();
+    loop {
+        let rows = db.query(&query, &[&last_id, &i64::from(batch_size)]).await?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in rows {
+            let id: i64 = row.get("id");
+            last_id = id;
+            let opencast_id: String = row.get("opencast_id");
+            let mut duration: Option<i32> = row.get("duration");
+            let mut tracks: Option<Vec<EventTrack>> = row.get("tracks");
+
+            ticks.tick().await;
+            let media = client.get_media(&opencast_id).await
+                .with_context(|| format!("failed to fetch media of event '{opencast_id}'"))?;
+            let Some(media) = media else {
+                debug!("Event '{opencast_id}' does not exist in Opencast anymore, skipping");
+                stats.not_found += 1;
+                continue;
+            };
+
+            let changed = apply(
+                &fields,
+                &mut duration,
+                tracks.as_deref_mut().unwrap_or_default(),
+                &media,
+            );
+            if changed {
+                db.execute(
+                    "update events set duration = $2, tracks = $3 where id = $1",
+                    &[&id, &duration, &tracks],
+                ).await?;
+                stats.updated += 1;
+            } else {
+                stats.unchanged += 1;
+            }
+        }
+
+        db.execute(
+            "insert into sync_backfill_progress (job, last_event_id) values ($1, $2) \
+                on conflict (job) do update \
+                set last_event_id = excluded.last_event_id, updated_at = now()",
+            &[&job, &last_id],
+        ).await?;
+        info!(
+            "Backfill progress: {} updated, {} without new information, {} not in Opencast \
+                (last event ID: {})",
+            stats.updated,
+            stats.unchanged,
+            stats.not_found,
+            last_id,
+        );
+    }
+
+    info!("Finished backfill of '{job}': {stats:?}. Events added later will be processed \
+        by the next run, use '--restart' to process all events again.");
+    Ok(())
+}
+
+/// Fills the missing `fields` from `media` and returns whether anything was
+/// changed. Existing values are never overwritten.
+fn apply(
+    fields: &[BackfillField],
+    duration: &mut Option<i32>,
+    tracks: &mut [EventTrack],
+    media: &[MediaInfo],
+) -> bool {
+    let mut changed = false;
+
+    if fields.contains(&BackfillField::Duration) && duration.is_none() {
+        *duration = media.iter()
+            .filter_map(|m| m.duration)
+            .filter(|&d| d > 0)
+            .max()
+            .and_then(|d| i32::try_from(d).ok());
+        changed |= duration.is_some();
+    }
+
+    if fields.contains(&BackfillField::Resolution) {
+        for track in tracks.iter_mut().filter(|t| t.resolution.is_none()) {
+            track.resolution = media.iter()
+                .find(|m| m.url == track.uri)
+                .and_then(|m| Some([m.width?, m.height?]));
+            changed |= track.resolution.is_some();
+        }
+    }
+
+    changed
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::types::EventTrack;
+    use super::{apply, BackfillField, MediaInfo};
+
+    fn track(uri: &str, resolution: Option<[i32; 2]>) -> EventTrack {
+        EventTrack {
+            uri: uri.into(),
+            flavor: "presenter/delivery".into(),
+            mimetype: Some("video/mp4".into()),
+            resolution,
+            is_master: None,
+        }
+    }
+
+    fn media(url: &str, duration: i64, size: Option<(i32, i32)>) -> MediaInfo {
+        MediaInfo {
+            url: url.into(),
+            duration: Some(duration),
+            width: size.map(|s| s.0),
+            height: size.map(|s| s.1),
+        }
+    }
+
+    #[test]
+    fn fills_only_missing_fields() {
+        let media = [
+            media("https://oc/a.mp4", 60_000, Some((1920, 1080))),
+            media("https://oc/b.mp4", 61_000, Some((1280, 720))),
+            media("https://oc/c.mp3", 61_500, None),
+        ];
+        let both = [BackfillField::Duration, BackfillField::Resolution];
+
+        let mut duration = None;
+        let mut tracks = [
+            track("https://oc/a.mp4", None),
+            track("https://oc/b.mp4", Some([640, 360])),
+            track("https://oc/unknown.mp4", None),
+        ];
+        assert!(apply(&both, &mut duration, &mut tracks, &media));
+        assert_eq!(duration, Some(61_500));
+        assert_eq!(tracks[0].resolution, Some([1920, 1080]));
+        assert_eq!(tracks[1].resolution, Some([640, 360]));
+        assert_eq!(tracks[2].resolution, None);
+
+        // Nothing new to add
+        assert!(!apply(&both, &mut duration, &mut tracks, &media));
+
+        let mut duration = None;
+        let mut tracks = [track("https://oc/a.mp4", None)];
+        assert!(apply(&[BackfillField::Resolution], &mut duration, &mut tracks, &media));
+        assert_eq!(duration, None);
+    }
+}
@@ -2,7 +2,7 @@ use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc, TimeZone};
 use hyper::{
-    Body, Request, Response, StatusCode,
+    Body, Method, Request, Response, StatusCode,
     client::{Client, HttpConnector},
     http::uri::{Authority, Scheme, Uri},
 };
@@ -19,6 +19,17 @@ use crate::{
 use super::VersionResponse;
 
 
+/// A media track of a publication, as returned by the external API. Only the
+/// fields we need are deserialized.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct MediaInfo {
+    pub(crate) url: String,
+    /// In milliseconds.
+    pub(crate) duration: Option<i64>,
+    pub(crate) width: Option<i32>,
+    pub(crate) height: Option<i32>,
+}
+
 /// Used to send request to the harvesting API.
 pub(crate) struct OcClient {
     http_client: Client<HttpsConnector<HttpConnector>, Body>,
@@ -30,6 +41,7 @@ pub(crate) struct OcClient {
 impl OcClient {
     const HARVEST_PATH: &'static str = "/tobira/harvest";
     const VERSION_PATH: &'static str = "/tobira/version";
+    const EVENTS_API_PATH: &'static str = "/api/events";
 
     pub(crate) fn new(config: &Config) -> Self {
         // Prepare HTTP client
@@ -75,6 +87,144 @@ impl OcClient {
             .context("test harvest request failed")
     }
 
+    /// Fetches the processing state (e.g. `RUNNING` or `FAILED`) of the event
+    /// with the given Opencast ID via the external API. Returns `None` if
+    /// Opencast does not know that event (yet).
+    pub(crate) async fn get_processing_state(&self, opencast_id: &str) -> Result<Option<String>> {
+        #[derive(serde::Deserialize)]
+        struct EventResponse {
+            processing_state: String,
+        }
+
+        let pq = format!(
+            "{}/{}",
+            Self::EVENTS_API_PATH,
+            percent_encoding::utf8_percent_encode(opencast_id, percent_encoding::NON_ALPHANUMERIC),
+        );
+        let (uri, req) = self.build_req(&pq);
+
+        trace!("Sending request to '{uri}'");
+        let response = self.http_client.request(req)
+            .await
+            .with_context(|| format!("HTTP request failed (to '{uri}')"))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let (out, _) = Self::deserialize_response::<EventResponse>(response, &uri).await?;
+        Ok(Some(out.processing_state))
+    }
+
+    /// Fetches information about all media tracks of all publications of the
+    /// event with the given Opencast ID via the external API. Returns `None`
+    /// if Opencast does not know that event (anymore).
+    pub(crate) async fn get_media(&self, opencast_id: &str) -> Result<Option<Vec<MediaInfo>>> {
+        #[derive(serde::Deserialize)]
+        struct PublicationResponse {
+            #[serde(default)]
+            media: Vec<MediaInfo>,
+        }
+
+        let pq = format!(
+            "{}/{}/publications",
+            Self::EVENTS_API_PATH,
+            percent_encoding::utf8_percent_encode(opencast_id, percent_encoding::NON_ALPHANUMERIC),
+        );
+        let (uri, req) = self.build_req(&pq);
+
+        trace!("Sending request to '{uri}'");
+        let response = self.http_client.request(req)
+            .await
+            .with_context(|| format!("HTTP request failed (to '{uri}')"))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let (out, _) = Self::deserialize_response::<Vec<PublicationResponse>>(response, &uri)
+            .await?;
+        Ok(Some(out.into_iter().flat_map(|p| p.media).collect()))
+    }
+
+    /// Replaces the ACL of the event with the given Opencast ID via the
+    /// external API. The given roles are allowed to read and write the
+    /// event, respectively.
+    pub(crate) async fn update_acl(
+        &self,
+        opencast_id: &str,
+        read_roles: &[String],
+        write_roles: &[String],
+    ) -> Result<()> {
+        let acl = read_roles.iter().map(|role| (role, "read"))
+            .chain(write_roles.iter().map(|role| (role, "write")))
+            .map(|(role, action)| serde_json::json!({
+                "allow": true,
+                "action": action,
+                "role": role,
+            }))
+            .collect::<Vec<_>>();
+        let body = format!(
+            "acl={}",
+            percent_encoding::utf8_percent_encode(
+                &serde_json::to_string(&acl)?,
+                percent_encoding::NON_ALPHANUMERIC,
+            ),
+        );
+
+        let pq = format!(
+            "{}/{}/acl",
+            Self::EVENTS_API_PATH,
+            percent_encoding::utf8_percent_encode(opencast_id, percent_encoding::NON_ALPHANUMERIC),
+        );
+        let (uri, mut req) = self.build_req(&pq);
+        *req.method_mut() = Method::PUT;
+        req.headers_mut().insert(
+            hyper::header::CONTENT_TYPE,
+            hyper::header::HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        *req.body_mut() = Body::from(body);
+
+        trace!("Sending request to '{uri}'");
+        let response = self.http_client.request(req)
+            .await
+            .with_context(|| format!("HTTP request failed (to '{uri}')"))?;
+
+        if !response.status().is_success() {
+            bail!("API returned unexpected HTTP code {} (for '{}')", response.status(), uri);
+        }
+
+        Ok(())
+    }
+
+    /// Sends a `GET` or `HEAD` request with the given headers to an arbitrary
+    /// URI, authenticated as the sync user, and returns the response without
+    /// reading its body. Callers have to make sure `uri` points to a trusted
+    /// Opencast host, as the credentials are sent along.
+    pub(crate) async fn request_authenticated(
+        &self,
+        method: Method,
+        uri: Uri,
+        headers: hyper::HeaderMap,
+    ) -> Result<Response<Body>> {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(&uri)
+            .body(Body::empty())
+            .expect("bug: failed to build request");
+        *req.headers_mut() = headers;
+        req.headers_mut().insert(
+            hyper::header::AUTHORIZATION,
+            self.auth_header.expose_secret().parse()
+                .expect("bug: invalid authorization header"),
+        );
+
+        trace!("Sending request to '{uri}'");
+        self.http_client.request(req)
+            .await
+            .with_context(|| format!("HTTP request failed (to '{uri}')"))
+    }
+
     /// Sends a request to the harvesting API, checks and deserializes the
     /// response.
     pub(super) async fn send_harvest(
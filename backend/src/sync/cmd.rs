@@ -4,6 +4,7 @@ use crate::{
     config::Config,
     prelude::*, db::DbConnection,
 };
+use super::backfill::BackfillField;
 
 
 #[derive(Debug, clap::Args)]
@@ -31,6 +32,37 @@ pub(crate) enum SyncCommand {
         #[clap(long)]
         yes_absolutely_reset: bool,
     },
+
+    /// Re-fetches media information via the external API for events that
+    /// are missing some fields, e.g. because they were synced by a very old
+    /// Tobira version. Changed events are also updated in the search index.
+    /// Progress is saved after each batch, so this can be interrupted and
+    /// started again. Events that do not exist in Opencast anymore are
+    /// skipped.
+    Backfill {
+        /// Comma-separated list of fields to backfill.
+        #[clap(long, arg_enum, value_delimiter = ',', required = true)]
+        fields: Vec<BackfillField>,
+
+        /// Number of events loaded from the DB at once. Progress is saved
+        /// after each batch.
+        #[clap(long, default_value = "50")]
+        batch_size: u32,
+
+        /// Maximum number of requests per second sent to Opencast.
+        #[clap(long, default_value = "5")]
+        rate: u32,
+
+        /// If specified, saved progress is ignored and all events are
+        /// processed again.
+        #[clap(long)]
+        restart: bool,
+    },
+
+    /// Imports all public events and their series from the Tobira instance
+    /// configured as `federation.source` once. The worker does this
+    /// regularly.
+    Federate,
 }
 
 /// Entry point for `search-index` commands.
@@ -38,8 +70,8 @@ pub(crate) async fn run(args: &Args, config: &Config) -> Result<()> {
     info!("Starting Tobira <-> Opencast synchronization ...");
     trace!("Configuration: {:#?}", config);
 
-    let db = crate::connect_and_migrate_db(config).await?;
-    let conn = db.get().await?;
+    let db = crate::connect_and_migrate_db(config, None).await?;
+    let mut conn = db.get().await?;
 
     match args.cmd {
         SyncCommand::Run { daemon } => {
@@ -49,6 +81,14 @@ pub(crate) async fn run(args: &Args, config: &Config) -> Result<()> {
             Ok(())
         }
         SyncCommand::Reset { yes_absolutely_reset: yes } => reset(conn, yes).await,
+        SyncCommand::Backfill { ref fields, batch_size, rate, restart } => {
+            super::backfill::run(conn, config, fields, batch_size, rate, restart).await
+        }
+        SyncCommand::Federate => {
+            let source = config.federation.source.as_ref()
+                .ok_or_else(|| anyhow!("`federation.source` is not configured"))?;
+            super::federation::run(&mut conn, source, config).await
+        }
     }
 }
 
@@ -0,0 +1,471 @@
+//! Importing public events and series from another Tobira instance.
+//!
+//! All public events are fetched via the `publicEvents` API of the other
+//! instance and stored like events synced from Opencast, but with
+//! `external_origin` set. Everything imported before that is not part of the
+//! response anymore is deleted. Imported items are never modified otherwise.
+
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+
+use chrono::{DateTime, Utc};
+use hyper::Uri;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    auth::ROLE_ANONYMOUS,
+    config::Config,
+    db::{series_stats, types::{EventCaption, EventState, EventTrack, SeriesState}, DbConnection},
+    prelude::*,
+    util::HttpHost,
+};
+use super::persons;
+
+
+/// Number of events requested from the other instance at once.
+const PAGE_SIZE: i32 = 200;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, confique::Config)]
+pub(crate) struct FederationConfig {
+    /// Base URL of another Tobira instance whose public events (i.e. events
+    /// readable by `ROLE_ANONYMOUS`) and their series are imported by the
+    /// worker. Imported items can be added to realms and are found by the
+    /// search like all others, but they are read-only and their videos are
+    /// streamed from wherever the other instance streams them. If not set,
+    /// nothing is imported.
+    ///
+    /// Example: "https://tobira.other-uni.edu".
+    pub(crate) source: Option<HttpHost>,
+
+    /// Token that is sent as `Authorization: Bearer <token>` header with all
+    /// requests to `source`. Tobira itself does not check this header, so
+    /// this is only useful if a reverse proxy in front of the other instance
+    /// restricts access to its API.
+    pub(crate) token: Option<Secret<String>>,
+
+    /// How often to import events from `source`. Items deleted or made
+    /// private in the other instance are removed on the next import.
+    #[config(default = "1h", deserialize_with = crate::config::deserialize_duration)]
+    pub(crate) poll_period: Duration,
+}
+
+
+/// Long running task that regularly imports events from `federation.source`.
+/// Never resolves if that is not configured.
+pub(crate) async fn run_daemon(db: &mut DbConnection, config: &Config) -> ! {
+    let Some(source) = &config.federation.source else {
+        // Returning would stop the worker.
+        loop {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    loop {
+        if let Err(e) = run(db, source, config).await {
+            error!("Error importing events from '{source}': {e:?}");
+        }
+
+        tokio::time::sleep(config.federation.poll_period).await;
+    }
+}
+
+/// Imports all public events from `source` once.
+pub(crate) async fn run(db: &mut DbConnection, source: &HttpHost, config: &Config) -> Result<()> {
+    let before = Instant::now();
+    let origin = source.to_string();
+    let events = fetch_all(source, config.federation.token.as_ref()).await?;
+    let fetch_time = before.elapsed();
+
+    let tx = db.transaction().await?;
+    let mut series_keys = HashMap::new();
+    let mut touched_series = HashSet::new();
+    let mut upserted = 0;
+    let mut skipped = 0;
+    for event in &events {
+        let series = match &event.series {
+            None => None,
+            Some(series) => match series_keys.get(&series.opencast_id) {
+                Some(key) => *key,
+                None => {
+                    let key = upsert_series(&tx, series, &origin).await?;
+                    series_keys.insert(series.opencast_id.clone(), key);
+                    key
+                }
+            },
+        };
+
+        let Some(data) = &event.synced_data else {
+            continue;
+        };
+        match upsert_event(&tx, event, data, series, source, &origin).await? {
+            Upsert::Changed(key) => {
+                persons::link(&*tx, key, &event.creators, config.sync.creator_normalization())
+                    .await?;
+                touched_series.extend(series);
+                upserted += 1;
+            }
+            Upsert::Unchanged => {}
+            Upsert::Conflict => {
+                warn!("Event '{}' from '{origin}' also exists in Opencast: not importing it",
+                    event.opencast_id);
+                skipped += 1;
+            }
+        }
+    }
+
+    // Everything that is not public in the other instance anymore is removed.
+    let ids = events.iter().map(|e| &e.opencast_id).collect::<Vec<_>>();
+    let removed_events = tx
+        .query(
+            "delete from events \
+                where external_origin = $1 and not (opencast_id = any($2)) \
+                returning series",
+            &[&origin, &ids],
+        )
+        .await?;
+    touched_series.extend(removed_events.iter().filter_map(|row| row.get::<_, Option<i64>>(0)));
+    let series_ids = series_keys.keys().collect::<Vec<_>>();
+    let removed_series = tx
+        .execute(
+            "delete from series where external_origin = $1 and not (opencast_id = any($2))",
+            &[&origin, &series_ids],
+        )
+        .await?;
+
+    if !touched_series.is_empty() {
+        let ids = touched_series.into_iter().collect::<Vec<_>>();
+        series_stats::update(&*tx, Some(&ids)).await
+            .context("failed to update event statistics of series")?;
+    }
+    tx.commit().await?;
+
+    info!(
+        "Imported {} public events from '{}' (fetched in {:.2?}): upserted {}, removed {} \
+            events and {} series, skipped {} events that exist in Opencast",
+        events.len(),
+        origin,
+        fetch_time,
+        upserted,
+        removed_events.len(),
+        removed_series,
+        skipped,
+    );
+    Ok(())
+}
+
+
+// ===== Storing ==================================================================================
+
+enum Upsert {
+    Changed(i64),
+    Unchanged,
+    /// An item with that Opencast ID exists, but was not imported from the
+    /// same origin.
+    Conflict,
+}
+
+async fn upsert_series(
+    tx: &deadpool_postgres::Transaction<'_>,
+    series: &RemoteSeries,
+    origin: &str,
+) -> Result<Option<i64>> {
+    let description = series.synced_data.as_ref().and_then(|d| d.description.as_ref());
+    tx.execute(
+        "insert into series \
+            (opencast_id, state, title, description, read_roles, write_roles, updated, \
+                external_origin) \
+            values ($1, $2, $3, $4, $5, '{}', now(), $6) \
+            on conflict (opencast_id) do update set \
+                title = excluded.title, \
+                description = excluded.description, \
+                updated = excluded.updated \
+            where series.external_origin = excluded.external_origin \
+                and (series.title, series.description) \
+                    is distinct from (excluded.title, excluded.description)",
+        &[
+            &series.opencast_id,
+            &SeriesState::Ready,
+            &series.title,
+            &description,
+            &vec![ROLE_ANONYMOUS],
+            &origin,
+        ],
+    ).await?;
+
+    let key = tx
+        .query_opt(
+            "select id from series where opencast_id = $1 and external_origin = $2",
+            &[&series.opencast_id, &origin],
+        )
+        .await?
+        .map(|row| row.get(0));
+    if key.is_none() {
+        warn!("Series '{}' from '{origin}' also exists in Opencast: not importing it",
+            series.opencast_id);
+    }
+    Ok(key)
+}
+
+async fn upsert_event(
+    tx: &deadpool_postgres::Transaction<'_>,
+    event: &RemoteEvent,
+    data: &RemoteSyncedData,
+    series: Option<i64>,
+    source: &HttpHost,
+    origin: &str,
+) -> Result<Upsert> {
+    let tracks = data.tracks.iter()
+        .map(|t| EventTrack {
+            uri: absolute_url(source, &t.uri),
+            flavor: t.flavor.clone(),
+            mimetype: t.mimetype.clone(),
+            resolution: t.resolution.as_deref().and_then(|r| r.try_into().ok()),
+            is_master: t.is_master,
+        })
+        .collect::<Vec<_>>();
+    let captions = data.captions.iter()
+        .map(|c| EventCaption { uri: absolute_url(source, &c.uri), lang: c.lang.clone() })
+        .collect::<Vec<_>>();
+    let thumbnail = data.thumbnail.as_ref().map(|uri| absolute_url(source, uri));
+    let part_of = event.series.as_ref().map(|s| &s.opencast_id);
+
+    // Only changed events are updated, as each update causes the event to be
+    // reindexed.
+    let row = tx
+        .query_opt(
+            "insert into events \
+                (opencast_id, state, series, part_of, is_live, title, description, \
+                    duration, created, start_time, end_time, updated, creators, thumbnail, \
+                    metadata, read_roles, write_roles, tracks, captions, external_origin) \
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, \
+                    '{}', $15, '{}', $16, $17, $18) \
+                on conflict (opencast_id) do update set \
+                    series = excluded.series, \
+                    part_of = excluded.part_of, \
+                    is_live = excluded.is_live, \
+                    title = excluded.title, \
+                    description = excluded.description, \
+                    duration = excluded.duration, \
+                    created = excluded.created, \
+                    start_time = excluded.start_time, \
+                    end_time = excluded.end_time, \
+                    updated = excluded.updated, \
+                    creators = excluded.creators, \
+                    thumbnail = excluded.thumbnail, \
+                    tracks = excluded.tracks, \
+                    captions = excluded.captions \
+                where events.external_origin = excluded.external_origin \
+                    and (events.updated, events.series) \
+                        is distinct from (excluded.updated, excluded.series) \
+                returning id",
+            &[
+                &event.opencast_id,
+                &EventState::Ready,
+                &series,
+                &part_of,
+                &event.is_live,
+                &event.title,
+                &event.description,
+                &data.duration,
+                &event.created,
+                &data.start_time,
+                &data.end_time,
+                &data.updated,
+                &event.creators,
+                &thumbnail,
+                &vec![ROLE_ANONYMOUS],
+                &tracks,
+                &captions,
+                &origin,
+            ],
+        )
+        .await?;
+
+    if let Some(row) = row {
+        return Ok(Upsert::Changed(row.get(0)));
+    }
+    let existing_origin = tx
+        .query_one(
+            "select external_origin from events where opencast_id = $1",
+            &[&event.opencast_id],
+        )
+        .await?
+        .get::<_, Option<String>>(0);
+    match existing_origin.as_deref() == Some(origin) {
+        true => Ok(Upsert::Unchanged),
+        false => Ok(Upsert::Conflict),
+    }
+}
+
+/// URLs of the other instance might be relative to it, e.g. for proxied
+/// tracks.
+fn absolute_url(source: &HttpHost, url: &str) -> String {
+    match url.starts_with('/') && !url.starts_with("//") {
+        true => format!("{source}{url}"),
+        false => url.to_owned(),
+    }
+}
+
+
+// ===== Fetching =================================================================================
+
+const QUERY: &str = "
+    query FederationImport($after: String, $first: Int!) {
+        publicEvents(after: $after, first: $first) {
+            opencastId title description created creators isLive
+            series { opencastId title syncedData { description } }
+            syncedData {
+                updated startTime endTime duration thumbnail
+                tracks { uri flavor mimetype resolution isMaster }
+                captions { uri lang }
+            }
+        }
+    }
+";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteEvent {
+    opencast_id: String,
+    title: String,
+    description: Option<String>,
+    created: DateTime<Utc>,
+    creators: Vec<String>,
+    is_live: bool,
+    series: Option<RemoteSeries>,
+    synced_data: Option<RemoteSyncedData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteSeries {
+    opencast_id: String,
+    title: String,
+    synced_data: Option<RemoteSeriesData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteSeriesData {
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteSyncedData {
+    updated: DateTime<Utc>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    duration: i32,
+    thumbnail: Option<String>,
+    tracks: Vec<RemoteTrack>,
+    captions: Vec<RemoteCaption>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteTrack {
+    uri: String,
+    flavor: String,
+    mimetype: Option<String>,
+    resolution: Option<Vec<i32>>,
+    is_master: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteCaption {
+    uri: String,
+    lang: Option<String>,
+}
+
+async fn fetch_all(source: &HttpHost, token: Option<&Secret<String>>) -> Result<Vec<RemoteEvent>> {
+    #[derive(Deserialize)]
+    struct Response {
+        data: Option<Data>,
+        #[serde(default)]
+        errors: Vec<serde_json::Value>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Data {
+        public_events: Vec<RemoteEvent>,
+    }
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+    let uri = format!("{source}/graphql").parse::<Uri>()
+        .expect("bug: invalid federation URL");
+
+    let mut out = Vec::<RemoteEvent>::new();
+    loop {
+        let body = serde_json::json!({
+            "query": QUERY,
+            "operationName": "FederationImport",
+            "variables": {
+                "after": out.last().map(|e| &e.opencast_id),
+                "first": PAGE_SIZE,
+            },
+        });
+        let mut req = hyper::Request::post(&uri)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string().into())
+            .expect("bug: failed to build federation request");
+        if let Some(token) = token {
+            let value = format!("Bearer {}", token.expose_secret()).parse()
+                .context("invalid `federation.token`")?;
+            req.headers_mut().insert(hyper::header::AUTHORIZATION, value);
+        }
+
+        trace!("Sending federation request to '{uri}'");
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, client.request(req))
+            .await
+            .map_err(|_| anyhow!("request to '{uri}' timed out after {REQUEST_TIMEOUT:?}"))?
+            .with_context(|| format!("HTTP request failed (to '{uri}')"))?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await
+            .with_context(|| format!("failed to download body from '{uri}'"))?;
+        if !status.is_success() {
+            bail!("'{uri}' responded with unexpected HTTP code {status}");
+        }
+
+        let response = serde_json::from_slice::<Response>(&body)
+            .with_context(|| format!("failed to deserialize API response from '{uri}'"))?;
+        let events = match (response.data, response.errors.is_empty()) {
+            (Some(data), true) => data.public_events,
+            _ => bail!("API of '{source}' returned errors (is it a compatible Tobira \
+                version?): {}", serde_json::to_string(&response.errors)?),
+        };
+
+        let done = events.len() < PAGE_SIZE as usize;
+        out.extend(events);
+        if done {
+            return Ok(out);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::absolute_url;
+
+    #[test]
+    fn absolute_urls() {
+        let source = "https://tobira.other-uni.edu".parse().unwrap();
+        assert_eq!(
+            absolute_url(&source, "/~proxy/track/abc/0"),
+            "https://tobira.other-uni.edu/~proxy/track/abc/0",
+        );
+        assert_eq!(
+            absolute_url(&source, "https://static.oc.other-uni.edu/a.mp4"),
+            "https://static.oc.other-uni.edu/a.mp4",
+        );
+        assert_eq!(absolute_url(&source, "//cdn.other-uni.edu/a.mp4"), "//cdn.other-uni.edu/a.mp4");
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     cmp::min,
+    collections::{HashMap, HashSet},
     time::{Duration, Instant},
 };
 
@@ -8,12 +9,13 @@ use tokio_postgres::types::ToSql;
 use crate::{
     auth::ROLE_ADMIN,
     config::Config,
-    db::{types::{EventTrack, EventState, SeriesState, EventCaption}, DbConnection},
+    db::{series_stats, types::{EventTrack, EventState, SeriesState, EventCaption}, DbConnection},
     prelude::*,
 };
-use super::{status::SyncStatus, OcClient};
+use super::{license, persons, status::SyncStatus, OcClient};
 
-pub(crate) use self::response::{HarvestItem, HarvestResponse};
+pub(crate) use self::response::{HarvestItem, HarvestResponse, SkippedItem};
+use self::response::Acl;
 
 
 mod response;
@@ -38,6 +40,23 @@ pub(crate) async fn run(
     let mut backoff = INITIAL_BACKOFF;
 
     let preferred_amount = config.sync.preferred_harvest_size.into();
+    let license_aliases = &config.sync.license_aliases;
+
+    // Rows synced before licenses were normalized, or before
+    // `sync.license_aliases` was changed, are updated here.
+    let renormalized = license::renormalize(&**db, license_aliases).await
+        .context("failed to normalize stored licenses")?;
+    if renormalized > 0 {
+        info!("Updated normalized license of {renormalized} events and series");
+    }
+
+    // Events synced before persons were introduced are linked here.
+    let creator_normalization = config.sync.creator_normalization();
+    let linked = persons::link_unlinked(&**db, creator_normalization).await
+        .context("failed to link events to persons")?;
+    if linked > 0 {
+        info!("Linked creators of {linked} events to persons");
+    }
 
     loop {
         let sync_status = SyncStatus::fetch(&**db).await
@@ -74,11 +93,31 @@ pub(crate) async fn run(
         }
 
 
+        // Deserialize the individual items. Malformed items are skipped (unless
+        // `sync.strict` is set), as otherwise sync would be stuck forever.
+        let last_updated = harvest_data.items.last().and_then(response::raw_updated);
+        let (items, skipped) = response::parse_items(harvest_data.items, config.sync.strict)
+            .context("invalid harvest response (note: `sync.strict` is enabled)")?;
+        for item in &skipped {
+            warn!(
+                "Skipping malformed harvest item (kind: {}, Opencast ID: {}) {}",
+                item.kind.as_deref().unwrap_or("?"),
+                item.opencast_id.as_deref().unwrap_or("?"),
+                item.error,
+            );
+        }
+
         // Write received data into the database, updating the sync status if
         // everything worked out alright.
-        let last_updated = harvest_data.items.last().map(|item| item.updated());
         let mut transaction = db.transaction().await?;
-        store_in_db(harvest_data.items, &sync_status, &mut transaction).await?;
+        store_in_db(
+            items,
+            &sync_status,
+            license_aliases,
+            creator_normalization,
+            &mut transaction,
+        ).await?;
+        SyncStatus::record_skipped_items(&skipped, &*transaction).await?;
         SyncStatus::update_harvested_until(harvest_data.includes_items_until, &*transaction).await?;
         transaction.commit().await?;
 
@@ -121,6 +160,8 @@ pub(crate) async fn run(
 async fn store_in_db(
     items: Vec<HarvestItem>,
     sync_status: &SyncStatus,
+    license_aliases: &HashMap<String, String>,
+    creator_normalization: persons::Normalization,
     db: &mut deadpool_postgres::Transaction<'_>,
 ) -> Result<()> {
     let before = Instant::now();
@@ -129,6 +170,9 @@ async fn store_in_db(
     let mut upserted_series = 0;
     let mut removed_series = 0;
 
+    // Series whose denormalized event statistics have to be updated.
+    let mut touched_series = HashSet::new();
+
     for item in items {
         // Make sure we haven't received this update yet. The code below can
         // handle duplicate items alright, but this way we can save on some DB
@@ -154,6 +198,7 @@ async fn store_in_db(
                 thumbnail,
                 mut acl,
                 is_live,
+                is_processing,
                 metadata,
                 updated,
             } => {
@@ -171,16 +216,42 @@ async fn store_in_db(
                 acl.read.retain(|role| role != ROLE_ADMIN);
                 acl.write.retain(|role| role != ROLE_ADMIN);
 
-                let tracks = tracks.into_iter().map(Into::into).collect::<Vec<EventTrack>>();
-                let captions = captions.into_iter().map(Into::into).collect::<Vec<EventCaption>>();
+                let license = metadata.dcterms.get("license").and_then(|v| v.first()).cloned();
+                let license_spdx = normalize_license(&license, license_aliases, &opencast_id);
+
+                // The event might be moved to another series. And while it is
+                // processed, its old publications might still be needed.
+                let old = db
+                    .query_opt(
+                        "select series, tracks, captions, thumbnail from events \
+                            where opencast_id = $1",
+                        &[&opencast_id],
+                    )
+                    .await?;
+                let old_series_id = old.as_ref().and_then(|row| row.get::<_, Option<i64>>(0));
+                touched_series.extend(old_series_id.into_iter().chain(series_id));
+
+                let publications = Publications {
+                    tracks: tracks.into_iter().map(Into::into).collect(),
+                    captions: captions.into_iter().map(Into::into).collect(),
+                    thumbnail,
+                };
+                let old_publications = old.map(|row| Publications {
+                    tracks: row.get(1),
+                    captions: row.get(2),
+                    thumbnail: row.get(3),
+                });
+                let Publications { tracks, captions, thumbnail } = publications
+                    .keep_while_processing(is_processing, old_publications);
 
                 // We upsert the event data.
-                upsert(db, "events", "opencast_id", &[
+                let event_id = upsert(db, "events", "opencast_id", &[
                     ("opencast_id", &opencast_id),
                     ("state", &EventState::Ready),
                     ("series", &series_id),
                     ("part_of", &part_of),
                     ("is_live", &is_live),
+                    ("is_processing", &is_processing),
                     ("title", &title),
                     ("description", &description),
                     ("duration", &duration),
@@ -191,25 +262,55 @@ async fn store_in_db(
                     ("creators", &creators),
                     ("thumbnail", &thumbnail),
                     ("metadata", &metadata),
+                    ("license", &license),
+                    ("license_spdx", &license_spdx),
                     ("read_roles", &acl.read),
                     ("write_roles", &acl.write),
                     ("tracks", &tracks),
                     ("captions", &captions),
+                    // Events from Opencast replace ones imported from
+                    // another Tobira (see `federation`).
+                    ("external_origin", &None::<String>),
                 ]).await?;
+                persons::link(&**db, event_id, &creators, creator_normalization).await?;
+
+                // If this event was uploaded via Tobira, it's done processing now.
+                db.execute(
+                    "update upload_jobs set state = 'ready', error_message = null, updated = now() \
+                        where opencast_id = $1 and state <> 'ready'",
+                    &[&opencast_id],
+                ).await?;
 
+                SyncStatus::forget_skipped_item(&opencast_id, &**db).await?;
                 trace!("Inserted or updated event {} ({})", opencast_id, title);
                 upserted_events += 1;
             }
 
             HarvestItem::EventDeleted { id: opencast_id, .. } => {
-                let rows_affected = db
-                    .execute("delete from events where opencast_id = $1", &[&opencast_id])
+                let rows = db
+                    .query(
+                        "delete from events where opencast_id = $1 returning series",
+                        &[&opencast_id],
+                    )
                     .await?;
-                check_affected_rows_removed(rows_affected, "event", &opencast_id);
+                touched_series.extend(rows.iter().filter_map(|row| row.get::<_, Option<i64>>(0)));
+                check_affected_rows_removed(rows.len() as u64, "event", &opencast_id);
+                SyncStatus::forget_skipped_item(&opencast_id, &**db).await?;
                 removed_events += 1;
             }
 
-            HarvestItem::Series { id: opencast_id, title, description, updated, acl } => {
+            HarvestItem::Series { id: opencast_id, title, description, updated, acl, license } => {
+                // Remember the old ACL to detect changes below.
+                let old_acl = db
+                    .query_opt(
+                        "select read_roles, write_roles from series where opencast_id = $1",
+                        &[&opencast_id],
+                    )
+                    .await?
+                    .and_then(|row| stored_acl(row.get(0), row.get(1)));
+
+                let license_spdx = normalize_license(&license, license_aliases, &opencast_id);
+
                 // We first simply upsert the series.
                 let new_id = upsert(db, "series", "opencast_id", &[
                     ("opencast_id", &opencast_id),
@@ -218,7 +319,10 @@ async fn store_in_db(
                     ("description", &description),
                     ("read_roles", &acl.read),
                     ("write_roles", &acl.write),
+                    ("license", &license),
+                    ("license_spdx", &license_spdx),
                     ("updated", &updated),
+                    ("external_origin", &None::<String>),
                 ]).await?;
 
                 // But now we have to fix the foreign key for any events that
@@ -226,7 +330,40 @@ async fn store_in_db(
                 // but did not have the correct foreign key yet.
                 let query = "update events set series = $1 where part_of = $2 and series <> $1";
                 let updated_events = db.execute(query, &[&new_id, &opencast_id]).await?;
+                if updated_events != 0 {
+                    touched_series.insert(new_id);
+                }
 
+                // Events inherit the ACL of their series in Opencast, but we
+                // only learn about that once each event is harvested again,
+                // which can take a long time. So we update all events whose
+                // ACL still equals the old series ACL right away. Events with
+                // a custom ACL are left alone. The search index is updated
+                // via the trigger on `events`.
+                if let Some(change) = old_acl.and_then(|old| AclChange::new(old, &acl)) {
+                    let query = "update events \
+                        set read_roles = $2, write_roles = $3 \
+                        from series \
+                        where events.series = series.id \
+                            and series.opencast_id = $1 \
+                            and events.read_roles @> $4 and events.read_roles <@ $4 \
+                            and events.write_roles @> $5 and events.write_roles <@ $5";
+                    let inherited = db.execute(query, &[
+                        &opencast_id,
+                        &change.new.read,
+                        &change.new.write,
+                        &change.old.read,
+                        &change.old.write,
+                    ]).await?;
+                    debug!(
+                        "ACL of series {} ({}) changed: updated ACL of {} event(s)",
+                        opencast_id,
+                        title,
+                        inherited,
+                    );
+                }
+
+                SyncStatus::forget_skipped_item(&opencast_id, &**db).await?;
                 trace!("Inserted or updated series {} ({})", opencast_id, title);
                 if updated_events != 0 {
                     debug!(
@@ -248,11 +385,18 @@ async fn store_in_db(
                     .execute("delete from series where opencast_id = $1", &[&opencast_id])
                     .await?;
                 check_affected_rows_removed(rows_affected, "series", &opencast_id);
+                SyncStatus::forget_skipped_item(&opencast_id, &**db).await?;
                 removed_series += 1;
             }
         }
     }
 
+    if !touched_series.is_empty() {
+        let ids = touched_series.into_iter().collect::<Vec<_>>();
+        series_stats::update(&**db, Some(&ids)).await
+            .context("failed to update event statistics of series")?;
+    }
+
     if upserted_events == 0 && upserted_series == 0 && removed_events == 0 && removed_series == 0 {
         info!("Harvest outcome: nothing changed!");
     } else {
@@ -270,6 +414,71 @@ async fn store_in_db(
     Ok(())
 }
 
+/// The data of an event that stems from its publications.
+#[derive(Debug)]
+struct Publications {
+    tracks: Vec<EventTrack>,
+    captions: Vec<EventCaption>,
+    thumbnail: Option<String>,
+}
+
+impl Publications {
+    /// While an event is processed, Opencast retracts (some of) its
+    /// publications, which would leave us with a broken player. So in that
+    /// case, missing data is replaced by the `old` data. Once processing is
+    /// done, the harvested data is used as is.
+    fn keep_while_processing(self, is_processing: bool, old: Option<Self>) -> Self {
+        let Some(old) = old.filter(|_| is_processing) else {
+            return self;
+        };
+
+        Self {
+            tracks: if self.tracks.is_empty() { old.tracks } else { self.tracks },
+            captions: if self.captions.is_empty() { old.captions } else { self.captions },
+            thumbnail: self.thumbnail.or(old.thumbnail),
+        }
+    }
+}
+
+/// The ACL of a series as stored in the DB. Series created by Tobira (e.g.
+/// when mounting one) have no ACL until their first harvest, so there is
+/// nothing to propagate for those.
+fn stored_acl(read: Option<Vec<String>>, write: Option<Vec<String>>) -> Option<Acl> {
+    Some(Acl { read: read?, write: write? })
+}
+
+/// A change of a series ACL, with both ACLs as stored for events, i.e.
+/// without the admin role.
+#[derive(Debug)]
+struct AclChange {
+    old: Acl,
+    new: Acl,
+}
+
+impl AclChange {
+    /// Returns `None` if both ACLs contain the same roles (ignoring order,
+    /// duplicates and the admin role).
+    fn new(old: Acl, new: &Acl) -> Option<Self> {
+        let normalize = |roles: &[String]| {
+            let mut out = roles.iter()
+                .filter(|role| *role != ROLE_ADMIN)
+                .cloned()
+                .collect::<Vec<_>>();
+            out.sort();
+            out.dedup();
+            out
+        };
+        let old = Acl { read: normalize(&old.read), write: normalize(&old.write) };
+        let new = Acl { read: normalize(&new.read), write: normalize(&new.write) };
+
+        if old.read == new.read && old.write == new.write {
+            None
+        } else {
+            Some(Self { old, new })
+        }
+    }
+}
+
 fn check_affected_rows_removed(rows_affected: u64, entity: &str, opencast_id: &str) {
     // The 0 rows affected case is fine: it is deleted anyway, so if we don't
     // have it, then we don't have to do anything.
@@ -280,6 +489,21 @@ fn check_affected_rows_removed(rows_affected: u64, entity: &str, opencast_id: &s
     }
 }
 
+/// Normalizes the given license, logging a warning if it is unknown.
+fn normalize_license(
+    license: &Option<String>,
+    aliases: &HashMap<String, String>,
+    opencast_id: &str,
+) -> Option<String> {
+    let license = license.as_deref().filter(|l| !l.trim().is_empty())?;
+    let normalized = license::normalize(license, aliases);
+    if normalized.is_none() {
+        warn!("Unknown license '{license}' of {opencast_id}: consider adding it \
+            to `sync.license_aliases`");
+    }
+    normalized
+}
+
 /// Inserts a new row or updates an existing one if the value in `unique_col`
 /// already exists. Returns the value of the `id` column, which is assumed to
 /// be `i64`.
@@ -328,3 +552,116 @@ async fn upsert(
     let statement = db.prepare_cached(&*query).await?;
     Ok(db.query_one(&statement, &values).await?.get::<_, i64>(0))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::types::{EventCaption, EventTrack};
+    use super::{stored_acl, Acl, AclChange, Publications};
+
+    fn acl(read: &[&str], write: &[&str]) -> Acl {
+        let roles = |roles: &[&str]| roles.iter().map(|r| r.to_string()).collect();
+        Acl { read: roles(read), write: roles(write) }
+    }
+
+    #[test]
+    fn series_acl_public_to_private_and_back() {
+        let public = || acl(&["ROLE_ANONYMOUS", "ROLE_USER_ADMIN"], &["ROLE_USER_ADMIN"]);
+        let private = || acl(&["ROLE_USER_ADMIN"], &["ROLE_USER_ADMIN"]);
+
+        let change = AclChange::new(public(), &private()).unwrap();
+        assert_eq!(change.old.read, ["ROLE_ANONYMOUS", "ROLE_USER_ADMIN"]);
+        assert_eq!(change.new.read, ["ROLE_USER_ADMIN"]);
+        assert_eq!(change.new.write, ["ROLE_USER_ADMIN"]);
+
+        let change = AclChange::new(private(), &public()).unwrap();
+        assert_eq!(change.old.read, ["ROLE_USER_ADMIN"]);
+        assert_eq!(change.new.read, ["ROLE_ANONYMOUS", "ROLE_USER_ADMIN"]);
+        assert_eq!(change.new.write, ["ROLE_USER_ADMIN"]);
+    }
+
+    #[test]
+    fn series_acl_unchanged() {
+        let old = acl(&["ROLE_B", "ROLE_A", "ROLE_A"], &["ROLE_A"]);
+        let new = acl(&["ROLE_A", "ROLE_B", "ROLE_ADMIN"], &["ROLE_ADMIN", "ROLE_A"]);
+        assert!(AclChange::new(old, &new).is_none());
+    }
+
+    #[test]
+    fn waiting_series_has_no_acl() {
+        assert!(stored_acl(None, None).is_none());
+
+        let roles = |roles: &[&str]| Some(roles.iter().map(|r| r.to_string()).collect());
+        let old = stored_acl(roles(&["ROLE_A"]), roles(&[])).unwrap();
+        let change = AclChange::new(old, &acl(&["ROLE_B"], &[])).unwrap();
+        assert_eq!(change.old.read, ["ROLE_A"]);
+        assert_eq!(change.new.read, ["ROLE_B"]);
+    }
+
+    #[test]
+    fn admin_role_is_removed() {
+        let old = acl(&["ROLE_ADMIN", "ROLE_A"], &["ROLE_ADMIN"]);
+        let new = acl(&["ROLE_ADMIN", "ROLE_B"], &["ROLE_ADMIN", "ROLE_B"]);
+        let change = AclChange::new(old, &new).unwrap();
+        assert_eq!(change.old.read, ["ROLE_A"]);
+        assert!(change.old.write.is_empty());
+        assert_eq!(change.new.read, ["ROLE_B"]);
+        assert_eq!(change.new.write, ["ROLE_B"]);
+    }
+
+    fn publications(uris: &[&str], thumbnail: Option<&str>) -> Publications {
+        Publications {
+            tracks: uris.iter().map(|uri| EventTrack {
+                uri: uri.to_string(),
+                flavor: "presenter/preview".into(),
+                mimetype: Some("video/mp4".into()),
+                resolution: Some([1280, 720]),
+                is_master: None,
+            }).collect(),
+            captions: uris.iter().map(|uri| EventCaption {
+                uri: format!("{uri}.vtt"),
+                lang: None,
+            }).collect(),
+            thumbnail: thumbnail.map(Into::into),
+        }
+    }
+
+    fn uris(p: &Publications) -> (Vec<&str>, Vec<&str>, Option<&str>) {
+        (
+            p.tracks.iter().map(|t| t.uri.as_str()).collect(),
+            p.captions.iter().map(|c| c.uri.as_str()).collect(),
+            p.thumbnail.as_deref(),
+        )
+    }
+
+    #[test]
+    fn publish_retract_for_processing_republish() {
+        // Published for the first time.
+        let stored = publications(&["a.mp4"], Some("a.jpg")).keep_while_processing(false, None);
+        assert_eq!(uris(&stored), (vec!["a.mp4"], vec!["a.mp4.vtt"], Some("a.jpg")));
+
+        // Retracted while reprocessing: the old data is kept.
+        let stored = publications(&[], None).keep_while_processing(true, Some(stored));
+        assert_eq!(uris(&stored), (vec!["a.mp4"], vec!["a.mp4.vtt"], Some("a.jpg")));
+
+        // Partially published while still processing: new data wins.
+        let stored = publications(&["b.mp4"], None).keep_while_processing(true, Some(stored));
+        assert_eq!(uris(&stored), (vec!["b.mp4"], vec!["b.mp4.vtt"], Some("a.jpg")));
+
+        // Republished after processing.
+        let stored = publications(&["c.mp4"], Some("c.jpg"))
+            .keep_while_processing(false, Some(stored));
+        assert_eq!(uris(&stored), (vec!["c.mp4"], vec!["c.mp4.vtt"], Some("c.jpg")));
+    }
+
+    #[test]
+    fn retract_without_processing_removes_publications() {
+        let old = publications(&["a.mp4"], Some("a.jpg"));
+        let stored = publications(&[], None).keep_while_processing(false, Some(old));
+        assert_eq!(uris(&stored), (vec![], vec![], None));
+
+        // A new event being processed has nothing to fall back to.
+        let stored = publications(&[], None).keep_while_processing(true, None);
+        assert_eq!(uris(&stored), (vec![], vec![], None));
+    }
+}
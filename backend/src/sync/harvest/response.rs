@@ -1,7 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::Deserialize;
 
-use crate::db::types::{EventTrack, ExtraMetadata, EventCaption};
+use crate::{
+    db::types::{EventTrack, ExtraMetadata, EventCaption},
+    prelude::*,
+};
 
 
 /// What the harvesting API returns.
@@ -11,11 +14,84 @@ pub(crate) struct HarvestResponse {
     #[serde(with = "chrono::serde::ts_milliseconds")]
     pub(crate) includes_items_until: DateTime<Utc>,
     pub(crate) has_more: bool,
-    pub(crate) items: Vec<HarvestItem>,
+
+    /// The raw items, which are deserialized individually via `parse_items`
+    /// so that a single malformed item does not break the whole response.
+    pub(crate) items: Vec<serde_json::Value>,
+}
+
+/// An item of the harvest response that could not be deserialized.
+#[derive(Debug)]
+pub(crate) struct SkippedItem {
+    /// `None` if the item does not even have a string `id` field.
+    pub(crate) opencast_id: Option<String>,
+    pub(crate) kind: Option<String>,
+    /// The deserialization error including the path to the offending field.
+    pub(crate) error: String,
+}
+
+/// Deserializes the given raw harvest items. Malformed items are returned as
+/// `SkippedItem`s, unless `strict` is set, in which case an error is returned
+/// for the first malformed item.
+pub(crate) fn parse_items(
+    raw_items: Vec<serde_json::Value>,
+    strict: bool,
+) -> Result<(Vec<HarvestItem>, Vec<SkippedItem>)> {
+    let mut items = Vec::with_capacity(raw_items.len());
+    let mut skipped = Vec::new();
+    for raw in raw_items {
+        let str_field = |name: &str| raw.get(name).and_then(|v| v.as_str()).map(String::from);
+        let opencast_id = str_field("id");
+        let kind = str_field("kind");
+
+        // Internally tagged enums are buffered by serde, losing the path to
+        // the offending field in errors. So we turn `{ "kind": "event", ... }`
+        // into `{ "event": { ... } }`, i.e. the externally tagged format.
+        let result = match (&kind, raw) {
+            (Some(kind), serde_json::Value::Object(mut fields)) => {
+                fields.remove("kind");
+                let mut tagged = serde_json::Map::new();
+                tagged.insert(kind.clone(), fields.into());
+                let tagged = serde_json::Value::Object(tagged);
+                serde_path_to_error::deserialize::<_, HarvestItem>(tagged)
+                    .map_err(|e| {
+                        // Strip the variant name, which is the first segment.
+                        let path = e.path().to_string();
+                        let path = path.split_once('.').map_or(".", |(_, rest)| rest).to_owned();
+                        format!("at '{path}': {}", e.inner())
+                    })
+            }
+            _ => Err("item is not an object with a string field 'kind'".to_owned()),
+        };
+
+        match result {
+            Ok(item) => items.push(item),
+            Err(error) => {
+                if strict {
+                    bail!("failed to deserialize harvest item (kind: {}, Opencast ID: {}) {}",
+                        kind.as_deref().unwrap_or("?"),
+                        opencast_id.as_deref().unwrap_or("?"),
+                        error,
+                    );
+                }
+                skipped.push(SkippedItem { opencast_id, kind, error });
+            }
+        }
+    }
+
+    Ok((items, skipped))
+}
+
+/// Returns the `updated` timestamp of a raw item, if it has a valid one.
+/// Used to make progress even if the last item of a response is malformed.
+pub(crate) fn raw_updated(raw: &serde_json::Value) -> Option<DateTime<Utc>> {
+    raw.get("updated")?.as_i64().and_then(|ms| Utc.timestamp_millis_opt(ms).single())
 }
 
+/// A single item of the harvest response. In the JSON, the variant is given
+/// by the `kind` field, but see `parse_items` for why this is not expressed
+/// via `#[serde(tag = "kind")]`.
 #[derive(Debug, Deserialize)]
-#[serde(tag = "kind")]
 #[serde(rename_all = "kebab-case")]
 pub(crate) enum HarvestItem {
     #[serde(rename_all = "camelCase")]
@@ -34,6 +110,10 @@ pub(crate) enum HarvestItem {
         thumbnail: Option<String>,
         acl: Acl,
         is_live: bool,
+        /// Whether a workflow is currently running for this event. Only sent
+        /// by newer versions of the Tobira module.
+        #[serde(default)]
+        is_processing: bool,
         metadata: ExtraMetadata,
         #[serde(with = "chrono::serde::ts_milliseconds_option")]
         start_time: Option<DateTime<Utc>>,
@@ -56,6 +136,10 @@ pub(crate) enum HarvestItem {
         title: String,
         description: Option<String>,
         acl: Acl,
+        /// Only sent by newer versions of the Tobira module. For events, the
+        /// license is part of `metadata`.
+        #[serde(default)]
+        license: Option<String>,
         #[serde(with = "chrono::serde::ts_milliseconds")]
         updated: DateTime<Utc>,
     },
@@ -122,3 +206,38 @@ pub(crate) struct Acl {
     pub(crate) read: Vec<String>,
     pub(crate) write: Vec<String>,
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_items, HarvestItem, HarvestResponse};
+
+    fn mixed_page() -> HarvestResponse {
+        serde_json::from_str(include_str!("test-data/mixed-page.json"))
+            .expect("failed to deserialize fixture")
+    }
+
+    #[test]
+    fn malformed_items_are_skipped() {
+        let (items, skipped) = parse_items(mixed_page().items, false).unwrap();
+
+        let ids = items.iter().map(|item| match item {
+            HarvestItem::Event { id, .. } | HarvestItem::EventDeleted { id, .. } => id,
+            HarvestItem::Series { id, .. } | HarvestItem::SeriesDeleted { id, .. } => id,
+        }).collect::<Vec<_>>();
+        assert_eq!(ids, ["valid-series", "valid-event", "deleted-event"]);
+
+        let skipped_ids = skipped.iter()
+            .map(|s| s.opencast_id.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(skipped_ids, [Some("event-without-title"), Some("event-bad-track"), None]);
+        assert!(skipped[0].error.contains("title"), "{}", skipped[0].error);
+        assert!(skipped[1].error.starts_with("at 'tracks[0].flavor'"), "{}", skipped[1].error);
+    }
+
+    #[test]
+    fn strict_fails_on_malformed_items() {
+        let err = parse_items(mixed_page().items, true).unwrap_err();
+        assert!(err.to_string().contains("event-without-title"), "{err}");
+    }
+}
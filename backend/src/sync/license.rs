@@ -0,0 +1,240 @@
+//! Normalization of the free-form license strings from Opencast to SPDX
+//! identifiers (https://spdx.org/licenses/).
+//!
+//! Opencast stores whatever was entered or configured as license, e.g.
+//! `CC-BY-SA` (the default license list of Opencast), `cc-by-sa-4.0` or a
+//! Creative Commons URL. We store that raw value, but also a normalized SPDX
+//! identifier, which is used for display and for filtering in the search
+//! index. Licenses that cannot be mapped are kept raw only.
+
+use std::collections::HashMap;
+
+use tokio_postgres::GenericClient;
+
+use crate::prelude::*;
+
+
+/// Licenses that Tobira knows the display name of, by SPDX identifier.
+/// `LicenseRef-` identifiers are SPDX' way of naming licenses that are not on
+/// the SPDX license list.
+const KNOWN: &[(&str, &str)] = &[
+    ("CC-BY-4.0", "Creative Commons Attribution 4.0 International"),
+    ("CC-BY-SA-4.0", "Creative Commons Attribution Share Alike 4.0 International"),
+    ("CC-BY-ND-4.0", "Creative Commons Attribution No Derivatives 4.0 International"),
+    ("CC-BY-NC-4.0", "Creative Commons Attribution Non Commercial 4.0 International"),
+    ("CC-BY-NC-SA-4.0",
+        "Creative Commons Attribution Non Commercial Share Alike 4.0 International"),
+    ("CC-BY-NC-ND-4.0",
+        "Creative Commons Attribution Non Commercial No Derivatives 4.0 International"),
+    ("CC-BY-3.0", "Creative Commons Attribution 3.0 Unported"),
+    ("CC-BY-SA-3.0", "Creative Commons Attribution Share Alike 3.0 Unported"),
+    ("CC-BY-ND-3.0", "Creative Commons Attribution No Derivatives 3.0 Unported"),
+    ("CC-BY-NC-3.0", "Creative Commons Attribution Non Commercial 3.0 Unported"),
+    ("CC-BY-NC-SA-3.0", "Creative Commons Attribution Non Commercial Share Alike 3.0 Unported"),
+    ("CC-BY-NC-ND-3.0",
+        "Creative Commons Attribution Non Commercial No Derivatives 3.0 Unported"),
+    ("CC0-1.0", "Creative Commons Zero v1.0 Universal"),
+    ("CC-PDDC", "Creative Commons Public Domain Dedication and Certification"),
+    ("MIT", "MIT License"),
+    ("Apache-2.0", "Apache License 2.0"),
+    ("LicenseRef-All-Rights-Reserved", "All rights reserved"),
+];
+
+/// Built-in aliases (lowercase, spaces replaced by dashes) for common non-SPDX
+/// spellings. The values of the default license list of Opencast are mapped
+/// to the latest version.
+const ALIASES: &[(&str, &str)] = &[
+    ("allrights", "LicenseRef-All-Rights-Reserved"),
+    ("all-rights-reserved", "LicenseRef-All-Rights-Reserved"),
+    ("cc-by", "CC-BY-4.0"),
+    ("cc-by-sa", "CC-BY-SA-4.0"),
+    ("cc-by-nd", "CC-BY-ND-4.0"),
+    ("cc-by-nc", "CC-BY-NC-4.0"),
+    ("cc-by-nc-sa", "CC-BY-NC-SA-4.0"),
+    ("cc-by-nc-nd", "CC-BY-NC-ND-4.0"),
+    ("cc0", "CC0-1.0"),
+];
+
+/// Returns the SPDX identifier for the given raw license string, or `None` if
+/// it is unknown. `extra_aliases` (from `sync.license_aliases`) take
+/// precedence over everything built-in. All matching is case-insensitive.
+pub(crate) fn normalize(raw: &str, extra_aliases: &HashMap<String, String>) -> Option<String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+
+    let configured = extra_aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(raw));
+    if let Some((_, id)) = configured {
+        return Some(id.clone());
+    }
+
+    // "CC BY-SA 4.0" is a common spelling as well.
+    let dashed = raw.to_ascii_lowercase().replace(' ', "-");
+    KNOWN.iter()
+        .map(|(id, _)| *id)
+        .find(|id| id.eq_ignore_ascii_case(&dashed))
+        .or_else(|| ALIASES.iter().find(|(alias, _)| *alias == dashed).map(|(_, id)| *id))
+        .map(String::from)
+        .or_else(|| from_cc_url(raw))
+}
+
+/// Maps Creative Commons URLs like `https://creativecommons.org/licenses/by-sa/4.0/`
+/// to the corresponding SPDX identifier.
+fn from_cc_url(raw: &str) -> Option<String> {
+    let lower = raw.to_ascii_lowercase();
+    let path = lower
+        .strip_prefix("https://")
+        .or_else(|| lower.strip_prefix("http://"))?
+        .trim_start_matches("www.")
+        .strip_prefix("creativecommons.org/")?;
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+
+    let id = match (segments.next()?, segments.next()?, segments.next()?) {
+        ("publicdomain", "zero", "1.0") => "CC0-1.0".to_owned(),
+        ("licenses", kind, version) => format!("CC-{}-{version}", kind.to_ascii_uppercase()),
+        _ => return None,
+    };
+
+    // Ported versions (e.g. `.../by/3.0/de/`) have separate SPDX identifiers
+    // that we do not know.
+    let is_known = KNOWN.iter().any(|(known, _)| *known == id);
+    (is_known && segments.next().is_none()).then_some(id)
+}
+
+/// Human readable name of the license with the given SPDX identifier. For
+/// unknown identifiers (e.g. configured via `sync.license_aliases`), the
+/// identifier itself is returned.
+pub(crate) fn display_name(id: &str) -> &str {
+    KNOWN.iter().find(|(known, _)| *known == id).map_or(id, |(_, name)| *name)
+}
+
+/// Link to the text of the license with the given SPDX identifier. `None` for
+/// `LicenseRef-` identifiers, which are not on the SPDX license list.
+pub(crate) fn link(id: &str) -> Option<String> {
+    if id.starts_with("LicenseRef-") {
+        return None;
+    }
+
+    let url = match id.strip_prefix("CC-").and_then(|rest| rest.rsplit_once('-')) {
+        Some((kind, version))
+            if kind.starts_with("BY") && version.chars().all(|c| c.is_ascii_digit() || c == '.') =>
+        {
+            format!("https://creativecommons.org/licenses/{}/{version}/", kind.to_lowercase())
+        }
+        _ if id == "CC0-1.0" => "https://creativecommons.org/publicdomain/zero/1.0/".into(),
+        _ => format!("https://spdx.org/licenses/{id}.html"),
+    };
+    Some(url)
+}
+
+/// Updates the normalized license of all events and series whose stored value
+/// does not match the current normalization (e.g. because rows were migrated
+/// without one or because `sync.license_aliases` changed). Logs a warning for
+/// each license that is unknown. Returns the number of updated rows.
+pub(crate) async fn renormalize(
+    db: &impl GenericClient,
+    extra_aliases: &HashMap<String, String>,
+) -> Result<u64> {
+    let rows = db
+        .query(
+            "select distinct license from events where license is not null \
+                union select distinct license from series where license is not null",
+            &[],
+        )
+        .await
+        .context("failed to load licenses")?;
+
+    let mut updated = 0;
+    for row in rows {
+        let raw = row.get::<_, String>(0);
+        let normalized = normalize(&raw, extra_aliases);
+        if normalized.is_none() {
+            warn!("Unknown license '{raw}': consider adding it to `sync.license_aliases`");
+        }
+        for table in ["events", "series"] {
+            let sql = format!("update {table} set license_spdx = $2 \
+                where license = $1 and license_spdx is distinct from $2");
+            updated += db.execute(&sql, &[&raw, &normalized]).await?;
+        }
+    }
+
+    Ok(updated)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{normalize, link, display_name};
+
+    fn norm(raw: &str) -> Option<String> {
+        normalize(raw, &HashMap::new())
+    }
+
+    #[test]
+    fn spdx_ids_and_aliases() {
+        assert_eq!(norm("CC-BY-SA-4.0").as_deref(), Some("CC-BY-SA-4.0"));
+        assert_eq!(norm(" cc-by-nc-nd-3.0 ").as_deref(), Some("CC-BY-NC-ND-3.0"));
+        assert_eq!(norm("CC-BY").as_deref(), Some("CC-BY-4.0"));
+        assert_eq!(norm("ALLRIGHTS").as_deref(), Some("LicenseRef-All-Rights-Reserved"));
+        assert_eq!(norm("CC BY-SA 4.0").as_deref(), Some("CC-BY-SA-4.0"));
+        assert_eq!(
+            norm("All rights reserved").as_deref(),
+            Some("LicenseRef-All-Rights-Reserved"),
+        );
+        assert_eq!(norm("cc0").as_deref(), Some("CC0-1.0"));
+    }
+
+    #[test]
+    fn creative_commons_urls() {
+        assert_eq!(
+            norm("https://creativecommons.org/licenses/by-nc-sa/4.0/").as_deref(),
+            Some("CC-BY-NC-SA-4.0"),
+        );
+        assert_eq!(
+            norm("http://www.creativecommons.org/licenses/by/3.0").as_deref(),
+            Some("CC-BY-3.0"),
+        );
+        assert_eq!(
+            norm("https://creativecommons.org/publicdomain/zero/1.0/").as_deref(),
+            Some("CC0-1.0"),
+        );
+        assert_eq!(norm("https://creativecommons.org/licenses/by/3.0/de/"), None);
+        assert_eq!(norm("https://example.com/licenses/by/4.0/"), None);
+    }
+
+    #[test]
+    fn unknown_and_configured() {
+        assert_eq!(norm("Proprietary"), None);
+        assert_eq!(norm("  "), None);
+
+        let aliases = HashMap::from([
+            ("Uni intern".to_owned(), "LicenseRef-Uni-Internal".to_owned()),
+            ("CC-BY".to_owned(), "CC-BY-3.0".to_owned()),
+        ]);
+        assert_eq!(
+            normalize("uni INTERN", &aliases).as_deref(),
+            Some("LicenseRef-Uni-Internal"),
+        );
+        assert_eq!(normalize("cc-by", &aliases).as_deref(), Some("CC-BY-3.0"));
+    }
+
+    #[test]
+    fn display_names_and_links() {
+        assert_eq!(display_name("CC-BY-4.0"), "Creative Commons Attribution 4.0 International");
+        assert_eq!(display_name("LicenseRef-Uni-Internal"), "LicenseRef-Uni-Internal");
+
+        assert_eq!(
+            link("CC-BY-NC-SA-4.0").as_deref(),
+            Some("https://creativecommons.org/licenses/by-nc-sa/4.0/"),
+        );
+        assert_eq!(
+            link("CC0-1.0").as_deref(),
+            Some("https://creativecommons.org/publicdomain/zero/1.0/"),
+        );
+        assert_eq!(link("MIT").as_deref(), Some("https://spdx.org/licenses/MIT.html"));
+        assert_eq!(link("CC-PDDC").as_deref(), Some("https://spdx.org/licenses/CC-PDDC.html"));
+        assert_eq!(link("LicenseRef-All-Rights-Reserved"), None);
+    }
+}
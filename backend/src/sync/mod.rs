@@ -1,12 +1,17 @@
 use secrecy::Secret;
 use core::fmt;
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use crate::{config::Config, db::DbConnection, prelude::*};
 
 
+pub(crate) mod backfill;
 pub(crate) mod cmd;
+pub(crate) mod federation;
 pub(crate) mod harvest;
+pub(crate) mod license;
+pub(crate) mod persons;
+pub(crate) mod uploads;
 mod client;
 mod status;
 
@@ -23,6 +28,21 @@ pub(crate) async fn run(daemon: bool, db: DbConnection, config: &Config) -> Resu
     harvest::run(daemon, config, &client, db).await
 }
 
+/// Runs the harvest as daemon and, next to it, tracks the processing state of
+/// uploads. Both only start once Opencast is known to be compatible.
+pub(crate) async fn run_daemon(
+    db: DbConnection,
+    uploads_db: DbConnection,
+    config: &Config,
+) -> Result<()> {
+    let client = OcClient::new(config);
+    check_compatibility(&client).await?;
+    tokio::select! {
+        res = harvest::run(true, config, &client, db) => res,
+        never = uploads::poll_processing_states(&uploads_db, &client, config) => never,
+    }
+}
+
 pub(crate) async fn check_compatibility(client: &OcClient) -> Result<()> {
     let response = client.get_version().await.context("failed to fetch API version")?;
     let version = response.version();
@@ -68,6 +88,52 @@ pub(crate) struct SyncConfig {
     /// relevant in `--daemon` mode.
     #[config(default = "30s", deserialize_with = crate::config::deserialize_duration)]
     poll_period: Duration,
+
+    /// If `true`, a single malformed item in a harvest response makes the
+    /// sync fail. Otherwise, such items are skipped with a warning and
+    /// recorded in the table `sync_skipped_items`. Only intended for
+    /// debugging, as with this, a single broken item stalls the sync forever.
+    #[config(default = false)]
+    strict: bool,
+
+    /// Additional mappings of license strings (as stored in Opencast) to SPDX
+    /// identifiers (https://spdx.org/licenses/), taking precedence over the
+    /// built-in ones. Matching is case-insensitive. Licenses that are not on
+    /// the SPDX list can be given a `LicenseRef-` identifier. Example:
+    ///
+    ///     [sync.license_aliases]
+    ///     "Creative Commons BY 4.0" = "CC-BY-4.0"
+    ///     "Internal use only" = "LicenseRef-Internal"
+    ///
+    /// Licenses that cannot be mapped are shown as is and logged as warning.
+    #[config(default = {})]
+    license_aliases: HashMap<String, String>,
+
+    /// Whether to trim creator names and collapse whitespace in them before
+    /// matching them to persons. See `creators_flip_last_first` for details.
+    #[config(default = true)]
+    creators_normalize_whitespace: bool,
+
+    /// Whether to turn creator names of the form "Last, First" into
+    /// "First Last" before matching them to persons. Names with more than one
+    /// comma are left alone.
+    ///
+    /// Creators of events are matched to persons by their normalized name
+    /// (case-insensitively), so that different spellings of the same name
+    /// are shown the same way. The original names are kept. Changing this
+    /// or `creators_normalize_whitespace` only affects events synced
+    /// afterwards.
+    #[config(default = false)]
+    creators_flip_last_first: bool,
+}
+
+impl SyncConfig {
+    pub(crate) fn creator_normalization(&self) -> persons::Normalization {
+        persons::Normalization {
+            collapse_whitespace: self.creators_normalize_whitespace,
+            flip_last_first: self.creators_flip_last_first,
+        }
+    }
 }
 
 
@@ -0,0 +1,128 @@
+//! Deduplication of the creators of events.
+//!
+//! Opencast stores creators as free-form strings, so the same person often
+//! appears with different spellings (e.g. `Doe, Jane` and `Jane Doe`). We
+//! store the raw strings in `events.creators`, but also normalize each of
+//! them and link the event to the person with that normalized name (see
+//! `30-persons.sql`). Persons can be merged via the API.
+
+use tokio_postgres::GenericClient;
+
+use crate::prelude::*;
+
+
+/// How creator names are normalized before they are matched to persons.
+/// Configured via `sync.creators_*`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Normalization {
+    pub(crate) collapse_whitespace: bool,
+    pub(crate) flip_last_first: bool,
+}
+
+/// Normalizes a creator name. Returns `None` if nothing is left of it.
+pub(crate) fn normalize(raw: &str, options: Normalization) -> Option<String> {
+    let mut name = if options.collapse_whitespace {
+        raw.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        raw.to_owned()
+    };
+
+    if options.flip_last_first {
+        // Names with more than one comma (e.g. `King, Jr., Martin`) are
+        // ambiguous, so they are left alone.
+        let parts = name.split(',').map(str::trim).collect::<Vec<_>>();
+        if let [last, first] = parts[..] {
+            if !last.is_empty() && !first.is_empty() {
+                name = format!("{first} {last}");
+            }
+        }
+    }
+
+    (!name.trim().is_empty()).then_some(name)
+}
+
+/// Links the event to the persons of its `creators` (replacing all existing
+/// links) and updates its `creator_names`.
+pub(crate) async fn link(
+    db: &impl GenericClient,
+    event: i64,
+    creators: &[String],
+    options: Normalization,
+) -> Result<()> {
+    db.execute("delete from event_persons where event = $1", &[&event]).await?;
+    for (position, raw) in creators.iter().enumerate() {
+        let Some(name) = normalize(raw, options) else {
+            continue;
+        };
+        let person = find_or_create(db, &name).await?;
+
+        // If two creators refer to the same person, the first one counts.
+        db.execute(
+            "insert into event_persons (event, person, position) values ($1, $2, $3) \
+                on conflict do nothing",
+            &[&event, &person, &(position as i16)],
+        ).await?;
+    }
+    db.execute("select update_creator_names(array[$1::bigint])", &[&event]).await?;
+
+    Ok(())
+}
+
+/// Links all events that were not linked to persons yet (e.g. because they
+/// were synced before persons were introduced). Returns the number of linked
+/// events.
+pub(crate) async fn link_unlinked(db: &impl GenericClient, options: Normalization) -> Result<u64> {
+    let rows = db
+        .query("select id, creators from events where creator_names is null", &[])
+        .await
+        .context("failed to load events without linked persons")?;
+
+    for row in &rows {
+        link(db, row.get(0), &row.get::<_, Vec<String>>(1), options).await?;
+    }
+
+    Ok(rows.len() as u64)
+}
+
+/// Returns the ID of the person with the given normalized name, creating the
+/// person if there is none.
+async fn find_or_create(db: &impl GenericClient, name: &str) -> Result<i64> {
+    let key = name.to_lowercase();
+    let existing = db
+        .query_opt("select person from person_names where name = $1", &[&key])
+        .await?;
+    if let Some(row) = existing {
+        return Ok(row.get(0));
+    }
+
+    let person = db
+        .query_one("insert into persons (name) values ($1) returning id", &[&name])
+        .await?
+        .get::<_, i64>(0);
+    db.execute("insert into person_names (name, person) values ($1, $2)", &[&key, &person])
+        .await?;
+    Ok(person)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, Normalization};
+
+    #[test]
+    fn normalizes_names() {
+        let all = Normalization { collapse_whitespace: true, flip_last_first: true };
+        let norm = |raw| normalize(raw, all);
+        assert_eq!(norm("  Jane   Doe "), Some("Jane Doe".into()));
+        assert_eq!(norm("Doe, Jane"), Some("Jane Doe".into()));
+        assert_eq!(norm("Doe ,  Jane"), Some("Jane Doe".into()));
+        assert_eq!(norm("King, Jr., Martin"), Some("King, Jr., Martin".into()));
+        assert_eq!(norm("Doe,"), Some("Doe,".into()));
+        assert_eq!(norm("   "), None);
+        assert_eq!(norm(""), None);
+
+        let none = Normalization { collapse_whitespace: false, flip_last_first: false };
+        assert_eq!(normalize(" Doe,  Jane", none), Some(" Doe,  Jane".into()));
+        assert_eq!(normalize(" ", none), None);
+    }
+}
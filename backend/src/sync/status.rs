@@ -2,6 +2,7 @@ use chrono::{DateTime, NaiveDateTime, Utc};
 use tokio_postgres::GenericClient;
 
 use crate::prelude::*;
+use super::harvest::SkippedItem;
 
 
 /// Stored in the database to keep track of the Opencast <-> Tobira sync. For
@@ -32,4 +33,37 @@ impl SyncStatus {
 
         Ok(())
     }
+
+    /// Records items that were skipped because they could not be
+    /// deserialized, so that operators can follow up on them. Items without
+    /// Opencast ID cannot be recorded; they are only logged.
+    pub(super) async fn record_skipped_items(
+        items: &[SkippedItem],
+        db: &impl GenericClient,
+    ) -> Result<()> {
+        for item in items {
+            if let Some(opencast_id) = &item.opencast_id {
+                db.execute(
+                    "insert into sync_skipped_items (opencast_id, kind, error) \
+                        values ($1, $2, $3) \
+                        on conflict (opencast_id) do update \
+                        set kind = excluded.kind, error = excluded.error, skipped_at = now()",
+                    &[opencast_id, &item.kind, &item.error],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the item from the list of skipped items, as it was successfully
+    /// synced now.
+    pub(super) async fn forget_skipped_item(
+        opencast_id: &str,
+        db: &impl GenericClient,
+    ) -> Result<()> {
+        db.execute("delete from sync_skipped_items where opencast_id = $1", &[&opencast_id])
+            .await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,61 @@
+//! Tracking the processing state of videos uploaded via Tobira.
+
+use deadpool_postgres::Client;
+
+use crate::{config::Config, prelude::*};
+use super::OcClient;
+
+
+/// Long running task that regularly asks Opencast about the workflow state
+/// of all uploads that are still processing and marks failed ones. Uploads
+/// are marked as ready by the harvest once the event is synced. Runs next to
+/// the harvest, see `sync::run_daemon`.
+pub(crate) async fn poll_processing_states(
+    db: &Client,
+    client: &OcClient,
+    config: &Config,
+) -> ! {
+    loop {
+        if let Err(e) = update_processing_states(db, client).await {
+            error!("Error updating processing state of uploads: {e:?}");
+        }
+
+        tokio::time::sleep(config.sync.poll_period).await;
+    }
+}
+
+async fn update_processing_states(db: &Client, client: &OcClient) -> Result<()> {
+    let rows = db.query("select opencast_id from upload_jobs where state = 'processing'", &[])
+        .await
+        .context("failed to load processing uploads")?;
+
+    for row in rows {
+        let opencast_id: String = row.get(0);
+        let state = match client.get_processing_state(&opencast_id).await {
+            Ok(Some(state)) => state,
+            Ok(None) => {
+                trace!("Upload '{opencast_id}' not yet known to Opencast");
+                continue;
+            }
+            Err(e) => {
+                warn!("Could not fetch processing state of upload '{opencast_id}': {e:?}");
+                continue;
+            }
+        };
+
+        // Only some states are final failures. Everything else means that
+        // Opencast is either still processing or done, in which case the
+        // harvest will pick it up.
+        if state == "FAILED" || state == "STOPPED" {
+            let message = format!("Opencast workflow ended in state '{state}'");
+            db.execute(
+                "update upload_jobs set state = 'failed', error_message = $2, updated = now() \
+                    where opencast_id = $1 and state = 'processing'",
+                &[&opencast_id, &message],
+            ).await?;
+            info!("Upload '{opencast_id}' failed processing (state '{state}')");
+        }
+    }
+
+    Ok(())
+}
@@ -35,6 +35,9 @@ macro_rules! lazy_format {
 pub(crate) use lazy_format;
 
 
+/// Has to be appended to URLs to allow unencrypted HTTP for non-local hosts.
+pub(crate) const INSECURE_HTTP_SAFE_WORD: &str = "#allow-insecure";
+
 #[derive(Clone, Deserialize)]
 #[serde(try_from = "String")]
 pub(crate) struct HttpHost {
@@ -98,7 +101,7 @@ impl FromStr for HttpHost {
             }
         };
 
-        const SAFE_WORD: &str = "#allow-insecure";
+        const SAFE_WORD: &str = INSECURE_HTTP_SAFE_WORD;
         if scheme == uri::Scheme::HTTP && !(is_local || src.ends_with(SAFE_WORD)) {
             bail!("if you really want to use unencrypted HTTP for non-local hosts, \
                 confirm by specifing the host as 'http://{host}{SAFE_WORD}'");
@@ -0,0 +1,141 @@
+//! Waiting for the database and MeiliSearch on startup (`--wait-for-db`).
+//! In containerized deployments, Tobira is often started before its
+//! dependencies are ready. Instead of exiting immediately (causing restart
+//! loops), connecting is retried with backoff until a deadline.
+
+use std::{future::Future, time::Duration};
+use deadpool_postgres::Pool;
+use meilisearch_sdk::errors::{Error as MeiliError, ErrorType};
+use tokio::time::Instant;
+use tokio_postgres::error::SqlState;
+
+use crate::{db::{self, DbConfig}, prelude::*, search};
+
+
+/// Delay before the first retry. It is doubled after each failed attempt, up
+/// to `MAX_DELAY`.
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// Connecting to an unresponsive host can take very long, so single attempts
+/// are aborted after this time.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors Postgres returns if the user does not exist or the password is wrong.
+const AUTH_ERRORS: &[SqlState] = &[
+    SqlState::INVALID_PASSWORD,
+    SqlState::INVALID_AUTHORIZATION_SPECIFICATION,
+];
+
+
+/// Why an attempt to connect to a dependency failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The service could not be reached at all or is not ready yet.
+    Unreachable,
+    /// The service is reachable, but rejected our credentials.
+    AuthFailed,
+    /// The service is reachable, but the connection failed for another reason.
+    Other,
+}
+
+/// Creates the DB pool like `db::create_pool`, but retries until `deadline`
+/// if the database is not reachable or refuses the connection.
+pub(crate) async fn for_db(config: &DbConfig, deadline: Instant) -> Result<Pool> {
+    retry("the database", deadline, || async {
+        db::create_pool(config).await.map_err(|e| {
+            let failure = match e.chain().find_map(|c| c.downcast_ref::<tokio_postgres::Error>()) {
+                // Errors unrelated to Postgres (e.g. invalid TLS config or a
+                // non-UTF8 database) won't go away by waiting.
+                None => return (None, e),
+                Some(pg) => match pg.code() {
+                    // No code means that no Postgres server answered.
+                    None => Failure::Unreachable,
+                    Some(code) if *code == SqlState::CANNOT_CONNECT_NOW => Failure::Unreachable,
+                    Some(code) if AUTH_ERRORS.contains(code) => Failure::AuthFailed,
+                    Some(_) => Failure::Other,
+                },
+            };
+            (Some(failure), e)
+        })
+    }).await
+}
+
+/// Checks the connection to MeiliSearch like `Client::check_connection`, but
+/// retries until `deadline`. Additionally checks that the configured key is
+/// accepted.
+pub(crate) async fn for_meili(client: &search::Client, deadline: Instant) -> Result<()> {
+    retry("MeiliSearch", deadline, || async {
+        if let Err(e) = client.check_connection().await {
+            return Err((Some(Failure::Unreachable), e));
+        }
+
+        // The health endpoint does not require authentication.
+        match client.client.get_version().await {
+            Ok(_) => Ok(()),
+            Err(MeiliError::Meilisearch(e)) => {
+                let failure = match e.error_type {
+                    ErrorType::Auth => Failure::AuthFailed,
+                    _ => Failure::Other,
+                };
+                Err((Some(failure), anyhow!("{}", e.error_message)))
+            }
+            Err(e) => Err((Some(Failure::Unreachable), anyhow!("{e}"))),
+        }
+    }).await
+}
+
+/// Runs `attempt` until it succeeds or `deadline` is reached, sleeping with
+/// exponential backoff in between. Failures without `Failure` are returned
+/// immediately, as retrying them is pointless.
+async fn retry<T, F, Fut>(what: &str, deadline: Instant, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, (Option<Failure>, anyhow::Error)>>,
+{
+    let start = Instant::now();
+    let mut delay = INITIAL_DELAY;
+    let mut was_reachable = false;
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let outcome = tokio::time::timeout(ATTEMPT_TIMEOUT, attempt())
+            .await
+            .unwrap_or_else(|_| Err((
+                Some(Failure::Unreachable),
+                anyhow!("no response within {}s", ATTEMPT_TIMEOUT.as_secs()),
+            )));
+
+        let (failure, e) = match outcome {
+            Ok(out) => {
+                info!("Connected to {what} after {attempts} attempt(s) ({:.1}s)",
+                    start.elapsed().as_secs_f32());
+                return Ok(out);
+            }
+            Err((None, e)) => return Err(e),
+            Err((Some(failure), e)) => (failure, e),
+        };
+        was_reachable |= failure != Failure::Unreachable;
+        debug!("Attempt {attempts} to connect to {what} failed: {e:#}");
+
+        let now = Instant::now();
+        if now >= deadline {
+            let reason = match failure {
+                Failure::Unreachable if was_reachable => "it was reachable at first, \
+                    but not anymore",
+                Failure::Unreachable => "it was never reachable",
+                Failure::AuthFailed => "it is reachable, but authentication failed \
+                    (check the configured credentials)",
+                Failure::Other => "it is reachable, but refused the connection",
+            };
+            return Err(e.context(format!(
+                "gave up waiting for {what} after {attempts} attempts ({:.0}s): {reason}",
+                start.elapsed().as_secs_f32(),
+            )));
+        }
+
+        tokio::time::sleep(delay.min(deadline - now)).await;
+        delay = (delay * 2).min(MAX_DELAY);
+    }
+}
@@ -44,5 +44,6 @@ export const userDataFragment = graphql`
             canUseStudio
             canUseEditor
         }
+        ... AnnouncementData
     }
 `;
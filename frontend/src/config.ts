@@ -24,9 +24,13 @@ type Config = {
     version: VersionInfo;
     auth: AuthConfig;
     siteTitle: TranslatedString;
+    languages: string[];
+    continueWatching: boolean;
     opencast: OpencastConfig;
     footerLinks: FooterLink[];
     metadataLabels: Record<string, Record<string, MetadataLabel>>;
+    /** Page to show instead of `/`. Only set when `/` was requested. */
+    initialRoute: string | null;
     logo: LogoConfig;
     plyr: PlyrConfig;
 };
@@ -39,6 +43,7 @@ type FooterLink = "about" | "graphiql" | {
 type AuthConfig = {
     loginLink: string | null;
     logoutLink: string | null;
+    postLogoutLink: string | null;
     userIdLabel: TranslatedString | null;
     passwordLabel: TranslatedString | null;
     loginPageNote: TranslatedString | null;
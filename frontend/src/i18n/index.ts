@@ -5,6 +5,7 @@ import LanguageDetector from "i18next-browser-languagedetector";
 
 import enTranslations from "./locales/en.yaml";
 import deTranslations from "./locales/de.yaml";
+import CONFIG from "../config";
 
 export const languages = {
     en: { translation: enTranslations as ResourceLanguage },
@@ -17,12 +18,17 @@ void i18n
     .use(LanguageDetector)
     .init({
         resources: languages,
-        fallbackLng: "en",
+        fallbackLng: CONFIG.languages[0],
+        supportedLngs: CONFIG.languages,
+        nonExplicitSupportedLngs: true,
         interpolation: {
             escapeValue: false,
         },
         detection: {
-            order: ["localStorage", "navigator"],
+            // The `lang` attribute of the HTML tag is set by the server, which
+            // considers the user's session and `Accept-Language`.
+            order: ["querystring", "localStorage", "htmlTag", "navigator"],
+            lookupQuerystring: "lang",
         },
         react: {
             transKeepBasicHtmlNodesFor: ["br", "strong", "i", "p", "code"],
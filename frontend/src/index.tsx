@@ -1,10 +1,17 @@
 import ReactDOM from "react-dom/client";
 
 import { App } from "./App";
+import CONFIG from "./config";
 import "./i18n";
 import { matchInitialRoute } from "./router";
 
 
+// The backend decides which page is shown on `/` (`general.initial_page`).
+if (window.location.pathname === "/" && CONFIG.initialRoute && CONFIG.initialRoute !== "/") {
+    const { search, hash } = window.location;
+    window.history.replaceState(null, "", CONFIG.initialRoute + search + hash);
+}
+
 const initialRoute = matchInitialRoute();
 const root = document.createElement("div");
 document.body.appendChild(root);
@@ -0,0 +1,114 @@
+import React, { useState } from "react";
+import { useTranslation } from "react-i18next";
+import { graphql, useFragment, useMutation } from "react-relay";
+import { FiAlertOctagon, FiAlertTriangle, FiInfo, FiX } from "react-icons/fi";
+
+import { TextBlock } from "../ui/Blocks/Text";
+import { match } from "../util";
+import { OUTER_CONTAINER_MARGIN } from ".";
+import { AnnouncementData$key } from "./__generated__/AnnouncementData.graphql";
+import { AnnouncementDismissMutation } from "./__generated__/AnnouncementDismissMutation.graphql";
+
+
+export const announcementFragment = graphql`
+    fragment AnnouncementData on Query {
+        announcement { id title body severity dismissed }
+    }
+`;
+
+const dismissMutation = graphql`
+    mutation AnnouncementDismissMutation($id: ID!) {
+        dismissAnnouncement(id: $id)
+    }
+`;
+
+/**
+ * Users without a Tobira session (e.g. anonymous users) cannot store their
+ * dismissal in the backend, so we remember it in the session storage instead.
+ */
+const storageKey = (id: string) => `tobiraDismissedAnnouncement-${id}`;
+
+type Props = {
+    fragRef: AnnouncementData$key;
+};
+
+/** The global announcement (e.g. about maintenance) shown above all pages. */
+export const Announcement: React.FC<Props> = ({ fragRef }) => {
+    const { t } = useTranslation();
+    const { announcement } = useFragment(announcementFragment, fragRef);
+    const [commit] = useMutation<AnnouncementDismissMutation>(dismissMutation);
+    const [dismissedNow, setDismissedNow] = useState(false);
+
+    if (!announcement || announcement.dismissed || dismissedNow
+        || window.sessionStorage.getItem(storageKey(announcement.id)) !== null) {
+        return null;
+    }
+
+    const dismiss = () => {
+        setDismissedNow(true);
+        commit({
+            variables: { id: announcement.id },
+            onCompleted: ({ dismissAnnouncement: storedInBackend }) => {
+                if (!storedInBackend) {
+                    window.sessionStorage.setItem(storageKey(announcement.id), "1");
+                }
+            },
+        });
+    };
+
+    return (
+        <div role="alert" css={{
+            margin: OUTER_CONTAINER_MARGIN,
+            padding: "0 16px",
+            marginBottom: 16,
+        }}>
+            <div css={{
+                display: "flex",
+                gap: 16,
+                alignItems: "flex-start",
+                borderRadius: 4,
+                padding: "8px 16px",
+                "& > svg": { fontSize: 24, minWidth: 24, marginTop: 2 },
+                ...match(announcement.severity, {
+                    "INFO": () => ({
+                        backgroundColor: "var(--grey97)",
+                    }) as Record<string, string>,
+                    "WARNING": () => ({
+                        backgroundColor: "var(--grey97)",
+                        border: "1.5px solid var(--danger-color)",
+                    }),
+                    "CRITICAL": () => ({
+                        backgroundColor: "var(--danger-color)",
+                        color: "var(--danger-color-bw-contrast)",
+                    }),
+                }, () => ({})),
+            }}>
+                {match(announcement.severity, {
+                    "INFO": () => <FiInfo css={{ color: "var(--grey40)" }} />,
+                    "WARNING": () => <FiAlertTriangle css={{ color: "var(--danger-color)" }} />,
+                    "CRITICAL": () => <FiAlertOctagon />,
+                }, () => <FiInfo />)}
+                <div css={{ flex: 1, minWidth: 0 }}>
+                    <strong>{announcement.title}</strong>
+                    <div css={{ marginTop: 4 }}>
+                        <TextBlock content={announcement.body} />
+                    </div>
+                </div>
+                <button
+                    title={t("announcement.dismiss")}
+                    aria-label={t("announcement.dismiss")}
+                    onClick={dismiss}
+                    css={{
+                        border: "none",
+                        background: "none",
+                        color: "inherit",
+                        cursor: "pointer",
+                        padding: 4,
+                        fontSize: 20,
+                        display: "flex",
+                    }}
+                ><FiX /></button>
+            </div>
+        </div>
+    );
+};
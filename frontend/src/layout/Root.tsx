@@ -14,16 +14,20 @@ import { GraphQLTaggedNode, PreloadedQuery, useFragment, usePreloadedQuery } fro
 import { OperationType } from "relay-runtime";
 import { UserData$key } from "../__generated__/UserData.graphql";
 import { useNoindexTag } from "../util";
+import { Announcement } from "./Announcement";
+import { AnnouncementData$key } from "./__generated__/AnnouncementData.graphql";
 
 
 export const MAIN_PADDING = 16;
 
 type Props = {
     nav: NavItems;
+    /** Query data containing the global announcement, if it was loaded. */
+    announcement?: AnnouncementData$key;
     children: ReactNode;
 };
 
-export const Root: React.FC<Props> = ({ nav, children }) => {
+export const Root: React.FC<Props> = ({ nav, announcement, children }) => {
     const menu = useMenu();
     const navElements = Array.isArray(nav) ? nav : [nav] as const;
     const navExists = navElements.length > 0;
@@ -41,6 +45,7 @@ export const Root: React.FC<Props> = ({ nav, children }) => {
                     backgroundColor: "var(--grey92)",
                 }}/>
             </div>
+            {announcement && <Announcement fragRef={announcement} />}
             <Main>
                 {/* Sidebar */}
                 {navExists && <div css={{
@@ -158,7 +163,7 @@ export const RootLoaderImpl = <Q extends QueryWithUserData>({
 
     return (
         <UserProvider data={userData?.currentUser}>
-            <Root nav={nav(data)}>{render(data)}</Root>
+            <Root nav={nav(data)} announcement={userData}>{render(data)}</Root>
         </UserProvider>
     );
 };
@@ -2,6 +2,7 @@ import { TFunction } from "i18next";
 import React, { MutableRefObject, KeyboardEvent, ReactNode } from "react";
 import { useRef, useState } from "react";
 import { useTranslation } from "react-i18next";
+import { commitMutation, graphql } from "react-relay";
 import {
     FiAlertTriangle,
     FiCheck, FiChevronLeft, FiFilm, FiLogIn, FiLogOut,
@@ -18,9 +19,10 @@ import { match } from "../../util";
 import { ActionIcon } from "./ui";
 import CONFIG from "../../config";
 import { Spinner } from "../../ui/Spinner";
-import { LOGIN_PATH } from "../../routes/paths";
-import { REDIRECT_STORAGE_KEY } from "../../routes/Login";
+import { loginPath } from "../../routes/Login";
 import { FOCUS_STYLE_INSET } from "../../ui";
+import { environment } from "../../relay";
+import { UserBoxSetLanguageMutation } from "./__generated__/UserBoxSetLanguageMutation.graphql";
 
 
 /** User-related UI in the header. */
@@ -67,11 +69,7 @@ const LoggedOut: React.FC<LoggedOutProps> = ({ menu }) => {
     return (
         <div ref={ref} css={{ display: "flex", padding: "8px 0" }}>
             <Link
-                to={CONFIG.auth.loginLink ?? LOGIN_PATH}
-                onClick={() => {
-                    // Store a redirect link in session storage.
-                    window.sessionStorage.setItem(REDIRECT_STORAGE_KEY, window.location.href);
-                }}
+                to={loginPath()}
                 htmlLink={!!CONFIG.auth.loginLink}
                 css={{
                     alignSelf: "center",
@@ -208,7 +206,7 @@ const Menu: React.FC<MenuProps> = ({ close, container }) => {
                 <MenuItem
                     icon={<FiLogIn />}
                     borderBottom
-                    linkTo={CONFIG.auth.loginLink ?? LOGIN_PATH}
+                    linkTo={loginPath()}
                     htmlLink={!!CONFIG.auth.loginLink}
                     css={{
                         color: "var(--nav-color)",
@@ -243,7 +241,7 @@ const Menu: React.FC<MenuProps> = ({ close, container }) => {
             <MenuItem icon={<FiChevronLeft />} onClick={() => setState("main")} borderBottom>
                 {t("back")}
             </MenuItem>
-            <LanguageMenu />
+            <LanguageMenu loggedIn={!!user} />
         </>,
     });
 
@@ -267,16 +265,36 @@ const Menu: React.FC<MenuProps> = ({ close, container }) => {
     );
 };
 
-/** Entries in the menu related to language. */
-const LanguageMenu: React.FC = () => {
+const setLanguageMutation = graphql`
+    mutation UserBoxSetLanguageMutation($lang: String!) {
+        setLanguage(lang: $lang)
+    }
+`;
+
+/**
+ * Entries in the menu related to language. For logged in users, the choice is
+ * also stored in their session, so that the server uses it as well.
+ */
+const LanguageMenu: React.FC<{ loggedIn: boolean }> = ({ loggedIn }) => {
     const { t, i18n } = useTranslation();
 
+    const choose = (lang: string) => {
+        void i18n.changeLanguage(lang);
+        if (loggedIn) {
+            commitMutation<UserBoxSetLanguageMutation>(environment, {
+                mutation: setLanguageMutation,
+                variables: { lang },
+                onError: error => console.warn("Failed to store language: ", error),
+            });
+        }
+    };
+
     return <>
-        {Object.keys(languages).map(lng => (
+        {Object.keys(languages).filter(lng => CONFIG.languages.includes(lng)).map(lng => (
             <MenuItem
                 key={lng}
                 icon={lng === i18n.resolvedLanguage ? <FiCheck /> : undefined}
-                onClick={() => i18n.changeLanguage(lng)}
+                onClick={() => choose(lng)}
             >{t("language-name", { lng })}</MenuItem>
         ))}
     </>;
@@ -385,8 +403,8 @@ const Logout: React.FC = () => {
                         // We deliberately ignore the `status`. See `handle_logout`
                         // for more information.
                         //
-                        // We hard forward to the home page to get rid of any stale state.
-                        window.location.href = "/";
+                        // We hard forward to get rid of any stale state.
+                        window.location.href = CONFIG.auth.postLogoutLink ?? "/";
                     })
                     .catch(error => {
                         // TODO: this is not great. It should happen only
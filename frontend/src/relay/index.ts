@@ -15,11 +15,11 @@ import { NetworkError } from "../util/err";
 export const environment = new Environment({
     store: new Store(new RecordSource()),
     network: Network.create(
-        async ({ text: query }, variables) => {
+        async ({ text: query, name: operationName }, variables) => {
             const response = await fetch("/graphql", {
                 method: "POST",
                 headers: { "Content-Type": "application/json" },
-                body: JSON.stringify({ query, variables }),
+                body: JSON.stringify({ query, variables, operationName }),
             }).catch(e => { throw new NetworkError(e); });
 
             if (!response.ok) {
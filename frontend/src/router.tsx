@@ -16,6 +16,7 @@ import { InvalidUrlRoute } from "./routes/InvalidUrl";
 import { BlockEmbedRoute, EmbedVideoRoute } from "./routes/Embed";
 import { ManageVideoDetailsRoute } from "./routes/manage/Video/Details";
 import { ManageVideoTechnicalDetailsRoute } from "./routes/manage/Video/TechnicalDetails";
+import { ManageVideoAccessRoute } from "./routes/manage/Video/Access";
 
 
 
@@ -44,6 +45,7 @@ const {
         ManageVideosRoute,
         ManageVideoDetailsRoute,
         ManageVideoTechnicalDetailsRoute,
+        ManageVideoAccessRoute,
         ManageRealmRoute,
         UploadRoute,
         AddChildRoute,
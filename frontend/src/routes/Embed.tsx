@@ -20,6 +20,7 @@ const query = graphql`
             __typename
             ... on NotAllowed { dummy }
             ... on AuthorizedEvent {
+                id
                 title
                 created
                 isLive
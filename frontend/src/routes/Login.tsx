@@ -22,7 +22,25 @@ import { LOGIN_PATH } from "./paths";
 import { makeRoute } from "../rauta";
 
 
-export const REDIRECT_STORAGE_KEY = "tobira-redirect-after-login";
+/**
+ * Returns the path of the login page with the current page as `return_to`
+ * target. If `auth.login_link` is set, the backend redirects from there to
+ * the external login, remembering the target.
+ */
+export const loginPath = (): string => {
+    const { pathname, search, hash } = window.location;
+    return `${LOGIN_PATH}?return_to=${encodeURIComponent(pathname + search + hash)}`;
+};
+
+/**
+ * Returns the `return_to` target of the current URL if it is a same-origin
+ * relative path, and "/" otherwise. Same rules as in the backend
+ * (`auth::return_to::validate`) to prevent open redirects.
+ */
+const returnTarget = (): string => {
+    const target = new URLSearchParams(window.location.search).get("return_to");
+    return target && /^\/(?!\/)[\x21-\x5B\x5D-\x7E]*$/.test(target) ? target : "/";
+};
 
 export const LoginRoute = makeRoute(url => {
     if (url.pathname !== LOGIN_PATH) {
@@ -55,9 +73,7 @@ const Login: React.FC<Props> = ({ queryRef }) => {
 
     React.useEffect(() => {
         if (isLoggedIn) {
-            const redirectTo = window.sessionStorage.getItem(REDIRECT_STORAGE_KEY) ?? "/";
-            window.sessionStorage.removeItem(REDIRECT_STORAGE_KEY);
-            router.goto(redirectTo, true);
+            router.goto(returnTarget(), true);
         }
     });
 
@@ -139,13 +155,12 @@ const LoginBox: React.FC = () => {
             // requests.
             setState("success");
 
-            // We hard forward to the home page. We do that to invalidate every
-            // data that we might have cached. It's probably be possible to
-            // wipe the relay cache manually, but I cannot figure it out right
-            // now. And well, this way we are sure everything is reloaded.
-            const redirectTo = window.sessionStorage.getItem(REDIRECT_STORAGE_KEY) ?? "/";
-            window.sessionStorage.removeItem(REDIRECT_STORAGE_KEY);
-            window.location.href = redirectTo;
+            // We hard forward to the page the user came from. We do that to
+            // invalidate every data that we might have cached. It's probably
+            // be possible to wipe the relay cache manually, but I cannot
+            // figure it out right now. And well, this way we are sure
+            // everything is reloaded.
+            window.location.href = returnTarget();
         } else if (response.status === 403) {
             // 403 Forbidden means the login data was incorrect
             setState("idle");
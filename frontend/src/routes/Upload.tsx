@@ -1,13 +1,14 @@
 import React, { ReactNode, useEffect, useRef, useState } from "react";
 import { useTranslation } from "react-i18next";
-import { graphql } from "react-relay";
+import { commitMutation, graphql } from "react-relay";
 import { keyframes } from "@emotion/react";
 import { useForm } from "react-hook-form";
 import { FiCheckCircle, FiUpload } from "react-icons/fi";
 
 import { RootLoader } from "../layout/Root";
-import { loadQuery } from "../relay";
+import { environment, loadQuery } from "../relay";
 import { UploadQuery } from "./__generated__/UploadQuery.graphql";
+import { UploadRegisterMutation } from "./__generated__/UploadRegisterMutation.graphql";
 import { UPLOAD_PATH } from "./paths";
 import { makeRoute } from "../rauta";
 import { assertNever, bug, ErrorDisplay, errorDisplayInfo, unreachable } from "../util/err";
@@ -48,6 +49,12 @@ const query = graphql`
     }
 `;
 
+const registerMutation = graphql`
+    mutation UploadRegisterMutation($opencastId: String!, $title: String!) {
+        registerUpload(opencastId: $opencastId, title: $title) { state }
+    }
+`;
+
 
 type Metadata = {
     title: string;
@@ -826,6 +833,20 @@ const finishUpload = async (
             await ocRequest("/ingest/ingest", { method: "post", body: body });
         }
 
+        // Let Tobira know about the upload so that its processing state can
+        // be shown. A failure here does not affect the upload itself.
+        const opencastId = new DOMParser()
+            .parseFromString(mediaPackage, "text/xml")
+            .documentElement
+            .getAttribute("id");
+        if (opencastId) {
+            commitMutation<UploadRegisterMutation>(environment, {
+                mutation: registerMutation,
+                variables: { opencastId, title: metadata.title },
+                onError: error => console.warn("Failed to register upload: ", error),
+            });
+        }
+
         setUploadState({ state: "done" });
     } catch (error) {
         setUploadState({ state: "error", error });
@@ -50,6 +50,7 @@ import { getEventTimeInfo } from "../util/video";
 import { Creators } from "../ui/Video";
 import { Description } from "../ui/metadata";
 import { ellipsisOverflowCss } from "../ui";
+import { Card } from "../ui/Card";
 
 
 // ===========================================================================================
@@ -211,8 +212,10 @@ const eventFragment = graphql`
             creators
             created
             isLive
+            isProcessing
             opencastId
             metadata
+            license { name link }
             canWrite
             syncedData {
                 updated
@@ -290,6 +293,9 @@ const VideoPage: React.FC<Props> = ({ eventRef, realmRef, basePath }) => {
     return <>
         <Breadcrumbs path={breadcrumbs} tail={event.title} />
         <script type="application/ld+json">{JSON.stringify(structuredData)}</script>
+        {event.isProcessing && <Card kind="info" css={{ marginBottom: 16 }}>
+            {t("video.processing")}
+        </Card>}
         <InlinePlayer event={event} css={{ margin: "0 auto" }} onEventStateChange={rerender} />
         <Metadata id={event.id} event={event} />
 
@@ -525,10 +531,15 @@ const MetadataTable: React.FC<MetadataTableProps> = ({ event }) => {
                         "builtin:source": () => t("video.source"),
                     });
 
-                const values = metadataNs[field].map((value, i) => <React.Fragment key={i}>
-                    {i > 0 && <br />}
-                    {isValidLink(value) ? <Link to={value}>{value}</Link> : value}
-                </React.Fragment>);
+                // For the license, we show the normalized name instead of the
+                // raw value, if the backend knows it.
+                const license = label === "builtin:license" ? event.license : null;
+                const values = license
+                    ? license.link ? <Link to={license.link}>{license.name}</Link> : license.name
+                    : metadataNs[field].map((value, i) => <React.Fragment key={i}>
+                        {i > 0 && <br />}
+                        {isValidLink(value) ? <Link to={value}>{value}</Link> : value}
+                    </React.Fragment>);
 
                 pairs.push([translatedLabel, values]);
             }
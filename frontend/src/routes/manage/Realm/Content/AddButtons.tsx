@@ -2,7 +2,7 @@ import React, { useRef, useState } from "react";
 import { useTranslation } from "react-i18next";
 import { graphql, useFragment, commitLocalUpdate, useRelayEnvironment } from "react-relay";
 import type { RecordProxy, RecordSourceProxy } from "relay-runtime";
-import { FiPlus, FiType, FiGrid, FiFilm, FiHash } from "react-icons/fi";
+import { FiPlus, FiType, FiGrid, FiFilm, FiHash, FiList } from "react-icons/fi";
 
 import { AddButtonsRealmData$key } from "./__generated__/AddButtonsRealmData.graphql";
 import { bug } from "../../../../util/err";
@@ -141,6 +141,16 @@ export const AddButtons: React.FC<Props> = ({ index, realm }) => {
                             block.setValue(true, "showTitle");
                         })}
                     />
+                    <AddItem
+                        close={() => setOpened(false)}
+                        Icon={FiList}
+                        label={t("manage.realm.content.add-toc")}
+                        onClick={() => addBlock("Toc", (_store, block) => {
+                            block.setValue(2, "depth");
+                            block.setValue(false, "showCounts");
+                            block.setValue("INHERIT", "order");
+                        })}
+                    />
                 </ul>
             </div>}
         </div>
@@ -11,6 +11,7 @@ import { EditModeForm } from ".";
 import { Heading, NiceRadio, NiceRadioOption } from "./util";
 import type {
     VideoListOrder,
+    VideoListLayout,
     SeriesEditModeBlockData$key,
 } from "./__generated__/SeriesEditModeBlockData.graphql";
 import {
@@ -29,6 +30,7 @@ type SeriesFormData = {
     series: string;
     order: VideoListOrder;
     layout: Layout;
+    videoLayout: VideoListLayout;
 };
 
 type Layout = "videos-only" | "title-and-videos" | "description-and-videos" | "everything";
@@ -52,7 +54,7 @@ export const EditSeriesBlock: React.FC<EditSeriesBlockProps> = ({ block: blockRe
         }
     `, useContext(ContentManageQueryContext) as SeriesEditModeSeriesData$key);
 
-    const { series, showTitle, showMetadata, order } = useFragment(graphql`
+    const { series, showTitle, showMetadata, order, layout } = useFragment(graphql`
         fragment SeriesEditModeBlockData on SeriesBlock {
             series {
                 id
@@ -65,6 +67,7 @@ export const EditSeriesBlock: React.FC<EditSeriesBlockProps> = ({ block: blockRe
             showTitle
             showMetadata
             order
+            layout
         }
     `, blockRef);
     const currentLayout = showTitle
@@ -89,7 +92,7 @@ export const EditSeriesBlock: React.FC<EditSeriesBlockProps> = ({ block: blockRe
         }
     `);
 
-    const mapFormData = ({ layout, order, series }: SeriesFormData) => {
+    const mapFormData = ({ layout, order, series, videoLayout }: SeriesFormData) => {
         const [showTitle, showMetadata] = match(layout, {
             "videos-only": () => [false, false],
             "title-and-videos": () => [true, false],
@@ -97,7 +100,7 @@ export const EditSeriesBlock: React.FC<EditSeriesBlockProps> = ({ block: blockRe
             "everything": () => [true, true],
         });
 
-        return { series, order, showTitle, showMetadata };
+        return { series, order, showTitle, showMetadata, layout: videoLayout };
     };
 
     const { t } = useTranslation();
@@ -141,10 +144,25 @@ export const EditSeriesBlock: React.FC<EditSeriesBlockProps> = ({ block: blockRe
                 defaultChecked={order === "OLD_TO_NEW"}
                 {...form.register("order")}
             >{t("manage.realm.content.series.order.old-to-new")}</NiceRadioOption>
+            <NiceRadioOption
+                value="ALPHABETIC"
+                defaultChecked={order === "ALPHABETIC"}
+                {...form.register("order")}
+            >{t("manage.realm.content.series.order.alphabetic")}</NiceRadioOption>
         </NiceRadio>
 
         <Heading>{t("manage.realm.content.series.layout.heading")}</Heading>
         <LayoutChooser {...{ currentLayout, form }} />
+
+        <Heading>{t("manage.realm.content.series.video-layout.heading")}</Heading>
+        <NiceRadio breakpoint={0}>
+            {(["GRID", "LIST", "SLIDER"] as const).map(value => <NiceRadioOption
+                key={value}
+                value={value}
+                defaultChecked={layout === value}
+                {...form.register("videoLayout")}
+            >{t(`manage.realm.content.series.video-layout.${value.toLowerCase()}`)}</NiceRadioOption>)}
+        </NiceRadio>
     </EditModeForm>;
 };
 
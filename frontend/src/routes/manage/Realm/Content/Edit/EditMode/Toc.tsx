@@ -0,0 +1,94 @@
+import React from "react";
+import { useTranslation } from "react-i18next";
+import { graphql, useFragment, useMutation } from "react-relay";
+import { useFormContext } from "react-hook-form";
+
+import { Select } from "../../../../../../ui/Input";
+import { EditModeForm } from ".";
+import { Heading, NiceRadio, NiceRadioOption } from "./util";
+import type {
+    TocOrder,
+    TocEditModeBlockData$key,
+} from "./__generated__/TocEditModeBlockData.graphql";
+import type { TocEditSaveMutation } from "./__generated__/TocEditSaveMutation.graphql";
+import type { TocEditCreateMutation } from "./__generated__/TocEditCreateMutation.graphql";
+
+
+type TocFormData = {
+    depth: string;
+    showCounts: boolean;
+    order: TocOrder;
+};
+
+type EditTocBlockProps = {
+    block: TocEditModeBlockData$key;
+};
+
+export const EditTocBlock: React.FC<EditTocBlockProps> = ({ block: blockRef }) => {
+    const { depth, showCounts, order } = useFragment(graphql`
+        fragment TocEditModeBlockData on TocBlock {
+            depth
+            showCounts
+            order
+        }
+    `, blockRef);
+
+
+    const [save] = useMutation<TocEditSaveMutation>(graphql`
+        mutation TocEditSaveMutation($id: ID!, $set: UpdateTocBlock!) {
+            updateTocBlock(id: $id, set: $set) {
+                ... BlocksBlockData
+            }
+        }
+    `);
+
+    const [create] = useMutation<TocEditCreateMutation>(graphql`
+        mutation TocEditCreateMutation($realm: ID!, $index: Int!, $block: NewTocBlock!) {
+            addTocBlock(realm: $realm, index: $index, block: $block) {
+                ... ContentManageRealmData
+            }
+        }
+    `);
+
+    const mapFormData = ({ depth, ...rest }: TocFormData) => ({
+        depth: parseInt(depth, 10),
+        ...rest,
+    });
+
+
+    const { t } = useTranslation();
+
+    const form = useFormContext<TocFormData>();
+
+    return <EditModeForm create={create} save={save} map={mapFormData}>
+        <Heading>{t("manage.realm.content.toc.depth.heading")}</Heading>
+        <Select defaultValue={depth} {...form.register("depth")}>
+            {[1, 2, 3, 4].map(d => <option key={d} value={d}>{d}</option>)}
+        </Select>
+
+        <Heading>{t("manage.realm.content.toc.order.heading")}</Heading>
+        <NiceRadio breakpoint={0}>
+            <NiceRadioOption
+                value="INHERIT"
+                defaultChecked={order === "INHERIT"}
+                {...form.register("order")}
+            >{t("manage.realm.content.toc.order.inherit")}</NiceRadioOption>
+            <NiceRadioOption
+                value="ALPHABETIC_ASC"
+                defaultChecked={order === "ALPHABETIC_ASC"}
+                {...form.register("order")}
+            >{t("manage.realm.content.toc.order.alphabetic-asc")}</NiceRadioOption>
+            <NiceRadioOption
+                value="ALPHABETIC_DESC"
+                defaultChecked={order === "ALPHABETIC_DESC"}
+                {...form.register("order")}
+            >{t("manage.realm.content.toc.order.alphabetic-desc")}</NiceRadioOption>
+        </NiceRadio>
+
+        <Heading>{t("manage.realm.content.toc.counts.heading")}</Heading>
+        <label>
+            <input type="checkbox" defaultChecked={showCounts} {...form.register("showCounts")} />
+            {t("manage.realm.content.toc.counts.show")}
+        </label>
+    </EditModeForm>;
+};
@@ -13,6 +13,7 @@ import { EditTitleBlock } from "./Title";
 import { EditTextBlock } from "./Text";
 import { EditSeriesBlock } from "./Series";
 import { EditVideoBlock } from "./Video";
+import { EditTocBlock } from "./Toc";
 
 
 type EditModeProps = {
@@ -41,6 +42,7 @@ export const EditMode: React.FC<EditModeProps> = props => {
                 ... on TextBlock { ...TextEditModeBlockData }
                 ... on SeriesBlock { ...SeriesEditModeBlockData }
                 ... on VideoBlock { ...VideoEditModeBlockData }
+                ... on TocBlock { ...TocEditModeBlockData }
             }
             ...EditModeFormRealmData
         }
@@ -57,6 +59,7 @@ export const EditMode: React.FC<EditModeProps> = props => {
                 TextBlock: () => <EditTextBlock block={block} />,
                 SeriesBlock: () => <EditSeriesBlock block={block} />,
                 VideoBlock: () => <EditVideoBlock block={block} />,
+                TocBlock: () => <EditTocBlock block={block} />,
             }, () => bug("unknown block type"))}
         </FormProvider>
     </EditModeFormContext.Provider>;
@@ -0,0 +1,188 @@
+import { useState } from "react";
+import { useTranslation } from "react-i18next";
+import { graphql, useFragment, useMutation } from "react-relay";
+
+import { NotAuthorized, boxError } from "../../../ui/error";
+import { useUser } from "../../../User";
+import { Breadcrumbs } from "../../../ui/Breadcrumbs";
+import { PageTitle } from "../../../layout/header/ui";
+import { Button } from "../../../ui/Button";
+import { displayCommitError } from "../Realm/util";
+import { AuthorizedEvent, makeManageVideoRoute, PAGE_WIDTH } from "./Shared";
+import { AccessAclData$data, AccessAclData$key } from "./__generated__/AccessAclData.graphql";
+import { AccessApplyAclPresetMutation } from "./__generated__/AccessApplyAclPresetMutation.graphql";
+
+
+export const ManageVideoAccessRoute = makeManageVideoRoute(
+    "acl",
+    "/access",
+    (event, data) => <Page event={event} aclRef={data} />,
+);
+
+const fragment = graphql`
+    fragment AccessAclData on Query {
+        aclPresets { id label { en de } readRoles writeRoles }
+        roleDisplayNames { role name { en de } }
+    }
+`;
+
+const applyMutation = graphql`
+    mutation AccessApplyAclPresetMutation($event: ID!, $preset: String!) {
+        applyAclPreset(event: $event, preset: $preset) {
+            id
+            readRoles
+            writeRoles
+        }
+    }
+`;
+
+type TranslatedText = AccessAclData$data["roleDisplayNames"][number]["name"];
+
+type Props = {
+    event: AuthorizedEvent;
+    aclRef: AccessAclData$key;
+};
+
+const Page: React.FC<Props> = ({ event, aclRef }) => {
+    const { t } = useTranslation();
+    const data = useFragment(fragment, aclRef);
+
+    const breadcrumbs = [
+        { label: t("manage.management"), link: "/~manage" },
+        { label: t("manage.my-videos.title"), link: "/~manage/videos" },
+        { label: event.title, link: `/~manage/videos/${event.id.substring(2)}` },
+    ];
+
+    const user = useUser();
+    if (user === "none" || user === "unknown") {
+        return <NotAuthorized />;
+    }
+
+    return <>
+        <Breadcrumbs path={breadcrumbs} tail={t("manage.my-videos.acl.title")} />
+        <PageTitle title={t("manage.my-videos.acl.title")} />
+        <div css={{
+            maxWidth: PAGE_WIDTH,
+            "& > section:not(:last-child)": {
+                marginBottom: 32,
+            },
+            "& > section > h2": {
+                fontSize: 18,
+                marginBottom: 8,
+            },
+        }}>
+            <CurrentAcl event={event} data={data} />
+            <Presets event={event} data={data} />
+        </div>
+    </>;
+};
+
+type SectionProps = {
+    event: AuthorizedEvent;
+    data: AccessAclData$data;
+};
+
+const CurrentAcl: React.FC<SectionProps> = ({ event, data }) => {
+    const { t } = useTranslation();
+
+    return <section>
+        <h2>{t("manage.my-videos.acl.current")}</h2>
+        <RoleList
+            label={t("manage.my-videos.acl.read")}
+            roles={event.readRoles ?? []}
+            data={data}
+        />
+        <RoleList
+            label={t("manage.my-videos.acl.write")}
+            roles={event.writeRoles ?? []}
+            data={data}
+        />
+    </section>;
+};
+
+type RoleListProps = {
+    label: string;
+    roles: readonly string[];
+    data: AccessAclData$data;
+};
+
+/** Shows roles by their configured display name, if any, or as raw string otherwise. */
+const RoleList: React.FC<RoleListProps> = ({ label, roles, data }) => {
+    const translate = useTranslate();
+
+    return <div css={{ marginBottom: 8 }}>
+        <span css={{ color: "var(--grey40)" }}>{label + ":"}</span>
+        <ul css={{ margin: "4px 0" }}>
+            {roles.map(role => {
+                const name = data.roleDisplayNames.find(n => n.role === role)?.name;
+                return <li key={role}>
+                    {name ? translate(name) : <code css={{ fontSize: 14 }}>{role}</code>}
+                </li>;
+            })}
+        </ul>
+    </div>;
+};
+
+const Presets: React.FC<SectionProps> = ({ event, data }) => {
+    const { t } = useTranslation();
+    const translate = useTranslate();
+    const [commit, isInFlight] = useMutation<AccessApplyAclPresetMutation>(applyMutation);
+    const [commitError, setCommitError] = useState<JSX.Element | null>(null);
+
+    if (data.aclPresets.length === 0) {
+        return null;
+    }
+
+    const apply = (preset: string) => {
+        setCommitError(null);
+        commit({
+            variables: { event: event.id, preset },
+            onError: error => {
+                const failure = t("manage.my-videos.acl.apply-failed");
+                setCommitError(displayCommitError(error, failure));
+            },
+        });
+    };
+
+    const isCurrent = (preset: AccessAclData$data["aclPresets"][number]) => (
+        sameRoles(preset.readRoles, event.readRoles)
+            && sameRoles(preset.writeRoles, event.writeRoles)
+    );
+
+    return <section>
+        <h2>{t("manage.my-videos.acl.presets")}</h2>
+        <p css={{ fontSize: 14, marginBottom: 8 }}>
+            {t("manage.my-videos.acl.presets-explanation")}
+        </p>
+        <ul css={{ listStyle: "none", padding: 0 }}>
+            {data.aclPresets.map(preset => <li key={preset.id} css={{
+                display: "flex",
+                alignItems: "center",
+                gap: 12,
+                marginBottom: 8,
+            }}>
+                <Button
+                    disabled={isInFlight || isCurrent(preset)}
+                    onClick={() => apply(preset.id)}
+                >{t("manage.my-videos.acl.apply")}</Button>
+                <span>{translate(preset.label)}</span>
+                {isCurrent(preset) && <i css={{ color: "var(--grey40)" }}>
+                    {t("manage.my-videos.acl.is-current")}
+                </i>}
+            </li>)}
+        </ul>
+        {boxError(commitError)}
+    </section>;
+};
+
+const sameRoles = (a: readonly string[], b: readonly string[] | null): boolean => (
+    b !== null && a.length === b.length && a.every(role => b.includes(role))
+);
+
+/** Returns a function picking the text of the current language, falling back to English. */
+const useTranslate = () => {
+    const { i18n } = useTranslation();
+    return (text: TranslatedText): string => (
+        (i18n.resolvedLanguage === "de" ? text.de : null) ?? text.en
+    );
+};
@@ -1,5 +1,5 @@
 import { useTranslation } from "react-i18next";
-import { FiCornerLeftUp, FiEdit3, FiInfo, FiPlay } from "react-icons/fi";
+import { FiCornerLeftUp, FiEdit3, FiInfo, FiPlay, FiShield } from "react-icons/fi";
 import { graphql } from "react-relay";
 
 import { RootLoader } from "../../../layout/Root";
@@ -21,13 +21,13 @@ export type QueryResponse = SharedVideoManageQuery["response"];
 export type Event = QueryResponse["event"];
 export type AuthorizedEvent = Extract<Event, { __typename: "AuthorizedEvent" }>;
 
-type ManageVideoSubPageType = "details" | "technical-details";
+type ManageVideoSubPageType = "details" | "technical-details" | "acl";
 
 /** Helper around `makeRoute` for manage single video subpages. */
 export const makeManageVideoRoute = (
     page: ManageVideoSubPageType,
     path: string,
-    render: (event: AuthorizedEvent) => JSX.Element,
+    render: (event: AuthorizedEvent, data: QueryResponse) => JSX.Element,
 ): Route => (
     makeRoute(url => {
         const regex = new RegExp(`^/~manage/videos/(${b64regex}+)${path}/?$`, "u");
@@ -55,7 +55,7 @@ export const makeManageVideoRoute = (
                         return <NotAuthorized />;
                     }
 
-                    return render(data.event);
+                    return render(data.event, data);
                 }}
             />,
             dispose: () => queryRef.dispose(),
@@ -70,6 +70,7 @@ export const makeManageVideoRoute = (
 const query = graphql`
     query SharedVideoManageQuery($id: ID!) {
         ...UserData
+        ...AccessAclData
         event: eventById(id: $id) {
             __typename
             ... on AuthorizedEvent {
@@ -79,6 +80,8 @@ const query = graphql`
                 opencastId
                 created
                 canWrite
+                readRoles
+                writeRoles
                 isLive
                 syncedData {
                     duration
@@ -137,11 +140,11 @@ const ManageVideoNav: React.FC<ManageVideoNavProps> = ({ event, active }) => {
             page: "details",
             body: <><FiEdit3 />{t("manage.my-videos.details.title")}</>,
         },
-        // {
-        //     url: `/~manage/videos/${id}/access`,
-        //     page: "acl",
-        //     body: <><FiShield />{t("manage.my-videos.acl.title")}</>,
-        // },
+        {
+            url: `/~manage/videos/${id}/access`,
+            page: "acl",
+            body: <><FiShield />{t("manage.my-videos.acl.title")}</>,
+        },
         {
             url: `/~manage/videos/${id}/technical-details`,
             page: "technical-details",
@@ -15,8 +15,31 @@ declare module "paella-core" {
         public loadManifest(): Promise<void>;
 
         public unload(): Promise<void>;
+
+        /**
+         * Calls `callback` whenever the given event (see `Events`) occurs. By
+         * default, the callback is removed when the player is unloaded.
+         */
+        public bindEvent(
+            event: string,
+            callback: (params: Record<string, unknown>) => void,
+            unregisterOnUnload?: boolean,
+        ): void;
+
+        public videoContainer: {
+            currentTime(): Promise<number>;
+        };
     }
 
+    // https://github.com/polimediaupv/paella-core/blob/main/doc/events.md
+    export const Events: {
+        PLAY: string;
+        PAUSE: string;
+        ENDED: string;
+        /** Params: `{ currentTime: number }` */
+        TIME_UPDATE: string;
+    };
+
     export interface InitParams {
         configResourcesUrl?: string;
         configUrl?: string;
@@ -28,20 +28,38 @@ type SharedProps = {
 const blockFragment = graphql`
     fragment SeriesBlockData on SeriesBlock {
         series {
-            ...SeriesBlockSeriesData
+            # The events are queried below, already ordered by the API.
+            ...SeriesBlockSeriesData @arguments(withEvents: false)
         }
         showTitle
         showMetadata
         order
+        layout
+        events {
+            id
+            title
+            created
+            creators
+            isLive
+            syncedData {
+                duration
+                thumbnail
+                startTime
+                endTime
+                tracks { resolution }
+            }
+        }
     }
 `;
 
 const seriesFragment = graphql`
-    fragment SeriesBlockSeriesData on Series {
+    fragment SeriesBlockSeriesData on Series
+        @argumentDefinitions(withEvents: { type: "Boolean", defaultValue: true })
+    {
         title
         # description is only queried to get the sync status
         syncedData { description }
-        events {
+        events @include(if: $withEvents) {
             id
             title
             created
@@ -70,7 +88,10 @@ export const SeriesBlockFromBlock: React.FC<FromBlockProps> = ({ fragRef, ...res
         : <SeriesBlockFromSeries fragRef={series} {...rest} {...block} />;
 };
 
-type BlockProps = Partial<Omit<Fields<SeriesBlockData$data>, "series">>;
+type BlockProps = Partial<Omit<Fields<SeriesBlockData$data>, "series" | "events">> & {
+    /** Events in the order they should be shown. If not given, the series' events are sorted. */
+    events?: readonly Event[];
+};
 
 type SharedFromSeriesProps = SharedProps & BlockProps & {
     title?: string;
@@ -117,33 +138,43 @@ const ReadySeriesBlock: React.FC<ReadyProps> = ({
     series,
     activeEventId,
     order = "NEW_TO_OLD",
+    layout = "GRID",
     showTitle = true,
     showMetadata,
+    events: orderedEvents,
 }) => {
     const { t } = useTranslation();
 
     const finalTitle = title ?? (showTitle ? series.title : undefined);
 
-    const events = series.events.filter(event =>
+    let sortedEvents: Event[];
+    if (orderedEvents) {
+        sortedEvents = [...orderedEvents];
+    } else {
+        sortedEvents = [...series.events ?? []];
+        sortedEvents.sort(match(order, {
+            "NEW_TO_OLD": () => compareNewToOld,
+            "OLD_TO_NEW": () => compareOldToNew,
+            "ALPHABETIC": () => compareAlphabetic,
+        }, unreachable));
+    }
+    const events = sortedEvents.filter(event =>
         !isPastLiveEvent(event.syncedData?.endTime ?? null, event.isLive));
 
-    const sortedEvents = [...events];
-    sortedEvents.sort(match(order, {
-        "NEW_TO_OLD": () => compareNewToOld,
-        "OLD_TO_NEW": () => compareOldToNew,
-    }, unreachable));
+    const tiles = events.map(event => <GridTile
+        key={event.id}
+        active={event.id === activeEventId}
+        horizontal={layout === "LIST"}
+        {...{ basePath, event }}
+    />);
 
     const eventsUI = events.length === 0
         ? t("series.no-events")
-        : <VideoGrid>
-            {sortedEvents.map(
-                event => <GridTile
-                    key={event.id}
-                    active={event.id === activeEventId}
-                    {...{ basePath, event }}
-                />,
-            )}
-        </VideoGrid>;
+        : match(layout, {
+            "GRID": () => <VideoGrid>{tiles}</VideoGrid>,
+            "LIST": () => <VideoList>{tiles}</VideoList>,
+            "SLIDER": () => <VideoSlider>{tiles}</VideoSlider>,
+        }, () => <VideoGrid>{tiles}</VideoGrid>);
 
     return <>
         {showMetadata && !showTitle && <Description text={series.syncedData.description} />}
@@ -157,12 +188,13 @@ const ReadySeriesBlock: React.FC<ReadyProps> = ({
     </>;
 };
 
-type Event = SeriesBlockSeriesData$data["events"][0];
+type Event = NonNullable<SeriesBlockSeriesData$data["events"]>[0];
 
 const compareNewToOld = compareByKey((event: Event): number => (
     new Date(event.created).getTime()
 ));
 const compareOldToNew = swap(compareNewToOld);
+const compareAlphabetic = (a: Event, b: Event) => a.title.localeCompare(b.title);
 
 type SeriesBlockContainerProps = {
     title?: string;
@@ -208,18 +240,43 @@ const VideoGrid: React.FC<React.PropsWithChildren> = ({ children }) => (
     </div>
 );
 
+const VideoList: React.FC<React.PropsWithChildren> = ({ children }) => (
+    <div css={{ display: "flex", flexDirection: "column" }}>
+        {children}
+    </div>
+);
+
+const VideoSlider: React.FC<React.PropsWithChildren> = ({ children }) => (
+    <div css={{
+        display: "flex",
+        overflowX: "auto",
+        scrollSnapType: "x proximity",
+        "& > *": {
+            flex: "0 0 auto",
+            scrollSnapAlign: "start",
+        },
+    }}>
+        {children}
+    </div>
+);
+
 type GridTypeProps = {
     basePath: string;
     event: Event;
     active: boolean;
+    /** If `true`, the metadata is shown next to the thumbnail instead of below. */
+    horizontal?: boolean;
 };
 
-const GridTile: React.FC<GridTypeProps> = ({ event, basePath, active }) => {
+const GridTile: React.FC<GridTypeProps> = ({ event, basePath, active, horizontal = false }) => {
     const TRANSITION_DURATION = "0.3s";
     const date = event.isLive ? event.syncedData?.startTime ?? event.created : event.created;
 
     const inner = <>
-        <div css={{ borderRadius: 8 }}>
+        <div css={{
+            borderRadius: 8,
+            ...horizontal && { flex: "0 0 auto", width: 16 * 12, position: "relative" },
+        }}>
             <Thumbnail event={event} active={active} />
             <div css={{
                 position: "absolute",
@@ -243,6 +300,7 @@ const GridTile: React.FC<GridTypeProps> = ({ event, basePath, active }) => {
             margin: "0px 4px",
             marginTop: 12,
             color: "black",
+            ...horizontal && { margin: 0, minWidth: 0 },
         }}>
             <h3 css={{
                 display: "flex",
@@ -304,6 +362,15 @@ const GridTile: React.FC<GridTypeProps> = ({ event, basePath, active }) => {
             width: "100%",
             maxWidth: 360,
         },
+        ...horizontal && {
+            display: "flex",
+            gap: 16,
+            width: "auto",
+            margin: "4px 6px",
+            [`@media (max-width: ${VIDEO_GRID_BREAKPOINT}px)`]: {
+                maxWidth: "none",
+            },
+        },
         ...active && {
             backgroundColor: "var(--grey86)",
         },
@@ -327,7 +394,9 @@ const GridTile: React.FC<GridTypeProps> = ({ event, basePath, active }) => {
     } as const;
 
     return active
-        ? <div css={{ ...containerStyle, display: "inline-block" }}>{inner}</div>
+        ? <div css={{ ...containerStyle, ...!horizontal && { display: "inline-block" } }}>
+            {inner}
+        </div>
         : <Link
             to={`${basePath}/${keyOfId(event.id)}`}
             css={containerStyle}
@@ -1,4 +1,5 @@
 import React from "react";
+import { CSSObject } from "@emotion/react";
 import { graphql, useFragment } from "react-relay";
 import ReactMarkdown from "react-markdown";
 import type { Options } from "react-markdown";
@@ -8,7 +9,7 @@ import { TextBlockData$key } from "./__generated__/TextBlockData.graphql";
 
 const fragment = graphql`
     fragment TextBlockData on TextBlock {
-        content
+        html
     }
 `;
 
@@ -16,9 +17,22 @@ type ByQueryProps = {
     fragRef: TextBlockData$key;
 };
 
+/**
+ * Shows a text block with the HTML rendered (and sanitized) by the backend.
+ */
 export const TextBlockByQuery: React.FC<ByQueryProps> = ({ fragRef }) => {
-    const { content } = useFragment(fragment, fragRef);
-    return <TextBlock {...{ content }} />;
+    const { html } = useFragment(fragment, fragRef);
+    return <div
+        css={{
+            ...CONTAINER_STYLE,
+            "& p": PARAGRAPH_STYLE,
+            "& blockquote": BLOCKQUOTE_STYLE,
+            "& :not(pre) > code": INLINE_CODE_STYLE,
+            "& pre": PRE_STYLE,
+            "& img": IMG_STYLE,
+        }}
+        dangerouslySetInnerHTML={{ __html: html }}
+    />;
 };
 
 type Props = {
@@ -26,7 +40,8 @@ type Props = {
 };
 
 // We don't just want to allow everything, so here we explicitly list what we
-// support. Notably missing:
+// support. This has to match the list in `markdown.rs` in the backend, which
+// renders text blocks. Notably missing:
 //
 // - headings (if we allow them, we need to map `#` to h2 or h3 so that they
 //   don't interfere with other headings on the page.)
@@ -36,56 +51,52 @@ const ALLOWED_MARKDOWN_TAGS = [
 
 const CODE_BACKGROUND_COLOR = "var(--grey97)";
 
+const CONTAINER_STYLE: CSSObject = {
+    maxWidth: 1200,
+    "& > *:first-of-type": { marginTop: 0 },
+    "& > *:last-of-type": { marginBottom: 0 },
+};
+const PARAGRAPH_STYLE: CSSObject = {
+    margin: "16px 0",
+    maxWidth: 800,
+};
+const BLOCKQUOTE_STYLE: CSSObject = {
+    borderLeft: "4px solid var(--grey80)",
+    padding: "2px 8px",
+    "& > *:first-of-type": { marginTop: 0 },
+    "& > *:last-of-type": { marginBottom: 0 },
+};
+const INLINE_CODE_STYLE: CSSObject = {
+    backgroundColor: CODE_BACKGROUND_COLOR,
+    padding: "1px 3px",
+    borderRadius: "4px",
+};
+const PRE_STYLE: CSSObject = {
+    backgroundColor: CODE_BACKGROUND_COLOR,
+    padding: "8px",
+    overflowX: "auto",
+    maxWidth: "100%",
+};
+const IMG_STYLE: CSSObject = {
+    maxWidth: "100%",
+    display: "block",
+};
+
 // We override some components emitted by the Markdown parser to add CSS.
 const MARKDOWN_COMPONENTS: Options["components"] = {
-    p: ({ node, ...props }) => <p
-        css={{
-            margin: "16px 0",
-            maxWidth: 800,
-        }}
-        {...props}
-    />,
-    blockquote: ({ node, ...props }) => <blockquote
-        css={{
-            borderLeft: "4px solid var(--grey80)",
-            padding: "2px 8px",
-            "& > *:first-of-type": { marginTop: 0 },
-            "& > *:last-of-type": { marginBottom: 0 },
-        }}
-        {...props}
-    />,
+    p: ({ node, ...props }) => <p css={PARAGRAPH_STYLE} {...props} />,
+    blockquote: ({ node, ...props }) => <blockquote css={BLOCKQUOTE_STYLE} {...props} />,
     code: ({ node, className, inline, ...props }) => <code
-        css={inline === true && {
-            backgroundColor: CODE_BACKGROUND_COLOR,
-            padding: "1px 3px",
-            borderRadius: "4px",
-        }}
-        {...props}
-    />,
-    pre: ({ node, ...props }) => <pre
-        css={{
-            backgroundColor: CODE_BACKGROUND_COLOR,
-            padding: "8px",
-            overflowX: "auto",
-            maxWidth: "100%",
-        }}
-        {...props}
-    />,
-    img: ({ node, ...props }) => <img
-        css={{
-            maxWidth: "100%",
-            display: "block",
-        }}
+        css={inline === true && INLINE_CODE_STYLE}
         {...props}
     />,
+    pre: ({ node, ...props }) => <pre css={PRE_STYLE} {...props} />,
+    img: ({ node, ...props }) => <img css={IMG_STYLE} {...props} />,
 };
 
+/** Renders the given Markdown in the browser, e.g. for announcements. */
 export const TextBlock: React.FC<Props> = ({ content }) => (
-    <div css={{
-        maxWidth: 1200,
-        "& > *:first-of-type": { marginTop: 0 },
-        "& > *:last-of-type": { marginBottom: 0 },
-    }}>
+    <div css={CONTAINER_STYLE}>
         <ReactMarkdown allowedElements={ALLOWED_MARKDOWN_TAGS} components={MARKDOWN_COMPONENTS}>
             {content}
         </ReactMarkdown>
@@ -0,0 +1,82 @@
+import React from "react";
+import { graphql, useFragment } from "react-relay";
+import { useTranslation } from "react-i18next";
+
+import { Link } from "../../router";
+import { Card } from "../Card";
+import { TocBlockData$key } from "./__generated__/TocBlockData.graphql";
+
+
+type Props = {
+    fragRef: TocBlockData$key;
+};
+
+export const TocBlock: React.FC<Props> = ({ fragRef }) => {
+    const { t } = useTranslation();
+
+    // Relay does not support recursive fragments, so we have to spell out all
+    // levels. The backend limits `depth` to 4.
+    const { contents } = useFragment(graphql`
+        fragment TocBlockData on TocBlock {
+            contents {
+                truncated
+                entries {
+                    realm { id name path }
+                    videoCount
+                    children {
+                        realm { id name path }
+                        videoCount
+                        children {
+                            realm { id name path }
+                            videoCount
+                            children {
+                                realm { id name path }
+                                videoCount
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    `, fragRef);
+
+    if (contents.entries.length === 0) {
+        return <Card kind="info">{t("realm.toc.empty")}</Card>;
+    }
+
+    return <nav>
+        <EntryList entries={contents.entries} />
+        {contents.truncated && <p css={{ fontSize: 14, color: "var(--grey40)" }}>
+            {t("realm.toc.truncated")}
+        </p>}
+    </nav>;
+};
+
+/** One level of the nested entries above. Only the deepest level has no `children`. */
+type Entry = {
+    readonly realm: { readonly id: string; readonly name: string | null; readonly path: string };
+    readonly videoCount: number | null;
+    readonly children?: readonly Entry[];
+};
+
+const EntryList: React.FC<{ entries: readonly Entry[] }> = ({ entries }) => {
+    const { t } = useTranslation();
+
+    return (
+        <ul css={{
+            margin: 0,
+            paddingLeft: 24,
+            "& > li": { margin: "4px 0" },
+        }}>
+            {entries.map(({ realm, videoCount, children }) => <li key={realm.id}>
+                <Link to={realm.path}>
+                    {realm.name ?? <i>{t("realm.missing-name")}</i>}
+                </Link>
+                {videoCount !== null && <span css={{ color: "var(--grey40)", marginLeft: 8 }}>
+                    ({videoCount})
+                </span>}
+                {children && children.length > 0 && <EntryList entries={children} />}
+            </li>)}
+        </ul>
+    );
+};
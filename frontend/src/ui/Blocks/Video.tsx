@@ -21,6 +21,7 @@ export const VideoBlock: React.FC<Props> = ({ fragRef }) => {
                 __typename
                 ... on NotAllowed { dummy } # workaround
                 ... on AuthorizedEvent {
+                    id
                     title
                     isLive
                     created
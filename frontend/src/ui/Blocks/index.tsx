@@ -9,6 +9,7 @@ import { TitleBlock } from "./Title";
 import { TextBlockByQuery } from "./Text";
 import { SeriesBlockFromBlock } from "./Series";
 import { VideoBlock } from "./Video";
+import { TocBlock } from "./Toc";
 
 
 type BlocksProps = {
@@ -60,6 +61,7 @@ export const Block: React.FC<BlockProps> = ({ block: blockRef, realm }) => {
             ... on TextBlock { ... TextBlockData }
             ... on SeriesBlock { ... SeriesBlockData }
             ... on VideoBlock { ... VideoBlockData }
+            ... on TocBlock { ... TocBlockData }
         }
     `, blockRef);
     const { __typename } = block;
@@ -71,6 +73,7 @@ export const Block: React.FC<BlockProps> = ({ block: blockRef, realm }) => {
             "TextBlock": () => <TextBlockByQuery fragRef={block} />,
             "SeriesBlock": () => <SeriesBlockFromBlock fragRef={block} basePath={basePath} />,
             "VideoBlock": () => <VideoBlock fragRef={block} />,
+            "TocBlock": () => <TocBlock fragRef={block} />,
         })}
     </div>;
 };
@@ -1,5 +1,5 @@
 import { useEffect, useRef } from "react";
-import { Config, Manifest, Paella, Source, Stream } from "paella-core";
+import { Config, Events, Manifest, Paella, Source, Stream } from "paella-core";
 import getBasicPluginsContext from "paella-basic-plugins";
 import getZoomPluginContext from "paella-zoom-plugin";
 
@@ -17,15 +17,25 @@ type PaellaPlayerProps = {
     tracks: readonly Track[];
     captions: readonly Caption[];
     isLive: boolean;
+
+    /**
+     * Called with the current position (in seconds) while playing. `force`
+     * is `true` when the video is paused or ended.
+     */
+    onPosition?: (seconds: number, force: boolean) => void;
 };
 
 const PaellaPlayer: React.FC<PaellaPlayerProps> = ({
-    tracks, title, duration, isLive, captions,
+    tracks, title, duration, isLive, captions, onPosition,
 }) => {
     const { t } = useTranslation();
     const ref = useRef<HTMLDivElement>(null);
     const paella = useRef<Paella>();
 
+    // Stored in a ref so that changing the callback does not recreate Paella.
+    const onPositionRef = useRef(onPosition);
+    onPositionRef.current = onPosition;
+
     useEffect(() => {
         // If the ref is not set yet (which should not usually happen), we do
         // nothing.
@@ -92,6 +102,17 @@ const PaellaPlayer: React.FC<PaellaPlayerProps> = ({
                 ],
             });
             paella.current.loadManifest();
+
+            const player = paella.current;
+            player.bindEvent(Events.TIME_UPDATE, params => {
+                onPositionRef.current?.(params.currentTime as number, false);
+            });
+            player.bindEvent(Events.PAUSE, async () => {
+                onPositionRef.current?.(await player.videoContainer.currentTime(), true);
+            });
+            player.bindEvent(Events.ENDED, () => {
+                onPositionRef.current?.(duration / 1000, true);
+            });
         }
 
         const paellaSnapshot = paella.current;
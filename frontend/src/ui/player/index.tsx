@@ -1,15 +1,22 @@
-import React, { PropsWithChildren, Suspense, useEffect } from "react";
+import React, { PropsWithChildren, Suspense, useEffect, useRef } from "react";
 import { useTranslation } from "react-i18next";
 import { FiClock } from "react-icons/fi";
 import { HiOutlineStatusOffline } from "react-icons/hi";
+import { commitMutation, graphql } from "react-relay";
 import { BREAKPOINT_MEDIUM } from "../../GlobalStyle";
 
 import { MAIN_PADDING } from "../../layout/Root";
+import CONFIG from "../../config";
+import { environment } from "../../relay";
+import { useUser } from "../../User";
 import { match, useForceRerender } from "../../util";
 import { getEventTimeInfo } from "../../util/video";
 import { Spinner } from "../Spinner";
 import { RelativeDate } from "../time";
 import PaellaPlayer from "./Paella";
+import {
+    playerSavePlaybackPositionMutation,
+} from "./__generated__/playerSavePlaybackPositionMutation.graphql";
 
 
 export type PlayerProps = {
@@ -22,6 +29,7 @@ export type PlayerProps = {
 };
 
 export type PlayerEvent = {
+    id: string;
     title: string;
     created: string;
     isLive: boolean;
@@ -60,6 +68,7 @@ export type Caption = {
 export const Player: React.FC<PlayerProps> = ({ event, onEventStateChange }) => {
     const { startTime, endTime, hasStarted, hasEnded } = getEventTimeInfo(event);
     const rerender = useForceRerender();
+    const savePosition = usePlaybackPositionSaver(event.id);
 
     // When the livestream starts or ends, rerender the parent. We add some
     // extra time (500ms) to be sure the stream is actually already running by
@@ -93,11 +102,45 @@ export const Player: React.FC<PlayerProps> = ({ event, onEventStateChange }) =>
                     isLive={event.isLive}
                     tracks={event.syncedData.tracks}
                     captions={event.syncedData.captions}
+                    onPosition={event.isLive ? undefined : savePosition}
                 />}
         </Suspense>
     );
 };
 
+const savePlaybackPositionMutation = graphql`
+    mutation playerSavePlaybackPositionMutation($event: ID!, $seconds: Float!, $force: Boolean!) {
+        savePlaybackPosition(event: $event, seconds: $seconds, force: $force)
+    }
+`;
+
+/**
+ * Returns a function that stores the playback position of the current user
+ * in Tobira, to continue watching later. Positions are only sent if they
+ * changed by more than 10 seconds, unless `force` is set. Returns `undefined`
+ * if the feature is disabled or the user is not logged in.
+ */
+const usePlaybackPositionSaver = (eventId: string) => {
+    const user = useUser();
+    const lastSent = useRef<number | null>(null);
+
+    if (!CONFIG.continueWatching || user === "none" || user === "unknown") {
+        return undefined;
+    }
+
+    return (seconds: number, force: boolean) => {
+        if (!force && lastSent.current !== null && Math.abs(seconds - lastSent.current) <= 10) {
+            return;
+        }
+        lastSent.current = seconds;
+        commitMutation<playerSavePlaybackPositionMutation>(environment, {
+            mutation: savePlaybackPositionMutation,
+            variables: { event: eventId, seconds, force },
+            onError: error => console.warn("Failed to store playback position: ", error),
+        });
+    };
+};
+
 /**
  * A more constrained version of the player component for use in normal page flow.
  * You probably want this one.
@@ -143,6 +143,7 @@ export const errorDisplayInfo = (error: unknown, i18n: i18n): ErrorDisplayInfo =
                         },
                         INVALID_INPUT: () => t("errors.invalid-input"),
                         NOT_AUTHORIZED: () => t("errors.not-authorized"),
+                        INVALID_REFERENCE: () => t("errors.invalid-reference"),
                     });
                     causes.add(msg);
                 }